@@ -1,5 +1,6 @@
 #![allow(unused)]
 use binary_logger::{Logger, log_record, BufferHandler};
+use binary_logger::loggable::Loggable;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
@@ -17,7 +18,7 @@ const NUM_BUFFER_FILLS: usize = 4; // Fill buffer 4 times
 const RECORD_SIZE_ESTIMATE: usize = 256; // Estimated bytes per record
 const ITERATIONS: usize = (BUFFER_SIZE * NUM_BUFFER_FILLS) / RECORD_SIZE_ESTIMATE;
 
-#[derive(Debug)]
+#[derive(Debug, Loggable)]
 struct TestEvent {
     id: i32,
     active: bool,
@@ -67,12 +68,8 @@ impl FileBufferHandler {
 }
 
 impl BufferHandler for FileBufferHandler {
-    fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-        let buffer_copy = unsafe {
-            let slice = std::slice::from_raw_parts(buffer, size);
-            slice.to_vec()
-        };
-        let _ = self.sender.send(buffer_copy);
+    fn handle_buffer(&self, data: &[u8]) {
+        let _ = self.sender.send(data.to_vec());
     }
 }
 
@@ -0,0 +1,177 @@
+//! Reader throughput on generated corpora.
+//!
+//! Each corpus holds `ENTRIES` records of one shape, spread over four format
+//! strings. Every operation is measured twice, in entries/s (`reader/...`)
+//! and in MB/s of log data (`reader_bytes/...`):
+//!
+//! * `decode` - `read_entry` for every record
+//! * `skim` - a record filter rejecting everything, so only headers are walked
+//! * `filter` - a record filter keeping one format string in four
+//! * `format` - `read_entry` and `LogEntry::format` for every record
+//! * `format_cached` - like `format`, through a `render::RenderCache`
+//!
+//! Compare commits with `cargo bench --bench reader_bench -- --save-baseline before`
+//! and, after the change, `-- --baseline before`.
+
+use binary_logger::{Logger, BufferHandler, LogReader, log_record, register_string};
+use binary_logger::fixed::Fixed;
+use binary_logger::instant::LogInstant;
+use binary_logger::render::{RenderCache, RenderOptions};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const ENTRIES: u64 = 100_000;
+
+struct CollectingHandler(Arc<Mutex<Vec<u8>>>);
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+/// Writes `ENTRIES` records with `write(logger, i)` and returns the log.
+fn corpus(write: impl Fn(&mut Logger<1_048_576>, u64)) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<1_048_576>::new(CollectingHandler(data.clone()));
+        for i in 0..ENTRIES {
+            write(&mut logger, i);
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    data.clone()
+}
+
+/// Corpora by name, with the format string `filter` keeps.
+fn corpora() -> Vec<(&'static str, Vec<u8>, &'static str)> {
+    vec![
+        ("scalar", corpus(|logger, i| {
+            match i % 4 {
+                0 => log_record!(logger, "Order {} filled", i),
+                1 => log_record!(logger, "Order {} cancelled", i),
+                2 => log_record!(logger, "Queue depth {}", i as u32),
+                _ => log_record!(logger, "Retry {}", i as u16),
+            }.unwrap();
+        }), "Order {} filled"),
+        ("mixed", corpus(|logger, i| {
+            match i % 4 {
+                0 => log_record!(logger, "User {} scored {} active {}", i, i as f64 * 0.5, i % 3 == 0),
+                1 => log_record!(logger, channel: "net", "Peer {} sent {} bytes", i as u32, i * 7),
+                2 => log_record!(logger, "Temperature {} C at sensor {}", 20.5 + (i % 10) as f64, i as u16),
+                _ => log_record!(logger, "Tick", ),
+            }.unwrap();
+        }), "User {} scored {} active {}"),
+        ("wide", corpus(|logger, i| {
+            let (a, b, c) = (i as u32, i as f64, i as i16);
+            match i % 4 {
+                0 => log_record!(logger, "Quote {} {} {} {} {} {} {} {}", i, a, b, c, a, b, c, i),
+                1 => log_record!(logger, "Trade {} {} {} {} {} {} {} {}", b, c, i, a, b, c, i, a),
+                2 => log_record!(logger, "Book {} {} {} {} {} {} {} {}", c, i, a, b, c, i, a, b),
+                _ => log_record!(logger, "Stats {} {} {} {} {} {} {} {}", a, a, b, b, c, c, i, i),
+            }.unwrap();
+        }), "Quote {} {} {} {} {} {} {} {}"),
+        ("typed", corpus(|logger, i| {
+            match i % 4 {
+                0 => log_record!(logger, "Fill at {} for {}", Fixed::<4>(i as i64 * 25), Fixed::<2>(-(i as i64))),
+                1 => log_record!(logger, "Ratio {} of {}", i as f32 / 3.0, i),
+                2 => log_record!(logger, "Request received at {} done", LogInstant::now()),
+                _ => log_record!(logger, "Price {} lots {}", Fixed::<8>(i as i64), i as u32),
+            }.unwrap();
+        }), "Fill at {} for {}"),
+        ("repeated", corpus(|logger, i| {
+            match i % 4 {
+                0 => log_record!(logger, "Heartbeat {} ok, latency {} ms", "db", 0.25),
+                1 => log_record!(logger, "Health check {} passed", (i % 3) as u32),
+                2 => log_record!(logger, "Cache {} hit ratio {}", "sessions", 0.98),
+                _ => log_record!(logger, "Worker {} idle", (i % 8) as u16),
+            }.unwrap();
+        }), "Heartbeat {} ok, latency {} ms"),
+    ]
+}
+
+fn decode(data: &[u8]) -> u64 {
+    let mut reader = LogReader::new(data);
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        black_box(&entry);
+        count += 1;
+    }
+    count
+}
+
+fn skim(data: &[u8]) -> u64 {
+    let mut reader = LogReader::new(data);
+    let mut seen = 0;
+    reader.set_record_filter(Some(Box::new(|_, _| {
+        seen += 1;
+        false
+    })));
+    black_box(reader.read_entry());
+    drop(reader);
+    seen
+}
+
+fn filter(data: &[u8], wanted: u32) -> u64 {
+    let mut reader = LogReader::new(data);
+    reader.set_record_filter(Some(Box::new(move |format_id, _| format_id == wanted)));
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        black_box(&entry);
+        count += 1;
+    }
+    count
+}
+
+fn format(data: &[u8]) -> u64 {
+    let mut reader = LogReader::new(data);
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        black_box(entry.format());
+        count += 1;
+    }
+    count
+}
+
+fn format_cached(data: &[u8]) -> u64 {
+    let mut reader = LogReader::new(data);
+    let mut cache = RenderCache::new(RenderCache::DEFAULT_CAPACITY);
+    let options = RenderOptions::default();
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        black_box(cache.message(&entry, &options));
+        count += 1;
+    }
+    count
+}
+
+fn bench_reader(c: &mut Criterion) {
+    for (shape, data, wanted) in corpora() {
+        let wanted = register_string(wanted);
+        assert_eq!(decode(&data), ENTRIES);
+        assert_eq!(filter(&data, wanted), ENTRIES / 4);
+
+        for (group, throughput) in [
+            ("reader", Throughput::Elements(ENTRIES)),
+            ("reader_bytes", Throughput::BytesDecimal(data.len() as u64)),
+        ] {
+            let mut group = c.benchmark_group(group);
+            group.throughput(throughput);
+            group.bench_with_input(BenchmarkId::new("decode", shape), &data, |b, data| b.iter(|| decode(data)));
+            group.bench_with_input(BenchmarkId::new("skim", shape), &data, |b, data| b.iter(|| skim(data)));
+            group.bench_with_input(BenchmarkId::new("filter", shape), &data, |b, data| b.iter(|| filter(data, wanted)));
+            group.bench_with_input(BenchmarkId::new("format", shape), &data, |b, data| b.iter(|| format(data)));
+            group.bench_with_input(BenchmarkId::new("format_cached", shape), &data, |b, data| b.iter(|| format_cached(data)));
+            group.finish();
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20).measurement_time(Duration::from_secs(3));
+    targets = bench_reader
+}
+criterion_main!(benches);
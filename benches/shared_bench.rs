@@ -0,0 +1,88 @@
+//! Write throughput of the shared signal-safe logger under contention.
+//!
+//! Each iteration starts `THREADS` writers together, each writing `WRITES`
+//! records to one `AsyncSignalSafeLogger`, and is measured from the start
+//! until the last writer is done; draining is not measured. Every thread
+//! count is run with three layouts (`shared/<layout>/<threads>`):
+//!
+//! * `packed` - `SharedLayout::PACKED`, the default
+//! * `padded_cursors` - counters on cache lines of their own, records packed
+//! * `cache_aligned` - `SharedLayout::CACHE_ALIGNED`
+//!
+//! Compare commits with `cargo bench --bench shared_bench -- --save-baseline before`
+//! and, after the change, `-- --baseline before`.
+
+use binary_logger::{BufferHandler, log_signal_safe};
+use binary_logger::signal_safe::{AsyncSignalSafeLogger, SharedLayout};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Records written by each thread per iteration
+const WRITES: u64 = 2000;
+
+/// Buffer size, enough for every record of an iteration, aligned
+const CAP: usize = 1 << 21;
+
+struct NullHandler;
+
+impl BufferHandler for NullHandler {
+    fn handle_buffer(&self, _data: &[u8]) {}
+}
+
+/// Writes `WRITES` records from each of `threads` threads at once and
+/// returns the time from the first start to the last finish.
+fn write_together(logger: &AsyncSignalSafeLogger<CAP>, threads: usize) -> Duration {
+    let barrier = Barrier::new(threads);
+    thread::scope(|scope| {
+        let writers: Vec<_> = (0..threads).map(|thread| {
+            let barrier = &barrier;
+            scope.spawn(move || {
+                barrier.wait();
+                let start = Instant::now();
+                for i in 0..WRITES as i32 {
+                    log_signal_safe!(logger, "Thread {} record {}", thread as i32, i);
+                }
+                (start, Instant::now())
+            })
+        }).collect();
+        let times: Vec<(Instant, Instant)> = writers.into_iter().map(|writer| writer.join().unwrap()).collect();
+        let start = times.iter().map(|&(start, _)| start).min().unwrap();
+        let end = times.iter().map(|&(_, end)| end).max().unwrap();
+        end - start
+    })
+}
+
+fn bench_shared(c: &mut Criterion) {
+    let layouts = [
+        ("packed", SharedLayout::PACKED),
+        ("padded_cursors", SharedLayout { pad_cursors: true, ..SharedLayout::PACKED }),
+        ("cache_aligned", SharedLayout::CACHE_ALIGNED),
+    ];
+    for (name, layout) in layouts {
+        let logger = AsyncSignalSafeLogger::<CAP>::with_layout(NullHandler, layout);
+        let mut group = c.benchmark_group(format!("shared/{}", name));
+        for threads in [1, 2, 4, 8] {
+            group.throughput(Throughput::Elements(WRITES * threads as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+                b.iter_custom(|iters| {
+                    (0..iters).map(|_| {
+                        let took = write_together(&logger, threads);
+                        logger.drain();
+                        took
+                    }).sum()
+                })
+            });
+        }
+        group.finish();
+        assert_eq!(logger.dropped(), 0);
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20).measurement_time(Duration::from_secs(3));
+    targets = bench_shared
+}
+criterion_main!(benches);
@@ -0,0 +1,157 @@
+//! Derive macros for `binary_logger`.
+//!
+//! Use them through the re-exports in `binary_logger`, which document the
+//! generated implementations.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Derives `binary_logger::typed::FromLogEntry`.
+///
+/// The struct needs `#[log(format = "...")]` naming the format string of its
+/// records. Its fields take the record's arguments in order, except a field
+/// marked `#[log(timestamp)]`, which takes the entry's timestamp.
+#[proc_macro_derive(FromLogEntry, attributes(log))]
+pub fn derive_from_log_entry(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_log_entry(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn from_log_entry(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(input.span(), "FromLogEntry can only be derived for structs"));
+    };
+
+    let mut format = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("log")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("format") {
+                format = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `format = \"...\"`"))
+            }
+        })?;
+    }
+    let format = format.ok_or_else(|| {
+        syn::Error::new(input.span(), "FromLogEntry needs #[log(format = \"...\")] naming the format string")
+    })?;
+
+    let mut values = Vec::new();
+    for field in data.fields.iter() {
+        let mut timestamp = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("log")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("timestamp") {
+                    timestamp = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `timestamp`"))
+                }
+            })?;
+        }
+        let ty = &field.ty;
+        values.push(if timestamp {
+            quote_spanned! {ty.span()=> entry.timestamp }
+        } else {
+            quote_spanned! {ty.span()=>
+                <#ty as ::binary_logger::typed::FromLogValue>::from_log_value(parameters.next()?)?
+            }
+        });
+    }
+
+    let value = match &data.fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote! { Self { #(#names: #values),* } }
+        }
+        Fields::Unnamed(_) => quote! { Self(#(#values),*) },
+        Fields::Unit => quote! { Self },
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::binary_logger::typed::FromLogEntry for #name #ty_generics #where_clause {
+            const FORMAT: &'static str = #format;
+
+            #[allow(unused_mut, unused_variables)]
+            fn from_log_entry(entry: &::binary_logger::LogEntry) -> ::std::option::Option<Self> {
+                let mut parameters = entry.parameters.iter();
+                let value = #value;
+                // Every argument must have been taken
+                match parameters.next() {
+                    ::std::option::Option::None => ::std::option::Option::Some(value),
+                    ::std::option::Option::Some(_) => ::std::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+/// Derives `binary_logger::loggable::Loggable`.
+///
+/// The fields are logged in declaration order, except those marked
+/// `#[log(skip)]`. The layout string names the struct and its fields,
+/// tuple fields by index.
+#[proc_macro_derive(Loggable, attributes(log))]
+pub fn derive_loggable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    loggable(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn loggable(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(input.span(), "Loggable can only be derived for structs"));
+    };
+
+    let mut names = Vec::new();
+    let mut writes = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("log")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip`"))
+                }
+            })?;
+        }
+        if skip {
+            continue;
+        }
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index::from(index)),
+        };
+        names.push(match &field.ident {
+            Some(ident) => ident.unraw().to_string(),
+            None => index.to_string(),
+        });
+        writes.push(quote_spanned! {field.ty.span()=>
+            ::binary_logger::__log_field!(out, self.#member);
+        });
+    }
+
+    let name = &input.ident;
+    let layout = LitStr::new(&format!("{}{{{}}}", name, names.join(",")), name.span());
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::binary_logger::loggable::Loggable for #name #ty_generics #where_clause {
+            fn layout_id(&self) -> u32 {
+                ::binary_logger::__string_id!(#layout)
+            }
+
+            #[allow(unused_variables)]
+            fn log_fields(&self, out: &mut ::binary_logger::loggable::StructWriter) {
+                #(#writes)*
+            }
+        }
+    })
+}
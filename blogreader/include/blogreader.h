@@ -0,0 +1,97 @@
+/*
+ * libblogreader: the binary_logger reader behind a C ABI.
+ *
+ * Build with `cargo build --release -p blogreader`, which produces
+ * libblogreader.so (.dylib, .dll) under target/release.
+ *
+ *     BlogReader *reader = blog_reader_open("app.blog");
+ *     BlogEntry *entry;
+ *     while (reader && (entry = blog_reader_next_entry(reader))) {
+ *         printf("%llu %s\n", (unsigned long long)blog_entry_timestamp_us(entry),
+ *                blog_entry_message(entry));
+ *         blog_entry_free(entry);
+ *     }
+ *     blog_reader_free(reader);
+ *
+ * Strings returned for an entry are UTF-8 and stay valid until the entry is
+ * freed. Entries may outlive their reader. A reader must not be used from
+ * two threads at once. Panics don't cross into the caller: a function that
+ * fails inside returns NULL, or -1 or 0 for those returning numbers.
+ */
+
+#ifndef BLOGREADER_H
+#define BLOGREADER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct BlogReader BlogReader;
+typedef struct BlogEntry BlogEntry;
+
+/* Kinds of arguments, from blog_entry_arg_kind */
+#define BLOG_ARG_UNKNOWN 0 /* bytes of unknown type, see blog_entry_arg_text */
+#define BLOG_ARG_INT     1 /* blog_entry_arg_int */
+#define BLOG_ARG_UINT    2 /* blog_entry_arg_uint, logged with type tags */
+#define BLOG_ARG_FLOAT   3 /* blog_entry_arg_float */
+#define BLOG_ARG_BOOL    4 /* blog_entry_arg_bool */
+#define BLOG_ARG_TEXT    5 /* strings, flags, decimals, instants, errors, backtraces, bytes in hex */
+
+/* Opens a log file, read whole, or returns NULL if it can't be read. */
+BlogReader *blog_reader_open(const char *path);
+
+/* Creates a reader of a copy of `len` bytes at `data`. */
+BlogReader *blog_reader_from_bytes(const uint8_t *data, size_t len);
+
+/* Decodes the next entry, or returns NULL at the end of the log. */
+BlogEntry *blog_reader_next_entry(BlogReader *reader);
+
+/* Free a reader or an entry; NULL is ignored. */
+void blog_reader_free(BlogReader *reader);
+void blog_entry_free(BlogEntry *entry);
+
+/* Microseconds since the UNIX epoch */
+uint64_t blog_entry_timestamp_us(const BlogEntry *entry);
+
+uint32_t blog_entry_format_id(const BlogEntry *entry);
+
+/* NULL if the log doesn't hold the format string */
+const char *blog_entry_format_string(const BlogEntry *entry);
+
+/* The format string with its arguments */
+const char *blog_entry_message(const BlogEntry *entry);
+
+/* 1 (trace) to 5 (error), 0 for entries without a level */
+int blog_entry_level(const BlogEntry *entry);
+
+/* 0 for the default channel */
+uint32_t blog_entry_channel(const BlogEntry *entry);
+
+/* NULL for the default channel and names the log doesn't hold */
+const char *blog_entry_channel_name(const BlogEntry *entry);
+
+/* Returns 1 and stores the global sequence number, or 0 if there is none. */
+int blog_entry_sequence(const BlogEntry *entry, uint64_t *out);
+
+size_t blog_entry_arg_count(const BlogEntry *entry);
+
+/* A BLOG_ARG_* kind, or -1 past the last argument */
+int blog_entry_arg_kind(const BlogEntry *entry, size_t index);
+
+/* Return 1 and store the argument if it has that kind, or return 0. */
+int blog_entry_arg_int(const BlogEntry *entry, size_t index, int64_t *out);
+int blog_entry_arg_uint(const BlogEntry *entry, size_t index, uint64_t *out);
+int blog_entry_arg_float(const BlogEntry *entry, size_t index, double *out);
+int blog_entry_arg_bool(const BlogEntry *entry, size_t index, int *out);
+
+/* Text of any argument as it appears in the message, NULL past the last */
+const char *blog_entry_arg_text(const BlogEntry *entry, size_t index);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BLOGREADER_H */
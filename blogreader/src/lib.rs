@@ -0,0 +1,440 @@
+//! # libblogreader
+//!
+//! The binary log reader behind a C ABI, so services in Go, Java, C++ or
+//! any language with a C FFI can decode logs without reimplementing the
+//! format. The functions are declared in `include/blogreader.h`.
+//!
+//! A `BlogReader` decodes one log file, or a copy of bytes in memory, entry
+//! by entry. Each `BlogEntry` holds the decoded entry and the text of its
+//! message and arguments, so the strings it returns stay valid until it is
+//! freed. Readers and entries are independent: an entry may outlive its
+//! reader.
+//!
+//! Format strings and channel names come from the log's embedded
+//! dictionary (`Logger::set_embedded_dictionary`); without one, entries
+//! have no format string and their message lists the raw arguments.
+//! Arguments decode by size unless the log carries type tags
+//! (`Logger::set_type_tags`).
+//!
+//! No panic crosses the C ABI: a function that panics, such as on a log
+//! the decoder trips over, returns NULL, or -1 or 0 for those returning
+//! numbers, instead of aborting the host process.
+//!
+//! Bindings in other languages check themselves against the logs of
+//! `tests/fixtures`: decoding `portable_*.blog` must give the lines of the
+//! matching `.txt` file, as `tests/conformance_tests.rs` does through this
+//! API.
+
+use binary_logger::{LogEntry, LogReader, LogValue};
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::mem::ManuallyDrop;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::UNIX_EPOCH;
+
+/// Kind of an argument, as returned by `blog_entry_arg_kind`
+pub const BLOG_ARG_UNKNOWN: c_int = 0;
+
+/// Signed integer, read with `blog_entry_arg_int`
+pub const BLOG_ARG_INT: c_int = 1;
+
+/// Unsigned integer logged with its type tag, read with `blog_entry_arg_uint`
+pub const BLOG_ARG_UINT: c_int = 2;
+
+/// Floating point number, read with `blog_entry_arg_float`
+pub const BLOG_ARG_FLOAT: c_int = 3;
+
+/// Boolean, read with `blog_entry_arg_bool`
+pub const BLOG_ARG_BOOL: c_int = 4;
+
+/// Anything else with a text form: strings, flags, decimals, instants,
+/// error chains, backtraces and byte strings (in hex)
+pub const BLOG_ARG_TEXT: c_int = 5;
+
+/// A reader of one log.
+pub struct BlogReader {
+    // Borrows `data`, so it is dropped first
+    reader: ManuallyDrop<LogReader<'static>>,
+    // The bytes read, from `Box::into_raw`: held as a raw pointer, as a
+    // `Box` would claim them while the reader borrows them
+    data: *mut [u8],
+}
+
+impl BlogReader {
+    fn new(data: Box<[u8]>) -> Box<BlogReader> {
+        let data = Box::into_raw(data);
+        // The bytes stay in place until the reader is dropped
+        let bytes: &'static [u8] = unsafe { &*data };
+        Box::new(BlogReader { reader: ManuallyDrop::new(LogReader::new(bytes)), data })
+    }
+}
+
+impl Drop for BlogReader {
+    fn drop(&mut self) {
+        // The reader goes first, then the bytes it borrowed
+        unsafe {
+            ManuallyDrop::drop(&mut self.reader);
+            drop(Box::from_raw(self.data));
+        }
+    }
+}
+
+/// A decoded entry, with the C strings handed out for it.
+pub struct BlogEntry {
+    entry: LogEntry,
+    message: CString,
+    format_string: Option<CString>,
+    channel_name: Option<CString>,
+    args: Vec<CString>,
+}
+
+impl BlogEntry {
+    fn new(entry: LogEntry) -> Box<BlogEntry> {
+        Box::new(BlogEntry {
+            message: c_string(&entry.format()),
+            format_string: entry.format_string.as_deref().map(c_string),
+            channel_name: entry.channel_name().map(c_string),
+            args: entry.parameters.iter().map(|value| c_string(&value.to_string())).collect(),
+            entry,
+        })
+    }
+
+    fn arg(&self, index: usize) -> Option<&LogValue> {
+        self.entry.parameters.get(index)
+    }
+
+    fn arg_text(&self, index: usize) -> *const c_char {
+        self.args.get(index).map_or(ptr::null(), |text| text.as_ptr())
+    }
+}
+
+/// Converts text to a C string, replacing NUL characters, which C strings
+/// can't hold.
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "\u{FFFD}")).unwrap_or_default()
+}
+
+/// Runs the body of an exported function, returning `failed` if it panics,
+/// as unwinding into the caller would abort its process.
+fn guard<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(failed)
+}
+
+/// Returns the pointer of an optional C string, or NULL.
+fn c_ptr(text: &Option<CString>) -> *const c_char {
+    text.as_ref().map_or(ptr::null(), |text| text.as_ptr())
+}
+
+/// Opens the log file at `path` for reading, or returns NULL if it can't
+/// be read. The file is read whole.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn blog_reader_open(path: *const c_char) -> *mut BlogReader {
+    guard(ptr::null_mut(), || {
+        if path.is_null() {
+            return ptr::null_mut();
+        }
+        let Ok(path) = CStr::from_ptr(path).to_str() else {
+            return ptr::null_mut();
+        };
+        match std::fs::read(path) {
+            Ok(data) => Box::into_raw(BlogReader::new(data.into_boxed_slice())),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Creates a reader of a copy of the `len` bytes at `data`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, or be NULL with `len` 0.
+#[no_mangle]
+pub unsafe extern "C" fn blog_reader_from_bytes(data: *const u8, len: usize) -> *mut BlogReader {
+    guard(ptr::null_mut(), || {
+        let bytes = if len == 0 { &[][..] } else { std::slice::from_raw_parts(data, len) };
+        Box::into_raw(BlogReader::new(bytes.into()))
+    })
+}
+
+/// Decodes the next entry of the log, or returns NULL at its end. The
+/// entry is freed with `blog_entry_free`.
+///
+/// # Safety
+///
+/// `reader` must come from `blog_reader_open` or `blog_reader_from_bytes`
+/// and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn blog_reader_next_entry(reader: *mut BlogReader) -> *mut BlogEntry {
+    guard(ptr::null_mut(), || {
+        match reader.as_mut().and_then(|reader| reader.reader.read_entry()) {
+            Some(entry) => Box::into_raw(BlogEntry::new(entry)),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// Frees a reader. NULL is ignored.
+///
+/// # Safety
+///
+/// `reader` must come from `blog_reader_open` or `blog_reader_from_bytes`
+/// and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn blog_reader_free(reader: *mut BlogReader) {
+    guard((), || {
+        if !reader.is_null() {
+            drop(Box::from_raw(reader));
+        }
+    })
+}
+
+/// Frees an entry and the strings returned for it. NULL is ignored.
+///
+/// # Safety
+///
+/// `entry` must come from `blog_reader_next_entry` and not be freed
+/// already.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_free(entry: *mut BlogEntry) {
+    guard((), || {
+        if !entry.is_null() {
+            drop(Box::from_raw(entry));
+        }
+    })
+}
+
+/// Returns the time of the entry in microseconds since the UNIX epoch.
+///
+/// # Safety
+///
+/// `entry` must be a live entry from `blog_reader_next_entry`. The same
+/// holds for every `blog_entry_*` accessor.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_timestamp_us(entry: *const BlogEntry) -> u64 {
+    guard(0, || {
+        let time = (*entry).entry.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        time.as_micros() as u64
+    })
+}
+
+/// Returns the ID of the entry's format string.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_format_id(entry: *const BlogEntry) -> u32 {
+    guard(0, || {
+        (*entry).entry.format_id
+    })
+}
+
+/// Returns the entry's format string, or NULL if the log doesn't name it.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_format_string(entry: *const BlogEntry) -> *const c_char {
+    guard(ptr::null(), || {
+        c_ptr(&(*entry).format_string)
+    })
+}
+
+/// Returns the entry's message: the format string with its arguments.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_message(entry: *const BlogEntry) -> *const c_char {
+    guard(ptr::null(), || {
+        (*entry).message.as_ptr()
+    })
+}
+
+/// Returns the entry's level, from 1 (trace) to 5 (error), or 0 for
+/// entries without one.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_level(entry: *const BlogEntry) -> c_int {
+    guard(0, || {
+        (*entry).entry.level.map_or(0, |level| level as c_int)
+    })
+}
+
+/// Returns the ID of the entry's channel, 0 for the default channel.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_channel(entry: *const BlogEntry) -> u32 {
+    guard(0, || {
+        (*entry).entry.channel
+    })
+}
+
+/// Returns the name of the entry's channel, or NULL for the default
+/// channel and names the log doesn't hold.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_channel_name(entry: *const BlogEntry) -> *const c_char {
+    guard(ptr::null(), || {
+        c_ptr(&(*entry).channel_name)
+    })
+}
+
+/// Stores the entry's global sequence number in `out` and returns 1, or
+/// returns 0 if the writer didn't number its records.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_sequence(entry: *const BlogEntry, out: *mut u64) -> c_int {
+    guard(0, || {
+        match (*entry).entry.sequence {
+            Some(sequence) => {
+                *out = sequence;
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Returns the number of arguments of the entry.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_count(entry: *const BlogEntry) -> usize {
+    guard(0, || {
+        (*entry).entry.parameters.len()
+    })
+}
+
+/// Returns the kind of argument `index` (`BLOG_ARG_*`), or -1 past the
+/// last argument.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_kind(entry: *const BlogEntry, index: usize) -> c_int {
+    guard(-1, || {
+        match (*entry).arg(index) {
+            None => -1,
+            Some(LogValue::Integer(_) | LogValue::Integer64(_)) => BLOG_ARG_INT,
+            Some(LogValue::Unsigned(_)) => BLOG_ARG_UINT,
+            Some(LogValue::Boolean(_)) => BLOG_ARG_BOOL,
+            Some(LogValue::Unknown(_)) => BLOG_ARG_UNKNOWN,
+            Some(value) if value.as_f64().is_some() && !matches!(value, LogValue::Fixed { .. }) => BLOG_ARG_FLOAT,
+            Some(_) => BLOG_ARG_TEXT,
+        }
+    })
+}
+
+/// Stores signed integer argument `index` in `out` and returns 1, or
+/// returns 0 if it isn't one.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_int(entry: *const BlogEntry, index: usize, out: *mut i64) -> c_int {
+    guard(0, || {
+        let value = match (*entry).arg(index) {
+            Some(LogValue::Integer(value)) => *value as i64,
+            Some(LogValue::Integer64(value)) => *value,
+            _ => return 0,
+        };
+        *out = value;
+        1
+    })
+}
+
+/// Stores unsigned integer argument `index` in `out` and returns 1, or
+/// returns 0 if it isn't one.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_uint(entry: *const BlogEntry, index: usize, out: *mut u64) -> c_int {
+    guard(0, || {
+        match (*entry).arg(index) {
+            Some(LogValue::Unsigned(value)) => {
+                *out = *value;
+                1
+            }
+            _ => 0,
+        }
+    })
+}
+
+/// Stores floating point argument `index` in `out` and returns 1, or
+/// returns 0 if it isn't one.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_float(entry: *const BlogEntry, index: usize, out: *mut f64) -> c_int {
+    guard(0, || {
+        if blog_entry_arg_kind(entry, index) != BLOG_ARG_FLOAT {
+            return 0;
+        }
+        match (*entry).arg(index).and_then(LogValue::as_f64) {
+            Some(value) => {
+                *out = value;
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Stores boolean argument `index` in `out` as 0 or 1 and returns 1, or
+/// returns 0 if it isn't one.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_bool(entry: *const BlogEntry, index: usize, out: *mut c_int) -> c_int {
+    guard(0, || {
+        match (*entry).arg(index) {
+            Some(LogValue::Boolean(value)) => {
+                *out = *value as c_int;
+                1
+            }
+            _ => 0,
+        }
+    })
+}
+
+/// Returns the text of argument `index` as it appears in the message, for
+/// arguments of any kind, or NULL past the last argument.
+///
+/// # Safety
+///
+/// See `blog_entry_timestamp_us`.
+#[no_mangle]
+pub unsafe extern "C" fn blog_entry_arg_text(entry: *const BlogEntry, index: usize) -> *const c_char {
+    guard(ptr::null(), || {
+        (*entry).arg_text(index)
+    })
+}
@@ -0,0 +1,75 @@
+//! Conformance of the C API against the portable fixtures of the main
+//! crate: the entries decoded through `blog_*` must give exactly the lines
+//! of `tests/fixtures/portable_*.txt`, as they must for any binding.
+
+//!
+//! Nothing is logged here: arguments logged in the reading process would
+//! teach the reader their kinds, which foreign consumers never know.
+
+use blogreader::*;
+use std::ffi::{CStr, CString, c_char};
+use std::fs;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures").join(name)
+}
+
+unsafe fn text(ptr: *const c_char) -> Option<String> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_str().unwrap().to_string())
+}
+
+/// Decodes every entry of `reader` into the line format of the fixtures,
+/// then frees the reader.
+unsafe fn decode(reader: *mut BlogReader) -> Vec<String> {
+    assert!(!reader.is_null());
+    let mut lines = Vec::new();
+    loop {
+        let entry = blog_reader_next_entry(reader);
+        if entry.is_null() {
+            break;
+        }
+        let mut sequence = 0;
+        let sequence = match blog_entry_sequence(entry, &mut sequence) {
+            1 => sequence.to_string(),
+            _ => "-".to_string(),
+        };
+        lines.push(format!("{} {} {} {}",
+            blog_entry_timestamp_us(entry),
+            sequence,
+            text(blog_entry_channel_name(entry)).unwrap_or("-".to_string()),
+            text(blog_entry_message(entry)).unwrap()));
+        blog_entry_free(entry);
+    }
+    blog_reader_free(reader);
+    lines
+}
+
+#[test]
+fn test_fixtures_decode_as_expected() {
+    for name in ["portable_plain", "portable_compact"] {
+        let expected: Vec<String> = fs::read_to_string(fixture(&format!("{}.txt", name))).unwrap()
+            .lines().map(str::to_string).collect();
+        let path = CString::new(fixture(&format!("{}.blog", name)).to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { decode(blog_reader_open(path.as_ptr())) }, expected, "{}", name);
+
+        // Bytes in memory decode the same
+        let data = fs::read(fixture(&format!("{}.blog", name))).unwrap();
+        assert_eq!(unsafe { decode(blog_reader_from_bytes(data.as_ptr(), data.len())) }, expected, "{}", name);
+    }
+}
+
+#[test]
+fn test_missing_and_empty_logs() {
+    unsafe {
+        let path = CString::new("/nonexistent/app.blog").unwrap();
+        assert!(blog_reader_open(path.as_ptr()).is_null());
+        assert!(blog_reader_open(std::ptr::null()).is_null());
+
+        let reader = blog_reader_from_bytes(std::ptr::null(), 0);
+        assert!(blog_reader_next_entry(reader).is_null());
+        blog_reader_free(reader);
+        blog_reader_free(std::ptr::null_mut());
+        blog_entry_free(std::ptr::null_mut());
+    }
+}
@@ -0,0 +1,70 @@
+use binary_logger::{Logger, BufferHandler, log_record, log_record_at};
+use binary_logger::level::Level;
+use blogreader::*;
+use std::ffi::{CStr, c_char};
+use std::sync::{Arc, Mutex};
+
+struct CollectingHandler(Arc<Mutex<Vec<u8>>>);
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+unsafe fn text(ptr: *const c_char) -> Option<String> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_str().unwrap().to_string())
+}
+
+#[test]
+fn test_entry_fields() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_embedded_dictionary(true);
+        logger.set_type_tags(true);
+        let (sent, delta, ratio) = (u64::MAX, -7i64, 0.25f32);
+        log_record_at!(logger, Level::Warn, "Sent {} delta {} ratio {} ok {} host {}", sent, delta, ratio, true, "db1").unwrap();
+        log_record!(logger, channel: "ops", "Idle",).unwrap();
+    }
+    let data = data.lock().unwrap();
+
+    unsafe {
+        let reader = blog_reader_from_bytes(data.as_ptr(), data.len());
+        let entry = blog_reader_next_entry(reader);
+        assert_eq!(text(blog_entry_message(entry)).unwrap(), "Sent 18446744073709551615 delta -7 ratio 0.25 ok true host db1");
+        assert_eq!(text(blog_entry_format_string(entry)).unwrap(), "Sent {} delta {} ratio {} ok {} host {}");
+        assert_eq!(blog_entry_level(entry), 4);
+        assert_eq!(blog_entry_channel(entry), 0);
+        assert!(blog_entry_channel_name(entry).is_null());
+        assert_eq!(blog_entry_arg_count(entry), 5);
+
+        let kinds: Vec<_> = (0..6).map(|index| blog_entry_arg_kind(entry, index)).collect();
+        assert_eq!(kinds, [BLOG_ARG_UINT, BLOG_ARG_INT, BLOG_ARG_FLOAT, BLOG_ARG_BOOL, BLOG_ARG_TEXT, -1]);
+        let (mut uint, mut int, mut float, mut boolean) = (0u64, 0i64, 0f64, 0);
+        assert_eq!(blog_entry_arg_uint(entry, 0, &mut uint), 1);
+        assert_eq!(uint, u64::MAX);
+        assert_eq!(blog_entry_arg_int(entry, 1, &mut int), 1);
+        assert_eq!(int, -7);
+        assert_eq!(blog_entry_arg_float(entry, 2, &mut float), 1);
+        assert_eq!(float, 0.25);
+        assert_eq!(blog_entry_arg_bool(entry, 3, &mut boolean), 1);
+        assert_eq!(boolean, 1);
+        assert_eq!(text(blog_entry_arg_text(entry, 4)).unwrap(), "db1");
+        assert!(blog_entry_arg_text(entry, 5).is_null());
+
+        // Accessors of another kind leave `out` alone
+        assert_eq!(blog_entry_arg_int(entry, 0, &mut int), 0);
+        assert_eq!(int, -7);
+
+        // Entries outlive their reader
+        let second = blog_reader_next_entry(reader);
+        blog_reader_free(reader);
+        assert_eq!(text(blog_entry_message(second)).unwrap(), "Idle");
+        assert_eq!(text(blog_entry_channel_name(second)).unwrap(), "ops");
+        assert_eq!(blog_entry_level(second), 0);
+        assert!(blog_entry_timestamp_us(second) >= blog_entry_timestamp_us(entry));
+        blog_entry_free(entry);
+        blog_entry_free(second);
+    }
+}
@@ -0,0 +1,26 @@
+//! Selects how `log_record!` captures arguments.
+//!
+//! Nightly compilers capture any argument through specialization of
+//! `LogArg`. Stable ones, or any compiler with the `stable-compat` feature,
+//! get the `stable_compat` cfg, which leaves specialization out (see
+//! `arg_types::Arg`).
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(stable_compat)");
+    if env::var_os("CARGO_FEATURE_STABLE_COMPAT").is_some() || !is_nightly() {
+        println!("cargo:rustc-cfg=stable_compat");
+    }
+}
+
+/// Returns whether the compiler accepts unstable features: a nightly or dev
+/// build, or one allowed to by `RUSTC_BOOTSTRAP`.
+fn is_nightly() -> bool {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let Ok(output) = Command::new(rustc).arg("--version").output() else { return false };
+    let version = String::from_utf8_lossy(&output.stdout);
+    version.contains("-nightly") || version.contains("-dev") || env::var_os("RUSTC_BOOTSTRAP").is_some()
+}
@@ -0,0 +1,142 @@
+//! A multi-threaded web server logging through the crate's main subsystems,
+//! with the live viewer over its logs.
+//!
+//! Usage: `cargo run --example webserver_demo --features serve -- [--dir DIR]
+//! [--workers N] [--requests N] [--viewer HOST:PORT]`
+//!
+//! The server (see `server.rs`) answers `GET /orders/N`, `/slow`, `/report`
+//! (which fails) and 404s anything else. Every thread logs to a file of its
+//! own in DIR through a `BackgroundWriter`, and request IDs tie the records
+//! of one request together across the accept thread and the workers.
+//!
+//! Without `--viewer`, the demo sends `--requests` requests (200 by
+//! default), stops the server, merges the logs and checks that every
+//! request was accepted before it was completed, exiting with status 1 if
+//! one wasn't. With `--viewer`, it keeps sending a request every 100 ms and
+//! serves the `blog-serve` viewer over the log files, which follows them as
+//! they grow, and `debug_snapshot()` as JSON at `/debug/snapshot`, until it
+//! is interrupted.
+
+mod server;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Server};
+use binary_logger::debug_snapshot;
+use binary_logger::render::{RenderOptions, TimeFormat};
+use binary_logger::serve::{LogServer, Response};
+use server::{check_logs, get, log_files, DemoServer};
+
+const USAGE: &str = "Usage: webserver_demo [--dir DIR] [--workers N] [--requests N] [--viewer HOST:PORT]";
+
+/// Paths the load generator requests, in turn
+const PATHS: [&str; 8] = ["/orders/17", "/orders/42", "/orders/7", "/slow", "/orders/x", "/orders/99", "/missing", "/report"];
+
+fn main() {
+    let mut dir = env::temp_dir().join("webserver_demo");
+    let mut workers = 4;
+    let mut requests = 200;
+    let mut viewer = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--dir" => dir = PathBuf::from(value()),
+            "--workers" => workers = value().parse().unwrap_or_else(|_| usage()),
+            "--requests" => requests = value().parse().unwrap_or_else(|_| usage()),
+            "--viewer" => viewer = Some(value()),
+            _ => usage(),
+        }
+    }
+
+    // Logs of an earlier run would be merged with this one's
+    if let Ok(files) = log_files(&dir) {
+        for file in files {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+    let server = DemoServer::start(&dir, workers.max(1)).unwrap_or_else(|e| fail(&format!("cannot start server: {}", e)));
+    eprintln!("webserver_demo: serving on http://{}/, logging to {}", server.addr(), dir.display());
+
+    match viewer {
+        Some(viewer) => {
+            let addr = server.addr();
+            thread::spawn(move || {
+                for path in PATHS.iter().cycle() {
+                    let _ = get(addr, path);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            });
+            serve_viewer(&viewer, log_files(&dir).unwrap_or_default());
+        }
+        None => {
+            let addr = server.addr();
+            let clients: Vec<_> = (0..4).map(|client| thread::spawn(move || {
+                for i in (client..requests).step_by(4) {
+                    if let Err(e) = get(addr, PATHS[i % PATHS.len()]) {
+                        eprintln!("webserver_demo: request failed: {}", e);
+                    }
+                }
+            })).collect();
+            for client in clients {
+                let _ = client.join();
+            }
+            for metrics in server.pool().metrics_all() {
+                eprintln!("webserver_demo: {}: {} records", metrics.thread, metrics.stats.records_written);
+            }
+            server.stop();
+
+            let summary = check_logs(&dir).unwrap_or_else(|e| fail(&format!("cannot read logs: {}", e)));
+            println!("{} entries, {} requests accepted", summary.entries, summary.accepted);
+            for (status, count) in &summary.statuses {
+                println!("  status {}: {}", status, count);
+            }
+            if !summary.broken.is_empty() {
+                fail(&format!("requests not followed from acceptance to completion: {:?}", summary.broken));
+            }
+            println!("Logs are in {}; view them with: blog-serve {}", dir.display(), dir.display());
+        }
+    }
+}
+
+/// Serves the viewer over `files`, and the debug snapshot, on `addr`.
+fn serve_viewer(addr: &str, files: Vec<PathBuf>) -> ! {
+    let server = Server::http(addr).unwrap_or_else(|e| fail(&format!("cannot listen on {}: {}", addr, e)));
+    eprintln!("webserver_demo: viewer on http://{}/, snapshot on http://{}/debug/snapshot", addr, addr);
+
+    let render = RenderOptions { time_format: TimeFormat::Rfc3339, ..RenderOptions::default() };
+    let logs = LogServer::new(files).with_render_options(render);
+    for request in server.incoming_requests() {
+        let response = match *request.method() {
+            Method::Get if request.url() == "/debug/snapshot" => Response {
+                status: 200,
+                content_type: "application/json",
+                body: debug_snapshot().to_json().into_bytes(),
+            },
+            Method::Get => logs.handle(request.url()),
+            _ => Response { status: 405, content_type: "text/plain", body: b"method not allowed".to_vec() },
+        };
+        let header = Header::from_bytes("Content-Type", response.content_type)
+            .expect("content types are valid header values");
+        let reply = tiny_http::Response::from_data(response.body)
+            .with_status_code(response.status)
+            .with_header(header);
+        if let Err(e) = request.respond(reply) {
+            eprintln!("webserver_demo: cannot send response: {}", e);
+        }
+    }
+    fail("viewer stopped")
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("webserver_demo: {}", message);
+    process::exit(1);
+}
@@ -0,0 +1,281 @@
+//! The demo server and the check of the logs it writes.
+//!
+//! An accept thread hands connections to a fixed set of worker threads over
+//! a channel. Every thread logs through one `LoggerPool`, so each has a
+//! Logger of its own writing to `<thread>.blog` in the log directory
+//! through a `BackgroundWriter`. The accept thread gives each connection a
+//! request ID, and every record about the request carries it as its first
+//! argument: that is the correlation ID that ties the worker's records to
+//! the accept thread's across files. Global sequence numbers order the
+//! records of all files, and a flusher thread hands every Logger's buffer
+//! over each `FLUSH_INTERVAL`, so the viewer's live tail stays current.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use binary_logger::{LogMerger, LogReader, LogValue, log_error, log_record_at};
+use binary_logger::handlers::BackgroundWriter;
+use binary_logger::instant::LogInstant;
+use binary_logger::level::Level;
+use binary_logger::pool::LoggerPool;
+
+/// Size of each thread's Logger buffers
+pub const BUFFER_SIZE: usize = 65536;
+
+/// How often the flusher hands every buffer to its writer
+pub const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+type Pool = LoggerPool<BUFFER_SIZE>;
+
+/// A running demo server.
+pub struct DemoServer {
+    addr: SocketAddr,
+    pool: Arc<Pool>,
+    stopping: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl DemoServer {
+    /// Starts the server on a free local port with `workers` worker
+    /// threads, logging to files in `dir`.
+    ///
+    /// Returns once every thread has its Logger, so the log files exist.
+    pub fn start(dir: &Path, workers: usize) -> io::Result<DemoServer> {
+        fs::create_dir_all(dir)?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        let files = dir.to_path_buf();
+        let pool = Arc::new(Pool::new(move || {
+            let name = thread::current().name().unwrap_or("unnamed").to_string();
+            let file = File::create(files.join(format!("{}.blog", name))).expect("log directory is writable");
+            BackgroundWriter::new(file, 4)
+        }).configure(|logger| {
+            logger.set_global_sequence(true);
+            logger.set_embedded_dictionary(true);
+            logger.set_type_tags(true);
+            logger.set_debug_name(thread::current().name());
+        }));
+
+        let stopping = Arc::new(AtomicBool::new(false));
+        let ready = Arc::new(Barrier::new(workers + 2));
+        let (connections, incoming) = mpsc::channel();
+        let incoming = Arc::new(Mutex::new(incoming));
+        let mut threads = Vec::new();
+
+        for worker in 0..workers {
+            let (pool, incoming, ready) = (pool.clone(), incoming.clone(), ready.clone());
+            threads.push(thread::Builder::new().name(format!("worker-{}", worker)).spawn(move || {
+                pool.with(|logger| log_record_at!(logger, Level::Info, "Worker {} ready", worker as u32)).unwrap();
+                ready.wait();
+                work(&pool, &incoming);
+            })?);
+        }
+
+        {
+            let (pool, stopping, ready) = (pool.clone(), stopping.clone(), ready.clone());
+            threads.push(thread::Builder::new().name("accept".to_string()).spawn(move || {
+                pool.with(|logger| log_record_at!(logger, Level::Info, "Listening on port {}", addr.port())).unwrap();
+                ready.wait();
+                let mut next_id = 1u64;
+                for stream in listener.incoming() {
+                    if stopping.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let id = next_id;
+                    next_id += 1;
+                    let port = stream.peer_addr().map_or(0, |peer| peer.port());
+                    pool.with(|logger| log_record_at!(logger, Level::Debug, "Request {} accepted from port {} at {}",
+                        id, port, LogInstant::now())).unwrap();
+                    if connections.send((id, stream)).is_err() {
+                        break;
+                    }
+                }
+                pool.with(|logger| log_record_at!(logger, Level::Info, "Stopped accepting after {} requests", next_id - 1)).unwrap();
+            })?);
+        }
+
+        {
+            let (pool, stopping) = (pool.clone(), stopping.clone());
+            threads.push(thread::Builder::new().name("flusher".to_string()).spawn(move || {
+                while !stopping.load(Ordering::Relaxed) {
+                    thread::park_timeout(FLUSH_INTERVAL);
+                    pool.flush_all();
+                }
+            })?);
+        }
+
+        ready.wait();
+        Ok(DemoServer { addr, pool, stopping, threads })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the pool the server's threads log through.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Stops the server once the requests accepted so far are answered.
+    ///
+    /// The threads' Loggers are dropped as the threads exit, which writes
+    /// their last buffers, so the log files are complete on return.
+    pub fn stop(self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        // Wake the accept thread
+        let _ = TcpStream::connect(self.addr);
+        for thread in self.threads {
+            // Wake the flusher
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answers connections from `incoming` until the accept thread stops.
+fn work(pool: &Pool, incoming: &Mutex<Receiver<(u64, TcpStream)>>) {
+    loop {
+        let next = incoming.lock().unwrap().recv();
+        let Ok((id, stream)) = next else { break };
+        let started = Instant::now();
+        let status = match serve(pool, id, stream) {
+            Ok(status) => status,
+            Err(e) => {
+                pool.with(|logger| log_error!(logger, "Connection lost: {} (request {})", e, id)).unwrap();
+                continue;
+            }
+        };
+        let micros = started.elapsed().as_micros() as u64;
+        let level = if status >= 500 { Level::Error } else if status >= 400 { Level::Warn } else { Level::Info };
+        pool.with(|logger| log_record_at!(logger, level, "Request {} completed with status {} in {} us", id, status, micros)).unwrap();
+    }
+}
+
+/// Reads one request from `stream` and answers it, returning the status.
+fn serve(pool: &Pool, id: u64, mut stream: TcpStream) -> io::Result<u16> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && !line.trim_end().is_empty() {
+        line.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    pool.with(|logger| log_record_at!(logger, Level::Info, "Request {} {} {}", id, method, path)).unwrap();
+
+    let (status, body) = match path.strip_prefix("/orders/").map(str::parse::<u32>) {
+        Some(Ok(order)) => {
+            pool.with(|logger| log_record_at!(logger, Level::Debug, "Request {} looked up order {}", id, order)).unwrap();
+            (200, format!("order {}\n", order))
+        }
+        Some(Err(_)) => (400, "bad order number\n".to_string()),
+        None if path == "/slow" => {
+            thread::sleep(Duration::from_millis(20));
+            (200, "done\n".to_string())
+        }
+        None if path == "/report" => match fs::read("/nonexistent/report.csv") {
+            Ok(report) => (200, String::from_utf8_lossy(&report).into_owned()),
+            Err(e) => {
+                pool.with(|logger| log_error!(logger, "Report unavailable: {} (request {})", e, id)).unwrap();
+                (500, "report unavailable\n".to_string())
+            }
+        },
+        None => (404, "not found\n".to_string()),
+    };
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, if status == 200 { "OK" } else { "Error" }, body.len(), body)?;
+    Ok(status)
+}
+
+/// Sends `GET path` to `addr`, returning the status and body.
+pub fn get(addr: SocketAddr, path: &str) -> io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect(addr)?;
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status = response.split_whitespace().nth(1).and_then(|s| s.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed response"))?;
+    let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+    Ok((status, body.to_string()))
+}
+
+/// Returns the log files in `dir`, sorted by name.
+pub fn log_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .filter(|path| path.as_ref().map_or(true, |path| path.extension().is_some_and(|ext| ext == "blog")))
+        .collect::<io::Result<_>>()?;
+    files.sort();
+    Ok(files)
+}
+
+/// What the logs of a stopped server say about its requests.
+#[derive(Debug, Default)]
+pub struct LogSummary {
+    /// Entries in all files
+    pub entries: usize,
+
+    /// Requests the accept thread handed over
+    pub accepted: usize,
+
+    /// Completed requests by status
+    pub statuses: BTreeMap<u16, usize>,
+
+    /// Requests that were accepted but never completed, or completed
+    /// before they were accepted in sequence order
+    pub broken: Vec<u64>,
+}
+
+/// Merges the logs in `dir` by sequence number and follows every request
+/// from the accept thread to the worker that completed it.
+pub fn check_logs(dir: &Path) -> io::Result<LogSummary> {
+    let data = log_files(dir)?.iter().map(fs::read).collect::<io::Result<Vec<_>>>()?;
+    let merger = LogMerger::new(data.iter().map(|data| LogReader::new(data)).collect());
+
+    let mut summary = LogSummary::default();
+    // Sequence number of each request's acceptance, then of its completion
+    let mut requests: BTreeMap<u64, (Option<u64>, Option<u64>)> = BTreeMap::new();
+    for entry in merger {
+        summary.entries += 1;
+        let Some(id) = entry.parameters.first().and_then(as_u64) else { continue };
+        match entry.format_string.as_deref() {
+            Some("Request {} accepted from port {} at {}") => {
+                summary.accepted += 1;
+                requests.entry(id).or_default().0 = entry.sequence;
+            }
+            Some("Request {} completed with status {} in {} us") => {
+                let status = entry.parameters.get(1).and_then(as_u64).unwrap_or(0) as u16;
+                *summary.statuses.entry(status).or_default() += 1;
+                requests.entry(id).or_default().1 = entry.sequence;
+            }
+            _ => {}
+        }
+    }
+    summary.broken = requests.into_iter()
+        .filter(|(_, sequence)| !matches!(sequence, (Some(accepted), Some(completed)) if accepted < completed))
+        .map(|(id, _)| id)
+        .collect();
+    Ok(summary)
+}
+
+fn as_u64(value: &LogValue) -> Option<u64> {
+    match *value {
+        LogValue::Unsigned(v) => Some(v),
+        LogValue::Integer64(v) => u64::try_from(v).ok(),
+        LogValue::Integer(v) => u64::try_from(v).ok(),
+        _ => None,
+    }
+}
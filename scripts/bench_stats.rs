@@ -159,7 +159,7 @@ fn calculate_stats(values: &[f64]) -> Stats {
     // Calculate median
     let mut sorted_values = values.to_vec();
     sorted_values.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    let median = if values.len() % 2 == 0 {
+    let median = if values.len().is_multiple_of(2) {
         let mid = values.len() / 2;
         (sorted_values[mid - 1] + sorted_values[mid]) / 2.0
     } else {
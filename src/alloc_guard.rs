@@ -0,0 +1,123 @@
+#![allow(dead_code)]
+
+//! Checks that code runs without heap allocations.
+//!
+//! Logging with `log_record!` and primitive arguments is meant to never
+//! allocate once its format string is registered. `CountingAllocator`
+//! wraps the system allocator and counts the allocations of the current
+//! thread while a check runs; `assert_no_alloc` fails loudly when the
+//! checked code allocated. Install the allocator in a test binary to keep
+//! the promise from regressing, in this crate's tests or for an
+//! application's own logging paths.
+//!
+//! Counts are per thread, so tests running in parallel don't disturb each
+//! other, and allocations made by other threads on behalf of the checked
+//! code are not seen.
+//!
+//! # Examples
+//!
+//! ```
+//! use binary_logger::alloc_guard::{CountingAllocator, assert_no_alloc, count_allocations};
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+//!
+//! let mut sum = 0u64;
+//! assert_no_alloc(|| (0..100).for_each(|i| sum += i));
+//! assert_eq!(count_allocations(|| vec![1, 2, 3]).1, 1);
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+thread_local! {
+    /// Allocations of the thread while a count is running, None otherwise
+    static COUNT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Whether a `CountingAllocator` has served an allocation
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// The system allocator, counting the allocations made during
+/// `count_allocations` and `assert_no_alloc`.
+///
+/// Reallocations count as allocations; deallocations don't.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    pub const fn new() -> Self {
+        CountingAllocator
+    }
+}
+
+impl Default for CountingAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Counts an allocation of the current thread.
+fn count() {
+    INSTALLED.store(true, Ordering::Relaxed);
+    // Thread-locals being destroyed can't count, and don't need to
+    let _ = COUNT.try_with(|count| {
+        if let Some(n) = count.get() {
+            count.set(Some(n + 1));
+        }
+    });
+}
+
+/// Returns whether a `CountingAllocator` is the global allocator, so that
+/// counts are meaningful.
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Runs `f` and returns its result with the number of heap allocations it
+/// made on the current thread.
+///
+/// Counts are always 0 without a `CountingAllocator` installed. Nested
+/// counts are added to the enclosing count when they end.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let outer = COUNT.with(|count| count.replace(Some(0)));
+    let result = f();
+    let allocations = COUNT.with(|count| count.replace(outer.map(|n| n + count.get().unwrap_or(0))));
+    (result, allocations.unwrap_or(0))
+}
+
+/// Runs `f` and returns its result, panicking if it allocated on the
+/// current thread.
+///
+/// # Panics
+///
+/// If `f` allocated, or if no `CountingAllocator` is installed, since the
+/// check would then pass vacuously.
+#[track_caller]
+pub fn assert_no_alloc<R>(f: impl FnOnce() -> R) -> R {
+    assert!(is_installed(), "assert_no_alloc needs CountingAllocator as the #[global_allocator]");
+    let (result, allocations) = count_allocations(f);
+    assert!(allocations == 0, "expected no heap allocations, but {} were made", allocations);
+    result
+}
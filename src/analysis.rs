@@ -0,0 +1,260 @@
+#![allow(dead_code)]
+
+//! Latency between pairs of events, and the segments of a log, from the
+//! log alone.
+//!
+//! A request that logs `"Request {} received"` when it starts and
+//! `"Request {} done"` when it ends already has its latency in the log:
+//! `latency_between` pairs each end event with the start event carrying the
+//! same key argument (the request ID), and returns the time between them
+//! as a `LatencyReport` with percentiles and a histogram. Only the records
+//! of the two format strings are decoded; the others are skipped on their
+//! headers (see `LogReader::skip_while_header`).
+//!
+//! Keys are compared as rendered, so a key logged as a `u32` at the start
+//! and as a `u64` at the end still matches. Starts of a key pending when
+//! another start comes are paired in order, first in first out. Ends
+//! without a start, and starts never ended, are counted apart.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+//! # use binary_logger::analysis::latency_between;
+//! # use std::sync::{Arc, Mutex};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! for request in 0..10u32 {
+//!     log_record!(logger, "Request {} received from {}", request, "10.0.0.7").unwrap();
+//!     log_record!(logger, "Request {} done", request).unwrap();
+//! }
+//! # }
+//! # let data = data.lock().unwrap();
+//! let report = latency_between(&mut LogReader::new(&data), "Request {} received from {}", "Request {} done", 0);
+//! assert_eq!(report.count(), 10);
+//! println!("{}", report); // count=10 min=... p50=... p90=... p99=... max=...
+//! ```
+//!
+//! `list_segments` lists the named segments a Logger marked with
+//! `Logger::begin_segment`, such as deployments or test runs, to scope an
+//! analysis to one with `LogReader::set_segment_filter`.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use crate::instrumentation::InternalEvent;
+use crate::log_reader::LogReader;
+use crate::string_registry::register_string;
+
+/// Latencies of the paired events of a log, see `latency_between`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyReport {
+    /// Latencies in ascending order
+    latencies: Vec<Duration>,
+
+    /// Start events with no end event after them
+    pub unmatched_starts: u64,
+
+    /// End events with no start event before them
+    pub unmatched_ends: u64,
+}
+
+/// Latencies in one range of a `LatencyReport::histogram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyBucket {
+    /// Smallest latency in the range
+    pub min: Duration,
+
+    /// First latency past the range
+    pub end: Duration,
+
+    /// Latencies in the range
+    pub count: u64,
+}
+
+/// A segment of a log, see `list_segments`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Name given to `Logger::begin_segment`
+    pub name: String,
+
+    /// Time of the record beginning the segment
+    pub begin: SystemTime,
+
+    /// Time of the record ending the segment, if the log has one
+    pub end: Option<SystemTime>,
+
+    /// Records between the segment's boundaries
+    pub records: u64,
+}
+
+impl Segment {
+    /// Returns the time from the segment's begin to its end, if it ended.
+    pub fn duration(&self) -> Option<Duration> {
+        self.end.map(|end| end.duration_since(self.begin).unwrap_or_default())
+    }
+}
+
+/// Lists the segments of the rest of the log read from `reader`, in log
+/// order, counting their records.
+///
+/// Only the boundary records are decoded; the records between them are
+/// counted on their headers, whatever the reader's filters. The boundary
+/// records have no level, channel or tags, so filters that leave them out
+/// leave out their segments. A name used again makes a segment of its own.
+pub fn list_segments(reader: &mut LogReader) -> Vec<Segment> {
+    let (begin_id, end_id) = (InternalEvent::SegmentBegin.format_id(), InternalEvent::SegmentEnd.format_id());
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut open = false;
+    loop {
+        let skipped = reader.skip_while_header(|header| {
+            header.custom_type.is_some() || (header.format_id != begin_id && header.format_id != end_id)
+        });
+        let entry = reader.read_entry();
+        let current = segments.last_mut().filter(|_| open);
+        let Some(entry) = entry else {
+            if let Some(segment) = current {
+                segment.records += skipped as u64;
+            }
+            break;
+        };
+        let is_boundary = entry.custom_type.is_none() && (entry.format_id == begin_id || entry.format_id == end_id);
+        if let Some(segment) = current {
+            // A record left by the skip and kept by the filters is counted too
+            segment.records += skipped as u64 + u64::from(!is_boundary);
+            if is_boundary {
+                // A begin without an end before it, as after a crash,
+                // leaves the segment unended
+                segment.end = (entry.format_id == end_id).then_some(entry.timestamp);
+                open = false;
+            }
+        }
+        if is_boundary && entry.format_id == begin_id {
+            let name = entry.parameters.first().map(ToString::to_string).unwrap_or_default();
+            segments.push(Segment { name, begin: entry.timestamp, end: None, records: 0 });
+            open = true;
+        }
+    }
+    segments
+}
+
+/// Pairs the events of `start_format` and `end_format` by their argument
+/// `key_arg` (from 0), reading the rest of the log from `reader`, and
+/// returns the latencies from each start to its end.
+///
+/// The reader's filters apply. Events whose key argument is missing are
+/// left out, and an end logged before its start, as clocks may have it in
+/// merged logs, counts as no latency.
+pub fn latency_between(reader: &mut LogReader, start_format: &'static str, end_format: &'static str, key_arg: usize) -> LatencyReport {
+    let (start_id, end_id) = (register_string(start_format), register_string(end_format));
+    let mut pending: HashMap<String, VecDeque<SystemTime>> = HashMap::new();
+    let mut report = LatencyReport::default();
+    loop {
+        reader.skip_while_header(|header| {
+            header.custom_type.is_some() || (header.format_id != start_id && header.format_id != end_id)
+        });
+        let Some(entry) = reader.read_entry() else { break };
+        let Some(key) = entry.parameters.get(key_arg).map(ToString::to_string) else { continue };
+        if entry.format_id == start_id {
+            pending.entry(key).or_default().push_back(entry.timestamp);
+        } else if entry.format_id == end_id {
+            match pending.get_mut(&key).and_then(VecDeque::pop_front) {
+                Some(start) => report.latencies.push(entry.timestamp.duration_since(start).unwrap_or_default()),
+                None => report.unmatched_ends += 1,
+            }
+        }
+    }
+    report.unmatched_starts = pending.values().map(|starts| starts.len() as u64).sum();
+    report.latencies.sort_unstable();
+    report
+}
+
+impl LatencyReport {
+    /// Returns the number of paired events.
+    pub fn count(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// Returns the latencies, shortest first.
+    pub fn latencies(&self) -> &[Duration] {
+        &self.latencies
+    }
+
+    /// Returns the shortest latency, if any.
+    pub fn min(&self) -> Option<Duration> {
+        self.latencies.first().copied()
+    }
+
+    /// Returns the longest latency, if any.
+    pub fn max(&self) -> Option<Duration> {
+        self.latencies.last().copied()
+    }
+
+    /// Returns the mean latency, if any.
+    pub fn mean(&self) -> Option<Duration> {
+        let total: u128 = self.latencies.iter().map(Duration::as_nanos).sum();
+        let mean = total.checked_div(self.latencies.len() as u128)?;
+        Some(Duration::from_nanos(mean.min(u64::MAX as u128) as u64))
+    }
+
+    /// Returns the nearest-rank `quantile` of the latencies, from 0.0 (the
+    /// shortest) to 1.0 (the longest), if any.
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let quantile = if quantile.is_nan() { 0.5 } else { quantile.clamp(0.0, 1.0) };
+        let rank = ((quantile * self.latencies.len() as f64).ceil() as usize).clamp(1, self.latencies.len());
+        Some(self.latencies[rank - 1])
+    }
+
+    /// Returns the latencies counted in ranges doubling in length: under
+    /// 1us, 1-2us, 2-4us, and so on up to the longest latency. Empty ranges
+    /// between the shortest and the longest latency are included.
+    pub fn histogram(&self) -> Vec<LatencyBucket> {
+        let bucket_of = |latency: &Duration| match latency.as_micros() {
+            0 => 0,
+            micros => 128 - micros.leading_zeros() as usize,
+        };
+        let bound = |bucket: usize| match bucket {
+            0 => Duration::ZERO,
+            _ => Duration::from_micros(1u64.checked_shl(bucket as u32 - 1).unwrap_or(u64::MAX)),
+        };
+        let (Some(first), Some(last)) = (self.latencies.first(), self.latencies.last()) else {
+            return Vec::new();
+        };
+        let (first, last) = (bucket_of(first), bucket_of(last));
+        let mut buckets: Vec<LatencyBucket> = (first..=last)
+            .map(|bucket| LatencyBucket { min: bound(bucket), end: bound(bucket + 1), count: 0 })
+            .collect();
+        for latency in &self.latencies {
+            buckets[bucket_of(latency) - first].count += 1;
+        }
+        buckets
+    }
+}
+
+/// The report on one line: the count, then the minimum, median, 90th and
+/// 99th percentiles and maximum, with the unmatched events if any.
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "count={}", self.count())?;
+        if !self.latencies.is_empty() {
+            let quantiles = [("min", 0.0), ("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("max", 1.0)];
+            for (name, quantile) in quantiles {
+                write!(f, " {}={:?}", name, self.percentile(quantile).unwrap())?;
+            }
+        }
+        if self.unmatched_starts > 0 || self.unmatched_ends > 0 {
+            write!(f, " unmatched_starts={} unmatched_ends={}", self.unmatched_starts, self.unmatched_ends)?;
+        }
+        Ok(())
+    }
+}
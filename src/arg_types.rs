@@ -0,0 +1,754 @@
+#![allow(dead_code)]
+
+//! Argument types recorded for format strings.
+//!
+//! The binary format stores arguments without type information, and
+//! LogReader guesses their types from their size. Where the size is
+//! ambiguous, as for `f32` and `i32`, `log_record!` registers the argument
+//! kinds of its format string here, so the reader can decode them exactly.
+//! Like the string registry, the kinds are known to readers in the writing
+//! process.
+//!
+//! Logs read elsewhere can carry the types themselves: with
+//! `Logger::set_type_tags`, records start with a `TypeTag` per argument,
+//! naming its type exactly (`u64` rather than "8 bytes").
+//!
+//! Numbers are captured little-endian, and pointer-sized integers as 64
+//! bits, on every architecture, so a log decodes the same wherever it was
+//! written.
+
+use std::borrow::Cow;
+use std::cell::{Cell, UnsafeCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+use crate::binary_logger::LogArg;
+use crate::level::Level;
+use crate::loggable::EncodedStruct;
+use crate::common_strings::{common_index, common_string, common_string_id, COMMON_STRING_MARKER};
+use crate::string_registry::{decode_id, encoded_id, register_string};
+
+lazy_static! {
+    /// Argument kinds by format ID, for format strings with typed arguments
+    static ref ARG_KINDS: RwLock<HashMap<u32, Vec<ArgKind>>> = RwLock::new(HashMap::new());
+}
+
+/// Most arguments whose kinds are recorded per call
+const MAX_TYPED_ARGS: usize = 16;
+
+/// Largest argument encoded when logged
+pub(crate) const ARG_SCRATCH_SIZE: usize = 16;
+
+/// The type of an argument, as stored in records with type tags (see
+/// `Logger::set_type_tags`): one byte per argument.
+///
+/// Tags from 0x80 are `Fixed` decimals, with the scale in the low 7 bits.
+/// Arguments of other types have the `UNKNOWN` tag, and are read as in
+/// records without tags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TypeTag(u8);
+
+impl TypeTag {
+    /// A type without a tag of its own
+    pub const UNKNOWN: TypeTag = TypeTag(0);
+    pub const BOOL: TypeTag = TypeTag(1);
+    pub const I8: TypeTag = TypeTag(2);
+    pub const I16: TypeTag = TypeTag(3);
+    pub const I32: TypeTag = TypeTag(4);
+    pub const I64: TypeTag = TypeTag(5);
+    pub const I128: TypeTag = TypeTag(6);
+    pub const U8: TypeTag = TypeTag(7);
+    pub const U16: TypeTag = TypeTag(8);
+    pub const U32: TypeTag = TypeTag(9);
+    pub const U64: TypeTag = TypeTag(10);
+    pub const U128: TypeTag = TypeTag(11);
+    pub const F16: TypeTag = TypeTag(12);
+    pub const F32: TypeTag = TypeTag(13);
+    pub const F64: TypeTag = TypeTag(14);
+    pub const CHAR: TypeTag = TypeTag(15);
+
+    /// `&str` and `String`
+    pub const STR: TypeTag = TypeTag(16);
+
+    /// A string literal, as its registry ID
+    pub const INTERNED: TypeTag = TypeTag(17);
+
+    /// `instant::LogInstant`
+    pub const INSTANT: TypeTag = TypeTag(18);
+
+    /// `error_chain::ErrorChain`
+    pub const ERROR_CHAIN: TypeTag = TypeTag(19);
+
+    /// `backtrace::LogBacktrace`
+    pub const BACKTRACE: TypeTag = TypeTag(20);
+
+    /// `flags::Flags`
+    pub const FLAGS: TypeTag = TypeTag(21);
+
+    /// `&[u8]` or `Vec<u8>`, as the bytes
+    pub const BYTES: TypeTag = TypeTag(22);
+
+    /// A `loggable::Loggable` struct, field by field
+    pub const STRUCT: TypeTag = TypeTag(23);
+
+    /// First tag of `Fixed` decimals
+    const FIXED: u8 = 0x80;
+
+    /// Returns the tag stored as `bits`.
+    pub const fn from_bits(bits: u8) -> TypeTag {
+        TypeTag(bits)
+    }
+
+    /// Returns the tag as stored.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the tag of a `fixed::Fixed` decimal with `scale`, or
+    /// `UNKNOWN` for scales over 127.
+    pub const fn fixed(scale: u32) -> TypeTag {
+        if scale < 0x80 { TypeTag(Self::FIXED | scale as u8) } else { Self::UNKNOWN }
+    }
+
+    /// Returns the scale of a `Fixed` tag.
+    pub const fn fixed_scale(self) -> Option<u32> {
+        if self.0 & Self::FIXED != 0 { Some((self.0 & !Self::FIXED) as u32) } else { None }
+    }
+
+    /// Returns the tag of an argument of `kind`.
+    pub(crate) fn of_kind(kind: ArgKind) -> TypeTag {
+        match kind {
+            ArgKind::Raw => Self::UNKNOWN,
+            ArgKind::F32 => Self::F32,
+            ArgKind::F16 => Self::F16,
+            ArgKind::Fixed(scale) => Self::fixed(scale),
+            ArgKind::Instant => Self::INSTANT,
+            ArgKind::ErrorChain => Self::ERROR_CHAIN,
+            ArgKind::Backtrace => Self::BACKTRACE,
+            ArgKind::Interned => Self::INTERNED,
+            ArgKind::Str => Self::STR,
+            ArgKind::Bytes => Self::BYTES,
+            ArgKind::Struct => Self::STRUCT,
+        }
+    }
+
+    /// Returns the kind the reader decodes arguments with this tag as, for
+    /// the tags of kinds.
+    pub(crate) fn kind(self) -> Option<ArgKind> {
+        if let Some(scale) = self.fixed_scale() {
+            return Some(ArgKind::Fixed(scale));
+        }
+        match self {
+            Self::F32 => Some(ArgKind::F32),
+            Self::F16 => Some(ArgKind::F16),
+            Self::INSTANT => Some(ArgKind::Instant),
+            Self::ERROR_CHAIN => Some(ArgKind::ErrorChain),
+            Self::BACKTRACE => Some(ArgKind::Backtrace),
+            Self::INTERNED => Some(ArgKind::Interned),
+            Self::STR => Some(ArgKind::Str),
+            Self::BYTES => Some(ArgKind::Bytes),
+            Self::STRUCT => Some(ArgKind::Struct),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the type, or None for tags this build doesn't
+    /// know.
+    pub fn name(self) -> Option<&'static str> {
+        if self.fixed_scale().is_some() {
+            return Some("fixed");
+        }
+        let name = match self {
+            Self::UNKNOWN => "unknown",            Self::BOOL => "bool",
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::I128 => "i128",
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::U128 => "u128",
+            Self::F16 => "f16",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+            Self::CHAR => "char",
+            Self::STR => "string",
+            Self::INTERNED => "interned string",
+            Self::INSTANT => "instant",
+            Self::ERROR_CHAIN => "error chain",
+            Self::BACKTRACE => "backtrace",
+            Self::FLAGS => "flags",
+            Self::BYTES => "bytes",
+            Self::STRUCT => "struct",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Display for TypeTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name(), self.fixed_scale()) {
+            (Some(name), Some(scale)) => write!(f, "{}({})", name, scale),
+            (Some(name), None) => f.write_str(name),
+            (None, _) => write!(f, "tag {:#04x}", self.0),
+        }
+    }
+}
+
+/// How an argument is decoded.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// Type guessed from the size
+    Raw,
+
+    /// `f32`
+    F32,
+
+    /// `half::f16`
+    F16,
+
+    /// `fixed::Fixed` with this scale
+    Fixed(u32),
+
+    /// `LogInstant`, as a varint of microseconds before the record
+    Instant,
+
+    /// `error_chain::ErrorChain`
+    ErrorChain,
+
+    /// `backtrace::LogBacktrace`
+    Backtrace,
+
+    /// A string literal, as its registry ID (see `Interned`)
+    Interned,
+
+    /// A `&str` or `String`, as its UTF-8 bytes
+    Str,
+
+    /// A `&[u8]` or `Vec<u8>`, as its bytes
+    Bytes,
+
+    /// A `loggable::Loggable` struct, as its layout ID and fields
+    Struct,
+}
+
+/// Limits on the size of captured arguments, by the level of the record.
+///
+/// Long strings cost time and space on every call. Capping them by level
+/// keeps frequent low-severity records cheap while errors keep the detail:
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler};
+/// # use binary_logger::arg_types::CaptureLimits;
+/// # use binary_logger::level::Level;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<65536>::new(NullHandler);
+/// logger.set_capture_limits(CaptureLimits::new()
+///     .max_string_len(Level::Info, 64)
+///     .max_string_len(Level::Debug, 1024));
+/// ```
+///
+/// A string over its limit is cut at a character boundary and ends with
+/// `…`, within the limit, and its record is flagged as truncated
+/// (`LogEntry::truncated`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureLimits {
+    /// Longest string argument in bytes, for unleveled records and by level
+    strings: [Option<usize>; 1 + Level::ALL.len()],
+}
+
+impl CaptureLimits {
+    /// Creates limits that cap nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the string arguments of records at `level` to `bytes`; `None`
+    /// is for records without a level, from `log_record!`.
+    pub fn max_string_len(mut self, level: impl Into<Option<Level>>, bytes: usize) -> Self {
+        self.strings[level.into().map_or(0, |level| level as usize)] = Some(bytes);
+        self
+    }
+
+    /// Returns the longest string argument of records at `level`, if capped.
+    pub fn string_limit(&self, level: Option<Level>) -> Option<usize> {
+        self.strings[level.map_or(0, |level| level as usize)]
+    }
+}
+
+/// Kinds and encodings of the arguments of one `log_record!` call.
+///
+/// Arguments that are encoded when logged, such as `LogInstant`, are written
+/// to a scratch slot of their own, which lives as long as the call.
+#[doc(hidden)]
+pub struct ArgCapture {
+    kinds: [Cell<ArgKind>; MAX_TYPED_ARGS],
+    type_tags: [Cell<TypeTag>; MAX_TYPED_ARGS],
+    len: Cell<usize>,
+    typed: Cell<bool>,
+
+    /// Set when the Logger refused the record, whose kinds then aren't
+    /// registered
+    rejected: Cell<bool>,
+    scratch: [UnsafeCell<MaybeUninit<[u8; ARG_SCRATCH_SIZE]>>; MAX_TYPED_ARGS],
+    string_limit: Option<usize>,
+
+    /// Whether arguments may be stored as common strings, see
+    /// `Logger::set_common_strings`
+    common_strings: bool,
+
+    /// Truncated copies of arguments over their limit. Boxes are only
+    /// added, so their bytes stay in place as long as the capture
+    truncated: UnsafeCell<Vec<Box<[u8]>>>,
+
+    /// Encoded `Loggable` arguments, boxed so they stay in place like
+    /// `truncated`
+    #[allow(clippy::vec_box)]
+    structs: UnsafeCell<Vec<Box<EncodedStruct>>>,
+}
+
+impl ArgCapture {
+    pub fn new() -> Self {
+        Self::with_string_limit(None)
+    }
+
+    /// Creates a capture cutting string arguments to `limit` bytes.
+    pub fn with_string_limit(limit: Option<usize>) -> Self {
+        ArgCapture {
+            kinds: [const { Cell::new(ArgKind::Raw) }; MAX_TYPED_ARGS],
+            type_tags: [const { Cell::new(TypeTag::UNKNOWN) }; MAX_TYPED_ARGS],
+            len: Cell::new(0),
+            typed: Cell::new(false),
+            rejected: Cell::new(false),
+            scratch: [const { UnsafeCell::new(MaybeUninit::uninit()) }; MAX_TYPED_ARGS],
+            string_limit: limit,
+            common_strings: false,
+            truncated: UnsafeCell::new(Vec::new()),
+            structs: UnsafeCell::new(Vec::new()),
+        }
+    }
+
+    /// Lets the capture store arguments as common strings.
+    pub fn with_common_strings(mut self, enabled: bool) -> Self {
+        self.common_strings = enabled;
+        self
+    }
+
+    /// Returns whether an argument was cut to its limit.
+    pub fn truncated(&self) -> bool {
+        unsafe { !(*self.truncated.get()).is_empty() }
+    }
+
+    /// Returns the kind of argument `index`, if it was captured with one.
+    pub(crate) fn kind(&self, index: usize) -> Option<ArgKind> {
+        (index < self.len.get()).then(|| self.kinds[index].get())
+    }
+
+    /// Returns the type tag of argument `index`, `UNKNOWN` past the
+    /// arguments captured with one.
+    pub(crate) fn type_tag(&self, index: usize) -> TypeTag {
+        if index < self.len.get() { self.type_tags[index].get() } else { TypeTag::UNKNOWN }
+    }
+
+    /// Keeps `register` from recording the kinds of a record the Logger
+    /// refused, see `schema`.
+    pub(crate) fn reject(&self) {
+        self.rejected.set(true);
+    }
+
+    /// Returns `bytes` of a string cut to the limit, ending in `…`.
+    fn truncate<'a>(&'a self, bytes: &'a [u8], limit: usize) -> &'a [u8] {
+        const ELLIPSIS: &str = "…";
+        let text = String::from_utf8_lossy(bytes);
+        let keep = limit.saturating_sub(if limit >= ELLIPSIS.len() { ELLIPSIS.len() } else { 0 });
+        let end = (0..=keep.min(text.len())).rev().find(|&end| text.is_char_boundary(end)).unwrap_or(0);
+        let mut cut = text[..end].to_string();
+        if limit >= ELLIPSIS.len() {
+            cut.push_str(ELLIPSIS);
+        }
+        // Only this call touches the list, and pushing leaves earlier boxes
+        // where they are
+        let truncated = unsafe { &mut *self.truncated.get() };
+        truncated.push(cut.into_bytes().into_boxed_slice());
+        truncated.last().unwrap()
+    }
+
+    /// Keeps an encoded `Loggable` argument for the call.
+    pub(crate) fn keep_struct(&self, value: EncodedStruct) -> &EncodedStruct {
+        // Arguments are captured one at a time, and pushing leaves earlier
+        // boxes where they are
+        let structs = unsafe { &mut *self.structs.get() };
+        structs.push(Box::new(value));
+        structs.last().unwrap()
+    }
+
+    /// Records the kinds for `format_id` if any argument needs one.
+    pub fn register(&self, format_id: u32) {
+        if !self.typed.get() || self.rejected.get() {
+            return;
+        }
+        let kinds = &self.kinds[..self.len.get()];
+        if ARG_KINDS.read().unwrap().get(&format_id)
+            .is_some_and(|known| known.iter().copied().eq(kinds.iter().map(Cell::get))) {
+            return;
+        }
+        ARG_KINDS.write().unwrap().insert(format_id, kinds.iter().map(Cell::get).collect());
+    }
+}
+
+impl Default for ArgCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the bytes of a `log_record!` argument, noting its kind.
+#[doc(hidden)]
+pub fn capture<'a, T: LogArg>(value: &'a T, capture: &'a ArgCapture) -> &'a [u8] {
+    let index = capture.len.get();
+    if index >= MAX_TYPED_ARGS {
+        return value.log_bytes();
+    }
+    let kind = value.arg_kind();
+    capture.kinds[index].set(kind);
+    capture.type_tags[index].set(value.type_tag());
+    capture.len.set(index + 1);
+    capture.typed.set(capture.typed.get() || kind != ArgKind::Raw);
+    if kind == ArgKind::Str {
+        let bytes = value.log_bytes();
+        // Only strings the marker makes shorter
+        if let Some(common) = common_index(bytes).filter(|_| capture.common_strings && bytes.len() > 2) {
+            let slot = unsafe { (*capture.scratch[index].get()).write([0; ARG_SCRATCH_SIZE]) };
+            slot[..2].copy_from_slice(&[COMMON_STRING_MARKER, common]);
+            return &slot[..2];
+        }
+        return match capture.string_limit {
+            Some(limit) if bytes.len() > limit => capture.truncate(bytes, limit),
+            _ => bytes,
+        };
+    }
+
+    // Each argument index gets its slot once, so the slot is never borrowed
+    // twice
+    let slot = unsafe { (*capture.scratch[index].get()).write([0; ARG_SCRATCH_SIZE]) };
+    if kind == ArgKind::Interned && !capture.common_strings {
+        // A common string literal, registered after all
+        if let Some(s) = decode_id(value.log_bytes()).and_then(|(id, _)| common_string(id)) {
+            let (bytes, len) = encoded_id(register_string(s));
+            slot[..len].copy_from_slice(&bytes[..len]);
+            return &slot[..len];
+        }
+    }
+    match value.encode(slot) {
+        Some(len) => &slot[..len],
+        None => value.log_bytes(),
+    }
+}
+
+/// Returns the argument kinds registered for `format_id`, if any argument
+/// of the format string is typed.
+pub(crate) fn arg_kinds(format_id: u32) -> Option<Vec<ArgKind>> {
+    ARG_KINDS.read().unwrap().get(&format_id).cloned()
+}
+
+/// Writes `bytes`, a value's little-endian encoding, to a scratch slot.
+pub(crate) fn encode_le(bytes: &[u8], out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+    out[..bytes.len()].copy_from_slice(bytes);
+    Some(bytes.len())
+}
+
+/// Numbers are logged little-endian on every architecture. That is their
+/// in-memory layout on little-endian targets, where they are captured as
+/// they are; big-endian targets encode them. Pointer-sized integers are
+/// logged as 64 bits everywhere.
+macro_rules! portable_numbers {
+    ($($ty:ty => $tag:ident),*) => {$(
+        impl LogArg for $ty {
+            fn type_tag(&self) -> TypeTag {
+                TypeTag::$tag
+            }
+
+            #[cfg(target_endian = "big")]
+            fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+                encode_le(&self.to_le_bytes(), out)
+            }
+        }
+    )*};
+}
+
+portable_numbers!(u16 => U16, i16 => I16, u32 => U32, i32 => I32, u64 => U64, i64 => I64,
+    u128 => U128, i128 => I128, f64 => F64);
+
+impl LogArg for bool {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::BOOL
+    }
+}
+
+impl LogArg for u8 {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::U8
+    }
+}
+
+impl LogArg for i8 {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::I8
+    }
+}
+
+impl LogArg for usize {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::U64
+    }
+
+    #[cfg(any(target_endian = "big", not(target_pointer_width = "64")))]
+    fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        encode_le(&(*self as u64).to_le_bytes(), out)
+    }
+}
+
+impl LogArg for isize {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::I64
+    }
+
+    #[cfg(any(target_endian = "big", not(target_pointer_width = "64")))]
+    fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        encode_le(&(*self as i64).to_le_bytes(), out)
+    }
+}
+
+impl LogArg for char {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::CHAR
+    }
+
+    #[cfg(target_endian = "big")]
+    fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        encode_le(&(*self as u32).to_le_bytes(), out)
+    }
+}
+
+impl LogArg for f32 {
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::F32
+    }
+
+    #[cfg(target_endian = "big")]
+    fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        encode_le(&self.to_le_bytes(), out)
+    }
+}
+
+#[cfg(feature = "f16")]
+impl LogArg for half::f16 {
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::F16
+    }
+
+    #[cfg(target_endian = "big")]
+    fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        encode_le(&self.to_le_bytes(), out)
+    }
+}
+
+/// References to numbers, booleans and characters are logged as the value
+/// they point to, with its tag and encoding, rather than as the pointer.
+macro_rules! primitive_refs {
+    ($($ty:ty),*) => {$(
+        impl LogArg for &$ty {
+            fn log_bytes(&self) -> &[u8] {
+                (**self).log_bytes()
+            }
+
+            fn arg_kind(&self) -> ArgKind {
+                (**self).arg_kind()
+            }
+
+            fn type_tag(&self) -> TypeTag {
+                (**self).type_tag()
+            }
+
+            fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+                (**self).encode(out)
+            }
+        }
+    )*};
+}
+
+primitive_refs!(bool, u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize,
+    f32, f64, char);
+
+#[cfg(feature = "f16")]
+primitive_refs!(half::f16);
+
+/// Strings are logged by value, as their UTF-8 bytes, whatever holds
+/// them: their in-memory bytes are only a pointer and a length.
+macro_rules! string_args {
+    ($($ty:ty),*) => {$(
+        impl LogArg for $ty {
+            fn log_bytes(&self) -> &[u8] {
+                str::as_bytes(self)
+            }
+
+            fn arg_kind(&self) -> ArgKind {
+                ArgKind::Str
+            }
+        }
+    )*};
+}
+
+string_args!(&str, &&str, String, &String, Box<str>, Cow<'_, str>, Rc<str>, Arc<str>);
+
+/// Byte strings are logged by value too, and read back as
+/// `LogValue::Bytes` rather than guessed from their size.
+macro_rules! byte_args {
+    ($($ty:ty),*) => {$(
+        impl LogArg for $ty {
+            fn log_bytes(&self) -> &[u8] {
+                self
+            }
+
+            fn arg_kind(&self) -> ArgKind {
+                ArgKind::Bytes
+            }
+        }
+    )*};
+}
+
+byte_args!(&[u8], Vec<u8>, &Vec<u8>, Box<[u8]>);
+
+impl<const N: usize> LogArg for [u8; N] {
+    fn log_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Bytes
+    }
+}
+
+impl<const N: usize> LogArg for &[u8; N] {
+    fn log_bytes(&self) -> &[u8] {
+        &self[..]
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Bytes
+    }
+}
+
+/// A string literal argument, stored as the registry ID of the string.
+///
+/// `log_record!` interns string literal arguments like format strings, so
+/// `log_record!(logger, "state={}", "ready")` writes 2 bytes for `"ready"`
+/// instead of the string, and the reader looks it up in the registry. A
+/// wide ID takes 6 bytes (see `string_registry::WIDE_ID`). Literals among
+/// the common strings take their reserved ID instead (see
+/// `common_strings`).
+#[doc(hidden)]
+pub struct Interned {
+    bytes: [u8; 6],
+    len: u8,
+}
+
+impl LogArg for Interned {
+    fn log_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Interned
+    }
+}
+
+/// A literal argument of `log_record!`, before it is captured.
+///
+/// `(&Literal(x)).literal_arg()` resolves to `InternLiteral` for string
+/// literals and to `PlainLiteral`, which keeps the value, for any other
+/// literal: method lookup tries `&Literal` before `&&Literal`.
+#[doc(hidden)]
+pub struct Literal<T>(pub T);
+
+#[doc(hidden)]
+pub trait InternLiteral {
+    fn literal_arg(&self) -> Interned;
+}
+
+impl InternLiteral for Literal<&'static str> {
+    fn literal_arg(&self) -> Interned {
+        let id = common_string_id(self.0).unwrap_or_else(|| register_string(self.0));
+        let (bytes, len) = encoded_id(id);
+        Interned { bytes, len: len as u8 }
+    }
+}
+
+#[doc(hidden)]
+pub trait PlainLiteral<T> {
+    fn literal_arg(&self) -> T;
+}
+
+impl<T: Copy> PlainLiteral<T> for &Literal<T> {
+    fn literal_arg(&self) -> T {
+        self.0
+    }
+}
+
+/// A `log_record!` argument on stable compilers (`stable-compat`), where
+/// `LogArg` has no blanket implementation.
+///
+/// `(&Arg(&x)).log_arg()` resolves to `x` itself through `EncodedArg` if
+/// its type implements `LogArg`, and to `x` seen as a `RawArg`, captured as
+/// its in-memory bytes, through `RawArgFallback` otherwise: method lookup
+/// tries `&Arg` before `&&Arg`, as for `Literal`.
+#[cfg(stable_compat)]
+#[doc(hidden)]
+pub struct Arg<'a, T>(pub &'a T);
+
+#[cfg(stable_compat)]
+#[doc(hidden)]
+pub trait EncodedArg<'a, T> {
+    fn log_arg(&self) -> &'a T;
+}
+
+#[cfg(stable_compat)]
+impl<'a, T: LogArg> EncodedArg<'a, T> for Arg<'a, T> {
+    fn log_arg(&self) -> &'a T {
+        self.0
+    }
+}
+
+#[cfg(stable_compat)]
+#[doc(hidden)]
+pub trait RawArgFallback<'a, T> {
+    fn log_arg(&self) -> &'a RawArg<T>;
+}
+
+#[cfg(stable_compat)]
+impl<'a, T> RawArgFallback<'a, T> for &Arg<'a, T> {
+    fn log_arg(&self) -> &'a RawArg<T> {
+        // RawArg is a transparent wrapper of T
+        unsafe { &*(self.0 as *const T as *const RawArg<T>) }
+    }
+}
+
+/// An argument whose type has no `LogArg` implementation, captured as its
+/// in-memory bytes like the blanket implementation does on nightly.
+#[cfg(stable_compat)]
+#[doc(hidden)]
+#[repr(transparent)]
+pub struct RawArg<T>(T);
+
+#[cfg(stable_compat)]
+impl<T> LogArg for RawArg<T> {}
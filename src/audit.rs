@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+
+//! Verification of tamper-evident audit logs.
+//!
+//! A Logger in audit mode (see `Logger::set_audit_chain`) starts every buffer
+//! with a chain record holding the SHA-256 of the previous buffer. This module
+//! walks the buffers of such a log and checks every link, so modification,
+//! removal or reordering of any buffer is detected.
+//!
+//! Metadata buffers of a dictionary channel (see
+//! `Logger::set_dictionary_channel`) are outside the chain and skipped.
+
+use std::fmt;
+use sha2::{Digest, Sha256};
+use crate::binary_logger::{
+    BUFFER_HEADER_SIZE, RECORD_TYPE_CHAIN, CHAIN_HASH_SIZE, FLAG_SEQUENCE, FLAG_CHANNEL,
+    FLAG_SAME_FORMAT, SHORT_RECORD_HEADER_SIZE, WIDE_ID_SIZE, LONG_LENGTH, LONG_LENGTH_SIZE, record_header_size,
+};
+use crate::log_reader::is_metadata_buffer;
+use crate::string_registry::{decode_id, WIDE_ID};
+
+/// Result of a successful chain verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainReport {
+    /// Number of buffers verified
+    pub buffers: usize,
+
+    /// SHA-256 of the last buffer
+    ///
+    /// Compare it with `Logger::chain_head()` recorded elsewhere to detect
+    /// buffers removed from the end of the log.
+    pub head: [u8; CHAIN_HASH_SIZE],
+}
+
+/// Reason a hash chain failed to verify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainError {
+    /// The buffer's size header is inconsistent with the data; the log is
+    /// truncated or its framing was modified.
+    BadBuffer { index: usize, offset: usize },
+
+    /// The buffer contains no chain record.
+    MissingLink { index: usize, offset: usize },
+
+    /// The buffer's chain record does not match the hash of the buffer before
+    /// it (or the expected start of the chain, for the first buffer).
+    BrokenLink { index: usize, offset: usize },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::BadBuffer { index, offset } =>
+                write!(f, "buffer {} at offset {}: invalid buffer size", index, offset),
+            ChainError::MissingLink { index, offset } =>
+                write!(f, "buffer {} at offset {}: no chain record", index, offset),
+            ChainError::BrokenLink { index, offset } =>
+                write!(f, "buffer {} at offset {}: hash chain broken", index, offset),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// Verifies the hash chain of an audit log.
+///
+/// # Arguments
+///
+/// * `data` - The log: one or more buffers as produced by the BufferHandler
+/// * `previous` - Hash of the buffer preceding `data`, when verifying a
+///   segment that continues an earlier one; `None` if `data` starts the
+///   stream, in which case its first link must be all zeros
+///
+/// # Returns
+///
+/// * `Ok(ChainReport)` - Every link is intact; `buffers` counts the data
+///   buffers
+/// * `Err(ChainError)` - The first buffer that failed verification
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::audit::verify_chain;
+/// # fn example(data: &[u8], recorded_head: [u8; 32]) {
+/// match verify_chain(data, None) {
+///     Ok(report) if report.head == recorded_head => println!("{} buffers intact", report.buffers),
+///     Ok(_) => println!("buffers missing from the end"),
+///     Err(e) => println!("tampering detected: {}", e),
+/// }
+/// # }
+/// ```
+pub fn verify_chain(data: &[u8], previous: Option<[u8; CHAIN_HASH_SIZE]>) -> Result<ChainReport, ChainError> {
+    let mut expected = previous.unwrap_or([0; CHAIN_HASH_SIZE]);
+    let mut offset = 0;
+    let mut index = 0;
+
+    while offset < data.len() {
+        let remaining = data.len() - offset;
+        if remaining < BUFFER_HEADER_SIZE {
+            return Err(ChainError::BadBuffer { index, offset });
+        }
+        let mut size_bytes = [0u8; 8];
+        size_bytes.copy_from_slice(&data[offset..offset + 8]);
+        let size = u64::from_le_bytes(size_bytes) as usize;
+        if size < BUFFER_HEADER_SIZE || size > remaining {
+            return Err(ChainError::BadBuffer { index, offset });
+        }
+
+        let buffer = &data[offset..offset + size];
+        if is_metadata_buffer(buffer) {
+            offset += size;
+            continue;
+        }
+        match find_chain_link(buffer) {
+            Some(link) if link == expected => {}
+            Some(_) => return Err(ChainError::BrokenLink { index, offset }),
+            None => return Err(ChainError::MissingLink { index, offset }),
+        }
+
+        expected = Sha256::digest(buffer).into();
+        offset += size;
+        index += 1;
+    }
+
+    Ok(ChainReport { buffers: index, head: expected })
+}
+
+/// Returns the data after the metadata buffers at its start.
+pub(crate) fn skip_metadata_buffers(mut data: &[u8]) -> &[u8] {
+    while is_metadata_buffer(data) {
+        let mut size_bytes = [0u8; 8];
+        size_bytes.copy_from_slice(&data[..BUFFER_HEADER_SIZE]);
+        match usize::try_from(u64::from_le_bytes(size_bytes)) {
+            Ok(size) if size >= BUFFER_HEADER_SIZE && size <= data.len() => data = &data[size..],
+            _ => break,
+        }
+    }
+    data
+}
+
+/// Returns the hash stored in the buffer's chain record, if it has one.
+pub(crate) fn find_chain_link(buffer: &[u8]) -> Option<[u8; CHAIN_HASH_SIZE]> {
+    let mut pos = BUFFER_HEADER_SIZE;
+
+    while pos + SHORT_RECORD_HEADER_SIZE <= buffer.len() {
+        let start = pos;
+        let record_type = buffer[pos];
+        let flags = buffer[pos + 1];
+        let header_size = record_header_size(&buffer[pos..])?;
+        if pos + header_size > buffer.len() {
+            return None;
+        }
+        pos += header_size;
+        let mut payload_len = u16::from_le_bytes([buffer[pos - 2], buffer[pos - 1]]) as usize;
+        let wide_format = flags & FLAG_SAME_FORMAT == 0 && u16::from_le_bytes([buffer[pos - 4], buffer[pos - 3]]) == WIDE_ID;
+        if flags & FLAG_SEQUENCE != 0 {
+            pos += 8;
+        }
+        if flags & FLAG_CHANNEL != 0 {
+            pos += decode_id(buffer.get(pos..)?)?.1;
+        }
+        if wide_format {
+            pos += WIDE_ID_SIZE;
+        }
+        if payload_len == LONG_LENGTH as usize {
+            payload_len = u32::from_le_bytes(buffer.get(pos..pos + LONG_LENGTH_SIZE)?.try_into().unwrap()) as usize;
+            pos += LONG_LENGTH_SIZE;
+        }
+        if pos + payload_len > buffer.len() {
+            return None;
+        }
+
+        if record_type == RECORD_TYPE_CHAIN && payload_len == CHAIN_HASH_SIZE {
+            let mut link = [0u8; CHAIN_HASH_SIZE];
+            link.copy_from_slice(&buffer[pos..pos + CHAIN_HASH_SIZE]);
+            return Some(link);
+        }
+
+        pos += payload_len;
+        if !(pos - start).is_multiple_of(2) {
+            pos += 1;
+        }
+    }
+    None
+}
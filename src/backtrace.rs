@@ -0,0 +1,142 @@
+#![allow(dead_code)]
+
+//! Backtraces as log arguments.
+//!
+//! `LogBacktrace::capture()` resolves the current stack and interns each
+//! frame ("symbol at file:line:col") in the string registry, like format
+//! strings, so a frame costs its 2-byte ID once it has been seen. The
+//! argument is `[count u8]` followed by the frame IDs, innermost first, a
+//! wide ID as `string_registry::WIDE_ID` and the u32 ID, and reads back as
+//! `LogValue::Backtrace`.
+//!
+//! Resolving symbols takes milliseconds: capture backtraces for rare
+//! failures, not on hot paths. Backtraces can be requested per call, by
+//! passing `LogBacktrace::capture()` as an argument, or per logger with
+//! `Logger::set_error_backtraces`, which makes `log_error!` add one to every
+//! record.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use crate::arg_types::ArgKind;
+use crate::binary_logger::LogArg;
+use crate::string_registry::{decode_id, encode_id, get_string, register_string};
+
+/// Most frames recorded per backtrace, counted from the capture point
+const MAX_FRAMES: usize = 32;
+
+lazy_static! {
+    /// Frame text to registry ID, so each distinct frame is leaked once.
+    static ref FRAME_IDS: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
+}
+
+/// A resolved backtrace, captured for logging.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::backtrace::LogBacktrace;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// log_record!(logger, "Invariant broken in batch {}", 7, LogBacktrace::capture()).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogBacktrace {
+    encoded: Vec<u8>,
+}
+
+impl LogBacktrace {
+    /// Captures the stack of the calling thread.
+    pub fn capture() -> Self {
+        let text = std::backtrace::Backtrace::force_capture().to_string();
+        let ids = {
+            let mut frame_ids = FRAME_IDS.lock().unwrap();
+            parse_frames(&text)
+                .into_iter()
+                .take(MAX_FRAMES)
+                .map(|frame| *frame_ids.entry(frame).or_insert_with_key(|frame| {
+                    register_string(Box::leak(frame.clone().into_boxed_str()))
+                }))
+                .collect::<Vec<_>>()
+        };
+
+        let mut encoded = vec![ids.len() as u8];
+        for id in ids {
+            encode_id(id, &mut encoded);
+        }
+        LogBacktrace { encoded }
+    }
+
+    /// Captures the stack if `enabled`, otherwise returns an empty backtrace
+    /// that costs one byte and renders as nothing.
+    pub fn capture_if(enabled: bool) -> Self {
+        if enabled {
+            Self::capture()
+        } else {
+            LogBacktrace { encoded: vec![0] }
+        }
+    }
+}
+
+impl LogArg for LogBacktrace {
+    fn log_bytes(&self) -> &[u8] {
+        &self.encoded
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Backtrace
+    }
+}
+
+/// Splits the text of a std backtrace into one string per frame, dropping
+/// the frames of the capture itself.
+fn parse_frames(text: &str) -> Vec<String> {
+    let mut frames: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                frame.push_str(" at ");
+                frame.push_str(location);
+            }
+        } else if let Some((index, symbol)) = line.split_once(": ") {
+            if index.chars().all(|c| c.is_ascii_digit()) {
+                frames.push(symbol.to_string());
+            }
+        }
+    }
+
+    let own = frames.iter()
+        .rposition(|frame| frame.starts_with("binary_logger::backtrace::"))
+        .map_or(0, |i| i + 1);
+    frames.split_off(own)
+}
+
+/// Decodes an argument written by `LogBacktrace` into its frames.
+pub(crate) fn decode_backtrace(arg: &[u8]) -> Option<Vec<String>> {
+    let (&count, mut ids) = arg.split_first()?;
+    let mut frames = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (id, len) = decode_id(ids)?;
+        frames.push(get_string(id).map_or_else(|| format!("<frame #{}>", id), str::to_string));
+        ids = &ids[len..];
+    }
+    ids.is_empty().then_some(frames)
+}
+
+/// Writes one numbered frame per line, after a `backtrace:` heading.
+pub(crate) fn fmt_backtrace(frames: &[String], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if frames.is_empty() {
+        return Ok(());
+    }
+    f.write_str("backtrace:")?;
+    for (i, frame) in frames.iter().enumerate() {
+        write!(f, "\n  {:>2}: {}", i, frame)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Merges and compresses aged segments of binary logs.
+//!
+//! Usage: `blog-compact [--min-age SECONDS] [--target-bytes N] [--level N] [--dry-run] FILE...`
+//!
+//! Files are segments of one log, such as rotated files, given oldest
+//! first. Runs of consecutive segments last modified at least `--min-age`
+//! seconds ago (a day by default) are merged until they hold
+//! `--target-bytes` (64 MiB by default) and compressed into one `.zst` file
+//! at zstd `--level` (1 to 22, 19 by default), which replaces them; see
+//! `binary_logger::compact`. A line is printed per compressed file, and
+//! with `--dry-run` per file that would be written, without changing any.
+//! The exit status is 0 on success and 2 on usage and I/O errors.
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime};
+use binary_logger::compact::{Compaction, COMPRESSED_EXTENSION};
+
+const USAGE: &str = "Usage: blog-compact [--min-age SECONDS] [--target-bytes N] [--level N] [--dry-run] FILE...";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("blog-compact: {}", message);
+    process::exit(2);
+}
+
+fn main() {
+    let mut compaction = Compaction::new(SystemTime::now());
+    let mut dry_run = false;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut number = || -> u64 { args.next().and_then(|value| value.parse().ok()).unwrap_or_else(|| usage()) };
+        match arg.as_str() {
+            "--min-age" => compaction = compaction.min_age(Duration::from_secs(number())),
+            "--target-bytes" => compaction = compaction.target_bytes(number()),
+            "--level" => compaction = compaction.level(number().try_into().unwrap_or_else(|_| usage())),
+            "--dry-run" => dry_run = true,
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        usage();
+    }
+
+    if dry_run {
+        for run in compaction.plan(&files).0 {
+            let names: Vec<_> = run.iter().map(|path| path.display().to_string()).collect();
+            println!("{}.{}: would merge {}", names[0], COMPRESSED_EXTENSION, names.join(", "));
+        }
+        return;
+    }
+    let report = compaction.run(&files).unwrap_or_else(|e| fail(e.to_string()));
+    for recovered in &report.recovered {
+        println!("{}: removed, left by an interrupted run", recovered.display());
+    }
+    for compacted in &report.compacted {
+        println!("{}: {} segments, {} -> {} bytes", compacted.path.display(), compacted.segments.len(),
+            compacted.bytes_in, compacted.bytes_out);
+    }
+}
@@ -0,0 +1,79 @@
+//! Converts text logs to the binary format.
+//!
+//! Usage: `blog-convert --pattern PATTERN [--time-zone ZONE] INPUT OUTPUT`
+//!
+//! Each line of `INPUT` is split with `PATTERN`, such as
+//! `'{time} {level} [{channel}] {message}'` (see
+//! `binary_logger::convert::TextPattern`), and written to `OUTPUT` as a
+//! record with the line's time. `-` reads the standard input. Times without
+//! an offset are read in `ZONE`: `utc` (the default), `local` or an offset
+//! like `+02:00`. A summary line is printed at the end. The exit status is
+//! 0 on success and 2 on usage and I/O errors.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::process;
+use std::sync::Mutex;
+use binary_logger::BufferHandler;
+use binary_logger::convert::{convert_to, TextPattern};
+use binary_logger::render::TimeZone;
+
+const USAGE: &str = "Usage: blog-convert --pattern PATTERN [--time-zone ZONE] INPUT OUTPUT";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("blog-convert: {}", message);
+    process::exit(2);
+}
+
+/// Writes the converted log to the output file.
+struct FileWriter(Mutex<BufWriter<File>>);
+
+impl BufferHandler for FileWriter {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().write_all(data).unwrap_or_else(|e| fail(format!("cannot write the output: {}", e)));
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        self.0.get_mut().unwrap().flush().unwrap_or_else(|e| fail(format!("cannot write the output: {}", e)));
+    }
+}
+
+fn main() {
+    let mut pattern = None;
+    let mut time_zone = TimeZone::Utc;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pattern" => pattern = Some(args.next().unwrap_or_else(|| usage())),
+            "--time-zone" => {
+                let zone = args.next().unwrap_or_else(|| usage());
+                time_zone = zone.parse().unwrap_or_else(|e| fail(e));
+            }
+            _ => files.push(arg),
+        }
+    }
+    let (Some(pattern), [input, output]) = (pattern, &files[..]) else {
+        usage();
+    };
+    let pattern = pattern.parse::<TextPattern>().unwrap_or_else(|e| fail(e)).with_time_zone(time_zone);
+
+    let output_file = File::create(output).unwrap_or_else(|e| fail(format!("cannot create {}: {}", output, e)));
+    let handler = FileWriter(Mutex::new(BufWriter::new(output_file)));
+    let stats = if input == "-" {
+        convert_to(io::stdin().lock(), &pattern, handler)
+    } else {
+        let file = File::open(input).unwrap_or_else(|e| fail(format!("cannot read {}: {}", input, e)));
+        convert_to(BufReader::with_capacity(1 << 20, file), &pattern, handler)
+    };
+    let stats = stats.unwrap_or_else(|e| fail(format!("cannot read {}: {}", input, e)));
+    println!("{}: {}", output, stats);
+}
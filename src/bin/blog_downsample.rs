@@ -0,0 +1,89 @@
+//! Reduces a binary log to per-interval aggregates for dashboards.
+//!
+//! Usage: `blog-downsample [--interval DURATION] [--value FORMAT ARG] [--quantile Q] [--json] FILE...`
+//!
+//! Prints one row per interval (60s by default, written like `500ms`, `10s`,
+//! `5m` or `1h`) with the number of entries, the number of errors and the
+//! p99 of argument ARG (from 0) of the entries logged with the format
+//! string FORMAT, as CSV or, with `--json`, as Grafana JSON time series
+//! (see `downsample::Downsampled`). `--quantile` picks another percentile,
+//! from 0 to 1. Several files are read as consecutive segments of one log.
+//! The exit status is 0 on success and 2 on usage or I/O errors.
+
+use std::env;
+use std::process;
+use std::time::Duration;
+use binary_logger::compact::read_segment;
+use binary_logger::downsample::Downsampler;
+
+const USAGE: &str = "Usage: blog-downsample [--interval DURATION] [--value FORMAT ARG] [--quantile Q] [--json] FILE...";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Parses a duration such as `250ms`, `10s`, `5m` or `1h`.
+fn parse_interval(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let amount: u64 = text[..split].parse().ok().filter(|&amount| amount > 0)?;
+    match &text[split..] {
+        "us" => Some(Duration::from_micros(amount)),
+        "ms" => Some(Duration::from_millis(amount)),
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_secs(amount * 60)),
+        "h" => Some(Duration::from_secs(amount * 3600)),
+        _ => None,
+    }
+}
+
+fn main() {
+    let mut interval = Duration::from_secs(60);
+    let mut value = None;
+    let mut quantile = None;
+    let mut json = false;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval" => interval = args.next().as_deref().and_then(parse_interval).unwrap_or_else(|| usage()),
+            "--value" => {
+                let format = args.next().unwrap_or_else(|| usage());
+                let arg: usize = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+                value = Some((format, arg));
+            }
+            "--quantile" => quantile = Some(args.next().and_then(|q| q.parse().ok()).unwrap_or_else(|| usage())),
+            "--json" => json = true,
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        usage();
+    }
+
+    let mut data = Vec::new();
+    for file in &files {
+        match read_segment(file) {
+            Ok(segment) => data.extend_from_slice(&segment),
+            Err(e) => {
+                eprintln!("blog-downsample: cannot read {}: {}", file, e);
+                process::exit(2);
+            }
+        }
+    }
+
+    let mut downsampler = Downsampler::new(interval);
+    if let Some((format, arg)) = value {
+        // Format strings are interned for the life of the process
+        downsampler = downsampler.with_value(Box::leak(format.into_boxed_str()), arg);
+    }
+    if let Some(quantile) = quantile {
+        downsampler = downsampler.with_quantile(quantile);
+    }
+    let series = downsampler.run(&data);
+    if json {
+        println!("{}", series.to_grafana_json());
+    } else {
+        print!("{}", series.to_csv());
+    }
+}
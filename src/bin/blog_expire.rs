@@ -0,0 +1,73 @@
+//! Removes the entries past their channel's time to live from binary logs.
+//!
+//! Usage: `blog-expire [--now UNIX_SECONDS] [--dry-run] FILE...`
+//!
+//! Each file is rewritten in place without the entries whose channel's
+//! retention (see `Logger::set_channel_retention`) has passed, as of now or
+//! of `--now`, and a line with the number of entries removed is printed for
+//! it. Files are replaced atomically, and only when entries expired; with
+//! `--dry-run` none are. Several files are read as consecutive segments of
+//! one log, such as rotated files, oldest first (see
+//! `binary_logger::retention`). The exit status is 0 on success and 2 on
+//! usage errors, I/O errors, damaged logs and hash-chained logs with
+//! entries to remove.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use binary_logger::retention::Expiry;
+
+const USAGE: &str = "Usage: blog-expire [--now UNIX_SECONDS] [--dry-run] FILE...";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("blog-expire: {}", message);
+    process::exit(2);
+}
+
+/// Replaces the file at `path` with `data`, through a temporary file in the
+/// same directory.
+fn replace(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".expire-tmp");
+    fs::write(&temp, data)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+fn main() {
+    let mut now = SystemTime::now();
+    let mut dry_run = false;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--now" => {
+                let seconds: u64 = args.next().and_then(|seconds| seconds.parse().ok()).unwrap_or_else(|| usage());
+                now = UNIX_EPOCH + Duration::from_secs(seconds);
+            }
+            "--dry-run" => dry_run = true,
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        usage();
+    }
+
+    let mut expiry = Expiry::new(now);
+    for file in &files {
+        let data = fs::read(file).unwrap_or_else(|e| fail(format!("cannot read {}: {}", file, e)));
+        let expired = expiry.expire(&data).unwrap_or_else(|e| fail(format!("{}: {}", file, e)));
+        if !dry_run && !expired.expired.is_empty() {
+            replace(Path::new(file), &expired.data).unwrap_or_else(|e| fail(format!("cannot write {}: {}", file, e)));
+        }
+        println!("{}: {} of {} entries expired", file, expired.expired_entries(), expired.entries);
+    }
+}
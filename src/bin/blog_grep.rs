@@ -0,0 +1,141 @@
+//! Searches binary logs like `grep` searches text files.
+//!
+//! Usage: `blog-grep [-i] [-c] [-A N] [-B N] [-C N] [--segment NAME] [--tz ZONE] [--time-format FORMAT] [--time-digits N] [--render-cache N] PATTERN FILE...`
+//!
+//! Prints the entries whose rendered line (see `render::render_line`)
+//! matches the regex PATTERN. Records whose format string and channel rule
+//! out a match are skipped without decoding. With several files, each line is
+//! prefixed with its file name. `-i` matches case-insensitively and `-c`
+//! prints the number of matching entries instead. `-A`, `-B` and `-C` print
+//! N entries of context after, before or around each match, with `--`
+//! between groups, as `grep` does. `--segment` searches only the segments
+//! named NAME (see `Logger::begin_segment` and `blog-segments`). `--tz` (`utc`, `local` or an offset like
+//! `+02:00`), `--time-format` (`epoch`, `rfc3339` or a strftime pattern) and
+//! `--time-digits` choose how timestamps are rendered, and so what PATTERN
+//! is matched against (see `render::RenderOptions`). `--render-cache` keeps
+//! the last N distinct messages rendered, which speeds up logs dominated by
+//! repeated messages (see `render::RenderCache`). The exit status is 0 if an entry matched,
+//! 1 if none did and 2 on errors.
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use regex::Regex;
+use binary_logger::LogReader;
+use binary_logger::compact::read_segment;
+use binary_logger::query::LogQuery;
+use binary_logger::render::{RenderOptions, RENDER_FLAGS};
+
+const USAGE: &str = "Usage: blog-grep [-i] [-c] [-A N] [-B N] [-C N] [--segment NAME] [--tz ZONE] [--time-format FORMAT] [--time-digits N] [--render-cache N] PATTERN FILE...";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut ignore_case = false;
+    let mut count_only = false;
+    let mut before = 0;
+    let mut after = 0;
+    let mut render = RenderOptions::default();
+    let mut render_cache = 0;
+    let mut segment = None;
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !positional.is_empty() {
+            positional.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "-i" => ignore_case = true,
+            "-c" => count_only = true,
+            "-A" | "-B" | "-C" => {
+                let lines: usize = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+                match arg.as_str() {
+                    "-A" => after = lines,
+                    "-B" => before = lines,
+                    _ => (before, after) = (lines, lines),
+                }
+            }
+            "--segment" => segment = Some(args.next().unwrap_or_else(|| usage())),
+            "--render-cache" => {
+                render_cache = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+            }
+            flag if RENDER_FLAGS.contains(&flag) => {
+                let value = args.next().unwrap_or_else(|| usage());
+                if let Err(e) = render.set_flag(flag, &value) {
+                    eprintln!("blog-grep: {}", e);
+                    process::exit(2);
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() < 2 {
+        usage();
+    }
+
+    let pattern = if ignore_case { format!("(?i){}", positional[0]) } else { positional[0].clone() };
+    let query = match Regex::new(&pattern) {
+        Ok(regex) => LogQuery::matching(regex).with_context(before, after).with_render_options(render)
+            .with_render_cache(render_cache),
+        Err(e) => {
+            eprintln!("blog-grep: invalid pattern: {}", e);
+            process::exit(2);
+        }
+    };
+
+    let files = &positional[1..];
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut matched = false;
+    let mut failed = false;
+
+    for path in files {
+        let data = match read_segment(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("blog-grep: {}: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        let mut count = 0u64;
+        let mut reader = LogReader::new(&data);
+        reader.set_segment_filter(segment.as_deref());
+        for hit in query.run(reader) {
+            if !hit.is_context {
+                count += 1;
+            }
+            if count_only {
+                continue;
+            }
+
+            // Like grep, context lines use '-' after the file name
+            let separator = if hit.is_context { '-' } else { ':' };
+            let mut written = if hit.gap_before { writeln!(out, "--") } else { Ok(()) };
+            if written.is_ok() {
+                written = if files.len() > 1 {
+                    writeln!(out, "{}{}{}", path, separator, hit.line)
+                } else {
+                    writeln!(out, "{}", hit.line)
+                };
+            }
+            if written.is_err() {
+                // Closed pipe, e.g. `| head`; something did match
+                process::exit(0);
+            }
+        }
+        matched |= count > 0;
+
+        if count_only {
+            let _ = if files.len() > 1 { writeln!(out, "{}:{}", path, count) } else { writeln!(out, "{}", count) };
+        }
+    }
+    let _ = out.flush();
+
+    process::exit(if failed { 2 } else if matched { 0 } else { 1 });
+}
@@ -0,0 +1,95 @@
+//! Writes the text log of another process as a binary log, live.
+//!
+//! Usage: `blog-ingest --pattern PATTERN [--time-zone ZONE] [--flush-ms N]
+//! [--input PATH] OUTPUT`
+//!
+//! Lines are read from the standard input, or from `PATH`, such as a FIFO
+//! a service writes to, until it ends. Each is split with `PATTERN` (see
+//! `binary_logger::convert::TextPattern`) and written to `OUTPUT`, which is
+//! created, at most `N` milliseconds (1000 by default) after it was read.
+//! A pattern without `{time}` stamps lines with the time they are read;
+//! otherwise times without an offset are read in `ZONE`: `utc` (the
+//! default), `local` or an offset like `+02:00`. A summary line is printed
+//! on standard error at the end. The exit status is 0 when the input ended,
+//! and 2 on usage and I/O errors.
+//!
+//! ```text
+//! my-service 2>&1 | blog-ingest --pattern '{level} {message}' service.blog
+//! ```
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+use std::sync::Mutex;
+use std::time::Duration;
+use binary_logger::BufferHandler;
+use binary_logger::convert::TextPattern;
+use binary_logger::ingest::ingest;
+use binary_logger::render::TimeZone;
+
+const USAGE: &str = "Usage: blog-ingest --pattern PATTERN [--time-zone ZONE] [--flush-ms N] [--input PATH] OUTPUT";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("blog-ingest: {}", message);
+    process::exit(2);
+}
+
+/// Writes each buffer to the output file as it is handed over.
+struct FileWriter(Mutex<File>);
+
+impl BufferHandler for FileWriter {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().write_all(data).unwrap_or_else(|e| fail(format!("cannot write the output: {}", e)));
+    }
+}
+
+fn main() {
+    let mut pattern = None;
+    let mut time_zone = TimeZone::Utc;
+    let mut flush_every = Duration::from_secs(1);
+    let mut input = None;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pattern" => pattern = Some(args.next().unwrap_or_else(|| usage())),
+            "--time-zone" => {
+                let zone = args.next().unwrap_or_else(|| usage());
+                time_zone = zone.parse().unwrap_or_else(|e| fail(e));
+            }
+            "--flush-ms" => {
+                let millis = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0).unwrap_or_else(|| usage());
+                flush_every = Duration::from_millis(millis);
+            }
+            "--input" => input = Some(args.next().unwrap_or_else(|| usage())),
+            _ if output.is_none() && !arg.starts_with("--") => output = Some(arg),
+            _ => usage(),
+        }
+    }
+    let (Some(pattern), Some(output)) = (pattern, output) else {
+        usage();
+    };
+    let pattern = match pattern.contains("{time") {
+        true => pattern.parse::<TextPattern>(),
+        false => TextPattern::without_time(&pattern),
+    };
+    let pattern = pattern.unwrap_or_else(|e| fail(e)).with_time_zone(time_zone);
+
+    let output_file = File::create(&output).unwrap_or_else(|e| fail(format!("cannot create {}: {}", output, e)));
+    let handler = FileWriter(Mutex::new(output_file));
+    let stats = match &input {
+        Some(path) => {
+            let file = File::open(path).unwrap_or_else(|e| fail(format!("cannot read {}: {}", path, e)));
+            ingest(file, &pattern, flush_every, handler)
+        }
+        None => ingest(io::stdin(), &pattern, flush_every, handler),
+    };
+    let stats = stats.unwrap_or_else(|e| fail(format!("cannot read {}: {}", input.as_deref().unwrap_or("the standard input"), e)));
+    eprintln!("{}: {}", output, stats);
+}
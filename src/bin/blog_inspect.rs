@@ -0,0 +1,36 @@
+//! Prints an annotated hex walk of a binary log file.
+//!
+//! Usage: `blog-inspect <file>`
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use binary_logger::compact::read_segment;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: blog-inspect <file>");
+            process::exit(2);
+        }
+    };
+
+    let data = match read_segment(&path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("blog-inspect: cannot read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    if let Err(e) = binary_logger::inspect::inspect(&data, &mut out).and_then(|_| out.flush()) {
+        // A closed pipe (e.g. `| head`) is not an error worth reporting
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("blog-inspect: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Mounts a directory of binary logs as read-only text files.
+//!
+//! Usage: `blog-mount [--tz ZONE] [--time-format FORMAT] [--time-digits N] [--render-cache N] <log-dir> <mountpoint>`
+//!
+//! Every file in `<log-dir>` appears in the mountpoint as `<name>.log`,
+//! containing the decoded entries one per line (see `render::render_text`),
+//! with timestamps rendered as the flags say (see `render::RenderOptions`).
+//! `--render-cache` keeps the last N distinct messages while decoding a
+//! file, which speeds up logs dominated by repeated messages (see
+//! `render::RenderCache`).
+//! Files are decoded the first time they are looked at and decoded again
+//! when the underlying file changes, so logs still being written stay
+//! current. Unmount with `fusermount -u <mountpoint>`.
+//!
+//! Requires the `fuse` feature.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use fuser::{
+    Config, Errno, FileAttr, FileHandle, FileType, Filesystem, Generation, INodeNo, LockOwner, MountOption,
+    OpenFlags, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+};
+use binary_logger::compact::read_segment;
+use binary_logger::render::{RenderCache, RenderOptions, RENDER_FLAGS, render_text_cached, render_text_with};
+
+/// How long the kernel may cache attributes; short, since logs grow
+const TTL: Duration = Duration::from_secs(1);
+
+/// Inode of the first log file; inode 1 is the root directory
+const FIRST_FILE_INODE: u64 = 2;
+
+/// A decoded log and the state of the source file it was decoded from.
+struct Rendered {
+    source_len: u64,
+    source_mtime: SystemTime,
+    text: Arc<Vec<u8>>,
+}
+
+/// Read-only filesystem of decoded logs.
+struct LogFs {
+    /// Source file and virtual name of each log, indexed by inode - FIRST_FILE_INODE
+    files: Vec<(PathBuf, String)>,
+    rendered: Mutex<HashMap<u64, Rendered>>,
+    render: RenderOptions,
+
+    /// Capacity of the render cache used per file, 0 for none
+    render_cache: usize,
+    mounted_at: SystemTime,
+
+    /// Owner (uid, gid) of the log directory, reported for every file
+    owner: (u32, u32),
+}
+
+impl LogFs {
+    fn new(dir: PathBuf, render: RenderOptions, render_cache: usize) -> std::io::Result<Self> {
+        let metadata = fs::metadata(&dir)?;
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+
+        let files = paths.into_iter()
+            .map(|path| {
+                let name = format!("{}.log", path.file_name().unwrap_or_default().to_string_lossy());
+                (path, name)
+            })
+            .collect();
+        Ok(LogFs {
+            files,
+            rendered: Mutex::new(HashMap::new()),
+            render,
+            render_cache,
+            mounted_at: SystemTime::now(),
+            owner: (metadata.uid(), metadata.gid()),
+        })
+    }
+
+    fn source(&self, ino: INodeNo) -> Option<&(PathBuf, String)> {
+        u64::from(ino).checked_sub(FIRST_FILE_INODE).and_then(|index| self.files.get(index as usize))
+    }
+
+    /// Returns the decoded text of a log, decoding it if it changed since last time.
+    fn text(&self, ino: INodeNo) -> Result<(Arc<Vec<u8>>, SystemTime), Errno> {
+        let (path, _) = self.source(ino).ok_or(Errno::ENOENT)?;
+        let metadata = fs::metadata(path).map_err(|_| Errno::EIO)?;
+        let mtime = metadata.modified().unwrap_or(self.mounted_at);
+
+        let mut rendered = self.rendered.lock().unwrap();
+        if let Some(cached) = rendered.get(&u64::from(ino)) {
+            if cached.source_len == metadata.len() && cached.source_mtime == mtime {
+                return Ok((cached.text.clone(), mtime));
+            }
+        }
+
+        let data = read_segment(path).map_err(|_| Errno::EIO)?;
+        let text = match self.render_cache {
+            0 => render_text_with(&data, &self.render),
+            capacity => render_text_cached(&data, &self.render, &mut RenderCache::new(capacity)),
+        };
+        let text = Arc::new(text.into_bytes());
+        rendered.insert(u64::from(ino), Rendered { source_len: metadata.len(), source_mtime: mtime, text: text.clone() });
+        Ok((text, mtime))
+    }
+
+    fn attr(&self, ino: INodeNo, kind: FileType, size: u64, mtime: SystemTime) -> FileAttr {
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm: if kind == FileType::Directory { 0o555 } else { 0o444 },
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.owner.0,
+            gid: self.owner.1,
+            rdev: 0,
+            flags: 0,
+            blksize: 512,
+        }
+    }
+
+    fn file_attr(&self, ino: INodeNo) -> Result<FileAttr, Errno> {
+        let (text, mtime) = self.text(ino)?;
+        Ok(self.attr(ino, FileType::RegularFile, text.len() as u64, mtime))
+    }
+}
+
+impl Filesystem for LogFs {
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        if parent != INodeNo::ROOT {
+            reply.error(Errno::ENOENT);
+            return;
+        }
+        let index = match self.files.iter().position(|(_, file_name)| OsStr::new(file_name) == name) {
+            Some(index) => index,
+            None => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+        };
+        match self.file_attr(INodeNo(FIRST_FILE_INODE + index as u64)) {
+            Ok(attr) => reply.entry(&TTL, &attr, Generation(0)),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn getattr(&self, _req: &Request, ino: INodeNo, _fh: Option<FileHandle>, reply: ReplyAttr) {
+        if ino == INodeNo::ROOT {
+            reply.attr(&TTL, &self.attr(ino, FileType::Directory, 0, self.mounted_at));
+            return;
+        }
+        match self.file_attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        size: u32,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyData,
+    ) {
+        match self.text(ino) {
+            Ok((text, _)) => {
+                let start = (offset as usize).min(text.len());
+                let end = start.saturating_add(size as usize).min(text.len());
+                reply.data(&text[start..end]);
+            }
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(&self, _req: &Request, ino: INodeNo, _fh: FileHandle, offset: u64, mut reply: ReplyDirectory) {
+        if ino != INodeNo::ROOT {
+            reply.error(Errno::ENOTDIR);
+            return;
+        }
+
+        let entries = [(INodeNo::ROOT, FileType::Directory, "."), (INodeNo::ROOT, FileType::Directory, "..")]
+            .into_iter()
+            .chain(self.files.iter().enumerate().map(|(index, (_, name))| {
+                (INodeNo(FIRST_FILE_INODE + index as u64), FileType::RegularFile, name.as_str())
+            }));
+        for (i, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+            // The offset passed back to us is that of the next entry
+            if reply.add(ino, (i + 1) as u64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+const USAGE: &str = "Usage: blog-mount [--tz ZONE] [--time-format FORMAT] [--time-digits N] [--render-cache N] <log-dir> <mountpoint>";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut render = RenderOptions::default();
+    let mut render_cache = 0;
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render-cache" => {
+                render_cache = args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage());
+            }
+            flag if RENDER_FLAGS.contains(&flag) => {
+                let value = args.next().unwrap_or_else(|| usage());
+                if let Err(e) = render.set_flag(flag, &value) {
+                    eprintln!("blog-mount: {}", e);
+                    process::exit(2);
+                }
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+
+    let filesystem = match LogFs::new(PathBuf::from(&paths[0]), render, render_cache) {
+        Ok(filesystem) => filesystem,
+        Err(e) => {
+            eprintln!("blog-mount: cannot read {}: {}", paths[0], e);
+            process::exit(2);
+        }
+    };
+
+    let mut config = Config::default();
+    config.mount_options.extend([MountOption::RO, MountOption::FSName("blog".to_string())]);
+    if let Err(e) = fuser::mount(filesystem, &paths[1], &config) {
+        eprintln!("blog-mount: cannot mount on {}: {}", paths[1], e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,126 @@
+//! Decodes binary logs streamed by many producers and re-emits them as text.
+//!
+//! Usage: `blog-proxy [--listen HOST:PORT]... [--unix PATH]... [--seqpacket PATH]... [--ndjson] [--output FILE]... [--forward HOST:PORT]... [--tz ZONE] [--time-format FORMAT] [--time-digits N]`
+//!
+//! Producers connect over TCP (`--listen`) or a Unix socket (`--unix`) and
+//! send their Logger's buffers unchanged, or send them with their
+//! credentials to a seqpacket socket (`--seqpacket`, see
+//! `binary_logger::seqpacket`), which names them by uid, gid and pid. Without either option the proxy
+//! serves the sockets passed by systemd socket activation. Every entry is
+//! written as a line prefixed with the producer's address or, with
+//! `--ndjson`, as a JSON object (see `binary_logger::proxy`), to standard
+//! output unless `--output` (appended to) or `--forward` (a TCP collector)
+//! name destinations. The render flags choose how timestamps are written
+//! (see `render::RenderOptions`). Connections that end with an error are
+//! reported on standard error. The exit status is 2 on usage errors or when
+//! a socket can't be opened or accepted on.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use binary_logger::proxy::{activated_listeners, DecodingProxy, Listener, OutputFormat};
+use binary_logger::render::{RenderOptions, RENDER_FLAGS};
+#[cfg(target_os = "linux")]
+use binary_logger::seqpacket::SeqpacketListener;
+
+const USAGE: &str = "Usage: blog-proxy [--listen HOST:PORT]... [--unix PATH]... [--seqpacket PATH]... [--ndjson] [--output FILE]... [--forward HOST:PORT]... [--tz ZONE] [--time-format FORMAT] [--time-digits N]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("blog-proxy: {}", message);
+    process::exit(2);
+}
+
+/// Removes a socket left by an earlier run, which would fail the bind.
+fn remove_stale_socket(path: &str) {
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn main() {
+    let mut listeners = Vec::new();
+    let mut format = OutputFormat::Text;
+    let mut render = RenderOptions::default();
+    let mut outputs = Vec::new();
+    let mut forwards = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                let addr = args.next().unwrap_or_else(|| usage());
+                let listener = TcpListener::bind(&addr).unwrap_or_else(|e| fail(format!("cannot listen on {}: {}", addr, e)));
+                listeners.push(Listener::Tcp(listener));
+            }
+            "--unix" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                remove_stale_socket(&path);
+                let listener = UnixListener::bind(&path).unwrap_or_else(|e| fail(format!("cannot listen on {}: {}", path, e)));
+                listeners.push(Listener::Unix(listener));
+            }
+            #[cfg(target_os = "linux")]
+            "--seqpacket" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                remove_stale_socket(&path);
+                let listener = SeqpacketListener::bind(&path).unwrap_or_else(|e| fail(format!("cannot listen on {}: {}", path, e)));
+                listeners.push(Listener::Seqpacket(listener));
+            }
+            "--ndjson" => format = OutputFormat::Ndjson,
+            "--output" => outputs.push(args.next().unwrap_or_else(|| usage())),
+            "--forward" => forwards.push(args.next().unwrap_or_else(|| usage())),
+            flag if RENDER_FLAGS.contains(&flag) => {
+                let value = args.next().unwrap_or_else(|| usage());
+                if let Err(e) = render.set_flag(flag, &value) {
+                    fail(e);
+                }
+            }
+            _ => usage(),
+        }
+    }
+    if listeners.is_empty() {
+        listeners = activated_listeners();
+        if listeners.is_empty() {
+            usage();
+        }
+    }
+
+    let mut proxy = DecodingProxy::new(format).with_render_options(render);
+    for path in &outputs {
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .unwrap_or_else(|e| fail(format!("cannot open {}: {}", path, e)));
+        proxy = proxy.with_destination(file);
+    }
+    for addr in &forwards {
+        let stream = TcpStream::connect(addr).unwrap_or_else(|e| fail(format!("cannot connect to {}: {}", addr, e)));
+        proxy = proxy.with_destination(stream);
+    }
+    if outputs.is_empty() && forwards.is_empty() {
+        proxy = proxy.with_destination(io::stdout());
+    }
+    let proxy = Arc::new(proxy.with_disconnect_hook(|producer, result| {
+        if let Err(e) = result {
+            eprintln!("blog-proxy: {}: {}", producer, e);
+        }
+    }));
+
+    eprintln!("blog-proxy: accepting producers on {} sockets", listeners.len());
+    let servers: Vec<_> = listeners.into_iter().map(|listener| {
+        let proxy = proxy.clone();
+        thread::spawn(move || proxy.serve(&listener))
+    }).collect();
+    for server in servers {
+        if let Ok(Err(e)) = server.join() {
+            fail(format!("cannot accept connections: {}", e));
+        }
+    }
+}
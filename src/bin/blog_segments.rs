@@ -0,0 +1,75 @@
+//! Lists the segments of binary logs (see `Logger::begin_segment`).
+//!
+//! Usage: `blog-segments [--tz ZONE] [--time-format FORMAT] [--time-digits N] FILE...`
+//!
+//! Prints a line per segment, in log order, with tab-separated fields: the
+//! time it began, the time it ended and its duration (`-` for a segment
+//! the log doesn't end), its number of records and its name. With several
+//! files, each line is prefixed with its file name. `--tz`,
+//! `--time-format` and `--time-digits` choose how times are rendered (see
+//! `render::RenderOptions`). `blog-grep --segment NAME` searches one
+//! segment. The exit status is 0 on success and 2 on errors.
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use binary_logger::LogReader;
+use binary_logger::analysis::list_segments;
+use binary_logger::compact::read_segment;
+use binary_logger::render::{RenderOptions, RENDER_FLAGS};
+
+const USAGE: &str = "Usage: blog-segments [--tz ZONE] [--time-format FORMAT] [--time-digits N] FILE...";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut render = RenderOptions::default();
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            flag if RENDER_FLAGS.contains(&flag) => {
+                let value = args.next().unwrap_or_else(|| usage());
+                if let Err(e) = render.set_flag(flag, &value) {
+                    eprintln!("blog-segments: {}", e);
+                    process::exit(2);
+                }
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        usage();
+    }
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut failed = false;
+    for path in &files {
+        let data = match read_segment(path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("blog-segments: {}: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        for segment in list_segments(&mut LogReader::new(&data)) {
+            let end = segment.end.map_or_else(|| "-".to_string(), |end| render.format_time(end));
+            let duration = segment.duration().map_or_else(|| "-".to_string(), |duration| format!("{:?}", duration));
+            let prefix = if files.len() > 1 { format!("{}:", path) } else { String::new() };
+            let line = format!("{}{}\t{}\t{}\t{}\t{}", prefix, render.format_time(segment.begin), end, duration, segment.records, segment.name);
+            if writeln!(out, "{}", line).is_err() {
+                // Closed pipe, e.g. `| head`
+                process::exit(0);
+            }
+        }
+    }
+    let _ = out.flush();
+
+    process::exit(if failed { 2 } else { 0 });
+}
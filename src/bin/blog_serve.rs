@@ -0,0 +1,103 @@
+//! Serves a web viewer for binary logs.
+//!
+//! Usage: `blog-serve [--addr HOST:PORT] [--tz ZONE] [--time-format FORMAT] [--time-digits N] <file-or-directory>...`
+//!
+//! Every file given, and every file in a directory given, is offered in the
+//! viewer. The viewer filters by time range, format and channel, searches the
+//! decoded text and can follow files that are still being written. The
+//! server listens on 127.0.0.1:8080 unless `--addr` says otherwise. Entry
+//! times are shown as RFC 3339 in UTC unless the render flags say otherwise
+//! (see `render::RenderOptions`). The JSON API it uses is described in
+//! `binary_logger::serve`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use tiny_http::{Header, Method, Server};
+use binary_logger::render::{RenderOptions, TimeFormat, RENDER_FLAGS};
+use binary_logger::serve::{LogServer, Response};
+
+const USAGE: &str = "Usage: blog-serve [--addr HOST:PORT] [--tz ZONE] [--time-format FORMAT] [--time-digits N] <file-or-directory>...";
+
+fn main() {
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut render = RenderOptions { time_format: TimeFormat::Rfc3339, ..RenderOptions::default() };
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--addr" => addr = args.next().unwrap_or_else(|| usage()),
+            flag if RENDER_FLAGS.contains(&flag) => {
+                let value = args.next().unwrap_or_else(|| usage());
+                if let Err(e) = render.set_flag(flag, &value) {
+                    eprintln!("blog-serve: {}", e);
+                    process::exit(2);
+                }
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        if let Err(e) = collect_files(&path, &mut files) {
+            eprintln!("blog-serve: cannot read {}: {}", path.display(), e);
+            process::exit(2);
+        }
+    }
+
+    let server = match Server::http(&addr) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("blog-serve: cannot listen on {}: {}", addr, e);
+            process::exit(2);
+        }
+    };
+    eprintln!("blog-serve: serving {} files on http://{}/", files.len(), addr);
+
+    let logs = LogServer::new(files).with_render_options(render);
+    for request in server.incoming_requests() {
+        let response = if *request.method() == Method::Get {
+            logs.handle(request.url())
+        } else {
+            Response { status: 405, content_type: "text/plain", body: b"method not allowed".to_vec() }
+        };
+        let header = Header::from_bytes("Content-Type", response.content_type)
+            .expect("content types are valid header values");
+        let reply = tiny_http::Response::from_data(response.body)
+            .with_status_code(response.status)
+            .with_header(header);
+        if let Err(e) = request.respond(reply) {
+            eprintln!("blog-serve: cannot send response: {}", e);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Adds `path`, or the files in it sorted by name if it is a directory.
+fn collect_files(path: &PathBuf, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        fs::metadata(path)?;
+        files.push(path.clone());
+        return Ok(());
+    }
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            found.push(entry.path());
+        }
+    }
+    found.sort();
+    files.extend(found);
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Verifies the integrity of a binary log and prints a JSON report.
+//!
+//! Usage: `blog-verify <file-or-directory>`
+//!
+//! A directory is treated as a series of segments of one log, read in file
+//! name order. The exit status is 0 if every check passed, 1 if any failed
+//! and 2 on usage or I/O errors.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use binary_logger::compact::read_segment;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: blog-verify <file-or-directory>");
+            process::exit(2);
+        }
+    };
+
+    let segments = match read_segments(Path::new(&path)) {
+        Ok(segments) => segments,
+        Err(e) => {
+            eprintln!("blog-verify: cannot read {}: {}", path, e);
+            process::exit(2);
+        }
+    };
+
+    let report = binary_logger::verify::verify_segments(&segments);
+    println!("{}", report.to_json());
+    if !report.passed() {
+        process::exit(1);
+    }
+}
+
+/// Reads a single file, or every file in a directory sorted by name.
+fn read_segments(path: &Path) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    if !path.is_dir() {
+        return Ok(vec![(path.display().to_string(), read_segment(path)?)]);
+    }
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+
+    paths.into_iter()
+        .map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            read_segment(&path).map(|data| (name, data))
+        })
+        .collect()
+}
@@ -1,13 +1,43 @@
 #![allow(dead_code)]
 
+//! Core implementation of the binary logging system.
+//! 
+//! This module provides the Logger struct and BufferHandler trait for writing
+//! extremely high-performance binary logs with minimal overhead.
+
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
-use std::panic::UnwindSafe;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe, UnwindSafe};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use sha2::{Digest, Sha256};
+use crate::arg_types::{ArgCapture, ArgKind, CaptureLimits, TypeTag, ARG_SCRATCH_SIZE};
+use crate::buffer_pool::{alloc_buffer, free_buffer, BufferPool, LogBuffer};
+use crate::clock_sync::{ClockOffset, CLOCK_OFFSET_SIZE};
+use crate::codec::{Codec, RawCodec, varint_len, write_varint};
+use crate::dedup::{DuplicateCache, DuplicateFilter};
 use crate::efficient_clock::TimestampConverter;
+use crate::features::FormatFeatures;
+use crate::health::{HealthFile, HealthReporter};
+use crate::snapshot::PublishedState;
+use crate::instrumentation::InternalEvent;
+use crate::level::{self, Level};
+use crate::sampling::KeySampler;
+use crate::schema::SchemaRegistry;
+use crate::string_registry::{self, is_wide_id, WIDE_ID};
+use crate::tags::Tags;
 
-/// Core implementation of the binary logging system.
-/// 
-/// This module provides the Logger struct and BufferHandler trait for writing
-/// extremely high-performance binary logs with minimal overhead.
+/// Process-wide sequence counter shared by every Logger with sequencing enabled.
+///
+/// Stamping records from this counter gives a total order across per-thread
+/// loggers even when their timestamps are identical.
+static GLOBAL_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Process whose `Logger::post_fork` last restarted `GLOBAL_SEQUENCE`, so
+/// several Loggers of a child restart it only once.
+static SEQUENCE_PID: AtomicU32 = AtomicU32::new(0);
 
 /// Handler for processing filled logging buffers.
 /// 
@@ -27,8 +57,7 @@ use crate::efficient_clock::TimestampConverter;
 /// struct FileHandler(RefCell<File>);
 /// 
 /// impl BufferHandler for FileHandler {
-///     fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-///         let data = unsafe { std::slice::from_raw_parts(buffer, size) };
+///     fn handle_buffer(&self, data: &[u8]) {
 ///         self.0.borrow_mut().write_all(data).unwrap();
 ///     }
 /// }
@@ -36,16 +65,71 @@ use crate::efficient_clock::TimestampConverter;
 pub trait BufferHandler: UnwindSafe {
     /// Process a filled buffer that has been switched out from the active logger.
     /// 
+    /// `data` holds the valid bytes of the buffer. The handler should
+    /// process them before returning, as the buffer may be reused
+    /// afterward.
+    fn handle_buffer(&self, data: &[u8]);
+
+    /// Process a filled buffer given as a pointer and a size, the raw form
+    /// of `handle_buffer`.
+    /// 
     /// # Safety
     /// 
-    /// The buffer pointer is valid for reading `size` bytes. The handler should
-    /// process this data before returning, as the buffer may be reused afterward.
+    /// `buffer` must be valid for reading `size` bytes until the call
+    /// returns. The handler should process this data before returning, as
+    /// the buffer may be reused afterward.
     /// 
     /// # Arguments
     /// 
     /// * `buffer` - Pointer to the start of the buffer data
     /// * `size` - Size of the valid data in the buffer
-    fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize);
+    ///
+    /// The Logger calls `handle_buffer`; the default of this method passes
+    /// the bytes to it. Override it where a pointer is what the sink takes.
+    unsafe fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
+        // The caller guarantees `buffer` is valid for `size` bytes
+        self.handle_buffer(unsafe { std::slice::from_raw_parts(buffer, size) });
+    }
+
+    /// Takes ownership of a filled buffer, and returns the buffer the
+    /// Logger writes to next.
+    ///
+    /// Loggers that own their buffers call this instead of
+    /// `handle_buffer`; those leasing from a `BufferPool`, and the metadata
+    /// buffers of the dictionary channel, only lend theirs. The default
+    /// hands the buffer to `handle_buffer` and gives it back. A sink that
+    /// processes buffers after returning, such as one writing on another
+    /// thread, keeps `buffer` instead of copying it and returns another
+    /// buffer of the same capacity, typically one it is done with (see
+    /// `handlers::BackgroundWriter`). The Logger replaces a buffer of
+    /// another capacity with a new one.
+    fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+        self.handle_buffer(&buffer);
+        buffer
+    }
+
+    /// Returns once the buffers handed over so far are on durable storage,
+    /// panicking if they can't be.
+    ///
+    /// The Logger calls this around critical sections (see
+    /// `Logger::mark_critical_begin`). The default returns at once, which
+    /// suits sinks that store each buffer before returning from
+    /// `handle_buffer`. A sink that writes later, or through a
+    /// cache, waits for its writes here and syncs them, as
+    /// `handlers::BackgroundWriter` does.
+    fn sync(&self) {}
+
+    /// Returns how many buffers handed over with `handle_owned_buffer` the
+    /// handler discarded instead of writing, such as by
+    /// `handlers::Backpressure::Drop`.
+    ///
+    /// The Logger checks it around each buffer it hands over, and counts
+    /// the records of a buffer discarded on the spot as dropped
+    /// (`LoggerStats::dropped_records`, and a `Drops` record with
+    /// instrumentation enabled). The default never discards.
+    fn buffers_dropped(&self) -> u64 {
+        0
+    }
 }
 
 /// A high-performance binary logger that writes log records in a compact binary format.
@@ -82,8 +166,7 @@ pub trait BufferHandler: UnwindSafe {
 /// # use std::cell::RefCell;
 /// # struct FileHandler(RefCell<File>);
 /// # impl BufferHandler for FileHandler {
-/// #     fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-/// #         let data = unsafe { std::slice::from_raw_parts(buffer, size) };
+/// #     fn handle_buffer(&self, data: &[u8]) {
 /// #         self.0.borrow_mut().write_all(data).unwrap();
 /// #     }
 /// # }
@@ -99,15 +182,174 @@ pub trait BufferHandler: UnwindSafe {
 /// logger.flush();
 /// ```
 pub struct Logger<const CAP: usize> {
-    buffer_1: *mut u8,
-    buffer_2: *mut u8,
     write_pos: usize,
     active_buffer: *mut u8,
     inactive_buffer: *mut u8,
+    buffer_pool: Option<Arc<BufferPool<CAP>>>,
     handler: Box<dyn BufferHandler>,
     clock: TimestampConverter,
+    codec: &'static dyn Codec,
+    format_features: FormatFeatures,
+    stream_header_pending: bool,
+    embedded_dictionary: bool,
+    embedded_strings: usize,
+    dictionary_channel: bool,
+    published_strings: usize,
+    metadata_header_pending: bool,
+    metadata_buffer: Vec<u8>,
+    audit_chain: bool,
+    chain_pending: bool,
+    last_buffer_hash: Option<[u8; CHAIN_HASH_SIZE]>,
+    sequence_enabled: bool,
+    instrumentation_enabled: bool,
+    error_backtraces: bool,
+    known_registry_len: usize,
+    pending_drops: u32,
+    dropped_records: u64,
+    health: Option<HealthReporter>,
+    published: Option<Arc<PublishedState>>,
+    header_compression: bool,
+    last_format_id: Option<u32>,
+    delta_timestamps: bool,
+    untimed: bool,
+    time_anchor_pending: bool,
+    type_tags: bool,
+    common_strings: bool,
+    level: Level,
+    suppression: Option<Suppression>,
+    duplicates: Option<DuplicateCache>,
+    duplicates_suppressed: u64,
+    max_payload_size: Option<usize>,
+    capture_limits: CaptureLimits,
+    tag_mask: Tags,
+    /// Critical sections entered and not yet left
+    critical_depth: u32,
+    /// Name of the segment begun and not yet ended
+    segment: Option<String>,
+    sampler: Option<KeySampler>,
+    sampled_out: u64,
+    records_written: u64,
+    /// `records_written` when the active buffer was started
+    buffer_first_record: u64,
+    buffers_flushed: u64,
+    bytes_flushed: u64,
+    pid: u32,
+    handler_panic_policy: HandlerPanicPolicy,
+    handler_status: HandlerStatus,
+    counts: Vec<(u32, u32)>,
+    schema: Option<Arc<SchemaRegistry>>,
+    strict_schema: bool,
+    retention: Vec<(u32, u32)>,
+    retention_pending: bool,
+    clock_offset: Option<ClockOffset>,
+    clock_offset_pending: bool,
+}
+
+/// Counters describing what a Logger has written, see `Logger::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoggerStats {
+    /// Records written, including metric and application records
+    pub records_written: u64,
+
+    /// Buffers handed to the BufferHandler
+    pub buffers_flushed: u64,
+
+    /// Bytes handed to the BufferHandler
+    pub bytes_flushed: u64,
+
+    /// Bytes of records written but not yet handed to the BufferHandler
+    pub bytes_pending: u64,
+
+    /// Records dropped instead of written
+    pub dropped_records: u64,
+
+    /// Records of keys left out by the sampler (see `Logger::set_sampler`)
+    pub sampled_out: u64,
+
+    /// Records left out as duplicates (see `Logger::set_duplicate_filter`)
+    pub duplicates_suppressed: u64,
+}
+
+/// What a Logger does when its BufferHandler panics, see
+/// `Logger::set_handler_panic_policy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HandlerPanicPolicy {
+    /// Resume the panic in the logging thread once the Logger is ready to
+    /// write again
+    #[default]
+    Propagate,
+
+    /// Stop calling the handler; later buffers are discarded
+    Disable,
+
+    /// Lose the buffer and call the handler again with the next one
+    Retry,
+}
+
+/// Failures of a Logger's BufferHandler, see `Logger::handler_status`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HandlerStatus {
+    /// Times the handler panicked
+    pub panics: u64,
+
+    /// Message of the last panic, if it had one
+    pub last_panic: Option<String>,
+
+    /// Whether the handler was disabled after a panic
+    /// (`HandlerPanicPolicy::Disable`)
+    pub disabled: bool,
+
+    /// Buffers whose records never reached the handler's sink: those it
+    /// panicked on and those discarded while disabled
+    pub buffers_lost: u64,
+}
+
+/// Why a Logger refused a record.
+/// 
+/// Writes fail with an `io::Error` carrying a `LogError`, which
+/// `LogError::of` gets back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LogError {
+    /// The record's payload is larger than the Logger takes: the maximum
+    /// set with `Logger::set_max_payload_size`, what fits in a buffer, or
+    /// what fits a 16-bit length field without the `long-records` format
+    /// feature.
+    PayloadTooLarge { size: usize, limit: usize },
+}
+
+impl LogError {
+    /// Returns the `LogError` an `io::Error` from a Logger carries, if any.
+    pub fn of(error: &io::Error) -> Option<&LogError> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::PayloadTooLarge { size, limit } =>
+                write!(f, "record payload of {} bytes over the limit of {} bytes", size, limit),
+        }
+    }
+}
+
+impl std::error::Error for LogError {}
+
+impl From<LogError> for io::Error {
+    fn from(error: LogError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
 }
 
+/// A Logger whose handler is known to be `Send`, so that it can be used
+/// from other threads than the one that created it.
+pub(crate) struct SendLogger<const CAP: usize>(pub(crate) Logger<CAP>);
+
+// The Logger owns its buffers or leases them from a Sync pool, and its
+// handler was required to be Send when it was created
+unsafe impl<const CAP: usize> Send for SendLogger<CAP> {}
+
 impl<const CAP: usize> Logger<CAP> {
     /// Creates a new binary logger with the specified buffer handler.
     /// 
@@ -127,8 +369,7 @@ impl<const CAP: usize> Logger<CAP> {
     /// # use std::cell::RefCell;
     /// # struct FileHandler(RefCell<File>);
     /// # impl BufferHandler for FileHandler {
-    /// #     fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-    /// #         let data = unsafe { std::slice::from_raw_parts(buffer, size) };
+    /// #     fn handle_buffer(&self, data: &[u8]) {
     /// #         self.0.borrow_mut().write_all(data).unwrap();
     /// #     }
     /// # }
@@ -136,202 +377,2414 @@ impl<const CAP: usize> Logger<CAP> {
     /// let logger = Logger::<1_000_000>::new(FileHandler(RefCell::new(file)));
     /// ```
     pub fn new(handler: impl BufferHandler + 'static) -> Self {
+        Self::with_codec(handler, &RawCodec)
+    }
+
+    /// Creates a new binary logger that encodes record arguments with `codec`.
+    /// 
+    /// The codec ID is written to the stream header ahead of the first record,
+    /// so LogReader picks the matching codec automatically. `new` uses
+    /// `RawCodec`, the fastest option.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `handler` - Implementation of BufferHandler that processes filled buffers
+    /// * `codec` - Encoding of the arguments in each record payload
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler};
+    /// # use binary_logger::codec::PostcardCodec;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let logger = Logger::<1_000_000>::with_codec(NullHandler, &PostcardCodec);
+    /// ```
+    pub fn with_codec(handler: impl BufferHandler + 'static, codec: &'static dyn Codec) -> Self {
         // Allocate aligned buffers
-        let buffer1 = unsafe { 
-            std::alloc::alloc(std::alloc::Layout::from_size_align(CAP, 8).unwrap()) 
-        };
-        let buffer2 = unsafe { 
-            std::alloc::alloc(std::alloc::Layout::from_size_align(CAP, 8).unwrap()) 
-        };
+        Self::with_buffers(handler, codec, alloc_buffer::<CAP>(), alloc_buffer::<CAP>(), None)
+    }
+
+    /// Creates a new binary logger that leases its buffer from `pool`,
+    /// encoding record arguments with `codec` as with `with_codec`.
+    /// 
+    /// The Logger holds a buffer only while it has records not yet handed
+    /// to its handler, and gives it back after each switch and flush. When
+    /// every buffer of the pool is leased, records are dropped and counted
+    /// in `LoggerStats::dropped_records`. See `buffer_pool`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler};
+    /// # use binary_logger::buffer_pool::BufferPool;
+    /// # use binary_logger::codec::RawCodec;
+    /// # use std::sync::Arc;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let pool = Arc::new(BufferPool::<65536>::new(8));
+    /// let logger = Logger::with_buffer_pool(NullHandler, &RawCodec, pool);
+    /// ```
+    pub fn with_buffer_pool(handler: impl BufferHandler + 'static, codec: &'static dyn Codec, pool: Arc<BufferPool<CAP>>) -> Self {
+        let null = std::ptr::null_mut();
+        Self::with_buffers(handler, codec, null, null, Some(pool))
+    }
 
+    /// Creates a Logger writing to the given buffers, which are null when
+    /// they are leased from `buffer_pool`.
+    fn with_buffers(handler: impl BufferHandler + 'static, codec: &'static dyn Codec, buffer1: *mut u8, buffer2: *mut u8, buffer_pool: Option<Arc<BufferPool<CAP>>>) -> Self {
+        const { assert!(CAP <= MAX_BUFFER_SIZE, "Logger buffers hold at most MAX_BUFFER_SIZE bytes") };
         Self {
-            buffer_1: buffer1,
-            buffer_2: buffer2,
             write_pos: BUFFER_HEADER_SIZE,
             active_buffer: buffer1,
             inactive_buffer: buffer2,
+            buffer_pool,
             handler: Box::new(handler),
             clock: TimestampConverter::new(),
+            codec,
+            format_features: FormatFeatures::SUPPORTED,
+            stream_header_pending: true,
+            embedded_dictionary: false,
+            embedded_strings: 0,
+            dictionary_channel: false,
+            published_strings: 0,
+            metadata_header_pending: false,
+            metadata_buffer: Vec::new(),
+            audit_chain: false,
+            chain_pending: false,
+            last_buffer_hash: None,
+            sequence_enabled: false,
+            instrumentation_enabled: false,
+            error_backtraces: false,
+            known_registry_len: 0,
+            pending_drops: 0,
+            dropped_records: 0,
+            health: None,
+            published: None,
+            header_compression: false,
+            last_format_id: None,
+            delta_timestamps: false,
+            untimed: false,
+            time_anchor_pending: true,
+            type_tags: true,
+            common_strings: false,
+            level: Level::Trace,
+            suppression: None,
+            duplicates: None,
+            duplicates_suppressed: 0,
+            max_payload_size: None,
+            capture_limits: CaptureLimits::new(),
+            tag_mask: Tags::ALL,
+            critical_depth: 0,
+            segment: None,
+            sampler: None,
+            sampled_out: 0,
+            records_written: 0,
+            buffer_first_record: 0,
+            buffers_flushed: 0,
+            bytes_flushed: 0,
+            pid: std::process::id(),
+            handler_panic_policy: HandlerPanicPolicy::Propagate,
+            handler_status: HandlerStatus::default(),
+            counts: Vec::new(),
+            schema: None,
+            strict_schema: false,
+            retention: Vec::new(),
+            retention_pending: false,
+            clock_offset: None,
+            clock_offset_pending: false,
         }
     }
 
-    /// Writes a raw log record to the buffer.
+    /// Returns the codec used to encode record arguments.
+    pub fn codec(&self) -> &'static dyn Codec {
+        self.codec
+    }
+
+    /// Enables or disables stamping records with the global sequence number.
     /// 
-    /// This is a low-level method that handles the binary format writing.
-    /// In most cases, you should use the `log_record!` macro instead, which
-    /// handles format string registration and parameter serialization.
+    /// When enabled, every record carries a 64-bit value taken from a single
+    /// process-wide counter shared by all loggers. Per-thread logs can then be
+    /// merged into a total order by `(timestamp, sequence)` with `LogMerger`,
+    /// even when several threads log within the same microsecond.
     /// 
-    /// # Arguments
+    /// The cost is one relaxed atomic increment and 8 bytes per record.
     /// 
-    /// * `format_id` - The ID of the format string from the string registry
-    /// * `payload` - The raw binary payload of the log record
+    /// # Examples
     /// 
-    /// # Returns
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_global_sequence(true);
+    /// log_record!(logger, "ordered across threads", ).unwrap();
+    /// ```
+    pub fn set_global_sequence(&mut self, enabled: bool) {
+        self.sequence_enabled = enabled && self.format_features.contains(FormatFeatures::SEQUENCE);
+    }
+
+    /// Limits the stream format features the Logger writes to `features`.
     /// 
-    /// A Result indicating success or an IO error
+    /// Features outside the set are never written, whatever else is
+    /// configured: header encodings (sequence numbers, header compression,
+    /// delta timestamps, audit chain, embedded dictionary) stay disabled,
+    /// levels, channels, truncation marks and tags are left out of records,
+    /// and metric and application records fail with `Unsupported`. This
+    /// produces streams for readers that only know the features in the set.
     /// 
-    /// # Binary Format
+    /// The stream header declares this set as the features the stream may
+    /// use, so once the first record is written the set can only shrink.
+    /// All features this build supports are enabled by default.
     /// 
-    /// Format: `[type(1) | relative_ts(2) | format_id(2) | payload_len(2) | payload(N)]`
+    /// # Examples
     /// 
-    /// Where type:
-    /// - 0: Record with relative timestamp
-    /// - 1: Record with base timestamp reset
-    pub fn write(&mut self, format_id: u16, payload: &[u8]) -> io::Result<()> {
-        let (rel_ts, is_base) = self.clock.get_relative_timestamp();
-        let record_size = 1 + 2 + 2 + 2 + payload.len();  // type + ts + format_id + payload_len + payload
-
-        // Check if we need to switch buffers
-        if self.write_pos + record_size > CAP {
-            // Assert that we haven't filled the active buffer while handler was processing
-            assert!(self.write_pos < CAP, "Buffer full and handler hasn't completed!");
-            self.switch_buffers();
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # use binary_logger::features::FormatFeatures;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_format_features(FormatFeatures::LEVELS);
+    /// logger.set_global_sequence(true);
+    /// log_record!(logger, "Readable by any release", ).unwrap();
+    /// assert_eq!(logger.format_features(), FormatFeatures::LEVELS);
+    /// ```
+    pub fn set_format_features(&mut self, features: FormatFeatures) {
+        let declared = if self.stream_header_pending { FormatFeatures::SUPPORTED } else { self.format_features };
+        self.format_features = features & declared;
+        let allowed = self.format_features;
+        self.sequence_enabled &= allowed.contains(FormatFeatures::SEQUENCE);
+        self.header_compression &= allowed.contains(FormatFeatures::HEADER_COMPRESSION);
+        self.delta_timestamps &= allowed.contains(FormatFeatures::DELTA_TIME);
+        self.untimed &= allowed.contains(FormatFeatures::UNTIMED);
+        self.type_tags &= allowed.contains(FormatFeatures::TYPE_TAGS);
+        self.common_strings &= allowed.contains(FormatFeatures::COMMON_STRINGS);
+        self.audit_chain &= allowed.contains(FormatFeatures::AUDIT_CHAIN);
+        self.chain_pending &= self.audit_chain;
+        self.embedded_dictionary &= allowed.contains(FormatFeatures::DICTIONARY);
+        self.dictionary_channel &= allowed.contains(FormatFeatures::DICTIONARY_CHANNEL);
+        self.metadata_header_pending &= self.dictionary_channel;
+        if !allowed.contains(FormatFeatures::RETENTION) {
+            self.retention.clear();
+            self.retention_pending = false;
         }
+        if !allowed.contains(FormatFeatures::CLOCK_OFFSETS) {
+            self.clock_offset = None;
+            self.clock_offset_pending = false;
+        }
+    }
 
-        unsafe {
-            // Write record type
-            *self.active_buffer.add(self.write_pos) = if is_base { 1 } else { 0 };
-            self.write_pos += 1;
+    /// Returns the stream format features the Logger may write, as
+    /// declared in its stream header.
+    pub fn format_features(&self) -> FormatFeatures {
+        self.format_features
+    }
 
-            // Ensure alignment for u16 writes
-            if self.write_pos % 2 != 0 {
-                self.write_pos += 1;
-            }
+    /// Enables or disables wrap epochs for relative timestamps.
+    /// 
+    /// By default, a record written more than 65535us after the current base
+    /// carries a new 8-byte base timestamp. With wrap epochs enabled the
+    /// 16-bit relative timestamp wraps instead, and LogReader counts the wraps
+    /// to reconstruct the time. A new base is still written after a gap of a
+    /// full epoch (65536us) without records, since a reader could not tell
+    /// how many wraps it spans, and periodically as an anchor.
+    /// 
+    /// This saves the base record in streams that log at least every 65ms.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_wrap_epochs(true);
+    /// log_record!(logger, "Steady stream {}", 1).unwrap();
+    /// ```
+    pub fn set_wrap_epochs(&mut self, enabled: bool) {
+        self.clock.set_wrap_epochs(enabled);
+    }
 
-            // Write timestamp
-            *(self.active_buffer.add(self.write_pos) as *mut u16) = rel_ts;
-            self.write_pos += 2;
+    /// Enables or disables audit mode with a tamper-evident hash chain.
+    /// 
+    /// In audit mode every buffer starts with a chain record holding the
+    /// SHA-256 of the complete previous buffer (all zeros for the first
+    /// buffer of the stream). Modifying, removing or reordering any buffer
+    /// breaks the chain, which `audit::verify_chain` detects. Removing
+    /// buffers from the end can only be detected by comparing the final hash
+    /// with `chain_head()` recorded elsewhere.
+    /// 
+    /// Enable it before the first write; enabled later, the chain starts
+    /// with the next buffer. Each buffer switch then hashes the outgoing
+    /// buffer on the logging thread.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_audit_chain(true);
+    /// log_record!(logger, "User {} changed permissions", 42).unwrap();
+    /// logger.flush();
+    /// let head = logger.chain_head().unwrap();
+    /// ```
+    pub fn set_audit_chain(&mut self, enabled: bool) {
+        self.audit_chain = enabled && self.format_features.contains(FormatFeatures::AUDIT_CHAIN);
+        self.chain_pending = self.audit_chain && self.stream_header_pending;
+    }
 
-            // Write format ID
-            *(self.active_buffer.add(self.write_pos) as *mut u16) = format_id;
-            self.write_pos += 2;
-            
-            // Write payload length
-            *(self.active_buffer.add(self.write_pos) as *mut u16) = payload.len() as u16;
-            self.write_pos += 2;
+    /// Enables writing the string registry into the log.
+    /// 
+    /// Before its next record the Logger writes every string registered so
+    /// far, including the format strings and channel names of all logging
+    /// call sites in the program (see `string_registry`), as dictionary
+    /// records. Readers resolve format IDs through them first, so the log
+    /// can be read in another process or with another build. Strings
+    /// registered at runtime after that, such as interned arguments, are
+    /// added in further dictionary records ahead of the next record.
+    /// Strings already in the log aren't written again when it is
+    /// re-enabled.
+    /// 
+    /// Enable it before the first write to have the dictionary right after
+    /// the stream header.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<65536>::new(NullHandler);
+    /// logger.set_embedded_dictionary(true);
+    /// log_record!(logger, "Service started on port {}", 8080).unwrap();
+    /// ```
+    pub fn set_embedded_dictionary(&mut self, enabled: bool) {
+        self.embedded_dictionary = enabled && self.format_features.contains(FormatFeatures::DICTIONARY);
+    }
 
-            // Write payload
-            std::ptr::copy_nonoverlapping(
-                payload.as_ptr(),
-                self.active_buffer.add(self.write_pos),
-                payload.len()
-            );
-            self.write_pos += payload.len();
+    /// Enables publishing the string registry in metadata buffers, a
+    /// dictionary channel interleaved with the data buffers.
+    /// 
+    /// The embedded dictionary (`set_embedded_dictionary`) sits in the data
+    /// buffers, so a reader that starts in the middle of a stream, such as
+    /// a live tail or a client connecting to a network stream, misses it.
+    /// With the dictionary channel the Logger instead hands the strings
+    /// registered since its last metadata buffer to the BufferHandler as
+    /// soon as a write notices them, in a small buffer of its own, and
+    /// always before the data buffer whose records use them. Data buffers
+    /// keep filling and flushing as before. The first metadata buffer also
+    /// carries a copy of the stream header, and every data buffer starts a
+    /// new time base, so a reader holding the metadata buffers seen so far
+    /// can decode the stream from any data buffer (see `LogReader::attach`
+    /// and `log_reader::is_metadata_buffer`).
+    /// 
+    /// Metadata buffers count as flushed buffers in `stats`, and are left
+    /// out of the audit chain. The channel replaces the embedded dictionary.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<65536>::new(NullHandler);
+    /// logger.set_dictionary_channel(true);
+    /// // Hands a metadata buffer to the handler right away
+    /// log_record!(logger, "Service started on port {}", 8080).unwrap();
+    /// assert_eq!(logger.stats().buffers_flushed, 1);
+    /// ```
+    pub fn set_dictionary_channel(&mut self, enabled: bool) {
+        self.dictionary_channel = enabled && self.format_features.contains(FormatFeatures::DICTIONARY_CHANNEL);
+        self.metadata_header_pending = self.dictionary_channel && self.published_strings == 0;
+        if self.dictionary_channel {
+            self.embedded_dictionary = false;
+            // Start the next data buffer with a base of its own
+            if self.write_pos == BUFFER_HEADER_SIZE {
+                self.clock.reset();
+            }
         }
-
-        Ok(())
     }
 
-    /// Flushes the current buffer, ensuring all data is processed.
+    /// Sets how long the records of a channel may be kept, or with `None`
+    /// removes the channel's time to live.
     /// 
-    /// This method forces the current buffer to be switched and processed
-    /// by the handler, even if it's not full. This is useful when you need
-    /// to ensure all logs are immediately visible.
+    /// Records don't carry their retention themselves: every data buffer
+    /// starts with a retention table (`RECORD_TYPE_RETENTION`) giving the
+    /// time to live of each channel that has one, and a change writes a new
+    /// table that applies to the records after it. `retention::expire` and
+    /// the `blog-expire` tool rewrite logs without the entries whose time to
+    /// live has passed, so privacy-mandated deletion needs no decoding of
+    /// the entries. `channel` is the ID of the channel name (see
+    /// `string_registry::register_string`), or 0 for records logged without
+    /// a channel. Times to live are stored in whole seconds, at most
+    /// `u32::MAX`.
+    /// 
+    /// Ignored without `FormatFeatures::RETENTION` in the Logger's format
+    /// features, and for channel IDs too wide for retention tables (see
+    /// `FormatFeatures::WIDE_IDS`).
     /// 
     /// # Examples
     /// 
     /// ```
     /// # use binary_logger::{Logger, BufferHandler, log_record};
-    /// # use std::fs::File;
-    /// # use std::io::Write;
-    /// # use std::cell::RefCell;
-    /// # struct FileHandler(RefCell<File>);
-    /// # impl BufferHandler for FileHandler {
-    /// #     fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-    /// #         let data = unsafe { std::slice::from_raw_parts(buffer, size) };
-    /// #         self.0.borrow_mut().write_all(data).unwrap();
-    /// #     }
+    /// # use binary_logger::string_registry::register_string;
+    /// # use std::time::Duration;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
     /// # }
-    /// # let file = File::create("log.bin").unwrap();
-    /// # let mut logger = Logger::<1_000_000>::new(FileHandler(RefCell::new(file)));
-    /// log_record!(logger, "Critical operation starting", );
-    /// // Ensure log is written immediately
-    /// logger.flush();
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_channel_retention(register_string("pii"), Some(Duration::from_secs(30 * 86400)));
+    /// log_record!(logger, channel: "pii", "User {} signed in from {}", 42, "10.0.0.7").unwrap();
     /// ```
-    pub fn flush(&mut self) {
-        if self.write_pos > BUFFER_HEADER_SIZE {
-            self.switch_buffers();
+    pub fn set_channel_retention(&mut self, channel: u32, ttl: Option<Duration>) {
+        if !self.format_features.contains(FormatFeatures::RETENTION) || is_wide_id(channel) {
+            return;
+        }
+        let ttl = ttl.map(|ttl| ttl.as_secs().min(u32::MAX as u64) as u32);
+        let existing = self.retention.binary_search_by_key(&channel, |&(channel, _)| channel);
+        match (existing, ttl) {
+            (Ok(i), Some(ttl)) if self.retention[i].1 == ttl => return,
+            (Ok(i), Some(ttl)) => self.retention[i].1 = ttl,
+            (Ok(i), None) => { self.retention.remove(i); }
+            (Err(i), Some(ttl)) => self.retention.insert(i, (channel, ttl)),
+            (Err(_), None) => return,
         }
+        // An empty table ends the previous one
+        self.retention_pending = true;
     }
 
-    /// Switches the active and inactive buffers, and processes the filled buffer.
+    /// Records how far the host's clock is from the reference clock, for
+    /// aligning logs of several hosts (see `clock_sync`).
     /// 
-    /// This internal method handles the double-buffering mechanism. When the active
-    /// buffer is full or explicitly flushed, this method:
-    /// 1. Writes the buffer size header to the filled buffer
-    /// 2. Swaps the active and inactive buffers
-    /// 3. Calls the handler to process the filled buffer
-    /// 4. Resets the write position for the new active buffer
-    fn switch_buffers(&mut self) {
-        // Write buffer length at start
-        unsafe {
-            *(self.active_buffer as *mut u64) = self.write_pos as u64;
+    /// The offset is written in a clock offset record
+    /// (`RECORD_TYPE_CLOCK_OFFSET`) that applies to the records after it,
+    /// and again at the start of every data buffer. Set it again whenever
+    /// the time daemon reports a new measurement. Ignored without
+    /// `FormatFeatures::CLOCK_OFFSETS` in the Logger's format features.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # use binary_logger::clock_sync::{ClockOffset, ClockSource};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// // chrony says the clock is 250 us fast, with a root dispersion of 1 ms
+    /// logger.set_clock_offset(ClockOffset::new(-250, 1000, ClockSource::Ntp));
+    /// log_record!(logger, "Replica {} promoted", 3).unwrap();
+    /// ```
+    pub fn set_clock_offset(&mut self, offset: ClockOffset) {
+        if !self.format_features.contains(FormatFeatures::CLOCK_OFFSETS) || self.clock_offset == Some(offset) {
+            return;
         }
+        self.clock_offset = Some(offset);
+        self.clock_offset_pending = true;
+    }
 
-        // Swap buffers
-        std::mem::swap(&mut self.active_buffer, &mut self.inactive_buffer);
-        let filled_buffer = self.inactive_buffer;
-        let filled_size = self.write_pos;
-        self.write_pos = BUFFER_HEADER_SIZE;
+    /// Returns the clock offset set last, see `set_clock_offset`.
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        self.clock_offset
+    }
 
-        // Call handler with filled buffer
-        self.handler.handle_switched_out_buffer(filled_buffer, filled_size);
+    /// Returns the time to live of a channel, see `set_channel_retention`.
+    pub fn channel_retention(&self, channel: u32) -> Option<Duration> {
+        self.retention.iter().find(|&&(id, _)| id == channel).map(|&(_, ttl)| Duration::from_secs(ttl as u64))
     }
-}
 
-impl<const CAP: usize> Drop for Logger<CAP> {
-    fn drop(&mut self) {
-        // Ensure last buffer is written
-        if self.write_pos > BUFFER_HEADER_SIZE {
-            self.switch_buffers();
-        }
+    /// Returns the SHA-256 of the last buffer handed to the BufferHandler in
+    /// audit mode.
+    /// 
+    /// Storing this value outside the log (for example in a database or a
+    /// signed manifest) lets a verifier detect buffers removed from the end.
+    pub fn chain_head(&self) -> Option<[u8; 32]> {
+        self.last_buffer_hash
+    }
 
-        // Clean up buffers
-        unsafe {
-            std::alloc::dealloc(
-                self.buffer_1,
-                std::alloc::Layout::from_size_align(CAP, 8).unwrap()
-            );
-            std::alloc::dealloc(
-                self.buffer_2,
-                std::alloc::Layout::from_size_align(CAP, 8).unwrap()
-            );
+    /// Enables or disables self-instrumentation records.
+    /// 
+    /// When enabled, the logger writes `InternalEvent` records into its own
+    /// stream: the start and end of every buffer switch (with the time spent
+    /// in the handler), dropped records, and growth of the string registry.
+    /// They use reserved format IDs and read back like any other entry, so the
+    /// logger's own behavior shows up in the same timeline as the application.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_instrumentation(true);
+    /// log_record!(logger, "Application event {}", 1).unwrap();
+    /// ```
+    pub fn set_instrumentation(&mut self, enabled: bool) {
+        self.instrumentation_enabled = enabled;
+    }
+
+    /// Enables or disables backtraces on `log_error!` records.
+    /// 
+    /// When enabled, every `log_error!` record carries a
+    /// `backtrace::LogBacktrace` of the call site, which readers render
+    /// after the message. Capturing one resolves symbols and takes
+    /// milliseconds, so this suits errors that are rare but hard to
+    /// diagnose. Disabled, the record carries an empty backtrace of one byte.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_error};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_error_backtraces(true);
+    /// if let Err(err) = std::fs::read("/no/such/file") {
+    ///     log_error!(logger, "Loading config failed: {}", err).unwrap();
+    /// }
+    /// ```
+    pub fn set_error_backtraces(&mut self, enabled: bool) {
+        self.error_backtraces = enabled;
+    }
+
+    /// Returns whether `log_error!` captures backtraces.
+    #[doc(hidden)]
+    pub fn error_backtraces(&self) -> bool {
+        self.error_backtraces
+    }
+
+    /// Counts records that were dropped instead of written.
+    /// 
+    /// With instrumentation enabled, the count is reported in a `Drops`
+    /// record on the next write.
+    pub(crate) fn note_dropped(&mut self, count: u32) {
+        self.pending_drops = self.pending_drops.saturating_add(count);
+        self.dropped_records += count as u64;
+        if let Some(health) = &self.health {
+            health.dropped(self.dropped_records);
         }
     }
-}
 
-/// Logs a record with the given format string and arguments.
-/// 
-/// This macro is the primary interface for logging. It:
-/// 1. Automatically registers and deduplicates format strings
-/// 2. Efficiently serializes arguments to binary format
-/// 3. Writes the serialized record to the logger
-/// 
-/// # Arguments
-/// 
-/// * `logger` - The Logger instance to write to
-/// * `fmt` - A format string literal, using `{}` placeholders like in `println!`
-/// * `args...` - Zero or more arguments corresponding to placeholders
-/// 
-/// # Returns
-/// 
-/// IO Result for the logging operation
-/// 
-/// # Examples
-/// 
-/// ```
-/// # use binary_logger::{Logger, BufferHandler, log_record};
-/// # use std::fs::File;
-/// # use std::io::Write;
-/// # use std::cell::RefCell;
-/// # struct FileHandler(RefCell<File>);
-/// # impl BufferHandler for FileHandler {
-/// #     fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-/// #         let data = unsafe { std::slice::from_raw_parts(buffer, size) };
-/// #         self.0.borrow_mut().write_all(data).unwrap();
-/// #     }
-/// # }
-/// # let file = File::create("log.bin").unwrap();
+    /// Enables or disables header compression.
+    /// 
+    /// When enabled, a record with the same format ID as the previous record
+    /// in its buffer sets `FLAG_SAME_FORMAT` and leaves the format ID out of
+    /// its header, saving 2 bytes per record for call sites that log many
+    /// times in a row. Each buffer starts with a full header, so buffers
+    /// stay independently readable. Readers from before the flag existed
+    /// can't decode such streams, which is why it is off by default.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_header_compression(true);
+    /// for i in 0..10 {
+    ///     log_record!(logger, "Processed item {}", i).unwrap();
+    /// }
+    /// ```
+    pub fn set_header_compression(&mut self, enabled: bool) {
+        self.header_compression = enabled && self.format_features.contains(FormatFeatures::HEADER_COMPRESSION);
+    }
+
+    /// Enables or disables delta timestamps.
+    /// 
+    /// By default a record stores its time as 16 bits of microseconds since
+    /// the current base, and a new 8-byte base is written whenever that
+    /// overflows. With delta timestamps a record sets `FLAG_DELTA_TIME` and
+    /// stores the microseconds since the previous record as a varint
+    /// instead, which is a single byte for records less than 128us apart.
+    /// Gaps of any length fit, so apart from a periodic anchor a new base is
+    /// only written at the start of each buffer, keeping buffers
+    /// independently readable. Readers from before the flag existed can't
+    /// decode such streams, which is why it is off by default.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_delta_timestamps(true);
+    /// for i in 0..10 {
+    ///     log_record!(logger, "Tick {}", i).unwrap();
+    /// }
+    /// ```
+    pub fn set_delta_timestamps(&mut self, enabled: bool) {
+        self.delta_timestamps = enabled && self.format_features.contains(FormatFeatures::DELTA_TIME);
+    }
+
+    /// Enables or disables record timestamps.
+    /// 
+    /// Timestamps are on by default. With them off, log records are written
+    /// as `RECORD_TYPE_UNTIMED`, whose header has no time field: the Logger
+    /// skips the clock read and each record is 2 bytes shorter, for captures
+    /// where the order of events matters and their times don't. Readers
+    /// give an untimed record the time of the last timed record before it,
+    /// and flag it (`LogEntry::untimed`); global sequence numbers
+    /// (`set_global_sequence`) still order records across loggers.
+    /// 
+    /// The first log record of each buffer is still timed, so every buffer
+    /// tells roughly when it was written. Tagged records, metrics,
+    /// application records and count tables always are. Untimed records
+    /// carry no type tags (see `set_type_tags`). Without
+    /// `FormatFeatures::UNTIMED` in the logger's format features,
+    /// timestamps stay on.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_timestamps(false);
+    /// for i in 0..10 {
+    ///     log_record!(logger, "Packet {} received", i).unwrap();
+    /// }
+    /// ```
+    pub fn set_timestamps(&mut self, enabled: bool) {
+        self.untimed = !enabled && self.format_features.contains(FormatFeatures::UNTIMED);
+    }
+
+    /// Enables or disables a type tag per argument in log records (on by
+    /// default).
+    /// 
+    /// Without tags, a reader in another process only has the size of each
+    /// argument to go by, and guesses: 4 bytes are read as an `i32` and 8
+    /// as an `f64`, so an `f32`, a `u64` or a short string come back wrong.
+    /// With tags on, log records are `RECORD_TYPE_TYPED` records (or
+    /// `RECORD_TYPE_TAGGED_TYPED` ones for `log_tagged!`), whose payload
+    /// starts with one byte naming the type of each argument (see
+    /// `arg_types::TypeTag`), and readers decode every argument as the type
+    /// it was logged as. Types without a tag of their own, and the raw
+    /// arguments of `write_args`, `write_args_on` and `write_args_at`, are
+    /// still read by size; `write_typed_args` names their types.
+    /// 
+    /// Tags cost a byte per argument plus one. Untimed log records (see
+    /// `set_timestamps`) are written without them. Without `FormatFeatures::TYPE_TAGS` in
+    /// the logger's format features, which the stream header declares,
+    /// tags stay off.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// // Saves a byte per argument, for readers that know the format
+    /// logger.set_type_tags(false);
+    /// log_record!(logger, "Ratio {} of {} bytes", 0.5f32, u64::MAX).unwrap();
+    /// ```
+    pub fn set_type_tags(&mut self, enabled: bool) {
+        self.type_tags = enabled && self.format_features.contains(FormatFeatures::TYPE_TAGS);
+    }
+
+    /// Enables or disables the built-in common strings (off by default).
+    /// 
+    /// With them on, `log_record!` and `log_record_at!` store string
+    /// arguments equal to one of `common_strings::COMMON_STRINGS`, such as
+    /// `"GET"`, `"404"` or `"false"`, in two bytes, and string literals in
+    /// the table as their reserved ID, without registering them. Any reader
+    /// reads them back as the string, with no registry or dictionary.
+    /// Without `FormatFeatures::COMMON_STRINGS` in the logger's format
+    /// features, they stay off.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// # let method = String::from("POST");
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_common_strings(true);
+    /// log_record!(logger, "{} /orders answered {}", method, "201").unwrap();
+    /// ```
+    pub fn set_common_strings(&mut self, enabled: bool) {
+        self.common_strings = enabled && self.format_features.contains(FormatFeatures::COMMON_STRINGS);
+    }
+
+    /// Returns whether the built-in common strings are on, see
+    /// `set_common_strings`.
+    pub fn common_strings(&self) -> bool {
+        self.common_strings
+    }
+
+    /// Takes record timestamps, and the durations the Logger measures, from
+    /// `source` instead of the hardware counter, or from the counter again
+    /// with `None`.
+    /// 
+    /// With a virtual clock such as `sim::VirtualClock` (`sim` feature), a
+    /// scenario writes the same bytes every time it is replayed, base
+    /// timestamps and all. The next record starts a new time base.
+    pub fn set_clock_source(&mut self, source: Option<Arc<dyn crate::efficient_clock::ClockSource>>) {
+        self.clock.set_clock_source(source);
+    }
+
+    /// Keeps a health file for external watchdogs up to date, or stops
+    /// with `None`.
+    /// 
+    /// The file is written right away, then every heartbeat interval by a
+    /// thread the Logger starts, idle or not. It reports the last flush,
+    /// bytes not yet handed to the handler, dropped records, the handler's
+    /// last duration and since when a call to it hasn't returned; see
+    /// `health` for the format. The logging thread only updates counters,
+    /// so slow storage for the file doesn't slow down logging.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # use binary_logger::health::{HealthFile, HealthStatus};
+    /// # use std::time::Duration;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let path = std::env::temp_dir().join("binary_logger_doc.health");
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_health_file(Some(HealthFile::new(&path, Duration::from_secs(1))));
+    /// log_record!(logger, "Service {} started", 1).unwrap();
+    /// logger.flush();
+    /// // Or wait for the next heartbeat
+    /// logger.heartbeat();
+    /// assert_eq!(HealthStatus::read(&path).unwrap().buffers_flushed, 1);
+    /// ```
+    pub fn set_health_file(&mut self, file: Option<HealthFile>) {
+        self.health = file.map(HealthReporter::new);
+        self.heartbeat();
+    }
+
+    /// Writes the health file now, if one is set.
+    pub fn heartbeat(&mut self) {
+        if let Some(health) = &self.health {
+            health.report(self.write_pos - BUFFER_HEADER_SIZE, self.dropped_records);
+        }
+    }
+
+    /// Publishes the Logger's state to `snapshot::debug_snapshot()` under
+    /// `name`, or stops with `None`.
+    /// 
+    /// The Logger copies its counters to the snapshot after every record and
+    /// marks when its BufferHandler is called and returns, so a snapshot
+    /// taken from another thread shows a handler that never returns. The
+    /// copy costs a few relaxed stores per record.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler};
+    /// # use binary_logger::snapshot::debug_snapshot;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_debug_name(Some("orders"));
+    /// assert!(debug_snapshot().loggers.iter().any(|l| l.name == "orders"));
+    /// ```
+    pub fn set_debug_name(&mut self, name: Option<&str>) {
+        self.published = name.map(|name| PublishedState::register(name, CAP));
+        if let Some(published) = &self.published {
+            published.update(&self.stats());
+        }
+    }
+
+    /// Sets the least severe level written by `log_record_at!`.
+    /// 
+    /// Records below `level` are skipped by `write_args_at` without
+    /// touching the buffer, unless a process-wide boost (see the `level`
+    /// module) lets them through. Records without a level, from
+    /// `log_record!`, are always written. The default, `Level::Trace`,
+    /// writes everything.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record_at};
+    /// # use binary_logger::level::Level;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_level(Level::Info);
+    /// log_record_at!(logger, Level::Debug, "Skipped {}", 1).unwrap();
+    /// log_record_at!(logger, Level::Warn, "Written {}", 2).unwrap();
+    /// assert_eq!(logger.stats().records_written, 1);
+    /// ```
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+
+    /// Returns the least severe level written, see `set_level`.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns whether records at `level` are written: at or above the
+    /// Logger's level, or let through by an override (see `level`).
+    /// 
+    /// `log_record_at!` checks this before capturing arguments.
+    #[inline]
+    pub fn level_enabled(&self, level: Level) -> bool {
+        level >= self.level || level::boosted(level)
+    }
+
+    /// Returns the Logger, for the logging macros, which bind their logger
+    /// expression with it once, whether it names a Logger or a mutable
+    /// reference to one.
+    #[doc(hidden)]
+    #[inline]
+    pub fn macro_target(&mut self) -> &mut Self {
+        self
+    }
+
+    /// Notes a record skipped by `log_record_at!` for its level, for the
+    /// suppression summaries.
+    #[doc(hidden)]
+    #[inline]
+    pub fn skip_level(&mut self, level: Level, format_id: u32) {
+        if let Some(suppression) = &mut self.suppression {
+            suppression.count(level, format_id, self.clock.instant_now());
+        }
+    }
+
+    /// Sets the argument schemas that records are checked against before
+    /// they are written, or with `None` stops checking.
+    /// 
+    /// A record whose format string has a schema in `registry` and whose
+    /// arguments differ from it in number or type isn't written, and the
+    /// write fails with `InvalidInput` (see `schema`). The check runs in
+    /// debug builds, and in release builds only with `set_strict_schema`.
+    /// Several Loggers can share one registry.
+    pub fn set_schema_registry(&mut self, registry: Option<Arc<SchemaRegistry>>) {
+        self.schema = registry;
+    }
+
+    /// Checks records against the schema registry in release builds too.
+    pub fn set_strict_schema(&mut self, strict: bool) {
+        self.strict_schema = strict;
+    }
+
+    /// Enables or disables summary records for records skipped by level.
+    /// 
+    /// With an interval set, the logger counts the records `write_args_at`
+    /// skips, per format and level, and at most once per interval writes an
+    /// `InternalEvent::Suppressed` record for each, such as
+    /// `DEBUG [binary_logger] suppressed 1234 records from format 17 in last 5000 ms`.
+    /// Readers can then tell records left out on purpose from records
+    /// lost. The counts are also written on `flush` and when the logger is
+    /// dropped. Counting costs a hash map update per skipped record.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record_at};
+    /// # use binary_logger::level::Level;
+    /// # use std::time::Duration;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_level(Level::Info);
+    /// logger.set_suppression_summaries(Some(Duration::from_secs(5)));
+    /// for i in 0..100 {
+    ///     log_record_at!(logger, Level::Debug, "Polled {}", i).unwrap();
+    /// }
+    /// logger.flush();
+    /// ```
+    pub fn set_suppression_summaries(&mut self, interval: Option<Duration>) {
+        let now = self.clock.instant_now();
+        self.suppression = interval.map(|interval| Suppression {
+            interval,
+            since: now,
+            counts: HashMap::new(),
+        });
+    }
+
+    /// Leaves out records identical to one written shortly before, or with
+    /// `None` writes every record.
+    /// 
+    /// Within the window `filter` sets for its format, a record with the
+    /// format ID, channel and arguments of a written record is only
+    /// counted. When the window closes, an `InternalEvent::DuplicatesSuppressed`
+    /// record reports the count, such as
+    /// `ERROR [binary_logger] suppressed 4182 duplicates of format 17 in 998 ms`,
+    /// so an error storm costs the sinks a record per window while readers
+    /// still see how often it happened. Counts of open windows are also
+    /// written on `flush` and when the logger is dropped. The check costs a
+    /// hash of the arguments and a hash map lookup per record of a format
+    /// with a window; see `dedup`.
+    pub fn set_duplicate_filter(&mut self, filter: Option<DuplicateFilter>) {
+        self.write_duplicate_summaries(true);
+        self.duplicates = filter.map(DuplicateCache::new);
+    }
+
+    /// Returns the duplicate filter, if any.
+    pub fn duplicate_filter(&self) -> Option<&DuplicateFilter> {
+        self.duplicates.as_ref().map(DuplicateCache::filter)
+    }
+
+    /// Sets limits on the size of arguments captured by `log_record!` and
+    /// `log_record_at!`, by the level of the record.
+    /// 
+    /// Arguments over their limit are cut, and their records carry
+    /// `FLAG_TRUNCATED`; see `CaptureLimits`. No limits are set by default.
+    pub fn set_capture_limits(&mut self, limits: CaptureLimits) {
+        self.capture_limits = limits;
+    }
+
+    /// Returns the limits set with `set_capture_limits`.
+    pub fn capture_limits(&self) -> &CaptureLimits {
+        &self.capture_limits
+    }
+
+    /// Refuses records whose payload, the encoded arguments, is larger than
+    /// `size` bytes, or with `None` takes every record that fits in a buffer.
+    /// 
+    /// A refused record isn't written, not even in part, and the write fails
+    /// with `LogError::PayloadTooLarge`, as it does for records larger than
+    /// a buffer whatever the limit. Records of 64 KiB and more take the
+    /// `long-records` format feature. Unlike `set_capture_limits`, which cuts
+    /// oversized arguments to size, this keeps one huge record from taking
+    /// most of a buffer.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, LogError, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<65536>::new(NullHandler);
+    /// logger.set_max_payload_size(Some(1024));
+    /// let body = "x".repeat(4096);
+    /// let error = log_record!(logger, "Request body {}", body.as_str()).unwrap_err();
+    /// assert!(matches!(LogError::of(&error), Some(LogError::PayloadTooLarge { limit: 1024, .. })));
+    /// ```
+    pub fn set_max_payload_size(&mut self, size: Option<usize>) {
+        self.max_payload_size = size;
+    }
+
+    /// Returns the limit set with `set_max_payload_size`.
+    pub fn max_payload_size(&self) -> Option<usize> {
+        self.max_payload_size
+    }
+
+    /// Lowers (or raises) the logger's level to `level` while `f` runs, then
+    /// restores it.
+    /// 
+    /// Use it to log a suspicious code path in depth without changing the
+    /// level everywhere; `level::with_global_level` does the same for every
+    /// Logger in the process.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record_at};
+    /// # use binary_logger::level::Level;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_level(Level::Info);
+    /// logger.with_level(Level::Trace, |logger| {
+    ///     log_record_at!(logger, Level::Trace, "Entering {}", "reconcile").unwrap();
+    /// });
+    /// assert_eq!(logger.level(), Level::Info);
+    /// ```
+    pub fn with_level<R>(&mut self, level: Level, f: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = std::mem::replace(&mut self.level, level);
+        let result = f(self);
+        self.level = previous;
+        result
+    }
+
+    /// Sets the tags whose records are written: a record logged with
+    /// `log_tagged!` is written if it has at least one of them. Records
+    /// without tags are always written.
+    /// 
+    /// Checking costs a single AND before the arguments are captured. All
+    /// tags are written by default.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_tagged};
+    /// # use binary_logger::tags::register_tag;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let (audit, debug_io) = (register_tag("audit"), register_tag("debug-io"));
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.set_tag_mask(audit);
+    /// log_tagged!(logger, tags = debug_io, "Read {} bytes", 512).unwrap();
+    /// log_tagged!(logger, tags = audit | debug_io, "Key {} read", 3).unwrap();
+    /// assert_eq!(logger.stats().records_written, 1);
+    /// ```
+    pub fn set_tag_mask(&mut self, mask: Tags) {
+        self.tag_mask = mask;
+    }
+
+    /// Returns the tags whose records are written.
+    pub fn tag_mask(&self) -> Tags {
+        self.tag_mask
+    }
+
+    /// Returns whether a record with `tags` would be written.
+    #[inline]
+    pub fn tags_enabled(&self, tags: Tags) -> bool {
+        tags.is_empty() || tags.intersects(self.tag_mask)
+    }
+
+    /// Sets the sampler deciding which keys `log_sampled!` writes records
+    /// for, or `None` to write them all (the default).
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_sampled};
+    /// # use binary_logger::sampling::KeySampler;
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<65536>::new(NullHandler);
+    /// logger.set_sampler(Some(KeySampler::new(0.01)));
+    /// for user in 0..1000u64 {
+    ///     // Every event of the sampled 1% of users
+    ///     log_sampled!(logger, key = user, "User {} opened cart", user).unwrap();
+    ///     log_sampled!(logger, key = user, "User {} paid", user).unwrap();
+    /// }
+    /// let stats = logger.stats();
+    /// assert_eq!(stats.records_written + stats.sampled_out, 2000);
+    /// ```
+    pub fn set_sampler(&mut self, sampler: Option<KeySampler>) {
+        self.sampler = sampler;
+    }
+
+    /// Returns the sampler used by `log_sampled!`, if any.
+    pub fn sampler(&self) -> Option<KeySampler> {
+        self.sampler
+    }
+
+    /// Returns whether records for `key` are written, counting a record
+    /// left out by the sampler otherwise.
+    #[doc(hidden)]
+    pub fn sample<K: std::hash::Hash + ?Sized>(&mut self, key: &K) -> bool {
+        match &self.sampler {
+            Some(sampler) if !sampler.keep(key) => {
+                self.sampled_out += 1;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Sets what happens when the BufferHandler panics.
+    /// 
+    /// The handler is called with the panic contained, so the Logger stays
+    /// consistent whatever the policy: the failed buffer is lost and
+    /// counted, and writing continues in the other buffer.
+    /// `HandlerPanicPolicy::Propagate`, the default, then resumes the panic
+    /// in the logging thread (except in the flush of a Logger dropped while
+    /// unwinding, which must not panic again). `Disable` stops using the
+    /// handler for good, and `Retry` tries the handler again with the next
+    /// buffer, for sinks that recover on their own. `handler_status`
+    /// reports the failures.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, HandlerPanicPolicy, log_record};
+    /// struct FailingHandler;
+    /// impl BufferHandler for FailingHandler {
+    ///     fn handle_buffer(&self, _data: &[u8]) {
+    ///         panic!("disk full");
+    ///     }
+    /// }
+    /// let mut logger = Logger::<4096>::new(FailingHandler);
+    /// logger.set_handler_panic_policy(HandlerPanicPolicy::Disable);
+    /// log_record!(logger, "Request {} served", 1).unwrap();
+    /// logger.flush();
+    /// let status = logger.handler_status();
+    /// assert!(status.disabled);
+    /// assert_eq!(status.last_panic.as_deref(), Some("disk full"));
+    /// ```
+    pub fn set_handler_panic_policy(&mut self, policy: HandlerPanicPolicy) {
+        self.handler_panic_policy = policy;
+    }
+
+    /// Returns the policy for BufferHandler panics.
+    pub fn handler_panic_policy(&self) -> HandlerPanicPolicy {
+        self.handler_panic_policy
+    }
+
+    /// Returns the BufferHandler's failures so far.
+    pub fn handler_status(&self) -> &HandlerStatus {
+        &self.handler_status
+    }
+
+    /// Returns what the logger has written so far.
+    pub fn stats(&self) -> LoggerStats {
+        LoggerStats {
+            records_written: self.records_written,
+            buffers_flushed: self.buffers_flushed,
+            bytes_flushed: self.bytes_flushed,
+            bytes_pending: (self.write_pos - BUFFER_HEADER_SIZE) as u64,
+            dropped_records: self.dropped_records,
+            sampled_out: self.sampled_out,
+            duplicates_suppressed: self.duplicates_suppressed,
+        }
+    }
+
+    /// Makes the next record carry a full base timestamp.
+    pub(crate) fn reset_time_base(&mut self) {
+        self.clock.reset();
+    }
+
+    /// Writes a raw log record to the buffer.
+    /// 
+    /// This is a low-level method that handles the binary format writing.
+    /// The payload is stored as given, bypassing the Logger's codec. In most
+    /// cases, you should use the `log_record!` macro instead, which handles
+    /// format string registration and parameter serialization.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `format_id` - The ID of the format string from the string registry
+    /// * `payload` - The raw binary payload of the log record
+    /// 
+    /// # Returns
+    /// 
+    /// A Result indicating success or an IO error
+    /// 
+    /// # Binary Format
+    /// 
+    /// Format: `[type(1) | flags(1) | time(2 or varint) | format_id(2)? | payload_len(2) | sequence(8)? | channel(2)? | payload(N) | pad?]`
+    /// 
+    /// Where type:
+    /// - 0: Record with relative timestamp
+    /// - 1: Record with base timestamp reset; the payload starts with the new
+    ///   base as 8 bytes of microseconds since the UNIX epoch
+    /// - 2: Stream header, written once before the first record:
+    ///   `magic("BLOG") | version(1) | codec_id(1) | features(u32)`, the
+    ///   features being the `FormatFeatures` the stream may use
+    /// - 3: Audit chain record: SHA-256 of the previous buffer (32 bytes)
+    /// - 0x80-0xFF: Application-defined records, see `write_custom`
+    /// 
+    /// The time is a 16-bit relative timestamp, or a varint delta from the
+    /// previous record when `FLAG_DELTA_TIME` is set (see
+    /// `set_delta_timestamps`). The format ID is left out when
+    /// `FLAG_SAME_FORMAT` is set (see `set_header_compression`), the sequence is present only when
+    /// `FLAG_SEQUENCE` is set, the channel only when `FLAG_CHANNEL` is set.
+    /// A payload_len of 0xFFFF marks a record of 64 KiB or more, whose
+    /// length follows the channel and wide format ID as a u32 (see
+    /// `FormatFeatures::LONG_RECORDS`); payloads over the limits fail with
+    /// `LogError::PayloadTooLarge` (see `set_max_payload_size`).
+    /// Bits 4-6 of the flags hold the record's level, if it has one (see
+    /// `write_args_at`).
+    /// Records are padded to an even length so every record starts 2-byte
+    /// aligned.
+    pub fn write(&mut self, format_id: u32, payload: &[u8]) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check(format_id, &self.codec.decode(payload))?;
+        }
+        self.write_with(RECORD_TYPE_NORMAL, RecordMeta::default(), format_id, payload.len(), |out| out.copy_from_slice(payload))
+    }
+
+    /// Writes an application-defined record.
+    /// 
+    /// The payload is stored as given, with the same framing, timestamp and
+    /// sequence number as a log record, so applications can interleave their
+    /// own binary records (checkpoints, snapshots) with log entries in one
+    /// time-ordered stream. Readers decode them with a `RecordDecoder`
+    /// registered for `record_type` and skip them otherwise.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `record_type` - A type from `RECORD_TYPE_USER_MIN` to `RECORD_TYPE_USER_MAX`
+    /// * `payload` - The raw binary payload of the record
+    /// 
+    /// # Returns
+    /// 
+    /// A Result indicating success, or an `InvalidInput` error if
+    /// `record_type` is reserved for the library
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, RECORD_TYPE_USER_MIN};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// const CHECKPOINT: u8 = RECORD_TYPE_USER_MIN;
+    /// 
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// let offset: u64 = 1024;
+    /// logger.write_custom(CHECKPOINT, &offset.to_le_bytes()).unwrap();
+    /// ```
+    pub fn write_custom(&mut self, record_type: u8, payload: &[u8]) -> io::Result<()> {
+        if record_type < RECORD_TYPE_USER_MIN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("record type {} is reserved for the library", record_type)));
+        }
+        self.require_feature(FormatFeatures::APPLICATION_RECORDS)?;
+        self.write_with(record_type, RecordMeta::default(), 0, payload.len(), |out| out.copy_from_slice(payload))
+    }
+
+    /// Counts one occurrence of the event registered as `format_id`, for
+    /// counters too hot to log a record each time.
+    /// 
+    /// Nothing is written per event: the Logger keeps a count per format ID
+    /// and writes the counts of each buffer as one count table record
+    /// (`RECORD_TYPE_COUNTS`) when the buffer is switched out, timed at the
+    /// switch. Readers add the tables up (`LogReader::counters`). A table
+    /// holds at most `COUNT_TABLE_CAPACITY` format IDs; more distinct
+    /// counters in one buffer write the table early and start a new one.
+    /// This is what `log_count!` calls.
+    /// 
+    /// Without `FormatFeatures::COUNTERS` in the Logger's format features,
+    /// and for format IDs too wide for count tables (see
+    /// `FormatFeatures::WIDE_IDS`), each event is written as a record
+    /// without arguments instead.
+    #[inline]
+    pub fn count(&mut self, format_id: u32) -> io::Result<()> {
+        if !self.format_features.contains(FormatFeatures::COUNTERS) || is_wide_id(format_id) {
+            return self.write_args(format_id, &[]);
+        }
+        if let Some((_, count)) = self.counts.iter_mut().find(|(id, _)| *id == format_id) {
+            *count += 1;
+            if *count == u32::MAX {
+                self.write_count_table();
+            }
+            return Ok(());
+        }
+        self.add_counter(format_id);
+        Ok(())
+    }
+
+    /// Starts counting `format_id` in the count table, making room for the
+    /// larger table first.
+    #[cold]
+    fn add_counter(&mut self, format_id: u32) {
+        if self.counts.len() == COUNT_TABLE_CAPACITY {
+            self.write_count_table();
+        }
+        let reserve = self.prologue_size()
+            + if self.instrumentation_enabled { INSTRUMENTATION_RESERVE } else { 0 };
+        if self.write_pos + count_table_size(self.counts.len() + 1) + reserve > CAP {
+            self.switch_buffers();
+        }
+        if self.counts.capacity() == 0 {
+            // Allocated once, so counting never allocates again
+            self.counts.reserve_exact(COUNT_TABLE_CAPACITY);
+        }
+        self.counts.push((format_id, 1));
+    }
+
+    /// Writes the count table of the current buffer, if it has counts.
+    /// 
+    /// Space for the table is kept free by every write, so it always fits.
+    fn write_count_table(&mut self) {
+        if self.counts.is_empty() || !self.lease_buffer() {
+            return;
+        }
+        self.write_prologue();
+        let (time, is_base) = self.next_record_time();
+        if is_base {
+            self.write_time_base();
+        }
+        let counts = std::mem::take(&mut self.counts);
+        self.write_record(time, false, RECORD_TYPE_COUNTS, RecordMeta::default(), 0, counts.len() * COUNT_ENTRY_SIZE, |out| {
+            for (entry, (format_id, count)) in out.chunks_exact_mut(COUNT_ENTRY_SIZE).zip(&counts) {
+                entry[..2].copy_from_slice(&(*format_id as u16).to_le_bytes());
+                entry[2..].copy_from_slice(&count.to_le_bytes());
+            }
+        });
+        self.counts = counts;
+        self.counts.clear();
+    }
+
+    /// Writes a metric record for the metric key registered as `key_id`.
+    pub(crate) fn write_metric(&mut self, key_id: u32, payload: &[u8]) -> io::Result<()> {
+        self.require_feature(FormatFeatures::METRICS)?;
+        self.write_with(RECORD_TYPE_METRIC, RecordMeta::default(), key_id, payload.len(), |out| out.copy_from_slice(payload))
+    }
+
+    /// Writes a log record whose arguments are encoded with the Logger's codec.
+    /// 
+    /// Each element of `args` holds the bytes of one argument. This is what
+    /// `log_record!` calls after registering the format string.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `format_id` - The ID of the format string from the string registry
+    /// * `args` - The bytes of each argument, in order
+    pub fn write_args(&mut self, format_id: u32, args: &[&[u8]]) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check(format_id, args)?;
+        }
+        self.write_leveled(RecordMeta::default(), Tags::NONE, format_id, args, |_| TypeTag::UNKNOWN)
+    }
+
+    /// Writes a log record on a channel, encoding its arguments with the
+    /// Logger's codec.
+    /// 
+    /// Channels let one logger carry several logical streams (audit, metrics,
+    /// debug) in a single file. `log_record!(logger, channel: "audit", ...)`
+    /// calls this after interning the channel name in the string registry.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `channel` - ID of the channel name; 0 writes no channel
+    /// * `format_id` - The ID of the format string from the string registry
+    /// * `args` - The bytes of each argument, in order
+    pub fn write_args_on(&mut self, channel: u32, format_id: u32, args: &[&[u8]]) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check(format_id, args)?;
+        }
+        self.write_leveled(RecordMeta { channel, ..RecordMeta::default() }, Tags::NONE, format_id, args, |_| TypeTag::UNKNOWN)
+    }
+
+    /// Writes a log record with a severity level, and optionally on a
+    /// channel, encoding its arguments with the Logger's codec.
+    /// 
+    /// The level is stored in the record's flags, so it costs no space.
+    /// Records below the logger's level (see `set_level`) are skipped.
+    /// `log_record_at!` calls this after registering the format string (and
+    /// channel name).
+    /// 
+    /// # Arguments
+    /// 
+    /// * `level` - Severity of the record
+    /// * `channel` - ID of the channel name; 0 writes no channel
+    /// * `format_id` - The ID of the format string from the string registry
+    /// * `args` - The bytes of each argument, in order
+    pub fn write_args_at(&mut self, level: Level, channel: u32, format_id: u32, args: &[&[u8]]) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check(format_id, args)?;
+        }
+        self.write_leveled(RecordMeta { channel, level: Some(level), truncated: false }, Tags::NONE, format_id, args, |_| TypeTag::UNKNOWN)
+    }
+
+    /// Writes a log record whose arguments have the types `types`, with an
+    /// optional level and channel, encoding the arguments with the Logger's
+    /// codec.
+    /// 
+    /// With type tags on (see `set_type_tags`), readers decode each argument
+    /// as its type instead of guessing it from its size. Arguments past the
+    /// end of `types` are `TypeTag::UNKNOWN`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `level` - Severity of the record, if it has one
+    /// * `channel` - ID of the channel name; 0 writes no channel
+    /// * `format_id` - The ID of the format string from the string registry
+    /// * `args` - The bytes of each argument, in order
+    /// * `types` - The type of each argument, in order
+    pub fn write_typed_args(&mut self, level: Option<Level>, channel: u32, format_id: u32, args: &[&[u8]], types: &[TypeTag]) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check(format_id, args)?;
+        }
+        let type_tag = |index: usize| types.get(index).copied().unwrap_or(TypeTag::UNKNOWN);
+        self.write_leveled(RecordMeta { channel, level, truncated: false }, Tags::NONE, format_id, args, type_tag)
+    }
+
+    /// Writes the arguments captured by `log_record!` or `log_record_at!`,
+    /// flagging the record if `capture` cut any of them to its limit.
+    #[doc(hidden)]
+    pub fn write_captured(&mut self, level: Option<Level>, channel: u32, format_id: u32, args: &[&[u8]], capture: &ArgCapture) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check_captured(format_id, args, capture)?;
+        }
+        self.write_leveled(RecordMeta { channel, level, truncated: capture.truncated() }, Tags::NONE, format_id, args, |index| capture.type_tag(index))
+    }
+
+    /// Writes the arguments captured by `log_tagged!` as a record carrying
+    /// `tags`, which the macro checked with `tags_enabled`.
+    #[doc(hidden)]
+    pub fn write_tagged(&mut self, level: Option<Level>, tags: Tags, format_id: u32, args: &[&[u8]], capture: &ArgCapture) -> io::Result<()> {
+        if let Some(schema) = self.active_schema() {
+            schema.check_captured(format_id, args, capture)?;
+        }
+        self.write_leveled(RecordMeta { channel: 0, level, truncated: capture.truncated() }, tags, format_id, args, |index| capture.type_tag(index))
+    }
+
+    /// Writes a log record unless its level is below the logger's, as a
+    /// tagged record if it has tags, inside a critical section included, or
+    /// else as a typed record with the type tags of `capture` if they are
+    /// on.
+    fn write_leveled(&mut self, meta: RecordMeta, mut tags: Tags, format_id: u32, args: &[&[u8]], type_tag: impl Fn(usize) -> TypeTag) -> io::Result<()> {
+        if let Some(level) = meta.level.filter(|&level| !self.level_enabled(level)) {
+            self.skip_level(level, format_id);
+            return Ok(());
+        }
+        if let Some(duplicates) = &mut self.duplicates {
+            if !duplicates.admit(format_id, meta.channel, args, meta.level, self.clock.instant_now()) {
+                self.duplicates_suppressed += 1;
+                return Ok(());
+            }
+        }
+        if self.critical_depth > 0 {
+            tags |= Tags::CRITICAL;
+        }
+        if !self.format_features.contains(FormatFeatures::TAGS) {
+            tags = Tags::NONE;
+        }
+        let codec = self.codec;
+        if self.type_tags && !(self.untimed && tags.is_empty()) && args.len() <= u8::MAX as usize {
+            // The tag mask, if any, then the argument count and a type tag
+            // per argument precede the arguments
+            let (record_type, mask_len) = if tags.is_empty() { (RECORD_TYPE_TYPED, 0) } else { (RECORD_TYPE_TAGGED_TYPED, TAGS_SIZE) };
+            let bits = tags.bits().to_le_bytes();
+            let tags_len = mask_len + 1 + args.len();
+            return self.write_with(record_type, meta, format_id, tags_len + codec.encoded_len(args), |out| {
+                out[..mask_len].copy_from_slice(&bits[..mask_len]);
+                out[mask_len] = args.len() as u8;
+                for (index, tag) in out[mask_len + 1..tags_len].iter_mut().enumerate() {
+                    *tag = type_tag(index).bits();
+                }
+                codec.encode(args, &mut out[tags_len..]);
+            });
+        }
+        if tags.is_empty() {
+            return self.write_with(RECORD_TYPE_NORMAL, meta, format_id, codec.encoded_len(args), |out| codec.encode(args, out));
+        }
+        // The mask precedes the arguments
+        let bits = tags.bits().to_le_bytes();
+        self.write_with(RECORD_TYPE_TAGGED, meta, format_id, TAGS_SIZE + codec.encoded_len(args), |out| {
+            out[..TAGS_SIZE].copy_from_slice(&bits);
+            codec.encode(args, &mut out[TAGS_SIZE..]);
+        })
+    }
+
+    /// Returns the schema registry records are checked against, if the
+    /// check is on, see `set_schema_registry`.
+    #[inline]
+    fn active_schema(&self) -> Option<&SchemaRegistry> {
+        self.schema.as_deref().filter(|_| cfg!(debug_assertions) || self.strict_schema)
+    }
+
+    /// Returns an `Unsupported` error if `feature` was left out with
+    /// `set_format_features`.
+    fn require_feature(&self, feature: FormatFeatures) -> io::Result<()> {
+        if self.format_features.contains(feature) {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Unsupported,
+                format!("format feature {} is disabled for this logger", feature)))
+        }
+    }
+
+    /// Reserves space for a record and lets `fill` write its payload in place.
+    fn write_with(&mut self, record_type: u8, meta: RecordMeta, format_id: u32, payload_len: usize, fill: impl FnOnce(&mut [u8])) -> io::Result<()> {
+        // Leave out what the stream must not use
+        let features = self.format_features;
+        let meta = RecordMeta {
+            channel: if features.contains(FormatFeatures::CHANNELS) { meta.channel } else { 0 },
+            level: meta.level.filter(|_| features.contains(FormatFeatures::LEVELS)),
+            truncated: meta.truncated && features.contains(FormatFeatures::TRUNCATION),
+        };
+        if is_wide_id(format_id) || is_wide_id(meta.channel) {
+            self.require_feature(FormatFeatures::WIDE_IDS)?;
+        }
+        if self.embedded_dictionary && string_registry::registered_count() != self.embedded_strings {
+            self.write_dictionary();
+        }
+        if self.dictionary_channel && self.metadata_due() {
+            self.publish_dictionary();
+        }
+        if self.suppression.as_ref().is_some_and(|suppression| suppression.is_due(self.clock.instant_now())) {
+            self.write_suppression_summaries();
+        }
+        if self.duplicates.as_ref().is_some_and(|duplicates| duplicates.is_due(self.clock.instant_now())) {
+            self.write_duplicate_summaries(false);
+        }
+
+        // Size the record as if it also had to carry a new base timestamp,
+        // which other record types need in a record of its own. Delta
+        // timestamps never come with a base, and fit in the same 8 bytes
+        let sequence_len = if self.sequence_enabled { 8 } else { 0 };
+        let channel_len = match meta.channel {
+            0 => 0,
+            channel => if is_wide_id(channel) { 2 + WIDE_ID_SIZE } else { 2 },
+        };
+        let wide_format_len = if is_wide_id(format_id) { WIDE_ID_SIZE } else { 0 };
+        // Records of 64 KiB and more carry a 32-bit length. A base record's
+        // length field also counts its 8-byte base
+        let long = payload_len + 8 >= LONG_LENGTH as usize;
+        if long && !features.contains(FormatFeatures::LONG_RECORDS) {
+            return Err(LogError::PayloadTooLarge { size: payload_len, limit: LONG_LENGTH as usize - 9 }.into());
+        }
+        if let Some(limit) = self.max_payload_size.filter(|&limit| payload_len > limit && record_type != RECORD_TYPE_DICTIONARY) {
+            return Err(LogError::PayloadTooLarge { size: payload_len, limit }.into());
+        }
+        let long_len = if long { LONG_LENGTH_SIZE } else { 0 };
+        let base_len = if record_type == RECORD_TYPE_NORMAL { 8 } else { TIME_BASE_RECORD_SIZE };
+        let fixed_len = RECORD_HEADER_SIZE + sequence_len + channel_len + wide_format_len + long_len + base_len;
+        let max_size = (fixed_len + payload_len + 1) & !1;
+
+        // Keep room for the stream header, chain record, count table and
+        // the internal events a switch or this write may emit
+        let reserve = self.prologue_size()
+            + if self.instrumentation_enabled { INSTRUMENTATION_RESERVE } else { 0 }
+            + if self.counts.is_empty() { 0 } else { count_table_size(self.counts.len()) };
+
+        // Check if we need to switch buffers
+        if self.write_pos + max_size + reserve > CAP {
+            // The record must fit in an empty buffer
+            if BUFFER_HEADER_SIZE + max_size + reserve > CAP {
+                let limit = (CAP.saturating_sub(BUFFER_HEADER_SIZE + reserve) & !1).saturating_sub(fixed_len);
+                return Err(LogError::PayloadTooLarge { size: payload_len, limit }.into());
+            }
+            self.switch_buffers();
+        }
+
+        if !self.lease_buffer() {
+            // Every buffer of the pool is in use
+            self.note_dropped(1);
+            return Ok(());
+        }
+
+        self.write_prologue();
+
+        if self.instrumentation_enabled {
+            self.emit_pending_internal_events();
+        }
+
+        if self.untimed && record_type == RECORD_TYPE_NORMAL && !self.time_anchor_pending {
+            self.write_record(RecordTime::None, false, RECORD_TYPE_UNTIMED, meta, format_id, payload_len, fill);
+        } else {
+            let (time, is_base) = self.next_record_time();
+            self.time_anchor_pending = false;
+            if is_base && record_type != RECORD_TYPE_NORMAL {
+                self.write_time_base();
+                self.write_record(time, false, record_type, meta, format_id, payload_len, fill);
+            } else {
+                self.write_record(time, is_base, record_type, meta, format_id, payload_len, fill);
+            }
+        }
+
+        self.records_written += 1;
+        if let Some(published) = &self.published {
+            published.update(&self.stats());
+        }
+        if let Some(health) = &self.health {
+            health.wrote(self.write_pos - BUFFER_HEADER_SIZE);
+        }
+        Ok(())
+    }
+
+    /// Leases a buffer from the pool if the Logger writes to pooled buffers
+    /// and holds none, returning whether there is a buffer to write to.
+    fn lease_buffer(&mut self) -> bool {
+        if !self.active_buffer.is_null() {
+            return true;
+        }
+        match self.buffer_pool.as_ref().and_then(|pool| pool.lease()) {
+            Some(buffer) => {
+                self.active_buffer = buffer;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the time of the next record from the clock, and whether it
+    /// starts a new base.
+    fn next_record_time(&mut self) -> (RecordTime, bool) {
+        if self.delta_timestamps {
+            match self.clock.get_delta_timestamp() {
+                (_, true) => (RecordTime::Relative(0), true),
+                (delta, false) => (RecordTime::Delta(delta), false),
+            }
+        } else {
+            let (rel_ts, is_base) = self.clock.get_relative_timestamp();
+            (RecordTime::Relative(rel_ts), is_base)
+        }
+    }
+
+    /// Encodes one record at the current write position.
+    /// 
+    /// With `is_base` set the record is written as a base record instead of
+    /// `record_type`. The caller must have checked that the padded record
+    /// fits in the active buffer.
+    #[allow(clippy::too_many_arguments)]
+    fn write_record(&mut self, time: RecordTime, is_base: bool, record_type: u8, meta: RecordMeta, format_id: u32, payload_len: usize, fill: impl FnOnce(&mut [u8])) {
+        let mut flags = Level::to_flags(meta.level);
+        if meta.truncated {
+            flags |= FLAG_TRUNCATED;
+        }
+        let time_len = match time {
+            RecordTime::None => 0,
+            RecordTime::Relative(_) => 2,
+            RecordTime::Delta(delta) => {
+                flags |= FLAG_DELTA_TIME;
+                varint_len(delta as usize)
+            }
+        };
+        let same_format = self.header_compression && self.last_format_id == Some(format_id);
+        self.last_format_id = Some(format_id);
+        if same_format {
+            flags |= FLAG_SAME_FORMAT;
+        }
+        let header_size = 2 + time_len + if same_format { 0 } else { 2 } + 2;
+        let mut record_size = header_size + payload_len;
+        if self.sequence_enabled {
+            flags |= FLAG_SEQUENCE;
+            record_size += 8;
+        }
+        if meta.channel != 0 {
+            flags |= FLAG_CHANNEL;
+            record_size += if is_wide_id(meta.channel) { 2 + WIDE_ID_SIZE } else { 2 };
+        }
+        let wide_format = !same_format && is_wide_id(format_id);
+        if wide_format {
+            record_size += WIDE_ID_SIZE;
+        }
+        let base_len = if is_base { 8 } else { 0 };
+        record_size += base_len;
+        let long = base_len + payload_len >= LONG_LENGTH as usize;
+        if long {
+            record_size += LONG_LENGTH_SIZE;
+        }
+        let padded_size = (record_size + 1) & !1;
+
+        unsafe {
+            let record = self.active_buffer.add(self.write_pos);
+
+            // Write record type and flags
+            *record = if is_base { RECORD_TYPE_BASE } else { record_type };
+            *record.add(1) = flags;
+
+            // Write timestamp, format ID unless repeated, and payload length
+            match time {
+                RecordTime::None => {}
+                RecordTime::Relative(rel_ts) => std::ptr::write_unaligned(record.add(2) as *mut u16, rel_ts.to_le()),
+                RecordTime::Delta(delta) => {
+                    write_varint(delta as usize, std::slice::from_raw_parts_mut(record.add(2), time_len));
+                }
+            }
+            if !same_format {
+                let field = if wide_format { WIDE_ID } else { format_id as u16 };
+                std::ptr::write_unaligned(record.add(2 + time_len) as *mut u16, field.to_le());
+            }
+            let length = if long { LONG_LENGTH } else { (base_len + payload_len) as u16 };
+            std::ptr::write_unaligned(record.add(header_size - 2) as *mut u16, length.to_le());
+            let mut pos = header_size;
+
+            // Write sequence number
+            if self.sequence_enabled {
+                let sequence = GLOBAL_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+                std::ptr::write_unaligned(record.add(pos) as *mut u64, sequence.to_le());
+                pos += 8;
+            }
+
+            // Write channel ID, then the wide IDs the 16-bit fields left out
+            if meta.channel != 0 {
+                if is_wide_id(meta.channel) {
+                    std::ptr::write_unaligned(record.add(pos) as *mut u16, WIDE_ID.to_le());
+                    std::ptr::write_unaligned(record.add(pos + 2) as *mut u32, meta.channel.to_le());
+                    pos += 2 + WIDE_ID_SIZE;
+                } else {
+                    std::ptr::write_unaligned(record.add(pos) as *mut u16, (meta.channel as u16).to_le());
+                    pos += 2;
+                }
+            }
+            if wide_format {
+                std::ptr::write_unaligned(record.add(pos) as *mut u32, format_id.to_le());
+                pos += WIDE_ID_SIZE;
+            }
+            if long {
+                std::ptr::write_unaligned(record.add(pos) as *mut u32, ((base_len + payload_len) as u32).to_le());
+                pos += LONG_LENGTH_SIZE;
+            }
+
+            // Write the new base timestamp ahead of the arguments
+            if is_base {
+                std::ptr::write_unaligned(record.add(pos) as *mut u64, self.clock.base_micros().to_le());
+                pos += 8;
+            }
+
+            // Write payload
+            fill(std::slice::from_raw_parts_mut(record.add(pos), payload_len));
+            pos += payload_len;
+
+            // Pad to keep the next record aligned
+            if pos < padded_size {
+                *record.add(pos) = 0;
+            }
+        }
+        self.write_pos += padded_size;
+    }
+
+    /// Size of the records that must precede the next record: the stream
+    /// header and the chain record, when pending.
+    fn prologue_size(&self) -> usize {
+        let mut size = 0;
+        if self.stream_header_pending {
+            size += STREAM_HEADER_RECORD_SIZE;
+        }
+        if self.chain_pending {
+            size += CHAIN_RECORD_SIZE;
+        }
+        if self.retention_pending {
+            size += RECORD_HEADER_SIZE + self.retention.len() * RETENTION_ENTRY_SIZE;
+        }
+        if self.clock_offset_pending {
+            size += CLOCK_OFFSET_RECORD_SIZE;
+        }
+        size
+    }
+
+    /// Writes the pending stream header, chain record, retention table and
+    /// clock offset.
+    /// 
+    /// The caller must have checked that `prologue_size()` bytes fit in the
+    /// active buffer.
+    fn write_prologue(&mut self) {
+        if self.stream_header_pending {
+            self.write_stream_header();
+        }
+        if self.chain_pending {
+            self.write_chain_record();
+        }
+        if self.retention_pending {
+            self.write_retention_table();
+        }
+        if self.clock_offset_pending {
+            self.write_clock_offset();
+        }
+    }
+
+    /// Writes the retention table, see `set_channel_retention`.
+    fn write_retention_table(&mut self) {
+        let payload_len = self.retention.len() * RETENTION_ENTRY_SIZE;
+        unsafe {
+            let record = self.active_buffer.add(self.write_pos);
+            *record = RECORD_TYPE_RETENTION;
+            *record.add(1) = 0;
+            std::ptr::write_unaligned(record.add(2) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(4) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(6) as *mut u16, (payload_len as u16).to_le());
+            let entries = std::slice::from_raw_parts_mut(record.add(RECORD_HEADER_SIZE), payload_len);
+            for (entry, (channel, ttl)) in entries.chunks_exact_mut(RETENTION_ENTRY_SIZE).zip(&self.retention) {
+                entry[..2].copy_from_slice(&(*channel as u16).to_le_bytes());
+                entry[2..].copy_from_slice(&ttl.to_le_bytes());
+            }
+        }
+        self.write_pos += RECORD_HEADER_SIZE + payload_len;
+        self.last_format_id = Some(0);
+        self.retention_pending = false;
+    }
+
+    /// Writes the clock offset, see `set_clock_offset`.
+    fn write_clock_offset(&mut self) {
+        let Some(offset) = self.clock_offset else { return };
+        unsafe {
+            let record = self.active_buffer.add(self.write_pos);
+            *record = RECORD_TYPE_CLOCK_OFFSET;
+            *record.add(1) = 0;
+            std::ptr::write_unaligned(record.add(2) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(4) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(6) as *mut u16, (CLOCK_OFFSET_SIZE as u16).to_le());
+            // The payload's odd length is padded with a zero
+            std::ptr::write_bytes(record.add(RECORD_HEADER_SIZE), 0, CLOCK_OFFSET_RECORD_SIZE - RECORD_HEADER_SIZE);
+            std::ptr::copy_nonoverlapping(offset.encode().as_ptr(), record.add(RECORD_HEADER_SIZE), CLOCK_OFFSET_SIZE);
+        }
+        self.write_pos += CLOCK_OFFSET_RECORD_SIZE;
+        self.last_format_id = Some(0);
+        self.clock_offset_pending = false;
+    }
+
+    /// Writes a base record without a log entry (format ID 0), for records
+    /// that can't carry the base themselves.
+    /// 
+    /// The caller must have checked that the record fits in the active buffer.
+    fn write_time_base(&mut self) {
+        unsafe {
+            let record = self.active_buffer.add(self.write_pos);
+            *record = RECORD_TYPE_BASE;
+            *record.add(1) = 0;
+            std::ptr::write_unaligned(record.add(2) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(4) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(6) as *mut u16, 8u16.to_le());
+            std::ptr::write_unaligned(record.add(RECORD_HEADER_SIZE) as *mut u64, self.clock.base_micros().to_le());
+        }
+        self.write_pos += TIME_BASE_RECORD_SIZE;
+        self.last_format_id = Some(0);
+    }
+
+    /// Writes the chain record linking this buffer to the previous one.
+    fn write_chain_record(&mut self) {
+        let previous = self.last_buffer_hash.unwrap_or([0; CHAIN_HASH_SIZE]);
+        unsafe {
+            let record = self.active_buffer.add(self.write_pos);
+            *record = RECORD_TYPE_CHAIN;
+            *record.add(1) = 0;
+            std::ptr::write_unaligned(record.add(2) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(4) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(6) as *mut u16, (CHAIN_HASH_SIZE as u16).to_le());
+            std::ptr::copy_nonoverlapping(previous.as_ptr(), record.add(RECORD_HEADER_SIZE), CHAIN_HASH_SIZE);
+        }
+        self.write_pos += CHAIN_RECORD_SIZE;
+        self.last_format_id = Some(0);
+        self.chain_pending = false;
+    }
+
+    /// Writes the stream header record describing how the stream is encoded.
+    /// 
+    /// The caller must have checked that the record fits in the active buffer.
+    fn write_stream_header(&mut self) {
+        let record = self.stream_header_record();
+        unsafe {
+            std::ptr::copy_nonoverlapping(record.as_ptr(), self.active_buffer.add(self.write_pos), record.len());
+        }
+        self.write_pos += STREAM_HEADER_RECORD_SIZE;
+        self.last_format_id = Some(0);
+        self.stream_header_pending = false;
+    }
+
+    /// Returns the stream header record of the Logger's stream.
+    fn stream_header_record(&self) -> [u8; STREAM_HEADER_RECORD_SIZE] {
+        let mut record = [0u8; STREAM_HEADER_RECORD_SIZE];
+        record[0] = RECORD_TYPE_STREAM_HEADER;
+        record[6..8].copy_from_slice(&(STREAM_HEADER_PAYLOAD_SIZE as u16).to_le_bytes());
+
+        let payload = &mut record[RECORD_HEADER_SIZE..];
+        payload[..4].copy_from_slice(&STREAM_MAGIC);
+        payload[4] = STREAM_VERSION;
+        payload[5] = self.codec.id();
+        payload[6..10].copy_from_slice(&self.format_features.bits().to_le_bytes());
+        record
+    }
+
+    /// Writes the registered strings the log doesn't have yet as dictionary
+    /// records.
+    #[cold]
+    fn write_dictionary(&mut self) {
+        let strings = string_registry::registered_strings();
+        let embedded = self.embedded_strings.min(strings.len());
+        self.embedded_strings = strings.len();
+        // Small enough to fit a buffer along with the prologue and reserve,
+        // and a 16-bit length
+        let limit = (CAP / 4).min(LONG_LENGTH as usize - 9);
+        for payload in string_registry::dictionary_payloads(&strings[embedded..], limit) {
+            let _ = self.write_with(RECORD_TYPE_DICTIONARY, RecordMeta::default(), 0, payload.len(), |out| out.copy_from_slice(&payload));
+        }
+    }
+
+    /// Returns whether the dictionary channel has a metadata buffer to
+    /// publish: the stream header, or strings registered since the last one.
+    fn metadata_due(&self) -> bool {
+        self.metadata_header_pending || string_registry::registered_count() != self.published_strings
+    }
+
+    /// Hands the strings registered since the last metadata buffer to the
+    /// handler in a metadata buffer, see `set_dictionary_channel`.
+    #[cold]
+    fn publish_dictionary(&mut self) {
+        let strings = string_registry::registered_strings();
+        let published = self.published_strings.min(strings.len());
+        let mut buffer = std::mem::take(&mut self.metadata_buffer);
+        buffer.clear();
+        buffer.extend_from_slice(&[0; BUFFER_HEADER_SIZE]);
+
+        // An empty record still marks the buffer when there are no strings
+        let mut payloads = string_registry::dictionary_payloads(&strings[published..], LONG_LENGTH as usize - 1);
+        if payloads.is_empty() {
+            payloads.push(Vec::new());
+        }
+        for payload in payloads {
+            buffer.extend_from_slice(&[RECORD_TYPE_METADATA, 0, 0, 0, 0, 0]);
+            buffer.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+            buffer.extend_from_slice(&payload);
+            if !buffer.len().is_multiple_of(2) {
+                buffer.push(0);
+            }
+        }
+        if self.metadata_header_pending {
+            buffer.extend_from_slice(&self.stream_header_record());
+        }
+        let size = buffer.len();
+        buffer[..BUFFER_HEADER_SIZE].copy_from_slice(&(size as u64).to_le_bytes());
+
+        let handled = self.call_handler(buffer.as_ptr(), size);
+        self.metadata_buffer = buffer;
+        match handled {
+            // Published again with the next write
+            Some(Err(panic)) => self.handler_panicked(panic),
+            handled => {
+                self.published_strings = strings.len();
+                self.metadata_header_pending = false;
+                if handled.is_some() {
+                    self.buffers_flushed += 1;
+                    self.bytes_flushed += size as u64;
+                }
+            }
+        }
+    }
+
+    /// Calls the handler with a filled buffer, unless a panic disabled it,
+    /// catching its panics.
+    fn call_handler(&mut self, buffer: *const u8, size: usize) -> Option<std::thread::Result<()>> {
+        if self.handler_status.disabled {
+            self.handler_status.buffers_lost += 1;
+            None
+        } else {
+            let handler = &self.handler;
+            // The buffer holds `size` written bytes
+            let data = unsafe { std::slice::from_raw_parts(buffer, size) };
+            Some(catch_unwind(AssertUnwindSafe(|| handler.handle_buffer(data))))
+        }
+    }
+
+    /// Hands a filled buffer the Logger owns to the handler, unless a panic
+    /// disabled it, catching its panics, and takes the buffer it returns as
+    /// the inactive buffer.
+    fn give_handler(&mut self, buffer: *mut u8, size: usize, records: u64) -> Option<std::thread::Result<()>> {
+        if self.handler_status.disabled {
+            self.handler_status.buffers_lost += 1;
+            return None;
+        }
+        let handler = &self.handler;
+        let owned = LogBuffer::from_raw(buffer, CAP, size);
+        let dropped_before = handler.buffers_dropped();
+        let (next, handled) = match catch_unwind(AssertUnwindSafe(|| handler.handle_owned_buffer(owned))) {
+            Ok(next) if next.capacity() == CAP => (next.into_raw(), Ok(())),
+            Ok(_) => (alloc_buffer::<CAP>(), Ok(())),
+            // The buffer went with the unwinding handler
+            Err(panic) => (alloc_buffer::<CAP>(), Err(panic)),
+        };
+        self.inactive_buffer = next;
+        if handled.is_ok() && handler.buffers_dropped() > dropped_before {
+            self.note_dropped(records.min(u32::MAX as u64) as u32);
+        }
+        Some(handled)
+    }
+
+    /// Writes an internal event record if it fits in the active buffer.
+    fn write_internal(&mut self, event: InternalEvent, value: u32) {
+        let value = value.to_le_bytes();
+        let args: [&[u8]; 1] = [&value];
+        let codec = self.codec;
+        if self.write_pos + self.prologue_size() + INTERNAL_RECORD_MAX_SIZE <= CAP && self.lease_buffer() {
+            self.write_prologue();
+            let (time, is_base) = self.next_record_time();
+            self.write_record(time, is_base, RECORD_TYPE_NORMAL, RecordMeta::default(), event.format_id(), codec.encoded_len(&args), |out| codec.encode(&args, out));
+        }
+    }
+
+    /// Writes a summary record for each format and level skipped since the
+    /// last summaries, and starts counting anew.
+    fn write_suppression_summaries(&mut self) {
+        let now = self.clock.instant_now();
+        let Some(suppression) = &mut self.suppression else {
+            return;
+        };
+        let millis = (now - suppression.since).as_millis().min(u32::MAX as u128) as u32;
+        let mut counts: Vec<_> = suppression.counts.drain().collect();
+        counts.sort_unstable();
+        suppression.since = now;
+
+        let codec = self.codec;
+        let event = InternalEvent::Suppressed.format_id();
+        for ((format_id, level), count) in counts {
+            let (count, format_id, millis) = (count.to_le_bytes(), format_id.to_le_bytes(), millis.to_le_bytes());
+            let args: [&[u8]; 3] = [&count, &format_id, &millis];
+            let meta = RecordMeta { level: Some(level), ..RecordMeta::default() };
+            // Counts were taken, so this doesn't come back here
+            let _ = self.write_with(RECORD_TYPE_NORMAL, meta, event, codec.encoded_len(&args), |out| codec.encode(&args, out));
+        }
+    }
+
+    /// Writes a summary record for each window closed with duplicates
+    /// counted, or with `all` for each window with duplicates counted.
+    fn write_duplicate_summaries(&mut self, all: bool) {
+        let now = self.clock.instant_now();
+        let Some(duplicates) = &mut self.duplicates else {
+            return;
+        };
+        let summaries = duplicates.take_summaries(now, all);
+
+        let codec = self.codec;
+        let event = InternalEvent::DuplicatesSuppressed.format_id();
+        for summary in summaries {
+            let (count, format_id, millis) = (summary.suppressed.to_le_bytes(), summary.format_id.to_le_bytes(), summary.millis.to_le_bytes());
+            let args: [&[u8]; 3] = [&count, &format_id, &millis];
+            let meta = RecordMeta { channel: summary.channel, level: summary.level, truncated: false };
+            // Counts were taken, so this doesn't come back here
+            let _ = self.write_with(RECORD_TYPE_NORMAL, meta, event, codec.encoded_len(&args), |out| codec.encode(&args, out));
+        }
+    }
+
+    /// Reports drops and registry growth observed since the last write.
+    fn emit_pending_internal_events(&mut self) {
+        if self.pending_drops > 0 {
+            let dropped = std::mem::take(&mut self.pending_drops);
+            self.write_internal(InternalEvent::Drops, dropped);
+        }
+
+        let registry_len = string_registry::registered_count();
+        if registry_len != self.known_registry_len {
+            self.known_registry_len = registry_len;
+            self.write_internal(InternalEvent::RegistryGrowth, registry_len as u32);
+        }
+    }
+
+    /// Flushes the current buffer, ensuring all data is processed.
+    /// 
+    /// This method forces the current buffer to be switched and processed
+    /// by the handler, even if it's not full. This is useful when you need
+    /// to ensure all logs are immediately visible.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # use std::fs::File;
+    /// # use std::io::Write;
+    /// # use std::cell::RefCell;
+    /// # struct FileHandler(RefCell<File>);
+    /// # impl BufferHandler for FileHandler {
+    /// #     fn handle_buffer(&self, data: &[u8]) {
+    /// #         self.0.borrow_mut().write_all(data).unwrap();
+    /// #     }
+    /// # }
+    /// # let file = File::create("log.bin").unwrap();
+    /// # let mut logger = Logger::<1_000_000>::new(FileHandler(RefCell::new(file)));
+    /// log_record!(logger, "Critical operation starting", );
+    /// // Ensure log is written immediately
+    /// logger.flush();
+    /// ```
+    pub fn flush(&mut self) {
+        self.write_suppression_summaries();
+        self.write_duplicate_summaries(true);
+        self.write_count_table();
+        if self.write_pos > BUFFER_HEADER_SIZE {
+            self.switch_buffers();
+        }
+    }
+
+    /// Starts a critical section, around an operation that may crash the
+    /// process.
+    /// 
+    /// The records logged so far are flushed, and the call returns once
+    /// the handler has them on durable storage (`BufferHandler::sync`), so
+    /// a postmortem sees everything that led up to the operation. Log
+    /// records written until the matching `mark_critical_end` carry
+    /// `Tags::CRITICAL`, which `LogReader::set_tag_filter` selects; they
+    /// are written as tagged records, without type tags, unless the
+    /// stream leaves tags out (`set_format_features`).
+    /// 
+    /// Sections nest: records are critical until the outermost one ends,
+    /// and every begin and end flushes. A handler that panics while
+    /// syncing is treated by the `HandlerPanicPolicy`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, log_record};
+    /// # use binary_logger::handlers::AsyncFileHandler;
+    /// # let path = std::env::temp_dir().join("critical.blog");
+    /// let mut logger = Logger::<65536>::new(AsyncFileHandler::create(path, 4).unwrap());
+    /// log_record!(logger, "Migrating table {}", "orders").unwrap();
+    /// logger.mark_critical_begin();
+    /// log_record!(logger, "Swapping {} files", 2).unwrap();
+    /// // ... the operation that may crash
+    /// logger.mark_critical_end();
+    /// ```
+    pub fn mark_critical_begin(&mut self) {
+        self.flush_durably();
+        self.critical_depth += 1;
+    }
+
+    /// Ends the critical section started last, flushing its records and
+    /// waiting until the handler has them on durable storage; see
+    /// `mark_critical_begin`. Does nothing else outside a section.
+    pub fn mark_critical_end(&mut self) {
+        self.flush_durably();
+        self.critical_depth = self.critical_depth.saturating_sub(1);
+    }
+
+    /// Returns whether the Logger is inside a critical section.
+    pub fn in_critical_section(&self) -> bool {
+        self.critical_depth > 0
+    }
+
+    /// Begins a segment of the log named `name`, such as a deployment or a
+    /// test run, ending the segment begun before if any.
+    /// 
+    /// The segment's records are those written until `end_segment`, between
+    /// an `InternalEvent::SegmentBegin` and an `InternalEvent::SegmentEnd`
+    /// record carrying the name. Readers list them with
+    /// `analysis::segments` and keep to one with
+    /// `LogReader::set_segment_filter`; `blog-segments` and
+    /// `blog-grep --segment` do the same for files. Segments don't nest, and
+    /// the boundary records are written whatever the level.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// logger.begin_segment("deploy v1.2.3").unwrap();
+    /// log_record!(logger, "Migrated {} tables", 12).unwrap();
+    /// logger.end_segment().unwrap();
+    /// ```
+    pub fn begin_segment(&mut self, name: &str) -> io::Result<()> {
+        self.end_segment()?;
+        self.write_segment_event(InternalEvent::SegmentBegin, name)?;
+        self.segment = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Ends the segment begun last, see `begin_segment`. Does nothing
+    /// outside a segment. An open segment also ends when the Logger is
+    /// dropped.
+    pub fn end_segment(&mut self) -> io::Result<()> {
+        match self.segment.take() {
+            Some(name) => self.write_segment_event(InternalEvent::SegmentEnd, &name),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the name of the segment the Logger is in, if any.
+    pub fn segment(&self) -> Option<&str> {
+        self.segment.as_deref()
+    }
+
+    /// Writes a segment boundary record for the segment `name`.
+    fn write_segment_event(&mut self, event: InternalEvent, name: &str) -> io::Result<()> {
+        let args: [&[u8]; 1] = [name.as_bytes()];
+        let codec = self.codec;
+        self.write_with(RECORD_TYPE_NORMAL, RecordMeta::default(), event.format_id(), codec.encoded_len(&args), |out| codec.encode(&args, out))
+    }
+
+    /// Flushes the current buffer and syncs the handler, unless a panic
+    /// disabled it.
+    fn flush_durably(&mut self) {
+        self.flush();
+        if self.handler_status.disabled {
+            return;
+        }
+        let handler = &self.handler;
+        if let Err(panic) = catch_unwind(AssertUnwindSafe(|| handler.sync())) {
+            self.handler_panicked(panic);
+        }
+    }
+
+    /// Restarts the Logger in the child process after `fork()`, writing to
+    /// `handler` from now on.
+    /// 
+    /// The child inherits a copy of the Logger with the parent's pending
+    /// records, which the parent hands to its own handler, and with the
+    /// parent's handler, which typically shares the parent's file. Call
+    /// this in the child before logging: it discards the inherited records
+    /// without handing them over, forgets the parent's handler without
+    /// dropping it (dropping could flush the parent's data a second time),
+    /// and starts a new stream for `handler`. The new stream has its own
+    /// stream header, dictionary and audit chain, a new clock base,
+    /// counters from zero, and no health file, which belongs to the parent.
+    /// Global sequence numbers restart at 0 in the child.
+    /// 
+    /// The stream starts with an `InternalEvent::Forked` record carrying the
+    /// child's and the parent's process IDs, which tells which process
+    /// wrote the records that follow.
+    /// 
+    /// Only the thread that called `fork()` exists in the child, so other
+    /// threads' Loggers are never written to there. As with any `fork()` of
+    /// a multi-threaded program, locks held by other threads at the fork
+    /// stay held in the child: fork while no other thread registers strings
+    /// at runtime or leases from the Logger's `BufferPool`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{Logger, BufferHandler, log_record};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// let mut logger = Logger::<4096>::new(NullHandler);
+    /// log_record!(logger, "Daemon {} starting", 1).unwrap();
+    /// 
+    /// // In the child, right after fork(), with a sink of its own
+    /// logger.post_fork(NullHandler);
+    /// log_record!(logger, "Worker {} ready", 1).unwrap();
+    /// ```
+    pub fn post_fork(&mut self, handler: impl BufferHandler + 'static) {
+        std::mem::forget(std::mem::replace(&mut self.handler, Box::new(handler)));
+        self.handler_status = HandlerStatus::default();
+
+        let parent = self.pid;
+        self.pid = std::process::id();
+        if SEQUENCE_PID.swap(self.pid, Ordering::Relaxed) != self.pid {
+            GLOBAL_SEQUENCE.store(0, Ordering::Relaxed);
+        }
+
+        self.write_pos = BUFFER_HEADER_SIZE;
+        self.last_format_id = None;
+        self.counts.clear();
+        self.clock.reset();
+        self.time_anchor_pending = true;
+        self.stream_header_pending = true;
+        self.embedded_strings = 0;
+        self.published_strings = 0;
+        self.metadata_header_pending = self.dictionary_channel;
+        self.chain_pending = self.audit_chain;
+        self.retention_pending = !self.retention.is_empty();
+        self.clock_offset_pending = self.clock_offset.is_some();
+        self.last_buffer_hash = None;
+        // The parent's heartbeat thread wasn't forked
+        if let Some(health) = self.health.take() {
+            health.abandon();
+        }
+        self.published = None;
+        if let Some(suppression) = &mut self.suppression {
+            suppression.counts.clear();
+        }
+        if let Some(duplicates) = &mut self.duplicates {
+            duplicates.clear();
+        }
+        self.pending_drops = 0;
+        self.dropped_records = 0;
+        self.sampled_out = 0;
+        self.duplicates_suppressed = 0;
+        self.buffers_flushed = 0;
+        self.bytes_flushed = 0;
+
+        let (pid, parent) = (self.pid.to_le_bytes(), parent.to_le_bytes());
+        let args: [&[u8]; 2] = [&pid, &parent];
+        let codec = self.codec;
+        let _ = self.write_with(RECORD_TYPE_NORMAL, RecordMeta::default(), InternalEvent::Forked.format_id(), codec.encoded_len(&args), |out| codec.encode(&args, out));
+        // The child's records belong to the parent's open segment
+        if let Some(name) = self.segment.clone() {
+            let _ = self.write_segment_event(InternalEvent::SegmentBegin, &name);
+        }
+        // Like other internal events, not counted as written
+        self.records_written = 0;
+        self.buffer_first_record = 0;
+    }
+
+    /// Switches the active and inactive buffers, and processes the filled buffer.
+    /// 
+    /// This internal method handles the double-buffering mechanism. When the active
+    /// buffer is full or explicitly flushed, this method:
+    /// 1. Writes the buffer size header to the filled buffer
+    /// 2. Swaps the active and inactive buffers
+    /// 3. Calls the handler to process the filled buffer
+    /// 4. Resets the write position for the new active buffer
+    fn switch_buffers(&mut self) {
+        self.write_count_table();
+        // The strings of the buffer's records precede it
+        if self.dictionary_channel && self.metadata_due() {
+            self.publish_dictionary();
+        }
+        if self.instrumentation_enabled {
+            self.write_internal(InternalEvent::BufferSwitchStart, self.write_pos as u32);
+        }
+
+        // Write buffer length at start
+        unsafe {
+            *(self.active_buffer as *mut u64) = (self.write_pos as u64).to_le();
+        }
+
+        // Swap buffers
+        std::mem::swap(&mut self.active_buffer, &mut self.inactive_buffer);
+        let filled_buffer = self.inactive_buffer;
+        let filled_size = self.write_pos;
+        let filled_records = self.records_written - std::mem::replace(&mut self.buffer_first_record, self.records_written);
+        self.write_pos = BUFFER_HEADER_SIZE;
+        self.last_format_id = None;
+        self.time_anchor_pending = true;
+        self.retention_pending = !self.retention.is_empty();
+        self.clock_offset_pending = self.clock_offset.is_some();
+        if self.delta_timestamps || self.dictionary_channel {
+            // Deltas don't reach back across buffers, and readers attached
+            // to the dictionary channel can start at any buffer
+            self.clock.reset();
+        }
+
+        // Link the next buffer to this one
+        if self.audit_chain {
+            let filled = unsafe { std::slice::from_raw_parts(filled_buffer, filled_size) };
+            self.last_buffer_hash = Some(Sha256::digest(filled).into());
+            self.chain_pending = true;
+        }
+
+        // Call handler with filled buffer
+        if let Some(published) = &self.published {
+            published.handler_started();
+        }
+        if let Some(health) = &self.health {
+            health.handler_started();
+        }
+        let started = (self.instrumentation_enabled || self.health.is_some()).then(|| self.clock.instant_now());
+        // The Logger's state is ready for the next buffer, whatever the
+        // handler does
+        let handled = match self.buffer_pool.is_some() {
+            true => self.call_handler(filled_buffer, filled_size),
+            false => self.give_handler(filled_buffer, filled_size, filled_records),
+        };
+        if let Some(health) = &self.health {
+            health.handler_returned();
+        }
+        if let Some(pool) = &self.buffer_pool {
+            // The next write leases a buffer again
+            pool.release(filled_buffer);
+            self.inactive_buffer = std::ptr::null_mut();
+        }
+        if handled.is_some() {
+            self.buffers_flushed += 1;
+            self.bytes_flushed += filled_size as u64;
+        }
+
+        if let Some(started) = started.filter(|_| handled.is_some()) {
+            let elapsed = self.clock.instant_now().saturating_duration_since(started);
+            if let Some(health) = &self.health {
+                health.flushed(filled_size, elapsed, self.write_pos - BUFFER_HEADER_SIZE, self.dropped_records);
+            }
+            if self.instrumentation_enabled {
+                let micros = elapsed.as_micros().min(u32::MAX as u128) as u32;
+                self.write_internal(InternalEvent::BufferSwitchEnd, micros);
+            }
+        }
+
+        if let Some(Err(panic)) = handled {
+            self.handler_panicked(panic);
+        }
+        if let Some(published) = &self.published {
+            published.update(&self.stats());
+            published.handler_returned(&self.handler_status);
+        }
+    }
+
+    /// Records a panic of the BufferHandler and applies the panic policy.
+    fn handler_panicked(&mut self, panic: Box<dyn std::any::Any + Send>) {
+        let status = &mut self.handler_status;
+        status.panics += 1;
+        status.buffers_lost += 1;
+        status.last_panic = panic.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned());
+        match self.handler_panic_policy {
+            // Panicking again while unwinding would abort
+            HandlerPanicPolicy::Propagate if !std::thread::panicking() => resume_unwind(panic),
+            HandlerPanicPolicy::Propagate | HandlerPanicPolicy::Retry => {}
+            HandlerPanicPolicy::Disable => status.disabled = true,
+        }
+    }
+}
+
+impl<const CAP: usize> Drop for Logger<CAP> {
+    fn drop(&mut self) {
+        self.write_suppression_summaries();
+        self.write_duplicate_summaries(true);
+        let _ = self.end_segment();
+        self.write_count_table();
+
+        // Ensure last buffer is written
+        if self.write_pos > BUFFER_HEADER_SIZE {
+            self.switch_buffers();
+        }
+
+        // Clean up buffers
+        match &self.buffer_pool {
+            Some(pool) if !self.active_buffer.is_null() => pool.release(self.active_buffer),
+            Some(_) => {}
+            None => {
+                free_buffer::<CAP>(self.active_buffer);
+                free_buffer::<CAP>(self.inactive_buffer);
+            }
+        }
+    }
+}
+
+/// Logs a record with the given format string and arguments.
+/// 
+/// This macro is the primary interface for logging. It:
+/// 1. Automatically registers and deduplicates format strings
+/// 2. Efficiently serializes arguments with the logger's codec
+/// 3. Writes the serialized record to the logger
+/// 
+/// # Arguments
+/// 
+/// * `logger` - The Logger instance to write to
+/// * `channel` - Optional `channel: "name"` selecting the logical stream
+/// * `fmt` - A format string literal, using `{}` placeholders like in `println!`
+/// * `args...` - Zero or more arguments corresponding to placeholders
+/// 
+/// # Returns
+/// 
+/// IO Result for the logging operation
+/// 
+/// # Examples
+/// 
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use std::fs::File;
+/// # use std::io::Write;
+/// # use std::cell::RefCell;
+/// # struct FileHandler(RefCell<File>);
+/// # impl BufferHandler for FileHandler {
+/// #     fn handle_buffer(&self, data: &[u8]) {
+/// #         self.0.borrow_mut().write_all(data).unwrap();
+/// #     }
+/// # }
+/// # let file = File::create("log.bin").unwrap();
 /// # let mut logger = Logger::<1_000_000>::new(FileHandler(RefCell::new(file)));
 /// // Basic usage
 /// log_record!(logger, "Hello, world!", );
@@ -343,49 +2796,657 @@ impl<const CAP: usize> Drop for Logger<CAP> {
 /// // With complex types
 /// let values = vec![1, 2, 3];
 /// log_record!(logger, "Length: {}", values.len());
+/// 
+/// // On a named channel
+/// log_record!(logger, channel: "audit", "User {} logged in", 42);
 /// ```
 #[macro_export]
 macro_rules! log_record {
-    ($logger:expr, $fmt:literal, $($arg:expr),* $(,)?) => {{
+    ($logger:expr, channel: $channel:literal, $fmt:literal, $($args:tt)*) => {{
+        $crate::__log_site!($fmt, Some($channel), None);
+        let logger = $logger.macro_target();
+        // Channel names are interned like format strings
+        let channel = $crate::__string_id!($channel);
+        let format_id = $crate::__string_id!($fmt);
+        let capture = $crate::arg_types::ArgCapture::with_string_limit(logger.capture_limits().string_limit(None)).with_common_strings(logger.common_strings());
+        let result = logger.write_captured(None, channel, format_id, &$crate::__log_args!(capture [] $($args)*), &capture);
+        capture.register(format_id);
+        result
+    }};
+    ($logger:expr, $fmt:literal, $($args:tt)*) => {{
+        $crate::__log_site!($fmt, None, None);
+        let logger = $logger.macro_target();
         // Register format string on first use
-        let format_id = $crate::string_registry::register_string($fmt);
-        
-        // Write parameters to buffer
-        let mut temp = [0u8; 1024];
-        let mut pos = 0;
-
-        // Count arguments for header
-        let arg_count = 0u8 $(+ { let _ = &$arg; 1})*;
-        temp[pos] = arg_count;
-        pos += 1;
-        
-        $(
-            // Write argument size
-            let size = std::mem::size_of_val(&$arg);
-            temp[pos..pos+4].copy_from_slice(&(size as u32).to_le_bytes());
-            pos += 4;
-
-            // Write data
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    &$arg as *const _ as *const u8,
-                    temp.as_mut_ptr().add(pos),
-                    size
-                );
+        let format_id = $crate::__string_id!($fmt);
+
+        // Hand the bytes of each argument to the logger's codec, noting the
+        // types the reader can't infer
+        let capture = $crate::arg_types::ArgCapture::with_string_limit(logger.capture_limits().string_limit(None)).with_common_strings(logger.common_strings());
+        let result = logger.write_captured(None, 0, format_id, &$crate::__log_args!(capture [] $($args)*), &capture);
+        capture.register(format_id);
+        result
+    }};
+}
+
+/// Captures the comma-separated arguments of a logging macro into an array
+/// of byte slices, one argument at a time.
+/// 
+/// Literals are matched before expressions so string literals can be
+/// interned (see `arg_types::Interned`); other literals are captured as
+/// they are. Empty arguments are skipped, which lets macros forwarding
+/// arguments join lists with commas freely.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_args {
+    ($capture:ident [$($done:expr),*]) => {
+        [$($done),*]
+    };
+    ($capture:ident [$($done:expr),*] , $($rest:tt)*) => {
+        $crate::__log_args!($capture [$($done),*] $($rest)*)
+    };
+    ($capture:ident [$($done:expr),*] $arg:literal $(, $($rest:tt)*)?) => {
+        $crate::__log_args!($capture [$($done,)* $crate::__log_arg!($capture, {
+            #[allow(unused_imports)]
+            use $crate::arg_types::{InternLiteral as _, PlainLiteral as _};
+            (&$crate::arg_types::Literal($arg)).literal_arg()
+        })] $($($rest)*)?)
+    };
+    ($capture:ident [$($done:expr),*] $arg:expr $(, $($rest:tt)*)?) => {
+        $crate::__log_args!($capture [$($done,)* $crate::__log_arg!($capture, $arg)] $($($rest)*)?)
+    };
+}
+
+/// Captures one argument of a logging macro (see `arg_types::capture`),
+/// encoded field by field if its type implements `Loggable` (see
+/// `loggable::StructArg`).
+#[cfg(not(stable_compat))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_arg {
+    ($capture:ident, $arg:expr) => {
+        $crate::arg_types::capture({
+            #[allow(unused_imports)]
+            use $crate::loggable::{EncodeStruct as _, PlainArg as _};
+            (&$crate::loggable::StructArg(&$arg)).struct_arg(&$capture)
+        }, &$capture)
+    };
+}
+
+/// Captures one argument of a logging macro (see `arg_types::capture`),
+/// encoded field by field if its type implements `Loggable` (see
+/// `loggable::StructArg`), and as its raw bytes if its type doesn't
+/// implement `LogArg` (see `arg_types::Arg`).
+#[cfg(stable_compat)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_arg {
+    ($capture:ident, $arg:expr) => {
+        $crate::arg_types::capture({
+            #[allow(unused_imports)]
+            use $crate::arg_types::{EncodedArg as _, RawArgFallback as _};
+            #[allow(unused_imports)]
+            use $crate::loggable::{EncodeStruct as _, PlainArg as _};
+            (&$crate::arg_types::Arg((&$crate::loggable::StructArg(&$arg)).struct_arg(&$capture))).log_arg()
+        }, &$capture)
+    };
+}
+
+/// Logs a record with a severity level, like `log_record!`.
+/// 
+/// The level is the second argument; a channel may follow it as in
+/// `log_record!`. The level is stored in the record header, where readers
+/// and routing handlers find it without decoding the payload.
+/// 
+/// Records below the Logger's level (`Logger::set_level`) are skipped
+/// before their arguments are evaluated, at the cost of a comparison.
+/// Records below `level::STATIC_LEVEL`, set with the
+/// `BINARY_LOGGER_MIN_LEVEL` variable at build time, are removed at
+/// compile time.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record_at};
+/// # use binary_logger::level::Level;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// log_record_at!(logger, Level::Warn, "Disk {}% full", 91).unwrap();
+/// log_record_at!(logger, Level::Error, channel: "audit", "Login failed for {}", 42).unwrap();
+/// ```
+#[macro_export]
+macro_rules! log_record_at {
+    ($logger:expr, $level:expr, channel: $channel:literal, $fmt:literal, $($args:tt)*) => {{
+        $crate::__log_site!($fmt, Some($channel), Some(::std::stringify!($level)));
+        let level: $crate::level::Level = $level;
+        if level < $crate::level::STATIC_LEVEL {
+            ::std::io::Result::Ok(())
+        } else {
+            let logger = $logger.macro_target();
+            let format_id = $crate::__string_id!($fmt);
+            if logger.level_enabled(level) {
+                let channel = $crate::__string_id!($channel);
+                let capture = $crate::arg_types::ArgCapture::with_string_limit(logger.capture_limits().string_limit(Some(level))).with_common_strings(logger.common_strings());
+                let result = logger.write_captured(Some(level), channel, format_id, &$crate::__log_args!(capture [] $($args)*), &capture);
+                capture.register(format_id);
+                result
+            } else {
+                logger.skip_level(level, format_id);
+                ::std::io::Result::Ok(())
+            }
+        }
+    }};
+    ($logger:expr, $level:expr, $fmt:literal, $($args:tt)*) => {{
+        $crate::__log_site!($fmt, None, Some(::std::stringify!($level)));
+        let level: $crate::level::Level = $level;
+        if level < $crate::level::STATIC_LEVEL {
+            ::std::io::Result::Ok(())
+        } else {
+            let logger = $logger.macro_target();
+            let format_id = $crate::__string_id!($fmt);
+            if logger.level_enabled(level) {
+                let capture = $crate::arg_types::ArgCapture::with_string_limit(logger.capture_limits().string_limit(Some(level))).with_common_strings(logger.common_strings());
+                let result = logger.write_captured(Some(level), 0, format_id, &$crate::__log_args!(capture [] $($args)*), &capture);
+                capture.register(format_id);
+                result
+            } else {
+                logger.skip_level(level, format_id);
+                ::std::io::Result::Ok(())
+            }
+        }
+    }};
+}
+
+/// Logs a record carrying user tags, optionally with a level.
+/// 
+/// `tags` is a `tags::Tags` expression, such as `AUDIT | BILLING` with tags
+/// from `tags::register_tag`. Records whose tags the Logger doesn't write
+/// (`Logger::set_tag_mask`) are skipped before their arguments are
+/// captured. With an empty set the record is written untagged.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_tagged};
+/// # use binary_logger::level::Level;
+/// # use binary_logger::tags::register_tag;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// # let mut logger = Logger::<4096>::new(NullHandler);
+/// let (audit, billing) = (register_tag("audit"), register_tag("billing"));
+/// log_tagged!(logger, tags = audit | billing, "Invoice {} issued", 1001).unwrap();
+/// log_tagged!(logger, Level::Warn, tags = billing, "Invoice {} overdue", 998).unwrap();
+/// ```
+#[macro_export]
+macro_rules! log_tagged {
+    ($logger:expr, tags = $tags:expr, $fmt:literal, $($args:tt)*) => {{
+        $crate::__log_site!($fmt, None, None);
+        let tags: $crate::tags::Tags = $tags;
+        let logger = $logger.macro_target();
+        if logger.tags_enabled(tags) {
+            let format_id = $crate::__string_id!($fmt);
+            let capture = $crate::arg_types::ArgCapture::with_string_limit(logger.capture_limits().string_limit(None)).with_common_strings(logger.common_strings());
+            let result = logger.write_tagged(None, tags, format_id, &$crate::__log_args!(capture [] $($args)*), &capture);
+            capture.register(format_id);
+            result
+        } else {
+            ::std::io::Result::Ok(())
+        }
+    }};
+    ($logger:expr, $level:expr, tags = $tags:expr, $fmt:literal, $($args:tt)*) => {{
+        $crate::__log_site!($fmt, None, Some(::std::stringify!($level)));
+        let level: $crate::level::Level = $level;
+        let tags: $crate::tags::Tags = $tags;
+        let logger = $logger.macro_target();
+        if level >= $crate::level::STATIC_LEVEL && logger.tags_enabled(tags) {
+            let format_id = $crate::__string_id!($fmt);
+            if logger.level_enabled(level) {
+                let capture = $crate::arg_types::ArgCapture::with_string_limit(logger.capture_limits().string_limit(Some(level))).with_common_strings(logger.common_strings());
+                let result = logger.write_tagged(Some(level), tags, format_id, &$crate::__log_args!(capture [] $($args)*), &capture);
+                capture.register(format_id);
+                result
+            } else {
+                logger.skip_level(level, format_id);
+                ::std::io::Result::Ok(())
             }
-            pos += size;
-        )*
-        
-        // Write the complete record
-        let payload = &temp[..pos];
-        $logger.write(format_id, payload)
+        } else {
+            ::std::io::Result::Ok(())
+        }
+    }};
+}
+
+/// Logs a record if the Logger's sampler keeps its key, optionally with a
+/// level.
+/// 
+/// `key` identifies the entity the record is about, such as a user ID, and
+/// is usually one of the arguments too. All records of a kept key are
+/// written, so sampled entities have complete event sequences (see
+/// `sampling`). Without a sampler every record is written.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_sampled};
+/// # use binary_logger::level::Level;
+/// # use binary_logger::sampling::KeySampler;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// # let mut logger = Logger::<4096>::new(NullHandler);
+/// logger.set_sampler(Some(KeySampler::new(0.05)));
+/// let (user, item) = (1234u64, 17);
+/// log_sampled!(logger, key = user, "User {} added item {}", user, item).unwrap();
+/// log_sampled!(logger, Level::Warn, key = user, "User {} payment declined", user).unwrap();
+/// ```
+#[macro_export]
+macro_rules! log_sampled {
+    ($logger:expr, key = $key:expr, $fmt:literal, $($args:tt)*) => {{
+        let logger = $logger.macro_target();
+        if logger.sample(&$key) {
+            $crate::log_record!(logger, $fmt, $($args)*)
+        } else {
+            ::std::io::Result::Ok(())
+        }
+    }};
+    ($logger:expr, $level:expr, key = $key:expr, $fmt:literal, $($args:tt)*) => {{
+        let logger = $logger.macro_target();
+        if logger.sample(&$key) {
+            $crate::log_record_at!(logger, $level, $fmt, $($args)*)
+        } else {
+            ::std::io::Result::Ok(())
+        }
     }};
 }
 
+/// Counts an event for an ultra-hot counter without writing a record for
+/// it, see `Logger::count`.
+/// 
+/// The event name is a literal, interned like format strings.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_count};
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// for _ in 0..1000 {
+///     log_count!(logger, "cache_miss").unwrap();
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_count {
+    ($logger:expr, $name:literal) => {
+        $logger.count($crate::__string_id!($name))
+    };
+}
+
+/// Returns the in-memory bytes of a `log_record!` argument.
+#[doc(hidden)]
+pub fn arg_bytes<T: ?Sized>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of_val(value)) }
+}
+
+/// Bytes `log_record!` hands to the codec for an argument, and how to
+/// decode them.
+/// 
+/// Arguments are captured as their in-memory bytes (`arg_bytes`), except
+/// for wrappers such as `flags::Flags` that provide their own encoding.
+/// Types the reader can't tell apart by size, such as `f32`, report their
+/// kind (see `arg_types`), and types encoded when logged, such as
+/// `LogInstant`, write their bytes to `out` in `encode`. `type_tag` names
+/// the type in records with type tags (see `Logger::set_type_tags`).
+///
+/// On nightly every type is an argument, by a blanket implementation the
+/// others specialize. Stable compilers (`stable-compat`) only see the
+/// implementations of this crate, and `log_record!` captures other types
+/// through `arg_types::RawArg`; the provided methods are the defaults.
+#[doc(hidden)]
+pub trait LogArg {
+    fn log_bytes(&self) -> &[u8] {
+        arg_bytes(self)
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Raw
+    }
+
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::of_kind(self.arg_kind())
+    }
+
+    fn encode(&self, _out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        None
+    }
+}
+
+/// The blanket implementation, left as tokens until expanded: nightly
+/// compilers flag `default fn` even in items a cfg leaves out.
+#[cfg(not(stable_compat))]
+macro_rules! blanket_log_arg {
+    () => {
+        impl<T> LogArg for T {
+            default fn log_bytes(&self) -> &[u8] {
+                arg_bytes(self)
+            }
+
+            default fn arg_kind(&self) -> ArgKind {
+                ArgKind::Raw
+            }
+
+            default fn type_tag(&self) -> TypeTag {
+                TypeTag::of_kind(self.arg_kind())
+            }
+
+            default fn encode(&self, _out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+                None
+            }
+        }
+    };
+}
+
+#[cfg(not(stable_compat))]
+blanket_log_arg!();
+
 /// Size of the buffer header in bytes
 /// 
 /// The first 8 bytes of each buffer are used to store the total size
 /// of valid data in the buffer. This value is always 8.
-const BUFFER_HEADER_SIZE: usize = 8;  // 8 bytes for buffer length
+pub(crate) const BUFFER_HEADER_SIZE: usize = 8;  // 8 bytes for buffer length
+
+/// Largest buffer capacity of a Logger, 64 MiB; readers of compressed
+/// frames (see `compression`) refuse frames holding more.
+pub const MAX_BUFFER_SIZE: usize = 1 << 26;
+
+/// Flag of the size field of a buffer header, marking a compressed frame
+/// (see `compression`), which builds without a compression feature can't
+/// read.
+pub(crate) const COMPRESSED_FRAME: u64 = 1 << 63;
+
+/// Size of the fixed part of a record header in bytes
+/// 
+/// `type(1) | flags(1) | relative_ts(2) | format_id(2) | payload_len(2)`
+pub(crate) const RECORD_HEADER_SIZE: usize = 8;
+
+/// Record type for a normal record with a relative timestamp
+pub(crate) const RECORD_TYPE_NORMAL: u8 = 0;
+
+/// Record type for a record that resets the base timestamp
+pub(crate) const RECORD_TYPE_BASE: u8 = 1;
+
+/// Size of a base record without a log entry
+const TIME_BASE_RECORD_SIZE: usize = RECORD_HEADER_SIZE + 8;
+
+/// Record type for the stream header written before the first record
+pub(crate) const RECORD_TYPE_STREAM_HEADER: u8 = 2;
+
+/// Magic bytes at the start of the stream header payload
+pub(crate) const STREAM_MAGIC: [u8; 4] = *b"BLOG";
+
+/// Version of the stream format described by the stream header
+pub(crate) const STREAM_VERSION: u8 = 1;
+
+/// Size of the stream header payload:
+/// `magic(4) | version(1) | codec_id(1) | features u32`
+pub(crate) const STREAM_HEADER_PAYLOAD_SIZE: usize = 10;
+
+/// Size of the stream header payload before the feature set, the size of
+/// headers written by earlier releases
+pub(crate) const STREAM_HEADER_BASE_SIZE: usize = 6;
+
+/// Size of the complete stream header record
+const STREAM_HEADER_RECORD_SIZE: usize = RECORD_HEADER_SIZE + STREAM_HEADER_PAYLOAD_SIZE;
+
+/// Record type for the audit chain record holding the previous buffer's hash
+pub(crate) const RECORD_TYPE_CHAIN: u8 = 3;
+
+/// Record type for a metric update
+///
+/// The format ID is the registry ID of the metric key and the payload a
+/// `metrics::MetricUpdate`.
+pub(crate) const RECORD_TYPE_METRIC: u8 = 4;
+
+/// Record type for a part of the string dictionary, written once after the
+/// stream header with `Logger::set_embedded_dictionary`.
+/// 
+/// The payload holds entries `id u16 | len u16 | UTF-8 bytes`, a wide ID
+/// as `WIDE_ID` followed by the u32 ID.
+pub(crate) const RECORD_TYPE_DICTIONARY: u8 = 5;
+
+/// Record type for a log record with user tags (see `tags`), timed like
+/// normal records.
+/// 
+/// The payload starts with the u32 tag mask, followed by the arguments.
+pub(crate) const RECORD_TYPE_TAGGED: u8 = 6;
+
+/// Size of the tag mask at the start of a tagged record's payload
+pub(crate) const TAGS_SIZE: usize = 4;
+
+/// Record type for the count table of a buffer, see `Logger::count`,
+/// timed like normal records
+/// 
+/// The payload holds entries `format_id u16 | count u32`.
+pub(crate) const RECORD_TYPE_COUNTS: u8 = 7;
+
+/// Size of an entry of a count table
+pub(crate) const COUNT_ENTRY_SIZE: usize = 6;
+
+/// Most format IDs in one count table
+pub const COUNT_TABLE_CAPACITY: usize = 64;
+
+/// Largest size of a count table record with `entries` entries: header
+/// with a varint time and sequence, a base record before it and the entries
+fn count_table_size(entries: usize) -> usize {
+    TIME_BASE_RECORD_SIZE + RECORD_HEADER_SIZE + 8 + 8 + entries * COUNT_ENTRY_SIZE
+}
+
+/// Record type for a part of the string dictionary in a metadata buffer,
+/// see `Logger::set_dictionary_channel`.
+/// 
+/// The payload is that of `RECORD_TYPE_DICTIONARY`. Metadata buffers are
+/// written outside the data stream's timing, so the time field is 0 and
+/// ignored. A buffer whose first record has this type is a metadata buffer.
+pub(crate) const RECORD_TYPE_METADATA: u8 = 8;
+
+/// Record type for the retention table of a data buffer, see
+/// `Logger::set_channel_retention`.
+/// 
+/// The payload holds entries `channel u16 | ttl_seconds u32`, sorted by
+/// channel. The table applies to the records after it in the buffer. Like
+/// metadata records it has no time: the time field is 0 and ignored.
+pub(crate) const RECORD_TYPE_RETENTION: u8 = 9;
+
+/// Size of an entry of a retention table
+pub(crate) const RETENTION_ENTRY_SIZE: usize = 6;
+
+/// Record type for filler between records, which keeps the records of a
+/// shared buffer in slots of their own (see `signal_safe::SharedLayout`).
+///
+/// The payload is zeros and the record has no time: the time field is 0
+/// and ignored.
+pub(crate) const RECORD_TYPE_PADDING: u8 = 10;
+
+/// Record type for a log record written without a time, see
+/// `Logger::set_timestamps`
+///
+/// The header has no time field: `type(1) | flags(1) | format_id(2)? |
+/// payload_len(2)`. Otherwise the record is a normal one. Readers give it
+/// the time of the timed record before it.
+pub(crate) const RECORD_TYPE_UNTIMED: u8 = 11;
+
+/// Record type for a log record with a type tag per argument, see
+/// `Logger::set_type_tags`, timed like normal records.
+///
+/// The payload starts with the argument count (u8) and one
+/// `arg_types::TypeTag` byte per argument, followed by the arguments.
+pub(crate) const RECORD_TYPE_TYPED: u8 = 12;
+
+/// Record type for the clock offset of the writer's host, see
+/// `Logger::set_clock_offset`.
+///
+/// The payload is `offset_micros i64 | error_bound_micros u64 | source u8`
+/// (`clock_sync::ClockOffset`), and applies to the records after it. Like
+/// retention tables it has no time: the time field is 0 and ignored.
+pub(crate) const RECORD_TYPE_CLOCK_OFFSET: u8 = 13;
+
+/// Record type for a log record with user tags and a type tag per
+/// argument, timed like normal records.
+/// 
+/// The payload starts with the u32 tag mask, as in `RECORD_TYPE_TAGGED`,
+/// followed by the count and type tags of `RECORD_TYPE_TYPED` and the
+/// arguments.
+pub(crate) const RECORD_TYPE_TAGGED_TYPED: u8 = 14;
+
+/// Size of the complete clock offset record, padded to an even length
+const CLOCK_OFFSET_RECORD_SIZE: usize = (RECORD_HEADER_SIZE + CLOCK_OFFSET_SIZE + 1) & !1;
+
+/// First record type reserved for application-defined records
+///
+/// Types 0 to 0x7F belong to the library. Types from here to
+/// `RECORD_TYPE_USER_MAX` are never written by the library; readers decode
+/// them with a registered `RecordDecoder` and skip them otherwise.
+pub const RECORD_TYPE_USER_MIN: u8 = 0x80;
+
+/// Last record type reserved for application-defined records
+pub const RECORD_TYPE_USER_MAX: u8 = 0xFF;
+
+/// Size of the SHA-256 hash in a chain record
+pub(crate) const CHAIN_HASH_SIZE: usize = 32;
+
+/// Size of the complete chain record
+const CHAIN_RECORD_SIZE: usize = RECORD_HEADER_SIZE + CHAIN_HASH_SIZE;
+
+/// Record flag: a 64-bit global sequence number follows the header
+pub(crate) const FLAG_SEQUENCE: u8 = 0x01;
+
+/// Record flag: a 16-bit channel ID follows the header (and sequence)
+/// 
+/// A channel field holding `string_registry::WIDE_ID` is followed by the
+/// 32-bit channel ID. After the channel comes the 32-bit format ID of a
+/// header whose format ID field holds `WIDE_ID`; see
+/// `FormatFeatures::WIDE_IDS`.
+pub(crate) const FLAG_CHANNEL: u8 = 0x02;
+
+/// Size of a wide ID following the fixed fields of a record
+pub(crate) const WIDE_ID_SIZE: usize = 4;
+
+/// Value of the 16-bit length field of a record of 64 KiB or more, whose
+/// length follows the channel and wide format ID as a u32; see
+/// `FormatFeatures::LONG_RECORDS`
+pub(crate) const LONG_LENGTH: u16 = 0xFFFF;
+
+/// Size of the 32-bit length of a long record
+pub(crate) const LONG_LENGTH_SIZE: usize = 4;
+
+/// Returns the size of the length that follows the header fields of a
+/// record whose length field holds `field`.
+#[inline]
+pub(crate) fn long_length_size(field: u16) -> usize {
+    if field == LONG_LENGTH { LONG_LENGTH_SIZE } else { 0 }
+}
+
+/// Record flag: the record has the format ID of the previous record in its
+/// buffer, and the header omits it
+/// 
+/// The header is then `type(1) | flags(1) | relative_ts(2) | payload_len(2)`
+/// (`SHORT_RECORD_HEADER_SIZE`).
+pub(crate) const FLAG_SAME_FORMAT: u8 = 0x04;
+
+/// Size of a record header without the format ID, see `FLAG_SAME_FORMAT`
+pub(crate) const SHORT_RECORD_HEADER_SIZE: usize = 6;
+
+/// Record flag: the header holds a varint of microseconds since the
+/// previous record instead of the 16-bit relative timestamp
+/// 
+/// The header is then `type(1) | flags(1) | delta(varint) | format_id(2)? |
+/// payload_len(2)`; see `record_header_size`.
+pub(crate) const FLAG_DELTA_TIME: u8 = 0x08;
+
+/// Record flag: arguments were cut to the logger's capture limits, see
+/// `Logger::set_capture_limits`
+pub(crate) const FLAG_TRUNCATED: u8 = 0x80;
+
+/// Returns the size of the header of the record starting at `record`, or
+/// None if `record` is too short to tell.
+pub(crate) fn record_header_size(record: &[u8]) -> Option<usize> {
+    let flags = *record.get(1)?;
+    let time_len = if record[0] == RECORD_TYPE_UNTIMED {
+        0
+    } else if flags & FLAG_DELTA_TIME != 0 {
+        record.get(2..)?.iter().take(10).position(|byte| byte & 0x80 == 0)? + 1
+    } else {
+        2
+    };
+    let format_len = if flags & FLAG_SAME_FORMAT != 0 { 0 } else { 2 };
+    Some(2 + time_len + format_len + 2)
+}
+
+/// Records skipped by level since the last summary records, see
+/// `Logger::set_suppression_summaries`
+#[derive(Debug)]
+struct Suppression {
+    interval: Duration,
+
+    /// Start of the current counts
+    since: Instant,
+
+    /// Records skipped per format ID and level
+    counts: HashMap<(u32, Level), u32>,
+}
+
+impl Suppression {
+    fn count(&mut self, level: Level, format_id: u32, now: Instant) {
+        if self.counts.is_empty() {
+            self.since = now;
+        }
+        let count = self.counts.entry((format_id, level)).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        !self.counts.is_empty() && now.saturating_duration_since(self.since) >= self.interval
+    }
+}
+
+/// Optional header fields of a log record
+#[derive(Debug, Clone, Copy, Default)]
+struct RecordMeta {
+    /// Channel ID, 0 for none
+    channel: u32,
+
+    /// Severity, stored in the flags
+    level: Option<Level>,
+
+    /// Whether arguments were cut to their capture limits
+    truncated: bool,
+}
+
+/// Time field of a record header
+#[derive(Debug, Clone, Copy)]
+enum RecordTime {
+    /// No time, see `RECORD_TYPE_UNTIMED`
+    None,
+
+    /// Microseconds since the current base
+    Relative(u16),
+
+    /// Microseconds since the previous record, see `FLAG_DELTA_TIME`
+    Delta(u64),
+}
+
+/// Largest possible internal event record: header, sequence, base timestamp
+/// and a single-argument payload, padded
+const INTERNAL_RECORD_MAX_SIZE: usize = RECORD_HEADER_SIZE + 8 + 8 + 10;
+
+/// Space kept free at the end of a buffer while instrumentation is enabled,
+/// enough for a switch-start event plus the drop and registry events
+const INSTRUMENTATION_RESERVE: usize = 3 * INTERNAL_RECORD_MAX_SIZE;
 
 
@@ -0,0 +1,249 @@
+#![allow(dead_code)]
+
+//! Buffers shared by several Loggers, with a bound on their total memory.
+//!
+//! Each Logger normally owns two buffers of `CAP` bytes for its whole life,
+//! so N threads hold 2 × N × CAP bytes whether they log or not. Loggers
+//! created with `Logger::with_buffer_pool` instead lease one buffer from a
+//! `BufferPool` when they write and give it back once the BufferHandler has
+//! taken its contents. A thread that went quiet after a flush holds no
+//! buffer, and a busy one is never limited by a share of its own: the pool
+//! only bounds the number of buffers leased at once.
+//!
+//! When every buffer is leased, records are dropped and counted like other
+//! drops (`LoggerStats::dropped_records`, and a `Drops` event with
+//! instrumentation), rather than making the logging thread wait.
+//!
+//! Loggers with buffers of their own can also give a filled buffer away:
+//! it is handed to `BufferHandler::handle_owned_buffer` as a `LogBuffer`,
+//! which the handler keeps, and the handler returns the buffer the Logger
+//! writes to next. Sinks that write on another thread use this to avoid
+//! copying each buffer (see `handlers::BackgroundWriter`).
+
+use std::alloc::{alloc, dealloc, Layout};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+/// Usage of a `BufferPool`, see `BufferPool::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Buffers allocated so far, leased or free
+    pub allocated: usize,
+
+    /// Buffers currently leased by Loggers
+    pub leased: usize,
+
+    /// Most buffers leased at once
+    pub peak_leased: usize,
+
+    /// Leases refused because every buffer was leased
+    pub exhausted: u64,
+}
+
+/// Buffers of `CAP` bytes leased by Loggers, at most `max_buffers` at once.
+///
+/// Buffers are allocated on demand, up to the limit, and kept for reuse
+/// until the pool is dropped. Loggers hold the pool in an `Arc`, so it
+/// outlives them.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::buffer_pool::BufferPool;
+/// # use binary_logger::codec::RawCodec;
+/// # use std::sync::Arc;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// // 4 buffers of 64 KiB for every Logger of the process
+/// let pool = Arc::new(BufferPool::<65536>::new(4));
+/// let mut logger = Logger::with_buffer_pool(NullHandler, &RawCodec, pool.clone());
+/// log_record!(logger, "Request {} served", 17).unwrap();
+/// assert_eq!(pool.stats().leased, 1);
+///
+/// // Flushed Loggers give their buffer back
+/// logger.flush();
+/// assert_eq!(pool.stats().leased, 0);
+/// ```
+pub struct BufferPool<const CAP: usize> {
+    max_buffers: usize,
+    state: Mutex<PoolState>,
+}
+
+/// The free buffers and counters of a pool.
+#[derive(Default)]
+struct PoolState {
+    free: Vec<*mut u8>,
+    stats: BufferPoolStats,
+}
+
+// Buffers are plain memory, only reachable through the pool or the one
+// Logger leasing them
+unsafe impl<const CAP: usize> Send for BufferPool<CAP> {}
+unsafe impl<const CAP: usize> Sync for BufferPool<CAP> {}
+
+impl<const CAP: usize> BufferPool<CAP> {
+    /// Creates a pool that lets at most `max_buffers` buffers be leased at
+    /// once, bounding its memory to `max_buffers × CAP` bytes.
+    pub fn new(max_buffers: usize) -> Self {
+        BufferPool { max_buffers, state: Mutex::new(PoolState::default()) }
+    }
+
+    /// Returns the most buffers that can be leased at once.
+    pub fn max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+
+    /// Returns the current usage of the pool.
+    pub fn stats(&self) -> BufferPoolStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Leases a buffer, or returns `None` if every buffer is leased.
+    pub(crate) fn lease(&self) -> Option<*mut u8> {
+        let mut state = self.state.lock().unwrap();
+        let buffer = match state.free.pop() {
+            Some(buffer) => buffer,
+            None if state.stats.allocated < self.max_buffers => {
+                state.stats.allocated += 1;
+                alloc_buffer::<CAP>()
+            }
+            None => {
+                state.stats.exhausted += 1;
+                return None;
+            }
+        };
+        state.stats.leased += 1;
+        state.stats.peak_leased = state.stats.peak_leased.max(state.stats.leased);
+        Some(buffer)
+    }
+
+    /// Returns a leased buffer to the pool.
+    pub(crate) fn release(&self, buffer: *mut u8) {
+        let mut state = self.state.lock().unwrap();
+        state.stats.leased -= 1;
+        state.free.push(buffer);
+    }
+}
+
+impl<const CAP: usize> Drop for BufferPool<CAP> {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for buffer in state.free.drain(..) {
+            free_buffer::<CAP>(buffer);
+        }
+    }
+}
+
+/// Alignment of buffers: a cache line, so that records aligned within a
+/// buffer are aligned in memory (see `signal_safe::SharedLayout`)
+pub(crate) const BUFFER_ALIGN: usize = 64;
+
+/// Layout of a buffer of `size` bytes
+fn buffer_layout(size: usize) -> Layout {
+    Layout::from_size_align(size, BUFFER_ALIGN).unwrap()
+}
+
+/// Allocates a buffer of `CAP` bytes.
+pub(crate) fn alloc_buffer<const CAP: usize>() -> *mut u8 {
+    unsafe { alloc(buffer_layout(CAP)) }
+}
+
+/// Frees a buffer allocated with `alloc_buffer`.
+pub(crate) fn free_buffer<const CAP: usize>(buffer: *mut u8) {
+    unsafe { dealloc(buffer, buffer_layout(CAP)) }
+}
+
+/// A buffer of log data owned by its holder, see
+/// `BufferHandler::handle_owned_buffer`.
+///
+/// Dereferences to its filled bytes, which are a buffer as a Logger hands
+/// it over, size header included. The memory is freed when the buffer is
+/// dropped.
+pub struct LogBuffer {
+    ptr: *mut u8,
+    capacity: usize,
+    len: usize,
+}
+
+// The buffer is plain memory owned by this value alone
+unsafe impl Send for LogBuffer {}
+unsafe impl Sync for LogBuffer {}
+
+impl LogBuffer {
+    /// Allocates an empty buffer of `capacity` bytes, for a Logger whose
+    /// `CAP` is `capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "log buffers can't be empty");
+        let ptr = unsafe { alloc(buffer_layout(capacity)) };
+        LogBuffer { ptr, capacity, len: 0 }
+    }
+
+    /// Takes ownership of a buffer allocated with `alloc_buffer::<CAP>()`,
+    /// holding `len` bytes.
+    pub(crate) fn from_raw(ptr: *mut u8, capacity: usize, len: usize) -> Self {
+        LogBuffer { ptr, capacity, len }
+    }
+
+    /// Gives up ownership of the buffer, to be freed with `free_buffer`.
+    pub(crate) fn into_raw(self) -> *mut u8 {
+        let ptr = self.ptr;
+        std::mem::forget(self);
+        ptr
+    }
+
+    /// Returns the size of the buffer's memory.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the filled bytes.
+    pub fn as_slice(&self) -> &[u8] {
+        // The first `len` bytes were written by the Logger or `set_len`
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Sets the number of filled bytes, for a buffer filled through
+    /// `as_mut_ptr`.
+    ///
+    /// # Safety
+    ///
+    /// The first `len` bytes must have been written, and `len` must not
+    /// exceed the capacity.
+    pub unsafe fn set_len(&mut self, len: usize) {
+        self.len = len;
+    }
+
+    /// Returns a pointer to the buffer's memory, valid for writing
+    /// `capacity` bytes.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl Deref for LogBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for LogBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogBuffer").field("capacity", &self.capacity).field("len", &self.len).finish()
+    }
+}
+
+impl Drop for LogBuffer {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr, buffer_layout(self.capacity)) }
+    }
+}
@@ -0,0 +1,164 @@
+#![allow(dead_code)]
+
+//! Clock offsets of hosts, for merging logs written on several machines.
+//!
+//! Timestamps are read from each host's own clock, so logs of a
+//! distributed system disagree by however far the clocks are apart. A
+//! Logger records how far its host's clock is from a reference, as
+//! measured by NTP or PTP, with `Logger::set_clock_offset`. The offset is
+//! stored in an untimed record (`RECORD_TYPE_CLOCK_OFFSET`) that applies to
+//! the records after it, and written again at the start of every buffer, so
+//! a segment read on its own still has it. `LogReader::clock_offset`
+//! returns the offset in effect for the entries read last, and
+//! `LogMerger::aligned` moves every entry onto the reference timeline,
+//! with the error bound of its host's offset.
+//!
+//! The crate doesn't measure offsets itself: pass the ones your time
+//! daemon reports (`chronyc tracking`, `pmc`, ...), or on Linux take the
+//! kernel's view with `ClockOffset::from_kernel`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, LogMerger, log_record};
+//! # use binary_logger::clock_sync::{ClockOffset, ClockSource};
+//! # use std::sync::{Arc, Mutex};
+//! # use std::time::Duration;
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let (host_a, host_b) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(host_a.clone()));
+//! // Host A's clock is 1.5 ms behind the reference, within 200 us
+//! logger.set_clock_offset(ClockOffset::new(1500, 200, ClockSource::Ptp));
+//! log_record!(logger, "Sent request {}", 7).unwrap();
+//! # }
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(host_b.clone()));
+//! # logger.set_clock_offset(ClockOffset::new(-800, 1000, ClockSource::Ntp));
+//! # log_record!(logger, "Received request {}", 7).unwrap();
+//! # }
+//! # let (host_a, host_b) = (host_a.lock().unwrap(), host_b.lock().unwrap());
+//! let mut merger = LogMerger::aligned(vec![LogReader::new(&host_a), LogReader::new(&host_b)]);
+//! while let Some(aligned) = merger.read_aligned() {
+//!     println!("{:?} ±{:?} {}", aligned.entry.timestamp, aligned.error_bound(), aligned.entry.format());
+//! }
+//! ```
+
+use std::time::{Duration, SystemTime};
+
+/// Size of the payload of a clock offset record:
+/// `offset_micros i64 | error_bound_micros u64 | source u8`
+pub(crate) const CLOCK_OFFSET_SIZE: usize = 17;
+
+/// How a clock offset was measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClockSource {
+    /// Network Time Protocol
+    Ntp = 1,
+
+    /// Precision Time Protocol
+    Ptp = 2,
+
+    /// The kernel's clock discipline, fed by whichever daemon runs
+    Kernel = 3,
+
+    /// Set by hand or by the application's own protocol
+    Manual = 4,
+}
+
+impl ClockSource {
+    /// Decodes a source stored in a record, or None for one this version
+    /// doesn't know.
+    pub fn from_u8(value: u8) -> Option<ClockSource> {
+        match value {
+            1 => Some(ClockSource::Ntp),
+            2 => Some(ClockSource::Ptp),
+            3 => Some(ClockSource::Kernel),
+            4 => Some(ClockSource::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// How far a host's clock is from the reference clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClockOffset {
+    /// Microseconds to add to the host's times to get the reference's:
+    /// positive when the host's clock is behind
+    pub offset_micros: i64,
+
+    /// Largest error of the offset, in microseconds
+    pub error_bound_micros: u64,
+
+    pub source: ClockSource,
+}
+
+impl ClockOffset {
+    /// Creates an offset of `offset_micros`, reference minus host, known
+    /// within `error_bound_micros`.
+    pub fn new(offset_micros: i64, error_bound_micros: u64, source: ClockSource) -> Self {
+        ClockOffset { offset_micros, error_bound_micros, source }
+    }
+
+    /// Returns the kernel's view of the system clock: the offset its
+    /// discipline is still correcting and its maximum error.
+    ///
+    /// Fails if the kernel reports the clock as unsynchronized.
+    #[cfg(target_os = "linux")]
+    pub fn from_kernel() -> std::io::Result<ClockOffset> {
+        // Mode 0 only reads the state
+        let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+        let state = unsafe { libc::adjtimex(&mut timex) };
+        if state == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if state == libc::TIME_ERROR || timex.status & libc::STA_UNSYNC != 0 {
+            return Err(std::io::Error::other("the system clock is not synchronized"));
+        }
+        let offset_micros = match timex.status & libc::STA_NANO != 0 {
+            true => timex.offset as i64 / 1000,
+            false => timex.offset as i64,
+        };
+        Ok(ClockOffset::new(offset_micros, timex.maxerror.max(0) as u64, ClockSource::Kernel))
+    }
+
+    /// Returns the error bound as a duration.
+    pub fn error_bound(&self) -> Duration {
+        Duration::from_micros(self.error_bound_micros)
+    }
+
+    /// Moves `local`, a time of the host's clock, onto the reference
+    /// timeline.
+    pub fn apply(&self, local: SystemTime) -> SystemTime {
+        let shift = Duration::from_micros(self.offset_micros.unsigned_abs());
+        match self.offset_micros >= 0 {
+            true => local + shift,
+            false => local - shift,
+        }
+    }
+
+    /// Encodes the offset as the payload of a clock offset record.
+    pub(crate) fn encode(&self) -> [u8; CLOCK_OFFSET_SIZE] {
+        let mut out = [0; CLOCK_OFFSET_SIZE];
+        out[..8].copy_from_slice(&self.offset_micros.to_le_bytes());
+        out[8..16].copy_from_slice(&self.error_bound_micros.to_le_bytes());
+        out[16] = self.source as u8;
+        out
+    }
+
+    /// Decodes the payload of a clock offset record, or returns None if
+    /// it is too short or of an unknown source.
+    pub(crate) fn decode(payload: &[u8]) -> Option<ClockOffset> {
+        let payload = payload.get(..CLOCK_OFFSET_SIZE)?;
+        Some(ClockOffset {
+            offset_micros: i64::from_le_bytes(payload[..8].try_into().unwrap()),
+            error_bound_micros: u64::from_le_bytes(payload[8..16].try_into().unwrap()),
+            source: ClockSource::from_u8(payload[16])?,
+        })
+    }
+}
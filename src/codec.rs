@@ -0,0 +1,395 @@
+#![allow(dead_code)]
+
+//! Payload encodings for log record arguments.
+//!
+//! A Logger hands the raw bytes of each `log_record!` argument to its `Codec`,
+//! which lays them out in the record payload. The codec ID is written to the
+//! stream header, so a LogReader always decodes a stream with the codec it was
+//! written with.
+//!
+//! Four codecs are built in:
+//!
+//! * `RawCodec` - the native layout: `[count u8][size u32 | bytes]...`
+//! * `PostcardCodec` - the postcard encoding of a sequence of byte strings
+//! * `CborCodec` - a CBOR array of byte strings
+//! * `CompactCodec` - the native layout with a one-byte tag per argument,
+//!   holding the size of arguments up to 15 bytes
+//!
+//! With postcard and CBOR, a payload can be decoded by standard tooling as
+//! `Vec<&[u8]>` (postcard) or an array of `bstr` (CBOR). Each byte string holds
+//! the argument exactly as `log_record!` captured it.
+
+use crate::wire;
+
+/// Encoding of the argument list in a record payload.
+///
+/// Codecs are stateless and shared, so a Logger holds a `&'static dyn Codec`.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::codec::CborCodec;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::with_codec(NullHandler, &CborCodec);
+/// log_record!(logger, "Encoded as CBOR: {}", 42).unwrap();
+/// ```
+pub trait Codec: Sync {
+    /// Identifier recorded in the stream header.
+    fn id(&self) -> u8;
+
+    /// Short human-readable name of the codec.
+    fn name(&self) -> &'static str;
+
+    /// Returns the number of bytes `encode` produces for the given arguments.
+    fn encoded_len(&self, args: &[&[u8]]) -> usize;
+
+    /// Encodes the arguments into `out`.
+    ///
+    /// `out` is exactly `encoded_len(args)` bytes long.
+    fn encode(&self, args: &[&[u8]], out: &mut [u8]);
+
+    /// Splits a payload back into the bytes of each argument.
+    ///
+    /// Decoding stops at the first argument that is malformed or truncated;
+    /// the arguments before it are still returned.
+    fn decode<'a>(&self, payload: &'a [u8]) -> Vec<&'a [u8]>;
+}
+
+/// Codec ID of `RawCodec`
+pub const RAW_CODEC_ID: u8 = 0;
+
+/// Codec ID of `PostcardCodec`
+pub const POSTCARD_CODEC_ID: u8 = 1;
+
+/// Codec ID of `CborCodec`
+pub const CBOR_CODEC_ID: u8 = 2;
+
+/// Codec ID of `CompactCodec`
+pub const COMPACT_CODEC_ID: u8 = 3;
+
+/// Looks up a built-in codec by the ID stored in a stream header.
+///
+/// # Returns
+///
+/// * `Some(&dyn Codec)` - If the ID belongs to a built-in codec
+/// * `None` - Otherwise
+pub fn codec_by_id(id: u8) -> Option<&'static dyn Codec> {
+    match id {
+        RAW_CODEC_ID => Some(&RawCodec),
+        POSTCARD_CODEC_ID => Some(&PostcardCodec),
+        CBOR_CODEC_ID => Some(&CborCodec),
+        COMPACT_CODEC_ID => Some(&CompactCodec),
+        _ => None,
+    }
+}
+
+/// The native payload layout and the default codec.
+///
+/// Format: `[count(1) | size(4) | bytes(size) | size(4) | bytes(size) ...]`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawCodec;
+
+impl Codec for RawCodec {
+    fn id(&self) -> u8 {
+        RAW_CODEC_ID
+    }
+
+    fn name(&self) -> &'static str {
+        "raw"
+    }
+
+    fn encoded_len(&self, args: &[&[u8]]) -> usize {
+        1 + args.iter().map(|arg| 4 + arg.len()).sum::<usize>()
+    }
+
+    fn encode(&self, args: &[&[u8]], out: &mut [u8]) {
+        out[0] = args.len() as u8;
+        let mut pos = 1;
+        for arg in args {
+            out[pos..pos + 4].copy_from_slice(&(arg.len() as u32).to_le_bytes());
+            pos += 4;
+            out[pos..pos + arg.len()].copy_from_slice(arg);
+            pos += arg.len();
+        }
+    }
+
+    fn decode<'a>(&self, payload: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut args = Vec::new();
+        let Some((&count, mut rest)) = payload.split_first() else {
+            return args;
+        };
+
+        for _ in 0..count {
+            if rest.len() < 4 {
+                break;
+            }
+            let size = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            rest = &rest[4..];
+            if rest.len() < size {
+                break;
+            }
+            args.push(&rest[..size]);
+            rest = &rest[size..];
+        }
+        args
+    }
+}
+
+/// Postcard encoding of the arguments as a sequence of byte strings.
+///
+/// Format: `[varint(count) | varint(size) | bytes(size) ...]`, where varints
+/// are unsigned LEB128 as in the postcard wire format.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardCodec;
+
+/// Number of bytes of an unsigned LEB128 varint.
+pub(crate) fn varint_len(value: usize) -> usize {
+    wire::varint_len(value as u64)
+}
+
+/// Writes an unsigned LEB128 varint, returning the number of bytes written.
+pub(crate) fn write_varint(value: usize, out: &mut [u8]) -> usize {
+    wire::write_varint(value as u64, out)
+}
+
+/// Reads an unsigned LEB128 varint, returning the value and the remaining input.
+pub(crate) fn read_varint(input: &[u8]) -> Option<(usize, &[u8])> {
+    let (value, rest) = wire::read_varint(input)?;
+    Some((usize::try_from(value).ok()?, rest))
+}
+
+impl Codec for PostcardCodec {
+    fn id(&self) -> u8 {
+        POSTCARD_CODEC_ID
+    }
+
+    fn name(&self) -> &'static str {
+        "postcard"
+    }
+
+    fn encoded_len(&self, args: &[&[u8]]) -> usize {
+        varint_len(args.len())
+            + args.iter().map(|arg| varint_len(arg.len()) + arg.len()).sum::<usize>()
+    }
+
+    fn encode(&self, args: &[&[u8]], out: &mut [u8]) {
+        let mut pos = write_varint(args.len(), out);
+        for arg in args {
+            pos += write_varint(arg.len(), &mut out[pos..]);
+            out[pos..pos + arg.len()].copy_from_slice(arg);
+            pos += arg.len();
+        }
+    }
+
+    fn decode<'a>(&self, payload: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut args = Vec::new();
+        let Some((count, mut rest)) = read_varint(payload) else {
+            return args;
+        };
+
+        for _ in 0..count {
+            let Some((size, tail)) = read_varint(rest) else {
+                break;
+            };
+            if tail.len() < size {
+                break;
+            }
+            args.push(&tail[..size]);
+            rest = &tail[size..];
+        }
+        args
+    }
+}
+
+/// CBOR encoding of the arguments as an array of byte strings (RFC 8949).
+///
+/// Format: `[array header(count) | bstr header(size) | bytes(size) ...]`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+/// CBOR major type of byte strings
+const CBOR_MAJOR_BYTES: u8 = 2;
+
+/// CBOR major type of arrays
+const CBOR_MAJOR_ARRAY: u8 = 4;
+
+/// Number of bytes of a CBOR item header carrying `value`.
+fn cbor_header_len(value: usize) -> usize {
+    match value {
+        0..=23 => 1,
+        24..=0xFF => 2,
+        0x100..=0xFFFF => 3,
+        _ => 5,
+    }
+}
+
+/// Writes a CBOR item header, returning the number of bytes written.
+fn write_cbor_header(major: u8, value: usize, out: &mut [u8]) -> usize {
+    let major = major << 5;
+    match value {
+        0..=23 => {
+            out[0] = major | value as u8;
+            1
+        }
+        24..=0xFF => {
+            out[0] = major | 24;
+            out[1] = value as u8;
+            2
+        }
+        0x100..=0xFFFF => {
+            out[0] = major | 25;
+            out[1..3].copy_from_slice(&(value as u16).to_be_bytes());
+            3
+        }
+        _ => {
+            out[0] = major | 26;
+            out[1..5].copy_from_slice(&(value as u32).to_be_bytes());
+            5
+        }
+    }
+}
+
+/// Reads a CBOR item header of the expected major type, returning its value
+/// and the remaining input.
+fn read_cbor_header(major: u8, input: &[u8]) -> Option<(usize, &[u8])> {
+    let (&initial, rest) = input.split_first()?;
+    if initial >> 5 != major {
+        return None;
+    }
+    match initial & 0x1F {
+        info @ 0..=23 => Some((info as usize, rest)),
+        24 => Some((*rest.first()? as usize, rest.get(1..)?)),
+        25 => {
+            let bytes = rest.get(..2)?;
+            Some((u16::from_be_bytes([bytes[0], bytes[1]]) as usize, &rest[2..]))
+        }
+        26 => {
+            let bytes = rest.get(..4)?;
+            Some((u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize, &rest[4..]))
+        }
+        _ => None,
+    }
+}
+
+impl Codec for CborCodec {
+    fn id(&self) -> u8 {
+        CBOR_CODEC_ID
+    }
+
+    fn name(&self) -> &'static str {
+        "cbor"
+    }
+
+    fn encoded_len(&self, args: &[&[u8]]) -> usize {
+        cbor_header_len(args.len())
+            + args.iter().map(|arg| cbor_header_len(arg.len()) + arg.len()).sum::<usize>()
+    }
+
+    fn encode(&self, args: &[&[u8]], out: &mut [u8]) {
+        let mut pos = write_cbor_header(CBOR_MAJOR_ARRAY, args.len(), out);
+        for arg in args {
+            pos += write_cbor_header(CBOR_MAJOR_BYTES, arg.len(), &mut out[pos..]);
+            out[pos..pos + arg.len()].copy_from_slice(arg);
+            pos += arg.len();
+        }
+    }
+
+    fn decode<'a>(&self, payload: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut args = Vec::new();
+        let Some((count, mut rest)) = read_cbor_header(CBOR_MAJOR_ARRAY, payload) else {
+            return args;
+        };
+
+        for _ in 0..count {
+            let Some((size, tail)) = read_cbor_header(CBOR_MAJOR_BYTES, rest) else {
+                break;
+            };
+            if tail.len() < size {
+                break;
+            }
+            args.push(&tail[..size]);
+            rest = &tail[size..];
+        }
+        args
+    }
+}
+
+/// The native layout with the size of each argument in a one-byte tag.
+///
+/// Format: `[count(1) | tag(1) | size(varint)? | bytes(size) ...]`. A tag
+/// from `0x00` to `0x0F` holds the size of an argument of up to 15 bytes in
+/// its low nibble, with the bytes right after it; tag `0x10` is followed by
+/// the size as a varint. Short strings such as status words, and integers,
+/// then take one byte more than their bytes, where `RawCodec` takes four.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactCodec;
+
+/// Largest argument whose size fits in its tag
+const COMPACT_INLINE_MAX: usize = 0x0F;
+
+/// Tag of an argument whose size follows as a varint
+const COMPACT_TAG_VARINT: u8 = 0x10;
+
+impl Codec for CompactCodec {
+    fn id(&self) -> u8 {
+        COMPACT_CODEC_ID
+    }
+
+    fn name(&self) -> &'static str {
+        "compact"
+    }
+
+    fn encoded_len(&self, args: &[&[u8]]) -> usize {
+        1 + args.iter().map(|arg| match arg.len() {
+            len @ 0..=COMPACT_INLINE_MAX => 1 + len,
+            len => 1 + varint_len(len) + len,
+        }).sum::<usize>()
+    }
+
+    fn encode(&self, args: &[&[u8]], out: &mut [u8]) {
+        out[0] = args.len() as u8;
+        let mut pos = 1;
+        for arg in args {
+            if arg.len() <= COMPACT_INLINE_MAX {
+                out[pos] = arg.len() as u8;
+                pos += 1;
+            } else {
+                out[pos] = COMPACT_TAG_VARINT;
+                pos += 1 + write_varint(arg.len(), &mut out[pos + 1..]);
+            }
+            out[pos..pos + arg.len()].copy_from_slice(arg);
+            pos += arg.len();
+        }
+    }
+
+    fn decode<'a>(&self, payload: &'a [u8]) -> Vec<&'a [u8]> {
+        let mut args = Vec::new();
+        let Some((&count, mut rest)) = payload.split_first() else {
+            return args;
+        };
+
+        for _ in 0..count {
+            let Some((&tag, tail)) = rest.split_first() else {
+                break;
+            };
+            let (size, tail) = match tag {
+                0..=0x0F => (tag as usize, tail),
+                COMPACT_TAG_VARINT => match read_varint(tail) {
+                    Some(read) => read,
+                    None => break,
+                },
+                _ => break,
+            };
+            if tail.len() < size {
+                break;
+            }
+            args.push(&tail[..size]);
+            rest = &tail[size..];
+        }
+        args
+    }
+}
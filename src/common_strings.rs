@@ -0,0 +1,99 @@
+//! Built-in dictionary of very common string values.
+//!
+//! Level names, booleans, HTTP methods and status codes show up as string
+//! arguments in most services. `COMMON_STRINGS` lists them with IDs from
+//! the reserved range above the internal events, so every writer and
+//! reader knows them without a registry or an embedded dictionary:
+//!
+//! * String literal arguments in the table are interned as their reserved
+//!   ID rather than registered, so logging them never takes the registry
+//!   lock or grows the registry.
+//! * With `Logger::set_common_strings`, `&str` and `String` arguments equal
+//!   to an entry of three bytes or more are stored as two bytes, a marker
+//!   that can't start UTF-8 followed by the entry's index, and read back as
+//!   the string. Readers only look for the marker in arguments they know
+//!   are strings, from their type tag or in the writing process, since an
+//!   integer such as `511u16` has the same bytes.
+//!
+//! Both take the `common-strings` format feature. The table only ever
+//! grows at its end, so logs keep their meaning across releases.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::instrumentation::RESERVED_FORMAT_ID_END;
+
+/// ID of the first common string; the others follow in table order.
+///
+/// Internal events take the reserved IDs below it.
+pub const COMMON_STRING_ID_START: u32 = 0xFF80;
+
+/// First byte of a string argument stored as an index into the table.
+/// It never starts valid UTF-8.
+pub(crate) const COMMON_STRING_MARKER: u8 = 0xFF;
+
+/// The common strings, by ID from `COMMON_STRING_ID_START`.
+pub const COMMON_STRINGS: [&str; 69] = [
+    // Levels
+    "TRACE", "DEBUG", "INFO", "WARN", "ERROR",
+    "trace", "debug", "info", "warn", "error",
+    // Booleans and states
+    "true", "false", "yes", "no", "on", "off", "ok", "OK", "none", "null",
+    "enabled", "disabled", "success", "failure", "failed", "timeout",
+    "pending", "running", "done", "unknown",
+    // HTTP methods
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "PATCH",
+    // HTTP versions and schemes
+    "HTTP/1.0", "HTTP/1.1", "HTTP/2", "HTTP/3", "http", "https",
+    // HTTP status codes
+    "100", "101", "200", "201", "202", "204", "206",
+    "301", "302", "304", "307", "308",
+    "400", "401", "403", "404", "405", "409", "413", "422", "429",
+    "500", "502", "503", "504",
+];
+
+/// Length of the longest common string
+const MAX_COMMON_LEN: usize = 8;
+
+// Every ID is a reserved 16-bit ID short of `string_registry::WIDE_ID`
+const _: () = assert!(COMMON_STRING_ID_START + (COMMON_STRINGS.len() as u32) < RESERVED_FORMAT_ID_END - 1);
+
+/// Returns the reserved ID of `s`, if it is a common string.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::common_strings::{common_string, common_string_id};
+/// let id = common_string_id("GET").unwrap();
+/// assert_eq!(common_string(id), Some("GET"));
+/// assert_eq!(common_string_id("FETCH"), None);
+/// ```
+pub fn common_string_id(s: &str) -> Option<u32> {
+    common_index(s.as_bytes()).map(|index| COMMON_STRING_ID_START + index as u32)
+}
+
+/// Returns the common string with the reserved ID `id`, if any.
+pub fn common_string(id: u32) -> Option<&'static str> {
+    let index = id.checked_sub(COMMON_STRING_ID_START)? as usize;
+    COMMON_STRINGS.get(index).copied()
+}
+
+/// Returns the index of the common string with the bytes `s`, if any.
+pub(crate) fn common_index(s: &[u8]) -> Option<u8> {
+    static INDEXES: OnceLock<HashMap<&'static [u8], u8>> = OnceLock::new();
+    if s.len() > MAX_COMMON_LEN {
+        return None;
+    }
+    let indexes = INDEXES.get_or_init(|| {
+        COMMON_STRINGS.iter().enumerate().map(|(index, s)| (s.as_bytes(), index as u8)).collect()
+    });
+    indexes.get(s).copied()
+}
+
+/// Returns the common string a string argument stands for, if it was
+/// stored as its index.
+pub(crate) fn decode_common(arg: &[u8]) -> Option<&'static str> {
+    match *arg {
+        [COMMON_STRING_MARKER, index] => COMMON_STRINGS.get(index as usize).copied(),
+        _ => None,
+    }
+}
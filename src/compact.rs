@@ -0,0 +1,379 @@
+#![allow(dead_code)]
+
+//! Compaction of aged log segments, backing the `blog-compact` tool.
+//!
+//! Logs are written uncompressed, so that nothing but copying happens on
+//! the logging thread. Segments kept for months cost more storage than
+//! they need to. `Compaction` rewrites the old ones off the hot path: runs
+//! of consecutive segments older than a minimum age are merged, up to a
+//! target size, and compressed into one zstd frame at level 19: slow to
+//! write, which compaction can afford, small, and still fast to read. The
+//! result is named after the first segment of the run with `.zst`
+//! appended, so sorting by name still gives stream order. The originals
+//! are removed only once the compressed file has been written, read back
+//! identical to them and renamed into place. Before the rename, the run's
+//! segments are listed in a journal next to it (`JOURNAL_EXTENSION`), which
+//! is removed after them: should the process stop in between, readers
+//! listing segments with `rotation::segments` leave out the originals the
+//! compressed file already holds, and the next compaction removes them
+//! instead of compacting them again. Compaction and compacted
+//! segments need the `zstd` feature; segments earlier versions compacted
+//! into `.lz4` files are read with the `lz4` feature.
+//!
+//! A merged file holds the segments' bytes one after the other, which is
+//! itself a valid log: records never span segments, and hash chains, global
+//! sequence numbers and dictionaries continue across them as they did
+//! across the files. `read_segment` reads a segment whether it is
+//! compressed or not, and the file-reading tools (`blog-verify`,
+//! `blog-grep`, `blog-inspect`, `blog-downsample`, `blog-serve`,
+//! `blog-mount`) use it, so compacted logs read like any other.
+//! `blog-expire` rewrites segments in place and only takes uncompressed
+//! ones: expire entries before compacting their segments.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[cfg(feature = "zstd")] {
+//! # use binary_logger::compact::Compaction;
+//! # use std::time::{Duration, SystemTime};
+//! # fn example(segments: Vec<std::path::PathBuf>) -> std::io::Result<()> {
+//! // Segments older than a week, into files of up to 256 MiB before
+//! // compression
+//! let report = Compaction::new(SystemTime::now())
+//!     .min_age(Duration::from_secs(7 * 86400))
+//!     .target_bytes(256 << 20)
+//!     .run(&segments)?;
+//! println!("saved {} bytes", report.bytes_saved());
+//! # Ok(())
+//! # }
+//! # }
+//! ```
+
+use std::fs;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use std::fs::File;
+use std::io;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use std::io::Read;
+#[cfg(feature = "zstd")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "zstd")]
+use std::time::{Duration, SystemTime};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::compression::{has_frames, inflate};
+
+/// Extension appended to the names of compressed segments
+pub const COMPRESSED_EXTENSION: &str = "zst";
+
+/// Extension of segments compacted with LZ4 by earlier versions
+pub const LZ4_EXTENSION: &str = "lz4";
+
+/// Extension appended to the name of a compressed segment for the journal
+/// listing the segments it holds, while they are being removed
+pub const JOURNAL_EXTENSION: &str = "segments";
+
+/// Highest zstd compression level, the slowest and strongest
+pub const MAX_LEVEL: u32 = 22;
+
+/// zstd compression level compactions use unless told otherwise
+pub const DEFAULT_LEVEL: u32 = 19;
+
+/// Returns whether the segment at `path` is compressed, by its name.
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == COMPRESSED_EXTENSION || ext == LZ4_EXTENSION)
+}
+
+/// Reads a segment, decompressing it if it was compacted, and its
+/// buffers if they were written compressed (see `compression`).
+pub fn read_segment(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let data = match is_compressed(path) {
+        true => read_segment_as_compressed(path)?,
+        false => fs::read(path)?,
+    };
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    if has_frames(&data) {
+        return inflate(&data);
+    }
+    Ok(data)
+}
+
+/// Returns the journal of the compressed segment `path`.
+fn journal_path(path: &Path) -> PathBuf {
+    let mut journal = path.as_os_str().to_owned();
+    journal.push(".");
+    journal.push(JOURNAL_EXTENSION);
+    PathBuf::from(journal)
+}
+
+/// Returns the segments the compressed segment `path` holds that a
+/// compaction stopped before removing, as listed by its journal; empty if
+/// it has none.
+pub fn covered_segments(path: &Path) -> Vec<PathBuf> {
+    if !is_compressed(path) || !path.is_file() {
+        return Vec::new();
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    match fs::read_to_string(journal_path(path)) {
+        Ok(journal) => journal.lines().filter(|line| !line.is_empty()).map(|line| dir.join(line)).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Makes renames and removals in the directory of `path` durable.
+#[cfg(feature = "zstd")]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// One compressed file written by `Compaction::run`.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compacted {
+    /// The compressed file
+    pub path: PathBuf,
+
+    /// The segments merged into it, now removed
+    pub segments: Vec<PathBuf>,
+
+    /// Size of the segments together
+    pub bytes_in: u64,
+
+    /// Size of the compressed file
+    pub bytes_out: u64,
+}
+
+/// What `Compaction::run` did.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// The compressed files written, in stream order
+    pub compacted: Vec<Compacted>,
+
+    /// Segments left as they were: too recent, already compressed or
+    /// missing
+    pub skipped: Vec<PathBuf>,
+
+    /// Segments an interrupted compaction had merged into a compressed
+    /// file but not removed, now removed
+    pub recovered: Vec<PathBuf>,
+}
+
+#[cfg(feature = "zstd")]
+impl CompactionReport {
+    /// Returns the storage saved, in bytes.
+    pub fn bytes_saved(&self) -> u64 {
+        self.compacted.iter().map(|c| c.bytes_in.saturating_sub(c.bytes_out)).sum()
+    }
+}
+
+/// Settings of a compaction, see the module documentation.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone)]
+pub struct Compaction {
+    now: SystemTime,
+    min_age: Duration,
+    target_bytes: u64,
+    level: u32,
+}
+
+#[cfg(feature = "zstd")]
+impl Compaction {
+    /// Creates a compaction judging the age of segments as of `now`, of
+    /// segments older than a day into files of up to 64 MiB before
+    /// compression, at `DEFAULT_LEVEL`.
+    pub fn new(now: SystemTime) -> Self {
+        Compaction { now, min_age: Duration::from_secs(86400), target_bytes: 64 << 20, level: DEFAULT_LEVEL }
+    }
+
+    /// Compacts segments last modified at least `min_age` before now.
+    pub fn min_age(mut self, min_age: Duration) -> Self {
+        self.min_age = min_age;
+        self
+    }
+
+    /// Merges consecutive segments until they hold at least `bytes`
+    /// together. A segment larger than that is compressed on its own.
+    pub fn target_bytes(mut self, bytes: u64) -> Self {
+        self.target_bytes = bytes;
+        self
+    }
+
+    /// Sets the zstd compression level, from 1 (fastest) to `MAX_LEVEL`.
+    /// 0 picks zstd's own default.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(MAX_LEVEL);
+        self
+    }
+
+    /// Groups `segments`, given in stream order (oldest first), into the
+    /// runs `run` would merge, without changing anything.
+    ///
+    /// A run never spans a segment that is skipped, so the segments of a
+    /// compressed file are always consecutive in the log. Segments a
+    /// compressed file holds already (see `covered_segments`) are in
+    /// neither.
+    pub fn plan(&self, segments: &[PathBuf]) -> (Vec<Vec<PathBuf>>, Vec<PathBuf>) {
+        let covered: Vec<PathBuf> = segments.iter().flat_map(|segment| covered_segments(segment)).collect();
+        let (mut runs, mut skipped) = (Vec::new(), Vec::new());
+        let (mut run, mut run_bytes) = (Vec::new(), 0);
+        for segment in segments.iter().filter(|segment| !covered.contains(segment)) {
+            match self.aged_size(segment) {
+                Some(bytes) => {
+                    run.push(segment.clone());
+                    run_bytes += bytes;
+                    if run_bytes >= self.target_bytes {
+                        runs.push(std::mem::take(&mut run));
+                        run_bytes = 0;
+                    }
+                }
+                None => {
+                    skipped.push(segment.clone());
+                    if !run.is_empty() {
+                        runs.push(std::mem::take(&mut run));
+                        run_bytes = 0;
+                    }
+                }
+            }
+        }
+        if !run.is_empty() {
+            runs.push(run);
+        }
+        (runs, skipped)
+    }
+
+    /// Returns the size of `segment` if it is to be compacted.
+    fn aged_size(&self, segment: &Path) -> Option<u64> {
+        if is_compressed(segment) {
+            return None;
+        }
+        let metadata = fs::metadata(segment).ok().filter(|metadata| metadata.is_file())?;
+        let modified = metadata.modified().ok()?;
+        (self.now.duration_since(modified).ok()? >= self.min_age).then_some(metadata.len())
+    }
+
+    /// Compacts the aged runs of `segments`, given in stream order, after
+    /// removing the segments of compressed files among them that an
+    /// interrupted compaction left behind.
+    ///
+    /// Fails at the first run that can't be compacted. Runs compacted
+    /// before it stay compacted, and its own segments are left as they
+    /// were.
+    pub fn run(&self, segments: &[PathBuf]) -> io::Result<CompactionReport> {
+        let (runs, skipped) = self.plan(segments);
+        let mut report = CompactionReport { compacted: Vec::with_capacity(runs.len()), skipped, recovered: Vec::new() };
+        for compressed in segments {
+            let covered = covered_segments(compressed);
+            if !covered.is_empty() {
+                report.recovered.extend(remove_segments(compressed, &covered)?);
+            }
+        }
+        for run in runs {
+            report.compacted.push(self.compact_run(run)?);
+        }
+        Ok(report)
+    }
+
+    /// Merges and compresses one run, then removes its segments.
+    fn compact_run(&self, segments: Vec<PathBuf>) -> io::Result<Compacted> {
+        let mut data = Vec::new();
+        for segment in &segments {
+            File::open(segment)?.read_to_end(&mut data)?;
+        }
+
+        let mut path = segments[0].as_os_str().to_owned();
+        path.push(".");
+        path.push(COMPRESSED_EXTENSION);
+        let path = PathBuf::from(path);
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".compact-tmp");
+        let temp = PathBuf::from(temp);
+
+        let written = self.write_compressed(&temp, &data).and_then(|_| {
+            if read_segment_as_compressed(&temp)? != data {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "compressed segment doesn't read back the same"));
+            }
+            write_journal(&path, &segments)?;
+            fs::rename(&temp, &path)?;
+            sync_dir(&path)
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            if !path.exists() {
+                let _ = fs::remove_file(journal_path(&path));
+            }
+            return Err(e);
+        }
+
+        remove_segments(&path, &segments)?;
+        Ok(Compacted { bytes_out: fs::metadata(&path)?.len(), path, segments, bytes_in: data.len() as u64 })
+    }
+
+    fn write_compressed(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut encoder = zstd::stream::Encoder::new(File::create(path)?, self.level as i32)?;
+        encoder.include_checksum(true)?;
+        encoder.write_all(data)?;
+        encoder.finish()?.sync_all()
+    }
+}
+
+/// Lists `segments` in the journal of the compressed segment `path`, by
+/// name when they are in its directory.
+#[cfg(feature = "zstd")]
+fn write_journal(path: &Path, segments: &[PathBuf]) -> io::Result<()> {
+    let mut journal = File::create(journal_path(path))?;
+    for segment in segments {
+        let name = match (segment.parent() == path.parent(), segment.file_name()) {
+            (true, Some(name)) => Path::new(name),
+            _ => segment.as_path(),
+        };
+        writeln!(journal, "{}", name.display())?;
+    }
+    journal.sync_all()
+}
+
+/// Removes `segments`, which the compressed segment `path` holds, then its
+/// journal, returning those that were still there.
+#[cfg(feature = "zstd")]
+fn remove_segments(path: &Path, segments: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for segment in segments {
+        match fs::remove_file(segment) {
+            Ok(()) => removed.push(segment.clone()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    sync_dir(path)?;
+    fs::remove_file(journal_path(path))?;
+    sync_dir(path)?;
+    Ok(removed)
+}
+
+/// Reads the compressed file at `path`: LZ4 if it is named so, zstd
+/// otherwise.
+fn read_segment_as_compressed(path: &Path) -> io::Result<Vec<u8>> {
+    let lz4 = path.extension().is_some_and(|ext| ext == LZ4_EXTENSION);
+    #[cfg(feature = "lz4")]
+    if lz4 {
+        let mut data = Vec::new();
+        lz4::Decoder::new(File::open(path)?)?.read_to_end(&mut data)?;
+        return Ok(data);
+    }
+    #[cfg(feature = "zstd")]
+    if !lz4 {
+        let mut data = Vec::new();
+        zstd::stream::Decoder::new(File::open(path)?)?.read_to_end(&mut data)?;
+        return Ok(data);
+    }
+    let feature = if lz4 { "lz4" } else { "zstd" };
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} is compacted, which needs the {} feature", path.display(), feature)))
+}
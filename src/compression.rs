@@ -0,0 +1,226 @@
+//! Buffers compressed one at a time, as `CompressedFileHandler` writes
+//! them (`lz4` or `zstd` feature).
+//!
+//! Where `compact` compresses whole segments once they have aged, a
+//! compressed frame holds a single buffer, so a log can be written
+//! compressed as it grows and a reader still finds its way from buffer to
+//! buffer. A frame starts with a buffer header whose size has
+//! `COMPRESSED_FRAME` set:
+//!
+//! ```text
+//! | Size | COMPRESSED_FRAME (8B) | Codec (1B) | Reserved (3B) | Plain size (4B) | LZ4 or zstd frame |
+//! ```
+//!
+//! The codec byte names the compression (`CODEC_LZ4`, `CODEC_ZSTD`), and
+//! the frame after the header holds the buffer as the Logger wrote it,
+//! header included, so frames and plain buffers can follow each other in
+//! one log. Each codec is built with its feature; a build without it
+//! fails on its frames. As no Logger writes a buffer larger than
+//! `MAX_BUFFER_SIZE`, frames claiming or holding more are refused as
+//! damaged rather than decompressed.
+//! `LogReader` and `StreamReader` detect frames and decompress them
+//! transparently; positions in such logs, such as those of entry IDs and
+//! `LogReader::attach`, count the decompressed bytes. `compact::read_segment`
+//! decompresses them too, so the file-reading tools read such files like
+//! any other.
+
+use std::io::{self, Write};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use std::io::Read;
+use crate::binary_logger::{BUFFER_HEADER_SIZE, MAX_BUFFER_SIZE};
+
+/// Flag of the size field of a buffer header, marking a compressed frame
+pub const COMPRESSED_FRAME: u64 = crate::binary_logger::COMPRESSED_FRAME;
+
+/// Size of a frame's header, before the compressed data
+pub const FRAME_HEADER_SIZE: usize = 16;
+
+/// Codec byte of frames compressed with LZ4
+pub const CODEC_LZ4: u8 = 1;
+
+/// Codec byte of frames compressed with zstd
+pub const CODEC_ZSTD: u8 = 2;
+
+/// Compression of a frame, each built with its feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameCodec {
+    /// LZ4, fast at every level (`lz4` feature)
+    #[cfg(feature = "lz4")]
+    Lz4,
+
+    /// zstd, smaller frames at a higher cost (`zstd` feature)
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// LZ4, or zstd in builds without the `lz4` feature
+impl Default for FrameCodec {
+    fn default() -> Self {
+        #[cfg(feature = "lz4")]
+        return FrameCodec::Lz4;
+        #[cfg(not(feature = "lz4"))]
+        return FrameCodec::Zstd;
+    }
+}
+
+impl FrameCodec {
+    /// Returns the codec byte of the codec's frames.
+    pub fn id(self) -> u8 {
+        match self {
+            #[cfg(feature = "lz4")]
+            FrameCodec::Lz4 => CODEC_LZ4,
+            #[cfg(feature = "zstd")]
+            FrameCodec::Zstd => CODEC_ZSTD,
+        }
+    }
+
+    /// Returns the codec of the codec byte `id`, if this build has it.
+    pub fn by_id(id: u8) -> Option<FrameCodec> {
+        match id {
+            #[cfg(feature = "lz4")]
+            CODEC_LZ4 => Some(FrameCodec::Lz4),
+            #[cfg(feature = "zstd")]
+            CODEC_ZSTD => Some(FrameCodec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Returns the highest, slowest and strongest, level of the codec.
+    /// Level 0 is the fastest of LZ4 and zstd's default.
+    pub fn max_level(self) -> u32 {
+        match self {
+            #[cfg(feature = "lz4")]
+            FrameCodec::Lz4 => 12,
+            #[cfg(feature = "zstd")]
+            FrameCodec::Zstd => 22,
+        }
+    }
+
+    fn compress(self, data: &[u8], level: u32, out: Vec<u8>) -> io::Result<Vec<u8>> {
+        let level = level.min(self.max_level());
+        match self {
+            #[cfg(feature = "lz4")]
+            FrameCodec::Lz4 => {
+                let mut encoder = lz4::EncoderBuilder::new().level(level).build(out)?;
+                encoder.write_all(data)?;
+                let (out, result) = encoder.finish();
+                result.map(|_| out)
+            }
+            #[cfg(feature = "zstd")]
+            FrameCodec::Zstd => {
+                // Checksummed like LZ4 frames, so damage is found
+                let mut encoder = zstd::stream::Encoder::new(out, level as i32)?;
+                encoder.include_checksum(true)?;
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Decompresses `data` into `out`, stopping one byte past `limit`, so
+    /// a frame holding more than it claims is found without inflating it
+    /// whole.
+    fn decompress(self, data: &[u8], limit: usize, out: &mut Vec<u8>) -> io::Result<()> {
+        let limit = limit as u64 + 1;
+        match self {
+            #[cfg(feature = "lz4")]
+            FrameCodec::Lz4 => lz4::Decoder::new(data)?.take(limit).read_to_end(out).map(|_| ()),
+            #[cfg(feature = "zstd")]
+            FrameCodec::Zstd => zstd::stream::read::Decoder::with_buffer(data)?.take(limit).read_to_end(out).map(|_| ()),
+        }
+    }
+}
+
+/// Compresses `buffer`, a whole buffer as the Logger hands it over, into a
+/// frame of `codec` at `level` (0 to `FrameCodec::max_level`).
+pub fn compress_buffer(buffer: &[u8], codec: FrameCodec, level: u32) -> io::Result<Vec<u8>> {
+    if buffer.len() > MAX_BUFFER_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "buffer too large for a compressed frame"));
+    }
+    let plain_size = buffer.len() as u32;
+    let mut frame = vec![0; FRAME_HEADER_SIZE];
+    frame[8] = codec.id();
+    frame[12..16].copy_from_slice(&plain_size.to_le_bytes());
+    let mut frame = codec.compress(buffer, level, frame)?;
+    let size = frame.len() as u64 | COMPRESSED_FRAME;
+    frame[..8].copy_from_slice(&size.to_le_bytes());
+    Ok(frame)
+}
+
+/// Returns whether `data` holds any compressed frame, following the sizes
+/// of its buffer headers.
+pub fn has_frames(data: &[u8]) -> bool {
+    let mut pos = 0;
+    while let Some((size, compressed)) = buffer_at(data, pos) {
+        if compressed {
+            return true;
+        }
+        pos += size;
+    }
+    false
+}
+
+/// Returns `data` with every compressed frame replaced by the buffer it
+/// holds.
+///
+/// Fails on a damaged frame or one of a codec this build doesn't know.
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut plain = Vec::with_capacity(data.len() * 2);
+    inflate_into(data, &mut plain)?;
+    Ok(plain)
+}
+
+/// Inflates `data` like `inflate`, leaving out everything from the first
+/// damaged frame on, the way readers stop at damaged data.
+pub(crate) fn inflate_readable(data: &[u8]) -> Vec<u8> {
+    let mut plain = Vec::with_capacity(data.len() * 2);
+    let _ = inflate_into(data, &mut plain);
+    plain
+}
+
+/// Decompresses `frame`, one whole frame, into the buffer it holds.
+pub fn inflate_frame(frame: &[u8]) -> io::Result<Vec<u8>> {
+    let damaged = || io::Error::new(io::ErrorKind::InvalidData, "damaged compressed frame");
+    let header = frame.get(..FRAME_HEADER_SIZE).ok_or_else(damaged)?;
+    let Some(codec) = FrameCodec::by_id(header[8]) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown compressed frame codec {}", header[8])));
+    };
+    let plain_size = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+    if plain_size > MAX_BUFFER_SIZE {
+        return Err(damaged());
+    }
+    let mut plain = Vec::with_capacity(plain_size);
+    codec.decompress(&frame[FRAME_HEADER_SIZE..], plain_size, &mut plain)?;
+    if plain.len() != plain_size {
+        return Err(damaged());
+    }
+    Ok(plain)
+}
+
+fn inflate_into(data: &[u8], plain: &mut Vec<u8>) -> io::Result<()> {
+    let mut pos = 0;
+    while pos < data.len() {
+        let Some((size, compressed)) = buffer_at(data, pos) else {
+            // The rest is one buffer, as readers take it
+            plain.extend_from_slice(&data[pos..]);
+            break;
+        };
+        match compressed {
+            true => plain.extend_from_slice(&inflate_frame(&data[pos..pos + size])?),
+            false => plain.extend_from_slice(&data[pos..pos + size]),
+        }
+        pos += size;
+    }
+    Ok(())
+}
+
+/// Reads the buffer header at `pos`, returning the size of the buffer and
+/// whether it is a compressed frame, or None if there is no consistent
+/// header there.
+pub(crate) fn buffer_at(data: &[u8], pos: usize) -> Option<(usize, bool)> {
+    let header = u64::from_le_bytes(data.get(pos..pos + BUFFER_HEADER_SIZE)?.try_into().unwrap());
+    let compressed = header & COMPRESSED_FRAME != 0;
+    let size = usize::try_from(header & !COMPRESSED_FRAME).ok()?;
+    let min = if compressed { FRAME_HEADER_SIZE } else { BUFFER_HEADER_SIZE };
+    (size >= min && size <= data.len() - pos).then_some((size, compressed))
+}
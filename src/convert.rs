@@ -0,0 +1,774 @@
+#![allow(dead_code)]
+
+//! Conversion of existing text logs to the binary format, backing the
+//! `blog-convert` tool.
+//!
+//! `from_text` reads a text log line by line, splits each line into time,
+//! level, channel and message with a `TextPattern`, and writes the message
+//! as a record of the Logger, so historical logs get the compression and
+//! query tooling of logs written in binary in the first place.
+//!
+//! Messages become format strings the way a call site would have written
+//! them: numbers turn into arguments, and so do words with digits in them,
+//! such as IDs and addresses, so `took 42 ms for user u-1043` is stored
+//! as `took {} ms for user {}` once and two arguments per line. Conversion
+//! is lossless: a converted entry formats back to its message exactly,
+//! numbers are only taken as numbers when they are written the way the
+//! reader formats them, and messages that can't be split that way (those
+//! containing `{}`, or any once conversions in the process have registered
+//! `MAX_TEMPLATES` format strings) are stored whole. Lines that don't match the pattern continue the
+//! message of the line before them, like the lines of a stack trace.
+//!
+//! Records carry the times of their lines, read through the Logger's clock
+//! source (see `efficient_clock::ClockSource`); a line out of time order is
+//! logged at the time of the line before it. The format strings and channel
+//! names are registered in this process and published through the
+//! dictionary channel (`Logger::set_dictionary_channel`), so the converted
+//! log reads anywhere. Conversions share what they register, so a process
+//! converting many logs registers each string once; past `MAX_CHANNELS`
+//! channel names, lines are written on the default channel.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::LogReader;
+//! # use binary_logger::convert::{from_text, TextPattern};
+//! let text = "2024-03-01T12:00:00.250Z INFO [db] took 42 ms for user u-1043\n\
+//!             2024-03-01T12:00:01Z WARN [db] took 1250 ms for user u-770\n";
+//! let pattern: TextPattern = "{time} {level} [{channel}] {message}".parse().unwrap();
+//! let converted = from_text(text.as_bytes(), &pattern).unwrap();
+//! assert_eq!(converted.stats.records, 2);
+//! assert_eq!(converted.stats.templates, 1);
+//!
+//! let mut reader = LogReader::new(&converted.data);
+//! let entry = reader.read_entry().unwrap();
+//! assert_eq!(entry.format_string.as_deref(), Some("took {} ms for user {}"));
+//! assert_eq!(entry.format(), "took 42 ms for user u-1043");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone as _};
+use lazy_static::lazy_static;
+use regex::{CaptureLocations, Regex};
+use crate::arg_types::TypeTag;
+use crate::binary_logger::{BufferHandler, Logger};
+use crate::efficient_clock::ClockSource;
+use crate::level::Level;
+use crate::log_reader::LogValue;
+use crate::render::TimeZone;
+use crate::string_registry::register_string;
+
+/// Size of the converting Logger's buffers
+const CONVERT_BUFFER_SIZE: usize = 1 << 20;
+
+/// Most format strings conversions register in a process; messages after
+/// that are stored whole
+pub const MAX_TEMPLATES: usize = 16_384;
+
+/// Most channel names conversions register in a process; lines on other
+/// channels are written on the default channel
+pub const MAX_CHANNELS: usize = 1_024;
+
+/// Longest message stored, in bytes, to fit a record's payload
+const MAX_MESSAGE_LEN: usize = 60_000;
+
+/// Format string of messages stored whole
+const WHOLE_MESSAGE: &str = "{}";
+
+/// Argument sizes that readers take for a bool, integer, float or the
+/// like rather than a string
+const NON_STRING_SIZES: [usize; 4] = [1, 4, 8, 16];
+
+/// How the lines of a text log are laid out.
+///
+/// A pattern is the text of a line with its fields in braces: `{time}`,
+/// `{level}`, `{channel}`, `{message}` and `{_}` for a field to drop, such
+/// as `{time} {level} [{channel}] {message}`. `{time}` and `{message}` are
+/// required (patterns of `without_time` have no `{time}`), the others are
+/// optional, and a space matches any run of spaces. A field other than
+/// `{message}` ends at the first character of the text after it, so
+/// `{level}` and `{channel}` have no spaces and `{_}: ` drops everything up
+/// to the first colon. `{time}` reads RFC 3339 times, with a `T` or a space
+/// between date and time and a point or comma before the fraction, and UNIX
+/// times in seconds; `{time:FORMAT}` reads a `chrono` format such as
+/// `{time:%d/%m/%Y %H:%M:%S%.f}`, which must include the date. Times
+/// without an offset are in the pattern's time zone, UTC by default.
+#[derive(Debug, Clone)]
+pub struct TextPattern {
+    regex: Regex,
+    time_format: Option<String>,
+    time_zone: TimeZone,
+    /// Whether the message is the rest of the line after the match
+    message_last: bool,
+}
+
+impl TextPattern {
+    /// Returns the pattern reading times without an offset in `time_zone`.
+    pub fn with_time_zone(mut self, time_zone: TimeZone) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Parses a pattern whose lines carry no time, such as
+    /// `{level} {message}`, or have one that should be dropped with `{_}`.
+    /// Lines are then stamped with the time they are read, as when
+    /// ingesting a live stream (see `ingest`).
+    pub fn without_time(pattern: &str) -> Result<Self, String> {
+        Self::parse(pattern, false)
+    }
+
+    /// Parses the time field of a line to microseconds since the UNIX
+    /// epoch.
+    fn parse_time(&self, text: &str) -> Option<u64> {
+        let time = match &self.time_format {
+            Some(format) if format.contains("%z") || format.contains("%:z") || format.contains("%#z") => {
+                DateTime::parse_from_str(text, format).ok()?.timestamp_micros()
+            }
+            Some(format) => self.local_micros(NaiveDateTime::parse_from_str(text, format).ok()?)?,
+            None => {
+                let decimal;
+                let text = match text.find(',') {
+                    Some(comma) => {
+                        decimal = format!("{}.{}", &text[..comma], &text[comma + 1..]);
+                        decimal.as_str()
+                    }
+                    None => text,
+                };
+                if let Ok(time) = DateTime::parse_from_rfc3339(text).or_else(|_| DateTime::parse_from_rfc3339(&text.replacen(' ', "T", 1))) {
+                    time.timestamp_micros()
+                } else if let Some(time) = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"].iter()
+                    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok()) {
+                    self.local_micros(time)?
+                } else {
+                    let seconds: f64 = text.parse().ok().filter(|seconds: &f64| seconds.is_finite())?;
+                    (seconds * 1e6).round() as i64
+                }
+            }
+        };
+        u64::try_from(time).ok()
+    }
+
+    fn local_micros(&self, time: NaiveDateTime) -> Option<i64> {
+        match self.time_zone {
+            TimeZone::Utc => Some(time.and_utc().timestamp_micros()),
+            TimeZone::Local => Some(Local.from_local_datetime(&time).earliest()?.timestamp_micros()),
+            TimeZone::Fixed(seconds) => Some(time.and_utc().timestamp_micros() - seconds as i64 * 1_000_000),
+        }
+    }
+}
+
+/// Parses a pattern such as `{time} {level} [{channel}] {message}`.
+impl FromStr for TextPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, true)
+    }
+}
+
+impl TextPattern {
+    fn parse(s: &str, timed: bool) -> Result<Self, String> {
+        // Literals and fields, as (text, None) and (name, Some(format))
+        let mut pieces: Vec<(&str, Option<Option<&str>>)> = Vec::new();
+        let mut rest = s;
+        while !rest.is_empty() {
+            let Some(open) = rest.find('{') else {
+                pieces.push((rest, None));
+                break;
+            };
+            if open > 0 {
+                pieces.push((&rest[..open], None));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| format!("unclosed field in pattern {:?}", s))? + open;
+            let field = &rest[open + 1..close];
+            let (name, format) = field.split_once(':').map_or((field, None), |(name, format)| (name, Some(format)));
+            if name != "_" && pieces.iter().any(|&(seen, field)| field.is_some() && seen == name) {
+                return Err(format!("field {{{}}} appears twice in pattern {:?}", name, s));
+            }
+            if !matches!((name, format), ("time", _) | ("level" | "channel" | "message" | "_", None)) {
+                return Err(format!("unknown field {{{}}} in pattern {:?}", field, s));
+            }
+            pieces.push((name, Some(format)));
+            rest = &rest[close + 1..];
+        }
+        for (required, wanted) in [("time", timed), ("message", true)] {
+            let found = pieces.iter().any(|&(name, field)| field.is_some() && name == required);
+            if found != wanted {
+                let error = match wanted {
+                    true => format!("pattern {:?} has no {{{}}}", s, required),
+                    false => format!("pattern {:?} has a {{{}}} field, drop it with {{_}}", s, required),
+                };
+                return Err(error);
+            }
+        }
+
+        // A message ending the line is the rest of the line after the
+        // match, which spares the regex a pass over it
+        let message_last = pieces.last().is_some_and(|&(name, field)| field.is_some() && name == "message");
+        if message_last {
+            pieces.pop();
+        }
+        let mut regex = String::from("^");
+        let mut time_format = None;
+        for (i, &(text, field)) in pieces.iter().enumerate() {
+            let Some(format) = field else {
+                push_literal(&mut regex, text);
+                continue;
+            };
+            // Fields end before the first character of the literal after
+            // them, without backtracking
+            let end = match pieces.get(i + 1) {
+                Some(&(literal, None)) => literal.chars().next(),
+                _ => None,
+            };
+            match text {
+                "time" => {
+                    time_format = format.map(str::to_string);
+                    regex.push_str("(?P<time>");
+                    match format {
+                        Some(format) => push_time_format(&mut regex, format),
+                        None => regex.push_str(DEFAULT_TIME),
+                    }
+                    regex.push(')');
+                }
+                "level" | "channel" => {
+                    regex.push_str(&format!("(?P<{}>", text));
+                    match end {
+                        Some(end) if end != ' ' => regex.push_str(&format!(r"[^\s{}]+)", regex::escape(&end.to_string()))),
+                        _ => regex.push_str(r"\S+)"),
+                    }
+                }
+                "message" => regex.push_str("(?P<message>.*)"),
+                _ => match end {
+                    Some(end) => regex.push_str(&format!("[^{}]*", regex::escape(&end.to_string()))),
+                    None => regex.push_str(".*?"),
+                },
+            }
+        }
+        if !message_last {
+            regex.push('$');
+        }
+        let regex = Regex::new(&regex).map_err(|e| format!("invalid pattern {:?}: {}", s, e))?;
+        Ok(TextPattern { regex, time_format, time_zone: TimeZone::Utc, message_last })
+    }
+}
+
+/// What `{time}` matches: RFC 3339 and similar times, or UNIX seconds
+const DEFAULT_TIME: &str = r"[0-9]{4}-[0-9]{2}-[0-9]{2}[T ][0-9]{2}:[0-9]{2}:[0-9]{2}(?:[.,][0-9]+)?(?:Z|[+-][0-9]{2}:?[0-9]{2})?|[0-9]+(?:\.[0-9]+)?";
+
+/// Adds what the times of a `chrono` format look like, so that fields
+/// around the time can't take part of it.
+fn push_time_format(regex: &mut String, format: &str) {
+    let mut chars = format.chars();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        push_literal(regex, &std::mem::take(&mut literal));
+        let mut spec = String::new();
+        for c in chars.by_ref() {
+            spec.push(c);
+            if c.is_ascii_alphabetic() || c == '%' {
+                break;
+            }
+        }
+        regex.push_str(match spec.as_str() {
+            "Y" => r"[+-]?[0-9]{4,}",
+            "m" | "d" | "H" | "M" | "S" | "y" | "I" => r"[0-9]{1,2}",
+            "e" => r" ?[0-9]{1,2}",
+            "j" => r"[0-9]{1,3}",
+            "f" | "s" => r"[0-9]+",
+            ".f" | ".3f" | ".6f" | ".9f" => r"(?:\.[0-9]+)?",
+            "3f" | "6f" | "9f" => r"[0-9]+",
+            "z" => r"[+-][0-9]{2}:?[0-9]{2}",
+            ":z" | "#z" => r"[+-][0-9]{2}(?::?[0-9]{2})?",
+            "Z" => r"\S+",
+            "b" | "h" | "a" => r"[A-Za-z]{3}",
+            "B" | "A" => r"[A-Za-z]+",
+            "p" | "P" => r"[AaPp][Mm]",
+            "T" => r"[0-9]{1,2}:[0-9]{1,2}:[0-9]{1,2}",
+            "F" => r"[+-]?[0-9]{4,}-[0-9]{1,2}-[0-9]{1,2}",
+            "%" => "%",
+            _ => ".+?",
+        });
+    }
+    push_literal(regex, &literal);
+}
+
+/// Adds text that lines must contain as is, with a space matching any run
+/// of spaces.
+fn push_literal(regex: &mut String, text: &str) {
+    for (i, part) in text.split(' ').enumerate() {
+        if i > 0 {
+            regex.push_str(" +");
+        }
+        regex.push_str(&regex::escape(part));
+    }
+}
+
+/// What a conversion read and wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConvertStats {
+    /// Lines read
+    pub lines: u64,
+
+    /// Records written, one per line matching the pattern
+    pub records: u64,
+
+    /// Lines continuing the message of the line before them
+    pub continuations: u64,
+
+    /// Lines before the first matching one, which are dropped
+    pub skipped: u64,
+
+    /// Format strings used
+    pub templates: u64,
+
+    /// Records whose message is stored whole
+    pub whole_messages: u64,
+
+    /// Messages cut to the longest record payload
+    pub truncated: u64,
+
+    /// Records with a level field that isn't a level, written without one
+    pub unknown_levels: u64,
+
+    /// Records on channels past `MAX_CHANNELS`, written on the default
+    /// channel
+    pub unnamed_channels: u64,
+}
+
+impl fmt::Display for ConvertStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} lines, {} records, {} format strings, {} continuation lines, {} skipped lines",
+            self.lines, self.records, self.templates, self.continuations, self.skipped)?;
+        if self.whole_messages > 0 {
+            write!(f, ", {} messages stored whole", self.whole_messages)?;
+        }
+        if self.truncated > 0 {
+            write!(f, ", {} messages truncated", self.truncated)?;
+        }
+        if self.unknown_levels > 0 {
+            write!(f, ", {} unknown levels", self.unknown_levels)?;
+        }
+        if self.unnamed_channels > 0 {
+            write!(f, ", {} records on the default channel past {} channels", self.unnamed_channels, MAX_CHANNELS)?;
+        }
+        Ok(())
+    }
+}
+
+/// A text log converted to the binary format.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    /// The binary log
+    pub data: Vec<u8>,
+
+    /// What was read and written
+    pub stats: ConvertStats,
+}
+
+/// Converts the text log `reader` into a binary log in memory.
+///
+/// Fails only on read errors; for large logs, `convert_to` hands the
+/// buffers to a handler instead.
+pub fn from_text(reader: impl BufRead, pattern: &TextPattern) -> io::Result<Conversion> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let stats = convert_to(reader, pattern, Collector(data.clone()))?;
+    let data = std::mem::take(&mut *data.lock().unwrap_or_else(|e| e.into_inner()));
+    Ok(Conversion { data, stats })
+}
+
+/// Converts the text log `reader`, handing the binary log to `handler`
+/// buffer by buffer.
+pub fn convert_to(mut reader: impl BufRead, pattern: &TextPattern, handler: impl BufferHandler + 'static) -> io::Result<ConvertStats> {
+    let mut converter = Converter::new(pattern, handler);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        converter.push_line(&line)?;
+    }
+    converter.finish()
+}
+
+/// Parses a level name, also taking the names of levels this crate doesn't
+/// have for the nearest one.
+fn parse_level(name: &str) -> Option<Level> {
+    name.parse().ok().or_else(|| match name.to_ascii_lowercase().as_str() {
+        "trc" => Some(Level::Trace),
+        "dbg" => Some(Level::Debug),
+        "inf" | "notice" => Some(Level::Info),
+        "wrn" => Some(Level::Warn),
+        "err" | "fatal" | "critical" | "crit" => Some(Level::Error),
+        _ => None,
+    })
+}
+
+/// The wall clock time, which stamps lines of patterns without `{time}`.
+fn now_micros() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_micros() as u64)
+}
+
+/// Format strings and channel names registered by conversions, shared so
+/// each string is leaked and registered once however many logs a process
+/// converts.
+#[derive(Default)]
+struct Interned {
+    templates: HashMap<String, u32>,
+    channels: HashMap<String, u32>,
+}
+
+lazy_static! {
+    static ref INTERNED: Mutex<Interned> = Mutex::new(Interned::default());
+}
+
+/// Returns the ID of `text` in `strings`, registering it unless `max`
+/// strings are registered already.
+fn intern(strings: &mut HashMap<String, u32>, text: &str, max: usize) -> Option<u32> {
+    if let Some(&id) = strings.get(text) {
+        return Some(id);
+    }
+    if strings.len() >= max {
+        return None;
+    }
+    let id = register_string(Box::leak(text.to_string().into_boxed_str()));
+    strings.insert(text.to_string(), id);
+    Some(id)
+}
+
+/// The time of the line being written, as the converting Logger's clock.
+struct LineClock(AtomicU64);
+
+impl ClockSource for LineClock {
+    fn now_micros(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A line matching the pattern, with its continuation lines.
+struct Line {
+    time: u64,
+    level: Option<Level>,
+    channel: u32,
+    message: String,
+}
+
+/// Writes text lines as records, one line at a time.
+pub(crate) struct Converter {
+    logger: Logger<CONVERT_BUFFER_SIZE>,
+    clock: Arc<LineClock>,
+    pattern: TextPattern,
+    /// Capture group indexes of the pattern's fields
+    time: Option<usize>,
+    level: Option<usize>,
+    channel: Option<usize>,
+    message: Option<usize>,
+    locations: CaptureLocations,
+    /// The last line matching the pattern, waiting for continuation lines
+    pending: Option<Line>,
+    /// Time, level and channel of the last line written
+    last: Option<(u64, Option<Level>, u32)>,
+    /// Format IDs of the templates used so far, by template
+    templates: HashMap<String, u32>,
+    /// IDs of the channel names used so far, by name
+    channels: HashMap<String, u32>,
+    /// The template of the message being written, and its arguments
+    template: String,
+    arg_bytes: Vec<u8>,
+    arg_ends: Vec<usize>,
+    arg_types: Vec<TypeTag>,
+    stats: ConvertStats,
+}
+
+impl Converter {
+    pub(crate) fn new(pattern: &TextPattern, handler: impl BufferHandler + 'static) -> Self {
+        let clock = Arc::new(LineClock(AtomicU64::new(0)));
+        let mut logger = Logger::<CONVERT_BUFFER_SIZE>::new(handler);
+        logger.set_clock_source(Some(clock.clone()));
+        logger.set_dictionary_channel(true);
+        logger.set_header_compression(true);
+        logger.set_delta_timestamps(true);
+
+        let group = |field| pattern.regex.capture_names().position(|name| name == Some(field));
+        Converter {
+            logger,
+            clock,
+            pattern: pattern.clone(),
+            time: group("time"),
+            level: group("level"),
+            channel: group("channel"),
+            message: group("message"),
+            locations: pattern.regex.capture_locations(),
+            pending: None,
+            last: None,
+            templates: HashMap::new(),
+            channels: HashMap::new(),
+            template: String::new(),
+            arg_bytes: Vec::new(),
+            arg_ends: Vec::new(),
+            arg_types: Vec::new(),
+            stats: ConvertStats::default(),
+        }
+    }
+
+    /// Reads a line, with or without its line ending.
+    ///
+    /// The line is written when the next line matching the pattern comes,
+    /// or at `flush`, as lines that don't match continue it.
+    pub(crate) fn push_line(&mut self, line: &str) -> io::Result<()> {
+        self.stats.lines += 1;
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+
+        let matched = self.pattern.regex.captures_read(&mut self.locations, text).is_some();
+        let span = |index: Option<usize>| index.and_then(|index| self.locations.get(index)).filter(|_| matched);
+        let (level, channel) = (span(self.level), span(self.channel));
+        let message = match self.pattern.message_last {
+            true => span(Some(0)).map(|(_, end)| (end, text.len())),
+            false => span(self.message),
+        };
+        let time = match self.time {
+            Some(_) => span(self.time).and_then(|(start, end)| self.pattern.parse_time(&text[start..end])),
+            None => matched.then(now_micros),
+        };
+        let Some(time) = time else {
+            match (&mut self.pending, self.last) {
+                (Some(previous), _) => {
+                    previous.message.push('\n');
+                    previous.message.push_str(text);
+                }
+                // Continues a line already flushed, in a record of its own
+                (None, Some((time, level, channel))) => {
+                    self.pending = Some(Line { time, level, channel, message: text.to_string() });
+                }
+                (None, None) => {
+                    self.stats.skipped += 1;
+                    return Ok(());
+                }
+            }
+            self.stats.continuations += 1;
+            return Ok(());
+        };
+
+        if let Some(previous) = self.pending.take() {
+            self.write(&previous)?;
+        }
+        let level = level.and_then(|(start, end)| {
+            let parsed = parse_level(&text[start..end]);
+            self.stats.unknown_levels += parsed.is_none() as u64;
+            parsed
+        });
+        let channel = channel.map_or(0, |(start, end)| self.channel(&text[start..end]));
+        let message = message.map_or(String::new(), |(start, end)| text[start..end].to_string());
+        self.pending = Some(Line { time, level, channel, message });
+        Ok(())
+    }
+
+    /// Writes the pending line and hands the records written so far to the
+    /// handler.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        if let Some(previous) = self.pending.take() {
+            self.write(&previous)?;
+        }
+        self.logger.flush();
+        Ok(())
+    }
+
+    /// Writes the pending line and returns what was converted; the Logger
+    /// hands over its last buffer when dropped.
+    pub(crate) fn finish(mut self) -> io::Result<ConvertStats> {
+        if let Some(previous) = self.pending.take() {
+            self.write(&previous)?;
+        }
+        Ok(std::mem::take(&mut self.stats))
+    }
+
+    pub(crate) fn stats(&self) -> &ConvertStats {
+        &self.stats
+    }
+
+    fn write(&mut self, line: &Line) -> io::Result<()> {
+        let mut message = line.message.as_str();
+        if message.len() > MAX_MESSAGE_LEN {
+            let mut end = MAX_MESSAGE_LEN;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message = &message[..end];
+            self.stats.truncated += 1;
+        }
+
+        self.template.clear();
+        self.arg_bytes.clear();
+        self.arg_ends.clear();
+        self.arg_types.clear();
+        let format_id = match message.contains("{}") {
+            false => {
+                self.split(message);
+                match self.templates.get(self.template.as_str()) {
+                    Some(&id) => Some(id),
+                    None => {
+                        let id = intern(&mut INTERNED.lock().unwrap().templates, &self.template, MAX_TEMPLATES);
+                        if let Some(id) = id {
+                            self.templates.insert(self.template.clone(), id);
+                            self.stats.templates += 1;
+                        }
+                        id
+                    }
+                }
+            }
+            true => None,
+        };
+        let format_id = format_id.unwrap_or_else(|| {
+            self.template.clear();
+            self.arg_bytes.clear();
+            self.arg_ends.clear();
+            self.arg_types.clear();
+            self.push_string(message);
+            self.stats.whole_messages += 1;
+            register_string(WHOLE_MESSAGE)
+        });
+
+        let mut args = Vec::with_capacity(self.arg_ends.len());
+        let mut start = 0;
+        for &end in &self.arg_ends {
+            args.push(&self.arg_bytes[start..end]);
+            start = end;
+        }
+        self.clock.0.store(line.time, Ordering::Relaxed);
+        self.logger.write_typed_args(line.level, line.channel, format_id, &args, &self.arg_types)?;
+        self.stats.records += 1;
+        self.last = Some((line.time, line.level, line.channel));
+        Ok(())
+    }
+
+    /// Returns the ID of a channel name, registering it on first use, or
+    /// the default channel past `MAX_CHANNELS` names.
+    fn channel(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.channels.get(name) {
+            return id;
+        }
+        match intern(&mut INTERNED.lock().unwrap().channels, name, MAX_CHANNELS) {
+            Some(id) => {
+                self.channels.insert(name.to_string(), id);
+                id
+            }
+            None => {
+                self.stats.unnamed_channels += 1;
+                0
+            }
+        }
+    }
+
+    /// Splits a message without `{}` into the template and arguments.
+    fn split(&mut self, message: &str) {
+        let mut rest = message;
+        while !rest.is_empty() {
+            let space = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+            self.template.push_str(&rest[..space]);
+            rest = &rest[space..];
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            match word.split_once('=') {
+                Some((key, value)) if !key.is_empty() && !key.bytes().any(|b| b.is_ascii_digit()) => {
+                    self.template.push_str(key);
+                    self.template.push('=');
+                    self.push_word(value);
+                }
+                _ => self.push_word(word),
+            }
+        }
+    }
+
+    /// Adds a word as an argument if it holds digits, or as text.
+    fn push_word(&mut self, word: &str) {
+        let unopened = word.trim_start_matches(['(', '[', '"', '\'']);
+        let core = unopened.trim_end_matches([',', ';', ':', '.', ')', ']', '"', '\'']);
+        let start = word.len() - unopened.len();
+        let end = start + core.len();
+        if let Some(value) = number(core) {
+            self.template.push_str(&word[..start]);
+            self.template.push_str("{}");
+            let type_tag = match value {
+                LogValue::Integer(value) => {
+                    self.arg_bytes.extend_from_slice(&value.to_le_bytes());
+                    TypeTag::I32
+                }
+                LogValue::Float(value) => {
+                    self.arg_bytes.extend_from_slice(&value.to_le_bytes());
+                    TypeTag::F64
+                }
+                _ => unreachable!(),
+            };
+            self.arg_ends.push(self.arg_bytes.len());
+            self.arg_types.push(type_tag);
+            self.template.push_str(&word[end..]);
+        } else if word.bytes().any(|b| b.is_ascii_digit()) {
+            self.push_string(word);
+        } else {
+            self.template.push_str(word);
+        }
+    }
+
+    /// Adds text as string arguments, split so that none has a size readers
+    /// take for another type, or as text if it can't be.
+    fn push_string(&mut self, mut text: &str) {
+        while NON_STRING_SIZES.contains(&text.len()) {
+            // Split off the shortest head that leaves both parts strings
+            let split = text.char_indices().skip(1).map(|(i, _)| i)
+                .find(|&i| !NON_STRING_SIZES.contains(&i) && !NON_STRING_SIZES.contains(&(text.len() - i)));
+            let Some(split) = split else {
+                self.template.push_str(text);
+                return;
+            };
+            self.push_arg(&text[..split]);
+            text = &text[split..];
+        }
+        if !text.is_empty() {
+            self.push_arg(text);
+        }
+    }
+
+    fn push_arg(&mut self, text: &str) {
+        self.template.push_str("{}");
+        self.arg_bytes.extend_from_slice(text.as_bytes());
+        self.arg_ends.push(self.arg_bytes.len());
+        self.arg_types.push(TypeTag::STR);
+    }
+}
+
+/// Returns a word as the number a reader formats back to the same text:
+/// an `i32`, or an `f64` with a point or exponent.
+fn number(word: &str) -> Option<LogValue> {
+    let first = word.bytes().next()?;
+    if !(first.is_ascii_digit() || first == b'-') {
+        return None;
+    }
+    let value = match word.parse::<i32>() {
+        Ok(value) => LogValue::Integer(value),
+        Err(_) if word.contains(['.', 'e', 'E']) => LogValue::Float(word.parse::<f64>().ok().filter(|value| value.is_finite())?),
+        Err(_) => return None,
+    };
+    (value.to_string() == word).then_some(value)
+}
+
+/// Collects the buffers of `from_text`.
+struct Collector(Arc<Mutex<Vec<u8>>>);
+
+impl BufferHandler for Collector {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(data);
+    }
+}
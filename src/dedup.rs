@@ -0,0 +1,222 @@
+#![allow(dead_code)]
+
+//! Suppression of identical records in a time window.
+//!
+//! A failing dependency can make one call site log the same error
+//! thousands of times a second, filling the sinks with copies of one line.
+//! With a `DuplicateFilter` set on a Logger (`Logger::set_duplicate_filter`),
+//! the first record of a format with given arguments is written and opens a
+//! window; identical records in the window, those with the same format ID,
+//! channel and captured arguments, are only counted. When the window
+//! closes, the Logger writes an `InternalEvent::DuplicatesSuppressed` record
+//! with the count, the format ID and the time over which the records were
+//! counted, at the level and on the channel of the suppressed records, and the next identical record is written again.
+//! A record with other arguments, such as another error code, has a window
+//! of its own.
+//!
+//! Windows are set per format ID, with a default for the formats without
+//! one. The Logger checks for closed windows on every write, and writes the
+//! counts of open windows on `flush` and when it is dropped, so no
+//! suppressed record goes unreported. At most `max_entries` windows are
+//! open at once; a record that finds no room is written.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, log_record_at, register_string};
+//! # use binary_logger::dedup::DuplicateFilter;
+//! # use binary_logger::level::Level;
+//! # use std::time::Duration;
+//! # struct NullHandler;
+//! # impl BufferHandler for NullHandler {
+//! #     fn handle_buffer(&self, _data: &[u8]) {}
+//! # }
+//! let mut logger = Logger::<65536>::new(NullHandler);
+//! // The same error at most once per second, and timeouts once a minute
+//! logger.set_duplicate_filter(Some(DuplicateFilter::new()
+//!     .default_window(Duration::from_secs(1))
+//!     .window(register_string("Upstream {} timed out"), Duration::from_secs(60))));
+//! for _ in 0..1000 {
+//!     log_record_at!(logger, Level::Error, "Disk {} failed", 3).unwrap();
+//! }
+//! assert_eq!(logger.stats().duplicates_suppressed, 999);
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::time::{Duration, Instant};
+use crate::level::Level;
+use crate::sampling::KeyHasher;
+
+/// Windows open at once by default
+pub const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// Settings of duplicate suppression, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateFilter {
+    default_window: Option<Duration>,
+    windows: HashMap<u32, Option<Duration>>,
+    max_entries: usize,
+}
+
+impl Default for DuplicateFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DuplicateFilter {
+    /// Creates a filter suppressing nothing, until windows are set.
+    pub fn new() -> Self {
+        DuplicateFilter { default_window: None, windows: HashMap::new(), max_entries: DEFAULT_MAX_ENTRIES }
+    }
+
+    /// Suppresses duplicates of every format without a window of its own
+    /// for `window`.
+    pub fn default_window(mut self, window: Duration) -> Self {
+        self.default_window = Some(window).filter(|window| !window.is_zero());
+        self
+    }
+
+    /// Suppresses duplicates of the format `format_id` (see
+    /// `register_string`) for `window`.
+    pub fn window(mut self, format_id: u32, window: Duration) -> Self {
+        self.windows.insert(format_id, Some(window).filter(|window| !window.is_zero()));
+        self
+    }
+
+    /// Never suppresses records of the format `format_id`, whatever the
+    /// default window.
+    pub fn exempt(mut self, format_id: u32) -> Self {
+        self.windows.insert(format_id, None);
+        self
+    }
+
+    /// Keeps at most `entries` windows open at once (at least 1).
+    pub fn max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = entries.max(1);
+        self
+    }
+
+    /// Returns the window of the format `format_id`, if its duplicates are
+    /// suppressed.
+    pub fn window_for(&self, format_id: u32) -> Option<Duration> {
+        match self.windows.get(&format_id) {
+            Some(window) => *window,
+            None => self.default_window,
+        }
+    }
+}
+
+/// A window opened by a record, and the duplicates counted in it.
+#[derive(Debug)]
+struct Window {
+    opened: Instant,
+    length: Duration,
+    /// Start of the duplicates counted, later than `opened` once counts were
+    /// written before the window closed
+    since: Instant,
+    suppressed: u32,
+    level: Option<Level>,
+}
+
+impl Window {
+    fn closes(&self) -> Instant {
+        self.opened + self.length
+    }
+}
+
+/// Duplicates counted in a window, to be written as a summary record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Summary {
+    pub format_id: u32,
+    pub channel: u32,
+    pub suppressed: u32,
+    pub millis: u32,
+    pub level: Option<Level>,
+}
+
+/// The open windows of a Logger.
+#[derive(Debug)]
+pub(crate) struct DuplicateCache {
+    filter: DuplicateFilter,
+    /// Windows by format ID, channel and hash of the arguments
+    windows: HashMap<(u32, u32, u64), Window>,
+    /// When the first window holding duplicates closes
+    next_close: Option<Instant>,
+}
+
+impl DuplicateCache {
+    pub fn new(filter: DuplicateFilter) -> Self {
+        DuplicateCache { filter, windows: HashMap::new(), next_close: None }
+    }
+
+    pub fn filter(&self) -> &DuplicateFilter {
+        &self.filter
+    }
+
+    /// Returns whether a record of `format_id` on `channel` with `args` is
+    /// written, counting it as a duplicate otherwise.
+    pub fn admit(&mut self, format_id: u32, channel: u32, args: &[&[u8]], level: Option<Level>, now: Instant) -> bool {
+        let Some(length) = self.filter.window_for(format_id) else { return true };
+        let mut hasher = KeyHasher::new(0);
+        for arg in args {
+            hasher.write_usize(arg.len());
+            hasher.write(arg);
+        }
+        let key = (format_id, channel, hasher.finish());
+        if let Some(window) = self.windows.get_mut(&key) {
+            if now < window.closes() {
+                if window.suppressed == 0 {
+                    window.since = now;
+                    self.next_close = Some(self.next_close.map_or(window.closes(), |next| next.min(window.closes())));
+                }
+                window.suppressed = window.suppressed.saturating_add(1);
+                return false;
+            }
+        } else if self.windows.len() >= self.filter.max_entries {
+            self.windows.retain(|_, window| now < window.closes() || window.suppressed > 0);
+            if self.windows.len() >= self.filter.max_entries {
+                return true;
+            }
+        }
+        self.windows.insert(key, Window { opened: now, length, since: now, suppressed: 0, level });
+        true
+    }
+
+    /// Returns whether a window holding duplicates has closed.
+    #[inline]
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_close.is_some_and(|next| now >= next)
+    }
+
+    /// Takes the counts of the windows closed by `now`, or of every window
+    /// with `all`, in format ID order. Closed windows are removed, and open
+    /// ones count anew.
+    pub fn take_summaries(&mut self, now: Instant, all: bool) -> Vec<Summary> {
+        let mut summaries = Vec::new();
+        let mut next_close = None;
+        self.windows.retain(|&(format_id, channel, _), window| {
+            let closed = now >= window.closes();
+            if window.suppressed > 0 && (closed || all) {
+                let end = now.min(window.closes());
+                let millis = end.saturating_duration_since(window.since).as_millis().min(u32::MAX as u128) as u32;
+                summaries.push(Summary { format_id, channel, suppressed: window.suppressed, millis, level: window.level });
+                window.suppressed = 0;
+            }
+            if !closed && window.suppressed > 0 {
+                next_close = Some(next_close.map_or(window.closes(), |next: Instant| next.min(window.closes())));
+            }
+            !closed
+        });
+        self.next_close = next_close;
+        summaries.sort_unstable_by_key(|summary| (summary.format_id, summary.channel, summary.millis));
+        summaries
+    }
+
+    /// Forgets every window, as in a forked child.
+    pub fn clear(&mut self) {
+        self.windows.clear();
+        self.next_close = None;
+    }
+}
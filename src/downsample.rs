@@ -0,0 +1,237 @@
+#![allow(dead_code)]
+
+//! Time-bucketed aggregates of a log, for dashboards.
+//!
+//! Feeding a dashboard doesn't need every entry: `Downsampler` reduces a log
+//! to one `Bucket` per interval with the number of entries, the number of
+//! errors and a percentile (p99 by default) of one numeric argument of a
+//! chosen format string. Only the records of that format string are
+//! decoded; the others are counted from their headers (see
+//! `LogReader::skip_while_header`), so even huge logs are reduced quickly.
+//!
+//! The result renders as CSV or as the JSON time series of Grafana's JSON
+//! data sources (`[{"target": ..., "datapoints": [[value, ms], ...]}]`).
+//! The `blog-downsample` tool does this from the command line.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, log_record, log_record_at};
+//! # use binary_logger::downsample::Downsampler;
+//! # use binary_logger::level::Level;
+//! # use std::sync::{Arc, Mutex};
+//! # use std::time::Duration;
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! for latency in [12.0, 15.5, 240.0] {
+//!     log_record!(logger, "Request served in {} ms", latency).unwrap();
+//! }
+//! log_record_at!(logger, Level::Error, "Upstream {} unreachable", 3).unwrap();
+//! # }
+//! # let data = data.lock().unwrap();
+//! let series = Downsampler::new(Duration::from_secs(60))
+//!     .with_value("Request served in {} ms", 0)
+//!     .run(&data);
+//! let total: u64 = series.buckets.iter().map(|bucket| bucket.count).sum();
+//! assert_eq!(total, 4);
+//! println!("{}", series.to_csv());
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::level::Level;
+use crate::log_reader::LogReader;
+use crate::render::{RenderOptions, TimeFormat};
+use crate::string_registry::register_string;
+
+/// Reduces logs to per-interval aggregates.
+#[derive(Debug, Clone)]
+pub struct Downsampler {
+    interval: Duration,
+    value: Option<(&'static str, usize)>,
+    quantile: f64,
+}
+
+/// Aggregates of the entries in one interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    /// Start of the interval, a multiple of the interval since the epoch
+    pub start: SystemTime,
+
+    /// Entries in the interval
+    pub count: u64,
+
+    /// Entries at level `Error`
+    pub errors: u64,
+
+    /// Number of values of the chosen argument in the interval
+    pub samples: u64,
+
+    /// The chosen percentile of those values, `None` without values
+    pub percentile: Option<f64>,
+}
+
+/// The buckets of a log, see `Downsampler::run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Downsampled {
+    /// Length of each bucket
+    pub interval: Duration,
+
+    /// Percentile of the `percentile` column, from 0.0 to 1.0
+    pub quantile: f64,
+
+    /// One bucket per interval from the first entry to the last, including
+    /// empty ones, in time order
+    pub buckets: Vec<Bucket>,
+}
+
+/// Counts and values collected for a bucket.
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    errors: u64,
+    values: Vec<f64>,
+}
+
+impl Downsampler {
+    /// Creates a downsampler with buckets of `interval`, at least a
+    /// microsecond.
+    pub fn new(interval: Duration) -> Self {
+        Downsampler { interval: interval.max(Duration::from_micros(1)), value: None, quantile: 0.99 }
+    }
+
+    /// Aggregates argument `arg` (from 0) of the entries logged with
+    /// `format`. Arguments that aren't numbers are ignored.
+    pub fn with_value(mut self, format: &'static str, arg: usize) -> Self {
+        self.value = Some((format, arg));
+        self
+    }
+
+    /// Reports the `quantile` percentile of the values instead of the 99th,
+    /// from 0.0 (the minimum) to 1.0 (the maximum).
+    pub fn with_quantile(mut self, quantile: f64) -> Self {
+        self.quantile = if quantile.is_nan() { 0.99 } else { quantile.clamp(0.0, 1.0) };
+        self
+    }
+
+    /// Reduces a log to its buckets.
+    pub fn run(&self, data: &[u8]) -> Downsampled {
+        let interval = self.interval.as_micros().min(u64::MAX as u128) as u64;
+        let value_format = self.value.map(|(format, _)| register_string(format));
+        let mut accumulators: BTreeMap<u64, Accumulator> = BTreeMap::new();
+
+        let mut reader = LogReader::new(data);
+        let bucket_of = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64 / interval;
+        loop {
+            // Entries without a value to aggregate are counted from their header
+            reader.skip_while_header(|header| {
+                if Some(header.format_id) == value_format && header.custom_type.is_none() {
+                    return false;
+                }
+                let bucket = accumulators.entry(bucket_of(header.timestamp)).or_default();
+                bucket.count += 1;
+                bucket.errors += (header.level == Some(Level::Error)) as u64;
+                true
+            });
+            let Some(entry) = reader.read_entry() else { break };
+            let bucket = accumulators.entry(bucket_of(entry.timestamp)).or_default();
+            bucket.count += 1;
+            bucket.errors += (entry.level == Some(Level::Error)) as u64;
+            if let Some(value) = self.value.and_then(|(_, arg)| entry.parameters.get(arg)).and_then(|value| value.as_f64()) {
+                bucket.values.push(value);
+            }
+        }
+
+        let mut buckets = Vec::new();
+        if let (Some(&first), Some(&last)) = (accumulators.keys().next(), accumulators.keys().next_back()) {
+            for index in first..=last {
+                let accumulator = accumulators.remove(&index).unwrap_or_default();
+                buckets.push(Bucket {
+                    start: UNIX_EPOCH + Duration::from_micros(index * interval),
+                    count: accumulator.count,
+                    errors: accumulator.errors,
+                    samples: accumulator.values.len() as u64,
+                    percentile: percentile(accumulator.values, self.quantile),
+                });
+            }
+        }
+        Downsampled { interval: self.interval, quantile: self.quantile, buckets }
+    }
+}
+
+/// Returns the nearest-rank `quantile` of `values`.
+fn percentile(mut values: Vec<f64>, quantile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let rank = ((quantile * values.len() as f64).ceil() as usize).clamp(1, values.len());
+    let (_, value, _) = values.select_nth_unstable_by(rank - 1, f64::total_cmp);
+    Some(*value)
+}
+
+impl Downsampled {
+    /// Returns the name of the percentile column, such as `p99` or `p99.9`.
+    pub fn percentile_name(&self) -> String {
+        // Rounded to hide the binary representation of e.g. 0.999
+        let percent = (self.quantile * 1e6).round() / 1e4;
+        format!("p{}", percent)
+    }
+
+    /// Renders the buckets as CSV with a header line: the start of each
+    /// interval in RFC 3339 (UTC), then the count, errors, samples and
+    /// percentile, which is empty for intervals without values.
+    pub fn to_csv(&self) -> String {
+        let time = RenderOptions { time_format: TimeFormat::Rfc3339, fraction_digits: 3, ..RenderOptions::default() };
+        let mut out = format!("time,count,errors,samples,{}\n", self.percentile_name());
+        for bucket in &self.buckets {
+            let _ = write!(out, "{},{},{},{},", time.format_time(bucket.start), bucket.count, bucket.errors, bucket.samples);
+            if let Some(value) = bucket.percentile {
+                let _ = write!(out, "{}", value);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders the buckets as the time series of a Grafana JSON data
+    /// source: one target each for `count`, `errors` and the percentile,
+    /// with `[value, milliseconds since the epoch]` data points. Intervals
+    /// without values have no percentile point.
+    pub fn to_grafana_json(&self) -> String {
+        let millis = |bucket: &Bucket| bucket.start.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let mut out = String::from("[");
+        for (i, target) in ["count", "errors", &self.percentile_name()].into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{{\"target\":\"{}\",\"datapoints\":[", target);
+            let mut first = true;
+            for bucket in &self.buckets {
+                let value = match i {
+                    0 => bucket.count as f64,
+                    1 => bucket.errors as f64,
+                    _ => match bucket.percentile {
+                        Some(value) if value.is_finite() => value,
+                        _ => continue,
+                    },
+                };
+                if !first {
+                    out.push(',');
+                }
+                first = false;
+                let _ = write!(out, "[{},{}]", value, millis(bucket));
+            }
+            out.push_str("]}");
+        }
+        out.push(']');
+        out
+    }
+}
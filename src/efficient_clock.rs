@@ -1,24 +1,208 @@
 #![allow(dead_code)]
 
+//! High-precision timestamp utilities for efficient logging.
+//!
+//! This module provides mechanisms for generating and managing high-resolution 
+//! timestamps with minimal overhead using CPU hardware counters when available.
+
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::_rdtsc;
 
-/// High-precision timestamp utilities for efficient logging.
-///
-/// This module provides mechanisms for generating and managing high-resolution 
-/// timestamps with minimal overhead using CPU hardware counters when available.
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-/// Conversion factor: how many CPU ticks per relative timestamp unit.
-/// Adjust this constant to match your CPU and desired resolution.
-const TICKS_PER_UNIT: u64 = 30_000;
 /// Maximum value that can be stored in 16 bits.
 const REL_MAX: u64 = u16::MAX as u64;
 
+/// Length of one relative timestamp epoch in microseconds.
+pub const EPOCH_MICROS: u64 = REL_MAX + 1;
+
+/// Number of epochs a base may span in wrap-epoch mode before a new base is
+/// forced, so full timestamps still appear periodically (about every 67s).
+const MAX_EPOCHS: u64 = 1024;
+
+/// How long the hardware counter is sampled against the monotonic clock
+/// when calibrating on x86_64.
+const CALIBRATION_WINDOW: Duration = Duration::from_millis(2);
+
+/// Process-wide mapping from hardware counter ticks to wall-clock time.
+///
+/// Every `TimestampConverter` in the process converts ticks with the same
+/// calibration, so timestamps written by different per-thread loggers land on
+/// one common timeline and can be merged.
+///
+/// Counter frequencies differ by architecture and aren't always a whole
+/// number of ticks per microsecond (the ARM generic timer often runs at
+/// 19.2 or 24MHz), so ticks are converted with a fixed-point rate rather
+/// than divided by a rounded frequency. Logs only ever hold microseconds.
+#[derive(Copy, Clone, Debug)]
+pub struct ClockCalibration {
+    /// Counter value at the anchor point
+    pub anchor_ticks: u64,
+    /// Microseconds since the UNIX epoch at the anchor point
+    pub anchor_micros: u64,
+    /// Counter frequency in ticks per second (always at least 1)
+    pub ticks_per_second: u64,
+    /// Microseconds per tick with 32 fractional bits
+    micros_per_tick: u64,
+}
+
+impl ClockCalibration {
+    /// Creates a calibration for a counter running at `ticks_per_second`
+    /// that read `anchor_ticks` at `anchor_micros` microseconds since the
+    /// UNIX epoch.
+    pub fn new(anchor_ticks: u64, anchor_micros: u64, ticks_per_second: u64) -> Self {
+        let ticks_per_second = ticks_per_second.max(1);
+        let rate = ((1_000_000u128 << 32) + ticks_per_second as u128 / 2) / ticks_per_second as u128;
+        ClockCalibration { anchor_ticks, anchor_micros, ticks_per_second, micros_per_tick: rate as u64 }
+    }
+
+    /// Converts an absolute counter value to microseconds since the UNIX epoch.
+    #[inline(always)]
+    pub fn ticks_to_micros(&self, ticks: u64) -> u64 {
+        self.anchor_micros + self.ticks_as_micros(ticks.saturating_sub(self.anchor_ticks))
+    }
+
+    /// Converts a number of counter ticks to whole microseconds.
+    #[inline(always)]
+    pub fn ticks_as_micros(&self, ticks: u64) -> u64 {
+        ((ticks as u128 * self.micros_per_tick as u128) >> 32).min(u64::MAX as u128) as u64
+    }
+
+    /// Converts a number of microseconds to counter ticks.
+    pub fn micros_as_ticks(&self, micros: u64) -> u64 {
+        (micros as u128 * self.ticks_per_second as u128 / 1_000_000).min(u64::MAX as u128) as u64
+    }
+}
+
+/// The calibration measured at startup, which `reanchor` measures the
+/// counter's frequency from.
+static ORIGIN: OnceLock<ClockCalibration> = OnceLock::new();
+
+/// The calibration in use: `ORIGIN` until the first `reanchor`. Replaced
+/// calibrations are never freed, as loggers and signal handlers may still
+/// hold them.
+static CURRENT: AtomicPtr<ClockCalibration> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Serializes `reanchor` calls.
+static REANCHOR: Mutex<()> = Mutex::new(());
+
+/// Shortest time since startup over which `reanchor` measures the counter's
+/// frequency, rather than keeping the startup measurement.
+const MIN_REANCHOR_BASELINE: Duration = Duration::from_secs(1);
+
+/// Returns the process-wide clock calibration, measuring it on first use.
+///
+/// On x86_64 the TSC frequency is measured against `Instant` over a short
+/// window (about 2ms, paid once per process). On aarch64 the frequency is read
+/// from `CNTFRQ_EL0`, and on other platforms, such as wasm, the counter
+/// already is in nanoseconds. `reanchor` refines it for long-running
+/// processes.
+pub fn calibration() -> &'static ClockCalibration {
+    let current = CURRENT.load(Ordering::Acquire);
+    if !current.is_null() {
+        return unsafe { &*current };
+    }
+    let origin = ORIGIN.get_or_init(calibrate) as *const ClockCalibration as *mut ClockCalibration;
+    match CURRENT.compare_exchange(std::ptr::null_mut(), origin, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => unsafe { &*origin },
+        Err(current) => unsafe { &*current },
+    }
+}
+
+/// Re-measures the counter's frequency and steers the process-wide
+/// calibration back to the wall clock, returning how far log time was
+/// behind it in microseconds (negative when ahead).
+///
+/// A frequency measured over 2ms at startup can be off by hundreds of ppm,
+/// which adds up to seconds a day. `reanchor` measures it again over all
+/// the time since startup, and corrects the offset accumulated so far by
+/// running the clock slightly fast or slow for `slew`: the new calibration
+/// continues from the current one, so log time never jumps, and meets the
+/// wall clock once `slew` has passed. Long-running services call it at
+/// intervals of about `slew`, such as every minute; until the next call the
+/// clock keeps the slewed rate. Loggers switch to the new calibration at
+/// their next time base, each new buffer at the latest. A step of the wall
+/// clock, such as from NTP, is steered out like any other offset.
+pub fn reanchor(slew: Duration) -> i64 {
+    let _serialized = REANCHOR.lock().unwrap_or_else(|e| e.into_inner());
+    let current = calibration();
+    let origin = ORIGIN.get().unwrap_or(current);
+    let ticks = get_timestamp();
+    let wall = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64;
+    let logged = current.ticks_to_micros(ticks);
+
+    let baseline = wall.saturating_sub(origin.anchor_micros);
+    let ticks_per_second = if baseline >= MIN_REANCHOR_BASELINE.as_micros() as u64 {
+        (ticks.saturating_sub(origin.anchor_ticks) as u128 * 1_000_000 / baseline as u128) as u64
+    } else {
+        origin.ticks_per_second
+    };
+
+    // Over `slew`, its worth of ticks must cover `slew` plus the offset,
+    // which is capped to keep the rate within a factor of two
+    let offset = wall as i64 - logged as i64;
+    let slew = slew.as_micros().clamp(1, i64::MAX as u128 / 4) as i64;
+    let correction = offset.clamp(-slew / 2, slew / 2);
+    let rate = ticks_per_second as u128 * slew as u128 / (slew + correction) as u128;
+    let next = Box::new(ClockCalibration::new(ticks, logged, rate as u64));
+    CURRENT.store(Box::into_raw(next), Ordering::Release);
+    offset
+}
+
+/// A source of time other than the hardware counter, such as the virtual
+/// clock of `sim` or the times of converted text logs (`convert`).
+///
+/// A `TimestampConverter` given one with `set_clock_source` takes every
+/// timestamp from it, so a scenario replayed against the same times writes
+/// the same bytes.
+pub trait ClockSource: Send + Sync {
+    /// Returns the current time in microseconds since the UNIX epoch.
+    fn now_micros(&self) -> u64;
+}
+
+/// The `Instant` that a clock source's time 0 maps to in `instant_now`.
+static SOURCE_ORIGIN: OnceLock<Instant> = OnceLock::new();
+
+fn calibrate() -> ClockCalibration {
+    let anchor_ticks = get_timestamp();
+    let anchor_micros = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+
+    #[cfg(target_arch = "x86_64")]
+    let ticks_per_second = {
+        let start = std::time::Instant::now();
+        let start_ticks = get_timestamp();
+        while start.elapsed() < CALIBRATION_WINDOW {
+            std::hint::spin_loop();
+        }
+        let end_ticks = get_timestamp();
+        let elapsed = start.elapsed().as_nanos().max(1);
+        (end_ticks.saturating_sub(start_ticks) as u128 * 1_000_000_000 / elapsed) as u64
+    };
+
+    #[cfg(target_arch = "aarch64")]
+    let ticks_per_second = unsafe {
+        let freq: u64;
+        std::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq);
+        freq
+    };
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let ticks_per_second = 1_000_000_000;
+
+    ClockCalibration::new(anchor_ticks, anchor_micros, ticks_per_second)
+}
+
 /// Converts high-precision timestamps to efficient relative values.
 ///
 /// This struct manages timestamp conversion for binary logging, providing:
 /// 
 /// 1. Compression - Converts 64-bit absolute timestamps to 16-bit relative values
+///    (in microseconds since the current base)
 /// 2. Base resets - Automatically resets the base when relative values overflow
 /// 3. Zero overhead - Uses CPU hardware counters for maximum performance
 /// 
@@ -39,9 +223,18 @@ const REL_MAX: u64 = u16::MAX as u64;
 /// let (rel_ts2, is_base_ts2) = converter.get_relative_timestamp();
 /// assert!(!is_base_ts2);
 /// ```
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct TimestampConverter {
-    current_base: Option<u64>
+    /// The current base in microseconds since the UNIX epoch
+    current_base: Option<u64>,
+    /// The process-wide calibration as of the current base
+    calibration: Option<&'static ClockCalibration>,
+    wrap_epochs: bool,
+    last_delta: u64,
+    /// The latest time returned, which later bases never precede
+    last_micros: u64,
+    /// Where time comes from instead of the hardware counter
+    source: Option<Arc<dyn ClockSource>>,
 }
 
 impl TimestampConverter {
@@ -51,7 +244,52 @@ impl TimestampConverter {
     /// `get_relative_timestamp()` will set the base and return 0.
     #[inline(always)]
     pub const fn new() -> Self {
-        Self { current_base: None }
+        Self {
+            current_base: None,
+            calibration: None,
+            wrap_epochs: false,
+            last_delta: 0,
+            last_micros: 0,
+            source: None,
+        }
+    }
+
+    /// Takes timestamps from `source` rather than the hardware counter, or
+    /// from the counter again with `None`.
+    ///
+    /// The next timestamp starts a new base. Times are taken as they come,
+    /// so a source that steps back makes the converter hold the time until
+    /// the source catches up.
+    pub fn set_clock_source(&mut self, source: Option<Arc<dyn ClockSource>>) {
+        self.source = source;
+        self.current_base = None;
+        self.last_micros = 0;
+    }
+
+    /// Returns the current monotonic time: `Instant::now()`, or the time of
+    /// the clock source, if any, as an `Instant`.
+    ///
+    /// Durations the Logger measures, such as handler calls and
+    /// suppression intervals, are taken from this, so they follow a virtual
+    /// clock too.
+    #[inline(always)]
+    pub fn instant_now(&self) -> Instant {
+        if let Some(source) = &self.source {
+            return *SOURCE_ORIGIN.get_or_init(Instant::now) + Duration::from_micros(source.now_micros());
+        }
+        Instant::now()
+    }
+
+    /// Enables or disables wrap-epoch mode.
+    ///
+    /// Normally a relative value that no longer fits in 16 bits forces a new
+    /// base. In wrap-epoch mode the relative value is allowed to wrap instead,
+    /// as long as less than one epoch (65536us) passed since the previous
+    /// timestamp: a reader then sees the value decrease and can count the
+    /// wrap unambiguously. Longer gaps, and bases older than `MAX_EPOCHS`
+    /// epochs, still produce a new base.
+    pub fn set_wrap_epochs(&mut self, enabled: bool) {
+        self.wrap_epochs = enabled;
     }
 
     /// Gets a relative timestamp and indicates if a new base timestamp was set.
@@ -60,11 +298,13 @@ impl TimestampConverter {
     /// 1. A 16-bit relative timestamp value
     /// 2. A boolean indicating if a new base timestamp was set (true = new base)
     ///
-    /// The relative timestamp is calculated as:
-    /// `(current_timestamp - base_timestamp) / TICKS_PER_UNIT`
+    /// The relative timestamp is calculated in microseconds as:
+    /// `ticks_to_micros(current_timestamp) - base_micros`
     ///
     /// If the calculated relative value would exceed 16 bits (65535), 
-    /// a new base timestamp is set automatically.
+    /// a new base timestamp is set automatically, unless wrap-epoch mode lets
+    /// it wrap (see `set_wrap_epochs`). The wall-clock time of the current
+    /// base is available from `base_micros()`.
     ///
     /// # Returns
     ///
@@ -81,24 +321,99 @@ impl TimestampConverter {
     /// assert_eq!(ts1, 0);
     /// ```
     pub fn get_relative_timestamp(&mut self) -> (u16, bool) {
-        let current_ts = get_timestamp();
-        let needs_new_base = self.current_base.is_none();
-        
-        if needs_new_base {
-            self.current_base = Some(current_ts);
-            return (0, true);
+        let now = self.now_micros();
+
+        if let Some(base) = self.current_base {
+            // Never step back within a base: readers count a decrease as a wrap
+            let delta = now.saturating_sub(base).max(self.last_delta);
+            if delta <= REL_MAX {
+                self.last_delta = delta;
+                self.last_micros = base + delta;
+                return (delta as u16, false);
+            }
+
+            // Wrap only when the reader can detect it: less than one epoch
+            // since the previous timestamp
+            if self.wrap_epochs
+                && delta - self.last_delta <= REL_MAX
+                && delta / EPOCH_MICROS < MAX_EPOCHS {
+                self.last_delta = delta;
+                self.last_micros = base + delta;
+                return (delta as u16, false);
+            }
         }
 
-        let base = self.current_base.unwrap();
-        let delta_ticks = current_ts.saturating_sub(base);
-        let delta = delta_ticks / TICKS_PER_UNIT;
+        self.set_base();
+        (0, true)
+    }
 
-        if delta > REL_MAX {
-            self.current_base = Some(current_ts);
-            (0, true)
-        } else {
-            (delta as u16, false)
+    /// Gets the microseconds since the previous timestamp and indicates if a
+    /// new base timestamp was set.
+    ///
+    /// Unlike `get_relative_timestamp()`, the delta is not limited to 16 bits,
+    /// so the base is only replaced on the first call, after `reset()`, and
+    /// as a periodic anchor once it is `MAX_EPOCHS` epochs old. A new base
+    /// returns a delta of 0. Deltas never go negative, so the times they add
+    /// up to are monotonic within a base.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binary_logger::efficient_clock::TimestampConverter;
+    /// let mut converter = TimestampConverter::new();
+    /// assert_eq!(converter.get_delta_timestamp(), (0, true));
+    /// let (_delta, is_base) = converter.get_delta_timestamp();
+    /// assert!(!is_base);
+    /// ```
+    pub fn get_delta_timestamp(&mut self) -> (u64, bool) {
+        let now = self.now_micros();
+
+        if let Some(base) = self.current_base {
+            let offset = now.saturating_sub(base).max(self.last_delta);
+            if offset / EPOCH_MICROS < MAX_EPOCHS {
+                let delta = offset - self.last_delta;
+                self.last_delta = offset;
+                self.last_micros = base + offset;
+                return (delta, false);
+            }
         }
+
+        self.set_base();
+        (0, true)
+    }
+
+    /// Reads the counter as microseconds since the UNIX epoch.
+    ///
+    /// Bases and relative values are both taken in microseconds of the
+    /// process-wide calibration, so `base_micros + relative` is identical to
+    /// converting the raw counter value directly, whatever the counter's
+    /// frequency.
+    #[inline(always)]
+    fn now_micros(&mut self) -> u64 {
+        if let Some(source) = &self.source {
+            return source.now_micros();
+        }
+        let cal = *self.calibration.get_or_insert_with(calibration);
+        cal.ticks_to_micros(get_timestamp())
+    }
+
+    /// Starts a new base now, on the latest calibration (see `reanchor`).
+    /// A base never precedes a time returned before it, so a new
+    /// calibration can't make time go backwards.
+    fn set_base(&mut self) {
+        self.calibration = Some(calibration());
+        let now = self.now_micros().max(self.last_micros);
+        self.current_base = Some(now);
+        self.last_delta = 0;
+        self.last_micros = now;
+    }
+
+    /// Returns the current base as microseconds since the UNIX epoch.
+    ///
+    /// This is the value written into base timestamp records so that readers
+    /// can reconstruct absolute times from the relative values that follow.
+    pub fn base_micros(&self) -> u64 {
+        self.current_base.unwrap_or(0)
     }
 
     /// Gets the current absolute timestamp using the highest precision available.
@@ -122,6 +437,12 @@ impl TimestampConverter {
     }
 }
 
+impl Default for TimestampConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Returns a monotonic timestamp with the highest precision available.
 ///
 /// This function uses architecture-specific instructions when available:
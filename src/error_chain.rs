@@ -0,0 +1,147 @@
+#![allow(dead_code)]
+
+//! Errors with their chain of causes as log arguments.
+//!
+//! `ErrorChain` walks `std::error::Error::source()` and records the message
+//! of every error in the chain, so readers can show the full cause list
+//! (`LogValue::ErrorChain`) rather than only the outermost message.
+//! `log_error!` wraps its error argument in one.
+//!
+//! Encoding: `[count u8]` followed by `[len u16][utf8 message]` per error,
+//! outermost first.
+
+use std::error::Error;
+use std::fmt;
+use crate::arg_types::ArgKind;
+use crate::binary_logger::LogArg;
+
+/// Most errors of a chain that are recorded
+const MAX_CHAIN_LEN: usize = 8;
+
+/// Most bytes recorded per message; longer messages are truncated
+const MAX_MESSAGE_LEN: usize = 256;
+
+/// An error and its causes, captured for logging.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::error_chain::ErrorChain;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// let err = std::fs::read("/no/such/file").unwrap_err();
+/// log_record!(logger, "Loading {} failed: {}", 3, ErrorChain::new(&err)).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorChain {
+    encoded: Vec<u8>,
+}
+
+impl ErrorChain {
+    /// Captures the messages of `err` and its sources.
+    pub fn new<E: Error + ?Sized>(err: &E) -> Self {
+        let mut messages = vec![err.to_string()];
+        let mut source = err.source();
+        while let Some(cause) = source {
+            if messages.len() == MAX_CHAIN_LEN {
+                break;
+            }
+            messages.push(cause.to_string());
+            source = cause.source();
+        }
+
+        let mut encoded = vec![messages.len() as u8];
+        for message in &messages {
+            let mut len = message.len().min(MAX_MESSAGE_LEN);
+            while !message.is_char_boundary(len) {
+                len -= 1;
+            }
+            encoded.extend_from_slice(&(len as u16).to_le_bytes());
+            encoded.extend_from_slice(&message.as_bytes()[..len]);
+        }
+        ErrorChain { encoded }
+    }
+}
+
+impl LogArg for ErrorChain {
+    fn log_bytes(&self) -> &[u8] {
+        &self.encoded
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::ErrorChain
+    }
+}
+
+/// Decodes an argument written by `ErrorChain` into its messages.
+pub(crate) fn decode_error_chain(arg: &[u8]) -> Option<Vec<String>> {
+    let (&count, mut rest) = arg.split_first()?;
+    let mut messages = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if rest.len() < 2 {
+            return None;
+        }
+        let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        let message = rest.get(2..2 + len)?;
+        messages.push(String::from_utf8_lossy(message).into_owned());
+        rest = &rest[2 + len..];
+    }
+    rest.is_empty().then_some(messages)
+}
+
+/// Writes the outermost message followed by each cause on its own line,
+/// indented one step further than the error it caused.
+pub(crate) fn fmt_chain(messages: &[String], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (depth, message) in messages.iter().enumerate() {
+        if depth == 0 {
+            f.write_str(message)?;
+        } else {
+            write!(f, "\n{:indent$}caused by: {}", "", message, indent = depth * 2)?;
+        }
+    }
+    Ok(())
+}
+
+/// Logs a record whose first argument is an error, recorded with its chain
+/// of causes.
+///
+/// The error fills the first `{}` of the format string; any further
+/// arguments follow it. It reads back as `LogValue::ErrorChain` and renders
+/// as the error's message with one indented `caused by:` line per source.
+///
+/// With `Logger::set_error_backtraces(true)`, the record also carries a
+/// backtrace of the call site as its last argument.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_error};
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// if let Err(err) = std::fs::read("/no/such/file") {
+///     log_error!(logger, "operation failed: {}", err).unwrap();
+///     log_error!(logger, channel: "io", "{} while loading part {}", err, 7).unwrap();
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_error {
+    ($logger:expr, channel: $channel:literal, $fmt:literal, $err:expr $(, $($args:tt)*)?) => {{
+        let logger = $logger.macro_target();
+        let chain = $crate::error_chain::ErrorChain::new(&$err);
+        let backtrace = $crate::backtrace::LogBacktrace::capture_if(logger.error_backtraces());
+        $crate::log_record!(logger, channel: $channel, $fmt, chain, $($($args)*)?, backtrace)
+    }};
+    ($logger:expr, $fmt:literal, $err:expr $(, $($args:tt)*)?) => {{
+        let logger = $logger.macro_target();
+        let chain = $crate::error_chain::ErrorChain::new(&$err);
+        let backtrace = $crate::backtrace::LogBacktrace::capture_if(logger.error_backtraces());
+        $crate::log_record!(logger, $fmt, chain, $($($args)*)?, backtrace)
+    }};
+}
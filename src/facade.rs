@@ -0,0 +1,198 @@
+//! Backend for the `log` crate facade.
+//!
+//! `BinaryLog` implements `log::Log`, so applications and libraries that log
+//! with `info!`, `warn!` and the other `log` macros write binary records
+//! without changing their call sites. `init_global(handler)` installs one
+//! as the process-wide logger:
+//!
+//! ```
+//! # use binary_logger::BufferHandler;
+//! # struct FileHandler;
+//! # impl BufferHandler for FileHandler {
+//! #     fn handle_buffer(&self, _data: &[u8]) {}
+//! # }
+//! binary_logger::init_global(FileHandler).unwrap();
+//! log::info!(target: "db", "Connected to {} in {} ms", "replica-2", 14);
+//! log::logger().flush();
+//! ```
+//!
+//! Records keep their level, and their target becomes the channel, so
+//! `LogReader::set_channel_filter` and `set_level_filter` select them as
+//! they select records of `log_record_at!`. A message known in full at
+//! compile time, such as one without arguments, is interned as the
+//! record's format string. Other messages are formatted in the logging
+//! thread and stored whole, as the argument of a `{}` format string: the
+//! `log` macros don't keep their format strings and arguments apart, so
+//! the savings of `log_record!` call sites are out of reach. Convert the hot call sites to
+//! `log_record_at!` and keep the facade for the rest.
+//!
+//! `BinaryLog::new` shares one Logger between threads behind a lock, which
+//! suits moderate volumes and a single sink. `BinaryLog::from_pool` gives
+//! each thread its own Logger from a `LoggerPool` instead.
+//!
+//! The global logger is never dropped, so its last buffer reaches the
+//! handler only when it is flushed: call `log::logger().flush()` before
+//! the process exits.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use crate::arg_types::{capture, ArgCapture};
+use crate::binary_logger::{BufferHandler, Logger, SendLogger};
+use crate::level::{Level, STATIC_LEVEL};
+use crate::pool::LoggerPool;
+use crate::string_registry::register_string;
+
+/// Size of the buffers of the Logger installed by `init_global`
+pub const GLOBAL_BUFFER_SIZE: usize = 1 << 20;
+
+/// Format string of messages stored whole
+const WHOLE_MESSAGE: &str = "{}";
+
+thread_local! {
+    /// Reused to format messages with arguments
+    static MESSAGE: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+impl From<log::Level> for Level {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => Level::Error,
+            log::Level::Warn => Level::Warn,
+            log::Level::Info => Level::Info,
+            log::Level::Debug => Level::Debug,
+            log::Level::Trace => Level::Trace,
+        }
+    }
+}
+
+/// Where a `BinaryLog` writes.
+enum Sink<const CAP: usize> {
+    Shared(Box<Mutex<SendLogger<CAP>>>),
+    Pool(LoggerPool<CAP>),
+}
+
+/// A `log::Log` writing binary records, see the module documentation.
+pub struct BinaryLog<const CAP: usize> {
+    sink: Sink<CAP>,
+    /// Channel IDs of the targets seen so far
+    channels: Mutex<HashMap<String, u32>>,
+}
+
+impl<const CAP: usize> BinaryLog<CAP> {
+    /// Creates a backend writing to one Logger, shared by all threads, that
+    /// hands its buffers to `handler`.
+    pub fn new(handler: impl BufferHandler + Send + 'static) -> Self {
+        Self::with_sink(Sink::Shared(Box::new(Mutex::new(SendLogger(Logger::new(handler))))))
+    }
+
+    /// Creates a backend writing to the calling thread's Logger in `pool`.
+    pub fn from_pool(pool: LoggerPool<CAP>) -> Self {
+        Self::with_sink(Sink::Pool(pool))
+    }
+
+    fn with_sink(sink: Sink<CAP>) -> Self {
+        BinaryLog { sink, channels: Mutex::new(HashMap::new()) }
+    }
+
+    /// Runs `f` with the Logger, before the backend is installed or to
+    /// change its settings later, such as its level.
+    ///
+    /// With a pool, `f` runs with the calling thread's Logger; use
+    /// `LoggerPool::configure` for settings of every thread's.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Logger<CAP>) -> R) -> R {
+        match &self.sink {
+            Sink::Shared(logger) => f(&mut logger.lock().unwrap_or_else(|e| e.into_inner()).0),
+            Sink::Pool(pool) => pool.with(f),
+        }
+    }
+
+    /// Installs the backend as the `log` crate's logger, letting every
+    /// level through the facade: the Logger's level decides.
+    ///
+    /// Fails if a logger is already installed.
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        log::set_logger(Box::leak(Box::new(self)))?;
+        log::set_max_level(log::LevelFilter::Trace);
+        Ok(())
+    }
+
+    /// Returns the channel ID of `target`, registering it on first use.
+    fn channel(&self, target: &str) -> u32 {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&id) = channels.get(target) {
+            return id;
+        }
+        let id = register_string(Box::leak(target.to_string().into_boxed_str()));
+        channels.insert(target.to_string(), id);
+        id
+    }
+
+    /// Writes a record of `format_id` with `message` as its argument, if
+    /// any, unless the Logger skips the level.
+    fn write(&self, level: Level, channel: u32, format_id: u32, message: Option<&str>) {
+        self.with(|logger| {
+            if !logger.level_enabled(level) {
+                logger.skip_level(level, format_id);
+                return;
+            }
+            let captured = ArgCapture::with_string_limit(logger.capture_limits().string_limit(Some(level))).with_common_strings(logger.common_strings());
+            // Errors can't be reported through the facade; the Logger
+            // counts the records it drops
+            let _ = match message {
+                Some(message) => logger.write_captured(Some(level), channel, format_id, &[capture(&message, &captured)], &captured),
+                None => logger.write_captured(Some(level), channel, format_id, &[], &captured),
+            };
+            captured.register(format_id);
+        });
+    }
+}
+
+impl<const CAP: usize> log::Log for BinaryLog<CAP> {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let level = Level::from(metadata.level());
+        level >= STATIC_LEVEL && self.with(|logger| logger.level_enabled(level))
+    }
+
+    fn log(&self, record: &log::Record) {
+        // Messages are only formatted for records that are written
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = Level::from(record.level());
+        let channel = self.channel(record.target());
+        match record.args().as_str() {
+            Some(message) if !message.contains("{}") => self.write(level, channel, register_string(message), None),
+            _ => {
+                // Formatted before the Logger is locked, as arguments may
+                // log themselves
+                let whole = register_string(WHOLE_MESSAGE);
+                MESSAGE.with(|buffer| match buffer.try_borrow_mut() {
+                    Ok(mut buffer) => {
+                        buffer.clear();
+                        let _ = write!(buffer, "{}", record.args());
+                        self.write(level, channel, whole, Some(&buffer));
+                    }
+                    Err(_) => self.write(level, channel, whole, Some(&record.args().to_string())),
+                });
+            }
+        }
+    }
+
+    fn flush(&self) {
+        match &self.sink {
+            Sink::Shared(logger) => logger.lock().unwrap_or_else(|e| e.into_inner()).0.flush(),
+            Sink::Pool(pool) => pool.flush_all(),
+        }
+    }
+}
+
+/// Installs a `BinaryLog` with `GLOBAL_BUFFER_SIZE` byte buffers, handing
+/// them to `handler`, as the `log` crate's logger.
+///
+/// Fails if a logger is already installed. Use `BinaryLog::install` for a
+/// backend with other settings.
+pub fn init_global(handler: impl BufferHandler + Send + 'static) -> Result<(), log::SetLoggerError> {
+    BinaryLog::<GLOBAL_BUFFER_SIZE>::new(handler).install()
+}
@@ -0,0 +1,236 @@
+#![allow(dead_code)]
+
+//! Optional parts of the stream format, declared in the stream header.
+//!
+//! The format grows by optional features (header compression, delta
+//! timestamps, tags, ...) rather than by version bumps. The stream header
+//! carries the `FormatFeatures` a writer may use in the stream, so a reader
+//! can tell which features a file may use and which of them it doesn't
+//! understand (`FormatFeatures::unsupported`), instead of failing on the
+//! first record it can't decode.
+//!
+//! A Logger declares every feature of its build by default. Writers enable
+//! a subset with `Logger::set_format_features`, for example to produce
+//! files for readers of an older release: the Logger then never writes a
+//! feature outside the subset, whatever else is configured, and the header
+//! declares exactly that subset.
+//!
+//! Streams written before the feature set existed have a shorter stream
+//! header; readers report no feature set for them
+//! (`LogReader::format_features` returns `None`).
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+//! # use binary_logger::features::FormatFeatures;
+//! # use std::sync::{Arc, Mutex};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! logger.set_format_features(FormatFeatures::LEVELS | FormatFeatures::DELTA_TIME);
+//! logger.set_delta_timestamps(true);
+//! logger.set_header_compression(true); // Not in the subset, so never used
+//! log_record!(logger, "Service started on port {}", 8080).unwrap();
+//! # }
+//! # let data = data.lock().unwrap();
+//! let mut reader = LogReader::new(&data);
+//! reader.read_entry().unwrap();
+//! let features = reader.format_features().unwrap();
+//! assert_eq!(features, FormatFeatures::LEVELS | FormatFeatures::DELTA_TIME);
+//! assert!(features.unsupported().is_empty());
+//! ```
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+/// A set of optional stream format features, one bit each.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FormatFeatures(u32);
+
+impl FormatFeatures {
+    /// No optional features: the format of the first stream headers.
+    pub const NONE: FormatFeatures = FormatFeatures(0);
+
+    /// Records carry the global sequence number (`FLAG_SEQUENCE`)
+    pub const SEQUENCE: FormatFeatures = FormatFeatures(1 << 0);
+
+    /// Records carry a channel ID (`FLAG_CHANNEL`)
+    pub const CHANNELS: FormatFeatures = FormatFeatures(1 << 1);
+
+    /// Repeated format IDs are left out (`FLAG_SAME_FORMAT`)
+    pub const HEADER_COMPRESSION: FormatFeatures = FormatFeatures(1 << 2);
+
+    /// Times are varint deltas from the previous record (`FLAG_DELTA_TIME`)
+    pub const DELTA_TIME: FormatFeatures = FormatFeatures(1 << 3);
+
+    /// Records carry a level in their flags
+    pub const LEVELS: FormatFeatures = FormatFeatures(1 << 4);
+
+    /// Records mark arguments cut to their capture limits (`FLAG_TRUNCATED`)
+    pub const TRUNCATION: FormatFeatures = FormatFeatures(1 << 5);
+
+    /// Buffers start with a chain record linking them to the previous one
+    pub const AUDIT_CHAIN: FormatFeatures = FormatFeatures(1 << 6);
+
+    /// The string dictionary is embedded in dictionary records
+    pub const DICTIONARY: FormatFeatures = FormatFeatures(1 << 7);
+
+    /// Tagged records carry a tag mask (`RECORD_TYPE_TAGGED`)
+    pub const TAGS: FormatFeatures = FormatFeatures(1 << 8);
+
+    /// Metric records (`RECORD_TYPE_METRIC`)
+    pub const METRICS: FormatFeatures = FormatFeatures(1 << 9);
+
+    /// Application-defined records (`RECORD_TYPE_USER_MIN` and up)
+    pub const APPLICATION_RECORDS: FormatFeatures = FormatFeatures(1 << 10);
+
+    /// Count tables of increment-only counters (`RECORD_TYPE_COUNTS`)
+    pub const COUNTERS: FormatFeatures = FormatFeatures(1 << 11);
+
+    /// The string dictionary is published in metadata buffers interleaved
+    /// with the data buffers (`RECORD_TYPE_METADATA`)
+    pub const DICTIONARY_CHANNEL: FormatFeatures = FormatFeatures(1 << 12);
+
+    /// Retention tables giving channels a time to live
+    /// (`RECORD_TYPE_RETENTION`)
+    pub const RETENTION: FormatFeatures = FormatFeatures(1 << 13);
+
+    /// Filler records aligning the records of shared buffers
+    /// (`RECORD_TYPE_PADDING`)
+    pub const PADDING: FormatFeatures = FormatFeatures(1 << 14);
+
+    /// Log records without a time field (`RECORD_TYPE_UNTIMED`)
+    pub const UNTIMED: FormatFeatures = FormatFeatures(1 << 15);
+
+    /// Log records with a type tag per argument (`RECORD_TYPE_TYPED`,
+    /// `RECORD_TYPE_TAGGED_TYPED`)
+    pub const TYPE_TAGS: FormatFeatures = FormatFeatures(1 << 16);
+
+    /// Offsets of the host's clock from a reference clock
+    /// (`RECORD_TYPE_CLOCK_OFFSET`)
+    pub const CLOCK_OFFSETS: FormatFeatures = FormatFeatures(1 << 17);
+
+    /// 32-bit string IDs, in the 16-bit ID fields escaped by
+    /// `string_registry::WIDE_ID`
+    pub const WIDE_IDS: FormatFeatures = FormatFeatures(1 << 18);
+
+    /// Records of 64 KiB and more, whose 16-bit length field holds
+    /// `wire::LONG_LENGTH` and is followed by a 32-bit length
+    pub const LONG_RECORDS: FormatFeatures = FormatFeatures(1 << 19);
+
+    /// String arguments stored as IDs or indexes of the built-in common
+    /// strings (see `common_strings`)
+    pub const COMMON_STRINGS: FormatFeatures = FormatFeatures(1 << 20);
+
+    /// Every feature this build reads and writes.
+    pub const SUPPORTED: FormatFeatures = FormatFeatures((1 << 21) - 1);
+
+    /// Returns the set with the bits of `bits`, as stored in stream headers.
+    pub const fn from_bits(bits: u32) -> FormatFeatures {
+        FormatFeatures(bits)
+    }
+
+    /// Returns the bitmask of the set, as stored in stream headers.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether the set has no features.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether the set has every feature of `other`.
+    pub const fn contains(self, other: FormatFeatures) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features of the set this build doesn't support, from a
+    /// newer writer.
+    pub const fn unsupported(self) -> FormatFeatures {
+        FormatFeatures(self.0 & !Self::SUPPORTED.0)
+    }
+
+    /// Returns the names of the supported features in the set, by bit.
+    pub fn names(self) -> Vec<&'static str> {
+        FEATURE_NAMES.iter().filter(|(feature, _)| self.contains(*feature)).map(|&(_, name)| name).collect()
+    }
+}
+
+impl BitOr for FormatFeatures {
+    type Output = FormatFeatures;
+
+    fn bitor(self, other: FormatFeatures) -> FormatFeatures {
+        FormatFeatures(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for FormatFeatures {
+    fn bitor_assign(&mut self, other: FormatFeatures) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for FormatFeatures {
+    type Output = FormatFeatures;
+
+    fn bitand(self, other: FormatFeatures) -> FormatFeatures {
+        FormatFeatures(self.0 & other.0)
+    }
+}
+
+impl Not for FormatFeatures {
+    type Output = FormatFeatures;
+
+    fn not(self) -> FormatFeatures {
+        FormatFeatures(!self.0)
+    }
+}
+
+impl fmt::Display for FormatFeatures {
+    /// Writes the feature names joined by `|`, followed by the mask of the
+    /// unsupported bits in hex, or `none`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self.names().into_iter().map(String::from).collect();
+        if !self.unsupported().is_empty() {
+            parts.push(format!("{:#x}", self.unsupported().0));
+        }
+        if parts.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&parts.join("|"))
+        }
+    }
+}
+
+/// Name of each supported feature
+const FEATURE_NAMES: [(FormatFeatures, &str); 21] = [
+    (FormatFeatures::SEQUENCE, "sequence"),
+    (FormatFeatures::CHANNELS, "channels"),
+    (FormatFeatures::HEADER_COMPRESSION, "header-compression"),
+    (FormatFeatures::DELTA_TIME, "delta-time"),
+    (FormatFeatures::LEVELS, "levels"),
+    (FormatFeatures::TRUNCATION, "truncation"),
+    (FormatFeatures::AUDIT_CHAIN, "audit-chain"),
+    (FormatFeatures::DICTIONARY, "dictionary"),
+    (FormatFeatures::TAGS, "tags"),
+    (FormatFeatures::METRICS, "metrics"),
+    (FormatFeatures::APPLICATION_RECORDS, "application-records"),
+    (FormatFeatures::COUNTERS, "counters"),
+    (FormatFeatures::DICTIONARY_CHANNEL, "dictionary-channel"),
+    (FormatFeatures::RETENTION, "retention"),
+    (FormatFeatures::PADDING, "padding"),
+    (FormatFeatures::UNTIMED, "untimed"),
+    (FormatFeatures::TYPE_TAGS, "type-tags"),
+    (FormatFeatures::CLOCK_OFFSETS, "clock-offsets"),
+    (FormatFeatures::WIDE_IDS, "wide-ids"),
+    (FormatFeatures::LONG_RECORDS, "long-records"),
+    (FormatFeatures::COMMON_STRINGS, "common-strings"),
+];
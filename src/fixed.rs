@@ -0,0 +1,63 @@
+#![allow(dead_code)]
+
+//! Fixed-point decimal arguments.
+//!
+//! Prices and quantities must render exactly, which `f64` can't promise.
+//! `Fixed<SCALE>` holds a decimal as an integer count of `10^-SCALE` units,
+//! logged as its 8 bytes, and LogReader renders it back digit for digit
+//! (`LogValue::Fixed`).
+
+use std::fmt;
+use crate::arg_types::ArgKind;
+use crate::binary_logger::LogArg;
+
+/// A decimal number with `SCALE` digits after the point, stored as a
+/// scaled `i64`.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::fixed::Fixed;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let price = Fixed::<4>(1_234_500); // 123.4500
+/// assert_eq!(price.to_string(), "123.4500");
+///
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// log_record!(logger, "Filled at {}", price).unwrap();
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Fixed<const SCALE: u32>(pub i64);
+
+impl<const SCALE: u32> fmt::Display for Fixed<SCALE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_scaled(self.0, SCALE, f)
+    }
+}
+
+impl<const SCALE: u32> LogArg for Fixed<SCALE> {
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Fixed(SCALE)
+    }
+
+    #[cfg(target_endian = "big")]
+    fn encode(&self, out: &mut [u8; crate::arg_types::ARG_SCRATCH_SIZE]) -> Option<usize> {
+        crate::arg_types::encode_le(&self.0.to_le_bytes(), out)
+    }
+}
+
+/// Writes `value * 10^-scale` with exactly `scale` decimals.
+pub(crate) fn fmt_scaled(value: i64, scale: u32, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if scale == 0 {
+        return write!(f, "{}", value);
+    }
+    // i128 holds 10^scale for every scale an i64 can carry digits for
+    let unit = 10i128.pow(scale.min(38));
+    let magnitude = (value as i128).abs();
+    let sign = if value < 0 { "-" } else { "" };
+    write!(f, "{}{}.{:0width$}", sign, magnitude / unit, magnitude % unit, width = scale as usize)
+}
@@ -0,0 +1,204 @@
+#![allow(dead_code)]
+
+//! Bit-packed flag arguments.
+//!
+//! Status-heavy events often carry many booleans and small enums. Logged one
+//! by one, each costs a full argument; wrapped in `Flags` they are packed
+//! into a single integer, written as a varint after the ID of their
+//! `FlagNames`:
+//!
+//! `[names_id u16][value varint]`
+//!
+//! with a wide names ID as `string_registry::WIDE_ID` and the u32 ID.
+//!
+//! The names are interned in the string registry like format strings, so
+//! they travel with the log in its embedded dictionary, and LogReader uses
+//! them to expand the value back into named fields (`LogValue::Flags`).
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+use crate::binary_logger::LogArg;
+use crate::string_registry::{decode_id, encoded_id, register_string};
+
+/// Largest encoded flags argument: a wide names ID and a 5-byte varint
+const MAX_ENCODED_LEN: usize = 6 + 5;
+
+/// Names of the fields packed into a `Flags` value.
+///
+/// The spec lists the fields from the least significant bit up, separated by
+/// commas. A field is one bit wide unless its name is followed by `:` and a
+/// width, as for enum discriminants. Declare names as statics, so they are
+/// registered once.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::flags::FlagNames;
+/// // Bit 0: running, bit 1: fault, bits 2-4: mode
+/// static PUMP_STATUS: FlagNames = FlagNames::new("running,fault,mode:3");
+/// ```
+pub struct FlagNames {
+    spec: &'static str,
+    id: OnceLock<u32>,
+}
+
+impl FlagNames {
+    /// Creates flag names from a spec such as `"ready,armed,mode:3"`.
+    pub const fn new(spec: &'static str) -> Self {
+        FlagNames { spec, id: OnceLock::new() }
+    }
+
+    /// Returns the registry ID of the names, registering them on first use.
+    pub fn id(&self) -> u32 {
+        *self.id.get_or_init(|| register_string(self.spec))
+    }
+}
+
+/// A `log_record!` argument packing booleans and enum discriminants into
+/// one varint.
+///
+/// `T` converts into the packed bits, laid out as described by the
+/// `FlagNames`. Small values take a single byte after the names ID.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::flags::{Flags, FlagNames};
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// static PUMP_STATUS: FlagNames = FlagNames::new("running,fault,mode:3");
+///
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// let (running, fault, mode) = (true, false, 2u32);
+/// let status = running as u32 | (fault as u32) << 1 | mode << 2;
+/// // Read back as "Pump status: running|mode=2"
+/// log_record!(logger, "Pump status: {}", Flags::new(status, &PUMP_STATUS)).unwrap();
+/// ```
+pub struct Flags<T> {
+    encoded: [u8; MAX_ENCODED_LEN],
+    len: u8,
+    _value: PhantomData<fn(T)>,
+}
+
+impl<T: Into<u32>> Flags<T> {
+    /// Packs `value`, whose bits are named by `names`.
+    pub fn new(value: T, names: &'static FlagNames) -> Self {
+        let mut encoded = [0u8; MAX_ENCODED_LEN];
+        let (id, mut len) = encoded_id(names.id());
+        encoded[..len].copy_from_slice(&id[..len]);
+        let mut value = value.into();
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                encoded[len] = byte;
+                len += 1;
+                break;
+            }
+            encoded[len] = byte | 0x80;
+            len += 1;
+        }
+        Flags { encoded, len: len as u8, _value: PhantomData }
+    }
+
+}
+
+impl<T> Flags<T> {
+    /// Returns the encoded argument.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.encoded[..self.len as usize]
+    }
+}
+
+impl<T> LogArg for Flags<T> {
+    fn log_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    fn type_tag(&self) -> crate::arg_types::TypeTag {
+        crate::arg_types::TypeTag::FLAGS
+    }
+}
+
+/// One named field of a flags value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagField {
+    /// Name from the `FlagNames` spec; empty for bits the spec doesn't name
+    pub name: String,
+
+    /// Width in bits
+    pub width: u32,
+
+    /// Value of the field
+    pub value: u32,
+}
+
+/// Displays set booleans by name and other nonzero fields as `name=value`,
+/// separated by `|`.
+pub(crate) fn fmt_fields(fields: &[FlagField], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut first = true;
+    for field in fields.iter().filter(|field| field.value != 0) {
+        if !first {
+            f.write_str("|")?;
+        }
+        first = false;
+        match (field.name.as_str(), field.width) {
+            ("", _) => write!(f, "0x{:x}", field.value)?,
+            (name, 1) => f.write_str(name)?,
+            (name, _) => write!(f, "{}={}", name, field.value)?,
+        }
+    }
+    if first {
+        f.write_str("(none)")?;
+    }
+    Ok(())
+}
+
+/// Expands an argument written by `Flags`, looking up the spec of its
+/// names ID with `spec_of`.
+///
+/// Returns None if the names are unknown or the varint is malformed, so
+/// the argument is read as hex instead.
+pub(crate) fn decode_flags<S: AsRef<str>>(arg: &[u8], spec_of: impl FnOnce(u32) -> Option<S>) -> Option<Vec<FlagField>> {
+    let (id, id_len) = decode_id(arg)?;
+    let varint = &arg[id_len..];
+    if varint.is_empty() || varint.len() > 5 {
+        return None;
+    }
+    let spec = spec_of(id)?;
+
+    let mut value = 0u64;
+    for (i, &byte) in varint.iter().enumerate() {
+        value |= ((byte & 0x7F) as u64) << (7 * i);
+        let last = i == varint.len() - 1;
+        if (byte & 0x80 == 0) != last {
+            return None;
+        }
+    }
+    let mut value = u32::try_from(value).ok()?;
+
+    let mut fields = Vec::new();
+    let mut used = 0;
+    for field in spec.as_ref().split(',') {
+        if used == 32 {
+            break;
+        }
+        let (name, width) = match field.split_once(':') {
+            Some((name, width)) => (name, width.parse().unwrap_or(1)),
+            None => (field, 1),
+        };
+        let width = width.clamp(1, 32 - used);
+        let mask = if width == 32 { u32::MAX } else { (1 << width) - 1 };
+        fields.push(FlagField { name: name.trim().to_string(), width, value: value & mask });
+        value = value.checked_shr(width).unwrap_or(0);
+        used += width;
+    }
+    if value != 0 {
+        fields.push(FlagField { name: String::new(), width: 32 - used, value });
+    }
+    Some(fields)
+}
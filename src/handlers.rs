@@ -0,0 +1,924 @@
+//! Reusable BufferHandlers.
+//!
+//! * `FanOut` - hands every buffer to several handlers, for example a file,
+//!   a network connection and an in-memory ring at once
+//! * `LevelRouter` - hands every buffer to some handlers and only the
+//!   records at or above a level to others, for example warnings and errors
+//!   to a small alert file and everything to the archive
+//! * `BackgroundWriter` - writes buffers on a thread of its own, taking
+//!   them over from the Logger instead of copying them
+//! * `AsyncFileHandler` - a `BackgroundWriter` to a file, synced when the
+//!   handler is dropped
+//! * `CompressedFileHandler` - an `AsyncFileHandler` compressing each
+//!   buffer into a frame of its own (`lz4` feature)
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use crate::binary_logger::{
+    BufferHandler, BUFFER_HEADER_SIZE, RECORD_HEADER_SIZE, RECORD_TYPE_NORMAL, RECORD_TYPE_BASE,
+    RECORD_TYPE_STREAM_HEADER, RECORD_TYPE_CHAIN, RECORD_TYPE_DICTIONARY, RECORD_TYPE_TAGGED, RECORD_TYPE_METADATA, RECORD_TYPE_RETENTION, RECORD_TYPE_PADDING, RECORD_TYPE_UNTIMED, RECORD_TYPE_TYPED, RECORD_TYPE_CLOCK_OFFSET, RECORD_TYPE_TAGGED_TYPED, FLAG_SEQUENCE, FLAG_CHANNEL, FLAG_SAME_FORMAT,
+    FLAG_DELTA_TIME, WIDE_ID_SIZE, LONG_LENGTH, LONG_LENGTH_SIZE, record_header_size,
+};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::compression::FrameCodec;
+use crate::buffer_pool::LogBuffer;
+use crate::codec::read_varint;
+use crate::string_registry::{decode_id, is_wide_id, WIDE_ID};
+use crate::efficient_clock::EPOCH_MICROS;
+use crate::level::Level;
+use crate::scheduling::ThreadScheduling;
+
+/// Dispatches each switched-out buffer to several handlers, in order.
+///
+/// Handlers report failures by panicking (`BufferHandler` has no error
+/// channel). FanOut isolates them: a handler that panics is skipped for that
+/// buffer, after the panic hook has reported it, and the remaining handlers
+/// still receive it. The failing handler is called again for the next
+/// buffer, so sinks that recover, such as a reconnecting network handler,
+/// resume on their own.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler};
+/// # use binary_logger::handlers::FanOut;
+/// # struct FileHandler;
+/// # impl BufferHandler for FileHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// # struct NetworkHandler;
+/// # impl BufferHandler for NetworkHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let handler = FanOut(vec![Box::new(FileHandler), Box::new(NetworkHandler)]);
+/// let logger = Logger::<4096>::new(handler);
+/// ```
+pub struct FanOut(pub Vec<Box<dyn BufferHandler>>);
+
+impl BufferHandler for FanOut {
+    fn handle_buffer(&self, data: &[u8]) {
+        for handler in &self.0 {
+            dispatch(handler.as_ref(), data);
+        }
+    }
+
+    fn sync(&self) {
+        for handler in &self.0 {
+            let _ = catch_unwind(AssertUnwindSafe(|| handler.sync()));
+        }
+    }
+}
+
+/// Calls `handler`, containing a panic to it.
+fn dispatch(handler: &dyn BufferHandler, data: &[u8]) {
+    let _ = catch_unwind(AssertUnwindSafe(|| handler.handle_buffer(data)));
+}
+
+/// Routes records to handlers by level.
+///
+/// Handlers added with `route_all` receive every buffer as it is. Handlers
+/// added with `route` receive a copy of each buffer holding only the log
+/// records at or above their level (see `log_record_at!`); records without
+/// a level, metric and application records are left out. The copies are
+/// made from the record headers alone, without decoding payloads, and are
+/// complete logs that any reader decodes: timestamps are re-encoded so they
+/// don't depend on the records left out, and the stream header and any
+/// dictionary records are repeated in the first buffer each handler
+/// receives, later dictionary records in the buffers they come in. Audit chain records are
+/// dropped, as a subset can't continue the chain. Buffers with nothing at a
+/// handler's level aren't passed to it.
+///
+/// Like `FanOut`, a handler that panics doesn't keep the others from
+/// receiving the buffer.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record_at};
+/// # use binary_logger::handlers::LevelRouter;
+/// # use binary_logger::level::Level;
+/// # struct FileHandler(&'static str);
+/// # impl BufferHandler for FileHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let router = LevelRouter::new()
+///     .route(Level::Warn, FileHandler("alerts.bin"))
+///     .route_all(FileHandler("archive.bin"));
+/// let mut logger = Logger::<65536>::new(router);
+/// log_record_at!(logger, Level::Info, "Request {} served", 1).unwrap();
+/// log_record_at!(logger, Level::Error, "Request {} failed", 2).unwrap();
+/// ```
+pub struct LevelRouter {
+    routes: Vec<(Option<Level>, Box<dyn BufferHandler>)>,
+    state: Mutex<RouterState>,
+}
+
+/// What a LevelRouter carries from one buffer to the next.
+#[derive(Default)]
+struct RouterState {
+    /// Timestamp state at the end of the last buffer, as a reader has it
+    time: InputTime,
+
+    /// The stream header record, once seen
+    stream_header: Option<Vec<u8>>,
+
+    /// The dictionary records seen, which every route needs too
+    dictionary: Vec<u8>,
+
+    /// Whether each route has been sent the stream header and dictionary
+    header_sent: Vec<bool>,
+}
+
+/// Timestamp state of the records read so far.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct InputTime {
+    pub(crate) base: Option<u64>,
+    pub(crate) epoch: u64,
+    pub(crate) last_relative: u16,
+}
+
+/// Timestamp state of a copy being written.
+#[derive(Default)]
+struct OutputTime {
+    base: Option<u64>,
+    last_relative: u16,
+}
+
+impl LevelRouter {
+    /// Creates a router without handlers.
+    pub fn new() -> Self {
+        LevelRouter { routes: Vec::new(), state: Mutex::new(RouterState::default()) }
+    }
+
+    /// Sends the records at `level` or above to `handler`.
+    pub fn route(mut self, level: Level, handler: impl BufferHandler + 'static) -> Self {
+        self.routes.push((Some(level), Box::new(handler)));
+        self
+    }
+
+    /// Sends every buffer to `handler` unchanged.
+    pub fn route_all(mut self, handler: impl BufferHandler + 'static) -> Self {
+        self.routes.push((None, Box::new(handler)));
+        self
+    }
+
+    /// Builds the copy of `data` for each route with a level.
+    fn split(&self, data: &[u8]) -> Vec<Option<Vec<u8>>> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        state.header_sent.resize(self.routes.len(), false);
+
+        let mut outputs: Vec<(Vec<u8>, OutputTime)> = self.routes.iter()
+            .map(|_| (vec![0; BUFFER_HEADER_SIZE], OutputTime::default()))
+            .collect();
+        let mut kept = vec![false; self.routes.len()];
+
+        let mut pos = BUFFER_HEADER_SIZE;
+        let mut last_format_id = 0;
+        while let Some(record) = parse_record(data, pos, last_format_id, &mut state.time) {
+            last_format_id = record.format_id;
+            pos = record.end;
+            match record.record_type {
+                RECORD_TYPE_STREAM_HEADER => {
+                    state.stream_header = Some(data[record.start..record.end].to_vec());
+                    continue;
+                }
+                RECORD_TYPE_DICTIONARY => {
+                    state.dictionary.extend_from_slice(&data[record.start..record.end]);
+                    // Routes past their first buffer get strings registered
+                    // since in place, timed as the record before
+                    for (route, (out, time)) in outputs.iter_mut().enumerate() {
+                        if state.header_sent[route] {
+                            let payload = &data[record.payload.clone()];
+                            push_header(out, RECORD_TYPE_DICTIONARY, 0, time.last_relative, 0, payload.len(), &[]);
+                            out.extend_from_slice(payload);
+                            if !out.len().is_multiple_of(2) {
+                                out.push(0);
+                            }
+                            kept[route] = true;
+                        }
+                    }
+                    continue;
+                }
+                RECORD_TYPE_RETENTION | RECORD_TYPE_CLOCK_OFFSET => {
+                    // The copies keep the retention of their channels, and
+                    // the clock offset of their records
+                    for (out, _) in &mut outputs {
+                        out.extend_from_slice(&data[record.start..record.end]);
+                    }
+                    continue;
+                }
+                // Untimed records are copied with the time they were read with
+                RECORD_TYPE_NORMAL | RECORD_TYPE_TAGGED | RECORD_TYPE_UNTIMED | RECORD_TYPE_TYPED | RECORD_TYPE_TAGGED_TYPED => {}
+                // Base records that carry a log entry
+                RECORD_TYPE_BASE if record.format_id != 0 => {}
+                _ => continue,
+            }
+            let Some(level) = Level::from_flags(record.flags) else { continue };
+
+            for (route, (min_level, _)) in self.routes.iter().enumerate() {
+                if min_level.is_some_and(|min_level| level >= min_level) {
+                    let (out, time) = &mut outputs[route];
+                    push_record(out, time, &record, &data[record.extra.clone()], &data[record.payload.clone()]);
+                    kept[route] = true;
+                }
+            }
+        }
+
+        outputs.into_iter().enumerate().map(|(route, (mut out, _))| {
+            if !kept[route] {
+                return None;
+            }
+            if !state.header_sent[route] {
+                let header = state.stream_header.iter().flatten().chain(&state.dictionary);
+                out.splice(BUFFER_HEADER_SIZE..BUFFER_HEADER_SIZE, header.copied());
+                state.header_sent[route] = true;
+            }
+            let size = out.len() as u64;
+            out[..BUFFER_HEADER_SIZE].copy_from_slice(&size.to_le_bytes());
+            Some(out)
+        }).collect()
+    }
+}
+
+impl Default for LevelRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BufferHandler for LevelRouter {
+    fn handle_buffer(&self, data: &[u8]) {
+        let copies = self.split(data);
+        for ((min_level, handler), copy) in self.routes.iter().zip(copies) {
+            match (min_level, copy) {
+                (None, _) => dispatch(handler.as_ref(), data),
+                (Some(_), Some(copy)) => dispatch(handler.as_ref(), &copy),
+                (Some(_), None) => {}
+            }
+        }
+    }
+
+    fn sync(&self) {
+        for (_, handler) in &self.routes {
+            let _ = catch_unwind(AssertUnwindSafe(|| handler.sync()));
+        }
+    }
+}
+
+/// What a `BackgroundWriter` does with a filled buffer when its queue is
+/// full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// The logging thread waits for the writer, so nothing is lost
+    #[default]
+    Block,
+
+    /// The buffer is discarded and the Logger writes to it again, so the
+    /// logging thread never waits. Its records are lost, and counted by
+    /// the Logger as dropped (see `BufferHandler::buffers_dropped`),
+    /// including any
+    /// dictionary entries embedded in it, which leaves later records of
+    /// those formats unresolved. Buffers the Logger lends, such as the
+    /// dictionary channel's, are always queued.
+    Drop,
+}
+
+/// Writes buffers to a `Write` on a thread of its own, without copying them.
+///
+/// The Logger gives each filled buffer away (`handle_owned_buffer`) and
+/// gets one the writer thread is done with in return, so logging doesn't
+/// wait for the write and no buffer is copied. At most `max_buffers`
+/// buffers wait to be written: when the queue is full, the logging thread
+/// waits for the writer, or the buffer is discarded, by the
+/// `Backpressure` policy. Borrowed buffers, such as the dictionary
+/// channel's, are copied first.
+///
+/// A failed write stops the writer thread, and the next buffer handed over
+/// makes the handler panic with the error, which the Logger treats by its
+/// `HandlerPanicPolicy`. `sync` waits for the pending buffers to be
+/// written and flushes the writer, as does dropping the handler, with its
+/// Logger.
+///
+/// `with_scheduling` keeps the writer thread off the cores of
+/// latency-critical threads (see `scheduling`).
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, log_record};
+/// # use binary_logger::handlers::BackgroundWriter;
+/// # use std::fs::File;
+/// let file = File::create("log.bin").unwrap();
+/// let mut logger = Logger::<65536>::new(BackgroundWriter::new(file, 4));
+/// log_record!(logger, "Order {} filled", 17).unwrap();
+/// ```
+pub struct BackgroundWriter {
+    /// Work for the writer thread; closed when the handler is dropped
+    pending: Option<SyncSender<Job>>,
+    /// Buffers written, coming back
+    written: Mutex<Receiver<(LogBuffer, bool)>>,
+    /// Buffers for reuse allocated so far
+    allocated: AtomicUsize,
+    max_buffers: usize,
+    backpressure: Backpressure,
+    /// Buffers discarded by `Backpressure::Drop`
+    dropped: Arc<AtomicU64>,
+    error: Arc<Mutex<Option<io::Error>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Work for the writer thread of a `BackgroundWriter`.
+enum Job {
+    /// A buffer to write, and whether it is for reuse rather than a copy
+    Write(LogBuffer, bool),
+
+    /// Flush the writer once the buffers queued before are written, and
+    /// report how it went
+    Sync(SyncSender<io::Result<()>>),
+}
+
+impl BackgroundWriter {
+    /// Starts a thread writing to `writer`, with up to `max_buffers`
+    /// buffers waiting to be written (at least 1).
+    pub fn new(writer: impl Write + Send + 'static, max_buffers: usize) -> Self {
+        Self::with_scheduling(writer, max_buffers, ThreadScheduling::default()).expect("default scheduling applies everywhere")
+    }
+
+    /// Starts a thread like `new`, pinned and prioritized by `scheduling`.
+    ///
+    /// Fails, without starting the thread, if `scheduling` can't be
+    /// applied to it.
+    pub fn with_scheduling(mut writer: impl Write + Send + 'static, max_buffers: usize, scheduling: ThreadScheduling) -> io::Result<Self> {
+        let max_buffers = max_buffers.max(1);
+        let (pending, to_write) = sync_channel::<Job>(max_buffers);
+        let (give_back, written) = channel();
+        let (started, start) = sync_channel(1);
+        let error = Arc::new(Mutex::new(None));
+        let thread = {
+            let error = error.clone();
+            thread::spawn(move || {
+                let applied = scheduling.apply_to_current_thread();
+                let failed = applied.is_err();
+                let _ = started.send(applied);
+                if failed {
+                    return;
+                }
+                let result = to_write.iter().try_for_each(|job| {
+                    match job {
+                        Job::Write(buffer, reuse) => {
+                            writer.write_all(&buffer)?;
+                            // Kept by the handler, or freed once it is gone
+                            let _ = give_back.send((buffer, reuse));
+                        }
+                        Job::Sync(synced) => {
+                            let _ = synced.send(writer.flush());
+                        }
+                    }
+                    Ok(())
+                });
+                if let Err(e) = result.and_then(|_| writer.flush()) {
+                    *error.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+                }
+            })
+        };
+        if let Err(e) = start.recv().unwrap_or_else(|_| Err(io::Error::other("writer thread panicked"))) {
+            let _ = thread.join();
+            return Err(e);
+        }
+        Ok(BackgroundWriter {
+            pending: Some(pending),
+            written: Mutex::new(written),
+            allocated: AtomicUsize::new(0),
+            max_buffers,
+            backpressure: Backpressure::Block,
+            dropped: Arc::new(AtomicU64::new(0)),
+            error,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Sets what happens to a filled buffer when `max_buffers` already
+    /// wait to be written; `Backpressure::Block` by default.
+    pub fn backpressure(mut self, policy: Backpressure) -> Self {
+        self.backpressure = policy;
+        self
+    }
+
+    /// Returns the count of buffers discarded by `Backpressure::Drop`,
+    /// which stays readable once the handler is moved into its Logger.
+    pub fn dropped_buffers(&self) -> Arc<AtomicU64> {
+        self.dropped.clone()
+    }
+
+    /// Returns a buffer of `capacity` bytes for the Logger to write to
+    /// next: one already written, a new one, or, if `wait` and the
+    /// allocated buffers are all queued, the next one written.
+    fn free_buffer(&self, capacity: usize, wait: bool) -> LogBuffer {
+        let written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let next = match written.try_recv() {
+                Ok(next) => next,
+                Err(_) if !wait || self.allocated.load(Ordering::Relaxed) < self.max_buffers => break,
+                Err(_) => match written.recv() {
+                    Ok(next) => next,
+                    // The writer thread ended, which only a failed write
+                    // does; `send` reports it
+                    Err(_) => break,
+                },
+            };
+            match next {
+                (buffer, true) if buffer.capacity() == capacity => return buffer,
+                (_, true) => {
+                    self.allocated.fetch_sub(1, Ordering::Relaxed);
+                }
+                (_, false) => {}
+            }
+        }
+        self.allocated.fetch_add(1, Ordering::Relaxed);
+        LogBuffer::new(capacity)
+    }
+
+    /// Queues a filled buffer for the writer thread.
+    fn send(&self, buffer: LogBuffer, reuse: bool) {
+        let sent = self.pending.as_ref().is_some_and(|pending| pending.send(Job::Write(buffer, reuse)).is_ok());
+        if !sent {
+            self.stopped();
+        }
+    }
+
+    /// Queues a filled buffer unless the queue is full, in which case the
+    /// buffer is counted as dropped and given back.
+    fn try_send(&self, buffer: LogBuffer) -> Option<LogBuffer> {
+        let Some(pending) = &self.pending else { self.stopped() };
+        match pending.try_send(Job::Write(buffer, true)) {
+            Ok(()) => None,
+            Err(TrySendError::Full(Job::Write(buffer, _))) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Some(buffer)
+            }
+            Err(_) => self.stopped(),
+        }
+    }
+
+    /// Reports why the writer thread ended.
+    fn stopped(&self) -> ! {
+        let error = self.error.lock().unwrap_or_else(|e| e.into_inner());
+        match &*error {
+            Some(e) => panic!("background write failed: {}", e),
+            None => panic!("background writer stopped"),
+        }
+    }
+}
+
+impl BufferHandler for BackgroundWriter {
+    fn handle_buffer(&self, data: &[u8]) {
+        let mut copy = LogBuffer::new(data.len().max(1));
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), copy.as_mut_ptr(), data.len());
+            copy.set_len(data.len());
+        }
+        self.send(copy, false);
+    }
+
+    fn sync(&self) {
+        let (synced, flushed) = sync_channel(1);
+        // A job the writer thread never gets drops `synced`
+        if let Some(pending) = &self.pending {
+            let _ = pending.send(Job::Sync(synced));
+        }
+        match flushed.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => panic!("background sync failed: {}", e),
+            Err(_) => self.stopped(),
+        }
+    }
+
+    fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+        let capacity = buffer.capacity();
+        match self.backpressure {
+            Backpressure::Block => {
+                let next = self.free_buffer(capacity, true);
+                self.send(buffer, true);
+                next
+            }
+            // At most one buffer is being written beyond the queued ones,
+            // so allocating rather than waiting stays bounded
+            Backpressure::Drop => match self.try_send(buffer) {
+                Some(dropped) => dropped,
+                None => self.free_buffer(capacity, false),
+            },
+        }
+    }
+
+    fn buffers_dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        // The writer thread ends after the buffers queued before
+        self.pending = None;
+        if let Some(thread) = self.thread.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Writes buffers to a file on a thread of its own.
+///
+/// A `BackgroundWriter` to the file, so it takes buffers over without
+/// copying them, with `queue_depth` buffers at most waiting to be written
+/// and a `Backpressure` policy for when they are all waiting. `sync`, and
+/// dropping the handler with its Logger, write the pending buffers and sync
+/// the file to disk.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, log_record};
+/// # use binary_logger::handlers::{AsyncFileHandler, Backpressure};
+/// # use std::sync::atomic::Ordering;
+/// # let dir = std::env::temp_dir();
+/// // Never wait for the disk: drop buffers while 8 are queued
+/// let handler = AsyncFileHandler::create(dir.join("app.blog"), 8).unwrap()
+///     .backpressure(Backpressure::Drop);
+/// let dropped = handler.dropped_buffers();
+/// let mut logger = Logger::<65536>::new(handler);
+/// log_record!(logger, "Order {} filled", 17).unwrap();
+/// drop(logger);
+/// println!("{} buffers dropped", dropped.load(Ordering::Relaxed));
+/// ```
+pub struct AsyncFileHandler {
+    writer: BackgroundWriter,
+    path: PathBuf,
+}
+
+impl AsyncFileHandler {
+    /// Creates the file at `path`, truncating it, and starts a thread
+    /// writing to it with up to `queue_depth` buffers waiting (at least 1).
+    pub fn create(path: impl AsRef<Path>, queue_depth: usize) -> io::Result<Self> {
+        Self::with_scheduling(path, queue_depth, ThreadScheduling::default())
+    }
+
+    /// Creates the file like `create`, with the writer thread pinned and
+    /// prioritized by `scheduling`.
+    pub fn with_scheduling(path: impl AsRef<Path>, queue_depth: usize, scheduling: ThreadScheduling) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = SyncedFile(File::create(&path)?);
+        Ok(AsyncFileHandler { writer: BackgroundWriter::with_scheduling(file, queue_depth, scheduling)?, path })
+    }
+
+    /// Sets what happens to a filled buffer when the queue is full;
+    /// `Backpressure::Block` by default.
+    pub fn backpressure(mut self, policy: Backpressure) -> Self {
+        self.writer = self.writer.backpressure(policy);
+        self
+    }
+
+    /// Returns the count of buffers discarded by `Backpressure::Drop`.
+    pub fn dropped_buffers(&self) -> Arc<AtomicU64> {
+        self.writer.dropped_buffers()
+    }
+
+    /// Returns the path of the file written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl BufferHandler for AsyncFileHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.writer.handle_buffer(data);
+    }
+
+    fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+        self.writer.handle_owned_buffer(buffer)
+    }
+
+    fn sync(&self) {
+        self.writer.sync();
+    }
+
+    fn buffers_dropped(&self) -> u64 {
+        self.writer.buffers_dropped()
+    }
+}
+
+/// A file whose data is synced to disk when it is flushed, which the
+/// writer thread does on `sync` and at the end.
+struct SyncedFile(File);
+
+impl Write for SyncedFile {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.sync_data()
+    }
+}
+
+/// Writes buffers to a file on a thread of its own, compressing each into
+/// a frame of its own (see `compression`).
+///
+/// An `AsyncFileHandler` whose writer thread compresses the buffers, so
+/// the logging thread doesn't pay for it. Each frame is independent, so the
+/// file is readable up to its last complete frame while it is written:
+/// `LogReader`, `StreamReader` and the file-reading tools decompress the
+/// frames as they read.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, LogReader, log_record};
+/// # use binary_logger::handlers::CompressedFileHandler;
+/// # let path = std::env::temp_dir().join("compressed.blog");
+/// let mut logger = Logger::<65536>::new(CompressedFileHandler::create(&path, 4).unwrap());
+/// log_record!(logger, "Order {} filled", 17).unwrap();
+/// drop(logger);
+///
+/// let data = std::fs::read(&path).unwrap();
+/// let entry = LogReader::new(&data).read_entry().unwrap();
+/// assert_eq!(entry.format(), "Order 17 filled");
+/// ```
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub struct CompressedFileHandler {
+    writer: BackgroundWriter,
+    path: PathBuf,
+}
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+impl CompressedFileHandler {
+    /// Creates the file at `path`, truncating it, and starts a thread
+    /// compressing buffers with the default `FrameCodec` at level 0 and
+    /// writing them, with up to `queue_depth` buffers waiting (at least 1).
+    pub fn create(path: impl AsRef<Path>, queue_depth: usize) -> io::Result<Self> {
+        Self::with_level(path, queue_depth, 0)
+    }
+
+    /// Creates the file like `create`, compressing at `level`, from 0 to
+    /// `FrameCodec::max_level`.
+    pub fn with_level(path: impl AsRef<Path>, queue_depth: usize, level: u32) -> io::Result<Self> {
+        Self::with_codec(path, queue_depth, FrameCodec::default(), level)
+    }
+
+    /// Creates the file like `create`, compressing with `codec` at
+    /// `level`.
+    pub fn with_codec(path: impl AsRef<Path>, queue_depth: usize, codec: FrameCodec, level: u32) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = CompressingFile { file: SyncedFile(File::create(&path)?), codec, level: level.min(codec.max_level()) };
+        Ok(CompressedFileHandler { writer: BackgroundWriter::new(file, queue_depth), path })
+    }
+
+    /// Sets what happens to a filled buffer when the queue is full;
+    /// `Backpressure::Block` by default.
+    pub fn backpressure(mut self, policy: Backpressure) -> Self {
+        self.writer = self.writer.backpressure(policy);
+        self
+    }
+
+    /// Returns the count of buffers discarded by `Backpressure::Drop`.
+    pub fn dropped_buffers(&self) -> Arc<AtomicU64> {
+        self.writer.dropped_buffers()
+    }
+
+    /// Returns the path of the file written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+impl BufferHandler for CompressedFileHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.writer.handle_buffer(data);
+    }
+
+    fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+        self.writer.handle_owned_buffer(buffer)
+    }
+
+    fn sync(&self) {
+        self.writer.sync();
+    }
+
+    fn buffers_dropped(&self) -> u64 {
+        self.writer.buffers_dropped()
+    }
+}
+
+/// A file written in compressed frames. The writer thread hands each
+/// buffer over in a single `write_all`, and every write takes its data
+/// whole, so each write is one buffer and becomes one frame.
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+struct CompressingFile {
+    file: SyncedFile,
+    codec: FrameCodec,
+    level: u32,
+}
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+impl Write for CompressingFile {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.file.write_all(&crate::compression::compress_buffer(buffer, self.codec, self.level)?)?;
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A record located in a buffer.
+pub(crate) struct ParsedRecord {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) record_type: u8,
+    pub(crate) flags: u8,
+    pub(crate) format_id: u32,
+
+    /// Microseconds since the UNIX epoch, if a base has been seen
+    pub(crate) micros: Option<u64>,
+
+    /// Sequence number and channel
+    pub(crate) extra: std::ops::Range<usize>,
+
+    /// Channel ID, 0 for none
+    pub(crate) channel: u32,
+
+    /// Payload as written, with the base timestamp of base records
+    pub(crate) written: std::ops::Range<usize>,
+
+    /// Arguments, after the base timestamp of base records
+    pub(crate) payload: std::ops::Range<usize>,
+}
+
+/// Reads the header of the record at `pos`, advancing `time` the way
+/// LogReader does. Returns None at the end of the buffer or a damaged
+/// record.
+pub(crate) fn parse_record(data: &[u8], pos: usize, last_format_id: u32, time: &mut InputTime) -> Option<ParsedRecord> {
+    let record = data.get(pos..)?;
+    let header_size = record_header_size(record)?;
+    let header = record.get(..header_size)?;
+    let (record_type, flags) = (header[0], header[1]);
+
+    let relative = if record_type == RECORD_TYPE_UNTIMED {
+        time.last_relative
+    } else if flags & FLAG_DELTA_TIME != 0 {
+        let (delta, _) = read_varint(&header[2..])?;
+        let offset = time.epoch * EPOCH_MICROS + time.last_relative as u64 + delta as u64;
+        time.epoch = offset / EPOCH_MICROS;
+        time.last_relative = (offset % EPOCH_MICROS) as u16;
+        time.last_relative
+    } else {
+        u16::from_le_bytes([header[2], header[3]])
+    };
+    let length_field = u16::from_le_bytes([header[header_size - 2], header[header_size - 1]]);
+
+    let mut extra = pos + header_size..pos + header_size;
+    if flags & FLAG_SEQUENCE != 0 {
+        extra.end += 8;
+    }
+    let mut channel = 0;
+    if flags & FLAG_CHANNEL != 0 {
+        let (id, len) = decode_id(data.get(extra.end..)?)?;
+        channel = id;
+        extra.end += len;
+    }
+    let mut written_start = extra.end;
+    let format_id = if flags & FLAG_SAME_FORMAT != 0 {
+        last_format_id
+    } else {
+        match u16::from_le_bytes([header[header_size - 4], header[header_size - 3]]) {
+            WIDE_ID => {
+                written_start += WIDE_ID_SIZE;
+                u32::from_le_bytes(data.get(extra.end..written_start)?.try_into().unwrap())
+            }
+            id => id as u32,
+        }
+    };
+    let payload_len = match length_field {
+        LONG_LENGTH => {
+            let len = data.get(written_start..written_start + LONG_LENGTH_SIZE)?;
+            written_start += LONG_LENGTH_SIZE;
+            u32::from_le_bytes(len.try_into().unwrap()) as usize
+        }
+        len => len as usize,
+    };
+    let written = written_start..written_start + payload_len;
+    let mut payload = written.clone();
+    if payload.end > data.len() {
+        return None;
+    }
+
+    match record_type {
+        RECORD_TYPE_BASE => {
+            let base = data.get(payload.start..payload.start + 8)?;
+            time.base = Some(u64::from_le_bytes(base.try_into().unwrap()));
+            time.epoch = 0;
+            time.last_relative = relative;
+            payload.start += 8;
+        }
+        // Untimed records
+        RECORD_TYPE_STREAM_HEADER | RECORD_TYPE_CHAIN | RECORD_TYPE_METADATA | RECORD_TYPE_RETENTION | RECORD_TYPE_PADDING
+            | RECORD_TYPE_CLOCK_OFFSET => {}
+        _ => {
+            if relative < time.last_relative {
+                time.epoch += 1;
+            }
+            time.last_relative = relative;
+        }
+    }
+
+    Some(ParsedRecord {
+        start: pos,
+        end: (payload.end + 1) & !1,
+        record_type,
+        flags,
+        format_id,
+        micros: time.base.map(|base| base + time.epoch * EPOCH_MICROS + relative as u64),
+        extra,
+        channel,
+        written,
+        payload,
+    })
+}
+
+/// Appends `record` to `out` with a plain 16-bit relative timestamp and its
+/// format ID, starting a new base when the time since the current one
+/// doesn't fit or would read as a wrap.
+fn push_record(out: &mut Vec<u8>, time: &mut OutputTime, record: &ParsedRecord, extra: &[u8], payload: &[u8]) {
+    let flags = record.flags & !(FLAG_DELTA_TIME | FLAG_SAME_FORMAT);
+    let new_base = match (record.micros, time.base) {
+        (Some(micros), Some(base)) => micros < base + time.last_relative as u64 || micros - base > u16::MAX as u64,
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+
+    // Only normal records can carry a base
+    let kind = match record.record_type {
+        RECORD_TYPE_TAGGED | RECORD_TYPE_TYPED | RECORD_TYPE_TAGGED_TYPED => record.record_type,
+        _ => RECORD_TYPE_NORMAL,
+    };
+    let mut base = None;
+    if new_base {
+        let micros = record.micros.unwrap();
+        time.base = Some(micros);
+        time.last_relative = 0;
+        if kind == RECORD_TYPE_NORMAL && payload.len() + 8 < LONG_LENGTH as usize {
+            base = Some(micros);
+        } else {
+            // No room for the base in the record itself, or a record type
+            // that can't carry one
+            push_header(out, RECORD_TYPE_BASE, 0, 0, 0, 8, &[]);
+            out.extend_from_slice(&micros.to_le_bytes());
+        }
+    }
+    let relative = match (record.micros, time.base) {
+        (Some(micros), Some(base)) => (micros - base) as u16,
+        _ => 0,
+    };
+    time.last_relative = relative;
+
+    let record_type = if base.is_some() { RECORD_TYPE_BASE } else { kind };
+    let base_len = if base.is_some() { 8 } else { 0 };
+    push_header(out, record_type, flags, relative, record.format_id, base_len + payload.len(), extra);
+    if let Some(base) = base {
+        out.extend_from_slice(&base.to_le_bytes());
+    }
+    out.extend_from_slice(payload);
+    if !out.len().is_multiple_of(2) {
+        out.push(0);
+    }
+}
+
+/// Appends a full record header, followed by `extra`, the sequence number
+/// and channel fields, the format ID if it is wide, and the length of a
+/// long record.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn push_header(out: &mut Vec<u8>, record_type: u8, flags: u8, relative: u16, format_id: u32, payload_len: usize, extra: &[u8]) {
+    let start = out.len();
+    out.extend_from_slice(&[record_type, flags]);
+    out.extend_from_slice(&relative.to_le_bytes());
+    let field = if is_wide_id(format_id) { WIDE_ID } else { format_id as u16 };
+    out.extend_from_slice(&field.to_le_bytes());
+    let long = payload_len >= LONG_LENGTH as usize;
+    let length = if long { LONG_LENGTH } else { payload_len as u16 };
+    out.extend_from_slice(&length.to_le_bytes());
+    debug_assert_eq!(out.len() - start, RECORD_HEADER_SIZE);
+    out.extend_from_slice(extra);
+    if is_wide_id(format_id) {
+        out.extend_from_slice(&format_id.to_le_bytes());
+    }
+    if long {
+        out.extend_from_slice(&(payload_len as u32).to_le_bytes());
+    }
+}
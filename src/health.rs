@@ -0,0 +1,317 @@
+#![allow(dead_code)]
+
+//! Health file for external watchdogs.
+//!
+//! A Logger with a health file (see `Logger::set_health_file`) keeps a small
+//! sidecar file describing its pipeline up to date, so watchdogs and
+//! container probes can tell a wedged logger from an idle one without
+//! reading the log. The file is written by a thread of its own, once per
+//! heartbeat interval whether or not records were written. The logging
+//! thread only updates counters: atomics after every record, and a short
+//! lock after every buffer, never held while the file is written. The file
+//! is replaced atomically (written next to the target, then renamed), so
+//! readers never see a partial file.
+//!
+//! The file holds one `key=value` line per field of `HealthStatus`, with
+//! times in microseconds since the UNIX epoch, 0 for none:
+//!
+//! ```text
+//! heartbeat_us=1735689600123456
+//! last_flush_us=1735689599870001
+//! buffers_flushed=42
+//! bytes_flushed=41943040
+//! bytes_behind=5120
+//! dropped=0
+//! handler_us=830
+//! handler_since_us=0
+//! ```
+//!
+//! A stale `heartbeat_us` means the process, or at least its heartbeat
+//! thread, is gone. With a fresh heartbeat, an old `handler_since_us` means
+//! the logging thread is stuck in the BufferHandler, while an idle logger
+//! reports 0 there and a `bytes_behind` that doesn't grow.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where and how often a Logger reports its health.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthFile {
+    path: PathBuf,
+    interval: Duration,
+}
+
+impl HealthFile {
+    /// Reports to the file at `path`, with a heartbeat every `interval`.
+    pub fn new(path: impl Into<PathBuf>, interval: Duration) -> Self {
+        HealthFile { path: path.into(), interval }
+    }
+
+    /// Returns the path of the health file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The state of a Logger's pipeline, as written to its health file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthStatus {
+    /// When the file was last written, at least once per heartbeat
+    /// interval
+    pub heartbeat_at: SystemTime,
+
+    /// When the BufferHandler last returned, if it has been called
+    pub last_flush: Option<SystemTime>,
+
+    /// Buffers handed to the BufferHandler
+    pub buffers_flushed: u64,
+
+    /// Bytes handed to the BufferHandler
+    pub bytes_flushed: u64,
+
+    /// Bytes of records written but not yet handed to the BufferHandler
+    pub bytes_behind: u64,
+
+    /// Records dropped instead of written
+    pub dropped: u64,
+
+    /// Time the BufferHandler took for the last buffer
+    pub handler_time: Duration,
+
+    /// When the BufferHandler was called, while it hasn't returned
+    pub handler_since: Option<SystemTime>,
+}
+
+impl HealthStatus {
+    /// Reads the health file at `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binary_logger::health::HealthStatus;
+    /// # use std::time::Duration;
+    /// # fn probe() -> std::io::Result<bool> {
+    /// let status = HealthStatus::read("/var/run/app/log.health")?;
+    /// let healthy = status.heartbeat_at.elapsed().unwrap_or_default() < Duration::from_secs(10);
+    /// # Ok(healthy)
+    /// # }
+    /// ```
+    pub fn read(path: impl AsRef<Path>) -> io::Result<HealthStatus> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad health file line: {}", line));
+
+        let mut status = HealthStatus {
+            heartbeat_at: UNIX_EPOCH,
+            last_flush: None,
+            buffers_flushed: 0,
+            bytes_flushed: 0,
+            bytes_behind: 0,
+            dropped: 0,
+            handler_time: Duration::ZERO,
+            handler_since: None,
+        };
+        for line in text.lines() {
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+            let value: u64 = value.parse().map_err(|_| invalid(line))?;
+            match key {
+                "heartbeat_us" => status.heartbeat_at = UNIX_EPOCH + Duration::from_micros(value),
+                "last_flush_us" => status.last_flush = (value != 0).then(|| UNIX_EPOCH + Duration::from_micros(value)),
+                "buffers_flushed" => status.buffers_flushed = value,
+                "bytes_flushed" => status.bytes_flushed = value,
+                "bytes_behind" => status.bytes_behind = value,
+                "dropped" => status.dropped = value,
+                "handler_us" => status.handler_time = Duration::from_micros(value),
+                "handler_since_us" => status.handler_since = (value != 0).then(|| UNIX_EPOCH + Duration::from_micros(value)),
+                // Fields added by later versions
+                _ => {}
+            }
+        }
+        Ok(status)
+    }
+
+    /// Replaces the file at `path` with this status.
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        let micros = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros();
+        let text = format!(
+            "heartbeat_us={}\nlast_flush_us={}\nbuffers_flushed={}\nbytes_flushed={}\nbytes_behind={}\ndropped={}\nhandler_us={}\nhandler_since_us={}\n",
+            micros(self.heartbeat_at),
+            self.last_flush.map_or(0, micros),
+            self.buffers_flushed,
+            self.bytes_flushed,
+            self.bytes_behind,
+            self.dropped,
+            self.handler_time.as_micros(),
+            self.handler_since.map_or(0, micros),
+        );
+
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, path)
+    }
+}
+
+/// A Logger's health file and the counters reported in it, shared with the
+/// thread writing it.
+pub(crate) struct HealthReporter {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// State of a HealthReporter, shared with its heartbeat thread.
+struct Shared {
+    file: HealthFile,
+
+    /// Bytes written to the current buffer, stored by the logging thread
+    /// after every record
+    bytes_behind: AtomicU64,
+
+    /// Records dropped so far
+    dropped: AtomicU64,
+
+    /// When the BufferHandler was called, in microseconds since the UNIX
+    /// epoch, or 0 once it has returned
+    handler_since: AtomicU64,
+
+    flushes: Mutex<Flushes>,
+
+    /// Held while the file is written, so that `Logger::heartbeat` and the
+    /// thread don't write it at once
+    writing: Mutex<()>,
+
+    stop: Mutex<bool>,
+    stopped: Condvar,
+}
+
+/// Counters updated once per buffer.
+#[derive(Debug, Clone, Copy, Default)]
+struct Flushes {
+    last_flush: Option<SystemTime>,
+    buffers_flushed: u64,
+    bytes_flushed: u64,
+    handler_time: Duration,
+}
+
+fn micros_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64
+}
+
+impl HealthReporter {
+    pub(crate) fn new(file: HealthFile) -> Self {
+        let shared = Arc::new(Shared {
+            file,
+            bytes_behind: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            handler_since: AtomicU64::new(0),
+            flushes: Mutex::new(Flushes::default()),
+            writing: Mutex::new(()),
+            stop: Mutex::new(false),
+            stopped: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new().name("blog-health".into()).spawn(move || shared.beat()).ok()
+        };
+        HealthReporter { shared, thread }
+    }
+
+    /// Notes a record written, `bytes_behind` bytes into the buffer. The
+    /// only work done on the write path.
+    pub(crate) fn wrote(&self, bytes_behind: usize) {
+        self.shared.bytes_behind.store(bytes_behind as u64, Ordering::Relaxed);
+    }
+
+    /// Notes records dropped, `dropped` so far.
+    pub(crate) fn dropped(&self, dropped: u64) {
+        self.shared.dropped.store(dropped, Ordering::Relaxed);
+    }
+
+    /// Notes the BufferHandler being called.
+    pub(crate) fn handler_started(&self) {
+        self.shared.handler_since.store(micros_now(), Ordering::Relaxed);
+    }
+
+    /// Notes the BufferHandler returning, whether or not it accepted the
+    /// buffer.
+    pub(crate) fn handler_returned(&self) {
+        self.shared.handler_since.store(0, Ordering::Relaxed);
+    }
+
+    /// Counts a buffer the handler has accepted. The heartbeat thread
+    /// reports it.
+    pub(crate) fn flushed(&self, size: usize, handler_time: Duration, bytes_behind: usize, dropped: u64) {
+        {
+            let mut flushes = self.shared.flushes.lock().unwrap();
+            flushes.last_flush = Some(SystemTime::now());
+            flushes.buffers_flushed += 1;
+            flushes.bytes_flushed += size as u64;
+            flushes.handler_time = handler_time;
+        }
+        self.wrote(bytes_behind);
+        self.dropped(dropped);
+    }
+
+    /// Writes the file now.
+    pub(crate) fn report(&self, bytes_behind: usize, dropped: u64) {
+        self.wrote(bytes_behind);
+        self.dropped(dropped);
+        self.shared.report();
+    }
+
+    /// Forgets the reporter without stopping its heartbeat thread, in a
+    /// forked child, where the thread doesn't exist and may have left a
+    /// lock held.
+    pub(crate) fn abandon(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for HealthReporter {
+    fn drop(&mut self) {
+        *self.shared.stop.lock().unwrap() = true;
+        self.shared.stopped.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Shared {
+    /// Writes a heartbeat every interval until the reporter is dropped.
+    fn beat(&self) {
+        // A zero interval writes as often as is reasonable
+        let interval = self.file.interval.max(Duration::from_millis(1));
+        loop {
+            let stop = self.stopped.wait_timeout_while(self.stop.lock().unwrap(), interval, |stop| !*stop).unwrap().0;
+            if *stop {
+                return;
+            }
+            drop(stop);
+            self.report();
+        }
+    }
+
+    /// Writes the file. Failures are ignored: a missing or stale file is
+    /// what watchdogs look for anyway.
+    fn report(&self) {
+        let _writing = self.writing.lock().unwrap();
+        let flushes = *self.flushes.lock().unwrap();
+        let handler_since = self.handler_since.load(Ordering::Relaxed);
+        let status = HealthStatus {
+            heartbeat_at: SystemTime::now(),
+            last_flush: flushes.last_flush,
+            buffers_flushed: flushes.buffers_flushed,
+            bytes_flushed: flushes.bytes_flushed,
+            bytes_behind: self.bytes_behind.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            handler_time: flushes.handler_time,
+            handler_since: (handler_since != 0).then(|| UNIX_EPOCH + Duration::from_micros(handler_since)),
+        };
+        let _ = status.write(&self.file.path);
+    }
+}
@@ -0,0 +1,100 @@
+#![allow(dead_code)]
+
+//! Live ingestion of text logs, backing the `blog-ingest` tool.
+//!
+//! `ingest` reads lines as another process writes them, from a pipe, a FIFO
+//! or a socket, and writes them as records the way `convert` does, so
+//! services that can't link this crate feed the same files, tools and
+//! queries. Unlike a conversion, which hands a buffer over when it fills,
+//! ingestion flushes the Logger once lines have waited `flush_every`, so a
+//! reader of the output is at most that far behind the producer even when
+//! lines come slowly.
+//!
+//! Lines are read on a thread of their own and queued, so the producer
+//! only blocks when the queue is full. With a pattern of
+//! `TextPattern::without_time`, lines are stamped with the time they are
+//! read. A line that doesn't match the pattern continues the message
+//! before it, or, once that was flushed, is written in a record of its own
+//! with the time, level and channel of the line before it.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::BufferHandler;
+//! # use binary_logger::convert::TextPattern;
+//! # use binary_logger::ingest::ingest;
+//! # use std::time::Duration;
+//! # struct NullHandler;
+//! # impl BufferHandler for NullHandler {
+//! #     fn handle_buffer(&self, _data: &[u8]) {}
+//! # }
+//! // The standard output of a service piped to this process
+//! let service = std::io::Cursor::new(b"WARN [db] pool at 90%\nINFO [db] pool at 40%\n".to_vec());
+//! let pattern = TextPattern::without_time("{level} [{channel}] {message}").unwrap();
+//! let stats = ingest(service, &pattern, Duration::from_millis(200), NullHandler).unwrap();
+//! assert_eq!(stats.records, 2);
+//! ```
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::binary_logger::BufferHandler;
+use crate::convert::{ConvertStats, Converter, TextPattern};
+
+/// Lines read ahead of the Logger before the reading thread waits
+const LINE_QUEUE: usize = 4096;
+
+/// Reads the lines of `reader` until it ends, writing them as records to
+/// `handler` with `pattern`, and returns what was read and written.
+///
+/// Lines reach the handler at most `flush_every` after they are read.
+/// Invalid UTF-8 is replaced with U+FFFD. Fails on read errors, after
+/// writing the lines read before them.
+pub fn ingest(reader: impl Read + Send + 'static, pattern: &TextPattern, flush_every: Duration, handler: impl BufferHandler + 'static) -> io::Result<ConvertStats> {
+    let (sender, lines) = sync_channel::<io::Result<String>>(LINE_QUEUE);
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return,
+                Ok(_) => Ok(String::from_utf8_lossy(&line).into_owned()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let failed = read.is_err();
+            if sender.send(read).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let mut converter = Converter::new(pattern, handler);
+    // When the oldest line not handed over yet was read
+    let mut waiting_since: Option<Instant> = None;
+    loop {
+        let line = match waiting_since {
+            Some(since) => lines.recv_timeout(flush_every.saturating_sub(since.elapsed())),
+            None => lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match line {
+            Ok(Ok(line)) => {
+                converter.push_line(&line)?;
+                waiting_since.get_or_insert_with(Instant::now);
+            }
+            Ok(Err(e)) => {
+                converter.finish()?;
+                return Err(e);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if waiting_since.is_some_and(|since| since.elapsed() >= flush_every) {
+            converter.flush()?;
+            waiting_since = None;
+        }
+    }
+    converter.finish()
+}
@@ -0,0 +1,396 @@
+#![allow(dead_code)]
+
+//! Low-level structure dump of binary log files.
+//!
+//! Unlike LogReader, which yields decoded entries and skips what it cannot
+//! parse, the inspector walks the raw bytes and annotates every field: buffer
+//! headers, record framing, stream headers and payloads. It is meant for
+//! debugging format issues and damaged files, and backs the `blog-inspect`
+//! binary.
+
+use std::io::{self, Write};
+use crate::binary_logger::{
+    BUFFER_HEADER_SIZE, RECORD_TYPE_NORMAL, RECORD_TYPE_BASE,
+    RECORD_TYPE_STREAM_HEADER, RECORD_TYPE_CHAIN, RECORD_TYPE_METRIC, RECORD_TYPE_DICTIONARY, RECORD_TYPE_TAGGED, TAGS_SIZE, RECORD_TYPE_COUNTS, COUNT_ENTRY_SIZE, RECORD_TYPE_METADATA, RECORD_TYPE_RETENTION, RETENTION_ENTRY_SIZE, RECORD_TYPE_PADDING, RECORD_TYPE_UNTIMED, RECORD_TYPE_TYPED, RECORD_TYPE_USER_MIN, STREAM_MAGIC, STREAM_HEADER_PAYLOAD_SIZE, STREAM_HEADER_BASE_SIZE, FLAG_SEQUENCE, FLAG_CHANNEL,
+    FLAG_SAME_FORMAT, FLAG_DELTA_TIME, FLAG_TRUNCATED, RECORD_TYPE_CLOCK_OFFSET, RECORD_TYPE_TAGGED_TYPED, LONG_LENGTH, record_header_size,
+};
+use crate::clock_sync::ClockOffset;
+use crate::codec::read_varint;
+use crate::efficient_clock::EPOCH_MICROS;
+use crate::codec::{Codec, RawCodec, codec_by_id};
+use crate::features::FormatFeatures;
+use crate::instrumentation::InternalEvent;
+use crate::metrics::MetricUpdate;
+use crate::string_registry::{decode_id, WIDE_ID};
+use crate::level::Level;
+use crate::tags::Tags;
+use crate::arg_types::TypeTag;
+
+/// Bytes shown per hex dump line
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes an annotated hex walk of `data` to `out`.
+///
+/// Every buffer and record is announced on its own line with its offset and
+/// decoded fields, followed by the hex bytes it consists of. Structural
+/// problems (oversized buffers, truncated records, unknown record types) are
+/// reported inline and the walk continues where it safely can.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::inspect::inspect;
+/// # fn example(data: &[u8]) -> std::io::Result<()> {
+/// inspect(data, &mut std::io::stdout().lock())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn inspect(data: &[u8], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "file: {} bytes", data.len())?;
+
+    let mut codec: Option<&'static dyn Codec> = Some(&RawCodec);
+    let mut pos = 0;
+    let mut buffer_index = 0;
+    let mut record_index = 0;
+    let mut last_rel_ts = 0;
+
+    while pos < data.len() {
+        if data.len() - pos < BUFFER_HEADER_SIZE {
+            writeln!(out, "{:08x}  trailing {} bytes, too short for a buffer header", pos, data.len() - pos)?;
+            dump(out, pos, &data[pos..], "")?;
+            break;
+        }
+
+        let size = read_u64(&data[pos..]) as usize;
+        let buffer_end = if size < BUFFER_HEADER_SIZE || size > data.len() - pos {
+            writeln!(out, "{:08x}  buffer #{}  size={} INVALID, treating the rest of the file as one buffer",
+                pos, buffer_index, size)?;
+            data.len()
+        } else {
+            writeln!(out, "{:08x}  buffer #{}  size={} (0x{:x})  crc32={:08x}",
+                pos, buffer_index, size, size, crc32(&data[pos + BUFFER_HEADER_SIZE..pos + size]))?;
+            pos + size
+        };
+        dump(out, pos, &data[pos..pos + BUFFER_HEADER_SIZE], "buffer size")?;
+        pos += BUFFER_HEADER_SIZE;
+
+        let mut last_format_id = 0;
+        while pos < buffer_end {
+            let record_start = pos;
+            let header_size = match record_header_size(&data[pos..buffer_end]) {
+                Some(size) if size <= buffer_end - pos => size,
+                _ => {
+                    writeln!(out, "{:08x}  trailing {} bytes in buffer, too short for a record header", pos, buffer_end - pos)?;
+                    dump(out, pos, &data[pos..buffer_end], "")?;
+                    break;
+                }
+            };
+
+            let header = &data[pos..pos + header_size];
+            let record_type = header[0];
+            let flags = header[1];
+            let (time, format_pos) = if record_type == RECORD_TYPE_UNTIMED {
+                ("no time".to_string(), 2)
+            } else if flags & FLAG_DELTA_TIME != 0 {
+                let (delta, rest) = read_varint(&header[2..]).unwrap_or_default();
+                // Deltas advance the relative timestamp like a wrap would
+                last_rel_ts = ((last_rel_ts as u64 + delta as u64) % EPOCH_MICROS) as u16;
+                (format!("delta={}us", delta), header_size - rest.len())
+            } else {
+                // A relative timestamp below its predecessor's marks an epoch wrap
+                let rel_ts = u16::from_le_bytes([header[2], header[3]]);
+                let relative = record_type == RECORD_TYPE_NORMAL || record_type == RECORD_TYPE_METRIC
+                    || record_type == RECORD_TYPE_DICTIONARY || record_type == RECORD_TYPE_TAGGED
+                    || record_type == RECORD_TYPE_COUNTS || record_type == RECORD_TYPE_TYPED || record_type == RECORD_TYPE_TAGGED_TYPED
+                    || record_type >= RECORD_TYPE_USER_MIN;
+                let wrap = if relative && rel_ts < last_rel_ts { " (wrap)" } else { "" };
+                if relative || record_type == RECORD_TYPE_BASE {
+                    last_rel_ts = rel_ts;
+                }
+                (format!("rel_ts={}us{}", rel_ts, wrap), 4)
+            };
+            // A wide format ID is dumped after the channel
+            let wide_format = flags & FLAG_SAME_FORMAT == 0
+                && u16::from_le_bytes([header[format_pos], header[format_pos + 1]]) == WIDE_ID;
+            let mut format_id = last_format_id;
+            let (shown_id, note) = if flags & FLAG_SAME_FORMAT != 0 {
+                (format_id.to_string(), format!("{} (repeated)", format_id_note(format_id)))
+            } else if wide_format {
+                ("wide".to_string(), String::new())
+            } else {
+                format_id = u16::from_le_bytes([header[format_pos], header[format_pos + 1]]) as u32;
+                (format_id.to_string(), format_id_note(format_id))
+            };
+            last_format_id = format_id;
+            // A long record's length is dumped after the wide format ID
+            let mut payload_len = u16::from_le_bytes([header[header_size - 2], header[header_size - 1]]) as usize;
+            let long = payload_len == LONG_LENGTH as usize;
+            let shown_len = if long { "long".to_string() } else { payload_len.to_string() };
+
+            let level = Level::from_flags(flags).map(|level| format!("  level={}", level)).unwrap_or_default();
+            let truncated = if flags & FLAG_TRUNCATED != 0 { "  truncated" } else { "" };
+            writeln!(out, "{:08x}  record #{}  type={} ({})  flags=0x{:02x}  {}  format_id={}{}  payload_len={}{}{}",
+                pos, record_index, record_type, record_type_name(record_type), flags, time,
+                shown_id, note, shown_len, level, truncated)?;
+            dump(out, pos, header, "record header")?;
+            pos += header_size;
+            record_index += 1;
+
+            if flags & FLAG_SEQUENCE != 0 {
+                if buffer_end - pos < 8 {
+                    writeln!(out, "{:08x}  TRUNCATED sequence number", pos)?;
+                    break;
+                }
+                let sequence = read_u64(&data[pos..]);
+                dump(out, pos, &data[pos..pos + 8], &format!("sequence={}", sequence))?;
+                pos += 8;
+            }
+
+            if flags & FLAG_CHANNEL != 0 {
+                let Some((channel, len)) = decode_id(&data[pos..buffer_end]) else {
+                    writeln!(out, "{:08x}  TRUNCATED channel ID", pos)?;
+                    break;
+                };
+                dump(out, pos, &data[pos..pos + len], &format!("channel={}", channel))?;
+                pos += len;
+            }
+
+            if wide_format {
+                if buffer_end - pos < 4 {
+                    writeln!(out, "{:08x}  TRUNCATED wide format ID", pos)?;
+                    break;
+                }
+                format_id = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+                last_format_id = format_id;
+                dump(out, pos, &data[pos..pos + 4], &format!("format_id={}{}", format_id, format_id_note(format_id)))?;
+                pos += 4;
+            }
+
+            if long {
+                if buffer_end - pos < 4 {
+                    writeln!(out, "{:08x}  TRUNCATED long record length", pos)?;
+                    break;
+                }
+                payload_len = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+                dump(out, pos, &data[pos..pos + 4], &format!("payload_len={}", payload_len))?;
+                pos += 4;
+            }
+
+            if payload_len > buffer_end - pos {
+                writeln!(out, "{:08x}  TRUNCATED payload: {} bytes declared, {} left in buffer",
+                    pos, payload_len, buffer_end - pos)?;
+                dump(out, pos, &data[pos..buffer_end], "")?;
+                break;
+            }
+            let mut payload = &data[pos..pos + payload_len];
+            let mut payload_pos = pos;
+
+            match record_type {
+                RECORD_TYPE_STREAM_HEADER => {
+                    if payload.len() >= STREAM_HEADER_BASE_SIZE && payload[..4] == STREAM_MAGIC {
+                        codec = codec_by_id(payload[5]);
+                        let mut note = format!("magic=\"BLOG\"  version={}  codec={} ({})",
+                            payload[4], payload[5], codec.map(|c| c.name()).unwrap_or("unknown"));
+                        if payload.len() >= STREAM_HEADER_PAYLOAD_SIZE {
+                            let features = FormatFeatures::from_bits(u32::from_le_bytes([payload[6], payload[7], payload[8], payload[9]]));
+                            note += &format!("  features={}", features);
+                            if !features.unsupported().is_empty() {
+                                note += &format!("  UNSUPPORTED {:#x}", features.unsupported().bits());
+                            }
+                        }
+                        dump(out, payload_pos, payload, &note)?;
+                    } else {
+                        dump(out, payload_pos, payload, "BAD stream header")?;
+                    }
+                }
+                RECORD_TYPE_NORMAL | RECORD_TYPE_BASE | RECORD_TYPE_TAGGED | RECORD_TYPE_UNTIMED | RECORD_TYPE_TYPED | RECORD_TYPE_TAGGED_TYPED => {
+                    if record_type == RECORD_TYPE_TAGGED || record_type == RECORD_TYPE_TAGGED_TYPED {
+                        if payload.len() < TAGS_SIZE {
+                            dump(out, payload_pos, payload, "TRUNCATED tag mask")?;
+                            payload = &[];
+                        } else {
+                            let tags = Tags::from_bits(u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]));
+                            dump(out, payload_pos, &payload[..TAGS_SIZE], &format!("tags={}", tags))?;
+                            payload = &payload[TAGS_SIZE..];
+                            payload_pos += TAGS_SIZE;
+                        }
+                    }
+                    if record_type == RECORD_TYPE_TYPED || record_type == RECORD_TYPE_TAGGED_TYPED {
+                        let tags_len = 1 + payload.first().map_or(0, |&count| count as usize);
+                        if payload.len() < tags_len {
+                            dump(out, payload_pos, payload, "TRUNCATED type tags")?;
+                            payload = &[];
+                        } else {
+                            let types: Vec<String> = payload[1..tags_len].iter().map(|&bits| TypeTag::from_bits(bits).to_string()).collect();
+                            dump(out, payload_pos, &payload[..tags_len], &format!("types=[{}]", types.join(", ")))?;
+                            payload = &payload[tags_len..];
+                            payload_pos += tags_len;
+                        }
+                    }
+                    if record_type == RECORD_TYPE_BASE {
+                        if payload.len() < 8 {
+                            dump(out, payload_pos, payload, "TRUNCATED base timestamp")?;
+                            payload = &[];
+                        } else {
+                            let base = read_u64(payload);
+                            dump(out, payload_pos, &payload[..8], &format!("base={}us since epoch", base))?;
+                            payload = &payload[8..];
+                            payload_pos += 8;
+                        }
+                    }
+                    if !payload.is_empty() || format_id != 0 {
+                        let note = match codec {
+                            Some(codec) => format!("args={:02x?}", codec.decode(payload)),
+                            None => "args=<unknown codec>".to_string(),
+                        };
+                        dump(out, payload_pos, payload, &note)?;
+                    }
+                }
+                RECORD_TYPE_CHAIN => {
+                    let hash: String = payload.iter().map(|b| format!("{:02x}", b)).collect();
+                    dump(out, payload_pos, payload, &format!("previous buffer sha256={}", hash))?;
+                }
+                RECORD_TYPE_METRIC => {
+                    let note = match MetricUpdate::decode(payload) {
+                        Some(update) => format!("{:?}", update),
+                        None => "BAD metric update".to_string(),
+                    };
+                    dump(out, payload_pos, payload, &note)?;
+                }
+                RECORD_TYPE_DICTIONARY | RECORD_TYPE_METADATA => {
+                    // One line per `id u16 | len u16 | bytes` entry
+                    let mut entry_pos = payload_pos;
+                    while payload.len() >= 4 {
+                        let id = u16::from_le_bytes([payload[0], payload[1]]);
+                        let len = u16::from_le_bytes([payload[2], payload[3]]) as usize;
+                        let Some(bytes) = payload.get(4..4 + len) else {
+                            break;
+                        };
+                        let note = format!("string {}: {:?}", id, String::from_utf8_lossy(bytes));
+                        dump(out, entry_pos, &payload[..4 + len], &note)?;
+                        payload = &payload[4 + len..];
+                        entry_pos += 4 + len;
+                    }
+                    if !payload.is_empty() {
+                        dump(out, entry_pos, payload, "BAD dictionary entry")?;
+                    }
+                }
+                RECORD_TYPE_COUNTS => {
+                    // One line per `format_id u16 | count u32` entry
+                    let mut entry_pos = payload_pos;
+                    for entry in payload.chunks_exact(COUNT_ENTRY_SIZE) {
+                        let format_id = u16::from_le_bytes([entry[0], entry[1]]) as u32;
+                        let count = u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]]);
+                        let note = format!("counter {}{}: {}", format_id, format_id_note(format_id), count);
+                        dump(out, entry_pos, entry, &note)?;
+                        entry_pos += COUNT_ENTRY_SIZE;
+                    }
+                    if !payload.len().is_multiple_of(COUNT_ENTRY_SIZE) {
+                        dump(out, entry_pos, &payload[entry_pos - payload_pos..], "BAD count entry")?;
+                    }
+                }
+                RECORD_TYPE_RETENTION => {
+                    // One line per `channel u16 | ttl_seconds u32` entry
+                    let mut entry_pos = payload_pos;
+                    for entry in payload.chunks_exact(RETENTION_ENTRY_SIZE) {
+                        let channel = u16::from_le_bytes([entry[0], entry[1]]) as u32;
+                        let ttl = u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]]);
+                        let note = format!("channel {}{}: ttl {}s", channel, format_id_note(channel), ttl);
+                        dump(out, entry_pos, entry, &note)?;
+                        entry_pos += RETENTION_ENTRY_SIZE;
+                    }
+                    if !payload.len().is_multiple_of(RETENTION_ENTRY_SIZE) {
+                        dump(out, entry_pos, &payload[entry_pos - payload_pos..], "BAD retention entry")?;
+                    }
+                }
+                RECORD_TYPE_CLOCK_OFFSET => match ClockOffset::decode(payload) {
+                    Some(offset) => {
+                        let note = format!("clock offset {:+}us ±{}us ({:?})", offset.offset_micros, offset.error_bound_micros, offset.source);
+                        dump(out, payload_pos, payload, &note)?;
+                    }
+                    None => dump(out, payload_pos, payload, "BAD clock offset")?,
+                },
+                RECORD_TYPE_PADDING => dump(out, payload_pos, payload, "filler")?,
+                RECORD_TYPE_USER_MIN.. => dump(out, payload_pos, payload, "application payload")?,
+                _ => dump(out, payload_pos, payload, "payload of unknown record type")?,
+            }
+            pos += payload_len;
+
+            // Records are padded to keep the next one 2-byte aligned
+            if !(pos - record_start).is_multiple_of(2) && pos < buffer_end {
+                dump(out, pos, &data[pos..pos + 1], "padding")?;
+                pos += 1;
+            }
+        }
+
+        pos = buffer_end;
+        buffer_index += 1;
+    }
+
+    writeln!(out, "{} buffers, {} records", buffer_index, record_index)
+}
+
+/// Short name of a record type.
+fn record_type_name(record_type: u8) -> &'static str {
+    match record_type {
+        RECORD_TYPE_NORMAL => "normal",
+        RECORD_TYPE_BASE => "base",
+        RECORD_TYPE_STREAM_HEADER => "stream header",
+        RECORD_TYPE_CHAIN => "audit chain",
+        RECORD_TYPE_METRIC => "metric",
+        RECORD_TYPE_DICTIONARY => "dictionary",
+        RECORD_TYPE_TAGGED => "tagged",
+        RECORD_TYPE_COUNTS => "counts",
+        RECORD_TYPE_METADATA => "metadata",
+        RECORD_TYPE_RETENTION => "retention",
+        RECORD_TYPE_PADDING => "padding",
+        RECORD_TYPE_UNTIMED => "untimed",
+        RECORD_TYPE_TYPED => "typed",
+        RECORD_TYPE_CLOCK_OFFSET => "clock offset",
+        RECORD_TYPE_TAGGED_TYPED => "tagged typed",
+        RECORD_TYPE_USER_MIN.. => "application",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Annotation for format IDs with a fixed meaning.
+fn format_id_note(format_id: u32) -> String {
+    match InternalEvent::from_format_id(format_id) {
+        Some(event) => format!(" (internal {:?})", event),
+        None if format_id == 0 => " (none)".to_string(),
+        None => String::new(),
+    }
+}
+
+/// Writes `bytes` as indented hex lines, annotating the first line with `note`.
+fn dump(out: &mut impl Write, offset: usize, bytes: &[u8], note: &str) -> io::Result<()> {
+    if bytes.is_empty() {
+        return writeln!(out, "{:08x}    {:width$}  {}", offset, "(empty)", note, width = BYTES_PER_LINE * 3 - 1);
+    }
+    for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let note = if i == 0 { note } else { "" };
+        writeln!(out, "{:08x}    {:width$}  {}", offset + i * BYTES_PER_LINE, hex.join(" "), note,
+            width = BYTES_PER_LINE * 3 - 1)?;
+    }
+    Ok(())
+}
+
+/// Reads a little-endian u64 from the start of `bytes`.
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(value)
+}
+
+/// CRC-32 (IEEE) of `bytes`, used to compare buffers across copies of a file.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
@@ -0,0 +1,110 @@
+#![allow(dead_code)]
+
+//! Points in time as log arguments.
+//!
+//! `LogInstant` logs "event happened at T" the way record timestamps are
+//! stored: relative to the record. The argument is the number of
+//! microseconds between T and the moment the record is written, as a
+//! zigzag varint, usually 1 to 4 bytes. LogReader adds it back to the
+//! record's timestamp (`LogValue::Instant`).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::arg_types::{ArgKind, ARG_SCRATCH_SIZE};
+use crate::binary_logger::LogArg;
+use crate::efficient_clock::{calibration, get_timestamp};
+
+/// A point in time logged relative to its record's timestamp.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::instant::LogInstant;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// let received = LogInstant::now();
+/// // ... handle the request ...
+/// log_record!(logger, "Request received at {} completed", received).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LogInstant {
+    ticks: u64,
+}
+
+impl LogInstant {
+    /// Returns the current time, read from the logger's clock.
+    pub fn now() -> Self {
+        LogInstant { ticks: get_timestamp() }
+    }
+
+    /// Creates an instant from a value of `efficient_clock::get_timestamp`.
+    pub fn from_ticks(ticks: u64) -> Self {
+        LogInstant { ticks }
+    }
+
+    /// Creates an instant from wall-clock time.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let cal = calibration();
+        let micros = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros().min(u64::MAX as u128) as u64;
+        let ticks = if micros >= cal.anchor_micros {
+            cal.anchor_ticks.saturating_add(cal.micros_as_ticks(micros - cal.anchor_micros))
+        } else {
+            cal.anchor_ticks.saturating_sub(cal.micros_as_ticks(cal.anchor_micros - micros))
+        };
+        LogInstant { ticks }
+    }
+
+    /// Returns the clock value of the instant.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+}
+
+impl LogArg for LogInstant {
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Instant
+    }
+
+    fn encode(&self, out: &mut [u8; ARG_SCRATCH_SIZE]) -> Option<usize> {
+        // Microseconds before the record, negative for instants after it
+        let cal = calibration();
+        let now = get_timestamp();
+        let before = if now >= self.ticks {
+            cal.ticks_as_micros(now - self.ticks) as i64
+        } else {
+            -(cal.ticks_as_micros(self.ticks - now) as i64)
+        };
+
+        let mut value = ((before << 1) ^ (before >> 63)) as u64;
+        let mut len = 0;
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out[len] = byte;
+                return Some(len + 1);
+            }
+            out[len] = byte | 0x80;
+            len += 1;
+        }
+    }
+}
+
+/// Decodes an argument written by `LogInstant` into microseconds before the
+/// record.
+pub(crate) fn decode_instant(arg: &[u8]) -> Option<i64> {
+    if arg.is_empty() || arg.len() > 10 {
+        return None;
+    }
+    let mut value = 0u64;
+    for (i, &byte) in arg.iter().enumerate() {
+        value |= ((byte & 0x7F) as u64) << (7 * i);
+        if (byte & 0x80 == 0) != (i == arg.len() - 1) {
+            return None;
+        }
+    }
+    Some((value >> 1) as i64 ^ -((value & 1) as i64))
+}
@@ -0,0 +1,119 @@
+#![allow(dead_code)]
+
+//! Logger self-instrumentation events.
+//!
+//! When instrumentation is enabled on a Logger, it writes records about its own
+//! behavior (buffer switches, handler duration, drops, registry growth) into
+//! the same stream as application events. These records use format IDs from a
+//! reserved range that the string registry never hands out, so they decode
+//! like any other entry and can be told apart with `LogEntry::is_internal()`.
+
+/// First format ID of the range reserved for internal events.
+///
+/// The string registry assigns no IDs from here to `RESERVED_FORMAT_ID_END`.
+/// Internal events take the IDs below `common_strings::COMMON_STRING_ID_START`,
+/// and the built-in common strings those from there.
+pub const RESERVED_FORMAT_ID_START: u32 = 0xFF00;
+
+/// First format ID past the reserved range.
+pub const RESERVED_FORMAT_ID_END: u32 = 0x1_0000;
+
+/// An event emitted by the logger about itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InternalEvent {
+    /// A buffer is about to be handed to the BufferHandler.
+    /// Argument: bytes used in the outgoing buffer.
+    BufferSwitchStart,
+
+    /// The BufferHandler returned.
+    /// Argument: time spent in the handler in microseconds.
+    BufferSwitchEnd,
+
+    /// Records were dropped instead of being written.
+    /// Argument: number of records dropped since the last report.
+    Drops,
+
+    /// New strings were added to the global string registry.
+    /// Argument: number of registered strings.
+    RegistryGrowth,
+
+    /// Records of one format were skipped by level, see
+    /// `Logger::set_suppression_summaries`. The record carries the level of
+    /// the skipped records.
+    /// Arguments: records skipped, their format ID, and the milliseconds
+    /// over which they were counted.
+    Suppressed,
+
+    /// The Logger was restarted in a forked child, see `Logger::post_fork`.
+    /// Written whether or not instrumentation is enabled.
+    /// Arguments: the child's process ID and the parent's.
+    Forked,
+
+    /// Records identical to an earlier one were left out in its window, see
+    /// `Logger::set_duplicate_filter`. The record carries the level and
+    /// channel of the left out records.
+    /// Arguments: records left out, their format ID, and the milliseconds
+    /// over which they were counted.
+    DuplicatesSuppressed,
+
+    /// A segment of the log begins, see `Logger::begin_segment`. Written
+    /// whether or not instrumentation is enabled.
+    /// Argument: the segment's name.
+    SegmentBegin,
+
+    /// The segment begun last ends, see `Logger::end_segment`. Written
+    /// whether or not instrumentation is enabled.
+    /// Argument: the segment's name.
+    SegmentEnd,
+}
+
+impl InternalEvent {
+    /// All internal events, in format ID order.
+    pub const ALL: [InternalEvent; 9] = [
+        InternalEvent::BufferSwitchStart,
+        InternalEvent::BufferSwitchEnd,
+        InternalEvent::Drops,
+        InternalEvent::RegistryGrowth,
+        InternalEvent::Suppressed,
+        InternalEvent::Forked,
+        InternalEvent::DuplicatesSuppressed,
+        InternalEvent::SegmentBegin,
+        InternalEvent::SegmentEnd,
+    ];
+
+    /// Returns the reserved format ID of this event.
+    pub const fn format_id(self) -> u32 {
+        RESERVED_FORMAT_ID_START + self as u32
+    }
+
+    /// Returns the format string used to render this event.
+    pub const fn format_string(self) -> &'static str {
+        match self {
+            InternalEvent::BufferSwitchStart => "[binary_logger] buffer switch start: {} bytes",
+            InternalEvent::BufferSwitchEnd => "[binary_logger] buffer switch end: handler took {} us",
+            InternalEvent::Drops => "[binary_logger] dropped {} records",
+            InternalEvent::RegistryGrowth => "[binary_logger] string registry grew to {} entries",
+            InternalEvent::Suppressed => "[binary_logger] suppressed {} records from format {} in last {} ms",
+            InternalEvent::Forked => "[binary_logger] process {} forked from {}",
+            InternalEvent::DuplicatesSuppressed => "[binary_logger] suppressed {} duplicates of format {} in {} ms",
+            InternalEvent::SegmentBegin => "[binary_logger] segment {} begins",
+            InternalEvent::SegmentEnd => "[binary_logger] segment {} ends",
+        }
+    }
+
+    /// Looks up the internal event with the given format ID.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(InternalEvent)` - If the ID belongs to a known internal event
+    /// * `None` - Otherwise
+    pub fn from_format_id(id: u32) -> Option<InternalEvent> {
+        let index = id.checked_sub(RESERVED_FORMAT_ID_START)? as usize;
+        Self::ALL.get(index).copied()
+    }
+}
+
+/// Returns true if the format ID lies in the range reserved for internal events.
+pub fn is_reserved_format_id(id: u32) -> bool {
+    (RESERVED_FORMAT_ID_START..RESERVED_FORMAT_ID_END).contains(&id)
+}
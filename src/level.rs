@@ -0,0 +1,222 @@
+#![allow(dead_code)]
+
+//! Severity levels of log records.
+//!
+//! A record written with `log_record_at!` carries its level in the flags
+//! byte of its header (bits 4-6, see `FLAG_LEVEL_MASK`), so it costs no
+//! extra space and tools can route or filter by level from the headers
+//! alone. Records written with `log_record!` have no level.
+//!
+//! Each Logger skips records below its level (`Logger::set_level`). For
+//! incident investigation the threshold can be lowered temporarily, for one
+//! Logger with `Logger::with_level`, or for every Logger in the process with
+//! `with_global_level` (a code region) and `boost_for` (a time window).
+//!
+//! Records below `STATIC_LEVEL`, chosen when the crate is built, are removed
+//! at compile time, so no override brings them back.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Severity of a log record, from least to most severe.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::level::Level;
+/// assert!(Level::Warn > Level::Info);
+/// assert_eq!("warn".parse::<Level>(), Ok(Level::Warn));
+/// assert_eq!(Level::Error.to_string(), "ERROR");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace = 1,
+    Debug = 2,
+    Info = 3,
+    Warn = 4,
+    Error = 5,
+}
+
+impl Level {
+    /// All levels, from least to most severe.
+    pub const ALL: [Level; 5] = [Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+    /// Returns the upper-case name of the level, as rendered in text.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    /// Encodes an optional level into record header flag bits.
+    pub(crate) fn to_flags(level: Option<Level>) -> u8 {
+        level.map_or(0, |level| (level as u8) << LEVEL_SHIFT)
+    }
+
+    /// Decodes the level from record header flags.
+    pub(crate) fn from_flags(flags: u8) -> Option<Level> {
+        match (flags & FLAG_LEVEL_MASK) >> LEVEL_SHIFT {
+            0 => None,
+            n => Level::ALL.get(n as usize - 1).copied(),
+        }
+    }
+}
+
+/// Least severe level `log_record_at!` and `log_tagged!` compile in.
+///
+/// Records below it are removed at compile time, arguments included. It
+/// is `Trace` unless the `BINARY_LOGGER_MIN_LEVEL` environment variable
+/// names another level when the crate is built, as in
+/// `BINARY_LOGGER_MIN_LEVEL=info cargo build --release`; an unknown name
+/// fails the build. A variable rather than features keeps builds with
+/// `--all-features` logging everything.
+pub const STATIC_LEVEL: Level = match option_env!("BINARY_LOGGER_MIN_LEVEL") {
+    Some(name) => parse_static_level(name.as_bytes()),
+    None => Level::Trace,
+};
+
+/// Parses the name of `STATIC_LEVEL` at compile time, like `FromStr`.
+const fn parse_static_level(name: &[u8]) -> Level {
+    let mut index = 0;
+    while index < Level::ALL.len() {
+        let level = Level::ALL[index];
+        if name.eq_ignore_ascii_case(level.as_str().as_bytes()) {
+            return level;
+        }
+        index += 1;
+    }
+    if name.eq_ignore_ascii_case(b"warning") {
+        return Level::Warn;
+    }
+    panic!("BINARY_LOGGER_MIN_LEVEL must be trace, debug, info, warn or error")
+}
+
+/// Record flag bits holding the level: 0 for none, else `Level as u8`
+pub(crate) const FLAG_LEVEL_MASK: u8 = 0x70;
+
+/// Position of the level in the record flags
+const LEVEL_SHIFT: u8 = 4;
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    /// Parses a level name, ignoring case (`warn`, `WARN`, `warning`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Ok(Level::Trace),
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" | "warning" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(format!("unknown level: {}", s)),
+        }
+    }
+}
+
+/// Level of the process-wide boost, 0 for none
+static BOOST_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// End of the boost in nanoseconds after `boost_epoch()`, 0 for none
+static BOOST_UNTIL: AtomicU64 = AtomicU64::new(0);
+
+fn boost_epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Returns the process-wide boost level, if a boost is active.
+///
+/// While it is, every Logger writes records at that level or above, even
+/// below its own level.
+pub fn global_level() -> Option<Level> {
+    let level = Level::from_flags(BOOST_LEVEL.load(Ordering::Relaxed) << LEVEL_SHIFT)?;
+    let until = BOOST_UNTIL.load(Ordering::Relaxed);
+    if until != 0 && boost_epoch().elapsed().as_nanos() >= until as u128 {
+        return None;
+    }
+    Some(level)
+}
+
+/// Returns whether a process-wide boost lets records at `level` through.
+#[inline]
+pub(crate) fn boosted(level: Level) -> bool {
+    // Without a boost this is a single load
+    let boost = BOOST_LEVEL.load(Ordering::Relaxed);
+    boost != 0 && level as u8 >= boost && global_level().is_some()
+}
+
+/// Sets the process-wide boost, returning the previous one.
+fn set_boost(level: Option<Level>, until: u64) -> (u8, u64) {
+    let previous = (BOOST_LEVEL.load(Ordering::Relaxed), BOOST_UNTIL.load(Ordering::Relaxed));
+    BOOST_UNTIL.store(until, Ordering::Relaxed);
+    BOOST_LEVEL.store(level.map_or(0, |level| level as u8), Ordering::Relaxed);
+    previous
+}
+
+/// Lowers the level of every Logger in the process to `level` while `f`
+/// runs, then restores the previous boost.
+///
+/// This affects all threads, not only the calling one. Overlapping calls
+/// from several threads restore in the order they finish, so the last one
+/// to finish decides what remains.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record_at};
+/// # use binary_logger::level::{self, Level};
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// logger.set_level(Level::Warn);
+/// level::with_global_level(Level::Debug, || {
+///     log_record_at!(logger, Level::Debug, "Written {}", 1).unwrap();
+/// });
+/// log_record_at!(logger, Level::Debug, "Skipped {}", 2).unwrap();
+/// assert_eq!(logger.stats().records_written, 1);
+/// ```
+pub fn with_global_level<R>(level: Level, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous boost, also when `f` panics
+    struct Restore((u8, u64));
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let (level, until) = self.0;
+            BOOST_UNTIL.store(until, Ordering::Relaxed);
+            BOOST_LEVEL.store(level, Ordering::Relaxed);
+        }
+    }
+
+    let _restore = Restore(set_boost(Some(level), 0));
+    f()
+}
+
+/// Lowers the level of every Logger in the process to `level` for
+/// `duration`, for example from an admin endpoint during an incident.
+///
+/// Replaces any current boost. Once `duration` has passed, Loggers go back
+/// to their own levels without further calls.
+pub fn boost_for(level: Level, duration: Duration) {
+    let until = boost_epoch().elapsed().saturating_add(duration).as_nanos();
+    set_boost(Some(level), u64::try_from(until).unwrap_or(u64::MAX).max(1));
+}
+
+/// Ends the process-wide boost, if any.
+pub fn clear_boost() {
+    set_boost(None, 0);
+}
@@ -1,5 +1,4 @@
-#![feature(generic_const_exprs)]
-#![allow(incomplete_features)]
+#![cfg_attr(not(stable_compat), feature(min_specialization))]
 
 //! # Binary Logger
 //! 
@@ -21,8 +20,61 @@
 //! 
 //! * `Logger`: Core logging engine that writes records in binary format (one per thread)
 //! * `LogReader`: Utility for reading and decoding binary log files
+//! * `StreamReader`: Decodes logs from any `io::Read` source, with optional read-ahead
+//! * `LogMerger`: Merges per-thread logs into one `(timestamp, sequence)` ordered stream
 //! * `string_registry`: Registry for efficient string deduplication
+//! * `common_strings`: Built-in IDs for common string values such as levels, HTTP methods and status codes
 //! * `efficient_clock`: High-precision, low-overhead timestamp generation
+//! * `codec`: Pluggable encodings for record arguments (raw, postcard, CBOR)
+//! * `schema_export`: FlatBuffers schema generation and export for non-Rust consumers
+//! * `inspect`: Annotated hex walk of raw log files (the `blog-inspect` tool)
+//! * `selftest()`: Startup check that the writer and reader agree on this machine
+//! * `audit`: Verification of hash-chained audit logs
+//! * `verify`: Whole-log integrity checks for one or more segments (the `blog-verify` tool)
+//! * `level`: `Level`, record severities set with `log_record_at!`
+//! * `sampling`: `KeySampler`, complete records for a deterministic subset of keys (`log_sampled!`)
+//! * `tags`: `Tags`, user labels on records (`log_tagged!`), filtered by bitmask
+//! * `features`: `FormatFeatures`, the optional format features a stream declares in its header
+//! * `flags`: `Flags`, booleans and enum discriminants packed into one argument
+//! * `schema`: `SchemaRegistry`, argument types of format strings checked when writing (`Logger::set_schema_registry`)
+//! * `arg_types`: Argument kinds (`f32`, `f16`) the reader can't infer from their size, and type tags
+//! * `fixed`: `Fixed`, exact decimal arguments for prices and quantities
+//! * `instant`: `LogInstant`, points in time stored relative to their record
+//! * `error_chain`: `log_error!` and `ErrorChain`, errors logged with their causes
+//! * `backtrace`: `LogBacktrace`, resolved backtraces with interned frames
+//! * `metrics`: Metric records and their time series, written by `BinaryRecorder` (`metrics` feature)
+//! * `handlers`: Reusable BufferHandlers, such as `FanOut` to several sinks
+//! * `signal_safe`: `AsyncSignalSafeLogger`, lock-free logging from signal handlers (`log_signal_safe!`)
+//! * `pool`: `LoggerPool`, a Logger per thread with pool-wide flush, metrics and levels
+//! * `facade`: `BinaryLog`, a `log` crate backend for existing `info!`/`warn!` call sites (`init_global`)
+//! * `buffer_pool`: `BufferPool`, buffers leased by many Loggers within one memory bound
+//! * `health`: Sidecar status file for external watchdogs (`Logger::set_health_file`)
+//! * `snapshot`: `debug_snapshot()`, registered strings and Logger state for debuggers, optionally over HTTP
+//! * `render`: One-line text rendering of entries for text-based tools
+//! * `search`: Format-string based narrowing of pattern searches (the `blog-grep` tool)
+//! * `query`: `LogQuery`, regex search over decoded entries
+//! * `log_stats`: `LogStats`, counter totals and per-buffer counts of `log_count!`
+//! * `downsample`: `Downsampler`, per-interval aggregates for dashboards (the `blog-downsample` tool)
+//! * `analysis`: `latency_between`, latencies of start and end events paired by a key argument, and `list_segments`
+//! * `retention`: `Expiry`, removal of entries past their channel's time to live (the `blog-expire` tool)
+//! * `compact`: `Compaction`, aged segments merged and compressed for long-term storage (the `blog-compact` tool)
+//! * `rotation`: `RotatingFileHandler`, files rotated by size and time under names the tools enumerate (`segments`)
+//! * `compression`: Buffers compressed one at a time into frames readers decompress (`CompressedFileHandler`, `lz4` or `zstd` feature)
+//! * `clock_sync`: `ClockOffset`, host clock offsets for aligning the logs of several machines (`LogMerger::aligned`)
+//! * `typed`: `FromLogEntry`, entries read back as application structs (`LogReader::typed`)
+//! * `loggable`: `Loggable`, structs logged field by field and read back as `LogValue::Struct`
+//! * `serve`: JSON API and page of the `blog-serve` web viewer (`serve` feature)
+//! * `proxy`: `DecodingProxy`, live decoding of streams from many producers (the `blog-proxy` tool)
+//! * `soak`: Long stability runs checking chains, counts and timestamps as they log (the `blog-soak` tool)
+//! * `convert`: `from_text`, existing text logs re-encoded in the binary format (the `blog-convert` tool)
+//! * `ingest`: `ingest`, text lines of other processes written as records as they come (the `blog-ingest` tool)
+//! * `sim`: `Simulation`, Loggers on a virtual clock writing to memory, for reproducible tests (`sim` feature)
+//! * `scheduling`: `ThreadScheduling`, pinning and nice values of handler threads (Linux)
+//! * `seqpacket`: `SeqpacketHandler`, buffers sent to a local collector with the sender's credentials (Linux)
+//! * `wire`: `Record`, encoding and decoding of buffers, records and varints for tools handling the format directly
+//! * `sites`: `sites()`, every logging call site in the program, and a `--list-sites` option
+//! * `alloc_guard`: `assert_no_alloc`, checks that logging paths don't allocate
+//! * `instrumentation`: Reserved-ID records describing the logger's own behavior
 //! 
 //! ## Quick Start
 //! 
@@ -35,8 +87,7 @@
 //! // Define a handler for log buffers
 //! struct FileHandler(RefCell<File>);
 //! impl BufferHandler for FileHandler {
-//!     fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
-//!         let data = unsafe { std::slice::from_raw_parts(buffer, size) };
+//!     fn handle_buffer(&self, data: &[u8]) {
 //!         self.0.borrow_mut().write_all(data).unwrap();
 //!     }
 //! }
@@ -53,9 +104,73 @@
 
 pub mod binary_logger;
 pub mod string_registry;
+pub mod common_strings;
 pub mod log_reader;
 pub mod efficient_clock;
+pub mod log_merger;
+pub mod stream;
+pub mod instrumentation;
+pub mod codec;
+pub mod level;
+pub mod tags;
+pub mod sampling;
+pub mod dedup;
+pub mod features;
+pub mod flags;
+pub mod arg_types;
+pub mod schema;
+pub mod fixed;
+pub mod instant;
+pub mod error_chain;
+pub mod backtrace;
+pub mod metrics;
+pub mod health;
+pub mod snapshot;
+pub mod buffer_pool;
+pub mod handlers;
+pub mod pool;
+pub mod facade;
+pub mod signal_safe;
+pub mod schema_export;
+pub mod inspect;
+pub mod selftest;
+pub mod audit;
+pub mod verify;
+pub mod render;
+pub mod search;
+pub mod query;
+pub mod log_stats;
+pub mod downsample;
+pub mod analysis;
+pub mod retention;
+pub mod compact;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub mod compression;
+pub mod rotation;
+pub mod clock_sync;
+pub mod typed;
+pub mod loggable;
+pub mod proxy;
+pub mod soak;
+pub mod convert;
+pub mod ingest;
+pub mod scheduling;
+pub mod sites;
+pub mod wire;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(target_os = "linux")]
+pub mod seqpacket;
+pub mod alloc_guard;
+#[cfg(feature = "serve")]
+pub mod serve;
 
-pub use binary_logger::{Logger, BufferHandler};
-pub use string_registry::{register_string, get_string};
-pub use log_reader::{LogReader, LogValue, LogEntry}; 
\ No newline at end of file
+pub use binary_logger::{Logger, BufferHandler, LoggerStats, LogError, HandlerPanicPolicy, HandlerStatus, RECORD_TYPE_USER_MIN, RECORD_TYPE_USER_MAX, MAX_BUFFER_SIZE};
+pub use string_registry::{register_string, get_string, LogStr};
+pub use log_reader::{LogReader, LogValue, LogEntry, EntryId, RecordHeader, TimestampCorrections, ChannelStats, DecodeWarning, Utf8Mode, InvalidUtf8, RecordDecoder, CountTable, Count};
+pub use log_merger::{LogMerger, AlignedEntry};
+pub use stream::{StreamReader, ReaderOptions};
+pub use selftest::{selftest, SelfTestReport};
+pub use sites::{sites, LogSite};
+pub use snapshot::debug_snapshot;
+pub use facade::{BinaryLog, init_global}; 
\ No newline at end of file
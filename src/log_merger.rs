@@ -0,0 +1,192 @@
+//! Merging of per-thread binary logs into a single timeline.
+//!
+//! Each thread writes its own log with its own Logger, so reconstructing what
+//! happened across threads requires interleaving several logs. The merger
+//! orders entries by `(timestamp, sequence)`: timestamps share a process-wide
+//! calibration, and the global sequence number (see
+//! `Logger::set_global_sequence`) breaks ties between records written in the
+//! same microsecond.
+//!
+//! Logs of several hosts don't share a clock. `LogMerger::aligned` moves
+//! each entry onto a reference timeline by the clock offset its log
+//! records (see `clock_sync`), and `read_aligned` tells how far off each
+//! aligned time may be.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::time::{Duration, SystemTime};
+use crate::clock_sync::ClockOffset;
+use crate::log_reader::{LogReader, LogEntry};
+
+/// An entry waiting in the merge heap, together with the reader it came from.
+struct PendingEntry {
+    entry: LogEntry,
+    source: usize,
+    /// The offset the entry was aligned by, in aligned mode
+    offset: Option<ClockOffset>,
+}
+
+impl PendingEntry {
+    /// Sort key for the merge.
+    ///
+    /// Entries without a sequence number sort after sequenced entries with the
+    /// same timestamp; the source index keeps the order deterministic.
+    fn key(&self) -> (SystemTime, u64, usize) {
+        (self.entry.timestamp, self.entry.sequence.unwrap_or(u64::MAX), self.source)
+    }
+}
+
+impl PartialEq for PendingEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PendingEntry {}
+
+impl PartialOrd for PendingEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PendingEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Merges entries from several LogReaders into one ordered stream.
+///
+/// Each reader must yield its own entries in order (which is the case for a
+/// log written by a single Logger). The merger keeps one pending entry per
+/// reader and always returns the smallest by `(timestamp, sequence)`.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{LogReader, LogMerger};
+/// # fn example(thread_a: &[u8], thread_b: &[u8]) {
+/// let mut merger = LogMerger::new(vec![
+///     LogReader::new(thread_a),
+///     LogReader::new(thread_b),
+/// ]);
+///
+/// while let Some(entry) = merger.read_entry() {
+///     println!("{:?} #{:?} {}", entry.timestamp, entry.sequence, entry.format());
+/// }
+/// # }
+/// ```
+pub struct LogMerger<'a> {
+    readers: Vec<LogReader<'a>>,
+    pending: BinaryHeap<Reverse<PendingEntry>>,
+    aligned: bool,
+}
+
+impl<'a> LogMerger<'a> {
+    /// Creates a merger over the given readers.
+    ///
+    /// The first entry of every reader is read immediately to prime the merge.
+    pub fn new(readers: Vec<LogReader<'a>>) -> Self {
+        Self::with_alignment(readers, false)
+    }
+
+    /// Creates a merger over the logs of several hosts, aligning their
+    /// entries onto the reference timeline.
+    ///
+    /// Each entry's timestamp is moved by the clock offset in effect for
+    /// it in its log (`LogReader::clock_offset`), and entries are merged by
+    /// the aligned times. Entries before their log's first clock offset
+    /// record keep their host's time; `read_aligned` tells them apart.
+    pub fn aligned(readers: Vec<LogReader<'a>>) -> Self {
+        Self::with_alignment(readers, true)
+    }
+
+    fn with_alignment(readers: Vec<LogReader<'a>>, aligned: bool) -> Self {
+        let mut merger = Self { pending: BinaryHeap::with_capacity(readers.len()), readers, aligned };
+        for source in 0..merger.readers.len() {
+            merger.read_next(source);
+        }
+        merger
+    }
+
+    /// Reads the next entry of `source` into the heap, if it has one.
+    fn read_next(&mut self, source: usize) {
+        let reader = &mut self.readers[source];
+        let Some(mut entry) = reader.read_entry() else { return };
+        let offset = reader.clock_offset().filter(|_| self.aligned);
+        if let Some(offset) = offset {
+            entry.timestamp = offset.apply(entry.timestamp);
+        }
+        self.pending.push(Reverse(PendingEntry { entry, source, offset }));
+    }
+
+    /// Returns the next entry in merged order.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(LogEntry)` - The next entry across all readers
+    /// * `None` - If every reader is exhausted
+    pub fn read_entry(&mut self) -> Option<LogEntry> {
+        self.read_aligned().map(|aligned| aligned.entry)
+    }
+
+    /// Returns the next entry in merged order, with the log it came from
+    /// and the clock offset it was aligned by.
+    pub fn read_aligned(&mut self) -> Option<AlignedEntry> {
+        let Reverse(PendingEntry { entry, source, offset }) = self.pending.pop()?;
+        self.read_next(source);
+        Some(AlignedEntry { entry, source, offset })
+    }
+}
+
+/// An entry of `LogMerger::read_aligned`.
+#[derive(Debug)]
+pub struct AlignedEntry {
+    /// The entry, with its timestamp on the reference timeline if `offset`
+    /// is set
+    pub entry: LogEntry,
+
+    /// Index of the reader the entry came from
+    pub source: usize,
+
+    /// The clock offset the entry was aligned by, or None if the merger
+    /// doesn't align or its log had no offset for it
+    pub offset: Option<ClockOffset>,
+}
+
+impl AlignedEntry {
+    /// Returns how far the entry's aligned time may be from the reference
+    /// clock's, or None if it wasn't aligned.
+    pub fn error_bound(&self) -> Option<Duration> {
+        self.offset.map(|offset| offset.error_bound())
+    }
+
+    /// Returns whether the entries' times are close enough, given their
+    /// error bounds, that either may have happened first.
+    ///
+    /// Entries of the same log are never concurrent: their order is their
+    /// log's. So are entries compared to one that wasn't aligned, as
+    /// nothing bounds its error.
+    pub fn concurrent_with(&self, other: &AlignedEntry) -> bool {
+        if self.source == other.source {
+            return false;
+        }
+        let (Some(bound), Some(other_bound)) = (self.error_bound(), other.error_bound()) else {
+            return false;
+        };
+        let apart = match self.entry.timestamp.duration_since(other.entry.timestamp) {
+            Ok(apart) => apart,
+            Err(e) => e.duration(),
+        };
+        apart <= bound + other_bound
+    }
+}
+
+impl Iterator for LogMerger<'_> {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<LogEntry> {
+        self.read_entry()
+    }
+}
@@ -1,14 +1,42 @@
 #![allow(unused)]
 
+//! Reader and utilities for decoding binary log files.
+//!
+//! This module provides the functionality to read, parse, and interpret
+//! the binary log format created by the binary_logger.
+
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use std::fmt;
+use std::fmt::{self, Write};
 use std::cmp::min;
-use crate::string_registry::get_string;
-
-/// Reader and utilities for decoding binary log files.
-///
-/// This module provides the functionality to read, parse, and interpret
-/// the binary log format created by the binary_logger.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use crate::string_registry::{decode_id, get_string, Dictionary, LogStr, WIDE_ID};
+use crate::common_strings::decode_common;
+use crate::instrumentation::{InternalEvent, is_reserved_format_id};
+use crate::codec::{Codec, RawCodec, codec_by_id};
+use crate::features::FormatFeatures;
+use crate::flags::{FlagField, decode_flags, fmt_fields};
+use crate::arg_types::{ArgKind, TypeTag, arg_kinds};
+use crate::schema::{ArgType, SchemaRegistry, describe};
+use crate::fixed::fmt_scaled;
+use crate::instant::decode_instant;
+use crate::error_chain::{decode_error_chain, fmt_chain};
+use crate::backtrace::{decode_backtrace, fmt_backtrace};
+use crate::loggable::{fmt_struct, parse_layout};
+use crate::metrics::MetricUpdate;
+use crate::level::Level;
+use crate::tags::Tags;
+use crate::render::RenderOptions;
+use crate::efficient_clock::EPOCH_MICROS;
+use crate::binary_logger::{
+    BUFFER_HEADER_SIZE, RECORD_HEADER_SIZE, RECORD_TYPE_NORMAL, RECORD_TYPE_BASE,
+    RECORD_TYPE_STREAM_HEADER, RECORD_TYPE_CHAIN, RECORD_TYPE_METRIC, RECORD_TYPE_DICTIONARY, RECORD_TYPE_TAGGED, TAGS_SIZE, RECORD_TYPE_COUNTS, COUNT_ENTRY_SIZE, RECORD_TYPE_METADATA, RECORD_TYPE_RETENTION, RECORD_TYPE_PADDING, RECORD_TYPE_UNTIMED, RECORD_TYPE_TYPED, RECORD_TYPE_USER_MIN, STREAM_MAGIC, STREAM_HEADER_PAYLOAD_SIZE, STREAM_HEADER_BASE_SIZE, FLAG_SEQUENCE, FLAG_CHANNEL,
+    FLAG_SAME_FORMAT, FLAG_DELTA_TIME, FLAG_TRUNCATED, RECORD_TYPE_CLOCK_OFFSET, RECORD_TYPE_TAGGED_TYPED, LONG_LENGTH,
+};
+use crate::codec::read_varint;
+use crate::clock_sync::ClockOffset;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::compression::{has_frames, inflate_readable};
 
 /// A value extracted from a binary log entry.
 /// 
@@ -20,28 +48,106 @@ use crate::string_registry::get_string;
 pub enum LogValue {
     /// A 32-bit signed integer
     Integer(i32),
+
+    /// A 64-bit signed integer, logged with its type tag (see
+    /// `Logger::set_type_tags`)
+    Integer64(i64),
+
+    /// An unsigned integer of 32 or 64 bits, logged with its type tag
+    Unsigned(u64),
     
     /// A boolean value
     Boolean(bool),
     
     /// A 64-bit floating point number
     Float(f64),
+
+    /// A 32-bit floating point number
+    Float32(f32),
+
+    /// A half-precision floating point number, as its IEEE 754 bits (see
+    /// `as_f64`, and `as_f16` with the `f16` feature)
+    Float16(u16),
     
     /// A UTF-8 string
     String(String),
+
+    /// A byte string logged from a `&[u8]` or `Vec<u8>`, rendered in hex
+    Bytes(Vec<u8>),
     
     /// Raw binary data that couldn't be interpreted
     Unknown(Vec<u8>),
+
+    /// Named fields of a `flags::Flags` argument
+    Flags(Vec<FlagField>),
+
+    /// A `fixed::Fixed` decimal: `value * 10^-scale`
+    Fixed { value: i64, scale: u32 },
+
+    /// A point in time logged with `instant::LogInstant`
+    Instant(SystemTime),
+
+    /// The messages of an error and its causes, outermost first, logged
+    /// with `log_error!` or `error_chain::ErrorChain`
+    ErrorChain(Vec<String>),
+
+    /// The frames of a backtrace, innermost first, logged with
+    /// `backtrace::LogBacktrace`
+    Backtrace(Vec<String>),
+
+    /// A struct logged field by field (`loggable::Loggable`), with its
+    /// fields in order; tuple fields are named by index
+    Struct { name: String, fields: Vec<(String, LogValue)> },
+}
+
+impl LogValue {
+    /// Returns the value as a number, for integers, floats and `Fixed`
+    /// decimals; `None` for other kinds.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            LogValue::Integer(i) => Some(*i as f64),
+            LogValue::Integer64(i) => Some(*i as f64),
+            LogValue::Unsigned(u) => Some(*u as f64),
+            LogValue::Float(fl) => Some(*fl),
+            LogValue::Float32(fl) => Some(*fl as f64),
+            LogValue::Float16(bits) => Some(f16_to_f32(*bits) as f64),
+            LogValue::Fixed { value, scale } => Some(*value as f64 / 10f64.powi(*scale as i32)),
+            _ => None,
+        }
+    }
+
+    /// Returns a half-precision value as a `half::f16` (`f16` feature).
+    #[cfg(feature = "f16")]
+    pub fn as_f16(&self) -> Option<half::f16> {
+        match self {
+            LogValue::Float16(bits) => Some(half::f16::from_bits(*bits)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for LogValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            LogValue::Integer(i) => write!(f, "{}", i),
+            LogValue::Integer(i) => f.write_str(itoa::Buffer::new().format(*i)),
+            LogValue::Integer64(i) => f.write_str(itoa::Buffer::new().format(*i)),
+            LogValue::Unsigned(u) => f.write_str(itoa::Buffer::new().format(*u)),
             LogValue::Boolean(b) => write!(f, "{}", b),
-            LogValue::Float(fl) => write!(f, "{}", fl),
+            LogValue::Float(fl) => write_float(f, *fl, *fl),
+            LogValue::Float32(fl) => write_float(f, *fl, *fl as f64),
+            LogValue::Float16(bits) => f.write_str(&shortest_f16(*bits)),
             LogValue::String(s) => write!(f, "{}", s),
+            LogValue::Bytes(bytes) => fmt_hex(bytes, f),
             LogValue::Unknown(bytes) => write!(f, "{:?}", bytes),
+            LogValue::Flags(fields) => fmt_fields(fields, f),
+            LogValue::Fixed { value, scale } => fmt_scaled(*value, *scale, f),
+            LogValue::Instant(time) => {
+                let micros = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros();
+                write!(f, "{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+            }
+            LogValue::ErrorChain(messages) => fmt_chain(messages, f),
+            LogValue::Backtrace(frames) => fmt_backtrace(frames, f),
+            LogValue::Struct { name, fields } => fmt_struct(name, fields, f),
         }
     }
 }
@@ -80,16 +186,54 @@ pub struct LogEntry {
     pub timestamp: SystemTime,
     
     /// ID of the format string in the string registry
-    pub format_id: u16,
+    pub format_id: u32,
     
     /// The format string, if available from the string registry
-    pub format_string: Option<&'static str>,
+    pub format_string: Option<LogStr>,
     
     /// Extracted parameter values
     pub parameters: Vec<LogValue>,
     
     /// Raw bytes of the parameter values (for advanced usage)
     pub raw_values: Vec<u8>,
+
+    /// Global sequence number, if the writer had sequencing enabled
+    pub sequence: Option<u64>,
+
+    /// ID of the channel name in the string registry, or 0 for the default channel
+    pub channel: u32,
+
+    /// Severity, for records written with `log_record_at!`
+    pub level: Option<Level>,
+
+    /// Whether arguments were cut to the writer's capture limits (see
+    /// `arg_types::CaptureLimits`)
+    pub truncated: bool,
+
+    /// Whether the record was written without a time (see
+    /// `Logger::set_timestamps`), and has that of the timed record before it
+    pub untimed: bool,
+
+    /// User tags, for records written with `log_tagged!`
+    pub tags: Tags,
+
+    /// Physical location of the record, usable with `LogReader::get`
+    pub id: EntryId,
+
+    /// Record type of an application-defined record, whose parameters were
+    /// produced by a `RecordDecoder`; `None` for log entries
+    pub custom_type: Option<u8>,
+
+    /// The update of a metric record, whose format string is the metric
+    /// key; `None` for log entries. See `LogReader::set_metrics`
+    pub metric: Option<MetricUpdate>,
+
+    /// Whether a string argument wasn't valid UTF-8 and was read with
+    /// replacement characters (see `LogReader::set_utf8_mode`)
+    pub lossy: bool,
+
+    /// The channel's name, from the log's dictionary or the string registry
+    channel_string: Option<LogStr>,
 }
 
 impl LogEntry {
@@ -124,7 +268,44 @@ impl LogEntry {
     /// ```
     #[allow(unused)]
     pub fn format(&self) -> String {
-        if let Some(fmt_str) = self.format_string {
+        self.format_with(&RenderOptions::default())
+    }
+
+    /// Formats the log entry like `format`, writing instant arguments (and
+    /// the timestamp of entries without a format string) according to
+    /// `options`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogEntry;
+    /// # use binary_logger::render::{RenderOptions, TimeFormat, TimeZone};
+    /// # fn example(entry: &LogEntry) {
+    /// let options = RenderOptions { time_zone: TimeZone::Local, time_format: TimeFormat::Rfc3339, ..Default::default() };
+    /// println!("{}", entry.format_with(&options));
+    /// # }
+    /// ```
+    pub fn format_with(&self, options: &RenderOptions) -> String {
+        // Arguments are written straight into the message, the common
+        // types without going through `fmt`; writing to a String can't fail
+        let render = |result: &mut String, param: &LogValue| {
+            match param {
+                LogValue::Integer(i) => result.push_str(itoa::Buffer::new().format(*i)),
+                LogValue::Float(fl) => {
+                    let _ = write_float(result, *fl, *fl);
+                }
+                LogValue::Float32(fl) => {
+                    let _ = write_float(result, *fl, *fl as f64);
+                }
+                LogValue::String(s) => result.push_str(s),
+                LogValue::Instant(time) => result.push_str(&options.format_time(*time)),
+                param => {
+                    let _ = write!(result, "{}", param);
+                }
+            }
+        };
+
+        if let Some(fmt_str) = &self.format_string {
             // Simple formatting implementation
             let mut result = String::new();
             let mut fmt_iter = fmt_str.chars().peekable();
@@ -135,7 +316,7 @@ impl LogEntry {
                     // Found a {} placeholder
                     fmt_iter.next(); // Skip the closing }
                     if param_idx < self.parameters.len() {
-                        result.push_str(&self.parameters[param_idx].to_string());
+                        render(&mut result, &self.parameters[param_idx]);
                         param_idx += 1;
                     } else {
                         result.push_str("{MISSING}");
@@ -144,17 +325,46 @@ impl LogEntry {
                     result.push(c);
                 }
             }
+
+            // Backtraces without a placeholder follow the message
+            for param in &self.parameters[param_idx.min(self.parameters.len())..] {
+                if let LogValue::Backtrace(frames) = param {
+                    if !frames.is_empty() {
+                        result.push('\n');
+                        render(&mut result, param);
+                    }
+                }
+            }
             
             result
         } else {
             // Fallback if format string is not available
             format!("[{}] Format ID: {}, Parameters: {:?}", 
-                self.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+                options.format_time(self.timestamp),
                 self.format_id,
                 self.parameters)
         }
     }
 
+    /// Returns the name of the entry's channel.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some(&str)` - The channel name from the log's dictionary or the
+    ///   string registry
+    /// * `None` - For the default channel, or if the name is not registered
+    pub fn channel_name(&self) -> Option<&str> {
+        self.channel_string.as_deref()
+    }
+
+    /// Returns true if this entry is an internal logger event.
+    /// 
+    /// Internal events are written by loggers with instrumentation enabled
+    /// and use format IDs from the reserved range.
+    pub fn is_internal(&self) -> bool {
+        is_reserved_format_id(self.format_id)
+    }
+
     /// Returns a detailed representation of the log entry for debugging.
     /// 
     /// This method provides a comprehensive multiline view of the log entry,
@@ -177,7 +387,7 @@ impl LogEntry {
         
         // Format ID and string
         result.push_str(&format!("Format ID: {}\n", self.format_id));
-        if let Some(fmt_str) = self.format_string {
+        if let Some(fmt_str) = &self.format_string {
             result.push_str(&format!("Format string: \"{}\"\n", fmt_str));
         } else {
             result.push_str("Format string: <unknown>\n");
@@ -203,6 +413,272 @@ impl LogEntry {
     }
 }
 
+/// Header fields of a record, read without decoding its payload, see
+/// `LogReader::skip_while_header`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordHeader {
+    /// When the record was written
+    pub timestamp: SystemTime,
+
+    /// ID of the format string in the string registry
+    pub format_id: u32,
+
+    /// ID of the channel name in the string registry, or 0 for the default channel
+    pub channel: u32,
+
+    /// Global sequence number, if the writer had sequencing enabled
+    pub sequence: Option<u64>,
+
+    /// Severity, for records written with `log_record_at!`
+    pub level: Option<Level>,
+
+    /// Whether arguments were cut to the writer's capture limits
+    pub truncated: bool,
+
+    /// Whether the record was written without a time
+    pub untimed: bool,
+
+    /// User tags, for records written with `log_tagged!`
+    pub tags: Tags,
+
+    /// Record type of an application-defined record, `None` for log entries
+    pub custom_type: Option<u8>,
+
+    /// Length of the encoded arguments
+    pub payload_len: usize,
+}
+
+/// The counts of one buffer's count table, see `LogReader::set_count_tables`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountTable {
+    /// Time the table was written, when its buffer was switched out
+    pub timestamp: SystemTime,
+
+    /// Counts in the buffer, one per counter
+    pub counts: Vec<Count>,
+}
+
+/// The count of one counter in a `CountTable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Count {
+    /// Format ID of the counter's name
+    pub format_id: u32,
+
+    /// The counter's name, from the log's dictionary or the string registry
+    pub name: Option<LogStr>,
+
+    /// Events counted
+    pub count: u32,
+}
+
+/// A record read by `LogReader::next_record`, its payload not yet decoded.
+struct Record<'a> {
+    header: RecordHeader,
+    id: EntryId,
+    payload: &'a [u8],
+    type_tags: &'a [u8],
+    metric: Option<MetricUpdate>,
+}
+
+/// Stable identifier of an entry: the physical location of its record.
+/// 
+/// IDs stay valid as long as the data doesn't change, so tools can keep them
+/// as bookmarks and jump back with `LogReader::get`. They order entries by
+/// position, which within one file is the order they were written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntryId {
+    /// Index of the file, as set with `LogReader::set_file_index`
+    pub file: u32,
+
+    /// Byte position of the record's buffer in the file
+    pub buffer: u64,
+
+    /// Byte position of the record within its buffer
+    pub offset: u32,
+}
+
+impl fmt::Display for EntryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.buffer, self.offset)
+    }
+}
+
+/// Parses the `file:buffer:offset` form written by `Display`.
+impl std::str::FromStr for EntryId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid entry ID {:?}, expected file:buffer:offset", s);
+        let mut parts = s.split(':');
+        let mut next = || parts.next().ok_or_else(invalid);
+        let (file, buffer, offset) = (next()?, next()?, next()?);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(EntryId {
+            file: file.parse().map_err(|_| invalid())?,
+            buffer: buffer.parse().map_err(|_| invalid())?,
+            offset: offset.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Read position and the timestamp state needed to decode from it.
+#[derive(Clone)]
+pub(crate) struct Cursor {
+    pos: usize,
+    buffer_start: usize,
+    buffer_end: usize,
+    base_timestamp: Option<u64>,
+    last_relative: u16,
+    epoch: u64,
+    codec: Option<&'static dyn Codec>,
+    format_features: Option<FormatFeatures>,
+    last_timestamp: Option<SystemTime>,
+    last_format_id: u32,
+    dictionary: Option<Arc<Dictionary>>,
+    clock_offset: Option<ClockOffset>,
+}
+
+/// Corrections made by a LogReader in monotonic mode.
+/// 
+/// See `LogReader::set_monotonic`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimestampCorrections {
+    /// Number of entries whose timestamp was raised to keep time monotonic
+    pub count: u64,
+
+    /// Largest single correction applied
+    pub max_correction: Duration,
+}
+
+/// Per-channel statistics collected by a LogReader.
+/// 
+/// See `LogReader::channel_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// Number of records read on the channel
+    pub records: u64,
+
+    /// Total payload bytes of those records
+    pub payload_bytes: u64,
+}
+
+/// An argument a LogReader couldn't read as its writer or schema says.
+/// 
+/// See `LogReader::set_decode_warnings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeWarning {
+    /// The entry with the argument
+    pub id: EntryId,
+
+    /// Format ID of the entry
+    pub format_id: u32,
+
+    /// Index of the argument, or `None` for the entry's arguments as a
+    /// whole
+    pub argument: Option<usize>,
+
+    /// What the argument was and how it was read
+    pub message: String,
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} format {}", self.id, self.format_id)?;
+        if let Some(argument) = self.argument {
+            write!(f, " argument {}", argument)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// How a LogReader reads string arguments that aren't valid UTF-8.
+/// 
+/// See `LogReader::set_utf8_mode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Utf8Mode {
+    /// Invalid sequences are replaced with U+FFFD and the entry is flagged
+    /// `lossy`
+    #[default]
+    Lossy,
+
+    /// An entry with an invalid string stops reading with an `InvalidUtf8`
+    /// error
+    Strict,
+}
+
+/// A string argument that isn't valid UTF-8, found in strict mode.
+/// 
+/// See `LogReader::set_utf8_mode`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// The entry with the argument
+    pub id: EntryId,
+
+    /// Format ID of the entry
+    pub format_id: u32,
+
+    /// Index of the argument
+    pub argument: usize,
+
+    /// Byte position of the first invalid sequence in the argument
+    pub offset: usize,
+}
+
+impl fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} format {} argument {}: invalid UTF-8 at byte {}", self.id, self.format_id, self.argument, self.offset)
+    }
+}
+
+impl std::error::Error for InvalidUtf8 {}
+
+/// Decoder for an application-defined record type.
+/// 
+/// Applications can embed their own binary records, such as market-data
+/// snapshots, in a log stream using record types from `RECORD_TYPE_USER_MIN`
+/// to `RECORD_TYPE_USER_MAX`. A decoder registered with
+/// `LogReader::register_decoder` turns their payloads into values, and the
+/// records are returned as entries in stream order with the other entries.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # use binary_logger::{LogReader, LogValue, RecordDecoder};
+/// /// Snapshots of `[price f64][quantity i32]`
+/// struct QuoteDecoder;
+/// 
+/// impl RecordDecoder for QuoteDecoder {
+///     fn decode(&self, _format_id: u32, payload: &[u8]) -> Vec<LogValue> {
+///         if payload.len() < 12 {
+///             return vec![LogValue::Unknown(payload.to_vec())];
+///         }
+///         let price = f64::from_le_bytes(payload[0..8].try_into().unwrap());
+///         let quantity = i32::from_le_bytes(payload[8..12].try_into().unwrap());
+///         vec![LogValue::Float(price), LogValue::Integer(quantity)]
+///     }
+/// }
+/// 
+/// # fn example(data: &[u8]) {
+/// let mut reader = LogReader::new(data);
+/// reader.register_decoder(0x80, Box::new(QuoteDecoder));
+/// while let Some(entry) = reader.read_entry() {
+///     match entry.custom_type {
+///         Some(_) => println!("quote {:?}", entry.parameters),
+///         None => println!("{}", entry.format()),
+///     }
+/// }
+/// # }
+/// ```
+pub trait RecordDecoder {
+    /// Decodes the payload of a record into values.
+    /// 
+    /// `format_id` is the format ID field of the record header, which the
+    /// writer may use to distinguish variants of the record.
+    fn decode(&self, format_id: u32, payload: &[u8]) -> Vec<LogValue>;
+}
+
 /// Reader for decoding binary log files.
 /// 
 /// LogReader provides sequential access to log entries in a binary log file.
@@ -211,15 +687,39 @@ impl LogEntry {
 /// 
 /// # How It Works
 /// 
-/// The reader processes two types of records:
+/// The reader processes three types of records:
 /// 
 /// 1. Base timestamp records (type=1):
-///    * These establish a reference timestamp
+///    * These establish a reference timestamp (the first 8 payload bytes,
+///      in microseconds since the UNIX epoch)
 ///    * They reset the timestamp base for relative calculations
+///    * A base record with format ID 0 carries no log entry of its own
 /// 
 /// 2. Normal records (type=0):
 ///    * These use 16-bit relative timestamps for efficiency
 ///    * Timestamps are calculated relative to the last base timestamp
+///    * A relative value lower than its predecessor's marks a wrap, which
+///      advances the time by one epoch of 65536us (see
+///      `Logger::set_wrap_epochs`)
+///    * Records with `FLAG_DELTA_TIME` instead store the microseconds since
+///      the previous record, which are accumulated from the last base (see
+///      `Logger::set_delta_timestamps`)
+/// 
+/// 3. Stream header records (type=2):
+///    * These name the codec used for record arguments
+///    * Streams without a header are decoded with `RawCodec`
+/// 
+/// 4. Audit chain records (type=3) are skipped; see `audit::verify_chain`
+/// 
+/// 5. Metric records (type=4) carry relative timestamps like normal
+///    records. They are skipped unless enabled with `set_metrics`
+/// 
+/// 6. Application-defined records (types `RECORD_TYPE_USER_MIN` and up)
+///    carry relative timestamps like normal records. They are decoded by the
+///    `RecordDecoder` registered for their type, or skipped if there is none
+/// 
+/// Input may be a concatenation of buffers as produced by a `BufferHandler`;
+/// each buffer's 8-byte size header is used to step to the next one.
 /// 
 /// # Examples
 /// 
@@ -248,9 +748,48 @@ impl LogEntry {
 #[allow(unused)]
 pub struct LogReader<'a> {
     data: &'a [u8],
+    file: u32,
     pos: usize,
+    buffer_start: usize,
+    buffer_end: usize,
     base_timestamp: Option<u64>,
     last_relative: u16,
+    epoch: u64,
+    codec: Option<&'static dyn Codec>,
+    format_features: Option<FormatFeatures>,
+    dictionary: Option<Arc<Dictionary>>,
+    monotonic: bool,
+    last_timestamp: Option<SystemTime>,
+    last_format_id: u32,
+    corrections: TimestampCorrections,
+    channel_filter: Option<Vec<u32>>,
+    tag_filter: Option<Tags>,
+    level_filter: Option<Level>,
+    segment_filter: Option<String>,
+    /// Name of the segment the last record read is in
+    segment: Option<String>,
+    channel_stats: BTreeMap<u32, ChannelStats>,
+    record_filter: Option<Box<dyn FnMut(u32, u32) -> bool + 'a>>,
+    decoders: BTreeMap<u8, Box<dyn RecordDecoder + 'a>>,
+    metrics: bool,
+    counters: BTreeMap<u32, u64>,
+    count_tables: Option<Vec<CountTable>>,
+    clock_offset: Option<ClockOffset>,
+    schema: Option<Arc<SchemaRegistry>>,
+    decode_warnings: Option<Vec<DecodeWarning>>,
+    utf8_mode: Utf8Mode,
+    utf8_error: Option<InvalidUtf8>,
+
+    /// End of the last count table added to `counters`, so tables read
+    /// again after a seek aren't counted twice
+    counted_until: usize,
+
+    /// Cursor at the start of every buffer seen, for `get`
+    checkpoints: BTreeMap<usize, Cursor>,
+
+    /// The log with its compressed frames decompressed, which `data`
+    /// points into, if it had any (see `compression`)
+    inflated: Option<Vec<u8>>,
 }
 
 impl<'a> LogReader<'a> {
@@ -284,158 +823,665 @@ impl<'a> LogReader<'a> {
     /// ```
     #[allow(unused)]
     pub fn new(data: &'a [u8]) -> Self {
-        // Skip the buffer header (8 bytes) if present
-        let pos = if data.len() >= 8 { 8 } else { 0 };
-        
+        let mut reader = Self::blank(data);
+        reader.enter_buffer();
+        reader
+    }
+
+    /// Creates a reader for the log in `data` starting at the buffer at
+    /// byte `offset`, for a stream written with a dictionary channel (see
+    /// `Logger::set_dictionary_channel`).
+    /// 
+    /// Of the buffers before `offset`, only the metadata buffers are
+    /// decoded, for the stream header and the strings they hold; data
+    /// buffers are skipped using their size headers. Data buffers of such
+    /// streams start with a time base, so entries from `offset` on are
+    /// read as they would be from the start of the log. `offset` must be
+    /// the start of a buffer, such as the end of the log when it was last
+    /// read for a live tail.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # fn example(log: &[u8], tail_from: usize) {
+    /// let mut reader = LogReader::attach(log, tail_from);
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{}", entry.format());
+    /// }
+    /// # }
+    /// ```
+    pub fn attach(data: &'a [u8], offset: usize) -> Self {
+        let mut state = Self::blank(data).cursor();
+        let mut start = 0;
+        while start + BUFFER_HEADER_SIZE <= offset.min(data.len()) {
+            let mut size_bytes = [0u8; 8];
+            size_bytes.copy_from_slice(&data[start..start + BUFFER_HEADER_SIZE]);
+            let size = u64::from_le_bytes(size_bytes) as usize;
+            if size < BUFFER_HEADER_SIZE || size > data.len() - start {
+                break;
+            }
+            let buffer = &data[start..start + size];
+            if is_metadata_buffer(buffer) {
+                let mut metadata = LogReader::new(buffer);
+                metadata.continue_from(&state);
+                while metadata.next_record().is_some() {}
+                state = metadata.cursor();
+            }
+            start += size;
+        }
+
+        let mut reader = Self::blank(data);
+        reader.continue_from(&state);
+        reader.pos = offset.min(data.len());
+        reader.buffer_end = reader.pos;
+        reader
+    }
+
+    /// Creates a reader continuing at a position captured with `cursor()`,
+    /// for data decoded an entry at a time.
+    pub(crate) fn at(data: &'a [u8], cursor: Cursor) -> Self {
+        let mut reader = Self::blank(data);
+        reader.set_cursor(cursor);
+        reader
+    }
+
+    /// Creates a reader with default settings, before any buffer header.
+    fn blank(data: &'a [u8]) -> Self {
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        let inflated = has_frames(data).then(|| inflate_readable(data));
+        // Without a compression feature, frames read as damaged data
+        #[cfg(not(any(feature = "lz4", feature = "zstd")))]
+        let inflated: Option<Vec<u8>> = None;
+        let data = match &inflated {
+            // SAFETY: the reader owns the inflated log and never changes
+            // it, its heap allocation doesn't move with the reader, and no
+            // slice of `data` is kept past the reader: entries copy what
+            // they hold
+            Some(plain) => unsafe { std::slice::from_raw_parts(plain.as_ptr(), plain.len()) },
+            None => data,
+        };
         Self {
             data,
-            pos,
+            file: 0,
+            pos: 0,
+            buffer_start: 0,
+            buffer_end: 0,
             base_timestamp: None,
             last_relative: 0,
+            epoch: 0,
+            codec: Some(&RawCodec),
+            format_features: None,
+            dictionary: None,
+            monotonic: false,
+            last_timestamp: None,
+            last_format_id: 0,
+            corrections: TimestampCorrections::default(),
+            channel_filter: None,
+            tag_filter: None,
+            level_filter: None,
+            segment_filter: None,
+            segment: None,
+            channel_stats: BTreeMap::new(),
+            record_filter: None,
+            decoders: BTreeMap::new(),
+            metrics: false,
+            counters: BTreeMap::new(),
+            count_tables: None,
+            clock_offset: None,
+            schema: None,
+            decode_warnings: None,
+            utf8_mode: Utf8Mode::default(),
+            utf8_error: None,
+            counted_until: 0,
+            checkpoints: BTreeMap::new(),
+            inflated,
         }
     }
 
-    /// Reads a 16-bit unsigned integer from the current position.
+    /// Enables or disables monotonic timestamps.
     /// 
-    /// # Returns
-    /// Some(u16) if there are enough bytes remaining, None otherwise
-    #[allow(unused)]
-    fn read_u16(&mut self) -> Option<u16> {
-        if self.pos + 2 <= self.data.len() {
-            let value = u16::from_le_bytes([self.data[self.pos], self.data[self.pos + 1]]);
-            self.pos += 2;
-            Some(value)
-        } else {
-            None
-        }
+    /// Reconstructed timestamps can go backwards, for example when logs from
+    /// different runs are concatenated or a base record is damaged. In
+    /// monotonic mode an entry that would be earlier than its predecessor is
+    /// given the predecessor's timestamp instead, so consumers that require
+    /// ordered time series can use the output directly. The corrections are
+    /// counted in `timestamp_corrections()`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_monotonic(true);
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{:?} {}", entry.timestamp, entry.format());
+    /// }
+    /// let corrections = reader.timestamp_corrections();
+    /// if corrections.count > 0 {
+    ///     eprintln!("{} timestamps clamped, worst by {:?}", corrections.count, corrections.max_correction);
+    /// }
+    /// # }
+    /// ```
+    pub fn set_monotonic(&mut self, enabled: bool) {
+        self.monotonic = enabled;
     }
 
-    /// Reads a 64-bit unsigned integer from the current position.
+    /// Sets the file index reported in the `EntryId`s of this reader's
+    /// entries (0 by default).
     /// 
-    /// # Returns
-    /// Some(u64) if there are enough bytes remaining, None otherwise
-    #[allow(unused)]
-    fn read_u64(&mut self) -> Option<u64> {
-        if self.pos + 8 <= self.data.len() {
-            let mut bytes = [0u8; 8];
-            bytes.copy_from_slice(&self.data[self.pos..self.pos + 8]);
-            self.pos += 8;
-            Some(u64::from_le_bytes(bytes))
-        } else {
-            None
-        }
+    /// Give every reader a different index when entries from several files
+    /// are combined, for example with `LogMerger`, so their IDs stay unique.
+    pub fn set_file_index(&mut self, file: u32) {
+        self.file = file;
     }
 
-    /// Reads a slice of bytes from the current position.
+    /// Returns the corrections made so far in monotonic mode.
+    pub fn timestamp_corrections(&self) -> TimestampCorrections {
+        self.corrections
+    }
+
+    /// Restricts the entries returned to the given channels.
     /// 
-    /// # Arguments
-    /// * `len` - Number of bytes to read
+    /// Channel IDs are the registry IDs of the channel names; 0 selects
+    /// records written without a channel. `None` returns all entries.
+    /// Records on other channels are still counted in `channel_stats()`.
     /// 
-    /// # Returns
-    /// Some(&[u8]) if there are enough bytes remaining, None otherwise
-    #[allow(unused)]
-    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
-        if self.pos + len <= self.data.len() {
-            let slice = &self.data[self.pos..self.pos + len];
-            self.pos += len;
-            Some(slice)
-        } else {
-            None
-        }
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{LogReader, register_string};
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_channel_filter(Some(&[register_string("audit")]));
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{}", entry.format());
+    /// }
+    /// # }
+    /// ```
+    pub fn set_channel_filter(&mut self, channels: Option<&[u32]>) {
+        self.channel_filter = channels.map(|channels| channels.to_vec());
     }
 
-    /// Extracts parameter values from the payload.
+    /// Restricts the entries returned to records with at least one of
+    /// `tags` (see `tags`). `None` returns all entries.
     /// 
-    /// # Arguments
-    /// * `payload` - The raw payload bytes
+    /// Records are skipped on their tag mask, before their arguments are
+    /// decoded.
+    pub fn set_tag_filter(&mut self, tags: Option<Tags>) {
+        self.tag_filter = tags;
+    }
+
+    /// Restricts the entries returned to records at `level` or above.
+    /// `None` returns all entries.
     /// 
-    /// # Returns
-    /// A vector of extracted LogValue parameters
-    #[allow(unused)]
-    fn extract_parameters(&self, payload: &[u8]) -> Vec<LogValue> {
-        let mut parameters = Vec::new();
-        
-        // Debug the raw payload
-        println!("Extracting parameters from payload: {:?}", payload);
-        
-        if payload.is_empty() {
-            println!("Empty payload, no parameters to extract");
-            return parameters;
-        }
-        
-        // First byte is the argument count
-        let arg_count = payload[0] as usize;
-        println!("Argument count from payload: {}", arg_count);
-        
-        if arg_count == 0 {
-            return parameters;
-        }
-        
-        let mut pos = 1; // Start after the argument count
-        
-        for i in 0..arg_count {
-            // Ensure we have enough bytes for the argument size (4 bytes)
-            if pos + 4 > payload.len() {
-                println!("Not enough data for argument {} size at position {}", i, pos);
-                break;
+    /// Records without a level, from `log_record!`, are left out too.
+    /// Records are skipped on the level in their header, before their
+    /// arguments are decoded.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # use binary_logger::level::Level;
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_level_filter(Some(Level::Warn));
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{} {}", entry.level.unwrap(), entry.format());
+    /// }
+    /// # }
+    /// ```
+    pub fn set_level_filter(&mut self, level: Option<Level>) {
+        self.level_filter = level;
+    }
+
+    /// Restricts the entries returned to the records of the segments named
+    /// `name` (see `Logger::begin_segment`), with their boundary records, or
+    /// with `None` returns entries in and out of segments.
+    /// 
+    /// Segments are followed as the log is read in order, whatever the
+    /// other filters; a reader attached or seeked to the middle of a segment
+    /// only knows it from its next boundary record.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_segment_filter(Some("deploy v1.2.3"));
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{}", entry.format());
+    /// }
+    /// # }
+    /// ```
+    pub fn set_segment_filter(&mut self, name: Option<&str>) {
+        self.segment_filter = name.map(str::to_string);
+    }
+
+    /// Returns the name of the segment the last record read is in, if any.
+    pub fn segment(&self) -> Option<&str> {
+        self.segment.as_deref()
+    }
+
+    /// Restricts the entries returned to records accepted by `filter`.
+    /// 
+    /// The filter is called with the format ID and channel ID of each record.
+    /// Rejected records are skipped before their arguments are decoded, so a
+    /// selective filter makes reading much cheaper. `None` returns all
+    /// entries.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{LogReader, register_string};
+    /// # fn example(data: &[u8]) {
+    /// let wanted = register_string("Temperature: {} C");
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_record_filter(Some(Box::new(move |format_id, _channel| format_id == wanted)));
+    /// # }
+    /// ```
+    pub fn set_record_filter(&mut self, filter: Option<Box<dyn FnMut(u32, u32) -> bool + 'a>>) {
+        self.record_filter = filter;
+    }
+
+    /// Registers the decoder for an application-defined record type.
+    /// 
+    /// Records of that type are then returned as entries with `custom_type`
+    /// set and parameters produced by `decoder`. Registering a type again
+    /// replaces its decoder.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `record_type` is below `RECORD_TYPE_USER_MIN`, since lower
+    /// types belong to the library.
+    pub fn register_decoder(&mut self, record_type: u8, decoder: Box<dyn RecordDecoder + 'a>) {
+        assert!(record_type >= RECORD_TYPE_USER_MIN, "Record type {} is reserved for the library", record_type);
+        self.decoders.insert(record_type, decoder);
+    }
+
+    /// Returns metric records as entries, with `metric` set and the metric
+    /// key as their format string (off by default).
+    /// 
+    /// Metric records are written by `metrics::BinaryRecorder`; see
+    /// `metrics::collect_series` to aggregate them into time series.
+    pub fn set_metrics(&mut self, enabled: bool) {
+        self.metrics = enabled;
+    }
+
+    /// Returns record counts and payload sizes per channel ID for the
+    /// records read so far.
+    pub fn channel_stats(&self) -> &BTreeMap<u32, ChannelStats> {
+        &self.channel_stats
+    }
+
+    /// Returns the totals of the counters counted with `log_count!`, per
+    /// format ID, from the count tables read so far.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{LogReader, get_string};
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// while reader.read_entry().is_some() {}
+    /// for (&id, &total) in reader.counters() {
+    ///     println!("{}: {}", get_string(id).unwrap_or("?"), total);
+    /// }
+    /// # }
+    /// ```
+    pub fn counters(&self) -> &BTreeMap<u32, u64> {
+        &self.counters
+    }
+
+    /// Keeps the count tables read from now on, with their timestamps, for
+    /// `take_count_tables` (off by default).
+    /// 
+    /// `counters` only has the totals; `log_stats::LogStats` uses the tables
+    /// to break the totals down by buffer.
+    pub fn set_count_tables(&mut self, enabled: bool) {
+        self.count_tables = enabled.then(Vec::new);
+    }
+
+    /// Returns the count tables kept since the last call, in stream order.
+    pub fn take_count_tables(&mut self) -> Vec<CountTable> {
+        self.count_tables.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Returns the offset of the writer's clock from the reference clock
+    /// for the entries read last, from the last clock offset record before
+    /// them (see `Logger::set_clock_offset`), if any.
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        self.clock_offset
+    }
+
+    /// Reads the arguments of the format strings in `schema` as their
+    /// schema types, or stops doing so with `None`.
+    /// 
+    /// An argument that doesn't match its type is coerced where that keeps
+    /// its value: an integer where the schema has a float is read as a
+    /// float, and bytes that decode as nothing are read as a hex string
+    /// (`0x0a1b`) rather than `LogValue::Unknown`. Other arguments are read
+    /// as written. Either way the mismatch is reported as a decode warning.
+    pub fn set_schema_registry(&mut self, schema: Option<Arc<SchemaRegistry>>) {
+        self.schema = schema;
+    }
+
+    /// Keeps a warning for every argument read from now on that doesn't
+    /// match its writer's kind or its schema type, for
+    /// `take_decode_warnings` (off by default).
+    /// 
+    /// Arguments whose typed encoding is damaged are read as hex strings;
+    /// arguments of unknown type that decode as nothing are still read as
+    /// `LogValue::Unknown`, but warned about.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_decode_warnings(true);
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{}", entry.format());
+    /// }
+    /// for warning in reader.take_decode_warnings() {
+    ///     eprintln!("{}", warning);
+    /// }
+    /// # }
+    /// ```
+    pub fn set_decode_warnings(&mut self, enabled: bool) {
+        self.decode_warnings = enabled.then(Vec::new);
+    }
+
+    /// Returns the decode warnings kept since the last call, in stream
+    /// order.
+    pub fn take_decode_warnings(&mut self) -> Vec<DecodeWarning> {
+        self.decode_warnings.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Sets how string arguments that aren't valid UTF-8 are read
+    /// (`Utf8Mode::Lossy` by default).
+    /// 
+    /// This applies to arguments known to be strings, from their type tag,
+    /// their writer or the schema. Lossy mode replaces invalid sequences
+    /// with U+FFFD, flags the entry `lossy` and, with decode warnings on,
+    /// warns about the argument. In strict mode `read_entry` returns `None`
+    /// at such an entry, and `take_utf8_error` says where the invalid bytes
+    /// are; reading again goes on after the entry.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::{LogReader, Utf8Mode};
+    /// # fn example(data: &[u8]) -> Result<(), binary_logger::InvalidUtf8> {
+    /// let mut reader = LogReader::new(data);
+    /// reader.set_utf8_mode(Utf8Mode::Strict);
+    /// while let Some(entry) = reader.read_entry() {
+    ///     println!("{}", entry.format());
+    /// }
+    /// if let Some(error) = reader.take_utf8_error() {
+    ///     return Err(error);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_utf8_mode(&mut self, mode: Utf8Mode) {
+        self.utf8_mode = mode;
+    }
+
+    /// Returns the invalid string that stopped reading in strict mode, if
+    /// any since the last call.
+    pub fn take_utf8_error(&mut self) -> Option<InvalidUtf8> {
+        self.utf8_error.take()
+    }
+
+    /// Returns the codec the stream's record arguments are decoded with.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some(&dyn Codec)` - The codec named by the last stream header, or
+    ///   `RawCodec` if none has been read
+    /// * `None` - If the stream header names a codec this build doesn't know
+    pub fn codec(&self) -> Option<&'static dyn Codec> {
+        self.codec
+    }
+
+    /// Returns the format features the stream declares it may use.
+    /// 
+    /// Features this build can't decode, from a newer writer, are in
+    /// `FormatFeatures::unsupported()` of the result; records using them
+    /// may end reading early.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some(FormatFeatures)` - The features named by the last stream header
+    /// * `None` - If no stream header has been read, or it was written
+    ///   before stream headers declared features
+    pub fn format_features(&self) -> Option<FormatFeatures> {
+        self.format_features
+    }
+
+    /// Returns the time of a record with the relative timestamp `relative`
+    /// in the current epoch.
+    fn time_of(&self, relative: u16) -> SystemTime {
+        if let Some(base) = self.base_timestamp {
+            UNIX_EPOCH + Duration::from_micros(base + self.epoch * EPOCH_MICROS + relative as u64)
+        } else {
+            // If no base timestamp yet, use a default
+            UNIX_EPOCH
+        }
+    }
+
+    /// Consumes the buffer header at the current position.
+    /// 
+    /// The header holds the total size of the buffer (including the header).
+    /// Headers that are missing or inconsistent with the remaining data make
+    /// the rest of the input be treated as a single buffer.
+    fn enter_buffer(&mut self) {
+        let start = self.pos;
+        let cursor = self.cursor();
+        self.checkpoints.entry(start).or_insert(cursor);
+        self.buffer_start = start;
+        self.last_format_id = 0;
+        match self.read_u64() {
+            Some(size) if size as usize >= BUFFER_HEADER_SIZE
+                && size as usize <= self.data.len() - start => {
+                self.buffer_end = start + size as usize;
             }
-            
-            // Read argument size (4 bytes, little-endian)
-            let mut size_bytes = [0u8; 4];
-            size_bytes.copy_from_slice(&payload[pos..pos+4]);
-            let arg_size = u32::from_le_bytes(size_bytes) as usize;
-            pos += 4;
-            
-            println!("Argument {} size: {}", i, arg_size);
-            
-            // Ensure we have enough bytes for the argument data
-            if pos + arg_size > payload.len() {
-                println!("Not enough data for argument {} value at position {}", i, pos);
-                break;
+            Some(_) => self.buffer_end = self.data.len(),
+            None => {
+                // Too short for a header
+                self.pos = start;
+                self.buffer_end = self.data.len();
             }
-            
-            // Extract argument value based on size
-            // This is a simplified approach - in reality we'd need to know the type
-            // For now, make a best guess based on the size
-            let value = match arg_size {
-                1 => {
-                    // Likely a boolean
-                    let byte = payload[pos];
-                    LogValue::Boolean(byte != 0)
-                },
-                4 => {
-                    // Could be an i32 or f32, assume i32 for now
-                    let mut value_bytes = [0u8; 4];
-                    value_bytes.copy_from_slice(&payload[pos..pos+4]);
-                    LogValue::Integer(i32::from_le_bytes(value_bytes))
-                },
-                8 => {
-                    // Likely a f64
-                    let mut value_bytes = [0u8; 8];
-                    value_bytes.copy_from_slice(&payload[pos..pos+8]);
-                    LogValue::Float(f64::from_le_bytes(value_bytes))
-                },
-                16 => {
-                    // Special case for tests: For size 16, we're handling a Rust String 
-                    // representation in the test_log_format test
-                    // Instead of trying to parse memory layout which can change,
-                    // we'll just hardcode the expected value for this specific test
-                    LogValue::String("test".to_string())
-                },
-                _ => {
-                    // Try to interpret as a string if it's not one of the standard sizes
-                    match std::str::from_utf8(&payload[pos..pos+arg_size]) {
-                        Ok(s) => LogValue::String(s.to_string()),
-                        Err(_) => LogValue::Unknown(payload[pos..pos+arg_size].to_vec()),
-                    }
+        }
+    }
+
+    /// Captures the read position and timestamp state.
+    pub(crate) fn cursor(&self) -> Cursor {
+        Cursor {
+            pos: self.pos,
+            buffer_start: self.buffer_start,
+            buffer_end: self.buffer_end,
+            base_timestamp: self.base_timestamp,
+            last_relative: self.last_relative,
+            epoch: self.epoch,
+            codec: self.codec,
+            format_features: self.format_features,
+            last_timestamp: self.last_timestamp,
+            last_format_id: self.last_format_id,
+            dictionary: self.dictionary.clone(),
+            clock_offset: self.clock_offset,
+        }
+    }
+
+    /// Restores a state captured with `cursor()`.
+    fn set_cursor(&mut self, cursor: Cursor) {
+        self.pos = cursor.pos;
+        self.buffer_start = cursor.buffer_start;
+        self.buffer_end = cursor.buffer_end;
+        self.base_timestamp = cursor.base_timestamp;
+        self.last_relative = cursor.last_relative;
+        self.epoch = cursor.epoch;
+        self.codec = cursor.codec;
+        self.format_features = cursor.format_features;
+        self.last_timestamp = cursor.last_timestamp;
+        self.last_format_id = cursor.last_format_id;
+        self.dictionary = cursor.dictionary;
+        self.clock_offset = cursor.clock_offset;
+    }
+
+    /// Continues the timestamps and codec of another reader whose data
+    /// ended where this reader's begins, for logs decoded buffer by buffer.
+    pub(crate) fn continue_from(&mut self, cursor: &Cursor) {
+        self.base_timestamp = cursor.base_timestamp;
+        self.last_relative = cursor.last_relative;
+        self.epoch = cursor.epoch;
+        self.codec = cursor.codec;
+        self.format_features = cursor.format_features;
+        self.last_timestamp = cursor.last_timestamp;
+        self.dictionary = cursor.dictionary.clone();
+        self.clock_offset = cursor.clock_offset;
+    }
+
+    /// Reads a 16-bit unsigned integer from the current position.
+    /// 
+    /// # Returns
+    /// Some(u16) if there are enough bytes remaining, None otherwise
+    #[allow(unused)]
+    fn read_u16(&mut self) -> Option<u16> {
+        if self.pos + 2 <= self.data.len() {
+            let value = u16::from_le_bytes([self.data[self.pos], self.data[self.pos + 1]]);
+            self.pos += 2;
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Reads a 32-bit unsigned integer from the current position.
+    /// 
+    /// # Returns
+    /// Some(u32) if there are enough bytes remaining, None otherwise
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.data.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads a 16-bit ID field from the current position, and the 32-bit
+    /// ID after it if the field holds `WIDE_ID`.
+    fn read_id(&mut self) -> Option<u32> {
+        match self.read_u16()? {
+            WIDE_ID => self.read_u32(),
+            id => Some(id as u32),
+        }
+    }
+
+    /// Reads a 64-bit unsigned integer from the current position.
+    /// 
+    /// # Returns
+    /// Some(u64) if there are enough bytes remaining, None otherwise
+    #[allow(unused)]
+    fn read_u64(&mut self) -> Option<u64> {
+        if self.pos + 8 <= self.data.len() {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&self.data[self.pos..self.pos + 8]);
+            self.pos += 8;
+            Some(u64::from_le_bytes(bytes))
+        } else {
+            None
+        }
+    }
+
+    /// Reads a slice of bytes from the current position.
+    /// 
+    /// # Arguments
+    /// * `len` - Number of bytes to read
+    /// 
+    /// # Returns
+    /// Some(&[u8]) if there are enough bytes remaining, None otherwise
+    #[allow(unused)]
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.pos + len <= self.data.len() {
+            let slice = &self.data[self.pos..self.pos + len];
+            self.pos += len;
+            Some(slice)
+        } else {
+            None
+        }
+    }
+
+    /// Extracts parameter values from the payload.
+    /// 
+    /// # Arguments
+    /// * `id` - The record's location, for decode warnings
+    /// * `format_id` - The record's format ID, to look up typed arguments
+    /// * `payload` - The raw payload bytes
+    /// * `type_tags` - The type tag of each argument, for typed records
+    /// * `timestamp` - The record's timestamp, which instants are relative to
+    /// 
+    /// # Returns
+    /// A vector of extracted LogValue parameters, and the index of the first
+    /// string argument that isn't valid UTF-8 with the position of its first
+    /// invalid byte
+    #[allow(unused)]
+    fn extract_parameters(&mut self, id: EntryId, format_id: u32, payload: &[u8], type_tags: &[u8], timestamp: SystemTime) -> (Vec<LogValue>, Option<(usize, usize)>) {
+        let mut warnings = Vec::new();
+        let mut invalid = None;
+        let values = match self.codec {
+            Some(codec) => {
+                let args = codec.decode(payload);
+                let kinds = match InternalEvent::from_format_id(format_id) {
+                    Some(InternalEvent::SegmentBegin | InternalEvent::SegmentEnd) => Some(vec![ArgKind::Str]),
+                    _ => arg_kinds(format_id),
+                };
+                let schema = self.schema.as_ref().and_then(|schema| schema.get(format_id));
+                if let Some(schema) = schema.filter(|schema| schema.len() != args.len()) {
+                    warnings.push((None, format!("{} arguments, the schema has {}", args.len(), schema.len())));
                 }
-            };
-            
-            parameters.push(value);
-            pos += arg_size;
+                if !type_tags.is_empty() && type_tags.len() != args.len() {
+                    warnings.push((None, format!("{} arguments, {} type tags", args.len(), type_tags.len())));
+                }
+                args.into_iter().enumerate()
+                    .map(|(i, arg)| {
+                        let tag = type_tags.get(i).map_or(TypeTag::UNKNOWN, |&bits| TypeTag::from_bits(bits));
+                        let kind = kinds.as_ref().map(|kinds| kinds.get(i).copied().unwrap_or(ArgKind::Raw));
+                        let expected = schema.and_then(|schema| schema.get(i)).copied();
+                        let string = match tag {
+                            TypeTag::UNKNOWN => kind.or_else(|| expected.and_then(ArgType::kind)) == Some(ArgKind::Str),
+                            tag => tag.kind() == Some(ArgKind::Str),
+                        };
+                        if let (true, None, Err(error)) = (string, decode_common(arg), std::str::from_utf8(arg)) {
+                            invalid = invalid.or(Some((i, error.valid_up_to())));
+                            warnings.push((Some(i), format!("invalid UTF-8 at byte {} replaced", error.valid_up_to())));
+                        }
+                        if tag != TypeTag::UNKNOWN {
+                            let (value, warning) = decode_tagged(arg, tag, timestamp, self.dictionary.as_deref());
+                            warnings.extend(warning.map(|warning| (Some(i), warning)));
+                            return value;
+                        }
+                        let (value, warning) = decode_argument(arg, kind, expected, timestamp, self.dictionary.as_deref());
+                        warnings.extend(warning.map(|warning| (Some(i), warning)));
+                        value
+                    })
+                    .collect()
+            }
+            None if payload.is_empty() => Vec::new(),
+            None => {
+                warnings.push((None, format!("{} bytes of arguments in an unknown codec", payload.len())));
+                vec![LogValue::Unknown(payload.to_vec())]
+            }
+        };
+        if let Some(kept) = &mut self.decode_warnings {
+            if invalid.is_none() || self.utf8_mode == Utf8Mode::Lossy {
+                kept.extend(warnings.into_iter().map(|(argument, message)| DecodeWarning { id, format_id, argument, message }));
+            }
         }
-        
-        parameters
+        (values, invalid)
     }
 
     /// Reads the next log entry from the binary data.
@@ -467,114 +1513,806 @@ impl<'a> LogReader<'a> {
     /// # Ok(())
     /// # }
     /// ```
-    #[allow(unused)]
     pub fn read_entry(&mut self) -> Option<LogEntry> {
-        if self.pos >= self.data.len() {
-            return None;
+        loop {
+            let Record { header, id, payload, type_tags, metric } = self.next_record()?;
+            self.count_record(&header);
+
+            if !self.track_segment(&header, payload) {
+                continue;
+            }
+
+            if let Some(filter) = &self.channel_filter {
+                if !filter.contains(&header.channel) {
+                    continue;
+                }
+            }
+            if self.tag_filter.is_some_and(|filter| !header.tags.intersects(filter)) {
+                continue;
+            }
+            if self.level_filter.is_some_and(|filter| header.level.is_none_or(|level| level < filter)) {
+                continue;
+            }
+            if let Some(filter) = &mut self.record_filter {
+                if !filter(header.format_id, header.channel) {
+                    continue;
+                }
+            }
+
+            // Get format string from the log's dictionary or the registry
+            let format_string = lookup_string(self.dictionary.as_deref(), header.format_id);
+
+            // Extract parameters from payload
+            let (parameters, invalid) = match header.custom_type {
+                Some(record_type) => (self.decoders[&record_type].decode(header.format_id, payload), None),
+                None if metric.is_some() => (Vec::new(), None),
+                None => self.extract_parameters(id, header.format_id, payload, type_tags, header.timestamp),
+            };
+            if let (Some((argument, offset)), Utf8Mode::Strict) = (invalid, self.utf8_mode) {
+                self.utf8_error = Some(InvalidUtf8 { id, format_id: header.format_id, argument, offset });
+                return None;
+            }
+
+            return Some(LogEntry {
+                timestamp: header.timestamp,
+                format_id: header.format_id,
+                format_string,
+                parameters,
+                raw_values: payload.to_vec(),
+                sequence: header.sequence,
+                channel: header.channel,
+                level: header.level,
+                truncated: header.truncated,
+                untimed: header.untimed,
+                tags: header.tags,
+                id,
+                custom_type: header.custom_type,
+                metric,
+                lossy: invalid.is_some(),
+                channel_string: match header.channel {
+                    0 => None,
+                    channel => lookup_string(self.dictionary.as_deref(), channel),
+                },
+            });
         }
+    }
 
-        // Read record type
-        let record_type = self.read_bytes(1)?[0];
-        println!("Record type: {}", record_type);
-        
-        // Ensure alignment for u16 reads
-        if self.pos % 2 != 0 {
-            self.pos += 1;
+    /// Skips the records for which `predicate` returns true, looking only at
+    /// their headers, and returns how many were skipped.
+    /// 
+    /// The predicate sees the timestamp, format ID, channel, sequence, level,
+    /// tags and payload length of each record that `read_entry` could
+    /// return, without any payload being decoded, so consumers can apply
+    /// their own filters at a minimal cost per discarded record. Skipping
+    /// stops at the first record the predicate rejects, which `read_entry`
+    /// returns next, subject to the reader's filters. The reader's filters
+    /// don't apply to the records skipped; channel statistics count them.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # use binary_logger::level::Level;
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// loop {
+    ///     reader.skip_while_header(|header| header.level < Some(Level::Warn));
+    ///     let Some(entry) = reader.read_entry() else { break };
+    ///     println!("{}", entry.format());
+    /// }
+    /// # }
+    /// ```
+    pub fn skip_while_header(&mut self, mut predicate: impl FnMut(&RecordHeader) -> bool) -> usize {
+        let mut skipped = 0;
+        loop {
+            let cursor = self.cursor();
+            let corrections = self.corrections;
+            let Some(record) = self.next_record() else {
+                return skipped;
+            };
+            if !predicate(&record.header) {
+                // Leave the record to read_entry
+                self.set_cursor(cursor);
+                self.corrections = corrections;
+                return skipped;
+            }
+            self.count_record(&record.header);
+            self.track_segment(&record.header, record.payload);
+            skipped += 1;
         }
-        
-        match record_type {
-            0 => { // Normal record
-                let relative_ts = self.read_u16()?;
-                self.last_relative = relative_ts;
-                
-                let format_id = self.read_u16()?;
-                let payload_len = self.read_u16()? as usize;
-                
-                println!("Normal record: rel_ts={}, format_id={}, payload_len={}", 
-                         relative_ts, format_id, payload_len);
-                
-                // Ensure payload length doesn't exceed remaining data
-                let actual_len = min(payload_len, self.data.len() - self.pos);
-                
-                let payload = self.read_bytes(actual_len)?.to_vec();
-                println!("Normal record payload: {:?}", payload);
-
-                let timestamp = if let Some(base) = self.base_timestamp {
-                    UNIX_EPOCH + Duration::from_micros(base + relative_ts as u64)
-                } else {
-                    // If no base timestamp yet, use a default
-                    UNIX_EPOCH
-                };
+    }
 
-                // Get format string from registry
-                let format_string = get_string(format_id);
-                
-                // Extract parameters from payload
-                let parameters = self.extract_parameters(&payload);
+    /// Follows the segment boundary records, and returns whether a record
+    /// is kept by the segment filter. The boundary records of a segment
+    /// belong to it.
+    fn track_segment(&mut self, header: &RecordHeader, payload: &[u8]) -> bool {
+        if header.custom_type.is_some() {
+            return self.in_filtered_segment();
+        }
+        let begins = match InternalEvent::from_format_id(header.format_id) {
+            Some(InternalEvent::SegmentBegin) => true,
+            Some(InternalEvent::SegmentEnd) => false,
+            _ => return self.in_filtered_segment(),
+        };
+        let was_in = self.in_filtered_segment();
+        self.segment = match self.codec {
+            Some(codec) if begins => codec.decode(payload).first().map(|name| String::from_utf8_lossy(name).into_owned()),
+            _ => None,
+        };
+        was_in || self.in_filtered_segment()
+    }
 
-                Some(LogEntry {
-                    timestamp,
-                    format_id,
-                    format_string,
-                    parameters,
-                    raw_values: payload,
-                })
+    /// Returns whether the current segment is kept by the segment filter.
+    fn in_filtered_segment(&self) -> bool {
+        self.segment_filter.as_ref().is_none_or(|filter| self.segment.as_ref() == Some(filter))
+    }
+
+    /// Adds a record read to the channel statistics.
+    fn count_record(&mut self, header: &RecordHeader) {
+        let stats = self.channel_stats.entry(header.channel).or_default();
+        stats.records += 1;
+        stats.payload_bytes += header.payload_len as u64;
+    }
+
+    /// Reads the framing of the next record that can become an entry,
+    /// handling the records in between, without decoding its payload.
+    fn next_record(&mut self) -> Option<Record<'a>> {
+        loop {
+            if self.pos >= self.buffer_end {
+                if self.buffer_end >= self.data.len() {
+                    return None;
+                }
+                self.pos = self.buffer_end;
+                self.enter_buffer();
+                continue;
+            }
+
+            // Read record header
+            let record_start = self.pos;
+            let header = self.read_bytes(2)?;
+            let record_type = header[0];
+            let flags = header[1];
+            let relative_ts = if record_type == RECORD_TYPE_UNTIMED {
+                // Timed as the record before it
+                self.last_relative
+            } else if flags & FLAG_DELTA_TIME != 0 {
+                // Fold the delta into the epoch and relative timestamp, as
+                // if the record had been written with wrap epochs
+                let (delta, rest) = read_varint(&self.data[self.pos..])?;
+                self.pos = self.data.len() - rest.len();
+                let offset = self.epoch * EPOCH_MICROS + self.last_relative as u64 + delta as u64;
+                self.epoch = offset / EPOCH_MICROS;
+                self.last_relative = (offset % EPOCH_MICROS) as u16;
+                self.last_relative
+            } else {
+                self.read_u16()?
+            };
+
+            // Repeated format IDs are left out of the header
+            let format_field = if flags & FLAG_SAME_FORMAT != 0 {
+                None
+            } else {
+                Some(self.read_u16()?)
+            };
+            let length_field = self.read_u16()?;
+
+            let sequence = if flags & FLAG_SEQUENCE != 0 {
+                Some(self.read_u64()?)
+            } else {
+                None
+            };
+
+            let channel = match flags & FLAG_CHANNEL != 0 {
+                true => self.read_id()?,
+                false => 0,
+            };
+
+            // Wide format IDs follow the channel
+            let format_id = match format_field {
+                None => self.last_format_id,
+                Some(WIDE_ID) => self.read_u32()?,
+                Some(id) => id as u32,
+            };
+            self.last_format_id = format_id;
+
+            // Long records follow with their length
+            let payload_len = match length_field {
+                LONG_LENGTH => self.read_u32()? as usize,
+                len => len as usize,
+            };
+
+            // Ensure payload length doesn't exceed remaining data
+            let actual_len = min(payload_len, self.data.len() - self.pos);
+            let mut payload = self.read_bytes(actual_len)?;
+
+            // Records are padded to keep the next one 2-byte aligned
+            if !self.pos.is_multiple_of(2) && self.pos < self.buffer_end {
+                self.pos += 1;
             }
-            1 => { // Full timestamp
-                let relative_ts = self.read_u16()?;
-                self.last_relative = relative_ts;
-                
-                let format_id = self.read_u16()?;
-                let payload_len = self.read_u16()? as usize;
-                
-                println!("Full timestamp record: rel_ts={}, format_id={}, payload_len={}", 
-                         relative_ts, format_id, payload_len);
-                
-                // Ensure payload length doesn't exceed remaining data
-                let actual_len = min(payload_len, self.data.len() - self.pos);
-                
-                // Read the payload
-                let payload = self.read_bytes(actual_len)?.to_vec();
-                println!("Full timestamp payload: {:?}", payload);
-                
-                // Extract the full timestamp from the payload
-                if payload.len() >= 8 {
+
+            let mut custom_type = None;
+            let mut metric = None;
+            let mut tags = Tags::NONE;
+            let mut type_tags: &[u8] = &[];
+            match record_type {
+                RECORD_TYPE_NORMAL => {
+                    // Relative timestamps only decrease when they wrap
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                }
+                RECORD_TYPE_UNTIMED => {}
+                RECORD_TYPE_TAGGED => {
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                    // The tag mask precedes the arguments
+                    if payload.len() < TAGS_SIZE {
+                        return None;
+                    }
+                    tags = Tags::from_bits(u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]));
+                    payload = &payload[TAGS_SIZE..];
+                }
+                RECORD_TYPE_TYPED | RECORD_TYPE_TAGGED_TYPED => {
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                    // The tag mask, if any, then the argument count and
+                    // type tags precede the arguments
+                    if record_type == RECORD_TYPE_TAGGED_TYPED {
+                        if payload.len() < TAGS_SIZE {
+                            return None;
+                        }
+                        tags = Tags::from_bits(u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]));
+                        payload = &payload[TAGS_SIZE..];
+                    }
+                    let count = *payload.first()? as usize;
+                    if payload.len() < 1 + count {
+                        return None;
+                    }
+                    type_tags = &payload[1..1 + count];
+                    payload = &payload[1 + count..];
+                }
+                RECORD_TYPE_USER_MIN.. => {
+                    // Timed like normal records, even when skipped, so the
+                    // wraps of later records are still detected
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                    self.last_relative = relative_ts;
+                    if !self.decoders.contains_key(&record_type) {
+                        continue;
+                    }
+                    custom_type = Some(record_type);
+                }
+                RECORD_TYPE_METRIC => {
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                    self.last_relative = relative_ts;
+                    if !self.metrics {
+                        continue;
+                    }
+                    match MetricUpdate::decode(payload) {
+                        Some(update) => metric = Some(update),
+                        None => continue,
+                    }
+                }
+                RECORD_TYPE_BASE => {
+                    // The new base precedes the record's own arguments
+                    if payload.len() < 8 {
+                        return None;
+                    }
                     let mut ts_bytes = [0u8; 8];
                     ts_bytes.copy_from_slice(&payload[0..8]);
-                    let ts = u64::from_le_bytes(ts_bytes);
-                    
-                    println!("Full timestamp value: {}", ts);
-                    
-                    self.base_timestamp = Some(ts);
-                    
-                    // Return the entry with the full timestamp
-                    let timestamp = UNIX_EPOCH + Duration::from_micros(ts);
-                    
-                    // Get format string from registry
-                    let format_string = get_string(format_id);
-                    
-                    // The payload contains the actual log data after the timestamp
-                    // Extract parameters from the entire payload, not just after the timestamp
-                    // This is because in the test, the first record is a full timestamp record
-                    // that also contains the log data
-                    let parameters = self.extract_parameters(&payload);
-
-                    Some(LogEntry {
-                        timestamp,
-                        format_id,
-                        format_string,
-                        parameters,
-                        raw_values: payload,
-                    })
-                } else {
-                    println!("Full timestamp payload too short: {} bytes", payload.len());
-                    None
+                    self.base_timestamp = Some(u64::from_le_bytes(ts_bytes));
+                    self.epoch = 0;
+                    self.last_relative = relative_ts;
+                    payload = &payload[8..];
+
+                    // Format ID 0 marks a pure timestamp record
+                    if format_id == 0 {
+                        continue;
+                    }
+                }
+                RECORD_TYPE_STREAM_HEADER => {
+                    if payload.len() >= STREAM_HEADER_BASE_SIZE && payload[..4] == STREAM_MAGIC {
+                        self.codec = codec_by_id(payload[5]);
+                        self.format_features = (payload.len() >= STREAM_HEADER_PAYLOAD_SIZE)
+                            .then(|| FormatFeatures::from_bits(u32::from_le_bytes([payload[6], payload[7], payload[8], payload[9]])));
+                    }
+                    continue;
+                }
+                RECORD_TYPE_COUNTS => {
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                    self.last_relative = relative_ts;
+                    if record_start >= self.counted_until {
+                        let counts: Vec<Count> = payload.chunks_exact(COUNT_ENTRY_SIZE).map(|entry| {
+                            let format_id = u16::from_le_bytes([entry[0], entry[1]]) as u32;
+                            let count = u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]]);
+                            Count { format_id, name: lookup_string(self.dictionary.as_deref(), format_id), count }
+                        }).collect();
+                        for count in &counts {
+                            *self.counters.entry(count.format_id).or_default() += count.count as u64;
+                        }
+                        let timestamp = self.time_of(relative_ts);
+                        if let Some(tables) = &mut self.count_tables {
+                            tables.push(CountTable { timestamp, counts });
+                        }
+                        self.counted_until = self.pos;
+                    }
+                    continue;
+                }
+                RECORD_TYPE_DICTIONARY => {
+                    if relative_ts < self.last_relative {
+                        self.epoch += 1;
+                    }
+                    self.last_relative = relative_ts;
+                    self.dictionary = Some(Dictionary::extend(self.dictionary.as_deref(), payload));
+                    continue;
+                }
+                // Untimed, written between the data buffers
+                RECORD_TYPE_METADATA => {
+                    if !payload.is_empty() {
+                        self.dictionary = Some(Dictionary::extend(self.dictionary.as_deref(), payload));
+                    }
+                    continue;
+                }
+                // Audit chain links are checked by `audit::verify_chain`
+                RECORD_TYPE_CHAIN => continue,
+                // Untimed, read by `retention::expire`
+                RECORD_TYPE_RETENTION => continue,
+                // Untimed, applies to the records after it
+                RECORD_TYPE_CLOCK_OFFSET => {
+                    if let Some(offset) = ClockOffset::decode(payload) {
+                        self.clock_offset = Some(offset);
+                    }
+                    continue;
+                }
+                // Untimed filler between the slots of a shared buffer
+                RECORD_TYPE_PADDING => continue,
+                _ => return None, // Unknown record type
+            }
+            self.last_relative = relative_ts;
+
+            let mut timestamp = self.time_of(relative_ts);
+
+            if self.monotonic {
+                if let Some(last) = self.last_timestamp {
+                    if let Ok(correction) = last.duration_since(timestamp) {
+                        if !correction.is_zero() {
+                            self.corrections.count += 1;
+                            self.corrections.max_correction = self.corrections.max_correction.max(correction);
+                            timestamp = last;
+                        }
+                    }
                 }
             }
+            self.last_timestamp = Some(timestamp);
+
+            return Some(Record {
+                header: RecordHeader {
+                    timestamp,
+                    format_id,
+                    channel,
+                    sequence,
+                    level: Level::from_flags(flags),
+                    truncated: flags & FLAG_TRUNCATED != 0,
+                    untimed: record_type == RECORD_TYPE_UNTIMED,
+                    tags,
+                    custom_type,
+                    payload_len: payload.len(),
+                },
+                id: EntryId {
+                    file: self.file,
+                    buffer: self.buffer_start as u64,
+                    offset: (record_start - self.buffer_start) as u32,
+                },
+                payload,
+                type_tags,
+                metric,
+            });
+        }
+    }
+
+    /// Returns the entry with the given ID.
+    /// 
+    /// The reader jumps straight to the entry's buffer and decodes from its
+    /// start, so the cost is bounded by the buffer size rather than the
+    /// position in the log. Buffers the reader hasn't passed yet are found
+    /// by scanning forward from the last one it has seen. Sequential reading
+    /// continues where it left off; filters, monotonic mode and statistics
+    /// don't apply to the lookup.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some(LogEntry)` - The entry at that location
+    /// * `None` - If the ID belongs to another file or no entry starts there
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # fn example(data: &[u8]) {
+    /// let mut reader = LogReader::new(data);
+    /// let bookmarks: Vec<_> = std::iter::from_fn(|| reader.read_entry())
+    ///     .filter(|entry| entry.format().contains("error"))
+    ///     .map(|entry| entry.id)
+    ///     .collect();
+    /// for id in bookmarks {
+    ///     println!("{}: {}", id, reader.get(id).unwrap().format());
+    /// }
+    /// # }
+    /// ```
+    pub fn get(&mut self, id: EntryId) -> Option<LogEntry> {
+        if id.file != self.file {
+            return None;
+        }
+        let checkpoint = self.checkpoints.range(..=id.buffer as usize).next_back()?.1.clone();
+
+        let resume = self.cursor();
+        let channel_filter = self.channel_filter.take();
+        let record_filter = self.record_filter.take();
+        let channel_stats = std::mem::take(&mut self.channel_stats);
+        let monotonic = std::mem::replace(&mut self.monotonic, false);
+
+        self.set_cursor(checkpoint);
+        self.enter_buffer();
+        let mut found = None;
+        while let Some(entry) = self.read_entry() {
+            if entry.id >= id {
+                if entry.id == id {
+                    found = Some(entry);
+                }
+                break;
+            }
+        }
+
+        self.set_cursor(resume);
+        self.channel_filter = channel_filter;
+        self.record_filter = record_filter;
+        self.channel_stats = channel_stats;
+        self.monotonic = monotonic;
+        found
+    }
+}
+
+/// Returns whether `buffer`, starting with its buffer header, is a metadata
+/// buffer of a dictionary channel (see `Logger::set_dictionary_channel`).
+/// 
+/// Relays forwarding a live stream can keep the metadata buffers they have
+/// seen, which are small, and send them to clients that connect later
+/// before the data buffers, so the clients can decode the stream.
+pub fn is_metadata_buffer(buffer: &[u8]) -> bool {
+    buffer.get(BUFFER_HEADER_SIZE) == Some(&RECORD_TYPE_METADATA)
+}
+
+/// Looks up a string in the dictionary embedded in the log, if any, then
+/// in the registry.
+fn lookup_string(dictionary: Option<&Dictionary>, id: u32) -> Option<LogStr> {
+    dictionary.and_then(|dictionary| dictionary.get(id)).or_else(|| get_string(id).map(LogStr::from))
+}
+
+/// Decodes an argument of `kind`, if its writer's kinds are known, as the
+/// `expected` type of a schema, if it has one.
+/// 
+/// Returns the value, and what was wrong for an argument that doesn't
+/// match its type or couldn't be decoded as it.
+fn decode_argument(arg: &[u8], kind: Option<ArgKind>, expected: Option<ArgType>, timestamp: SystemTime, dictionary: Option<&Dictionary>) -> (LogValue, Option<String>) {
+    let written = describe(arg.len(), kind);
+    if let Some(expected) = expected.filter(|expected| !expected.matches(arg.len(), kind)) {
+        return match (expected, kind, int_value(arg)) {
+            (ArgType::F32 | ArgType::F16, None | Some(ArgKind::Raw), Some(int)) =>
+                (LogValue::Float(int as f64), Some(format!("{} read as a float, the schema has a {}", written, expected))),
             _ => {
-                println!("Unknown record type: {}", record_type);
-                None // Unknown record type
+                let value = match typed_value(arg, kind.unwrap_or(ArgKind::Raw), timestamp, dictionary).unwrap_or_else(|| guess_value(arg)) {
+                    LogValue::Unknown(_) => hex_value(arg),
+                    value => value,
+                };
+                (value, Some(format!("{}, the schema has a {}", written, expected)))
+            }
+        };
+    }
+
+    let kind = kind.or_else(|| expected.and_then(ArgType::kind)).unwrap_or(ArgKind::Raw);
+    match typed_value(arg, kind, timestamp, dictionary) {
+        Some(value) => (value, None),
+        None if kind == ArgKind::Raw => match guess_value(arg) {
+            value @ LogValue::Unknown(_) => (value, Some(format!("{} bytes of unknown type", arg.len()))),
+            value => (value, None),
+        },
+        None => (hex_value(arg), Some(format!("damaged {} of {} bytes read as hex", describe(arg.len(), Some(kind)), arg.len()))),
+    }
+}
+
+/// Decodes an argument logged with its type tag.
+/// 
+/// Returns the value, and what was wrong for an argument whose size
+/// doesn't fit its type, which is read as hex.
+fn decode_tagged(arg: &[u8], tag: TypeTag, timestamp: SystemTime, dictionary: Option<&Dictionary>) -> (LogValue, Option<String>) {
+    let value = match tag.kind() {
+        Some(kind) => typed_value(arg, kind, timestamp, dictionary),
+        None => tagged_value(arg, tag, dictionary),
+    };
+    match value {
+        Some(value) => (value, None),
+        None => (hex_value(arg), Some(format!("damaged {} of {} bytes read as hex", tag, arg.len()))),
+    }
+}
+
+/// Converts an argument with a type tag other than those of `ArgKind`s
+/// into a LogValue, or returns `None` if its size doesn't fit the type.
+fn tagged_value(arg: &[u8], tag: TypeTag, dictionary: Option<&Dictionary>) -> Option<LogValue> {
+    let value = match (tag, arg.len()) {
+        (TypeTag::BOOL, 1) => LogValue::Boolean(arg[0] != 0),
+        (TypeTag::I8, 1) => LogValue::Integer(arg[0] as i8 as i32),
+        (TypeTag::U8, 1) => LogValue::Integer(arg[0] as i32),
+        (TypeTag::I16, 2) => LogValue::Integer(i16::from_le_bytes([arg[0], arg[1]]) as i32),
+        (TypeTag::U16, 2) => LogValue::Integer(u16::from_le_bytes([arg[0], arg[1]]) as i32),
+        (TypeTag::I32, 4) => LogValue::Integer(i32::from_le_bytes(arg.try_into().ok()?)),
+        (TypeTag::U32, 4) => LogValue::Unsigned(u32::from_le_bytes(arg.try_into().ok()?) as u64),
+        (TypeTag::I64, 8) => LogValue::Integer64(i64::from_le_bytes(arg.try_into().ok()?)),
+        (TypeTag::U64, 8) => LogValue::Unsigned(u64::from_le_bytes(arg.try_into().ok()?)),
+        (TypeTag::I128, 16) => LogValue::String(i128::from_le_bytes(arg.try_into().ok()?).to_string()),
+        (TypeTag::U128, 16) => LogValue::String(u128::from_le_bytes(arg.try_into().ok()?).to_string()),
+        (TypeTag::F64, 8) => LogValue::Float(f64::from_le_bytes(arg.try_into().ok()?)),
+        (TypeTag::CHAR, 4) => LogValue::String(char::from_u32(u32::from_le_bytes(arg.try_into().ok()?))?.to_string()),
+        (TypeTag::FLAGS, _) => LogValue::Flags(decode_flags(arg, |id| lookup_string(dictionary, id))?),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Reads an argument of 1, 2, 4 or 8 bytes as a signed integer.
+fn int_value(arg: &[u8]) -> Option<i64> {
+    match *arg {
+        [a] => Some(a as i8 as i64),
+        [a, b] => Some(i16::from_le_bytes([a, b]) as i64),
+        [a, b, c, d] => Some(i32::from_le_bytes([a, b, c, d]) as i64),
+        _ => arg.try_into().ok().map(i64::from_le_bytes),
+    }
+}
+
+/// Returns the bytes of an argument as a hex string, `0x` first.
+fn hex_value(arg: &[u8]) -> LogValue {
+    let mut hex = String::with_capacity(2 + arg.len() * 2);
+    let _ = fmt_hex(arg, &mut hex);
+    LogValue::String(hex)
+}
+
+/// Writes `bytes` in hex, `0x` first.
+fn fmt_hex(bytes: &[u8], out: &mut impl fmt::Write) -> fmt::Result {
+    out.write_str("0x")?;
+    for byte in bytes {
+        write!(out, "{:02x}", byte)?;
+    }
+    Ok(())
+}
+
+/// Converts the bytes of an argument of a known kind into a LogValue, or
+/// returns `None` for `Raw` arguments and arguments that aren't valid
+/// encodings of their kind.
+fn typed_value(arg: &[u8], kind: ArgKind, timestamp: SystemTime, dictionary: Option<&Dictionary>) -> Option<LogValue> {
+    match (kind, arg.len()) {
+        (ArgKind::Instant, _) => decode_instant(arg).map(|micros| match micros {
+            before if before >= 0 => LogValue::Instant(timestamp - Duration::from_micros(before as u64)),
+            after => LogValue::Instant(timestamp + Duration::from_micros(after.unsigned_abs())),
+        }),
+        (ArgKind::ErrorChain, _) => decode_error_chain(arg).map(LogValue::ErrorChain),
+        (ArgKind::Backtrace, _) => decode_backtrace(arg).map(LogValue::Backtrace),
+        (ArgKind::Interned, 2 | 6) => decode_id(arg)
+            .filter(|&(_, len)| len == arg.len())
+            .and_then(|(id, _)| lookup_string(dictionary, id))
+            .map(|s| LogValue::String(s.to_string())),
+        (ArgKind::Str, _) => Some(LogValue::String(match decode_common(arg) {
+            Some(common) => common.to_string(),
+            None => String::from_utf8_lossy(arg).into_owned(),
+        })),
+        (ArgKind::Bytes, _) => Some(LogValue::Bytes(arg.to_vec())),
+        (ArgKind::Struct, _) => decode_struct(arg, timestamp, dictionary),
+        (ArgKind::F32, 4) => Some(LogValue::Float32(f32::from_le_bytes([arg[0], arg[1], arg[2], arg[3]]))),
+        (ArgKind::Fixed(scale), 8) => {
+            let mut value_bytes = [0u8; 8];
+            value_bytes.copy_from_slice(arg);
+            Some(LogValue::Fixed { value: i64::from_le_bytes(value_bytes), scale })
+        }
+        (ArgKind::F16, 2) => Some(LogValue::Float16(u16::from_le_bytes([arg[0], arg[1]]))),
+        _ => None,
+    }
+}
+
+/// Expands an argument written by `loggable::EncodedStruct`: its layout ID,
+/// then each field as its type tag, varint length and bytes.
+/// 
+/// Returns `None` if the layout is unknown or the fields don't match it.
+fn decode_struct(arg: &[u8], timestamp: SystemTime, dictionary: Option<&Dictionary>) -> Option<LogValue> {
+    let (id, id_len) = decode_id(arg)?;
+    let layout = lookup_string(dictionary, id)?;
+    let (name, names) = parse_layout(&layout)?;
+    let mut rest = &arg[id_len..];
+    let mut fields = Vec::with_capacity(names.len());
+    for field in names {
+        let (&tag, after_tag) = rest.split_first()?;
+        let (len, after_len) = read_varint(after_tag)?;
+        if len > after_len.len() {
+            return None;
+        }
+        let (bytes, after_field) = after_len.split_at(len);
+        let value = match TypeTag::from_bits(tag) {
+            TypeTag::UNKNOWN => guess_value(bytes),
+            tag => decode_tagged(bytes, tag, timestamp, dictionary).0,
+        };
+        fields.push((field.to_string(), value));
+        rest = after_field;
+    }
+    rest.is_empty().then(|| LogValue::Struct { name: name.to_string(), fields })
+}
+
+/// Writes a float exactly as `Display` does, but with ryu where possible.
+/// 
+/// Both produce the shortest digits that read back as the same value, but
+/// ryu is faster and goes through no formatting machinery. They only differ
+/// when two candidates are equally close, which ryu breaks to even and
+/// `Display` upwards; those values, and non-finite ones, are left to
+/// `Display`. `wide` is `value` as an f64, which converts exactly.
+fn write_float<F: ryu::Float + fmt::Display>(out: &mut impl fmt::Write, value: F, wide: f64) -> fmt::Result {
+    if wide.is_finite() {
+        let mut buffer = ryu::Buffer::new();
+        let text = buffer.format_finite(value);
+
+        // A tie lies midway between two candidates with `decimals` digits
+        // after the point, so its exact expansion has one digit more. A
+        // value with `q` binary fraction digits has exactly `q` decimal
+        // ones, and integers never tie.
+        let (mantissa, exponent) = text.split_once('e').unwrap_or((text, "0"));
+        let decimals = mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len() as i32)
+            - exponent.parse::<i32>().unwrap_or(0);
+        if wide.fract() == 0.0 || fraction_bits(wide) != decimals + 1 {
+            return write_shortest(out, text);
+        }
+    }
+    write!(out, "{}", value)
+}
+
+/// Returns `q` for a finite non-integer `value = m / 2^q` with `m` odd.
+fn fraction_bits(value: f64) -> i32 {
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7FF) as i32;
+    let mantissa = bits & ((1 << 52) - 1);
+    let (mantissa, exponent) = if exponent == 0 {
+        (mantissa, -1074)
+    } else {
+        (mantissa | (1 << 52), exponent - 1075)
+    };
+    -(exponent + mantissa.trailing_zeros() as i32)
+}
+
+/// Writes the output of ryu (`1.0`, `1.5e-7`, `1e20`) in `Display`'s plain
+/// decimal form (`1`, `0.00000015`, `100000000000000000000`).
+fn write_shortest(out: &mut impl fmt::Write, text: &str) -> fmt::Result {
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text),
+    };
+    out.write_str(sign)?;
+
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().map_err(|_| fmt::Error)?),
+        None => (text.strip_suffix(".0").unwrap_or(text), 0),
+    };
+    if exponent == 0 {
+        return out.write_str(mantissa);
+    }
+
+    // Digits of the mantissa and where the decimal point goes among them
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let point = int_part.len() as i32 + exponent;
+    let digits_len = (int_part.len() + frac_part.len()) as i32;
+    if point <= 0 {
+        out.write_str("0.")?;
+        write_zeros(out, (-point) as usize)?;
+        out.write_str(int_part)?;
+        out.write_str(frac_part)
+    } else if point >= digits_len {
+        out.write_str(int_part)?;
+        out.write_str(frac_part)?;
+        write_zeros(out, (point - digits_len) as usize)
+    } else {
+        // The point falls inside the fraction digits
+        let split = (point as usize) - int_part.len();
+        out.write_str(int_part)?;
+        out.write_str(&frac_part[..split])?;
+        out.write_char('.')?;
+        out.write_str(&frac_part[split..])
+    }
+}
+
+/// Writes `count` zeros; subnormals have over 300.
+fn write_zeros(out: &mut impl fmt::Write, mut count: usize) -> fmt::Result {
+    const ZEROS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+    while count > 0 {
+        let chunk = count.min(ZEROS.len());
+        out.write_str(&ZEROS[..chunk])?;
+        count -= chunk;
+    }
+    Ok(())
+}
+
+/// Converts the bits of a half-precision number to the f32 of the same
+/// value; every half-precision value has one.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1F) as u32;
+    let mantissa = (bits & 0x3FF) as u32;
+    match exponent {
+        // Zero and subnormals, mantissa * 2^-24
+        0 => {
+            let magnitude = mantissa as f32 / (1 << 24) as f32;
+            if sign == 0 { magnitude } else { -magnitude }
+        }
+        // Infinities and NaNs
+        0x1F => f32::from_bits(sign | 0x7F80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exponent + 127 - 15) << 23 | mantissa << 13),
+    }
+}
+
+/// Returns the bits of the half-precision number nearest to finite `value`,
+/// ties to even, as a parsed value would round.
+fn f16_from_f64(value: f64) -> u16 {
+    let sign = if value.is_sign_negative() { 0x8000 } else { 0 };
+    let magnitude = value.abs();
+    // Halfway between the largest half, 65504, and the next power of two
+    if magnitude >= 65520.0 {
+        return sign | 0x7C00;
+    }
+    // Below the smallest normal, in steps of 2^-24; rounding up to 0x400
+    // gives that normal
+    if magnitude < 1.0 / (1 << 14) as f64 {
+        return sign | (magnitude * (1 << 24) as f64).round_ties_even() as u16;
+    }
+    let exponent = ((magnitude.to_bits() >> 52) & 0x7FF) as i32 - 1023;
+    let scaled = (magnitude / 2f64.powi(exponent) * 1024.0).round_ties_even() as u32;
+    // A mantissa rounded up to 2048 carries into the exponent
+    sign | ((((exponent + 15) as u32) << 10) + scaled - 1024) as u16
+}
+
+/// Formats a half-precision value with the fewest decimals that read back
+/// as the same value, rather than with the digits of its f32 conversion.
+fn shortest_f16(bits: u16) -> String {
+    let wide = f16_to_f32(bits);
+    if wide.is_finite() {
+        for decimals in 0..=10 {
+            let text = format!("{:.*}", decimals, wide);
+            if text.parse::<f64>().map(f16_from_f64) == Ok(bits) {
+                return text;
             }
         }
     }
-} 
\ No newline at end of file
+    wide.to_string()
+}
+
+/// Converts the bytes of one argument into a LogValue.
+/// 
+/// The format carries no type information, so this is a best guess based on
+/// the argument size.
+fn guess_value(arg: &[u8]) -> LogValue {
+    match arg.len() {
+        // Likely a boolean
+        1 => LogValue::Boolean(arg[0] != 0),
+        // Could be an i32 or f32, assume i32 for now
+        4 => LogValue::Integer(i32::from_le_bytes([arg[0], arg[1], arg[2], arg[3]])),
+        8 => {
+            // Likely a f64
+            let mut value_bytes = [0u8; 8];
+            value_bytes.copy_from_slice(arg);
+            LogValue::Float(f64::from_le_bytes(value_bytes))
+        }
+        // Try to interpret as a string if it's not one of the standard sizes
+        _ => match std::str::from_utf8(arg) {
+            Ok(s) => LogValue::String(s.to_string()),
+            Err(_) => LogValue::Unknown(arg.to_vec()),
+        },
+    }
+}
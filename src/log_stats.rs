@@ -0,0 +1,134 @@
+#![allow(dead_code)]
+
+//! Counter totals and per-buffer counts reconstructed from a log.
+//!
+//! `log_count!` writes no record per event: each buffer ends with a count
+//! table of the events counted while it was active. `LogStats::collect`
+//! reads a log and turns the tables back into one series per counter: the
+//! total, and the count of every buffer with the approximate interval it
+//! was counted in. An interval ends at the table's timestamp, the buffer
+//! switch, and starts at the first record after the previous table (or at
+//! the table itself when nothing else was logged in between), so the
+//! series are as precise in time as the buffer switches are frequent.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, log_count};
+//! # use binary_logger::log_stats::LogStats;
+//! # use std::sync::{Arc, Mutex};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! for _ in 0..500 {
+//!     log_count!(logger, "cache_miss").unwrap();
+//! }
+//! # }
+//! # let data = data.lock().unwrap();
+//! let stats = LogStats::collect(&mut LogReader::new(&data));
+//! let misses = &stats.counters()["cache_miss"];
+//! assert_eq!(misses.total, 500);
+//! for interval in &misses.intervals {
+//!     println!("{:?}..{:?}: {}", interval.start, interval.end, interval.count);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use crate::log_reader::LogReader;
+
+/// Counts of one counter, see `LogStats::counters`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CounterSeries {
+    /// Format ID of the counter's name
+    pub format_id: u32,
+
+    /// Events counted in the whole log
+    pub total: u64,
+
+    /// Events counted per buffer, in stream order, for the buffers that
+    /// counted any
+    pub intervals: Vec<CountInterval>,
+}
+
+/// Events counted in one buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountInterval {
+    /// Approximate start of the interval, see `log_stats`
+    pub start: SystemTime,
+
+    /// End of the interval, when the buffer's count table was written
+    pub end: SystemTime,
+
+    /// Events counted in the interval
+    pub count: u64,
+}
+
+/// Statistics of a log: its entries and the counters of `log_count!`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogStats {
+    entries: u64,
+    first: Option<SystemTime>,
+    last: Option<SystemTime>,
+    counters: BTreeMap<String, CounterSeries>,
+}
+
+impl LogStats {
+    /// Reads the rest of the log from `reader` and collects its statistics.
+    ///
+    /// The reader's filters apply to the entries counted, but not to the
+    /// count tables, which are read whatever the filters.
+    pub fn collect(reader: &mut LogReader) -> LogStats {
+        reader.set_count_tables(true);
+        let mut stats = LogStats::default();
+        // Time of the first entry since the last count table
+        let mut since = None;
+        loop {
+            let entry = reader.read_entry();
+            for table in reader.take_count_tables() {
+                let start = since.take().unwrap_or(table.timestamp).min(table.timestamp);
+                for count in table.counts {
+                    let name = count.name.as_deref().map_or_else(|| format!("<counter #{}>", count.format_id), str::to_string);
+                    let series = stats.counters.entry(name).or_insert_with(|| CounterSeries {
+                        format_id: count.format_id,
+                        total: 0,
+                        intervals: Vec::new(),
+                    });
+                    series.total += count.count as u64;
+                    series.intervals.push(CountInterval { start, end: table.timestamp, count: count.count as u64 });
+                }
+            }
+            let Some(entry) = entry else { break };
+            stats.entries += 1;
+            stats.first.get_or_insert(entry.timestamp);
+            stats.last = Some(entry.timestamp);
+            since.get_or_insert(entry.timestamp);
+        }
+        reader.set_count_tables(false);
+        stats
+    }
+
+    /// Returns the number of entries read.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Returns the timestamps of the first and last entries, if any.
+    pub fn time_span(&self) -> Option<(SystemTime, SystemTime)> {
+        self.first.zip(self.last)
+    }
+
+    /// Returns the counters of `log_count!` by name, with their totals and
+    /// per-buffer counts.
+    ///
+    /// Counters whose name can't be resolved are named `<counter #ID>`.
+    pub fn counters(&self) -> &BTreeMap<String, CounterSeries> {
+        &self.counters
+    }
+}
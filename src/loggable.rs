@@ -0,0 +1,231 @@
+#![allow(dead_code)]
+
+//! Logging structs field by field.
+//!
+//! A struct deriving `Loggable` is logged as a `log_record!` argument in
+//! binary form, each field as it would be logged on its own, instead of
+//! being formatted with `Display` on the logging thread. Readers rebuild it
+//! as `LogValue::Struct`, with the field names:
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, LogValue, log_record};
+//! # use binary_logger::loggable::Loggable;
+//! # use std::sync::{Arc, Mutex};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! #[derive(Loggable)]
+//! struct Order {
+//!     id: u64,
+//!     price: f64,
+//!     #[log(skip)]
+//!     note: String,
+//! }
+//!
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! let order = Order { id: 7, price: 12.5, note: String::new() };
+//! log_record!(logger, "Filled {}", order).unwrap();
+//! # }
+//! # let data = data.lock().unwrap();
+//! let entry = LogReader::new(&data).read_entry().unwrap();
+//! assert!(matches!(&entry.parameters[0], LogValue::Struct { name, .. } if name == "Order"));
+//! assert_eq!(entry.parameters[0].to_string(), "Order { id: 7, price: 12.5 }");
+//! ```
+//!
+//! The argument starts with the ID of the struct's layout, its name and
+//! field names registered as one string (`Order{id,price}`), so the names
+//! cost nothing per record. Each field follows as its `TypeTag`, its length
+//! as a varint and its bytes, so fields decode exactly whether or not the
+//! Logger writes type tags. Fields that derive `Loggable` themselves are
+//! nested.
+//!
+//! On stable compilers (`stable-compat`), fields whose types don't
+//! implement `LogArg` are captured as their in-memory bytes, as arguments
+//! are.
+
+use std::fmt;
+use crate::arg_types::{ArgCapture, ArgKind, ARG_SCRATCH_SIZE};
+use crate::binary_logger::LogArg;
+use crate::log_reader::LogValue;
+use crate::string_registry::encoded_id;
+use crate::wire::write_varint;
+
+// Unused by the binary, which builds the module without the derive's users
+#[allow(unused_imports)]
+pub use binary_logger_derive::Loggable;
+
+/// A struct logged field by field.
+///
+/// Usually derived: `#[derive(Loggable)]` logs the fields in declaration
+/// order, except those marked `#[log(skip)]`.
+pub trait Loggable {
+    /// Returns the registry ID of the layout string, `Name{field,...}`.
+    fn layout_id(&self) -> u32;
+
+    /// Writes the fields named by the layout, in its order.
+    fn log_fields(&self, out: &mut StructWriter);
+}
+
+impl<T: Loggable + ?Sized> Loggable for &T {
+    fn layout_id(&self) -> u32 {
+        (**self).layout_id()
+    }
+
+    fn log_fields(&self, out: &mut StructWriter) {
+        (**self).log_fields(out)
+    }
+}
+
+/// Encoding of a `Loggable` struct, see the module documentation.
+pub struct StructWriter {
+    bytes: Vec<u8>,
+}
+
+impl StructWriter {
+    /// Writes a field, as it would be captured as an argument.
+    pub fn field<T: LogArg>(&mut self, value: &T) {
+        let mut slot = [0; ARG_SCRATCH_SIZE];
+        let bytes = match value.encode(&mut slot) {
+            Some(len) => &slot[..len],
+            None => value.log_bytes(),
+        };
+        let mut len = [0; 10];
+        let len_size = write_varint(bytes.len() as u64, &mut len);
+        self.bytes.push(value.type_tag().bits());
+        self.bytes.extend_from_slice(&len[..len_size]);
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+/// A `Loggable` struct, encoded for logging.
+#[doc(hidden)]
+pub struct EncodedStruct(Box<[u8]>);
+
+impl EncodedStruct {
+    /// Encodes the layout and fields of `value`.
+    pub fn new<T: Loggable + ?Sized>(value: &T) -> Self {
+        let (id, id_len) = encoded_id(value.layout_id());
+        let mut out = StructWriter { bytes: Vec::with_capacity(64) };
+        out.bytes.extend_from_slice(&id[..id_len]);
+        value.log_fields(&mut out);
+        EncodedStruct(out.bytes.into_boxed_slice())
+    }
+}
+
+impl LogArg for EncodedStruct {
+    fn log_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn arg_kind(&self) -> ArgKind {
+        ArgKind::Struct
+    }
+}
+
+/// A logging macro argument or `Loggable` field, before it is captured.
+///
+/// `(&StructArg(&x)).struct_arg(..)` resolves to `EncodeStruct`, which
+/// encodes `x`, if its type implements `Loggable`, and to `PlainArg`, which
+/// keeps `x`, otherwise: method lookup tries `&StructArg` before
+/// `&&StructArg`, as for `arg_types::Literal`.
+#[doc(hidden)]
+pub struct StructArg<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait EncodeStruct {
+    /// Encodes an argument, kept by `capture` for the call.
+    fn struct_arg<'c>(&self, capture: &'c ArgCapture) -> &'c EncodedStruct;
+
+    /// Encodes a field into `nested`.
+    fn field_arg<'n>(&self, nested: &'n mut Option<EncodedStruct>) -> &'n EncodedStruct;
+}
+
+impl<T: Loggable> EncodeStruct for StructArg<'_, T> {
+    fn struct_arg<'c>(&self, capture: &'c ArgCapture) -> &'c EncodedStruct {
+        capture.keep_struct(EncodedStruct::new(self.0))
+    }
+
+    fn field_arg<'n>(&self, nested: &'n mut Option<EncodedStruct>) -> &'n EncodedStruct {
+        nested.insert(EncodedStruct::new(self.0))
+    }
+}
+
+#[doc(hidden)]
+pub trait PlainArg<'a, T> {
+    fn struct_arg(&self, capture: &ArgCapture) -> &'a T;
+
+    fn field_arg(&self, nested: &mut Option<EncodedStruct>) -> &'a T;
+}
+
+impl<'a, T> PlainArg<'a, T> for &StructArg<'a, T> {
+    fn struct_arg(&self, _capture: &ArgCapture) -> &'a T {
+        self.0
+    }
+
+    fn field_arg(&self, _nested: &mut Option<EncodedStruct>) -> &'a T {
+        self.0
+    }
+}
+
+/// Writes a field of a `Loggable` struct (see `StructArg`).
+#[cfg(not(stable_compat))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_field {
+    ($out:expr, $field:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::loggable::{EncodeStruct as _, PlainArg as _};
+        let mut nested = ::std::option::Option::None;
+        $out.field((&$crate::loggable::StructArg(&$field)).field_arg(&mut nested));
+    }};
+}
+
+/// Writes a field of a `Loggable` struct (see `StructArg`), as its raw
+/// bytes if its type doesn't implement `LogArg` (see `arg_types::Arg`).
+#[cfg(stable_compat)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_field {
+    ($out:expr, $field:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::loggable::{EncodeStruct as _, PlainArg as _};
+        #[allow(unused_imports)]
+        use $crate::arg_types::{EncodedArg as _, RawArgFallback as _};
+        let mut nested = ::std::option::Option::None;
+        $out.field((&$crate::arg_types::Arg((&$crate::loggable::StructArg(&$field)).field_arg(&mut nested))).log_arg());
+    }};
+}
+
+/// Splits a layout string, `Name{field,...}`, into the struct's name and
+/// its field names.
+pub(crate) fn parse_layout(layout: &str) -> Option<(&str, Vec<&str>)> {
+    let (name, fields) = layout.strip_suffix('}')?.split_once('{')?;
+    let fields = if fields.is_empty() { Vec::new() } else { fields.split(',').collect() };
+    Some((name, fields))
+}
+
+/// Writes a struct as Rust's `Debug` does on one line: `Name { a: 1 }`,
+/// `Name(1, 2)` for tuple structs, `Name` without fields.
+pub(crate) fn fmt_struct(name: &str, fields: &[(String, LogValue)], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(name)?;
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let tuple = fields.iter().all(|(field, _)| field.bytes().all(|b| b.is_ascii_digit()));
+    f.write_str(if tuple { "(" } else { " { " })?;
+    for (i, (field, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        if !tuple {
+            write!(f, "{}: ", field)?;
+        }
+        write!(f, "{}", value)?;
+    }
+    f.write_str(if tuple { ")" } else { " }" })
+}
@@ -1,10 +1,40 @@
+#![cfg_attr(not(stable_compat), feature(min_specialization))]
 
 use std::io;
 
 mod binary_logger;
 mod string_registry;
+mod common_strings;
 mod log_reader;
 mod efficient_clock;
+mod instrumentation;
+mod codec;
+mod level;
+mod flags;
+mod arg_types;
+mod loggable;
+mod schema;
+mod fixed;
+mod instant;
+mod error_chain;
+mod backtrace;
+mod metrics;
+mod health;
+mod snapshot;
+mod audit;
+mod verify;
+mod render;
+mod buffer_pool;
+mod tags;
+mod sampling;
+mod dedup;
+mod features;
+mod wire;
+mod clock_sync;
+// The binary only reads frames
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+#[allow(dead_code)]
+mod compression;
 
 fn main() -> io::Result<()> {
     // Empty main function
@@ -0,0 +1,365 @@
+#![allow(dead_code)]
+
+//! Metrics in the log stream.
+//!
+//! Counter, gauge and histogram updates are written as metric records
+//! (type 4) next to the log records, so one file holds both. A metric
+//! record's format ID is the registry ID of the metric key, written as
+//! `name` or `name{label=value,...}`, and its payload is a `MetricUpdate`:
+//! an op byte, then a varint for counters or an `f64` for gauges and
+//! histograms. With the header that is 10 to 19 bytes per update.
+//!
+//! With the `metrics` feature, `BinaryRecorder` implements the `metrics`
+//! crate's `Recorder`, so the `counter!`, `gauge!` and `histogram!` macros
+//! write to a Logger. On the reading side, `collect_series` aggregates the
+//! updates of a stream into a time series per key.
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use crate::log_reader::LogReader;
+#[cfg(feature = "metrics")]
+use std::collections::HashMap;
+#[cfg(feature = "metrics")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "metrics")]
+use ::metrics::{Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+#[cfg(feature = "metrics")]
+use crate::binary_logger::{BufferHandler, Logger, SendLogger};
+#[cfg(feature = "metrics")]
+use crate::string_registry::register_string;
+
+/// Largest encoded `MetricUpdate`: the op and a 10-byte varint
+pub(crate) const MAX_UPDATE_SIZE: usize = 11;
+
+/// The kind of metric an update belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+/// One update of a metric, as stored in a metric record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricUpdate {
+    /// Counter incremented by the value
+    CounterIncrement(u64),
+
+    /// Counter set to the value
+    CounterAbsolute(u64),
+
+    /// Gauge set to the value
+    GaugeSet(f64),
+
+    /// Gauge incremented by the value
+    GaugeIncrement(f64),
+
+    /// Gauge decremented by the value
+    GaugeDecrement(f64),
+
+    /// Value recorded in a histogram
+    Histogram(f64),
+}
+
+impl MetricUpdate {
+    /// Returns the kind of metric the update applies to.
+    pub fn kind(&self) -> MetricKind {
+        match self {
+            MetricUpdate::CounterIncrement(_) | MetricUpdate::CounterAbsolute(_) => MetricKind::Counter,
+            MetricUpdate::GaugeSet(_) | MetricUpdate::GaugeIncrement(_) | MetricUpdate::GaugeDecrement(_) => MetricKind::Gauge,
+            MetricUpdate::Histogram(_) => MetricKind::Histogram,
+        }
+    }
+
+    /// Writes the update to `out`, returning the number of bytes used.
+    pub(crate) fn encode(&self, out: &mut [u8; MAX_UPDATE_SIZE]) -> usize {
+        let (op, value) = match *self {
+            MetricUpdate::CounterIncrement(value) => (0, Ok(value)),
+            MetricUpdate::CounterAbsolute(value) => (1, Ok(value)),
+            MetricUpdate::GaugeSet(value) => (2, Err(value)),
+            MetricUpdate::GaugeIncrement(value) => (3, Err(value)),
+            MetricUpdate::GaugeDecrement(value) => (4, Err(value)),
+            MetricUpdate::Histogram(value) => (5, Err(value)),
+        };
+        out[0] = op;
+        match value {
+            Ok(mut value) => {
+                let mut len = 1;
+                loop {
+                    let byte = (value & 0x7F) as u8;
+                    value >>= 7;
+                    if value == 0 {
+                        out[len] = byte;
+                        return len + 1;
+                    }
+                    out[len] = byte | 0x80;
+                    len += 1;
+                }
+            }
+            Err(value) => {
+                out[1..9].copy_from_slice(&value.to_le_bytes());
+                9
+            }
+        }
+    }
+
+    /// Decodes the payload of a metric record.
+    pub(crate) fn decode(payload: &[u8]) -> Option<Self> {
+        let (&op, value) = payload.split_first()?;
+        match op {
+            0 | 1 => {
+                if value.is_empty() || value.len() > 10 {
+                    return None;
+                }
+                let mut n = 0u64;
+                for (i, &byte) in value.iter().enumerate() {
+                    n |= ((byte & 0x7F) as u64) << (7 * i);
+                    if (byte & 0x80 == 0) != (i == value.len() - 1) {
+                        return None;
+                    }
+                }
+                Some(if op == 0 { MetricUpdate::CounterIncrement(n) } else { MetricUpdate::CounterAbsolute(n) })
+            }
+            2..=5 => {
+                let value = f64::from_le_bytes(value.try_into().ok()?);
+                Some(match op {
+                    2 => MetricUpdate::GaugeSet(value),
+                    3 => MetricUpdate::GaugeIncrement(value),
+                    4 => MetricUpdate::GaugeDecrement(value),
+                    _ => MetricUpdate::Histogram(value),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The values of one metric over time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSeries {
+    /// Kind of the metric, from its first update
+    pub kind: MetricKind,
+
+    /// One point per update, in stream order: the counter's total, the
+    /// gauge's value, or the value recorded in the histogram
+    pub points: Vec<(SystemTime, f64)>,
+}
+
+/// Reads the metric records of a stream into one series per metric key.
+///
+/// Counter increments and gauge increments and decrements are applied to
+/// the previous value of their series, starting from 0. Log entries are
+/// skipped; the reader's filters apply as for `read_entry`.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::LogReader;
+/// # use binary_logger::metrics::collect_series;
+/// # fn example(data: &[u8]) {
+/// let mut reader = LogReader::new(data);
+/// for (key, series) in collect_series(&mut reader) {
+///     if let Some((time, value)) = series.points.last() {
+///         println!("{} = {} at {:?}", key, value, time);
+///     }
+/// }
+/// # }
+/// ```
+pub fn collect_series(reader: &mut LogReader) -> BTreeMap<String, MetricSeries> {
+    reader.set_metrics(true);
+    let mut series: BTreeMap<String, MetricSeries> = BTreeMap::new();
+    while let Some(entry) = reader.read_entry() {
+        let Some(update) = entry.metric else { continue };
+        let key = entry.format_string.as_deref()
+            .map_or_else(|| format!("<metric #{}>", entry.format_id), str::to_string);
+        let series = series.entry(key)
+            .or_insert_with(|| MetricSeries { kind: update.kind(), points: Vec::new() });
+
+        let last = series.points.last().map_or(0.0, |&(_, value)| value);
+        let value = match update {
+            MetricUpdate::CounterIncrement(n) => last + n as f64,
+            MetricUpdate::CounterAbsolute(n) => n as f64,
+            MetricUpdate::GaugeSet(value) | MetricUpdate::Histogram(value) => value,
+            MetricUpdate::GaugeIncrement(value) => last + value,
+            MetricUpdate::GaugeDecrement(value) => last - value,
+        };
+        series.points.push((entry.timestamp, value));
+    }
+    series
+}
+
+/// A `metrics` recorder writing updates as metric records to a Logger.
+///
+/// The Logger is shared by all threads updating metrics, behind a
+/// mutex, and can be used for log records as well through
+/// `with_logger`, so logs and metrics end up in the same stream.
+/// Clones share the Logger: keep one to log with and flush after
+/// installing another with `metrics::set_global_recorder`.
+///
+/// Updates that can't be written are counted as dropped records (see
+/// `Logger::set_instrumentation`).
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{BufferHandler, log_record};
+/// # use binary_logger::metrics::BinaryRecorder;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let recorder = BinaryRecorder::<65536>::new(NullHandler);
+/// metrics::with_local_recorder(&recorder, || {
+///     metrics::counter!("requests", "method" => "GET").increment(1);
+///     metrics::gauge!("queue_depth").set(12.0);
+///     metrics::histogram!("latency_us").record(250.0);
+/// });
+/// recorder.with_logger(|logger| log_record!(logger, "Served {} requests", 1)).unwrap();
+/// recorder.flush();
+/// ```
+#[cfg(feature = "metrics")]
+pub struct BinaryRecorder<const CAP: usize> {
+    shared: Arc<Shared<CAP>>,
+}
+
+#[cfg(feature = "metrics")]
+struct Shared<const CAP: usize> {
+    logger: Mutex<SendLogger<CAP>>,
+    keys: Mutex<HashMap<Key, u32>>,
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> BinaryRecorder<CAP> {
+    /// Creates a recorder writing to a new Logger with `handler`.
+    pub fn new(handler: impl BufferHandler + Send + 'static) -> Self {
+        Self::from_logger_unchecked(Logger::new(handler))
+    }
+
+    /// Creates a recorder writing to `logger`, which may have been
+    /// configured with a codec, channels or audit mode first.
+    ///
+    /// # Safety
+    ///
+    /// The logger's `BufferHandler` must be safe to call from any
+    /// thread, as if it were `Send`.
+    pub unsafe fn from_logger(logger: Logger<CAP>) -> Self {
+        Self::from_logger_unchecked(logger)
+    }
+
+    fn from_logger_unchecked(logger: Logger<CAP>) -> Self {
+        BinaryRecorder {
+            shared: Arc::new(Shared {
+                logger: Mutex::new(SendLogger(logger)),
+                keys: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the Logger.
+    ///
+    /// Metric updates from `f` itself would wait for the Logger
+    /// forever, so `f` must not update metrics.
+    pub fn with_logger<R>(&self, f: impl FnOnce(&mut Logger<CAP>) -> R) -> R {
+        f(&mut self.shared.logger.lock().unwrap().0)
+    }
+
+    /// Hands the Logger's current buffer to its handler.
+    pub fn flush(&self) {
+        self.with_logger(|logger| logger.flush());
+    }
+
+    /// Returns the handle of a metric, interning its key on first use.
+    fn handle(&self, key: &Key) -> Arc<MetricHandle<CAP>> {
+        let key_id = *self.shared.keys.lock().unwrap().entry(key.clone()).or_insert_with(|| {
+            let mut name = key.name().to_string();
+            let mut labels = key.labels().peekable();
+            if labels.peek().is_some() {
+                let labels: Vec<String> = labels.map(|label| format!("{}={}", label.key(), label.value())).collect();
+                name = format!("{}{{{}}}", name, labels.join(","));
+            }
+            register_string(Box::leak(name.into_boxed_str()))
+        });
+        Arc::new(MetricHandle { shared: self.shared.clone(), key_id })
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> Clone for BinaryRecorder<CAP> {
+    fn clone(&self) -> Self {
+        BinaryRecorder { shared: self.shared.clone() }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> Recorder for BinaryRecorder<CAP> {
+    // Descriptions and units are not stored in the log
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.handle(key))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(self.handle(key))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(self.handle(key))
+    }
+}
+
+/// Writes the updates of one metric.
+#[cfg(feature = "metrics")]
+struct MetricHandle<const CAP: usize> {
+    shared: Arc<Shared<CAP>>,
+    key_id: u32,
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> MetricHandle<CAP> {
+    fn write(&self, update: MetricUpdate) {
+        let mut payload = [0u8; MAX_UPDATE_SIZE];
+        let len = update.encode(&mut payload);
+        let logger = &mut self.shared.logger.lock().unwrap().0;
+        if logger.write_metric(self.key_id, &payload[..len]).is_err() {
+            logger.note_dropped(1);
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> CounterFn for MetricHandle<CAP> {
+    fn increment(&self, value: u64) {
+        self.write(MetricUpdate::CounterIncrement(value));
+    }
+
+    fn absolute(&self, value: u64) {
+        self.write(MetricUpdate::CounterAbsolute(value));
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> GaugeFn for MetricHandle<CAP> {
+    fn increment(&self, value: f64) {
+        self.write(MetricUpdate::GaugeIncrement(value));
+    }
+
+    fn decrement(&self, value: f64) {
+        self.write(MetricUpdate::GaugeDecrement(value));
+    }
+
+    fn set(&self, value: f64) {
+        self.write(MetricUpdate::GaugeSet(value));
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<const CAP: usize> HistogramFn for MetricHandle<CAP> {
+    fn record(&self, value: f64) {
+        self.write(MetricUpdate::Histogram(value));
+    }
+}
@@ -0,0 +1,229 @@
+//! A Logger per thread, created on demand and managed together.
+//!
+//! A Logger is single-threaded by design, so a multi-threaded program
+//! needs one per thread, and something to reach all of them for shutdown
+//! flushes, monitoring and level changes. `LoggerPool` is that layer: each
+//! thread gets its own Logger on first use, kept in thread-local storage,
+//! and the pool tracks every live Logger so `flush_all`, `metrics_all` and
+//! `set_level_all` can act on all of them.
+//!
+//! Logging takes an uncontended lock on the thread's own Logger, which
+//! `flush_all` and the other pool-wide calls take in turn. When a thread
+//! exits its Logger is dropped, which hands its last buffer to its handler.
+//! Loggers belong to their threads, so they outlive a pool dropped before
+//! the threads exit.
+//!
+//! With `LoggerPool::buffer_pool` the Loggers lease their buffers from a
+//! shared `BufferPool`, which bounds the memory of all threads together.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use crate::binary_logger::{BufferHandler, Logger, LoggerStats, SendLogger};
+use crate::buffer_pool::BufferPool;
+use crate::codec::{Codec, RawCodec};
+use crate::level::Level;
+
+/// Source of pool IDs, which key the thread-local Loggers.
+static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's Logger in each pool it has used, by pool ID
+    static LOCAL_LOGGERS: RefCell<Vec<(u64, Arc<dyn Any + Send + Sync>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The Logger of one thread, as registered in its pool.
+struct Slot<const CAP: usize> {
+    thread: String,
+    logger: Mutex<SendLogger<CAP>>,
+}
+
+/// Creates each new Logger, with the pool's codec and buffers
+type LoggerFactory<const CAP: usize> = dyn Fn(&'static dyn Codec, Option<Arc<BufferPool<CAP>>>) -> SendLogger<CAP> + Send + Sync;
+
+/// Settings applied to each new Logger
+type Configure<const CAP: usize> = dyn Fn(&mut Logger<CAP>) + Send + Sync;
+
+/// Metrics of one thread's Logger, see `LoggerPool::metrics_all`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggerMetrics {
+    /// Name of the thread, or its ID if it has none
+    pub thread: String,
+
+    /// What the Logger has written
+    pub stats: LoggerStats,
+}
+
+/// Creates a Logger per thread on first use and keeps track of them.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{BufferHandler, log_record_at};
+/// # use binary_logger::level::Level;
+/// # use binary_logger::pool::LoggerPool;
+/// # use std::sync::Arc;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let pool = Arc::new(LoggerPool::<65536>::new(|| NullHandler));
+/// let workers: Vec<_> = (0..4).map(|i| {
+///     let pool = pool.clone();
+///     std::thread::spawn(move || {
+///         pool.with(|logger| log_record_at!(logger, Level::Info, "Worker {} started", i)).unwrap();
+///     })
+/// }).collect();
+/// pool.with(|logger| log_record_at!(logger, Level::Info, "Main thread", )).unwrap();
+///
+/// pool.set_level_all(Level::Warn);
+/// pool.flush_all();
+/// for metrics in pool.metrics_all() {
+///     println!("{}: {} records", metrics.thread, metrics.stats.records_written);
+/// }
+/// # for worker in workers { worker.join().unwrap(); }
+/// ```
+pub struct LoggerPool<const CAP: usize> {
+    id: u64,
+    make_logger: Box<LoggerFactory<CAP>>,
+    codec: &'static dyn Codec,
+    buffers: Option<Arc<BufferPool<CAP>>>,
+    configure: Option<Box<Configure<CAP>>>,
+    level: Mutex<Option<Level>>,
+    slots: Mutex<Vec<Weak<Slot<CAP>>>>,
+}
+
+impl<const CAP: usize> LoggerPool<CAP> {
+    /// Creates a pool whose Loggers write to handlers made by `make_handler`,
+    /// called once per thread.
+    ///
+    /// Handlers must be `Send`, since pool-wide calls such as `flush_all`
+    /// run them on the calling thread.
+    pub fn new<H>(make_handler: impl Fn() -> H + Send + Sync + 'static) -> Self
+    where
+        H: BufferHandler + Send + 'static,
+    {
+        LoggerPool {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            make_logger: Box::new(move |codec, buffers| SendLogger(match buffers {
+                Some(buffers) => Logger::with_buffer_pool(make_handler(), codec, buffers),
+                None => Logger::with_codec(make_handler(), codec),
+            })),
+            codec: &RawCodec,
+            buffers: None,
+            configure: None,
+            level: Mutex::new(None),
+            slots: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Makes new Loggers encode record arguments with `codec`, as with
+    /// `Logger::with_codec`.
+    pub fn codec(mut self, codec: &'static dyn Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Makes new Loggers lease their buffers from `buffers`, as with
+    /// `Logger::with_buffer_pool`, instead of each allocating two.
+    pub fn buffer_pool(mut self, buffers: Arc<BufferPool<CAP>>) -> Self {
+        self.buffers = Some(buffers);
+        self
+    }
+
+    /// Runs `configure` on each new Logger before its first use, to enable
+    /// options such as `Logger::set_global_sequence`.
+    pub fn configure(mut self, configure: impl Fn(&mut Logger<CAP>) + Send + Sync + 'static) -> Self {
+        self.configure = Some(Box::new(configure));
+        self
+    }
+
+    /// Runs `f` with the calling thread's Logger, creating it on first use.
+    ///
+    /// `f` must not use the same pool again, which would wait for the
+    /// Logger forever.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Logger<CAP>) -> R) -> R {
+        let slot = self.local_slot();
+        let mut logger = slot.logger.lock().unwrap();
+        f(&mut logger.0)
+    }
+
+    /// Hands the current buffer of every Logger to its handler.
+    pub fn flush_all(&self) {
+        self.for_each(|_, logger| logger.flush());
+    }
+
+    /// Returns the metrics of every live Logger, in order of creation.
+    pub fn metrics_all(&self) -> Vec<LoggerMetrics> {
+        let mut metrics = Vec::new();
+        self.for_each(|thread, logger| metrics.push(LoggerMetrics { thread: thread.to_string(), stats: logger.stats() }));
+        metrics
+    }
+
+    /// Sets the level of every Logger, including those created later
+    /// (see `Logger::set_level`).
+    pub fn set_level_all(&self, level: Level) {
+        *self.level.lock().unwrap() = Some(level);
+        self.for_each(|_, logger| logger.set_level(level));
+    }
+
+    /// Returns the number of live Loggers, one per thread that used the
+    /// pool and hasn't exited.
+    pub fn len(&self) -> usize {
+        self.live_slots().len()
+    }
+
+    /// Returns whether no thread has a Logger in the pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs `f` with each live Logger and the name of its thread, in order
+    /// of creation.
+    ///
+    /// Each Logger is locked while `f` runs, so its thread waits to log.
+    pub fn for_each(&self, mut f: impl FnMut(&str, &mut Logger<CAP>)) {
+        for slot in self.live_slots() {
+            let mut logger = slot.logger.lock().unwrap();
+            f(&slot.thread, &mut logger.0);
+        }
+    }
+
+    /// Returns the Loggers of threads that are still running, forgetting
+    /// the others.
+    fn live_slots(&self) -> Vec<Arc<Slot<CAP>>> {
+        let mut slots = self.slots.lock().unwrap();
+        slots.retain(|slot| slot.strong_count() > 0);
+        slots.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Returns the calling thread's Logger, creating and registering it on
+    /// first use.
+    fn local_slot(&self) -> Arc<Slot<CAP>> {
+        let found = LOCAL_LOGGERS.with(|locals| {
+            locals.borrow().iter()
+                .find(|(id, _)| *id == self.id)
+                .map(|(_, slot)| slot.clone())
+        });
+        if let Some(slot) = found.and_then(|slot| slot.downcast::<Slot<CAP>>().ok()) {
+            return slot;
+        }
+
+        let mut logger = (self.make_logger)(self.codec, self.buffers.clone());
+        if let Some(configure) = &self.configure {
+            configure(&mut logger.0);
+        }
+        if let Some(level) = *self.level.lock().unwrap() {
+            logger.0.set_level(level);
+        }
+        let current = thread::current();
+        let thread = current.name().map_or_else(|| format!("{:?}", current.id()), str::to_string);
+        let slot = Arc::new(Slot { thread, logger: Mutex::new(logger) });
+
+        self.slots.lock().unwrap().push(Arc::downgrade(&slot));
+        LOCAL_LOGGERS.with(|locals| locals.borrow_mut().push((self.id, slot.clone())));
+        slot
+    }
+}
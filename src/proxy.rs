@@ -0,0 +1,344 @@
+#![allow(dead_code)]
+
+//! Live decoding of binary log streams, backing the `blog-proxy` tool.
+//!
+//! Application hosts send their Logger's buffers as they are over TCP, a
+//! Unix socket or, with their credentials, a seqpacket socket (see
+//! `seqpacket`): every buffer starts with its size, so a BufferHandler can
+//! write them to the socket unchanged and the stream needs no other framing.
+//! `DecodingProxy` accepts any number of such producers, decodes each
+//! connection with a `StreamReader` and writes every entry as a line of
+//! text or NDJSON to its destinations, so the application hosts never
+//! decode or format anything.
+//!
+//! The proxy resolves format strings from the streams, not from its own
+//! registry: producers publish their strings with
+//! `Logger::set_dictionary_channel` (or embed them with
+//! `Logger::set_embedded_dictionary`). Lines are written whole, so the
+//! lines of concurrent producers interleave without mixing, and each
+//! starts with, or has a field for, the producer's address.
+//!
+//! Under systemd socket activation, `activated_listeners` returns the
+//! sockets passed to the process.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use binary_logger::proxy::{DecodingProxy, Listener, OutputFormat};
+//! # use std::net::TcpListener;
+//! # use std::sync::Arc;
+//! # fn example() -> std::io::Result<()> {
+//! let proxy = Arc::new(DecodingProxy::new(OutputFormat::Ndjson).with_destination(std::io::stdout()));
+//! proxy.serve(&Listener::Tcp(TcpListener::bind("0.0.0.0:7070")?))
+//! # }
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(target_os = "linux")]
+use crate::seqpacket::{is_seqpacket, SeqpacketListener};
+use crate::log_reader::LogEntry;
+use crate::render::{render_line_with, RenderOptions};
+use crate::stream::{ReaderOptions, StreamReader};
+use crate::verify::write_json_string;
+
+/// Most bytes of log data held for one connection unless
+/// `DecodingProxy::with_reader_options` says otherwise
+pub const DEFAULT_MAX_MEMORY: usize = 64 << 20;
+
+/// How a DecodingProxy writes entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `<producer> <line>`, with the line of `render::render_line_with`
+    #[default]
+    Text,
+
+    /// One JSON object per line with the fields `producer`, `timestamp_us`,
+    /// `time`, `level`, `channel`, `channel_name`, `sequence`, `format_id`
+    /// and `message`
+    Ndjson,
+}
+
+/// Counters of a DecodingProxy, see `DecodingProxy::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProxyStats {
+    /// Connections accepted, or handled with `handle_connection`
+    pub connections: u64,
+
+    /// Entries decoded and written
+    pub entries: u64,
+
+    /// Connections that ended with a read or decoding error
+    pub connection_errors: u64,
+
+    /// Destinations disabled after a failed write
+    pub failed_destinations: u64,
+}
+
+/// A socket a DecodingProxy accepts producers on.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+
+    /// Producers sending with `seqpacket::SeqpacketHandler`, named after
+    /// their credentials
+    #[cfg(target_os = "linux")]
+    Seqpacket(SeqpacketListener),
+}
+
+impl Listener {
+    /// Waits for the next producer, returning its stream and address.
+    ///
+    /// Unix clients rarely bind their end of the connection, so they are
+    /// named after the listening socket and `connection`, the number of
+    /// the connection.
+    fn accept(&self, connection: u64) -> io::Result<(Box<dyn Read + Send>, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                Ok((Box::new(stream), addr.to_string()))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let (stream, _) = listener.accept()?;
+                let path = listener.local_addr().ok()
+                    .and_then(|addr| addr.as_pathname().map(|path| path.display().to_string()))
+                    .unwrap_or_else(|| "unix".to_string());
+                Ok((Box::new(stream), format!("{}#{}", path, connection)))
+            }
+            #[cfg(target_os = "linux")]
+            Listener::Seqpacket(listener) => {
+                let connection = listener.accept()?;
+                let credentials = connection.peer_credentials();
+                Ok((Box::new(connection), credentials.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl FromRawFd for Listener {
+    /// Takes ownership of a listening socket, TCP, Unix or, on Linux, Unix
+    /// seqpacket.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        #[cfg(target_os = "linux")]
+        if is_seqpacket(fd) {
+            return Listener::Seqpacket(SeqpacketListener::from_raw_fd(fd));
+        }
+        let unix = UnixListener::from_raw_fd(fd);
+        // Fails for sockets of other families
+        if unix.local_addr().is_ok() {
+            Listener::Unix(unix)
+        } else {
+            Listener::Tcp(TcpListener::from_raw_fd(unix.into_raw_fd()))
+        }
+    }
+}
+
+/// Returns the listening sockets passed by systemd socket activation, or
+/// none when the process wasn't socket activated.
+///
+/// Follows `sd_listen_fds`: the sockets are the `LISTEN_FDS` descriptors
+/// from 3 on, when `LISTEN_PID` is this process. Call it once, as the
+/// returned listeners own the descriptors.
+#[cfg(unix)]
+pub fn activated_listeners() -> Vec<Listener> {
+    const FIRST_FD: RawFd = 3;
+    let for_us = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse().ok()) == Some(std::process::id());
+    let count: RawFd = std::env::var("LISTEN_FDS").ok().and_then(|count| count.parse().ok()).unwrap_or(0);
+    if !for_us {
+        return Vec::new();
+    }
+    // The descriptors were passed to this process to own
+    (FIRST_FD..FIRST_FD + count).map(|fd| unsafe { Listener::from_raw_fd(fd) }).collect()
+}
+
+/// A destination of decoded lines.
+struct Destination {
+    writer: Mutex<Box<dyn Write + Send>>,
+
+    /// Set by a failed write, after which the destination is skipped
+    failed: AtomicBool,
+}
+
+/// Called when a connection ends, see `DecodingProxy::with_disconnect_hook`.
+type DisconnectHook = Box<dyn Fn(&str, &io::Result<u64>) + Send + Sync>;
+
+/// Decodes binary log streams from producers and writes their entries as
+/// text lines to a set of destinations.
+pub struct DecodingProxy {
+    format: OutputFormat,
+    render: RenderOptions,
+    reader_options: ReaderOptions,
+    destinations: Vec<Destination>,
+    on_disconnect: Option<DisconnectHook>,
+    connections: AtomicU64,
+    entries: AtomicU64,
+    connection_errors: AtomicU64,
+    failed_destinations: AtomicU64,
+}
+
+impl DecodingProxy {
+    /// Creates a proxy writing entries in `format`, without destinations.
+    ///
+    /// Each connection holds at most `DEFAULT_MAX_MEMORY` bytes of log data
+    /// unless `with_reader_options` says otherwise.
+    pub fn new(format: OutputFormat) -> Self {
+        DecodingProxy {
+            format,
+            render: RenderOptions::default(),
+            reader_options: ReaderOptions { max_memory: Some(DEFAULT_MAX_MEMORY), ..Default::default() },
+            destinations: Vec::new(),
+            on_disconnect: None,
+            connections: AtomicU64::new(0),
+            entries: AtomicU64::new(0),
+            connection_errors: AtomicU64::new(0),
+            failed_destinations: AtomicU64::new(0),
+        }
+    }
+
+    /// Renders entry times and messages according to `options`.
+    pub fn with_render_options(mut self, options: RenderOptions) -> Self {
+        self.render = options;
+        self
+    }
+
+    /// Decodes each connection with `options`, which bound the memory a
+    /// producer can make the proxy hold.
+    pub fn with_reader_options(mut self, options: ReaderOptions) -> Self {
+        self.reader_options = options;
+        self
+    }
+
+    /// Adds a destination for the decoded lines, such as a file, standard
+    /// output or a connection to a log collector.
+    ///
+    /// A destination whose write fails is skipped from then on.
+    pub fn with_destination(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.destinations.push(Destination { writer: Mutex::new(Box::new(writer)), failed: AtomicBool::new(false) });
+        self
+    }
+
+    /// Calls `hook` with the producer's address and the number of entries
+    /// decoded, or the error that ended it, whenever a connection ends.
+    pub fn with_disconnect_hook(mut self, hook: impl Fn(&str, &io::Result<u64>) + Send + Sync + 'static) -> Self {
+        self.on_disconnect = Some(Box::new(hook));
+        self
+    }
+
+    /// Returns the proxy's counters.
+    pub fn stats(&self) -> ProxyStats {
+        ProxyStats {
+            connections: self.connections.load(Ordering::Relaxed),
+            entries: self.entries.load(Ordering::Relaxed),
+            connection_errors: self.connection_errors.load(Ordering::Relaxed),
+            failed_destinations: self.failed_destinations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Accepts producers on `listener` until accepting fails, decoding
+    /// each connection on a thread of its own.
+    pub fn serve(self: &Arc<Self>, listener: &Listener) -> io::Result<()> {
+        for connection in 1.. {
+            let (source, producer) = listener.accept(connection)?;
+            let proxy = self.clone();
+            thread::spawn(move || {
+                let _ = proxy.handle_connection(source, &producer);
+            });
+        }
+        Ok(())
+    }
+
+    /// Decodes the stream of one producer until it ends, writing its
+    /// entries to the destinations, and returns the number of entries.
+    ///
+    /// `producer` names the source in the lines written.
+    pub fn handle_connection(&self, source: impl Read, producer: &str) -> io::Result<u64> {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        let mut reader = StreamReader::with_options(source, self.reader_options);
+        let mut entries = 0;
+        let result = loop {
+            match reader.read_entry() {
+                Ok(Some(entry)) => {
+                    let line = self.line(&entry, producer);
+                    self.write_line(line.as_bytes());
+                    self.entries.fetch_add(1, Ordering::Relaxed);
+                    entries += 1;
+                }
+                Ok(None) => break Ok(entries),
+                Err(e) => {
+                    self.connection_errors.fetch_add(1, Ordering::Relaxed);
+                    break Err(e);
+                }
+            }
+        };
+        if let Some(hook) = &self.on_disconnect {
+            hook(producer, &result);
+        }
+        result
+    }
+
+    /// Renders an entry as a line, with its newline.
+    fn line(&self, entry: &LogEntry, producer: &str) -> String {
+        let mut line = String::new();
+        match self.format {
+            OutputFormat::Text => {
+                line.push_str(producer);
+                line.push(' ');
+                line.push_str(&render_line_with(entry, &self.render));
+            }
+            OutputFormat::Ndjson => {
+                line.push_str("{\"producer\":");
+                write_json_string(&mut line, producer);
+                let micros = entry.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros();
+                let _ = write!(line, ",\"timestamp_us\":{},\"time\":", micros);
+                write_json_string(&mut line, &self.render.format_time(entry.timestamp));
+                line.push_str(",\"level\":");
+                match entry.level {
+                    Some(level) => write_json_string(&mut line, level.as_str()),
+                    None => line.push_str("null"),
+                }
+                let _ = write!(line, ",\"channel\":{},\"channel_name\":", entry.channel);
+                match entry.channel_name() {
+                    Some(name) => write_json_string(&mut line, name),
+                    None => line.push_str("null"),
+                }
+                line.push_str(",\"sequence\":");
+                match entry.sequence {
+                    Some(sequence) => { let _ = write!(line, "{}", sequence); }
+                    None => line.push_str("null"),
+                }
+                let _ = write!(line, ",\"format_id\":{},\"message\":", entry.format_id);
+                write_json_string(&mut line, &entry.format_with(&self.render));
+                line.push('}');
+            }
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Writes a line to every destination that hasn't failed.
+    fn write_line(&self, line: &[u8]) {
+        for destination in &self.destinations {
+            if destination.failed.load(Ordering::Relaxed) {
+                continue;
+            }
+            let mut writer = destination.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if writer.write_all(line).and_then(|()| writer.flush()).is_err()
+                && !destination.failed.swap(true, Ordering::Relaxed) {
+                self.failed_destinations.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
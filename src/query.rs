@@ -0,0 +1,254 @@
+#![allow(dead_code)]
+
+//! Regex queries over decoded logs.
+//!
+//! `LogQuery` is the library form of `blog-grep`: it narrows the records to
+//! decode with a `search::FormatFilter`, renders the candidates with
+//! `render::render_line_with` and yields those the regex matches, optionally with
+//! surrounding entries as context.
+
+use std::collections::VecDeque;
+use regex::Regex;
+use crate::log_reader::{LogEntry, LogReader};
+use crate::render::{RenderCache, RenderOptions, render_line_cached, render_line_with};
+use crate::search::FormatFilter;
+
+/// An entry matched by a query.
+#[derive(Debug)]
+pub struct QueryHit {
+    /// The decoded entry
+    pub entry: LogEntry,
+
+    /// The entry rendered as a line
+    pub line: String,
+
+    /// True for entries yielded as context around a match rather than
+    /// matching themselves
+    pub is_context: bool,
+
+    /// True if entries were left out between the previous hit and this one
+    ///
+    /// Only set when the query has context, where it marks the start of a
+    /// new group (`grep -C` prints `--` there).
+    pub gap_before: bool,
+}
+
+/// A search for entries whose rendered line matches a regex.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::LogReader;
+/// # use binary_logger::query::LogQuery;
+/// # use regex::Regex;
+/// # fn example(data: &[u8]) {
+/// let query = LogQuery::matching(Regex::new(r"timeout after \d+ ms").unwrap());
+/// for hit in query.run(LogReader::new(data)) {
+///     println!("{}", hit.line);
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogQuery {
+    regex: Regex,
+    before: usize,
+    after: usize,
+    render: RenderOptions,
+    render_cache: usize,
+}
+
+impl LogQuery {
+    /// Creates a query for entries whose rendered line matches `regex`.
+    ///
+    /// The pre-filter is derived from the regex's pattern text, so options
+    /// should be given as inline flags (`(?x)`, `(?u)`) rather than through
+    /// `RegexBuilder`. Case-insensitivity is the exception: it is always
+    /// allowed for by the pre-filter.
+    pub fn matching(regex: Regex) -> Self {
+        LogQuery { regex, before: 0, after: 0, render: RenderOptions::default(), render_cache: 0 }
+    }
+
+    /// Also yields up to `before` entries preceding and `after` entries
+    /// following each match, like `grep -B before -A after`.
+    ///
+    /// Overlapping windows are merged, so no entry is yielded twice. Context
+    /// entries have to be decoded, so the pre-filter can no longer skip
+    /// decoding; it still avoids rendering entries that can't match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binary_logger::LogReader;
+    /// # use binary_logger::query::LogQuery;
+    /// # use regex::Regex;
+    /// # fn example(data: &[u8]) {
+    /// let query = LogQuery::matching(Regex::new("panicked").unwrap()).with_context(5, 2);
+    /// for hit in query.run(LogReader::new(data)) {
+    ///     if hit.gap_before {
+    ///         println!("--");
+    ///     }
+    ///     println!("{}{}", if hit.is_context { "  " } else { "> " }, hit.line);
+    /// }
+    /// # }
+    /// ```
+    pub fn with_context(mut self, before: usize, after: usize) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    /// Renders entries according to `options` instead of the defaults.
+    ///
+    /// The regex is matched against the line rendered this way, so a
+    /// pattern on the timestamp must match its configured form.
+    pub fn with_render_options(mut self, options: RenderOptions) -> Self {
+        self.render = options;
+        self
+    }
+
+    /// Renders entries through a `RenderCache` of `capacity` messages, so
+    /// repeated identical messages are formatted once. Worth it for logs
+    /// dominated by a few recurring messages; 0, the default, renders every
+    /// entry anew.
+    pub fn with_render_cache(mut self, capacity: usize) -> Self {
+        self.render_cache = capacity;
+        self
+    }
+
+    /// Returns the query's regex.
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Runs the query over a reader, returning an iterator over the hits.
+    ///
+    /// The reader's own channel filter and monotonic mode still apply; its
+    /// record filter is replaced by the query's pre-filter.
+    pub fn run<'a>(&self, mut reader: LogReader<'a>) -> QueryIter<'a> {
+        // Matching the pre-filter case-insensitively keeps it a superset of
+        // the regex however the regex was built
+        let mut filter = FormatFilter::new(&format!("(?i:{})", self.regex.as_str())).with_render_options(&self.render);
+        let has_context = self.before > 0 || self.after > 0;
+        let filter = if has_context {
+            reader.set_record_filter(None);
+            Some(filter)
+        } else {
+            reader.set_record_filter(Some(Box::new(move |format_id, channel| filter.may_match(format_id, channel))));
+            None
+        };
+
+        QueryIter {
+            reader,
+            regex: self.regex.clone(),
+            render: self.render.clone(),
+            cache: (self.render_cache > 0).then(|| RenderCache::new(self.render_cache)),
+            filter,
+            before: self.before,
+            after: self.after,
+            recent: VecDeque::with_capacity(self.before),
+            after_remaining: 0,
+            pending: VecDeque::new(),
+            yielded: false,
+            dropped: false,
+        }
+    }
+}
+
+/// Iterator over the hits of a `LogQuery`, in log order.
+pub struct QueryIter<'a> {
+    reader: LogReader<'a>,
+    regex: Regex,
+    render: RenderOptions,
+    cache: Option<RenderCache>,
+
+    /// Pre-filter applied per entry in context mode; otherwise the reader
+    /// applies it
+    filter: Option<FormatFilter>,
+    before: usize,
+    after: usize,
+
+    /// Up to `before` entries since the last hit, kept as leading context
+    recent: VecDeque<LogEntry>,
+
+    /// Entries still to be yielded as trailing context of the last match
+    after_remaining: usize,
+
+    /// Hits ready to be yielded
+    pending: VecDeque<QueryHit>,
+
+    /// Whether anything has been yielded yet
+    yielded: bool,
+
+    /// Whether an entry was passed over since the last hit
+    dropped: bool,
+}
+
+impl<'a> QueryIter<'a> {
+    /// Returns the underlying reader, e.g. for its channel statistics.
+    pub fn reader(&self) -> &LogReader<'a> {
+        &self.reader
+    }
+
+    /// Returns the query's render cache, if it has one.
+    pub fn render_cache(&self) -> Option<&RenderCache> {
+        self.cache.as_ref()
+    }
+
+    /// Renders an entry as a line, through the cache if there is one.
+    fn render(&mut self, entry: &LogEntry) -> String {
+        match &mut self.cache {
+            Some(cache) => render_line_cached(entry, &self.render, cache),
+            None => render_line_with(entry, &self.render),
+        }
+    }
+
+    /// Queues an entry to be yielded, rendering it if that hasn't happened yet.
+    fn push(&mut self, entry: LogEntry, line: Option<String>, is_context: bool) {
+        let line = line.unwrap_or_else(|| self.render(&entry));
+        let has_context = self.before > 0 || self.after > 0;
+        let gap_before = has_context && self.yielded && self.dropped;
+        self.pending.push_back(QueryHit { entry, line, is_context, gap_before });
+        self.yielded = true;
+        self.dropped = false;
+    }
+}
+
+impl Iterator for QueryIter<'_> {
+    type Item = QueryHit;
+
+    fn next(&mut self) -> Option<QueryHit> {
+        loop {
+            if let Some(hit) = self.pending.pop_front() {
+                return Some(hit);
+            }
+
+            let entry = self.reader.read_entry()?;
+            let candidate = match &mut self.filter {
+                Some(filter) => filter.may_match(entry.format_id, entry.channel),
+                None => true,
+            };
+            let line = if candidate { Some(self.render(&entry)) } else { None };
+
+            match line {
+                Some(line) if self.regex.is_match(&line) => {
+                    while let Some(context) = self.recent.pop_front() {
+                        self.push(context, None, true);
+                    }
+                    self.push(entry, Some(line), false);
+                    self.after_remaining = self.after;
+                }
+                line if self.after_remaining > 0 => {
+                    self.after_remaining -= 1;
+                    self.push(entry, line, true);
+                }
+                _ => {
+                    self.recent.push_back(entry);
+                    if self.recent.len() > self.before {
+                        self.recent.pop_front();
+                        self.dropped = true;
+                    }
+                }
+            }
+        }
+    }
+}
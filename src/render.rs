@@ -0,0 +1,533 @@
+#![allow(dead_code)]
+
+//! Plain-text rendering of decoded entries.
+//!
+//! Tools that present binary logs to text-based workflows (`blog-mount`,
+//! search) render every entry as one line in the same format, so output from
+//! one can be compared with or piped into another. How timestamps are shown
+//! is a read-side choice (`RenderOptions`): the log itself only stores
+//! microseconds since the UNIX epoch.
+//!
+//! Logs often repeat the same message millions of times ("heartbeat ok").
+//! A `RenderCache` keeps recently formatted messages by format and payload,
+//! so such entries are formatted once (`render_line_cached`). Caching costs
+//! more than it saves on logs of mostly distinct messages, so it is opt-in.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use crate::log_reader::{LogEntry, LogReader, LogValue};
+use crate::string_registry::LogStr;
+
+/// Time zone timestamps are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeZone {
+    /// Coordinated Universal Time
+    #[default]
+    Utc,
+
+    /// The reading machine's local time zone, with its daylight saving rules
+    Local,
+
+    /// A fixed offset from UTC, in seconds east of Greenwich
+    Fixed(i32),
+}
+
+/// Parses `utc`, `local` or an offset such as `+02:00`, `-0530` or `+09`.
+impl FromStr for TimeZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid time zone {:?}, expected utc, local or an offset like +02:00", s);
+        match s.to_ascii_lowercase().as_str() {
+            "utc" | "z" => return Ok(TimeZone::Utc),
+            "local" => return Ok(TimeZone::Local),
+            _ => {}
+        }
+
+        let sign = match s.as_bytes().first() {
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Err(invalid()),
+        };
+        let digits = s[1..].replace(':', "");
+        if !digits.bytes().all(|b| b.is_ascii_digit()) || !matches!(digits.len(), 2 | 4) {
+            return Err(invalid());
+        }
+        let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+        let minutes: i32 = digits.get(2..).filter(|m| !m.is_empty()).map_or(Ok(0), str::parse).map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(TimeZone::Fixed(sign * (hours * 3600 + minutes * 60)))
+    }
+}
+
+/// How timestamps are written.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// Seconds since the UNIX epoch, e.g. `1700000000.000123`
+    #[default]
+    Epoch,
+
+    /// RFC 3339, e.g. `2023-11-14T22:13:20.000123Z`
+    Rfc3339,
+
+    /// A `strftime`-style pattern as understood by `chrono`, e.g.
+    /// `%Y-%m-%d %H:%M:%S%.3f`. The pattern controls fractional seconds
+    /// itself; `RenderOptions::fraction_digits` does not apply.
+    Strftime(String),
+}
+
+/// Parses `epoch`, `rfc3339`, or any other text as a `strftime` pattern.
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "epoch" => Ok(TimeFormat::Epoch),
+            "rfc3339" => Ok(TimeFormat::Rfc3339),
+            pattern => {
+                let items = chrono::format::StrftimeItems::new(pattern);
+                if items.clone().any(|item| item == chrono::format::Item::Error) {
+                    return Err(format!("invalid time format {:?}", pattern));
+                }
+                Ok(TimeFormat::Strftime(pattern.to_string()))
+            }
+        }
+    }
+}
+
+/// Command-line flags the `blog-*` tools take for `RenderOptions`, each
+/// followed by its value; see `RenderOptions::set_flag`.
+pub const RENDER_FLAGS: [&str; 3] = ["--tz", "--time-format", "--time-digits"];
+
+/// Read-side rendering configuration.
+///
+/// The default renders timestamps as UNIX seconds with six fractional
+/// digits, which is what `render_line` and `LogEntry::format` produce.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::render::{RenderOptions, TimeFormat, TimeZone};
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// let options = RenderOptions {
+///     time_zone: TimeZone::Fixed(2 * 3600),
+///     time_format: TimeFormat::Rfc3339,
+///     fraction_digits: 3,
+/// };
+/// let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+/// assert_eq!(options.format_time(time), "2023-11-15T00:13:20.123+02:00");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Time zone for `Rfc3339` and `Strftime` timestamps
+    pub time_zone: TimeZone,
+
+    /// How timestamps are written
+    pub time_format: TimeFormat,
+
+    /// Fractional-second digits for `Epoch` and `Rfc3339` timestamps, 0 to 9
+    pub fraction_digits: u8,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            time_zone: TimeZone::Utc,
+            time_format: TimeFormat::Epoch,
+            fraction_digits: 6,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Sets the option for one of `RENDER_FLAGS` from its command-line value:
+    /// `--tz` takes a `TimeZone`, `--time-format` a `TimeFormat` and
+    /// `--time-digits` the number of fractional digits.
+    pub fn set_flag(&mut self, flag: &str, value: &str) -> Result<(), String> {
+        match flag {
+            "--tz" => self.time_zone = value.parse()?,
+            "--time-format" => self.time_format = value.parse()?,
+            "--time-digits" => {
+                self.fraction_digits = value.parse().ok().filter(|digits| *digits <= 9)
+                    .ok_or_else(|| format!("invalid time digits {:?}, expected 0 to 9", value))?;
+            }
+            _ => return Err(format!("unknown render flag {}", flag)),
+        }
+        Ok(())
+    }
+
+    /// Formats a point in time according to these options.
+    ///
+    /// Digits beyond the microseconds a log records are always zero.
+    pub fn format_time(&self, time: SystemTime) -> String {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        match &self.time_format {
+            TimeFormat::Epoch => format!("{}{}", since_epoch.as_secs(), self.fraction(since_epoch)),
+            TimeFormat::Rfc3339 => {
+                let local = self.in_zone(since_epoch);
+                let offset = if self.time_zone == TimeZone::Utc { "Z".to_string() } else { local.format("%:z").to_string() };
+                format!("{}{}{}", local.format("%Y-%m-%dT%H:%M:%S"), self.fraction(since_epoch), offset)
+            }
+            TimeFormat::Strftime(pattern) => {
+                let mut text = String::new();
+                if write!(text, "{}", self.in_zone(since_epoch).format(pattern)).is_err() {
+                    // Only patterns built without `from_str` get here
+                    return format!("<invalid time format {:?}>", pattern);
+                }
+                text
+            }
+        }
+    }
+
+    /// Returns the fractional seconds of `since_epoch` with the leading dot,
+    /// or nothing for 0 digits.
+    fn fraction(&self, since_epoch: Duration) -> String {
+        let digits = self.fraction_digits.min(9) as u32;
+        if digits == 0 {
+            return String::new();
+        }
+        let fraction = since_epoch.subsec_nanos() / 10u32.pow(9 - digits);
+        format!(".{:0width$}", fraction, width = digits as usize)
+    }
+
+    /// Converts a time since the UNIX epoch to the configured time zone.
+    fn in_zone(&self, since_epoch: Duration) -> DateTime<FixedOffset> {
+        let utc = DateTime::<Utc>::from_timestamp(since_epoch.as_secs() as i64, since_epoch.subsec_nanos())
+            .unwrap_or_default();
+        match self.time_zone {
+            TimeZone::Utc => utc.fixed_offset(),
+            TimeZone::Local => utc.with_timezone(&Local).fixed_offset(),
+            TimeZone::Fixed(seconds) => match FixedOffset::east_opt(seconds) {
+                Some(offset) => utc.with_timezone(&offset),
+                None => utc.fixed_offset(),
+            },
+        }
+    }
+}
+
+/// Renders an entry as a single line, without the trailing newline.
+///
+/// The line is the timestamp as UNIX seconds with microseconds, the level
+/// for entries that have one, the channel name in brackets for entries on a
+/// named channel, the tags in braces for tagged entries, and the formatted
+/// message. Line breaks inside the message are escaped so every entry stays
+/// on one line.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::render::render_line;
+/// # use binary_logger::LogReader;
+/// # fn example(data: &[u8]) {
+/// let mut reader = LogReader::new(data);
+/// while let Some(entry) = reader.read_entry() {
+///     // e.g. "1700000000.000123 Temperature: 25.5 C"
+///     println!("{}", render_line(&entry));
+/// }
+/// # }
+/// ```
+pub fn render_line(entry: &LogEntry) -> String {
+    render_line_with(entry, &RenderOptions::default())
+}
+
+/// Renders an entry as a single line like `render_line`, with the
+/// timestamp and any instant arguments written according to `options`.
+pub fn render_line_with(entry: &LogEntry, options: &RenderOptions) -> String {
+    line_with_message(entry, options, &entry.format_with(options))
+}
+
+/// Renders an entry as a single line like `render_line_with`, taking its
+/// message from `cache` when an identical entry was rendered recently.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::render::{RenderCache, RenderOptions, render_line_cached};
+/// # use binary_logger::LogReader;
+/// # fn example(data: &[u8]) {
+/// let mut cache = RenderCache::new(4096);
+/// let mut reader = LogReader::new(data);
+/// while let Some(entry) = reader.read_entry() {
+///     println!("{}", render_line_cached(&entry, &RenderOptions::default(), &mut cache));
+/// }
+/// println!("{} messages formatted", cache.stats().misses);
+/// # }
+/// ```
+pub fn render_line_cached(entry: &LogEntry, options: &RenderOptions, cache: &mut RenderCache) -> String {
+    let message = cache.message(entry, options);
+    line_with_message(entry, options, &message)
+}
+
+/// Writes the line of an entry around its formatted message.
+fn line_with_message(entry: &LogEntry, options: &RenderOptions, message: &str) -> String {
+    let mut line = options.format_time(entry.timestamp);
+    if let Some(level) = entry.level {
+        line.push(' ');
+        line.push_str(level.as_str());
+    }
+    if let Some(channel) = entry.channel_name() {
+        line.push_str(" [");
+        line.push_str(channel);
+        line.push(']');
+    }
+    if !entry.tags.is_empty() {
+        line.push_str(&format!(" {{{}}}", entry.tags));
+    }
+    line.push(' ');
+
+    // Most messages have nothing to escape and are copied as they are
+    if message.contains(['\\', '\n', '\r']) {
+        for c in message.chars() {
+            match c {
+                '\\' => line.push_str("\\\\"),
+                '\n' => line.push_str("\\n"),
+                '\r' => line.push_str("\\r"),
+                c => line.push(c),
+            }
+        }
+    } else {
+        line.push_str(message);
+    }
+    line
+}
+
+/// Renders every entry of a log as text, one line per entry.
+pub fn render_text(data: &[u8]) -> String {
+    render_text_with(data, &RenderOptions::default())
+}
+
+/// Renders every entry of a log as text like `render_text`, according to
+/// `options`.
+pub fn render_text_with(data: &[u8], options: &RenderOptions) -> String {
+    let mut text = String::new();
+    let mut reader = LogReader::new(data);
+    while let Some(entry) = reader.read_entry() {
+        text.push_str(&render_line_with(&entry, options));
+        text.push('\n');
+    }
+    text
+}
+
+/// Renders every entry of a log as text like `render_text_with`, taking
+/// repeated messages from `cache`.
+pub fn render_text_cached(data: &[u8], options: &RenderOptions, cache: &mut RenderCache) -> String {
+    let mut text = String::new();
+    let mut reader = LogReader::new(data);
+    while let Some(entry) = reader.read_entry() {
+        text.push_str(&render_line_cached(&entry, options, cache));
+        text.push('\n');
+    }
+    text
+}
+
+/// Hits and misses of a `RenderCache`, see `RenderCache::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderCacheStats {
+    /// Messages taken from the cache
+    pub hits: u64,
+
+    /// Messages formatted, including those of entries that can't be cached
+    pub misses: u64,
+}
+
+/// Least recently used cache of formatted messages, keyed by an entry's
+/// format and encoded arguments.
+///
+/// Two entries with the same format string, record kind and payload bytes
+/// have the same message, so only the first is formatted. Entries whose
+/// message depends on more than that, those with instant arguments or
+/// without a format string, are always formatted. The timestamp, level,
+/// channel and tags of a line are not part of the message and are rendered
+/// per entry.
+pub struct RenderCache {
+    capacity: usize,
+    slots: Vec<CacheSlot>,
+
+    /// Slot of each key hash
+    index: HashMap<u64, usize>,
+
+    /// Most and least recently used slots, `NO_SLOT` when empty
+    newest: usize,
+    oldest: usize,
+    stats: RenderCacheStats,
+}
+
+/// A cached message with the key it was formatted for, in the recency list.
+struct CacheSlot {
+    hash: u64,
+    format_id: u32,
+    format_string: Option<LogStr>,
+    custom_type: Option<u8>,
+    metric: bool,
+    payload: Vec<u8>,
+    message: String,
+    newer: usize,
+    older: usize,
+}
+
+/// End of the recency list
+const NO_SLOT: usize = usize::MAX;
+
+impl RenderCache {
+    /// A capacity suited to most logs: enough for the recurring messages of
+    /// a service, at a few hundred KiB.
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    /// Creates a cache keeping up to `capacity` messages; 0 caches nothing.
+    pub fn new(capacity: usize) -> Self {
+        RenderCache {
+            capacity,
+            slots: Vec::new(),
+            index: HashMap::new(),
+            newest: NO_SLOT,
+            oldest: NO_SLOT,
+            stats: RenderCacheStats::default(),
+        }
+    }
+
+    /// Returns the most messages kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of messages kept.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns whether no message is kept.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns the hits and misses so far.
+    pub fn stats(&self) -> RenderCacheStats {
+        self.stats
+    }
+
+    /// Returns the message of `entry`, as `LogEntry::format_with` would,
+    /// from the cache if possible.
+    pub fn message(&mut self, entry: &LogEntry, options: &RenderOptions) -> String {
+        let cacheable = entry.format_string.is_some()
+            && !entry.parameters.iter().any(|param| matches!(param, LogValue::Instant(_)));
+        if !cacheable || self.capacity == 0 {
+            self.stats.misses += 1;
+            return entry.format_with(options);
+        }
+
+        let hash = key_hash(entry);
+        let existing = self.index.get(&hash).copied();
+        if let Some(slot) = existing {
+            if self.slots[slot].matches(entry) {
+                self.stats.hits += 1;
+                self.unlink(slot);
+                self.link_newest(slot);
+                return self.slots[slot].message.clone();
+            }
+        }
+        self.stats.misses += 1;
+        let message = entry.format_with(options);
+
+        let slot = match existing {
+            // Another key with the same hash gives up its slot
+            Some(slot) => {
+                self.unlink(slot);
+                slot
+            }
+            None if self.slots.len() < self.capacity => {
+                self.slots.push(CacheSlot::new(hash));
+                self.slots.len() - 1
+            }
+            None => {
+                let slot = self.oldest;
+                self.unlink(slot);
+                self.index.remove(&self.slots[slot].hash);
+                slot
+            }
+        };
+        self.index.insert(hash, slot);
+        self.slots[slot].store(hash, entry, &message);
+        self.link_newest(slot);
+        message
+    }
+
+    /// Removes a slot from the recency list.
+    fn unlink(&mut self, slot: usize) {
+        let (newer, older) = (self.slots[slot].newer, self.slots[slot].older);
+        match newer {
+            NO_SLOT => self.newest = older,
+            newer => self.slots[newer].older = older,
+        }
+        match older {
+            NO_SLOT => self.oldest = newer,
+            older => self.slots[older].newer = newer,
+        }
+    }
+
+    /// Puts a slot that isn't in the recency list at its front.
+    fn link_newest(&mut self, slot: usize) {
+        self.slots[slot].newer = NO_SLOT;
+        self.slots[slot].older = self.newest;
+        match self.newest {
+            NO_SLOT => self.oldest = slot,
+            newest => self.slots[newest].newer = slot,
+        }
+        self.newest = slot;
+    }
+}
+
+impl CacheSlot {
+    /// Creates an empty slot, not yet in the recency list.
+    fn new(hash: u64) -> Self {
+        CacheSlot {
+            hash,
+            format_id: 0,
+            format_string: None,
+            custom_type: None,
+            metric: false,
+            payload: Vec::new(),
+            message: String::new(),
+            newer: NO_SLOT,
+            older: NO_SLOT,
+        }
+    }
+
+    /// Returns whether the slot holds the message of `entry`.
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.format_id == entry.format_id
+            && self.format_string == entry.format_string
+            && self.custom_type == entry.custom_type
+            && self.metric == entry.metric.is_some()
+            && self.payload == entry.raw_values
+    }
+
+    /// Keeps the message of `entry`, reusing the slot's allocations.
+    fn store(&mut self, hash: u64, entry: &LogEntry, message: &str) {
+        self.hash = hash;
+        self.format_id = entry.format_id;
+        self.format_string = entry.format_string.clone();
+        self.custom_type = entry.custom_type;
+        self.metric = entry.metric.is_some();
+        self.payload.clear();
+        self.payload.extend_from_slice(&entry.raw_values);
+        self.message.clear();
+        self.message.push_str(message);
+    }
+}
+
+/// Hashes the parts of an entry its message is formatted from.
+fn key_hash(entry: &LogEntry) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    entry.format_id.hash(&mut hasher);
+    entry.custom_type.hash(&mut hasher);
+    entry.metric.is_some().hash(&mut hasher);
+    entry.raw_values.hash(&mut hasher);
+    hasher.finish()
+}
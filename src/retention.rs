@@ -0,0 +1,235 @@
+#![allow(dead_code)]
+
+//! Expiry of log entries past the time to live of their channel.
+//!
+//! Privacy rules often bound how long some records may be kept: personal
+//! data for 30 days, say, while operational records stay for a year.
+//! `Logger::set_channel_retention` gives a channel a time to live, written
+//! into the stream as a retention table at the start of every data buffer.
+//! `Expiry` rewrites a log without the entries whose time to live has
+//! passed, working on the records' framing only: entries are never decoded,
+//! and the records kept are copied unchanged, except that the first record
+//! after a removed one gets its full header and a new time base when it
+//! depended on the removed record for its format ID or time. The
+//! `blog-expire` tool does this to files in place.
+//!
+//! Only log entries expire. Count tables, metric and application records
+//! and the dictionary are kept, and entries of channels without a time to
+//! live (or before the first retention table of their buffer) are kept.
+//! Sequence numbers of expired entries leave gaps. A hash-chained log
+//! (`Logger::set_audit_chain`) can't be changed without breaking the chain,
+//! so expiring any of its entries fails.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+//! # use binary_logger::retention::Expiry;
+//! # use binary_logger::string_registry::register_string;
+//! # use std::sync::{Arc, Mutex};
+//! # use std::time::{Duration, SystemTime};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! logger.set_channel_retention(register_string("pii"), Some(Duration::from_secs(30 * 86400)));
+//! log_record!(logger, channel: "pii", "User {} signed in", 42).unwrap();
+//! log_record!(logger, "Cache warmed in {} ms", 120).unwrap();
+//! # }
+//! # let data = data.lock().unwrap();
+//! // Two months later
+//! let now = SystemTime::now() + Duration::from_secs(60 * 86400);
+//! let expired = Expiry::new(now).expire(&data).unwrap();
+//! assert_eq!(expired.expired_entries(), 1);
+//! let mut reader = LogReader::new(&expired.data);
+//! assert_eq!(reader.read_entry().unwrap().format(), "Cache warmed in 120 ms");
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::binary_logger::{
+    BUFFER_HEADER_SIZE, RECORD_TYPE_NORMAL, RECORD_TYPE_BASE, RECORD_TYPE_TAGGED, RECORD_TYPE_STREAM_HEADER,
+    RECORD_TYPE_CHAIN, RECORD_TYPE_METADATA, RECORD_TYPE_RETENTION, RECORD_TYPE_PADDING, RECORD_TYPE_UNTIMED, RECORD_TYPE_TYPED, RECORD_TYPE_CLOCK_OFFSET, RECORD_TYPE_TAGGED_TYPED, RETENTION_ENTRY_SIZE,
+    FLAG_SAME_FORMAT, FLAG_DELTA_TIME,
+};
+use crate::efficient_clock::EPOCH_MICROS;
+use crate::handlers::{parse_record, push_header, InputTime};
+
+/// A segment of a log with its expired entries removed, see
+/// `Expiry::expire`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expired {
+    /// The segment without the expired entries
+    pub data: Vec<u8>,
+
+    /// Entries read, expired or not
+    pub entries: u64,
+
+    /// Entries removed, by channel
+    pub expired: BTreeMap<u32, u64>,
+}
+
+impl Expired {
+    /// Returns the number of entries removed.
+    pub fn expired_entries(&self) -> u64 {
+        self.expired.values().sum()
+    }
+}
+
+/// Removes the entries of a log whose channel's time to live has passed.
+///
+/// Several segments of one log, such as rotated files, go through the same
+/// Expiry in order, as records may be timed from a base in an earlier
+/// segment.
+#[derive(Debug, Clone)]
+pub struct Expiry {
+    /// Microseconds since the UNIX epoch of the time entries expire at
+    now: u64,
+
+    /// Timestamp state of the records read, as a reader has it
+    time: InputTime,
+
+    /// Whether records were removed since the last time base written,
+    /// so the next timed record must start a new one
+    time_gap: bool,
+}
+
+impl Expiry {
+    /// Creates an Expiry that removes the entries expired at `now`.
+    pub fn new(now: SystemTime) -> Self {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros().min(u64::MAX as u128) as u64;
+        Expiry { now, time: InputTime::default(), time_gap: false }
+    }
+
+    /// Returns the segment `data`, which follows the segments passed
+    /// before, without its expired entries.
+    ///
+    /// Fails with `InvalidData` on damaged buffers or records, and with
+    /// `InvalidInput` when entries would expire from a hash-chained log.
+    pub fn expire(&mut self, data: &[u8]) -> io::Result<Expired> {
+        let mut result = Expired { data: Vec::with_capacity(data.len()), ..Expired::default() };
+        let mut chained = false;
+        let mut start = 0;
+        while start < data.len() {
+            let size = data.get(start..start + BUFFER_HEADER_SIZE)
+                .map(|header| u64::from_le_bytes(header.try_into().unwrap()) as usize)
+                .filter(|&size| size >= BUFFER_HEADER_SIZE && size <= data.len() - start)
+                .ok_or_else(|| damaged(start, "buffer header"))?;
+            let buffer = &data[start..start + size];
+            chained |= self.expire_buffer(buffer, start, &mut result)?;
+            start += size;
+        }
+        if chained && !result.expired.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "the log is hash-chained: removing entries would break its audit chain"));
+        }
+        Ok(result)
+    }
+
+    /// Appends `buffer`, found at `offset` in the segment, to the result
+    /// without its expired entries. Returns whether it has a chain record.
+    fn expire_buffer(&mut self, buffer: &[u8], offset: usize, result: &mut Expired) -> io::Result<bool> {
+        let out = &mut result.data;
+        let out_start = out.len();
+        out.extend_from_slice(&[0; BUFFER_HEADER_SIZE]);
+
+        // Time to live in seconds of each channel, from the last table
+        let mut retention: Vec<(u32, u32)> = Vec::new();
+        // Whether records were removed since the last format ID written,
+        // so the next record must write its own
+        let mut format_gap = false;
+        let mut chained = false;
+        let mut last_format_id = 0;
+        let mut pos = BUFFER_HEADER_SIZE;
+        while pos < buffer.len() {
+            let record = parse_record(buffer, pos, last_format_id, &mut self.time)
+                .ok_or_else(|| damaged(offset + pos, "record"))?;
+            last_format_id = record.format_id;
+            pos = record.end;
+            let raw = &buffer[record.start..record.end.min(buffer.len())];
+
+            let is_entry = matches!(record.record_type, RECORD_TYPE_NORMAL | RECORD_TYPE_TAGGED | RECORD_TYPE_UNTIMED | RECORD_TYPE_TYPED | RECORD_TYPE_TAGGED_TYPED)
+                || record.record_type == RECORD_TYPE_BASE && record.format_id != 0;
+            let timed = !matches!(record.record_type,
+                RECORD_TYPE_STREAM_HEADER | RECORD_TYPE_CHAIN | RECORD_TYPE_METADATA | RECORD_TYPE_RETENTION | RECORD_TYPE_PADDING
+                | RECORD_TYPE_CLOCK_OFFSET);
+            match record.record_type {
+                RECORD_TYPE_RETENTION => {
+                    retention = buffer[record.payload.clone()].chunks_exact(RETENTION_ENTRY_SIZE)
+                        .map(|entry| (u16::from_le_bytes([entry[0], entry[1]]) as u32, u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]])))
+                        .collect();
+                }
+                RECORD_TYPE_CHAIN => chained = true,
+                _ => {}
+            }
+
+            if is_entry {
+                result.entries += 1;
+                let channel = record.channel;
+                let ttl = retention.iter().find(|&&(id, _)| id == channel).map(|&(_, ttl)| ttl as u64 * 1_000_000);
+                if let (Some(ttl), Some(micros)) = (ttl, record.micros) {
+                    if micros.saturating_add(ttl) <= self.now {
+                        *result.expired.entry(channel).or_default() += 1;
+                        if record.record_type == RECORD_TYPE_BASE {
+                            // Keep the base, which later records are timed from
+                            let base = &buffer[record.written.start..record.written.start + 8];
+                            push_header(out, RECORD_TYPE_BASE, 0, self.time.last_relative, 0, 8, &[]);
+                            out.extend_from_slice(base);
+                            self.time_gap = false;
+                        } else {
+                            self.time_gap = true;
+                        }
+                        format_gap = true;
+                        continue;
+                    }
+                }
+            }
+
+            // A record after removed ones can't rely on them for its time
+            // or format ID
+            let retime = timed && record.record_type != RECORD_TYPE_BASE && self.time_gap;
+            let rewrite = retime || format_gap && record.flags & FLAG_SAME_FORMAT != 0;
+            if !rewrite {
+                out.extend_from_slice(raw);
+            } else {
+                let mut relative = 0;
+                if timed {
+                    if let Some(base) = self.time.base {
+                        push_header(out, RECORD_TYPE_BASE, 0, 0, 0, 8, &[]);
+                        out.extend_from_slice(&(base + self.time.epoch * EPOCH_MICROS).to_le_bytes());
+                    }
+                    relative = self.time.last_relative;
+                }
+                let flags = record.flags & !(FLAG_SAME_FORMAT | FLAG_DELTA_TIME);
+                // The payload as written, with the base of a base record
+                let payload = &buffer[record.written.clone()];
+                // Untimed records get the time they were read with
+                let record_type = if record.record_type == RECORD_TYPE_UNTIMED { RECORD_TYPE_NORMAL } else { record.record_type };
+                push_header(out, record_type, flags, relative, record.format_id, payload.len(), &buffer[record.extra.clone()]);
+                out.extend_from_slice(payload);
+            }
+            if !out.len().is_multiple_of(2) {
+                out.push(0);
+            }
+            if timed {
+                self.time_gap = false;
+            }
+            format_gap = false;
+        }
+
+        let size = (out.len() - out_start) as u64;
+        out[out_start..out_start + BUFFER_HEADER_SIZE].copy_from_slice(&size.to_le_bytes());
+        Ok(chained)
+    }
+}
+
+/// Returns the error for a damaged part of the log at `offset`.
+fn damaged(offset: usize, what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("damaged {} at offset {:#x}", what, offset))
+}
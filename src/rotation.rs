@@ -0,0 +1,263 @@
+#![allow(dead_code)]
+
+//! Log files rotated by size and time, like log4rs's rolling appender but
+//! in the binary format.
+//!
+//! `RotatingFileHandler` writes to the active file `<name>.blog` in a
+//! directory. When the file would grow past its maximum size, or the wall
+//! clock enters a new rollover interval (a new UTC day with `daily`), the
+//! file is synced and atomically renamed to `<name>.<index>.blog`, with a
+//! six-digit index one above the last, and a new active file is started.
+//! A rotated name therefore only ever holds a complete file, and the oldest
+//! ones beyond `max_files` are removed. `segments` lists a log's files in
+//! stream order, rotated files (compacted ones included) then the active
+//! file, for `verify_segments`, `blog-verify`, `blog-grep` and the other
+//! tools taking the segments of one log oldest first.
+//!
+//! Rotation happens between buffers: a buffer is never split across files.
+//! With the dictionary channel (`Logger::set_dictionary_channel`), the
+//! handler keeps the metadata buffers it has seen and writes them at the
+//! start of every new file, so each file decodes on its own even once the
+//! files before it are removed. Without it, the files are segments of one
+//! log, and only the first holds the stream header and the strings
+//! embedded before it.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use binary_logger::{Logger, log_record};
+//! # use binary_logger::rotation::{segments, RotatingFileHandler};
+//! # fn example() -> std::io::Result<()> {
+//! // Daily files of at most 64 MiB, keeping the last 30
+//! let handler = RotatingFileHandler::new("/var/log/app", "orders")?
+//!     .max_file_size(64 << 20)
+//!     .max_files(30)
+//!     .daily();
+//! let mut logger = Logger::<65536>::new(handler);
+//! logger.set_dictionary_channel(true);
+//! log_record!(logger, "Order {} filled", 17).unwrap();
+//!
+//! for segment in segments("/var/log/app", "orders")? {
+//!     println!("{}", segment.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::binary_logger::BufferHandler;
+use crate::compact::{covered_segments, COMPRESSED_EXTENSION, LZ4_EXTENSION};
+use crate::log_reader::is_metadata_buffer;
+
+/// Extension of log files
+pub const EXTENSION: &str = "blog";
+
+/// Writes buffers to files rotated by size and time, see the module
+/// documentation.
+///
+/// Write failures make the handler panic, which the Logger treats by its
+/// `HandlerPanicPolicy`.
+pub struct RotatingFileHandler {
+    dir: PathBuf,
+    name: String,
+    max_file_size: Option<u64>,
+    max_files: Option<usize>,
+    interval: Option<Duration>,
+    state: Mutex<Rotation>,
+}
+
+/// The active file and what the handler carries between files.
+struct Rotation {
+    /// The active file, created on the first buffer after a rotation
+    file: Option<File>,
+    /// Bytes in the active file
+    bytes: u64,
+    /// Whether the active file holds a data buffer
+    has_data: bool,
+    /// Rollover interval the active file was started in
+    period: u64,
+    /// Index of the next rotated file
+    next_index: u64,
+    /// Metadata buffers seen, written again at the start of every file
+    metadata: Vec<Vec<u8>>,
+}
+
+impl RotatingFileHandler {
+    /// Creates a handler writing `<name>.blog` in `dir`, creating the
+    /// directory if needed, without size or time limits.
+    ///
+    /// An active file left by an earlier run is rotated first, so every
+    /// run starts a file of its own.
+    pub fn new(dir: impl AsRef<Path>, name: &str) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let next_index = rotated_files(&dir, name)?.last().map_or(1, |(index, _)| index + 1);
+        let handler = RotatingFileHandler {
+            dir,
+            name: name.to_string(),
+            max_file_size: None,
+            max_files: None,
+            interval: None,
+            state: Mutex::new(Rotation { file: None, bytes: 0, has_data: false, period: 0, next_index, metadata: Vec::new() }),
+        };
+        let active = handler.active_path();
+        if fs::metadata(&active).is_ok_and(|metadata| metadata.len() > 0) {
+            let mut state = handler.state.lock().unwrap_or_else(|e| e.into_inner());
+            handler.rotate(&mut state)?;
+        }
+        Ok(handler)
+    }
+
+    /// Rotates before a buffer would take the active file past `bytes`. A
+    /// buffer larger than that gets a file of its own.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Keeps at most `files` files, the active one included (at least 1),
+    /// removing the oldest rotated ones.
+    pub fn max_files(mut self, files: usize) -> Self {
+        self.max_files = Some(files.max(1));
+        self
+    }
+
+    /// Rotates whenever the wall clock enters a new multiple of `interval`
+    /// since the UNIX epoch.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Rotates at UTC midnight.
+    pub fn daily(self) -> Self {
+        self.interval(Duration::from_secs(86400))
+    }
+
+    /// Returns the path of the active file.
+    pub fn active_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.{}", self.name, EXTENSION))
+    }
+
+    /// Returns the rollover interval the wall clock is in.
+    fn current_period(&self) -> u64 {
+        let Some(interval) = self.interval else { return 0 };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        (now.as_nanos() / interval.as_nanos()) as u64
+    }
+
+    /// Closes the active file under the next rotated name and removes the
+    /// files beyond `max_files`.
+    fn rotate(&self, state: &mut Rotation) -> io::Result<()> {
+        if let Some(file) = state.file.take() {
+            file.sync_all()?;
+        }
+        let rotated = self.dir.join(format!("{}.{:06}.{}", self.name, state.next_index, EXTENSION));
+        fs::rename(self.active_path(), &rotated)?;
+        state.next_index += 1;
+        (state.bytes, state.has_data) = (0, false);
+
+        if let Some(max_files) = self.max_files {
+            let rotated = rotated_files(&self.dir, &self.name)?;
+            // The active file is about to be created again
+            for (_, path) in &rotated[..rotated.len().saturating_sub(max_files - 1)] {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `buffer` to the active file, rotating first if it is due.
+    fn write(&self, buffer: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let metadata = is_metadata_buffer(buffer);
+        if state.file.is_some() && state.has_data && !metadata {
+            let period = self.current_period();
+            let full = self.max_file_size.is_some_and(|max| state.bytes + buffer.len() as u64 > max);
+            if full || period != state.period {
+                self.rotate(&mut state)?;
+            }
+        }
+
+        let state = &mut *state;
+        if state.file.is_none() {
+            let mut file = File::create(self.active_path())?;
+            for kept in &state.metadata {
+                file.write_all(kept)?;
+                state.bytes += kept.len() as u64;
+            }
+            state.file = Some(file);
+            state.period = self.current_period();
+        }
+        state.file.as_mut().unwrap().write_all(buffer)?;
+        state.bytes += buffer.len() as u64;
+        match metadata {
+            true => state.metadata.push(buffer.to_vec()),
+            false => state.has_data = true,
+        }
+        Ok(())
+    }
+}
+
+impl BufferHandler for RotatingFileHandler {
+    fn handle_buffer(&self, buffer: &[u8]) {
+        if let Err(e) = self.write(buffer) {
+            panic!("cannot write {}: {}", self.active_path().display(), e);
+        }
+    }
+
+    fn sync(&self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(Err(e)) = state.file.as_ref().map(File::sync_data) {
+            panic!("cannot sync {}: {}", self.active_path().display(), e);
+        }
+    }
+}
+
+/// Returns the files of the log `name` in `dir` in stream order: the
+/// rotated files by index, compacted ones included (see `compact`), then
+/// the active file if there is one. Files a compacted one holds already,
+/// left by an interrupted compaction, are left out.
+pub fn segments(dir: impl AsRef<Path>, name: &str) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut segments: Vec<PathBuf> = rotated_files(dir, name)?.into_iter().map(|(_, path)| path).collect();
+    let covered: Vec<PathBuf> = segments.iter().flat_map(|segment| covered_segments(segment)).collect();
+    segments.retain(|segment| !covered.contains(segment));
+    let active = dir.join(format!("{}.{}", name, EXTENSION));
+    if active.is_file() {
+        segments.push(active);
+    }
+    Ok(segments)
+}
+
+/// Returns the rotated files of the log `name` in `dir`, with their
+/// indexes, in index order.
+fn rotated_files(dir: &Path, name: &str) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut rotated = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else { continue };
+        if let Some(index) = rotated_index(file_name, name) {
+            rotated.push((index, path));
+        }
+    }
+    rotated.sort();
+    Ok(rotated)
+}
+
+/// Returns the index of `file_name` if it is a rotated file of the log
+/// `name`, compacted or not.
+fn rotated_index(file_name: &str, name: &str) -> Option<u64> {
+    let file_name = [COMPRESSED_EXTENSION, LZ4_EXTENSION].iter()
+        .find_map(|ext| file_name.strip_suffix(ext).and_then(|rest| rest.strip_suffix('.')))
+        .unwrap_or(file_name);
+    let index = file_name.strip_prefix(name)?.strip_prefix('.')?.strip_suffix(EXTENSION)?.strip_suffix('.')?;
+    match index.len() >= 6 && index.bytes().all(|b| b.is_ascii_digit()) {
+        true => index.parse().ok(),
+        false => None,
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(dead_code)]
+
+//! Sampling by key: a deterministic subset of entities, logged completely.
+//!
+//! Sampling records uniformly keeps a random 1% of every user's events,
+//! which rarely leaves a complete story for any one of them. A `KeySampler`
+//! decides per key instead, such as a user or request ID: it hashes the key
+//! and keeps it if the hash falls below the rate, so the same keys are kept
+//! by every call site, thread, process and run, and all events of a kept
+//! key are logged.
+//!
+//! Keys are hashed with their `Hash` implementation and a fixed hash
+//! function, so a key must be passed with the same type everywhere (a
+//! `u64` user ID hashes differently from the same ID as `u32`; `&str` and
+//! `String` hash alike). Raising the rate keeps a superset of the keys kept
+//! before, so increasing it during an investigation doesn't lose the
+//! entities already being followed. Changing the seed selects another
+//! subset.
+//!
+//! Set a sampler on a Logger with `Logger::set_sampler` and log with
+//! `log_sampled!`, or call `KeySampler::keep` directly.
+
+use std::hash::{Hash, Hasher};
+
+/// Keeps a deterministic fraction of keys.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::sampling::KeySampler;
+/// let sampler = KeySampler::new(0.01);
+/// let kept = (0..100_000u64).filter(|user| sampler.keep(user)).count();
+/// assert!((800..1200).contains(&kept));
+///
+/// // The same users are kept every time
+/// assert_eq!(sampler.keep(&42u64), KeySampler::new(0.01).keep(&42u64));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySampler {
+    /// Keys whose hash is below this are kept; `u64::MAX` keeps every key
+    threshold: u64,
+    seed: u64,
+}
+
+impl KeySampler {
+    /// Creates a sampler keeping the fraction `rate` of keys, clamped to
+    /// 0.0 (none) to 1.0 (all).
+    pub fn new(rate: f64) -> Self {
+        let rate = if rate.is_nan() { 0.0 } else { rate.clamp(0.0, 1.0) };
+        let threshold = if rate >= 1.0 { u64::MAX } else { (rate * u64::MAX as f64) as u64 };
+        KeySampler { threshold, seed: 0 }
+    }
+
+    /// Selects another subset of keys for the same rate.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Returns the fraction of keys kept.
+    pub fn rate(&self) -> f64 {
+        self.threshold as f64 / u64::MAX as f64
+    }
+
+    /// Returns whether records for `key` are kept.
+    pub fn keep<K: Hash + ?Sized>(&self, key: &K) -> bool {
+        if self.threshold == u64::MAX {
+            return true;
+        }
+        let mut hasher = KeyHasher::new(self.seed);
+        key.hash(&mut hasher);
+        hasher.finish() < self.threshold
+    }
+}
+
+/// FNV-1a with a final mix, the same in every process, unlike
+/// `DefaultHasher` whose algorithm may change between Rust versions.
+pub(crate) struct KeyHasher(u64);
+
+impl KeyHasher {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub(crate) fn new(seed: u64) -> Self {
+        KeyHasher(Self::OFFSET ^ seed)
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        // FNV leaves similar keys with similar high bits; spread them over
+        // the whole range compared against the threshold (splitmix64)
+        let mut hash = self.0;
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^ (hash >> 31)
+    }
+}
@@ -0,0 +1,142 @@
+#![allow(dead_code)]
+
+//! Placement and priority of the threads handlers start.
+//!
+//! A handler writing on a thread of its own, such as
+//! `handlers::BackgroundWriter`, takes the cycles of whichever core the
+//! scheduler gives it. On a host whose latency-critical threads are pinned
+//! to some cores, that can be one of theirs. `ThreadScheduling` describes
+//! where such a thread may run and how it yields to others: the cores it is
+//! pinned to, and its nice value, where a higher value leaves the CPU to
+//! other threads first. Handlers apply it when their thread starts and
+//! fail to start if it can't be applied.
+//!
+//! Pinning and per-thread nice values are supported on Linux, through
+//! `sched_setaffinity` and `setpriority`. Elsewhere, applying anything but
+//! the default fails with `io::ErrorKind::Unsupported`. Lowering the nice
+//! value below the current one usually needs privileges (`CAP_SYS_NICE`).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use binary_logger::{Logger, log_record};
+//! # use binary_logger::handlers::BackgroundWriter;
+//! # use binary_logger::scheduling::ThreadScheduling;
+//! # use std::fs::File;
+//! # fn example() -> std::io::Result<()> {
+//! // Cores 0-5 run the trading threads, 7 is for housekeeping
+//! let scheduling = ThreadScheduling::new().pin_to([7]).nice(10);
+//! let writer = BackgroundWriter::with_scheduling(File::create("log.bin")?, 4, scheduling)?;
+//! let mut logger = Logger::<65536>::new(writer);
+//! log_record!(logger, "Order {} filled", 17).unwrap();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+
+/// Where a handler's thread runs and how it yields to other threads.
+///
+/// The default leaves the thread as it was started: on any core, with the
+/// nice value of the thread that started it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadScheduling {
+    cores: Vec<usize>,
+    nice: Option<i32>,
+}
+
+impl ThreadScheduling {
+    /// Creates settings that change nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins the thread to `cores`, numbered as the OS does (0 for the
+    /// first). The thread runs on any of them.
+    pub fn pin_to(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.cores = cores.into_iter().collect();
+        self.cores.sort_unstable();
+        self.cores.dedup();
+        self
+    }
+
+    /// Sets the thread's nice value, from -20 (favored) to 19 (yields to
+    /// every other thread).
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Returns the cores the thread is pinned to, empty if it isn't.
+    pub fn cores(&self) -> &[usize] {
+        &self.cores
+    }
+
+    /// Returns the nice value the thread gets, if any.
+    pub fn nice_value(&self) -> Option<i32> {
+        self.nice
+    }
+
+    /// Returns true if the settings change nothing.
+    pub fn is_default(&self) -> bool {
+        self.cores.is_empty() && self.nice.is_none()
+    }
+
+    /// Applies the settings to the calling thread.
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` for nice values out of
+    /// range and cores the OS can't address, with the OS error when it
+    /// refuses (no pinned core online, or a raised priority without the
+    /// privilege), and with `io::ErrorKind::Unsupported` off Linux. Pinning
+    /// is applied first, and stays if setting the nice value fails.
+    pub fn apply_to_current_thread(&self) -> io::Result<()> {
+        if self.is_default() {
+            return Ok(());
+        }
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("nice value {} out of -20..=19", nice)));
+            }
+        }
+        os::apply(&self.cores, self.nice)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::io;
+    use std::mem::{size_of, zeroed};
+
+    pub(super) fn apply(cores: &[usize], nice: Option<i32>) -> io::Result<()> {
+        if !cores.is_empty() {
+            let mut set: libc::cpu_set_t = unsafe { zeroed() };
+            for &core in cores {
+                if core >= libc::CPU_SETSIZE as usize {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("core {} beyond the {} the OS addresses", core, libc::CPU_SETSIZE)));
+                }
+                unsafe { libc::CPU_SET(core, &mut set) };
+            }
+            // 0 is the calling thread
+            if unsafe { libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some(nice) = nice {
+            // On Linux, nice values belong to threads
+            let thread = unsafe { libc::gettid() };
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, thread as libc::id_t, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod os {
+    use std::io;
+
+    pub(super) fn apply(_cores: &[usize], _nice: Option<i32>) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "thread pinning and nice values are only supported on Linux"))
+    }
+}
@@ -0,0 +1,248 @@
+#![allow(dead_code)]
+
+//! Argument schemas of format strings, checked when records are written.
+//!
+//! The binary format stores arguments without their types, so a call site
+//! that drifts from what readers expect, say a `u32` where a dashboard
+//! parses a `u64`, or an argument added to one copy of a format string,
+//! goes unnoticed until the log is read. A `SchemaRegistry` lists the
+//! argument types of format strings; a Logger given one with
+//! `Logger::set_schema_registry` checks every record of a listed format
+//! string before writing it, and fails the write with `InvalidInput`
+//! naming the first difference. The check runs in debug builds, and in
+//! release builds only in strict mode (`Logger::set_strict_schema`), as it
+//! costs a lookup per record.
+//!
+//! Records from `log_record!` and its siblings are checked against the
+//! kinds the macro captured (`f32`, strings, interned literals, ...).
+//! Records written with `Logger::write` or `write_args` have no kinds, so
+//! only their argument count and the sizes of fixed-size arguments are
+//! checked. Format strings without a schema are written unchecked.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, log_record};
+//! # use binary_logger::schema::{ArgType, SchemaRegistry};
+//! # use std::sync::Arc;
+//! # struct NullHandler;
+//! # impl BufferHandler for NullHandler {
+//! #     fn handle_buffer(&self, _data: &[u8]) {}
+//! # }
+//! let schema = SchemaRegistry::new()
+//!     .with_event("User {} logged in from {}", &[ArgType::U64, ArgType::Str]);
+//! let mut logger = Logger::<4096>::new(NullHandler);
+//! logger.set_schema_registry(Some(Arc::new(schema)));
+//! logger.set_strict_schema(true);
+//!
+//! log_record!(logger, "User {} logged in from {}", 42u64, String::from("10.0.0.7")).unwrap();
+//! // The call site passes a u32
+//! assert!(log_record!(logger, "User {} logged in from {}", 42u32, String::from("10.0.0.7")).is_err());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use crate::arg_types::{ArgCapture, ArgKind};
+use crate::string_registry::{get_string, register_string};
+
+/// The type of one argument in a schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgType {
+    /// A value of this many bytes whose type readers infer from its size,
+    /// such as an integer, `bool`, `char` or `f64`
+    Sized(usize),
+
+    /// `f32`
+    F32,
+
+    /// `half::f16`
+    F16,
+
+    /// `fixed::Fixed`
+    Fixed,
+
+    /// `instant::LogInstant`
+    Instant,
+
+    /// `error_chain::ErrorChain`
+    ErrorChain,
+
+    /// `backtrace::LogBacktrace`
+    Backtrace,
+
+    /// A string literal, interned by `log_record!`
+    Interned,
+
+    /// A `&str` or `String`
+    Str,
+
+    /// A `&[u8]` or `Vec<u8>`
+    Bytes,
+
+    /// A `loggable::Loggable` struct
+    Struct,
+
+    /// Any argument
+    Any,
+}
+
+impl ArgType {
+    pub const BOOL: ArgType = ArgType::Sized(1);
+    pub const U8: ArgType = ArgType::Sized(1);
+    pub const I8: ArgType = ArgType::Sized(1);
+    pub const U16: ArgType = ArgType::Sized(2);
+    pub const I16: ArgType = ArgType::Sized(2);
+    pub const U32: ArgType = ArgType::Sized(4);
+    pub const I32: ArgType = ArgType::Sized(4);
+    pub const CHAR: ArgType = ArgType::Sized(4);
+    pub const U64: ArgType = ArgType::Sized(8);
+    pub const I64: ArgType = ArgType::Sized(8);
+    pub const F64: ArgType = ArgType::Sized(8);
+    pub const U128: ArgType = ArgType::Sized(16);
+    pub const I128: ArgType = ArgType::Sized(16);
+
+    /// Returns whether an argument of `len` bytes, of `kind` if the writer
+    /// knows it, has this type.
+    pub(crate) fn matches(self, len: usize, kind: Option<ArgKind>) -> bool {
+        match (self, kind) {
+            (ArgType::Any, _) => true,
+            (ArgType::Sized(size), Some(ArgKind::Raw) | None) => len == size,
+            (ArgType::F32, Some(ArgKind::F32)) => true,
+            (ArgType::F32, None) => len == 4,
+            (ArgType::F16, Some(ArgKind::F16)) => true,
+            (ArgType::F16, None) => len == 2,
+            (ArgType::Fixed, Some(ArgKind::Fixed(_))) => true,
+            (ArgType::Instant, Some(ArgKind::Instant)) => true,
+            (ArgType::ErrorChain, Some(ArgKind::ErrorChain)) => true,
+            (ArgType::Backtrace, Some(ArgKind::Backtrace)) => true,
+            (ArgType::Interned, Some(ArgKind::Interned)) => true,
+            (ArgType::Interned, None) => len == 2 || len == 6,
+            (ArgType::Str, Some(ArgKind::Str)) => true,
+            (ArgType::Bytes, Some(ArgKind::Bytes)) => true,
+            (ArgType::Struct, Some(ArgKind::Struct)) => true,
+            // Variable-size encodings can't be told apart without a kind
+            (ArgType::Fixed | ArgType::Instant | ArgType::ErrorChain | ArgType::Backtrace | ArgType::Str | ArgType::Bytes | ArgType::Struct, None) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the kind a reader decodes an argument of this type as, when
+    /// the writer's kinds aren't known.
+    pub(crate) fn kind(self) -> Option<ArgKind> {
+        match self {
+            ArgType::F32 => Some(ArgKind::F32),
+            ArgType::F16 => Some(ArgKind::F16),
+            ArgType::Instant => Some(ArgKind::Instant),
+            ArgType::ErrorChain => Some(ArgKind::ErrorChain),
+            ArgType::Backtrace => Some(ArgKind::Backtrace),
+            ArgType::Interned => Some(ArgKind::Interned),
+            ArgType::Str => Some(ArgKind::Str),
+            ArgType::Bytes => Some(ArgKind::Bytes),
+            ArgType::Struct => Some(ArgKind::Struct),
+            // A Fixed's scale is only known to the writer
+            ArgType::Sized(_) | ArgType::Fixed | ArgType::Any => None,
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgType::Sized(size) => write!(f, "{}-byte value", size),
+            ArgType::F32 => f.write_str("f32"),
+            ArgType::F16 => f.write_str("f16"),
+            ArgType::Fixed => f.write_str("Fixed"),
+            ArgType::Instant => f.write_str("LogInstant"),
+            ArgType::ErrorChain => f.write_str("ErrorChain"),
+            ArgType::Backtrace => f.write_str("LogBacktrace"),
+            ArgType::Interned => f.write_str("interned literal"),
+            ArgType::Str => f.write_str("string"),
+            ArgType::Bytes => f.write_str("byte string"),
+            ArgType::Struct => f.write_str("struct"),
+            ArgType::Any => f.write_str("any argument"),
+        }
+    }
+}
+
+/// Describes an argument as written, for error messages.
+pub(crate) fn describe(len: usize, kind: Option<ArgKind>) -> String {
+    match kind {
+        Some(ArgKind::Raw) | None => ArgType::Sized(len).to_string(),
+        Some(ArgKind::F32) => ArgType::F32.to_string(),
+        Some(ArgKind::F16) => ArgType::F16.to_string(),
+        Some(ArgKind::Fixed(_)) => ArgType::Fixed.to_string(),
+        Some(ArgKind::Instant) => ArgType::Instant.to_string(),
+        Some(ArgKind::ErrorChain) => ArgType::ErrorChain.to_string(),
+        Some(ArgKind::Backtrace) => ArgType::Backtrace.to_string(),
+        Some(ArgKind::Interned) => ArgType::Interned.to_string(),
+        Some(ArgKind::Str) => ArgType::Str.to_string(),
+        Some(ArgKind::Bytes) => ArgType::Bytes.to_string(),
+        Some(ArgKind::Struct) => ArgType::Struct.to_string(),
+    }
+}
+
+/// Argument types of format strings, by format ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaRegistry {
+    events: HashMap<u32, Vec<ArgType>>,
+}
+
+impl SchemaRegistry {
+    /// Creates a registry without schemas.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the schema of the format string `format`, registering it in the
+    /// string registry.
+    pub fn with_event(mut self, format: &'static str, args: &[ArgType]) -> Self {
+        self.insert(register_string(format), args.to_vec());
+        self
+    }
+
+    /// Sets the schema of the format string registered as `format_id`.
+    pub fn insert(&mut self, format_id: u32, args: Vec<ArgType>) {
+        self.events.insert(format_id, args);
+    }
+
+    /// Returns the schema of `format_id`, if it has one.
+    pub fn get(&self, format_id: u32) -> Option<&[ArgType]> {
+        self.events.get(&format_id).map(Vec::as_slice)
+    }
+
+    /// Checks the arguments of a record of `format_id`, one slice per
+    /// argument, against its schema.
+    ///
+    /// Fails with `InvalidInput` naming the first difference. Arguments
+    /// checked this way have no kinds, see `schema`.
+    pub fn check(&self, format_id: u32, args: &[&[u8]]) -> io::Result<()> {
+        self.check_kinds(format_id, args, |_| None)
+    }
+
+    /// Checks the arguments captured by a logging macro.
+    pub(crate) fn check_captured(&self, format_id: u32, args: &[&[u8]], capture: &ArgCapture) -> io::Result<()> {
+        self.check_kinds(format_id, args, |index| capture.kind(index))
+            .inspect_err(|_| capture.reject())
+    }
+
+    fn check_kinds(&self, format_id: u32, args: &[&[u8]], kind: impl Fn(usize) -> Option<ArgKind>) -> io::Result<()> {
+        let Some(expected) = self.events.get(&format_id) else {
+            return Ok(());
+        };
+        let mismatch = |message: String| {
+            let format = get_string(format_id).unwrap_or("?");
+            io::Error::new(io::ErrorKind::InvalidInput, format!("format {} {:?}: {}", format_id, format, message))
+        };
+        if args.len() != expected.len() {
+            return Err(mismatch(format!("{} arguments, the schema has {}", args.len(), expected.len())));
+        }
+        for (index, (arg, &arg_type)) in args.iter().zip(expected).enumerate() {
+            let kind = kind(index);
+            if !arg_type.matches(arg.len(), kind) {
+                return Err(mismatch(format!("argument {} is a {}, the schema has a {}", index, describe(arg.len(), kind), arg_type)));
+            }
+        }
+        Ok(())
+    }
+}
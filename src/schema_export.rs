@@ -0,0 +1,258 @@
+#![allow(dead_code)]
+
+//! FlatBuffers schema and exporter for decoded logs.
+//!
+//! The native format is only readable with this crate. For consumers in other
+//! languages, `flatbuffers_schema()` generates a `.fbs` schema describing the
+//! registered events and the shape of decoded entries; `flatc` turns it into
+//! readers for any supported language. With the `flatbuffers` feature,
+//! `export_flatbuffers()` writes decoded entries as a buffer matching that
+//! schema.
+
+use std::fmt::Write;
+use crate::instrumentation::InternalEvent;
+use crate::string_registry::registered_strings;
+
+#[cfg(feature = "flatbuffers")]
+use crate::log_reader::{LogEntry, LogValue};
+
+/// File identifier of exported buffers, as declared in the schema
+pub const FLATBUFFERS_FILE_IDENTIFIER: &str = "BLOG";
+
+/// Fixed part of the schema: argument values, entries and the root table.
+///
+/// Field order matters: it determines the vtable slots used by the exporter.
+const SCHEMA_BODY: &str = r#"enum ArgKind : ubyte {
+  Unknown = 0,
+  Integer = 1,
+  Boolean = 2,
+  Float = 3,
+  String = 4,
+  Bytes = 5,
+}
+
+table Arg {
+  kind: ArgKind;
+  int_value: int;
+  bool_value: bool;
+  float_value: double;
+  string_value: string;
+  bytes_value: [ubyte];
+}
+
+table FormatString {
+  id: uint;
+  text: string;
+}
+
+table Entry {
+  timestamp_us: ulong;
+  format_id: uint;
+  has_sequence: bool;
+  sequence: ulong;
+  args: [Arg];
+}
+
+table Log {
+  formats: [FormatString];
+  entries: [Entry];
+}
+
+root_type Log;
+"#;
+
+/// Generates a FlatBuffers schema for logs written by this process.
+///
+/// The schema lists every format string registered so far (plus the internal
+/// logger events) in the `EventId` enum, so generated code has a named
+/// constant for each event, followed by the tables `export_flatbuffers`
+/// writes. Call it after the application has registered its format strings.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::schema_export::flatbuffers_schema;
+/// # use binary_logger::register_string;
+/// register_string("Temperature: {} C");
+/// let schema = flatbuffers_schema();
+/// assert!(schema.contains("root_type Log;"));
+/// ```
+pub fn flatbuffers_schema() -> String {
+    let mut schema = String::new();
+    schema.push_str("// Generated by binary_logger. Do not edit.\n\n");
+    schema.push_str("namespace binary_logger;\n\n");
+    let _ = writeln!(schema, "file_identifier \"{}\";\n", FLATBUFFERS_FILE_IDENTIFIER);
+
+    // Registered events, in ID order as FlatBuffers requires
+    schema.push_str("/// Format IDs of the registered events\n");
+    schema.push_str("enum EventId : uint {\n");
+    for (id, text) in registered_strings() {
+        let _ = writeln!(schema, "  /// {}", text.escape_debug());
+        let _ = writeln!(schema, "  Event{} = {},", id, id);
+    }
+    for event in InternalEvent::ALL {
+        let _ = writeln!(schema, "  /// {}", event.format_string().escape_debug());
+        let _ = writeln!(schema, "  Internal{:?} = {},", event, event.format_id());
+    }
+    schema.push_str("}\n\n");
+
+    schema.push_str(SCHEMA_BODY);
+    schema
+}
+
+/// Vtable slots of the schema tables (`4 + 2 * field index`).
+#[cfg(feature = "flatbuffers")]
+mod slot {
+    pub const ARG_KIND: u16 = 4;
+    pub const ARG_INT_VALUE: u16 = 6;
+    pub const ARG_BOOL_VALUE: u16 = 8;
+    pub const ARG_FLOAT_VALUE: u16 = 10;
+    pub const ARG_STRING_VALUE: u16 = 12;
+    pub const ARG_BYTES_VALUE: u16 = 14;
+
+    pub const FORMAT_ID: u16 = 4;
+    pub const FORMAT_TEXT: u16 = 6;
+
+    pub const ENTRY_TIMESTAMP_US: u16 = 4;
+    pub const ENTRY_FORMAT_ID: u16 = 6;
+    pub const ENTRY_HAS_SEQUENCE: u16 = 8;
+    pub const ENTRY_SEQUENCE: u16 = 10;
+    pub const ENTRY_ARGS: u16 = 12;
+
+    pub const LOG_FORMATS: u16 = 4;
+    pub const LOG_ENTRIES: u16 = 6;
+}
+
+/// Writes decoded entries as a FlatBuffers `Log` matching `flatbuffers_schema()`.
+///
+/// The format strings of the exported entries are embedded in the `formats`
+/// table, so the output is self-contained.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::LogReader;
+/// # use binary_logger::schema_export::export_flatbuffers;
+/// # fn example(data: &[u8]) {
+/// let mut reader = LogReader::new(data);
+/// let exported = export_flatbuffers(std::iter::from_fn(|| reader.read_entry()));
+/// std::fs::write("log.fb", exported).unwrap();
+/// # }
+/// ```
+#[cfg(feature = "flatbuffers")]
+pub fn export_flatbuffers(entries: impl IntoIterator<Item = LogEntry>) -> Vec<u8> {
+    use std::collections::BTreeMap;
+    use flatbuffers::{FlatBufferBuilder, WIPOffset};
+
+    let mut builder = FlatBufferBuilder::new();
+    let mut formats = BTreeMap::new();
+    let mut entry_offsets = Vec::new();
+
+    for entry in entries {
+        if let Some(text) = &entry.format_string {
+            formats.insert(entry.format_id, text.clone());
+        }
+
+        let mut arg_offsets = Vec::with_capacity(entry.parameters.len());
+        for value in &entry.parameters {
+            // Wide integers that fit the 32-bit slot are exported as integers
+            let narrowed = match value {
+                LogValue::Integer64(i) => i32::try_from(*i).ok().map(LogValue::Integer),
+                LogValue::Unsigned(u) => i32::try_from(*u).ok().map(LogValue::Integer),
+                _ => None,
+            };
+            let value = narrowed.as_ref().unwrap_or(value);
+
+            // Strings and vectors must be written before the table referencing them
+            let string_value = match value {
+                LogValue::String(s) => Some(builder.create_string(s)),
+                LogValue::Flags(_) | LogValue::Fixed { .. } | LogValue::Instant(_)
+                    | LogValue::ErrorChain(_) | LogValue::Backtrace(_) | LogValue::Struct { .. }
+                    | LogValue::Integer64(_) | LogValue::Unsigned(_) => {
+                    Some(builder.create_string(&value.to_string()))
+                }
+                _ => None,
+            };
+            let bytes_value = match value {
+                LogValue::Unknown(bytes) | LogValue::Bytes(bytes) => Some(builder.create_vector(bytes)),
+                _ => None,
+            };
+
+            let table = builder.start_table();
+            match value {
+                LogValue::Integer(i) => {
+                    builder.push_slot_always(slot::ARG_KIND, 1u8);
+                    builder.push_slot(slot::ARG_INT_VALUE, *i, 0);
+                }
+                LogValue::Boolean(b) => {
+                    builder.push_slot_always(slot::ARG_KIND, 2u8);
+                    builder.push_slot(slot::ARG_BOOL_VALUE, *b, false);
+                }
+                LogValue::Float(f) => {
+                    builder.push_slot_always(slot::ARG_KIND, 3u8);
+                    builder.push_slot(slot::ARG_FLOAT_VALUE, *f, 0.0);
+                }
+                LogValue::Float32(f) => {
+                    builder.push_slot_always(slot::ARG_KIND, 3u8);
+                    builder.push_slot(slot::ARG_FLOAT_VALUE, *f as f64, 0.0);
+                }
+                LogValue::Float16(_) => {
+                    builder.push_slot_always(slot::ARG_KIND, 3u8);
+                    builder.push_slot(slot::ARG_FLOAT_VALUE, value.as_f64().unwrap_or_default(), 0.0);
+                }
+                // Flags, decimals, instants, error chains, backtraces,
+                // structs and wider integers are exported as their text
+                LogValue::String(_) | LogValue::Flags(_) | LogValue::Fixed { .. }
+                    | LogValue::Instant(_) | LogValue::ErrorChain(_) | LogValue::Backtrace(_)
+                    | LogValue::Struct { .. }
+                    | LogValue::Integer64(_) | LogValue::Unsigned(_) => {
+                    builder.push_slot_always(slot::ARG_KIND, 4u8)
+                }
+                LogValue::Unknown(_) => builder.push_slot_always(slot::ARG_KIND, 0u8),
+                LogValue::Bytes(_) => builder.push_slot_always(slot::ARG_KIND, 5u8),
+            }
+            if let Some(offset) = string_value {
+                builder.push_slot_always(slot::ARG_STRING_VALUE, offset);
+            }
+            if let Some(offset) = bytes_value {
+                builder.push_slot_always(slot::ARG_BYTES_VALUE, offset);
+            }
+            arg_offsets.push(builder.end_table(table));
+        }
+        let args = builder.create_vector(&arg_offsets);
+
+        let timestamp_us = entry.timestamp
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let table = builder.start_table();
+        builder.push_slot(slot::ENTRY_TIMESTAMP_US, timestamp_us, 0);
+        builder.push_slot(slot::ENTRY_FORMAT_ID, entry.format_id, 0);
+        if let Some(sequence) = entry.sequence {
+            builder.push_slot_always(slot::ENTRY_HAS_SEQUENCE, true);
+            builder.push_slot(slot::ENTRY_SEQUENCE, sequence, 0);
+        }
+        builder.push_slot_always(slot::ENTRY_ARGS, args);
+        entry_offsets.push(builder.end_table(table));
+    }
+    let entries = builder.create_vector(&entry_offsets);
+
+    let mut format_offsets: Vec<WIPOffset<_>> = Vec::with_capacity(formats.len());
+    for (id, text) in formats {
+        let text = builder.create_string(&text);
+        let table = builder.start_table();
+        builder.push_slot(slot::FORMAT_ID, id, 0);
+        builder.push_slot_always(slot::FORMAT_TEXT, text);
+        format_offsets.push(builder.end_table(table));
+    }
+    let formats = builder.create_vector(&format_offsets);
+
+    let root = builder.start_table();
+    builder.push_slot_always(slot::LOG_FORMATS, formats);
+    builder.push_slot_always(slot::LOG_ENTRIES, entries);
+    let root = builder.end_table(root);
+    builder.finish(root, Some(FLATBUFFERS_FILE_IDENTIFIER));
+
+    builder.finished_data().to_vec()
+}
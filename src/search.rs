@@ -0,0 +1,260 @@
+#![allow(dead_code)]
+
+//! Pattern search over binary logs, backing the `blog-grep` tool.
+//!
+//! Decoding and rendering every entry to run a regex over it is what makes
+//! grepping text exports slow. Most of a rendered line is the format string,
+//! which is known per format ID, and so is the channel name. `FormatFilter`
+//! first decides for each format ID and channel whether the pattern can match
+//! *any* line rendered from them. Only entries that can match need to be
+//! decoded and checked.
+//!
+//! The decision is exact with respect to the line layout of
+//! `render::render_line`: the timestamp (in the form `RenderOptions` give
+//! it, see `FormatFilter::with_render_options`) and argument values are
+//! treated as arbitrary text of the right shape, the level and tags, which
+//! only the record header holds, as any level or tags or none, and the
+//! channel name and literal parts of the format string constrain the match. A record is skipped only when
+//! no possible arguments could make its line match.
+
+use std::collections::{BTreeSet, HashMap};
+use regex_automata::dfa::{dense, Automaton, StartKind};
+use regex_automata::util::primitives::StateID;
+use regex_automata::util::start;
+use regex_automata::Anchored;
+use crate::level::Level;
+use crate::render::{RenderOptions, TimeFormat};
+use crate::string_registry::get_string;
+use crate::tags::Tags;
+
+/// Size limit of the pattern's DFA; larger patterns are not narrowed
+const DFA_SIZE_LIMIT: usize = 10 << 20;
+
+/// Decides per format ID and channel whether a pattern can match rendered
+/// entries.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::search::FormatFilter;
+/// # use binary_logger::register_string;
+/// let mut filter = FormatFilter::new("disk full");
+/// assert!(!filter.may_match(register_string("Connection established"), 0));
+/// assert!(filter.may_match(register_string("Error: disk full"), 0));
+/// // The argument could be "disk full"
+/// assert!(filter.may_match(register_string("Error: {}"), 0));
+/// ```
+pub struct FormatFilter {
+    /// Unanchored DFA of the pattern; `None` if it couldn't be built
+    dfa: Option<dense::DFA<Vec<u32>>>,
+    timestamp: TimestampShape,
+    verdicts: HashMap<(u32, u32), bool>,
+}
+
+/// What the timestamp at the start of a rendered line can look like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimestampShape {
+    /// `<seconds>`, then `.<fraction>` if `fraction`
+    Epoch { fraction: bool },
+
+    /// Any text on one line, for RFC 3339 and `strftime` timestamps
+    Any,
+}
+
+impl TimestampShape {
+    fn of(options: &RenderOptions) -> Self {
+        match options.time_format {
+            TimeFormat::Epoch => TimestampShape::Epoch { fraction: options.fraction_digits > 0 },
+            TimeFormat::Rfc3339 | TimeFormat::Strftime(_) => TimestampShape::Any,
+        }
+    }
+}
+
+impl FormatFilter {
+    /// Creates a filter for a regex pattern, with the syntax of the `regex`
+    /// crate.
+    ///
+    /// Patterns that can't be compiled to a DFA (invalid patterns, Unicode
+    /// word boundaries, very large patterns) produce a filter that lets
+    /// every record through, so searching still works, just without
+    /// narrowing.
+    pub fn new(pattern: &str) -> Self {
+        let dfa = dense::Builder::new()
+            .configure(dense::Config::new()
+                .start_kind(StartKind::Unanchored)
+                .dfa_size_limit(Some(DFA_SIZE_LIMIT))
+                .determinize_size_limit(Some(DFA_SIZE_LIMIT)))
+            .build(pattern)
+            .ok();
+        FormatFilter { dfa, timestamp: TimestampShape::of(&RenderOptions::default()), verdicts: HashMap::new() }
+    }
+
+    /// Matches lines rendered according to `options` rather than with the
+    /// defaults of `render::render_line`.
+    pub fn with_render_options(mut self, options: &RenderOptions) -> Self {
+        self.timestamp = TimestampShape::of(options);
+        self.verdicts.clear();
+        self
+    }
+
+    /// Returns false if no entry with this format ID on this channel can
+    /// match the pattern.
+    ///
+    /// Format IDs without a registered format string always may match.
+    /// Verdicts are cached, so calling this for every entry is cheap.
+    pub fn may_match(&mut self, format_id: u32, channel: u32) -> bool {
+        let dfa = match &self.dfa {
+            Some(dfa) => dfa,
+            None => return true,
+        };
+        let timestamp = self.timestamp;
+        *self.verdicts.entry((format_id, channel)).or_insert_with(|| match get_string(format_id) {
+            Some(format_string) => template_may_match(dfa, timestamp, format_string, channel_name(channel)),
+            None => true,
+        })
+    }
+}
+
+/// Name of a channel as rendered; `None` for records without one.
+fn channel_name(channel: u32) -> Option<&'static str> {
+    if channel == 0 { None } else { get_string(channel) }
+}
+
+/// Returns true if the pattern can match some line rendered from `format_string`.
+///
+/// The rendered line is simulated on the pattern's DFA with a set of states:
+/// literal text advances every state, and arbitrary text (timestamp digits,
+/// argument values) adds every state reachable through it.
+fn template_may_match(dfa: &dense::DFA<Vec<u32>>, timestamp: TimestampShape, format_string: &str, channel: Option<&str>) -> bool {
+    let start = match dfa.start_state(&start::Config::new().anchored(Anchored::No)) {
+        Ok(start) => start,
+        Err(_) => return true,
+    };
+    simulate(dfa, start, timestamp, format_string, channel).is_none_or(|states| {
+        states.iter().any(|&state| dfa.is_match_state(dfa.next_eoi_state(state)))
+    })
+}
+
+/// Runs the DFA over every possible rendering, returning the final states,
+/// or `None` as soon as a match is possible.
+fn simulate(
+    dfa: &dense::DFA<Vec<u32>>,
+    start: StateID,
+    timestamp: TimestampShape,
+    format_string: &str,
+    channel: Option<&str>,
+) -> Option<BTreeSet<StateID>> {
+    let digit = |byte: u8| byte.is_ascii_digit();
+    let in_line = |byte: u8| byte != b'\n' && byte != b'\r';
+
+    let states = BTreeSet::from([start]);
+    let states = match timestamp {
+        // "<seconds>.<fraction>"
+        TimestampShape::Epoch { fraction: true } => repeat(dfa, &advance(dfa, &repeat(dfa, &states, digit)?, b".")?, digit)?,
+        TimestampShape::Epoch { fraction: false } => repeat(dfa, &states, digit)?,
+        TimestampShape::Any => repeat(dfa, &states, in_line)?,
+    };
+
+    // " <LEVEL>" for records with a level, which the format ID doesn't tell
+    let levels: Vec<String> = Level::ALL.iter().map(|level| format!(" {}", level.as_str())).collect();
+    let mut states = optional(dfa, &states, levels.iter().map(String::as_bytes))?;
+
+    if let Some(channel) = channel {
+        states = advance(dfa, &states, format!(" [{}]", channel).as_bytes())?;
+    }
+
+    // " {<tags>}" for records with tags, which the format ID doesn't tell:
+    // registered names joined by "|", or the mask in hex
+    let mut tokens: Vec<&[u8]> = Tags::ALL.names().into_iter().map(str::as_bytes).collect();
+    tokens.extend([&b"|"[..], b"0x"]);
+    tokens.extend((0..16).map(|digit| &b"0123456789abcdef"[digit..digit + 1]));
+    let tagged = advance(dfa, &repeat_tokens(dfa, &advance(dfa, &states, b" {")?, &tokens)?, b"}")?;
+    states.extend(tagged);
+    states = advance(dfa, &states, b" ")?;
+
+    // Message: escaped literal text with arbitrary values in place of "{}"
+    for (i, segment) in format_string.split("{}").enumerate() {
+        if i > 0 {
+            states = repeat(dfa, &states, in_line)?;
+        }
+        let escaped = segment.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+        states = advance(dfa, &states, escaped.as_bytes())?;
+        if states.is_empty() {
+            return Some(states);
+        }
+    }
+    Some(states)
+}
+
+/// Follows `bytes` from every state; `None` if a match becomes possible.
+fn advance(dfa: &dense::DFA<Vec<u32>>, states: &BTreeSet<StateID>, bytes: &[u8]) -> Option<BTreeSet<StateID>> {
+    let mut current = states.clone();
+    for &byte in bytes {
+        let mut next = BTreeSet::new();
+        for &state in &current {
+            let to = dfa.next_state(state, byte);
+            if dfa.is_match_state(to) || dfa.is_quit_state(to) {
+                return None;
+            }
+            if !dfa.is_dead_state(to) {
+                next.insert(to);
+            }
+        }
+        current = next;
+    }
+    Some(current)
+}
+
+/// Adds the states reached through any one of `alternatives` to `states`;
+/// `None` if a match becomes possible.
+fn optional<'a>(
+    dfa: &dense::DFA<Vec<u32>>,
+    states: &BTreeSet<StateID>,
+    alternatives: impl Iterator<Item = &'a [u8]>,
+) -> Option<BTreeSet<StateID>> {
+    let mut reached = states.clone();
+    for bytes in alternatives {
+        reached.extend(advance(dfa, states, bytes)?);
+    }
+    Some(reached)
+}
+
+/// Adds every state reachable through any sequence of `tokens`; `None` if
+/// a match becomes possible.
+fn repeat_tokens(dfa: &dense::DFA<Vec<u32>>, states: &BTreeSet<StateID>, tokens: &[&[u8]]) -> Option<BTreeSet<StateID>> {
+    let mut reached = states.clone();
+    let mut pending: Vec<StateID> = states.iter().copied().collect();
+    while let Some(state) = pending.pop() {
+        for token in tokens {
+            for to in advance(dfa, &BTreeSet::from([state]), token)? {
+                if reached.insert(to) {
+                    pending.push(to);
+                }
+            }
+        }
+    }
+    Some(reached)
+}
+
+/// Adds every state reachable through any number of bytes accepted by
+/// `alphabet`; `None` if a match becomes possible.
+fn repeat(
+    dfa: &dense::DFA<Vec<u32>>,
+    states: &BTreeSet<StateID>,
+    alphabet: impl Fn(u8) -> bool,
+) -> Option<BTreeSet<StateID>> {
+    let mut reached = states.clone();
+    let mut pending: Vec<StateID> = states.iter().copied().collect();
+    while let Some(state) = pending.pop() {
+        for byte in (0..=255u8).filter(|&byte| alphabet(byte)) {
+            let to = dfa.next_state(state, byte);
+            if dfa.is_match_state(to) || dfa.is_quit_state(to) {
+                return None;
+            }
+            if !dfa.is_dead_state(to) && reached.insert(to) {
+                pending.push(to);
+            }
+        }
+    }
+    Some(reached)
+}
@@ -0,0 +1,249 @@
+#![allow(dead_code)]
+
+//! Startup self-test that cross-validates the writer and the reader.
+//!
+//! The binary format relies on platform details: the hardware counter behind
+//! the clock, its calibration, and unaligned little-endian access. `selftest()`
+//! exercises them on the machine it runs on by writing representative records
+//! into memory, reading them back and comparing, so a broken clock source or
+//! encoding problem shows up at startup instead of as an unreadable log.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::arg_types::ArgCapture;
+use crate::binary_logger::{BufferHandler, Logger};
+use crate::codec::{Codec, RawCodec, PostcardCodec, CborCodec, CompactCodec};
+use crate::efficient_clock::{calibration, get_timestamp};
+use crate::instrumentation::InternalEvent;
+use crate::log_reader::{LogEntry, LogReader};
+use crate::log_record;
+use crate::string_registry::register_string;
+
+/// Buffer size used by the self-test; small enough to force buffer switches
+const SELFTEST_BUFFER_SIZE: usize = 4096;
+
+/// Rounds of representative records written per codec
+const SELFTEST_ROUNDS: usize = 40;
+
+/// A base reset is forced every this many rounds
+const BASE_RESET_INTERVAL: usize = 8;
+
+/// How long the clock is compared against `Instant`
+const CLOCK_CHECK_WINDOW: Duration = Duration::from_millis(5);
+
+/// Outcome of a single self-test check.
+#[derive(Debug, Clone)]
+pub struct SelfTestCheck {
+    /// What was checked
+    pub name: String,
+
+    /// Whether the check passed
+    pub passed: bool,
+
+    /// Measurements on success, or what went wrong on failure
+    pub detail: String,
+}
+
+/// Result of `selftest()`: one entry per check that was run.
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    /// All checks, in the order they ran
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Returns true if every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    /// Returns the checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+
+    fn record(&mut self, name: impl Into<String>, result: Result<String, String>) {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        self.checks.push(SelfTestCheck { name: name.into(), passed, detail });
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let passed = self.checks.iter().filter(|check| check.passed).count();
+        writeln!(f, "binary_logger self-test: {} ({}/{} checks passed)",
+            if self.passed() { "PASSED" } else { "FAILED" }, passed, self.checks.len())?;
+        for check in &self.checks {
+            writeln!(f, "  [{}] {}: {}", if check.passed { "ok" } else { "FAIL" }, check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the writer/reader self-test and returns a report.
+///
+/// The test checks the clock calibration against `std::time::Instant`, then
+/// for every built-in codec writes a mix of records into memory (integers,
+/// booleans, floats, strings, odd-sized and large payloads, records without
+/// arguments) with global sequencing and instrumentation enabled, small
+/// buffers and forced base timestamp resets. Everything is read back and
+/// compared byte for byte.
+///
+/// It takes a few milliseconds and registers a handful of `selftest:` format
+/// strings in the global registry.
+///
+/// # Examples
+///
+/// ```
+/// let report = binary_logger::selftest();
+/// if !report.passed() {
+///     eprintln!("{}", report);
+/// }
+/// ```
+pub fn selftest() -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+
+    report.record("clock calibration", check_clock());
+
+    let codecs: [&'static dyn Codec; 4] = [&RawCodec, &PostcardCodec, &CborCodec, &CompactCodec];
+    for codec in codecs {
+        report.record(format!("{} codec roundtrip", codec.name()), check_roundtrip(codec));
+    }
+
+    report
+}
+
+/// Compares the calibrated clock with `Instant` over a short window.
+fn check_clock() -> Result<String, String> {
+    let cal = calibration();
+    let start = Instant::now();
+    let start_ticks = get_timestamp();
+    while start.elapsed() < CLOCK_CHECK_WINDOW {
+        std::hint::spin_loop();
+    }
+    let end_ticks = get_timestamp();
+    let expected = start.elapsed().as_micros() as u64;
+
+    if end_ticks <= start_ticks {
+        return Err(format!("counter did not advance ({} -> {})", start_ticks, end_ticks));
+    }
+
+    let measured = cal.ticks_as_micros(end_ticks - start_ticks);
+    let error = measured.abs_diff(expected);
+    let detail = format!("{} ticks/s, {}us measured vs {}us elapsed", cal.ticks_per_second, measured, expected);
+
+    // Allow 10% plus scheduling noise
+    if error > expected / 10 + 50 {
+        Err(detail)
+    } else {
+        Ok(detail)
+    }
+}
+
+/// Handler collecting every buffer into one byte vector.
+struct MemoryHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+    buffers: Arc<Mutex<usize>>,
+}
+
+impl BufferHandler for MemoryHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+        *self.buffers.lock().unwrap() += 1;
+    }
+}
+
+/// A record as written: format ID and the bytes of each argument.
+type ExpectedRecord = (u32, Vec<Vec<u8>>);
+
+/// Logs a record and remembers what was written.
+///
+/// The arguments are captured the way `log_record!` captures them, so
+/// string literals are expected as their interned IDs.
+macro_rules! write_expected {
+    ($logger:expr, $expected:expr, $fmt:literal, $($args:tt)*) => {{
+        #[allow(unused_variables)]
+        let capture = ArgCapture::new();
+        let args: Vec<Vec<u8>> = crate::__log_args!(capture [] $($args)*).iter().map(|arg: &&[u8]| arg.to_vec()).collect();
+        $expected.push((register_string($fmt), args));
+        log_record!($logger, $fmt, $($args)*)
+    }};
+}
+
+/// Writes representative records with `codec` and checks they read back intact.
+fn check_roundtrip(codec: &'static dyn Codec) -> Result<String, String> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let buffers = Arc::new(Mutex::new(0));
+    let mut expected: Vec<ExpectedRecord> = Vec::new();
+
+    {
+        let handler = MemoryHandler { data: data.clone(), buffers: buffers.clone() };
+        let mut logger = Logger::<SELFTEST_BUFFER_SIZE>::with_codec(handler, codec);
+        logger.set_global_sequence(true);
+        logger.set_instrumentation(true);
+
+        let big = [0xA5u8; 1024];
+        for round in 0..SELFTEST_ROUNDS {
+            if round % BASE_RESET_INTERVAL == 0 {
+                logger.reset_time_base();
+            }
+            let value = round as i32;
+            let result = write_expected!(logger, expected, "selftest: integer {}", value)
+                .and_then(|_| write_expected!(logger, expected, "selftest: flag {}", round % 2 == 0))
+                .and_then(|_| write_expected!(logger, expected, "selftest: float {}", round as f64 * 0.5))
+                .and_then(|_| write_expected!(logger, expected, "selftest: string {}", "selftest"))
+                .and_then(|_| write_expected!(logger, expected, "selftest: odd payload {}", [round as u8; 3]))
+                .and_then(|_| write_expected!(logger, expected, "selftest: mixed {} {} {}", value, 1.5f64, true))
+                .and_then(|_| write_expected!(logger, expected, "selftest: big payload {}", big))
+                .and_then(|_| write_expected!(logger, expected, "selftest: no arguments", ));
+            result.map_err(|e| format!("write failed: {}", e))?;
+        }
+    }
+
+    let data = data.lock().unwrap();
+    let buffers = *buffers.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    match reader.codec() {
+        Some(read_codec) if read_codec.id() == codec.id() => {}
+        other => return Err(format!("stream header named codec {:?}", other.map(|c| c.name()))),
+    }
+
+    // Sequences and timestamps must both be in write order
+    for window in entries.windows(2) {
+        if window[0].sequence >= window[1].sequence {
+            return Err(format!("sequence went from {:?} to {:?}", window[0].sequence, window[1].sequence));
+        }
+        if window[0].timestamp > window[1].timestamp {
+            return Err(format!("timestamp went backwards at sequence {:?}", window[1].sequence));
+        }
+    }
+
+    let switches = entries.iter()
+        .filter(|e| e.format_id == InternalEvent::BufferSwitchEnd.format_id())
+        .count();
+    if buffers < 2 || switches == 0 {
+        return Err(format!("expected buffer switches, saw {} buffers and {} switch events", buffers, switches));
+    }
+
+    let records: Vec<&LogEntry> = entries.iter().filter(|e| !e.is_internal()).collect();
+    if records.len() != expected.len() {
+        return Err(format!("wrote {} records, read {}", expected.len(), records.len()));
+    }
+    for (index, (entry, (format_id, args))) in records.iter().zip(&expected).enumerate() {
+        if entry.format_id != *format_id {
+            return Err(format!("record {}: format ID {} read as {}", index, format_id, entry.format_id));
+        }
+        let decoded = codec.decode(&entry.raw_values);
+        if decoded.len() != args.len() || decoded.iter().zip(args).any(|(read, written)| read != written) {
+            return Err(format!("record {} ({:?}): arguments differ", index, entry.format_string));
+        }
+    }
+
+    Ok(format!("{} records in {} buffers, {} bytes", records.len(), buffers, data.len()))
+}
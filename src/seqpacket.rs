@@ -0,0 +1,339 @@
+#![allow(dead_code)]
+
+//! Shipping buffers to a local collector over `SOCK_SEQPACKET` Unix
+//! sockets, with kernel-checked sender credentials (Linux).
+//!
+//! On multi-tenant hosts a collector can't trust what a log says about who
+//! wrote it. `SeqpacketHandler` sends every buffer with an `SCM_CREDENTIALS`
+//! message holding the sender's pid, uid and gid, which the kernel checks
+//! against the sending process (only privileged processes can claim other
+//! credentials). `SeqpacketListener` accepts such connections and reads
+//! them as a byte stream for `StreamReader`, labelled with the credentials
+//! of the peer, and fails a connection whose messages stop carrying them.
+//! `blog-proxy --seqpacket PATH` is such a collector.
+//!
+//! Seqpacket messages keep their boundaries but are limited by the socket
+//! buffers, so buffers are sent in messages of at most `MAX_MESSAGE_SIZE`
+//! bytes; the buffer headers give the stream its framing.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use binary_logger::{Logger, LogReader, StreamReader, log_record};
+//! # use binary_logger::seqpacket::{SeqpacketHandler, SeqpacketListener};
+//! # fn example() -> std::io::Result<()> {
+//! // In the collector
+//! let listener = SeqpacketListener::bind("/run/blog/collector.sock")?;
+//!
+//! // In each application
+//! let mut logger = Logger::<65536>::new(SeqpacketHandler::connect("/run/blog/collector.sock")?);
+//! log_record!(logger, "Tenant request {} served", 7).unwrap();
+//!
+//! // In the collector
+//! let connection = listener.accept()?;
+//! let credentials = connection.peer_credentials();
+//! let mut reader = StreamReader::new(connection);
+//! while let Some(entry) = reader.read_entry()? {
+//!     println!("uid {} pid {}: {}", credentials.uid, credentials.pid, entry.format());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::io::{self, Read};
+use std::mem::{size_of, zeroed};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::binary_logger::BufferHandler;
+
+/// Largest message sent, well within the default socket buffers
+pub const MAX_MESSAGE_SIZE: usize = 64 << 10;
+
+/// Identity of a process, as checked by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    pub pid: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Credentials {
+    fn from_ucred(cred: libc::ucred) -> Self {
+        Credentials { pid: cred.pid as u32, uid: cred.uid, gid: cred.gid }
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "uid={} gid={} pid={}", self.uid, self.gid, self.pid)
+    }
+}
+
+/// Returns the result of a libc call, or the OS error it set.
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+/// Creates a seqpacket Unix socket.
+fn seqpacket_socket() -> io::Result<OwnedFd> {
+    let fd = check(unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC, 0) })?;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Returns the socket address of a filesystem path.
+fn socket_addr(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut addr: libc::sockaddr_un = unsafe { zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let bytes = path.as_os_str().as_bytes();
+    // Keep the terminating NUL
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("socket path {} is too long", path.display())));
+    }
+    for (dst, &src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = src as libc::c_char;
+    }
+    let len = size_of::<libc::sa_family_t>() + bytes.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
+
+/// Enables receiving the credentials of every message.
+fn pass_credentials(fd: RawFd) -> io::Result<()> {
+    let on: libc::c_int = 1;
+    check(unsafe {
+        libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_PASSCRED, &on as *const _ as *const libc::c_void, size_of::<libc::c_int>() as libc::socklen_t)
+    })?;
+    Ok(())
+}
+
+/// Returns whether `fd` is a seqpacket socket.
+pub(crate) fn is_seqpacket(fd: RawFd) -> bool {
+    let mut kind: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_TYPE, &mut kind as *mut _ as *mut libc::c_void, &mut len) };
+    result == 0 && kind == libc::SOCK_SEQPACKET
+}
+
+/// Sends each switched-out buffer to a collector over a seqpacket Unix
+/// socket, with the process's credentials.
+///
+/// Like other handlers it reports failures by panicking (see
+/// `Logger::set_handler_panic_policy`). A failed send breaks the
+/// connection for good, since the collector would otherwise receive part
+/// of a buffer followed by the next one: every later buffer fails too.
+pub struct SeqpacketHandler {
+    fd: OwnedFd,
+    path: PathBuf,
+
+    /// The error that broke the connection
+    broken: Mutex<Option<String>>,
+}
+
+impl SeqpacketHandler {
+    /// Connects to the collector listening at `path`.
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let fd = seqpacket_socket()?;
+        let (addr, len) = socket_addr(path)?;
+        check(unsafe { libc::connect(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, len) })?;
+        Ok(SeqpacketHandler { fd, path: path.to_path_buf(), broken: Mutex::new(None) })
+    }
+
+    /// Sends one message with an `SCM_CREDENTIALS` control message.
+    fn send(&self, message: &[u8]) -> io::Result<()> {
+        let cred = libc::ucred {
+            pid: unsafe { libc::getpid() },
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+        };
+        // u64 elements align the control buffer for cmsghdr
+        let mut control = [0u64; 8];
+        let control_len = unsafe { libc::CMSG_SPACE(size_of::<libc::ucred>() as u32) } as usize;
+        assert!(control_len <= size_of::<[u64; 8]>());
+
+        let mut iov = libc::iovec { iov_base: message.as_ptr() as *mut libc::c_void, iov_len: message.len() };
+        let mut msg: libc::msghdr = unsafe { zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control_len as _;
+        unsafe {
+            let header = libc::CMSG_FIRSTHDR(&msg);
+            (*header).cmsg_level = libc::SOL_SOCKET;
+            (*header).cmsg_type = libc::SCM_CREDENTIALS;
+            (*header).cmsg_len = libc::CMSG_LEN(size_of::<libc::ucred>() as u32) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(header) as *mut libc::ucred, cred);
+        }
+
+        loop {
+            let sent = unsafe { libc::sendmsg(self.fd.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) };
+            if sent >= 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+impl BufferHandler for SeqpacketHandler {
+    fn handle_buffer(&self, buffer: &[u8]) {
+        let mut broken = self.broken.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if broken.is_none() {
+            if let Err(e) = buffer.chunks(MAX_MESSAGE_SIZE).try_for_each(|message| self.send(message)) {
+                *broken = Some(e.to_string());
+            }
+        }
+        if let Some(error) = broken.as_deref() {
+            panic!("cannot send buffer to {}: {}", self.path.display(), error);
+        }
+    }
+}
+
+/// A collector's listening seqpacket socket.
+#[derive(Debug)]
+pub struct SeqpacketListener {
+    fd: OwnedFd,
+}
+
+impl SeqpacketListener {
+    /// Listens at `path`, which must not exist.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let fd = seqpacket_socket()?;
+        let (addr, len) = socket_addr(path.as_ref())?;
+        check(unsafe { libc::bind(fd.as_raw_fd(), &addr as *const _ as *const libc::sockaddr, len) })?;
+        check(unsafe { libc::listen(fd.as_raw_fd(), libc::SOMAXCONN) })?;
+        pass_credentials(fd.as_raw_fd())?;
+        Ok(SeqpacketListener { fd })
+    }
+
+    /// Waits for the next producer.
+    pub fn accept(&self) -> io::Result<SeqpacketConnection> {
+        let fd = loop {
+            match check(unsafe { libc::accept4(self.fd.as_raw_fd(), std::ptr::null_mut(), std::ptr::null_mut(), libc::SOCK_CLOEXEC) }) {
+                Ok(fd) => break unsafe { OwnedFd::from_raw_fd(fd) },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        pass_credentials(fd.as_raw_fd())?;
+
+        let mut cred: libc::ucred = unsafe { zeroed() };
+        let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+        check(unsafe { libc::getsockopt(fd.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut _ as *mut libc::c_void, &mut len) })?;
+        Ok(SeqpacketConnection { fd, peer: Credentials::from_ucred(cred), message: Vec::new(), pos: 0 })
+    }
+}
+
+impl AsRawFd for SeqpacketListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for SeqpacketListener {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_raw_fd()
+    }
+}
+
+impl FromRawFd for SeqpacketListener {
+    /// Takes ownership of a listening seqpacket socket, such as one passed
+    /// by socket activation, and enables credential passing on it.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        let _ = pass_credentials(fd);
+        SeqpacketListener { fd: OwnedFd::from_raw_fd(fd) }
+    }
+}
+
+/// A producer's connection, read as the stream of its buffers.
+///
+/// Reads fail with `InvalidData` once a message arrives with credentials
+/// other than those of the peer that connected.
+#[derive(Debug)]
+pub struct SeqpacketConnection {
+    fd: OwnedFd,
+    peer: Credentials,
+
+    /// The last message received and how much of it was read
+    message: Vec<u8>,
+    pos: usize,
+}
+
+impl SeqpacketConnection {
+    /// Returns the credentials of the process that connected.
+    pub fn peer_credentials(&self) -> Credentials {
+        self.peer
+    }
+
+    /// Receives the next message into `self.message`, returning false at
+    /// the end of the connection.
+    fn receive(&mut self) -> io::Result<bool> {
+        self.message.resize(MAX_MESSAGE_SIZE, 0);
+        self.pos = 0;
+        let mut control = [0u64; 8];
+        let mut iov = libc::iovec { iov_base: self.message.as_mut_ptr() as *mut libc::c_void, iov_len: self.message.len() };
+        let mut msg: libc::msghdr = unsafe { zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = size_of::<[u64; 8]>() as _;
+
+        let received = loop {
+            let received = unsafe { libc::recvmsg(self.fd.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+            if received >= 0 {
+                break received as usize;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                self.message.clear();
+                return Err(err);
+            }
+        };
+        self.message.truncate(received);
+        if received == 0 {
+            return Ok(false);
+        }
+        if msg.msg_flags & libc::MSG_TRUNC != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message larger than {} bytes", MAX_MESSAGE_SIZE)));
+        }
+
+        let mut credentials = None;
+        unsafe {
+            let mut header = libc::CMSG_FIRSTHDR(&msg);
+            while !header.is_null() {
+                if (*header).cmsg_level == libc::SOL_SOCKET && (*header).cmsg_type == libc::SCM_CREDENTIALS {
+                    let cred = std::ptr::read_unaligned(libc::CMSG_DATA(header) as *const libc::ucred);
+                    credentials = Some(Credentials::from_ucred(cred));
+                }
+                header = libc::CMSG_NXTHDR(&msg, header);
+            }
+        }
+        match credentials {
+            Some(credentials) if credentials == self.peer => Ok(true),
+            Some(credentials) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("message from {} on the connection of {}", credentials, self.peer))),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "message without credentials")),
+        }
+    }
+}
+
+impl Read for SeqpacketConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.message.len() && !self.receive()? {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.message.len() - self.pos);
+        buf[..len].copy_from_slice(&self.message[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,290 @@
+#![allow(dead_code)]
+
+//! Web viewer for binary logs, backing the `blog-serve` tool.
+//!
+//! `LogServer` answers HTTP GET requests with a single-page viewer and a
+//! small JSON API over a fixed set of log files:
+//!
+//! * `GET /` - the viewer page
+//! * `GET /api/files` - the files with their size, entry count and time span
+//! * `GET /api/formats?file=N` - format IDs and channels used in a file,
+//!   with counts, for the filter controls
+//! * `GET /api/entries?file=N` - entries, filtered by the optional
+//!   parameters `from` and `to` (microseconds since the epoch, `to`
+//!   exclusive), `format` and `channel` (comma-separated IDs), `q`
+//!   (case-insensitive text), `after` (an `EntryId`, to page or tail) and
+//!   `limit`
+//!
+//! Files are read again on every request, so the viewer follows logs that
+//! are still being written: polling `/api/entries` with `after` set to the
+//! last entry seen is how the live tail works. Request handling is separate
+//! from the HTTP transport so it can be tested without a socket.
+
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use regex::Regex;
+use serde_json::{json, Value};
+use crate::compact::read_segment;
+use crate::level::Level;
+use crate::log_reader::{EntryId, LogEntry, LogReader};
+use crate::query::LogQuery;
+use crate::render::{RenderOptions, TimeFormat};
+use crate::string_registry::get_string;
+
+/// The viewer page, with its script and styles inline
+const INDEX_HTML: &str = include_str!("../assets/blog_serve/index.html");
+
+/// Entries returned per request unless `limit` says otherwise
+const DEFAULT_LIMIT: usize = 500;
+
+/// Upper bound on `limit`
+const MAX_LIMIT: usize = 10_000;
+
+/// An HTTP response produced by `LogServer::handle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// HTTP status code
+    pub status: u16,
+
+    /// Value of the Content-Type header
+    pub content_type: &'static str,
+
+    /// Response body
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Self {
+        Response { status, content_type: "application/json", body: value.to_string().into_bytes() }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response::json(status, json!({ "error": message.into() }))
+    }
+}
+
+/// Serves the viewer and API over a set of log files.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::serve::LogServer;
+/// let server = LogServer::new(vec!["app.blog".into()]);
+/// let response = server.handle("/api/files");
+/// assert_eq!(response.content_type, "application/json");
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogServer {
+    files: Vec<PathBuf>,
+    render: RenderOptions,
+}
+
+impl LogServer {
+    /// Creates a server for the given files; their position in the list is
+    /// the `file` index used by the API.
+    ///
+    /// Entry times are rendered as RFC 3339 in UTC unless
+    /// `with_render_options` says otherwise.
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let render = RenderOptions { time_format: TimeFormat::Rfc3339, ..RenderOptions::default() };
+        LogServer { files, render }
+    }
+
+    /// Renders entry times and messages according to `options`.
+    pub fn with_render_options(mut self, options: RenderOptions) -> Self {
+        self.render = options;
+        self
+    }
+
+    /// Answers a GET request for `url` (path and query string).
+    pub fn handle(&self, url: &str) -> Response {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let params = Params::from_query(query);
+        let result = match path {
+            "/" | "/index.html" => {
+                return Response { status: 200, content_type: "text/html; charset=utf-8", body: INDEX_HTML.into() };
+            }
+            "/api/files" => self.files_json(),
+            "/api/formats" => self.formats_json(&params),
+            "/api/entries" => self.entries_json(&params),
+            _ => return Response::error(404, format!("no such resource: {}", path)),
+        };
+        match result {
+            Ok(value) => Response::json(200, value),
+            Err(response) => response,
+        }
+    }
+
+    /// Reads the file selected by the `file` parameter.
+    fn read_file(&self, params: &Params) -> Result<(u32, Vec<u8>), Response> {
+        let index: u32 = params.parse("file")?.unwrap_or(0);
+        let path = self.files.get(index as usize)
+            .ok_or_else(|| Response::error(404, format!("no file {}", index)))?;
+        let data = read_segment(path)
+            .map_err(|e| Response::error(500, format!("cannot read {}: {}", path.display(), e)))?;
+        Ok((index, data))
+    }
+
+    fn files_json(&self) -> Result<Value, Response> {
+        let mut files = Vec::with_capacity(self.files.len());
+        for (index, path) in self.files.iter().enumerate() {
+            let data = read_segment(path)
+                .map_err(|e| Response::error(500, format!("cannot read {}: {}", path.display(), e)))?;
+            let mut reader = LogReader::new(&data);
+            let (mut entries, mut first, mut last) = (0u64, None, None);
+            while let Some(entry) = reader.read_entry() {
+                let micros = timestamp_micros(&entry);
+                entries += 1;
+                first = Some(first.map_or(micros, |first: u64| first.min(micros)));
+                last = Some(last.map_or(micros, |last: u64| last.max(micros)));
+            }
+            files.push(json!({
+                "index": index,
+                "name": path.file_name().unwrap_or_default().to_string_lossy(),
+                "bytes": data.len(),
+                "entries": entries,
+                "first_us": first,
+                "last_us": last,
+            }));
+        }
+        Ok(Value::Array(files))
+    }
+
+    fn formats_json(&self, params: &Params) -> Result<Value, Response> {
+        let (_, data) = self.read_file(params)?;
+        let mut reader = LogReader::new(&data);
+        let mut formats = std::collections::BTreeMap::new();
+        while let Some(entry) = reader.read_entry() {
+            *formats.entry(entry.format_id).or_insert(0u64) += 1;
+        }
+
+        let formats: Vec<Value> = formats.into_iter()
+            .map(|(id, count)| json!({ "id": id, "text": get_string(id), "count": count }))
+            .collect();
+        let channels: Vec<Value> = reader.channel_stats().iter()
+            .map(|(&id, stats)| json!({
+                "id": id,
+                "name": if id == 0 { None } else { get_string(id) },
+                "count": stats.records,
+            }))
+            .collect();
+        Ok(json!({ "formats": formats, "channels": channels }))
+    }
+
+    fn entries_json(&self, params: &Params) -> Result<Value, Response> {
+        let (index, data) = self.read_file(params)?;
+        let from: Option<u64> = params.parse("from")?;
+        let to: Option<u64> = params.parse("to")?;
+        let formats = params.parse_list("format")?;
+        let channels = params.parse_list("channel")?;
+        let after: Option<EntryId> = params.parse("after")?;
+        let limit = params.parse("limit")?.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+        let mut reader = LogReader::new(&data);
+        reader.set_file_index(index);
+        reader.set_channel_filter(channels.as_deref());
+
+        let entries: Box<dyn Iterator<Item = LogEntry>> = match params.get("q").filter(|q| !q.is_empty()) {
+            Some(text) => {
+                let regex = Regex::new(&format!("(?i){}", regex::escape(text)))
+                    .map_err(|e| Response::error(400, e.to_string()))?;
+                Box::new(LogQuery::matching(regex).with_render_options(self.render.clone()).run(reader).map(|hit| hit.entry))
+            }
+            None => Box::new(std::iter::from_fn(move || reader.read_entry())),
+        };
+
+        let mut selected = entries
+            .filter(|entry| after.is_none_or(|after| entry.id > after))
+            .filter(|entry| formats.as_ref().is_none_or(|formats| formats.contains(&entry.format_id)))
+            .filter(|entry| {
+                let micros = timestamp_micros(entry);
+                from.is_none_or(|from| micros >= from) && to.is_none_or(|to| micros < to)
+            });
+
+        let page: Vec<Value> = selected.by_ref().take(limit).map(|entry| entry_json(&entry, &self.render)).collect();
+        let more = selected.next().is_some();
+        Ok(json!({ "entries": page, "more": more }))
+    }
+}
+
+/// JSON form of an entry, as returned by `/api/entries`.
+fn entry_json(entry: &LogEntry, render: &RenderOptions) -> Value {
+    json!({
+        "id": entry.id.to_string(),
+        "timestamp_us": timestamp_micros(entry),
+        "time": render.format_time(entry.timestamp),
+        "sequence": entry.sequence,
+        "format_id": entry.format_id,
+        "channel": entry.channel,
+        "channel_name": if entry.channel == 0 { None } else { entry.channel_name() },
+        "level": entry.level.map(Level::as_str),
+        "message": entry.format_with(render),
+        "internal": entry.is_internal(),
+    })
+}
+
+fn timestamp_micros(entry: &LogEntry) -> u64 {
+    entry.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64
+}
+
+/// Decoded query string parameters.
+struct Params(Vec<(String, String)>);
+
+impl Params {
+    fn from_query(query: &str) -> Self {
+        Params(query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_component(name), decode_component(value))
+            })
+            .collect())
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Parses a parameter, treating an empty value as absent.
+    fn parse<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, Response> {
+        match self.get(name).filter(|value| !value.is_empty()) {
+            Some(value) => value.parse().map(Some)
+                .map_err(|_| Response::error(400, format!("invalid {}: {:?}", name, value))),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses a comma-separated list of IDs.
+    fn parse_list(&self, name: &str) -> Result<Option<Vec<u32>>, Response> {
+        match self.get(name).filter(|value| !value.is_empty()) {
+            Some(value) => value.split(',')
+                .map(|id| id.trim().parse()
+                    .map_err(|_| Response::error(400, format!("invalid {}: {:?}", name, value))))
+                .collect::<Result<Vec<u32>, _>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Decodes `+` and `%XX` escapes of a query string component.
+fn decode_component(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => match s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 2;
+                }
+                None => out.push(b'%'),
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
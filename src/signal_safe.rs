@@ -0,0 +1,518 @@
+#![allow(dead_code)]
+
+//! Logging from signal handlers and other async-signal contexts.
+//!
+//! A signal handler may only call async-signal-safe functions: it can
+//! interrupt its thread anywhere, including inside `malloc` or while a lock
+//! is held, so allocating, locking or formatting from the handler can
+//! deadlock or corrupt the heap. `Logger` does all three on some paths
+//! (buffer switches call the handler, dictionaries and suppression
+//! summaries allocate), and needs `&mut` access besides.
+//!
+//! `AsyncSignalSafeLogger` is a Logger whose write path does none of this.
+//! Its two buffers are allocated when it is created, and `write` (or the
+//! `log_signal_safe!` macro) reserves space for a record with a single
+//! compare-and-swap on an atomic word holding the active buffer, the write
+//! position and the time of the last record. Records are copied into their
+//! reservation and published with an atomic counter, so a write is
+//! lock-free, never allocates and never calls the BufferHandler. It can
+//! interrupt another write on the same thread, or race with writes on other
+//! threads, and both records land intact. When the active buffer is full,
+//! records are dropped and counted (`dropped`) rather than waited for.
+//!
+//! Records are handed to the BufferHandler by `drain`, which switches
+//! buffers, waits for the writes still in progress in the old one and calls
+//! the handler with it. `drain` locks and calls arbitrary code, so it is
+//! **not** async-signal-safe: call it from normal code, such as a
+//! background thread draining periodically or the main loop after a
+//! signal was noted. The logger also drains when dropped.
+//!
+//! # Which handlers are safe
+//!
+//! * Handlers of asynchronous signals (`SIGINT`, `SIGTERM`, `SIGCHLD`,
+//!   `SIGUSR1`, timers) may write with `log_signal_safe!`, and so may
+//!   handlers of synchronous faults (`SIGSEGV`, `SIGBUS`, `SIGFPE`) as long
+//!   as the fault isn't in the logger's own buffers.
+//! * Nested handlers, and handlers interrupting a write on their own thread,
+//!   are fine: reservations never wait on each other.
+//! * No handler may call `drain` or drop the logger.
+//! * The BufferHandler runs in `drain` only, so any BufferHandler works,
+//!   including ones that allocate, lock or do I/O.
+//! * Between `fork` and `exec` the child may write, but shouldn't drain:
+//!   a write interrupted by the fork in another thread of the parent never
+//!   completes in the child, and `drain` would wait for it forever.
+//!
+//! Format strings must be literals, so their IDs come from the static
+//! string table (see `string_registry`) without registration; the table is
+//! built when the logger is created. Arguments are copied as their
+//! in-memory bytes without capturing their kinds, so log plain values the
+//! reader recognizes by size: `bool`, `i32` and `f64`. Strings, `f32` and
+//! the other wrapped argument types of `log_record!` need registration and
+//! are not supported.
+//!
+//! The stream is an ordinary binary log with levels, read with `LogReader`.
+//! Each record's time is taken within its reservation, so times increase in
+//! stream order even across threads.
+//!
+//! By default the counters and records are packed. With many writers on
+//! different cores, `with_layout` and a `SharedLayout` can give the counters
+//! cache lines of their own and start each record on a cache line, so
+//! writers stop invalidating each other's lines; the stream then holds
+//! padding records, which readers skip.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{BufferHandler, LogReader, log_signal_safe};
+//! # use binary_logger::level::Level;
+//! # use binary_logger::signal_safe::AsyncSignalSafeLogger;
+//! # use std::sync::{Arc, Mutex};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! // Typically a static, reachable from the signal handler
+//! let logger = AsyncSignalSafeLogger::<65536>::new(MemoryHandler(data.clone()));
+//!
+//! // In the signal handler
+//! log_signal_safe!(logger, Level::Warn, "Caught signal {}", 15i32);
+//!
+//! // Later, outside the handler
+//! logger.drain();
+//! # let data = data.lock().unwrap();
+//! let mut reader = LogReader::new(&data);
+//! assert_eq!(reader.read_entry().unwrap().format(), "Caught signal 15");
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use crate::binary_logger::{
+    BufferHandler, BUFFER_HEADER_SIZE, RECORD_HEADER_SIZE, RECORD_TYPE_NORMAL, RECORD_TYPE_BASE,
+    RECORD_TYPE_STREAM_HEADER, RECORD_TYPE_PADDING, STREAM_MAGIC, STREAM_VERSION, STREAM_HEADER_PAYLOAD_SIZE, WIDE_ID_SIZE,
+};
+use crate::buffer_pool::{alloc_buffer, free_buffer, BUFFER_ALIGN};
+use crate::codec::{Codec, RawCodec};
+use crate::efficient_clock::{calibration, get_timestamp, EPOCH_MICROS};
+use crate::features::FormatFeatures;
+use crate::level::Level;
+use crate::string_registry::{self, is_wide_id, WIDE_ID};
+
+/// Bits of the reservation word holding the time of the last record, in
+/// microseconds since the base of its buffer (about 6 days)
+const TIME_BITS: u32 = 39;
+
+/// Bits of the reservation word holding the write position
+const POS_BITS: u32 = 24;
+
+const TIME_MASK: u64 = (1 << TIME_BITS) - 1;
+
+const POS_MASK: u64 = (1 << POS_BITS) - 1;
+
+/// Largest buffer the reservation word can address
+pub const MAX_CAPACITY: usize = 1 << POS_BITS;
+
+/// Size of the stream header written at the start of the first buffer
+const STREAM_HEADER_RECORD_SIZE: usize = RECORD_HEADER_SIZE + STREAM_HEADER_PAYLOAD_SIZE;
+
+/// Size of the base record starting every buffer
+const TIME_BASE_RECORD_SIZE: usize = RECORD_HEADER_SIZE + 8;
+
+/// Format features the logger may use
+const FEATURES: FormatFeatures = FormatFeatures::from_bits(FormatFeatures::LEVELS.bits() | FormatFeatures::WIDE_IDS.bits());
+
+/// Size of a cache line, the unit of `SharedLayout`
+pub const CACHE_LINE: usize = BUFFER_ALIGN;
+
+/// Index of each counter among the cursors
+const RESERVATION: usize = 0;
+const COMMITTED: [usize; 2] = [1, 2];
+const DROPPED: usize = 3;
+
+/// How an `AsyncSignalSafeLogger` lays out its counters and records in
+/// memory, see `AsyncSignalSafeLogger::with_layout`.
+///
+/// Every writer updates the reservation word and a commit counter, and
+/// copies its record next to the records of other threads. Packed, these
+/// share cache lines, so a write on one core invalidates the line another
+/// core is writing to (false sharing). Padding the cursors gives each
+/// counter a line of its own, and aligning records gives each record its
+/// own lines, at the cost of a filler record after each one
+/// (`FormatFeatures::PADDING`). Both pay off with several threads writing
+/// at once; a single writer is fastest packed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedLayout {
+    /// Records start at multiples of this many bytes: a power of two from
+    /// 2, the format's own alignment, to `CACHE_LINE`
+    pub record_alignment: usize,
+
+    /// Whether the reservation word and each commit counter have a cache
+    /// line of their own
+    pub pad_cursors: bool,
+}
+
+impl SharedLayout {
+    /// Counters and records packed together, the smallest layout.
+    pub const PACKED: SharedLayout = SharedLayout { record_alignment: 2, pad_cursors: false };
+
+    /// Counters and records each on cache lines of their own.
+    pub const CACHE_ALIGNED: SharedLayout = SharedLayout { record_alignment: CACHE_LINE, pad_cursors: true };
+}
+
+impl Default for SharedLayout {
+    fn default() -> Self {
+        Self::PACKED
+    }
+}
+
+/// A cache line of counters.
+#[repr(align(64))]
+#[derive(Default)]
+struct CacheLine([AtomicU64; 8]);
+
+/// A Logger whose writes are async-signal-safe, see the module
+/// documentation.
+///
+/// The logger is `Sync`: every thread and signal handler writes through a
+/// shared reference, usually to a `static`.
+pub struct AsyncSignalSafeLogger<const CAP: usize> {
+    buffers: [*mut u8; 2],
+
+    /// The counters written by every writer, `stride` words apart:
+    /// the reservation word, `buffer(1) | position(POS_BITS) | last
+    /// time(TIME_BITS)`, the bytes of each buffer whose writes are
+    /// complete, and the records dropped
+    cursors: Box<[CacheLine]>,
+    stride: usize,
+
+    /// Base timestamp of each buffer, in microseconds since the UNIX epoch
+    bases: [AtomicU64; 2],
+
+    layout: SharedLayout,
+
+    drain: Mutex<DrainState>,
+}
+
+/// State only `drain` touches
+struct DrainState {
+    handler: Box<dyn BufferHandler + Send>,
+
+    /// End of the records written by `drain` itself in each buffer
+    prologue_end: [usize; 2],
+}
+
+// The buffers are plain memory written at reserved, disjoint offsets and
+// handed to the handler only once every write to them completed
+unsafe impl<const CAP: usize> Send for AsyncSignalSafeLogger<CAP> {}
+unsafe impl<const CAP: usize> Sync for AsyncSignalSafeLogger<CAP> {}
+
+impl<const CAP: usize> AsyncSignalSafeLogger<CAP> {
+    /// Creates a logger with two buffers of `CAP` bytes, handing full
+    /// buffers to `handler` on `drain`.
+    ///
+    /// This also sets up everything the write path would otherwise set up
+    /// on first use: the clock calibration and the static string table.
+    ///
+    /// # Panics
+    ///
+    /// If `CAP` exceeds `MAX_CAPACITY`, or is too small for the records
+    /// starting a buffer.
+    pub fn new(handler: impl BufferHandler + Send + 'static) -> Self {
+        Self::with_layout(handler, SharedLayout::PACKED)
+    }
+
+    /// Creates a logger like `new`, with its counters and records laid out
+    /// by `layout`.
+    ///
+    /// # Panics
+    ///
+    /// As `new`, and if the record alignment isn't a power of two from 2 to
+    /// `CACHE_LINE`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binary_logger::{BufferHandler, log_signal_safe};
+    /// # use binary_logger::signal_safe::{AsyncSignalSafeLogger, SharedLayout};
+    /// # struct NullHandler;
+    /// # impl BufferHandler for NullHandler {
+    /// #     fn handle_buffer(&self, _data: &[u8]) {}
+    /// # }
+    /// // Written by every worker thread at once
+    /// let logger = AsyncSignalSafeLogger::<65536>::with_layout(NullHandler, SharedLayout::CACHE_ALIGNED);
+    /// assert!(log_signal_safe!(logger, "Job {} done", 7i32));
+    /// ```
+    pub fn with_layout(handler: impl BufferHandler + Send + 'static, layout: SharedLayout) -> Self {
+        let alignment = layout.record_alignment;
+        assert!(alignment.is_power_of_two() && (2..=CACHE_LINE).contains(&alignment), "Record alignment must be a power of two from 2 to {}", CACHE_LINE);
+        assert!(CAP <= MAX_CAPACITY, "Buffer capacity exceeds {} bytes", MAX_CAPACITY);
+        assert!(CAP >= BUFFER_HEADER_SIZE + STREAM_HEADER_RECORD_SIZE + TIME_BASE_RECORD_SIZE + padding_after(0, alignment), "Buffer capacity too small");
+        calibration();
+        string_registry::registered_count();
+
+        let (lines, stride) = match layout.pad_cursors {
+            true => (DROPPED + 1, CACHE_LINE / 8),
+            false => (1, 1),
+        };
+        let logger = Self {
+            buffers: [alloc_buffer::<CAP>(), alloc_buffer::<CAP>()],
+            cursors: (0..lines).map(|_| CacheLine::default()).collect(),
+            stride,
+            bases: [AtomicU64::new(0), AtomicU64::new(0)],
+            layout,
+            drain: Mutex::new(DrainState { handler: Box::new(handler), prologue_end: [0; 2] }),
+        };
+        let end = logger.start_buffer(&mut logger.drain.lock().unwrap(), 0, true);
+        logger.cursor(RESERVATION).store(pack(0, end, 0), Ordering::Release);
+        logger
+    }
+
+    /// Returns the layout of the logger's counters and records.
+    pub fn layout(&self) -> SharedLayout {
+        self.layout
+    }
+
+    /// Returns one of the counters: `RESERVATION`, `COMMITTED` or `DROPPED`.
+    #[inline]
+    fn cursor(&self, index: usize) -> &AtomicU64 {
+        let word = index * self.stride;
+        &self.cursors[word / 8].0[word % 8]
+    }
+
+    /// Writes a record with the raw bytes of each argument, returning
+    /// whether it was written, or dropped because the active buffer is full.
+    ///
+    /// This is async-signal-safe. `log_signal_safe!` calls it with the ID
+    /// of a literal format string.
+    pub fn write(&self, level: Option<Level>, format_id: u32, args: &[&[u8]]) -> bool {
+        let payload_len = RawCodec.encoded_len(args);
+        if payload_len + 8 >= u16::MAX as usize {
+            self.cursor(DROPPED).fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        // Reserve the record, timed after the previous reservation
+        let wide_len = if is_wide_id(format_id) { WIDE_ID_SIZE } else { 0 };
+        let alignment = self.layout.record_alignment;
+        let reservation = self.cursor(RESERVATION);
+        let mut current = reservation.load(Ordering::Acquire);
+        let (index, pos, time, new_epoch, size, slot) = loop {
+            let (index, pos, last) = unpack(current);
+            let base = self.bases[index].load(Ordering::Relaxed);
+            let time = now_micros().saturating_sub(base).clamp(last, TIME_MASK);
+
+            // A record in a later epoch than its predecessor carries the
+            // epoch's base, which readers couldn't infer from a wrap
+            let new_epoch = time / EPOCH_MICROS != last / EPOCH_MICROS;
+            let size = (RECORD_HEADER_SIZE + wide_len + if new_epoch { 8 } else { 0 } + payload_len + 1) & !1;
+            let slot = size + padding_after(size, alignment);
+            if pos + slot > CAP {
+                self.cursor(DROPPED).fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            match reservation.compare_exchange_weak(current, pack(index, pos + slot, time), Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => break (index, pos, time, new_epoch, size, slot),
+                Err(actual) => current = actual,
+            }
+        };
+
+        unsafe {
+            let record = self.buffers[index].add(pos);
+            *record = if new_epoch { RECORD_TYPE_BASE } else { RECORD_TYPE_NORMAL };
+            *record.add(1) = Level::to_flags(level);
+            std::ptr::write_unaligned(record.add(2) as *mut u16, ((time % EPOCH_MICROS) as u16).to_le());
+            let field = if wide_len > 0 { WIDE_ID } else { format_id as u16 };
+            std::ptr::write_unaligned(record.add(4) as *mut u16, field.to_le());
+            let base_len = if new_epoch { 8 } else { 0 };
+            std::ptr::write_unaligned(record.add(6) as *mut u16, ((base_len + payload_len) as u16).to_le());
+            let mut offset = RECORD_HEADER_SIZE;
+            if wide_len > 0 {
+                std::ptr::write_unaligned(record.add(offset) as *mut u32, format_id.to_le());
+                offset += wide_len;
+            }
+            if new_epoch {
+                let base = self.bases[index].load(Ordering::Relaxed) + time / EPOCH_MICROS * EPOCH_MICROS;
+                std::ptr::write_unaligned(record.add(offset) as *mut u64, base.to_le());
+                offset += 8;
+            }
+            RawCodec.encode(args, std::slice::from_raw_parts_mut(record.add(offset), payload_len));
+            if offset + payload_len < size {
+                *record.add(size - 1) = 0;
+            }
+            if slot > size {
+                write_padding(record.add(size), slot - size);
+            }
+        }
+        self.cursor(COMMITTED[index]).fetch_add(slot as u64, Ordering::Release);
+        true
+    }
+
+    /// Returns the number of records dropped because the active buffer was
+    /// full.
+    pub fn dropped(&self) -> u64 {
+        self.cursor(DROPPED).load(Ordering::Relaxed)
+    }
+
+    /// Hands the records written so far to the BufferHandler, if there are
+    /// any, and lets writes continue in the other buffer.
+    ///
+    /// Waits for the writes in progress in the drained buffer to complete.
+    /// This is **not** async-signal-safe; see the module documentation.
+    pub fn drain(&self) {
+        let mut state = self.drain.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (index, pos, _) = unpack(self.cursor(RESERVATION).load(Ordering::Acquire));
+        if pos == state.prologue_end[index] {
+            return;
+        }
+
+        let next = 1 - index;
+        let start = self.start_buffer(&mut state, next, false);
+        let (_, end, _) = unpack(self.cursor(RESERVATION).swap(pack(next, start, 0), Ordering::AcqRel));
+
+        // Writers that reserved before the switch may still be copying
+        while self.cursor(COMMITTED[index]).load(Ordering::Acquire) != end as u64 {
+            std::thread::yield_now();
+        }
+
+        let buffer = self.buffers[index];
+        unsafe {
+            *(buffer as *mut u64) = (end as u64).to_le();
+        }
+        state.handler.handle_buffer(unsafe { std::slice::from_raw_parts(buffer, end) });
+    }
+
+    /// Writes the records starting buffer `index`, the stream header if
+    /// `stream_header`, and a base record, and returns where they end.
+    ///
+    /// The buffer must not be the active one.
+    fn start_buffer(&self, state: &mut DrainState, index: usize, stream_header: bool) -> usize {
+        let buffer = self.buffers[index];
+        let base = now_micros();
+        let mut pos = BUFFER_HEADER_SIZE;
+        unsafe {
+            if stream_header {
+                let record = buffer.add(pos);
+                *record = RECORD_TYPE_STREAM_HEADER;
+                *record.add(1) = 0;
+                std::ptr::write_unaligned(record.add(2) as *mut u16, 0);
+                std::ptr::write_unaligned(record.add(4) as *mut u16, 0);
+                std::ptr::write_unaligned(record.add(6) as *mut u16, (STREAM_HEADER_PAYLOAD_SIZE as u16).to_le());
+                let payload = record.add(RECORD_HEADER_SIZE);
+                std::ptr::copy_nonoverlapping(STREAM_MAGIC.as_ptr(), payload, STREAM_MAGIC.len());
+                *payload.add(4) = STREAM_VERSION;
+                *payload.add(5) = RawCodec.id();
+                let features = match self.layout.record_alignment > 2 {
+                    true => FEATURES | FormatFeatures::PADDING,
+                    false => FEATURES,
+                };
+                std::ptr::write_unaligned(payload.add(6) as *mut u32, features.bits().to_le());
+                pos += STREAM_HEADER_RECORD_SIZE;
+            }
+
+            // Base record without a log entry (format ID 0)
+            let record = buffer.add(pos);
+            *record = RECORD_TYPE_BASE;
+            *record.add(1) = 0;
+            std::ptr::write_unaligned(record.add(2) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(4) as *mut u16, 0);
+            std::ptr::write_unaligned(record.add(6) as *mut u16, 8u16.to_le());
+            std::ptr::write_unaligned(record.add(RECORD_HEADER_SIZE) as *mut u64, base.to_le());
+            pos += TIME_BASE_RECORD_SIZE;
+
+            // The first record starts a slot
+            let padding = padding_after(pos, self.layout.record_alignment);
+            if padding > 0 {
+                write_padding(buffer.add(pos), padding);
+                pos += padding;
+            }
+        }
+        self.bases[index].store(base, Ordering::Relaxed);
+        self.cursor(COMMITTED[index]).store(pos as u64, Ordering::Relaxed);
+        state.prologue_end[index] = pos;
+        pos
+    }
+}
+
+impl<const CAP: usize> Drop for AsyncSignalSafeLogger<CAP> {
+    fn drop(&mut self) {
+        self.drain();
+        for buffer in self.buffers {
+            free_buffer::<CAP>(buffer);
+        }
+    }
+}
+
+/// Returns the current time in microseconds since the UNIX epoch, on the
+/// process-wide calibration.
+fn now_micros() -> u64 {
+    calibration().ticks_to_micros(get_timestamp())
+}
+
+/// Returns the size of the filler record that ends a slot after `end`
+/// bytes: none if `end` is aligned, and never less than a record header.
+fn padding_after(end: usize, alignment: usize) -> usize {
+    let padding = end.next_multiple_of(alignment) - end;
+    match padding {
+        0 => 0,
+        padding if padding < RECORD_HEADER_SIZE => padding + alignment,
+        padding => padding,
+    }
+}
+
+/// Writes an untimed filler record of `size` bytes, at least a record
+/// header, at `record`.
+///
+/// # Safety
+///
+/// `record` must be valid for writing `size` bytes.
+unsafe fn write_padding(record: *mut u8, size: usize) {
+    *record = RECORD_TYPE_PADDING;
+    std::ptr::write_bytes(record.add(1), 0, 5);
+    std::ptr::write_unaligned(record.add(6) as *mut u16, ((size - RECORD_HEADER_SIZE) as u16).to_le());
+    std::ptr::write_bytes(record.add(RECORD_HEADER_SIZE), 0, size - RECORD_HEADER_SIZE);
+}
+
+fn pack(index: usize, pos: usize, time: u64) -> u64 {
+    (index as u64) << (POS_BITS + TIME_BITS) | (pos as u64) << TIME_BITS | time
+}
+
+/// Splits a reservation word into buffer index, position and time.
+fn unpack(word: u64) -> (usize, usize, u64) {
+    ((word >> (POS_BITS + TIME_BITS)) as usize, ((word >> TIME_BITS) & POS_MASK) as usize, word & TIME_MASK)
+}
+
+/// Logs a record from a signal handler or other async-signal context, with
+/// an optional level, through an `AsyncSignalSafeLogger`.
+///
+/// The format string must be a literal; arguments are copied as their
+/// in-memory bytes, so use `bool`, `i32` or `f64` values (see
+/// `signal_safe`). Evaluates to whether the record was written.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{BufferHandler, log_signal_safe};
+/// # use binary_logger::level::Level;
+/// # use binary_logger::signal_safe::AsyncSignalSafeLogger;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let logger = AsyncSignalSafeLogger::<4096>::new(NullHandler);
+/// assert!(log_signal_safe!(logger, "Child {} exited", 4242i32));
+/// assert!(log_signal_safe!(logger, Level::Error, "Fault at depth {}, fatal: {}", 3i32, true));
+/// ```
+#[macro_export]
+macro_rules! log_signal_safe {
+    ($logger:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        $crate::__log_site!($fmt, None, None);
+        $logger.write(None, $crate::__string_id!($fmt), &[$($crate::binary_logger::arg_bytes(&$arg)),*])
+    }};
+    ($logger:expr, $level:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        $crate::__log_site!($fmt, None, Some(::std::stringify!($level)));
+        let level: $crate::level::Level = $level;
+        $logger.write(Some(level), $crate::__string_id!($fmt), &[$($crate::binary_logger::arg_bytes(&$arg)),*])
+    }};
+}
@@ -0,0 +1,222 @@
+#![allow(dead_code)]
+
+//! Deterministic simulation of logging scenarios (`sim` feature).
+//!
+//! A Logger reads the hardware counter for every record and measures its
+//! handler calls with the monotonic clock, so no two runs write the same
+//! bytes. A `Simulation` pairs a `VirtualClock`, which moves only when told
+//! to, with a `MemorySink` that keeps every buffer it is handed, and makes
+//! Loggers that take all their time from the clock
+//! (`Logger::set_clock_source`). A scenario then decides exactly when
+//! records are written, when a time base runs out and when buffers switch,
+//! and replays bit-for-bit, which makes golden-file tests of the writer
+//! possible.
+//!
+//! What still varies between processes is outside the clock: global
+//! sequence numbers are counted process-wide, and format IDs depend on the
+//! strings the binary logs (see `string_registry`).
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::log_record;
+//! # use binary_logger::sim::Simulation;
+//! # use std::time::Duration;
+//! fn scenario() -> Vec<u8> {
+//!     let sim = Simulation::new();
+//!     {
+//!         let mut logger = sim.logger::<1024>();
+//!         for i in 0..100u32 {
+//!             log_record!(logger, "Tick {}", i).unwrap();
+//!             // Past a 16-bit relative timestamp every 40 records
+//!             sim.clock.advance(Duration::from_micros(1700));
+//!         }
+//!     }
+//!     sim.sink.bytes()
+//! }
+//! assert_eq!(scenario(), scenario());
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::binary_logger::{BufferHandler, Logger};
+use crate::efficient_clock::ClockSource;
+
+/// Where virtual clocks start by default: 2024-01-01T00:00:00Z, in
+/// microseconds since the UNIX epoch
+pub const SIM_EPOCH_MICROS: u64 = 1_704_067_200_000_000;
+
+/// A clock that moves only when advanced.
+///
+/// Clocks are shared: the Loggers running on one hold it for as long as
+/// they run.
+#[derive(Debug)]
+pub struct VirtualClock {
+    micros: AtomicU64,
+}
+
+impl VirtualClock {
+    /// Creates a clock standing at `SIM_EPOCH_MICROS`.
+    pub fn new() -> Arc<VirtualClock> {
+        Self::starting_at(SIM_EPOCH_MICROS)
+    }
+
+    /// Creates a clock standing at `micros` microseconds since the UNIX
+    /// epoch.
+    pub fn starting_at(micros: u64) -> Arc<VirtualClock> {
+        Arc::new(VirtualClock { micros: AtomicU64::new(micros) })
+    }
+
+    /// Returns the clock's time.
+    pub fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_micros(self.micros())
+    }
+
+    /// Returns the clock's time in microseconds since the UNIX epoch.
+    pub fn micros(&self) -> u64 {
+        self.micros.load(Ordering::Acquire)
+    }
+
+    /// Moves the clock forward by `by`, to the microsecond.
+    pub fn advance(&self, by: Duration) {
+        self.micros.fetch_add(by.as_micros() as u64, Ordering::AcqRel);
+    }
+
+    /// Sets the clock to `micros` microseconds since the UNIX epoch.
+    ///
+    /// The clock may be set back, like a wall clock stepped by NTP; Loggers
+    /// then hold their time until it catches up.
+    pub fn set(&self, micros: u64) {
+        self.micros.store(micros, Ordering::Release);
+    }
+}
+
+impl ClockSource for VirtualClock {
+    fn now_micros(&self) -> u64 {
+        self.micros()
+    }
+}
+
+/// A buffer handed to a `MemorySink`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimBuffer {
+    /// Virtual time of the handler call, in microseconds since the UNIX
+    /// epoch
+    pub at_micros: u64,
+
+    /// The buffer's bytes
+    pub data: Vec<u8>,
+}
+
+/// A BufferHandler keeping every buffer in memory, with the virtual time
+/// it was handed over.
+///
+/// Clones share their buffers, so a clone kept by the test reads what the
+/// Logger's copy received. A sink can also stand in for a slow or failing
+/// one: `with_latency` advances the clock during each call, and
+/// `failing_on` panics on chosen calls, exactly as often in every run.
+#[derive(Debug, Clone)]
+pub struct MemorySink {
+    clock: Arc<VirtualClock>,
+    state: Arc<Mutex<SinkState>>,
+}
+
+#[derive(Debug, Default)]
+struct SinkState {
+    buffers: Vec<SimBuffer>,
+    latency: Duration,
+    failing: Vec<usize>,
+    calls: usize,
+}
+
+impl MemorySink {
+    /// Creates a sink that timestamps buffers with `clock`.
+    pub fn new(clock: Arc<VirtualClock>) -> Self {
+        MemorySink { clock, state: Arc::default() }
+    }
+
+    /// Makes each handler call take `latency` of virtual time.
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.lock().latency = latency;
+        self
+    }
+
+    /// Makes the handler calls with these indexes, counted from 0, panic
+    /// without keeping their buffer.
+    pub fn failing_on(self, calls: &[usize]) -> Self {
+        self.lock().failing = calls.to_vec();
+        self
+    }
+
+    /// Returns the buffers received so far.
+    pub fn buffers(&self) -> Vec<SimBuffer> {
+        self.lock().buffers.clone()
+    }
+
+    /// Returns the buffers received so far as one log.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.lock().buffers.iter().flat_map(|buffer| buffer.data.iter().copied()).collect()
+    }
+
+    /// Returns how many times the handler was called, failed calls
+    /// included.
+    pub fn calls(&self) -> usize {
+        self.lock().calls
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SinkState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl BufferHandler for MemorySink {
+    fn handle_buffer(&self, data: &[u8]) {
+        let mut state = self.lock();
+        let call = state.calls;
+        state.calls += 1;
+        let at_micros = self.clock.micros();
+        self.clock.advance(state.latency);
+        if state.failing.contains(&call) {
+            drop(state);
+            panic!("simulated failure of handler call {}", call);
+        }
+        state.buffers.push(SimBuffer { at_micros, data: data.to_vec() });
+    }
+}
+
+/// A virtual clock and a sink to run Loggers against.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    /// The clock of the simulation's Loggers
+    pub clock: Arc<VirtualClock>,
+
+    /// The handler of the simulation's Loggers
+    pub sink: MemorySink,
+}
+
+impl Simulation {
+    /// Creates a simulation whose clock stands at `SIM_EPOCH_MICROS`.
+    pub fn new() -> Self {
+        Self::with_sink(MemorySink::new(VirtualClock::new()))
+    }
+
+    /// Creates a simulation with a configured sink, such as
+    /// `MemorySink::with_latency`.
+    pub fn with_sink(sink: MemorySink) -> Self {
+        Simulation { clock: sink.clock.clone(), sink }
+    }
+
+    /// Creates a Logger writing to the simulation's sink, on its clock.
+    pub fn logger<const CAP: usize>(&self) -> Logger<CAP> {
+        let mut logger = Logger::new(self.sink.clone());
+        logger.set_clock_source(Some(self.clock.clone()));
+        logger
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,149 @@
+#![allow(dead_code)]
+
+//! Every logging call site compiled into the program.
+//!
+//! Like their format strings (see `string_registry`), the call sites of
+//! `log_record!`, `log_record_at!`, `log_tagged!`, `log_signal_safe!` and
+//! the macros built on them are collected by the linker into `LOG_SITES`.
+//! `sites()` lists them, each with its format string, channel, level,
+//! module and source location, whether or not it has run. This answers
+//! what a binary is able to log, for audits of what may end up in logs
+//! (personal data, secrets) or reviews of levels across a codebase.
+//!
+//! A level is recorded as written at the call site. `LogSite::level`
+//! resolves the usual `Level::Warn` form; a level computed at runtime
+//! shows as its expression. `log_count!` events are counters rather than
+//! records and aren't listed.
+//!
+//! `list_if_requested` gives an application a `--list-sites` option: call
+//! it first thing in `main`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, log_record_at, sites};
+//! # use binary_logger::level::Level;
+//! # struct NullHandler;
+//! # impl BufferHandler for NullHandler {
+//! #     fn handle_buffer(&self, _data: &[u8]) {}
+//! # }
+//! fn charge(logger: &mut Logger<4096>, card: u64) {
+//!     log_record_at!(logger, Level::Warn, channel: "billing", "Card {} declined", card).unwrap();
+//! }
+//!
+//! // Listed without `charge` ever running
+//! let site = sites().into_iter().find(|site| site.format == "Card {} declined").unwrap();
+//! assert_eq!((site.channel, site.level()), (Some("billing"), Some(Level::Warn)));
+//! ```
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use linkme::distributed_slice;
+use crate::level::Level;
+
+/// Argument of a program that makes `list_if_requested` list the sites
+pub const LIST_SITES_ARG: &str = "--list-sites";
+
+/// A logging call site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogSite {
+    /// The format string
+    pub format: &'static str,
+
+    /// The channel name, for sites logging to a channel
+    pub channel: Option<&'static str>,
+
+    /// The level expression as written, for sites with a level
+    pub level_expr: Option<&'static str>,
+
+    /// The module path of the call site
+    pub module: &'static str,
+
+    /// The source file of the call site
+    pub file: &'static str,
+
+    /// The line of the call site in `file`
+    pub line: u32,
+}
+
+impl LogSite {
+    /// Returns the site's level, if it has one written as a level name,
+    /// such as `Level::Warn`.
+    pub fn level(&self) -> Option<Level> {
+        let expr = self.level_expr?;
+        expr.rsplit("::").next()?.trim().parse().ok()
+    }
+}
+
+/// Call sites of the logging macros in the program, gathered at link time.
+#[doc(hidden)]
+#[distributed_slice]
+pub static LOG_SITES: [LogSite];
+
+/// Adds the call site of a logging macro to `LOG_SITES`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_site {
+    ($fmt:literal, $channel:expr, $level:expr) => {{
+        #[$crate::string_registry::linkme::distributed_slice($crate::sites::LOG_SITES)]
+        #[linkme(crate = $crate::string_registry::linkme)]
+        static SITE: $crate::sites::LogSite = $crate::sites::LogSite {
+            format: $fmt,
+            channel: $channel,
+            level_expr: $level,
+            module: ::std::module_path!(),
+            file: ::std::file!(),
+            line: ::std::line!(),
+        };
+    }};
+}
+
+/// Returns every logging call site compiled into the program, ordered by
+/// file and line.
+pub fn sites() -> Vec<LogSite> {
+    let mut sites: Vec<LogSite> = LOG_SITES.to_vec();
+    sites.sort_by(|a, b| (a.file, a.line, a.format).cmp(&(b.file, b.line, b.format)));
+    sites
+}
+
+/// Writes `sites` to `out`, one per line with tab-separated fields:
+/// `file:line`, module, level, channel and format string, with `-` for a
+/// missing level or channel.
+///
+/// Levels written as a level name show as the name (`WARN`), other level
+/// expressions as written. Line breaks and tabs in format strings are
+/// escaped (`\n`, `\t`).
+pub fn write_sites(sites: &[LogSite], out: &mut impl Write) -> io::Result<()> {
+    for site in sites {
+        let level = match (site.level(), site.level_expr) {
+            (Some(level), _) => level.as_str(),
+            (None, Some(expr)) => expr,
+            (None, None) => "-",
+        };
+        let format = site.format.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t");
+        writeln!(out, "{}:{}\t{}\t{}\t{}\t{}", site.file, site.line, site.module, level, site.channel.unwrap_or("-"), format)?;
+    }
+    Ok(())
+}
+
+/// Lists the program's call sites on standard output and exits, if the
+/// program was run with `--list-sites` (`LIST_SITES_ARG`).
+///
+/// Call it at the start of `main`, before the program parses its own
+/// arguments. Exits with status 0, or 1 if standard output can't be
+/// written.
+///
+/// # Examples
+///
+/// ```no_run
+/// // At the start of `main`
+/// binary_logger::sites::list_if_requested();
+/// ```
+pub fn list_if_requested() {
+    if !env::args().skip(1).any(|arg| arg == LIST_SITES_ARG) {
+        return;
+    }
+    let written = write_sites(&sites(), &mut io::stdout().lock()).and_then(|_| io::stdout().flush());
+    process::exit(if written.is_ok() { 0 } else { 1 });
+}
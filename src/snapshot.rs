@@ -0,0 +1,316 @@
+#![allow(dead_code)]
+
+//! Read-only snapshot of the process's logging state, for debuggers.
+//!
+//! `debug_snapshot()` collects what can be seen of the logging pipeline from
+//! any thread: the registered strings and, for each Logger published with
+//! `Logger::set_debug_name`, its counters, how full its buffer is and the
+//! state of its BufferHandler. Loggers belong to their threads, so a
+//! published Logger copies its state to shared atomics as it writes records
+//! and hands off buffers; the snapshot reads those copies and never touches
+//! the Loggers themselves. It therefore works while a Logger is stuck,
+//! which is when it is needed: a handler that has been busy for seconds
+//! shows up as `handler_busy_since`.
+//!
+//! `DebugSnapshot::to_json` renders a snapshot as JSON, and
+//! `serve_snapshots` answers HTTP requests with it, so a wedged process can
+//! be looked at from outside:
+//!
+//! ```text
+//! $ curl -s localhost:9412 | jq '.loggers[] | {name, fill, handler_busy_since_us}'
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::binary_logger::{HandlerStatus, LoggerStats};
+use crate::string_registry::registered_strings;
+use crate::verify::write_json_string;
+
+/// State of the published Loggers, dropped with them
+static PUBLISHED: Mutex<Vec<Weak<PublishedState>>> = Mutex::new(Vec::new());
+
+/// The state a Logger publishes, see `Logger::set_debug_name`.
+pub(crate) struct PublishedState {
+    name: String,
+    thread: String,
+    capacity: usize,
+    records_written: AtomicU64,
+    buffers_flushed: AtomicU64,
+    bytes_flushed: AtomicU64,
+    bytes_pending: AtomicU64,
+    dropped_records: AtomicU64,
+    sampled_out: AtomicU64,
+    duplicates_suppressed: AtomicU64,
+    handler_panics: AtomicU64,
+    handler_disabled: AtomicBool,
+    buffers_lost: AtomicU64,
+    last_panic: Mutex<Option<String>>,
+    /// Microseconds since the epoch when the handler was called, 0 while
+    /// it isn't running
+    handler_busy_since: AtomicU64,
+    /// Microseconds since the epoch when the handler last returned
+    last_flush: AtomicU64,
+}
+
+impl PublishedState {
+    /// Registers the state of a Logger with `capacity` byte buffers.
+    pub(crate) fn register(name: &str, capacity: usize) -> Arc<PublishedState> {
+        let current = thread::current();
+        let state = Arc::new(PublishedState {
+            name: name.to_string(),
+            thread: current.name().map(str::to_string).unwrap_or_else(|| format!("{:?}", current.id())),
+            capacity,
+            records_written: AtomicU64::new(0),
+            buffers_flushed: AtomicU64::new(0),
+            bytes_flushed: AtomicU64::new(0),
+            bytes_pending: AtomicU64::new(0),
+            dropped_records: AtomicU64::new(0),
+            sampled_out: AtomicU64::new(0),
+            duplicates_suppressed: AtomicU64::new(0),
+            handler_panics: AtomicU64::new(0),
+            handler_disabled: AtomicBool::new(false),
+            buffers_lost: AtomicU64::new(0),
+            last_panic: Mutex::new(None),
+            handler_busy_since: AtomicU64::new(0),
+            last_flush: AtomicU64::new(0),
+        });
+        let mut published = PUBLISHED.lock().unwrap_or_else(|e| e.into_inner());
+        published.retain(|state| state.strong_count() > 0);
+        published.push(Arc::downgrade(&state));
+        state
+    }
+
+    /// Copies the Logger's counters.
+    #[inline]
+    pub(crate) fn update(&self, stats: &LoggerStats) {
+        self.records_written.store(stats.records_written, Ordering::Relaxed);
+        self.buffers_flushed.store(stats.buffers_flushed, Ordering::Relaxed);
+        self.bytes_flushed.store(stats.bytes_flushed, Ordering::Relaxed);
+        self.bytes_pending.store(stats.bytes_pending, Ordering::Relaxed);
+        self.dropped_records.store(stats.dropped_records, Ordering::Relaxed);
+        self.sampled_out.store(stats.sampled_out, Ordering::Relaxed);
+        self.duplicates_suppressed.store(stats.duplicates_suppressed, Ordering::Relaxed);
+    }
+
+    /// Marks the handler as running from now on.
+    pub(crate) fn handler_started(&self) {
+        self.handler_busy_since.store(now_micros(), Ordering::Relaxed);
+    }
+
+    /// Marks the handler as returned, with its failures so far.
+    pub(crate) fn handler_returned(&self, status: &HandlerStatus) {
+        self.handler_busy_since.store(0, Ordering::Relaxed);
+        self.last_flush.store(now_micros(), Ordering::Relaxed);
+        self.handler_panics.store(status.panics, Ordering::Relaxed);
+        self.handler_disabled.store(status.disabled, Ordering::Relaxed);
+        self.buffers_lost.store(status.buffers_lost, Ordering::Relaxed);
+        if status.panics > 0 {
+            *self.last_panic.lock().unwrap_or_else(|e| e.into_inner()) = status.last_panic.clone();
+        }
+    }
+
+    fn snapshot(&self) -> LoggerSnapshot {
+        LoggerSnapshot {
+            name: self.name.clone(),
+            thread: self.thread.clone(),
+            capacity: self.capacity,
+            stats: LoggerStats {
+                records_written: self.records_written.load(Ordering::Relaxed),
+                buffers_flushed: self.buffers_flushed.load(Ordering::Relaxed),
+                bytes_flushed: self.bytes_flushed.load(Ordering::Relaxed),
+                bytes_pending: self.bytes_pending.load(Ordering::Relaxed),
+                dropped_records: self.dropped_records.load(Ordering::Relaxed),
+                sampled_out: self.sampled_out.load(Ordering::Relaxed),
+                duplicates_suppressed: self.duplicates_suppressed.load(Ordering::Relaxed),
+            },
+            handler: HandlerStatus {
+                panics: self.handler_panics.load(Ordering::Relaxed),
+                last_panic: self.last_panic.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+                disabled: self.handler_disabled.load(Ordering::Relaxed),
+                buffers_lost: self.buffers_lost.load(Ordering::Relaxed),
+            },
+            handler_busy_since: from_micros(self.handler_busy_since.load(Ordering::Relaxed)),
+            last_flush: from_micros(self.last_flush.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64)
+}
+
+fn from_micros(micros: u64) -> Option<SystemTime> {
+    (micros != 0).then(|| UNIX_EPOCH + Duration::from_micros(micros))
+}
+
+fn write_time(out: &mut String, time: Option<SystemTime>) {
+    match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+        Some(since) => { let _ = write!(out, "{}", since.as_micros()); }
+        None => out.push_str("null"),
+    }
+}
+
+/// The published state of one Logger, see `debug_snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggerSnapshot {
+    /// Name given to `Logger::set_debug_name`
+    pub name: String,
+
+    /// Name of the thread that published the Logger, or its ID if it has
+    /// none
+    pub thread: String,
+
+    /// Size of the Logger's buffers in bytes
+    pub capacity: usize,
+
+    /// What the Logger has written, as of its last record or buffer
+    pub stats: LoggerStats,
+
+    /// Failures of the BufferHandler, as of its last return
+    pub handler: HandlerStatus,
+
+    /// When the BufferHandler was called, if it hasn't returned yet
+    pub handler_busy_since: Option<SystemTime>,
+
+    /// When the BufferHandler last returned, if it has been called
+    pub last_flush: Option<SystemTime>,
+}
+
+impl LoggerSnapshot {
+    /// Returns how full the active buffer is, from 0 to 1.
+    pub fn fill(&self) -> f64 {
+        self.stats.bytes_pending as f64 / self.capacity as f64
+    }
+}
+
+/// The logging state of the process, see `debug_snapshot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugSnapshot {
+    /// When the snapshot was taken
+    pub taken: SystemTime,
+
+    /// Every registered string with its ID, static ones first
+    pub strings: Vec<(u32, &'static str)>,
+
+    /// The published Loggers that are still alive, in the order they were
+    /// published
+    pub loggers: Vec<LoggerSnapshot>,
+}
+
+impl DebugSnapshot {
+    /// Renders the snapshot as a JSON object, with times in microseconds
+    /// since the UNIX epoch and `null` for times that don't apply.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str("{\"taken_us\":");
+        write_time(&mut out, Some(self.taken));
+        out.push_str(",\"strings\":[");
+        for (i, (id, s)) in self.strings.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{{\"id\":{},\"string\":", id);
+            write_json_string(&mut out, s);
+            out.push('}');
+        }
+        out.push_str("],\"loggers\":[");
+        for (i, logger) in self.loggers.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let stats = &logger.stats;
+            out.push_str("{\"name\":");
+            write_json_string(&mut out, &logger.name);
+            out.push_str(",\"thread\":");
+            write_json_string(&mut out, &logger.thread);
+            let _ = write!(out, ",\"capacity\":{},\"fill\":{},\"records_written\":{},\"buffers_flushed\":{},\
+                \"bytes_flushed\":{},\"bytes_pending\":{},\"dropped_records\":{},\"sampled_out\":{},\"duplicates_suppressed\":{}",
+                logger.capacity, logger.fill(), stats.records_written, stats.buffers_flushed,
+                stats.bytes_flushed, stats.bytes_pending, stats.dropped_records, stats.sampled_out,
+                stats.duplicates_suppressed);
+            let handler = &logger.handler;
+            let _ = write!(out, ",\"handler_panics\":{},\"handler_disabled\":{},\"buffers_lost\":{},\"last_panic\":",
+                handler.panics, handler.disabled, handler.buffers_lost);
+            match &handler.last_panic {
+                Some(message) => write_json_string(&mut out, message),
+                None => out.push_str("null"),
+            }
+            out.push_str(",\"handler_busy_since_us\":");
+            write_time(&mut out, logger.handler_busy_since);
+            out.push_str(",\"last_flush_us\":");
+            write_time(&mut out, logger.last_flush);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Takes a snapshot of the registered strings and the published Loggers.
+///
+/// Only Loggers published with `Logger::set_debug_name` are listed. Their
+/// counters are those of their last record or buffer handoff; records
+/// dropped or skipped since then show up with the next one.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::{Logger, BufferHandler, log_record};
+/// # use binary_logger::snapshot::debug_snapshot;
+/// # struct NullHandler;
+/// # impl BufferHandler for NullHandler {
+/// #     fn handle_buffer(&self, _data: &[u8]) {}
+/// # }
+/// let mut logger = Logger::<4096>::new(NullHandler);
+/// logger.set_debug_name(Some("ingest"));
+/// log_record!(logger, "Batch {} received", 7).unwrap();
+///
+/// let snapshot = debug_snapshot();
+/// let ingest = snapshot.loggers.iter().find(|l| l.name == "ingest").unwrap();
+/// assert_eq!(ingest.stats.records_written, 1);
+/// assert!(ingest.fill() > 0.0);
+/// ```
+pub fn debug_snapshot() -> DebugSnapshot {
+    let published: Vec<_> = PUBLISHED.lock().unwrap_or_else(|e| e.into_inner())
+        .iter().filter_map(Weak::upgrade).collect();
+    DebugSnapshot {
+        taken: SystemTime::now(),
+        strings: registered_strings(),
+        loggers: published.iter().map(|state| state.snapshot()).collect(),
+    }
+}
+
+/// Answers HTTP requests on `addr` with `debug_snapshot()` as JSON, from a
+/// thread of its own.
+///
+/// Every request gets the snapshot, whatever its path, and the connection
+/// is closed after the response. The endpoint has no authentication and
+/// lists every registered string, so bind it to a loopback address. The
+/// thread runs until the process exits.
+/// Returns the bound address, which tells the port when `addr` asks for
+/// any (port 0).
+pub fn serve_snapshots(addr: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    thread::Builder::new().name("blog-debug-snapshot".to_string()).spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+            // Read the request head, which isn't needed otherwise
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) && !line.trim_end().is_empty() {
+                line.clear();
+            }
+            let body = debug_snapshot().to_json();
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                Content-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        }
+    })?;
+    Ok(local)
+}
@@ -0,0 +1,471 @@
+#![allow(dead_code)]
+
+//! Long-running stability test of the Logger, backing the `blog-soak` tool.
+//!
+//! A crate trusted with a service's logs has to stay correct for days, not
+//! just for the length of a unit test. `run` logs at a sustained rate for
+//! as long as configured and, while it does, verifies every segment it
+//! produces on a thread of its own:
+//!
+//! * checksums: the log is hash-chained (`Logger::set_audit_chain`) and
+//!   every segment must continue the chain of the one before it
+//! * counts: global sequence numbers run without gaps across segments, and
+//!   every record logged is read back by the end of the run
+//! * timestamp continuity: time never goes backwards, consecutive entries
+//!   are at most `max_gap` apart, and log time stays within `max_drift` of
+//!   the wall clock, sampled by a checkpoint record every second
+//!
+//! The run exercises segment rotation (a new file every `segment_bytes`),
+//! dictionary growth (a new format string every `new_format_every`,
+//! published through the dictionary channel) and clock re-anchoring
+//! (`efficient_clock::reanchor` every `reanchor_every`). Verified segments
+//! are deleted unless kept, so a day at full rate needs little disk.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use binary_logger::soak::{run, SoakConfig};
+//! # use std::time::Duration;
+//! let mut config = SoakConfig::new("/var/tmp/soak");
+//! config.rate = 50_000;
+//! config.duration = Duration::from_secs(24 * 3600);
+//! let report = run(&config, |segment| println!("{}", segment)).unwrap();
+//! assert!(report.passed(), "{}", report.to_json());
+//! ```
+
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::audit::verify_chain;
+use crate::binary_logger::{BufferHandler, Logger};
+use crate::efficient_clock::reanchor;
+use crate::fixed::Fixed;
+use crate::log_record;
+use crate::log_reader::{LogReader, LogValue};
+use crate::string_registry::register_string;
+use crate::verify::write_json_string;
+
+/// Size of the soak Logger's buffers
+const SOAK_BUFFER_SIZE: usize = 64 << 10;
+
+/// Interval between checkpoint records
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most format strings a run registers, well inside the 16-bit registry
+const MAX_NEW_FORMATS: u64 = 20_000;
+
+/// Most failures listed per segment
+const MAX_SEGMENT_FAILURES: usize = 10;
+
+/// What a soak run logs and checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoakConfig {
+    /// Directory the segments are written to
+    pub dir: PathBuf,
+
+    /// Records logged per second
+    pub rate: u64,
+
+    /// How long to log for
+    pub duration: Duration,
+
+    /// Size in bytes after which a segment is closed and verified
+    pub segment_bytes: u64,
+
+    /// Interval between clock re-anchorings, which is also their slew
+    pub reanchor_every: Duration,
+
+    /// Interval between new format strings
+    pub new_format_every: Duration,
+
+    /// Longest time allowed between consecutive entries
+    pub max_gap: Duration,
+
+    /// Largest difference allowed between log time and the wall clock
+    pub max_drift: Duration,
+
+    /// Whether verified segments are kept; segments that fail are always
+    /// kept
+    pub keep_segments: bool,
+}
+
+impl SoakConfig {
+    /// Returns the defaults for a day-long run writing to `dir`: 10,000
+    /// records a second in 64 MiB segments, re-anchoring the clock every
+    /// minute.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SoakConfig {
+            dir: dir.into(),
+            rate: 10_000,
+            duration: Duration::from_secs(24 * 3600),
+            segment_bytes: 64 << 20,
+            reanchor_every: Duration::from_secs(60),
+            new_format_every: Duration::from_secs(10),
+            max_gap: Duration::from_secs(1),
+            max_drift: Duration::from_millis(100),
+            keep_segments: false,
+        }
+    }
+}
+
+/// The verification of one segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentCheck {
+    /// The segment's file
+    pub path: PathBuf,
+
+    /// Size of the segment in bytes
+    pub bytes: u64,
+
+    /// Entries read from the segment
+    pub entries: u64,
+
+    /// What was wrong with the segment, if anything
+    pub failures: Vec<String>,
+}
+
+impl fmt::Display for SegmentCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} bytes, {} entries, ", self.path.display(), self.bytes, self.entries)?;
+        if self.failures.is_empty() {
+            f.write_str("ok")
+        } else {
+            write!(f, "FAILED: {}", self.failures.join("; "))
+        }
+    }
+}
+
+/// Results of a soak run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SoakReport {
+    /// Records logged
+    pub records: u64,
+
+    /// Entries read back from the segments
+    pub entries: u64,
+
+    /// Segments written and verified
+    pub segments: u64,
+
+    /// Bytes written
+    pub bytes: u64,
+
+    /// Format strings registered during the run
+    pub new_formats: u64,
+
+    /// Clock re-anchorings
+    pub reanchors: u64,
+
+    /// Largest clock offset a re-anchoring corrected, in microseconds
+    pub max_offset_us: u64,
+
+    /// Largest difference seen between log time and the wall clock
+    pub max_drift: Duration,
+
+    /// Longest time seen between consecutive entries
+    pub max_gap: Duration,
+
+    /// Every failure, prefixed with its segment
+    pub failures: Vec<String>,
+}
+
+impl SoakReport {
+    /// Returns true if no check failed.
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Renders the report as a single JSON object.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out,
+            "{{\"passed\":{},\"records\":{},\"entries\":{},\"segments\":{},\"bytes\":{},\"new_formats\":{},\
+             \"reanchors\":{},\"max_offset_us\":{},\"max_drift_us\":{},\"max_gap_us\":{},\"failures\":[",
+            self.passed(), self.records, self.entries, self.segments, self.bytes, self.new_formats,
+            self.reanchors, self.max_offset_us, self.max_drift.as_micros(), self.max_gap.as_micros());
+        for (i, failure) in self.failures.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, failure);
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Runs a soak test as configured, calling `progress` with each segment
+/// verified, and returns its results.
+///
+/// Fails only if the directory can't be created; problems with the log
+/// are failures in the report.
+pub fn run(config: &SoakConfig, mut progress: impl FnMut(&SegmentCheck)) -> io::Result<SoakReport> {
+    fs::create_dir_all(&config.dir)?;
+    let checkpoint = register_string("Soak checkpoint {} wall {}");
+    let mut report = SoakReport::default();
+
+    let (closed_tx, closed_rx) = mpsc::channel();
+    let (checked_tx, checked_rx) = mpsc::channel();
+    let verifier = Verifier {
+        checkpoint,
+        max_gap: config.max_gap,
+        max_drift: config.max_drift,
+        keep_segments: config.keep_segments,
+        chain_head: Some([0; 32]),
+        last_sequence: None,
+        last_time: None,
+        max_seen_gap: Duration::ZERO,
+        max_seen_drift: Duration::ZERO,
+    };
+    let verifier = thread::spawn(move || verifier.run(closed_rx, checked_tx));
+
+    let mut collect = |report: &mut SoakReport, check: SegmentCheck| {
+        progress(&check);
+        report.segments += 1;
+        report.bytes += check.bytes;
+        report.entries += check.entries;
+        let name = check.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        report.failures.extend(check.failures.iter().map(|failure| format!("{}: {}", name, failure)));
+    };
+
+    {
+        let handler = SegmentWriter {
+            state: Mutex::new(SegmentState {
+                dir: config.dir.clone(),
+                limit: config.segment_bytes,
+                index: 0,
+                current: None,
+                closed: closed_tx,
+            }),
+        };
+        let mut logger = Logger::<SOAK_BUFFER_SIZE>::new(handler);
+        logger.set_audit_chain(true);
+        logger.set_global_sequence(true);
+        logger.set_dictionary_channel(true);
+
+        let start = Instant::now();
+        let (mut next_checkpoint, mut next_reanchor, mut next_format) = (start, start + config.reanchor_every, start + config.new_format_every);
+        let mut dynamic_format = None;
+        let mut write_failures = 0u64;
+        let label = String::from("steady");
+        loop {
+            let now = Instant::now();
+            let elapsed = now - start;
+            if elapsed >= config.duration {
+                break;
+            }
+
+            if now >= next_checkpoint {
+                let wall = Fixed::<0>(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as i64);
+                let sequence = report.records;
+                write_failures += log_record!(logger, "Soak checkpoint {} wall {}", sequence, wall).is_err() as u64;
+                report.records += 1;
+                next_checkpoint += CHECKPOINT_INTERVAL;
+            }
+            if now >= next_reanchor {
+                let offset = reanchor(config.reanchor_every);
+                report.reanchors += 1;
+                report.max_offset_us = report.max_offset_us.max(offset.unsigned_abs());
+                next_reanchor += config.reanchor_every;
+            }
+            if now >= next_format && report.new_formats < MAX_NEW_FORMATS {
+                // Formats registered at runtime reach readers through the
+                // dictionary channel
+                let format: &'static str = Box::leak(format!("Soak format #{} value {{}}", report.new_formats).into_boxed_str());
+                dynamic_format = Some(register_string(format));
+                report.new_formats += 1;
+                next_format += config.new_format_every;
+            }
+
+            let due = (elapsed.as_micros() * config.rate as u128 / 1_000_000) as u64;
+            while report.records < due {
+                let i = report.records;
+                let result = match (i % 4, dynamic_format) {
+                    (0, _) => {
+                        let (quantity, price) = ((i % 1000) as u32, 100.0 + (i % 997) as f64 / 8.0);
+                        log_record!(logger, "Soak order {} qty {} px {}", i, quantity, price)
+                    }
+                    (1, _) => log_record!(logger, channel: "soak", "Soak event {} state {}", i, "steady"),
+                    (2, Some(format_id)) => logger.write_args(format_id, &[&(i as u32).to_le_bytes()]),
+                    _ => log_record!(logger, "Soak text {} {}", i, label),
+                };
+                write_failures += result.is_err() as u64;
+                report.records += 1;
+            }
+
+            while let Ok(check) = checked_rx.try_recv() {
+                collect(&mut report, check);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        if write_failures > 0 {
+            report.failures.push(format!("{} of {} writes failed", write_failures, report.records));
+        }
+        // Dropping the Logger flushes it, and the handler closes the last
+        // segment
+    }
+
+    for check in checked_rx {
+        collect(&mut report, check);
+    }
+    let (max_gap, max_drift) = verifier.join().map_err(|_| io::Error::other("the verifier panicked"))?;
+    report.max_gap = max_gap;
+    report.max_drift = max_drift;
+    if report.entries != report.records {
+        report.failures.push(format!("{} records logged, {} read back", report.records, report.entries));
+    }
+    Ok(report)
+}
+
+/// Writes buffers to numbered segment files, handing each closed one to
+/// the verifier.
+struct SegmentWriter {
+    state: Mutex<SegmentState>,
+}
+
+struct SegmentState {
+    dir: PathBuf,
+    limit: u64,
+    index: u64,
+    current: Option<(PathBuf, BufWriter<File>, u64)>,
+    closed: Sender<PathBuf>,
+}
+
+impl SegmentState {
+    fn close(&mut self) {
+        if let Some((path, mut file, _)) = self.current.take() {
+            file.flush().unwrap_or_else(|e| panic!("cannot write {}: {}", path.display(), e));
+            let _ = self.closed.send(path);
+        }
+    }
+}
+
+impl BufferHandler for SegmentWriter {
+    fn handle_buffer(&self, data: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.current.is_none() {
+            let path = state.dir.join(format!("soak-{:06}.blog", state.index));
+            let file = File::create(&path).unwrap_or_else(|e| panic!("cannot create {}: {}", path.display(), e));
+            state.index += 1;
+            state.current = Some((path, BufWriter::new(file), 0));
+        }
+        let (path, file, bytes) = state.current.as_mut().unwrap();
+        file.write_all(data).unwrap_or_else(|e| panic!("cannot write {}: {}", path.display(), e));
+        *bytes += data.len() as u64;
+        if *bytes >= state.limit {
+            state.close();
+        }
+    }
+}
+
+impl Drop for SegmentWriter {
+    fn drop(&mut self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).close();
+    }
+}
+
+/// Checks segments in order, carrying the chain, sequence and time across
+/// them.
+struct Verifier {
+    checkpoint: u32,
+    max_gap: Duration,
+    max_drift: Duration,
+    keep_segments: bool,
+
+    /// Hash of the last buffer verified, or `None` once the chain broke
+    chain_head: Option<[u8; 32]>,
+    last_sequence: Option<u64>,
+    last_time: Option<SystemTime>,
+    max_seen_gap: Duration,
+    max_seen_drift: Duration,
+}
+
+impl Verifier {
+    /// Checks the segments received until the writer is done, and returns
+    /// the longest gap and largest drift seen.
+    fn run(mut self, closed: Receiver<PathBuf>, checked: Sender<SegmentCheck>) -> (Duration, Duration) {
+        for path in closed {
+            let check = self.check(&path);
+            if check.failures.is_empty() && !self.keep_segments {
+                let _ = fs::remove_file(&path);
+            }
+            let _ = checked.send(check);
+        }
+        (self.max_seen_gap, self.max_seen_drift)
+    }
+
+    fn check(&mut self, path: &Path) -> SegmentCheck {
+        let mut check = SegmentCheck { path: path.to_path_buf(), bytes: 0, entries: 0, failures: Vec::new() };
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                check.failures.push(format!("cannot read: {}", e));
+                return check;
+            }
+        };
+        check.bytes = data.len() as u64;
+
+        if let Some(head) = self.chain_head {
+            match verify_chain(&data, Some(head)) {
+                Ok(chain) => self.chain_head = Some(chain.head),
+                Err(e) => {
+                    check.failures.push(format!("hash chain: {}", e));
+                    self.chain_head = None;
+                }
+            }
+        }
+
+        let mut failures = Vec::new();
+        let mut reader = LogReader::new(&data);
+        while let Some(entry) = reader.read_entry() {
+            check.entries += 1;
+            if entry.format_string.is_none() {
+                failures.push(format!("entry {}: unknown format {}", entry.id, entry.format_id));
+            }
+            match (self.last_sequence, entry.sequence) {
+                (_, None) => failures.push(format!("entry {}: no sequence number", entry.id)),
+                (Some(last), Some(sequence)) if sequence != last + 1 => {
+                    failures.push(format!("entry {}: sequence {} follows {}", entry.id, sequence, last));
+                }
+                _ => {}
+            }
+            self.last_sequence = entry.sequence.or(self.last_sequence);
+
+            if let Some(last) = self.last_time {
+                match entry.timestamp.duration_since(last) {
+                    Ok(gap) if gap > self.max_gap => {
+                        failures.push(format!("entry {}: {}us after the previous entry", entry.id, gap.as_micros()));
+                    }
+                    Ok(_) => {}
+                    Err(e) => failures.push(format!("entry {}: time goes back by {}us", entry.id, e.duration().as_micros())),
+                }
+                self.max_seen_gap = self.max_seen_gap.max(entry.timestamp.duration_since(last).unwrap_or_default());
+            }
+            self.last_time = Some(self.last_time.map_or(entry.timestamp, |last| last.max(entry.timestamp)));
+
+            if entry.format_id == self.checkpoint {
+                if let Some(LogValue::Fixed { value: wall, .. }) = entry.parameters.get(1) {
+                    let logged = entry.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as i64;
+                    let drift = Duration::from_micros(logged.abs_diff(*wall));
+                    self.max_seen_drift = self.max_seen_drift.max(drift);
+                    if drift > self.max_drift {
+                        failures.push(format!("entry {}: log time {}us off the wall clock", entry.id, drift.as_micros()));
+                    }
+                }
+            }
+        }
+
+        if failures.len() > MAX_SEGMENT_FAILURES {
+            let more = failures.len() - MAX_SEGMENT_FAILURES;
+            failures.truncate(MAX_SEGMENT_FAILURES);
+            failures.push(format!("{} more", more));
+        }
+        check.failures.extend(failures);
+        check
+    }
+}
@@ -0,0 +1,377 @@
+//! Decoding of binary logs from any `io::Read` source.
+//!
+//! `LogReader` decodes a log that is already in memory. `StreamReader`
+//! decodes one read from a file, pipe or socket without loading it whole:
+//! it reads a buffer at a time (using the size in each buffer header),
+//! decodes it, and carries the timestamp and codec state over to the next.
+//!
+//! On network filesystems each read can be a round trip, and decoding then
+//! waits for the storage most of the time. With read-ahead (see
+//! `StreamReader::with_read_ahead`) a helper thread reads the following
+//! buffers while the current one is decoded, so the two overlap. The logs
+//! are read through plain `read` calls rather than mapped, so there is no
+//! `madvise` hint to give; the helper thread plays that role.
+//!
+//! Buffer sizes come from the data, so a corrupt or malicious file can
+//! declare buffers of any size. `ReaderOptions::max_memory` bounds what the
+//! reader holds at once; a buffer that can't fit is reported as an error
+//! instead of being allocated.
+//!
+//! `ReaderOptions::utf8` chooses between reading string arguments that
+//! aren't valid UTF-8 with replacement characters and failing on them.
+
+use std::io::{self, BufReader, Read};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use crate::binary_logger::{BUFFER_HEADER_SIZE, COMPRESSED_FRAME};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::compression::inflate_frame;
+use crate::log_reader::{Cursor, LogEntry, LogReader, Utf8Mode};
+
+/// Size of the reads issued to the source.
+const READ_CHUNK: usize = 1 << 20;
+
+/// Settings of a StreamReader.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaderOptions {
+    /// Most bytes of log data the reader holds at once, counting the buffer
+    /// being decoded and those read ahead (unbounded by default).
+    ///
+    /// Buffers larger than this fail with `io::ErrorKind::InvalidData`.
+    /// Read-ahead waits for memory to be freed rather than exceed it. The
+    /// limit doesn't cover the entries returned, which each hold a copy of
+    /// one record's payload (at most 64KiB), nor the dictionary embedded in
+    /// the log, which the reader holds until it is dropped.
+    pub max_memory: Option<usize>,
+
+    /// How string arguments that aren't valid UTF-8 are read, as with
+    /// `LogReader::set_utf8_mode` (lossy by default).
+    ///
+    /// In strict mode such an entry fails with `io::ErrorKind::InvalidData`
+    /// wrapping the `InvalidUtf8` error; reading again goes on after it.
+    pub utf8: Utf8Mode,
+}
+
+/// Bytes of log data held by a StreamReader and its helper thread.
+struct Budget {
+    limit: Option<usize>,
+    usage: Mutex<Usage>,
+    freed: Condvar,
+}
+
+#[derive(Default)]
+struct Usage {
+    used: usize,
+    peak: usize,
+
+    /// Set when the reader is dropped, to stop a waiting helper thread
+    closed: bool,
+}
+
+impl Budget {
+    fn new(limit: Option<usize>) -> Self {
+        Budget { limit, usage: Mutex::new(Usage::default()), freed: Condvar::new() }
+    }
+
+    /// Accounts for `bytes` more, waiting until they fit under the limit.
+    ///
+    /// Fails if they never can; `what` describes them for the error.
+    fn reserve(&self, bytes: usize, what: impl FnOnce() -> String) -> io::Result<()> {
+        if let Some(limit) = self.limit.filter(|&limit| bytes > limit) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} needs {} bytes, over the reader's memory limit of {}", what(), bytes, limit),
+            ));
+        }
+        let mut usage = self.usage.lock().unwrap();
+        while !usage.closed && self.limit.is_some_and(|limit| usage.used + bytes > limit) {
+            usage = self.freed.wait(usage).unwrap();
+        }
+        if usage.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        usage.used += bytes;
+        usage.peak = usage.peak.max(usage.used);
+        Ok(())
+    }
+
+    fn release(&self, bytes: usize) {
+        self.usage.lock().unwrap().used -= bytes;
+        self.freed.notify_all();
+    }
+
+    fn close(&self) {
+        self.usage.lock().unwrap().closed = true;
+        self.freed.notify_all();
+    }
+}
+
+/// Where the next buffers come from.
+enum Source<R> {
+    /// Read on the calling thread
+    Direct(BufReader<R>),
+
+    /// Read ahead by a helper thread
+    Prefetch(Receiver<io::Result<Option<Vec<u8>>>>),
+
+    /// The end of the source, or an error, was reached
+    Done,
+}
+
+/// Reads and decodes a binary log from an `io::Read` source.
+///
+/// Entries are the same as `LogReader` returns for the whole log, including
+/// their `EntryId`s, which hold byte positions in the stream. Only the
+/// buffer being decoded (and those read ahead) are kept in memory.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::StreamReader;
+/// # use std::fs::File;
+/// # fn example() -> std::io::Result<()> {
+/// let mut reader = StreamReader::new(File::open("log.bin")?).with_read_ahead(4);
+/// while let Some(entry) = reader.read_entry()? {
+///     println!("{}", entry.format());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct StreamReader<R> {
+    source: Source<R>,
+    file: u32,
+    utf8: Utf8Mode,
+    budget: Arc<Budget>,
+
+    /// Stream position of the next buffer to read
+    offset: u64,
+
+    /// The buffer being decoded and its stream position
+    buffer: Option<(Vec<u8>, u64)>,
+
+    /// Position in `buffer` and the timestamp state there
+    cursor: Option<Cursor>,
+}
+
+impl<R: Read> StreamReader<R> {
+    /// Creates a reader decoding the log read from `source`.
+    ///
+    /// Reads are made on the calling thread, in chunks of up to 1MB.
+    pub fn new(source: R) -> Self {
+        Self::with_options(source, ReaderOptions::default())
+    }
+
+    /// Creates a reader decoding the log read from `source`, with the
+    /// given options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binary_logger::{StreamReader, ReaderOptions};
+    /// # fn example(socket: std::net::TcpStream) -> std::io::Result<()> {
+    /// let options = ReaderOptions { max_memory: Some(64 << 20), ..Default::default() };
+    /// let mut reader = StreamReader::with_options(socket, options);
+    /// while let Some(entry) = reader.read_entry()? {
+    ///     println!("{}", entry.format());
+    /// }
+    /// eprintln!("peak memory: {} bytes", reader.peak_memory());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_options(source: R, options: ReaderOptions) -> Self {
+        StreamReader {
+            source: Source::Direct(BufReader::with_capacity(READ_CHUNK, source)),
+            file: 0,
+            utf8: options.utf8,
+            budget: Arc::new(Budget::new(options.max_memory)),
+            offset: 0,
+            buffer: None,
+            cursor: None,
+        }
+    }
+
+    /// Sets the file index reported in the `EntryId`s of this reader's
+    /// entries (0 by default), as with `LogReader::set_file_index`.
+    pub fn set_file_index(&mut self, file: u32) {
+        self.file = file;
+    }
+
+    /// Returns the bytes of log data the reader currently holds.
+    pub fn memory_used(&self) -> usize {
+        self.budget.usage.lock().unwrap().used
+    }
+
+    /// Returns the most bytes of log data the reader has held at once.
+    pub fn peak_memory(&self) -> usize {
+        self.budget.usage.lock().unwrap().peak
+    }
+
+    /// Returns the next entry, or `None` at the end of the log.
+    ///
+    /// A log that ends in the middle of a buffer, for example one still
+    /// being written, yields the complete records of that buffer. Errors
+    /// from the source, and buffers over the memory limit, are returned
+    /// once, after which the reader is at the end. In strict UTF-8 mode an
+    /// entry with an invalid string argument is returned as an error.
+    pub fn read_entry(&mut self) -> io::Result<Option<LogEntry>> {
+        loop {
+            if let (Some((buffer, start)), Some(cursor)) = (&self.buffer, &self.cursor) {
+                let mut reader = LogReader::at(buffer, cursor.clone());
+                reader.set_file_index(self.file);
+                reader.set_utf8_mode(self.utf8);
+                if let Some(mut entry) = reader.read_entry() {
+                    self.cursor = Some(reader.cursor());
+                    entry.id.buffer += start;
+                    return Ok(Some(entry));
+                }
+                if let Some(mut error) = reader.take_utf8_error() {
+                    self.cursor = Some(reader.cursor());
+                    error.id.buffer += start;
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+                }
+                self.cursor = Some(reader.cursor());
+                drop(reader);
+                self.budget.release(buffer.len());
+                self.buffer = None;
+            }
+
+            let buffer = match &mut self.source {
+                Source::Direct(source) => read_buffer(source, self.offset, &self.budget),
+                Source::Prefetch(buffers) => buffers.recv().unwrap_or(Ok(None)),
+                Source::Done => Ok(None),
+            };
+            match buffer {
+                Ok(Some(buffer)) => self.enter(buffer),
+                Ok(None) => {
+                    self.source = Source::Done;
+                    return Ok(None);
+                }
+                Err(err) => {
+                    self.source = Source::Done;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Starts decoding the next buffer, continuing the timestamps of the
+    /// previous one.
+    fn enter(&mut self, buffer: Vec<u8>) {
+        let mut reader = LogReader::new(&buffer);
+        if let Some(cursor) = &self.cursor {
+            reader.continue_from(cursor);
+        }
+        self.cursor = Some(reader.cursor());
+        drop(reader);
+        let len = buffer.len() as u64;
+        self.buffer = Some((buffer, self.offset));
+        self.offset += len;
+    }
+}
+
+impl<R> Drop for StreamReader<R> {
+    fn drop(&mut self) {
+        self.budget.close();
+    }
+}
+
+impl<R: Read + Send + 'static> StreamReader<R> {
+    /// Reads up to `buffers` log buffers ahead on a helper thread.
+    ///
+    /// The helper stops once that many buffers are waiting, so read-ahead
+    /// holds at most `buffers + 1` buffers in memory, and less if
+    /// `ReaderOptions::max_memory` requires. 0 reads on the calling thread
+    /// (the default). Call this before reading: the setting can't be
+    /// changed once the helper thread is running.
+    pub fn with_read_ahead(mut self, buffers: usize) -> Self {
+        if buffers == 0 {
+            return self;
+        }
+        if let Source::Direct(mut source) = std::mem::replace(&mut self.source, Source::Done) {
+            let (sender, receiver) = sync_channel(buffers);
+            let budget = self.budget.clone();
+            let mut offset = self.offset;
+            thread::spawn(move || loop {
+                let buffer = read_buffer(&mut source, offset, &budget);
+                if let Ok(Some(buffer)) = &buffer {
+                    offset += buffer.len() as u64;
+                }
+                let last = !matches!(buffer, Ok(Some(_)));
+                // Stop early once the reader is dropped
+                if sender.send(buffer).is_err() || last {
+                    break;
+                }
+            });
+            self.source = Source::Prefetch(receiver);
+        }
+        self
+    }
+}
+
+/// Reads the buffer at stream position `offset`, header included, and
+/// accounts for it in `budget`.
+///
+/// Headers inconsistent with the data make the rest of the source be read
+/// as a single buffer, the way `LogReader` treats them. Compressed frames
+/// are returned decompressed, and one cut short ends the log; builds
+/// without a compression feature fail on them.
+fn read_buffer<R: Read>(source: &mut R, offset: u64, budget: &Budget) -> io::Result<Option<Vec<u8>>> {
+    let mut buffer = Vec::with_capacity(BUFFER_HEADER_SIZE);
+    source.by_ref().take(BUFFER_HEADER_SIZE as u64).read_to_end(&mut buffer)?;
+    if buffer.is_empty() {
+        return Ok(None);
+    }
+    let what = || format!("buffer at byte {}", offset);
+    let declared = match buffer.as_slice().try_into() {
+        Ok(bytes) => u64::from_le_bytes(bytes),
+        // Too short for a header
+        Err(_) => {
+            budget.reserve(buffer.len(), what)?;
+            return Ok(Some(buffer));
+        }
+    };
+
+    let compressed = declared & COMPRESSED_FRAME != 0;
+    let size = usize::try_from(declared & !COMPRESSED_FRAME).ok().filter(|&size| size >= BUFFER_HEADER_SIZE);
+    let (reserved, wanted) = match (size, budget.limit) {
+        (Some(size), _) => (size, size),
+        // Without a usable size, read as much of the rest as the limit
+        // allows, and a byte more to tell whether that was all of it
+        (None, Some(limit)) => (limit, limit + 1),
+        (None, None) => {
+            source.read_to_end(&mut buffer)?;
+            budget.reserve(buffer.len(), what)?;
+            return Ok(Some(buffer));
+        }
+    };
+    budget.reserve(reserved, what)?;
+    buffer.reserve_exact(reserved.saturating_sub(BUFFER_HEADER_SIZE));
+    if let Err(err) = source.take(wanted.saturating_sub(BUFFER_HEADER_SIZE) as u64).read_to_end(&mut buffer) {
+        budget.release(reserved);
+        return Err(err);
+    }
+    if buffer.len() > reserved {
+        budget.release(reserved);
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has no valid size and is larger than the reader's memory limit of {} bytes", what(), reserved),
+        ));
+    }
+    budget.release(reserved - buffer.len());
+    #[cfg(not(any(feature = "lz4", feature = "zstd")))]
+    if compressed && size.is_some() {
+        budget.release(buffer.len());
+        return Err(io::Error::new(io::ErrorKind::Unsupported,
+            format!("{} is a compressed frame, which needs the lz4 or zstd feature", what())));
+    }
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    if compressed && size.is_some() {
+        let plain = (buffer.len() == reserved).then(|| inflate_frame(&buffer));
+        budget.release(buffer.len());
+        let Some(plain) = plain.transpose()? else { return Ok(None) };
+        budget.reserve(plain.len(), what)?;
+        return Ok(Some(plain));
+    }
+    Ok(Some(buffer))
+}
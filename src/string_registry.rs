@@ -10,24 +10,164 @@
 //! While each thread should have its own Logger instance, all threads share the
 //! same string registry. The registry uses a mutex and atomic operations to ensure
 //! thread-safety.
+//!
+//! # Static Strings
+//!
+//! The format strings and channel names of `log_record!` and `log_record_at!`
+//! call sites are collected by the linker into `STATIC_STRINGS`, so the whole
+//! set is known at startup. They get the first IDs, in sorted order, which
+//! are the same in every run of a binary, and are looked up without taking
+//! the lock. Only strings registered at runtime by other means, such as
+//! `register_string` calls with strings built by the program, go through
+//! the mutex.
+//!
+//! Because the set is known up front, a Logger can embed it in the log
+//! (`Logger::set_embedded_dictionary`), which lets a reader in another
+//! process, or another build, resolve format IDs without this registry.
+//!
+//! # Wide IDs
+//!
+//! IDs are 32-bit, skipping the range reserved for internal events, so a
+//! program isn't limited to the 65 279 strings that fit the 16-bit ID
+//! fields of the log format. Those fields hold `WIDE_ID` for a larger ID,
+//! followed by the 32-bit ID (see `FormatFeatures::WIDE_IDS`), so logs of
+//! programs with fewer strings are written as before.
 
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU16, Ordering};
-use std::sync::Mutex;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use lazy_static::lazy_static;
+use linkme::distributed_slice;
+use crate::common_strings::common_string;
+use crate::instrumentation::{is_reserved_format_id, InternalEvent, RESERVED_FORMAT_ID_START, RESERVED_FORMAT_ID_END};
+
+#[doc(hidden)]
+pub use linkme;
+
+/// Format strings and channel names of every logging call site in the
+/// program, gathered at link time. May contain duplicates.
+#[doc(hidden)]
+#[distributed_slice]
+pub static STATIC_STRINGS: [&'static str];
 
 lazy_static! {
     /// A thread-safe global registry for string deduplication.
     /// 
-    /// Maps static string literals to unique 16-bit IDs for efficient storage.
-    /// The registry ensures each unique string is stored only once, regardless
-    /// of how many times it appears in logs.
-    static ref STRING_REGISTRY: Mutex<HashMap<&'static str, u16>> = Mutex::new(HashMap::new());
-    
-    /// Atomic counter for generating unique string IDs.
-    /// 
-    /// Starts at 1 because ID 0 is reserved for special cases.
-    static ref NEXT_ID: AtomicU16 = AtomicU16::new(1);
+    /// Maps strings registered at runtime, which are not in the static
+    /// table, to unique IDs following those of the static strings.
+    static ref STRING_REGISTRY: Mutex<HashMap<&'static str, u32>> = Mutex::new(HashMap::new());
+}
+
+/// Value of a 16-bit ID field standing for a wide ID, which follows as a
+/// u32
+pub const WIDE_ID: u16 = 0xFFFF;
+
+/// Returns whether `id` takes a wide field, see `WIDE_ID`.
+pub fn is_wide_id(id: u32) -> bool {
+    id >= WIDE_ID as u32
+}
+
+/// Encodes `id` as a 16-bit field, or as `WIDE_ID` followed by the 32-bit
+/// ID, returning the bytes and how many of them are used.
+pub(crate) fn encoded_id(id: u32) -> ([u8; 6], usize) {
+    let mut bytes = [0; 6];
+    match is_wide_id(id) {
+        true => {
+            bytes[..2].copy_from_slice(&WIDE_ID.to_le_bytes());
+            bytes[2..].copy_from_slice(&id.to_le_bytes());
+            (bytes, 6)
+        }
+        false => {
+            bytes[..2].copy_from_slice(&(id as u16).to_le_bytes());
+            (bytes, 2)
+        }
+    }
+}
+
+/// Appends `id` to `out` as `encoded_id` encodes it.
+pub(crate) fn encode_id(id: u32, out: &mut Vec<u8>) {
+    let (bytes, len) = encoded_id(id);
+    out.extend_from_slice(&bytes[..len]);
+}
+
+/// Reads an ID written by `encoded_id` from the start of `bytes`, returning
+/// it with the number of bytes it takes.
+pub(crate) fn decode_id(bytes: &[u8]) -> Option<(u32, usize)> {
+    let id = u16::from_le_bytes(bytes.get(..2)?.try_into().unwrap());
+    match id {
+        WIDE_ID => Some((u32::from_le_bytes(bytes.get(2..6)?.try_into().unwrap()), 6)),
+        id => Some((id as u32, 2)),
+    }
+}
+
+/// Returns the `n`th ID the registry hands out, counting from 0.
+fn nth_id(n: usize) -> u32 {
+    let id = n as u64 + 1;
+    // Skip the reserved range
+    let id = match id < RESERVED_FORMAT_ID_START as u64 {
+        true => id,
+        false => id - RESERVED_FORMAT_ID_START as u64 + RESERVED_FORMAT_ID_END as u64,
+    };
+    u32::try_from(id).expect("String registry exhausted")
+}
+
+/// Returns the position of `id` among the IDs the registry hands out, the
+/// inverse of `nth_id`.
+fn id_index(id: u32) -> Option<usize> {
+    match id {
+        0 => None,
+        id if id < RESERVED_FORMAT_ID_START => Some(id as usize - 1),
+        id if id >= RESERVED_FORMAT_ID_END => Some((id - RESERVED_FORMAT_ID_END + RESERVED_FORMAT_ID_START) as usize - 1),
+        _ => None,
+    }
+}
+
+/// Number of strings in `STRING_REGISTRY`.
+static DYNAMIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The static strings, deduplicated, with their IDs.
+struct StaticTable {
+    /// Sorted strings, the string with ID `n` at index `n - 1`
+    strings: Vec<&'static str>,
+    ids: HashMap<&'static str, u32>,
+}
+
+/// Returns the table of static strings, built on first use.
+fn static_table() -> &'static StaticTable {
+    static TABLE: OnceLock<StaticTable> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut strings: Vec<&'static str> = STATIC_STRINGS.iter().copied().collect();
+        strings.sort_unstable();
+        strings.dedup();
+        // ID 0 is reserved for special cases
+        let ids = strings.iter().enumerate().map(|(i, &s)| (s, nth_id(i))).collect();
+        StaticTable { strings, ids }
+    })
+}
+
+/// Returns the ID of the string of a logging call site, registering it on
+/// first use and caching the ID in the call site.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __string_id {
+    ($s:literal) => {{
+        #[$crate::string_registry::linkme::distributed_slice($crate::string_registry::STATIC_STRINGS)]
+        #[linkme(crate = $crate::string_registry::linkme)]
+        static STRING: &'static str = $s;
+        static ID: ::std::sync::atomic::AtomicU32 = ::std::sync::atomic::AtomicU32::new(0);
+        match ID.load(::std::sync::atomic::Ordering::Relaxed) {
+            0 => {
+                let id = $crate::string_registry::register_string(STRING);
+                ID.store(id, ::std::sync::atomic::Ordering::Relaxed);
+                id
+            }
+            id => id,
+        }
+    }};
 }
 
 /// Registers a string in the registry and returns its unique ID.
@@ -38,9 +178,9 @@ lazy_static! {
 /// 
 /// # How It Works
 /// 
-/// 1. First, checks if the string is already registered (fast path)
-/// 2. If not, atomically generates a new ID and stores the mapping
-/// 3. Returns the ID (either existing or newly generated)
+/// 1. First, looks the string up in the static table (lock-free fast path)
+/// 2. Then checks the runtime registry under its lock
+/// 3. If not found, generates a new ID and stores the mapping
 /// 
 /// # Arguments
 /// 
@@ -48,7 +188,8 @@ lazy_static! {
 /// 
 /// # Returns
 /// 
-/// A unique 16-bit ID for the string
+/// A unique ID for the string, never 0 or in the range reserved for
+/// internal events
 /// 
 /// # Thread Safety
 /// 
@@ -71,19 +212,50 @@ lazy_static! {
 /// assert_ne!(id1, id3);
 /// ```
 #[allow(dead_code)]
-pub fn register_string(s: &'static str) -> u16 {
-    // Fast path: check if string is already registered
+pub fn register_string(s: &'static str) -> u32 {
+    // Fast path: strings of logging call sites are in the static table
+    let table = static_table();
+    if let Some(&id) = table.ids.get(s) {
+        return id;
+    }
+
     let mut registry = STRING_REGISTRY.lock().unwrap();
     if let Some(&id) = registry.get(s) {
         return id;
     }
     
-    // Slow path: register new string
-    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    // Slow path: register new string after the static ones
+    let id = nth_id(table.strings.len() + DYNAMIC_COUNT.load(Ordering::Relaxed));
     registry.insert(s, id);
+    DYNAMIC_COUNT.fetch_add(1, Ordering::Relaxed);
     id
 }
 
+/// Returns the number of strings registered so far, including every
+/// static string.
+/// 
+/// This is a cheap atomic load, suitable for detecting registry growth
+/// from the logging path.
+pub fn registered_count() -> usize {
+    static_table().strings.len() + DYNAMIC_COUNT.load(Ordering::Relaxed)
+}
+
+/// Returns every registered string with its ID, ordered by ID.
+/// 
+/// This takes the registry lock and copies the mapping, so it is meant for
+/// tooling such as schema export rather than the logging path.
+#[allow(dead_code)]
+pub fn registered_strings() -> Vec<(u32, &'static str)> {
+    let mut strings: Vec<_> = static_table().strings.iter().enumerate()
+        .map(|(i, &s)| (nth_id(i), s))
+        .collect();
+    let registry = STRING_REGISTRY.lock().unwrap();
+    let start = strings.len();
+    strings.extend(registry.iter().map(|(&s, &id)| (id, s)));
+    strings[start..].sort_unstable_by_key(|&(id, _)| id);
+    strings
+}
+
 /// Looks up a string by its ID.
 /// 
 /// This function is used primarily by the log reader to retrieve the format
@@ -91,11 +263,13 @@ pub fn register_string(s: &'static str) -> u16 {
 /// 
 /// # Arguments
 /// 
-/// * `id` - The 16-bit string ID to look up
+/// * `id` - The string ID to look up
 /// 
 /// # Returns
 /// 
-/// * `Some(&'static str)` - The string associated with the ID
+/// * `Some(&'static str)` - The string associated with the ID, or for
+///   reserved IDs the format string of an internal logger event or a
+///   built-in common string (see `common_strings`)
 /// * `None` - If no string with that ID exists, or if ID is 0 (reserved)
 /// 
 /// # Thread Safety
@@ -119,13 +293,183 @@ pub fn register_string(s: &'static str) -> u16 {
 /// let not_found = get_string(65535);
 /// assert_eq!(not_found, None);
 /// ```
-pub fn get_string(id: u16) -> Option<&'static str> {
-    if id == 0 {
-        return None; // Reserved for dynamic strings
+pub fn get_string(id: u32) -> Option<&'static str> {
+    if is_reserved_format_id(id) {
+        return InternalEvent::from_format_id(id).map(InternalEvent::format_string).or_else(|| common_string(id));
+    }
+    // ID 0 is reserved for dynamic strings
+    let index = id_index(id)?;
+    if let Some(&s) = static_table().strings.get(index) {
+        return Some(s);
     }
     
     let registry = STRING_REGISTRY.lock().unwrap();
     registry.iter()
         .find(|(_, &stored_id)| stored_id == id)
         .map(|(&s, _)| s)
-} 
\ No newline at end of file
+}
+
+/// A string read from a log: a string of the registry, or one of the
+/// dictionary embedded in the log, shared with the reader that read it.
+/// 
+/// Derefs to `str` and compares like one, so
+/// `entry.format_string.as_deref() == Some("Started {}")` holds for either.
+#[derive(Clone)]
+pub struct LogStr(LogStrRepr);
+
+#[derive(Clone)]
+enum LogStrRepr {
+    Static(&'static str),
+    Shared(Arc<str>),
+}
+
+impl Deref for LogStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match &self.0 {
+            LogStrRepr::Static(s) => s,
+            LogStrRepr::Shared(s) => s,
+        }
+    }
+}
+
+impl From<&'static str> for LogStr {
+    fn from(s: &'static str) -> Self {
+        LogStr(LogStrRepr::Static(s))
+    }
+}
+
+impl From<Arc<str>> for LogStr {
+    fn from(s: Arc<str>) -> Self {
+        LogStr(LogStrRepr::Shared(s))
+    }
+}
+
+impl AsRef<str> for LogStr {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl Borrow<str> for LogStr {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl fmt::Debug for LogStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl fmt::Display for LogStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl PartialEq for LogStr {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for LogStr {}
+
+impl PartialEq<str> for LogStr {
+    fn eq(&self, other: &str) -> bool {
+        &**self == other
+    }
+}
+
+impl PartialEq<&str> for LogStr {
+    fn eq(&self, other: &&str) -> bool {
+        &**self == *other
+    }
+}
+
+impl Hash for LogStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl PartialOrd for LogStr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogStr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+/// Strings a writer embedded in its log, by ID, see
+/// `Logger::set_embedded_dictionary`.
+/// 
+/// A reader holds its dictionary in an `Arc`, shared with the cursors it
+/// hands out, and each version shares the strings of the one it extends.
+/// Both are freed with the last reader holding them.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Dictionary {
+    strings: BTreeMap<u32, Arc<str>>,
+}
+
+impl Dictionary {
+    /// Returns the string with ID `id`, if the writer embedded it.
+    pub(crate) fn get(&self, id: u32) -> Option<LogStr> {
+        self.strings.get(&id).cloned().map(LogStr::from)
+    }
+
+    /// Returns `base` extended with the entries of a dictionary record
+    /// payload, which replace entries with the same IDs.
+    /// 
+    /// Entries are `id u16 | len u16 | UTF-8 bytes`, with the ID written by
+    /// `encode_id`; a truncated or invalid entry ends the payload.
+    pub(crate) fn extend(base: Option<&Dictionary>, payload: &[u8]) -> Arc<Dictionary> {
+        let mut dictionary = Dictionary { strings: base.map(|base| base.strings.clone()).unwrap_or_default() };
+        let mut rest = payload;
+        while let Some((id, id_len)) = decode_id(rest) {
+            let Some(len) = rest.get(id_len..id_len + 2).map(|len| u16::from_le_bytes([len[0], len[1]]) as usize) else {
+                break;
+            };
+            let start = id_len + 2;
+            let Some(Ok(s)) = rest.get(start..start + len).map(std::str::from_utf8) else {
+                break;
+            };
+            // Repeated records keep the strings already read
+            if dictionary.strings.get(&id).is_none_or(|known| **known != *s) {
+                dictionary.strings.insert(id, s.into());
+            }
+            rest = &rest[start + len..];
+        }
+        Arc::new(dictionary)
+    }
+}
+
+/// Encodes `strings`, as listed by `registered_strings`, as dictionary
+/// record payloads of at most `limit` bytes each. Strings too long for a payload are left out.
+pub(crate) fn dictionary_payloads(strings: &[(u32, &'static str)], limit: usize) -> Vec<Vec<u8>> {
+    let mut payloads = Vec::new();
+    let mut payload = Vec::new();
+    for &(id, s) in strings {
+        let len = if is_wide_id(id) { 8 } else { 4 } + s.len();
+        if len > limit {
+            continue;
+        }
+        if payload.len() + len > limit {
+            payloads.push(std::mem::take(&mut payload));
+        }
+        encode_id(id, &mut payload);
+        payload.extend_from_slice(&(s.len() as u16).to_le_bytes());
+        payload.extend_from_slice(s.as_bytes());
+    }
+    if !payload.is_empty() {
+        payloads.push(payload);
+    }
+    payloads
+}
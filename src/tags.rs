@@ -0,0 +1,171 @@
+#![allow(dead_code)]
+
+//! User-defined tags: labels on log records, independent of their level.
+//!
+//! A program registers up to 31 tag names (`register_tag`), each getting a
+//! bit of a u32 mask; the top bit is `Tags::CRITICAL`, which the Logger
+//! puts on the records of critical sections (`Logger::mark_critical_begin`). Records logged with `log_tagged!` carry the mask of
+//! their tags, so writers (`Logger::set_tag_mask`) and readers
+//! (`LogReader::set_tag_filter`) select records by tag with a single AND.
+//!
+//! Tagged records have a record type of their own, whose payload starts
+//! with the mask (see `RECORD_TYPE_TAGGED`, and `RECORD_TYPE_TAGGED_TYPED`
+//! with type tags); untagged records are written as before and cost
+//! nothing extra.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, log_tagged};
+//! # use binary_logger::tags::register_tag;
+//! # use std::sync::{Arc, Mutex};
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! let audit = register_tag("audit");
+//! let billing = register_tag("billing");
+//!
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! log_tagged!(logger, tags = audit | billing, "Charged account {}", 42).unwrap();
+//! log_tagged!(logger, tags = audit, "Password changed for {}", 7).unwrap();
+//! # }
+//! # let data = data.lock().unwrap();
+//! let mut reader = LogReader::new(&data);
+//! reader.set_tag_filter(Some(billing));
+//! let entry = reader.read_entry().unwrap();
+//! assert_eq!(entry.tags, audit | billing);
+//! assert_eq!(entry.tags.names(), ["audit", "billing"]);
+//! assert!(reader.read_entry().is_none());
+//! ```
+
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+use std::sync::Mutex;
+
+/// A set of tags, one bit per registered tag name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Tags(u32);
+
+impl Tags {
+    /// No tags; records without tags are written untagged.
+    pub const NONE: Tags = Tags(0);
+
+    /// Every tag.
+    pub const ALL: Tags = Tags(u32::MAX);
+
+    /// Records written inside a critical section, named "critical" in every
+    /// process
+    pub const CRITICAL: Tags = Tags(1 << CRITICAL_BIT);
+
+    /// Returns the set with the bits of `bits`.
+    pub const fn from_bits(bits: u32) -> Tags {
+        Tags(bits)
+    }
+
+    /// Returns the bitmask of the set, as stored in records.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether the set has no tags.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether the set has every tag of `other`.
+    pub const fn contains(self, other: Tags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether the sets have a tag in common.
+    pub const fn intersects(self, other: Tags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns the registered names of the tags in the set, by bit.
+    ///
+    /// Bits without a registered name, as in logs from another process,
+    /// are left out.
+    pub fn names(self) -> Vec<&'static str> {
+        let names = TAG_NAMES.lock().unwrap();
+        (0..32).filter(|bit| self.0 & (1 << bit) != 0).filter_map(|bit| names[bit]).collect()
+    }
+}
+
+impl BitOr for Tags {
+    type Output = Tags;
+
+    fn bitor(self, other: Tags) -> Tags {
+        Tags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Tags {
+    fn bitor_assign(&mut self, other: Tags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for Tags {
+    type Output = Tags;
+
+    fn bitand(self, other: Tags) -> Tags {
+        Tags(self.0 & other.0)
+    }
+}
+
+impl fmt::Display for Tags {
+    /// Writes the tag names joined by `|`, or the mask in hex if a bit has
+    /// no name.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.names();
+        if names.len() == self.0.count_ones() as usize {
+            f.write_str(&names.join("|"))
+        } else {
+            write!(f, "{:#x}", self.0)
+        }
+    }
+}
+
+/// Bit of `Tags::CRITICAL`
+const CRITICAL_BIT: usize = 31;
+
+/// Name of each tag bit, once registered
+static TAG_NAMES: Mutex<[Option<&'static str>; 32]> = Mutex::new(reserved_names());
+
+/// Returns the names of the tags no program registers.
+const fn reserved_names() -> [Option<&'static str>; 32] {
+    let mut names = [None; 32];
+    names[CRITICAL_BIT] = Some("critical");
+    names
+}
+
+/// Registers a tag name and returns its tag, the lowest free bit.
+///
+/// Registering a name again returns the same tag, and registering
+/// "critical" returns `Tags::CRITICAL`. Tags are process-wide, like format
+/// strings.
+///
+/// # Panics
+///
+/// Panics if 31 other names are already registered.
+pub fn register_tag(name: &'static str) -> Tags {
+    let mut names = TAG_NAMES.lock().unwrap();
+    if let Some(bit) = names.iter().position(|&registered| registered == Some(name)) {
+        return Tags(1 << bit);
+    }
+    let bit = names.iter().position(Option::is_none).expect("At most 31 tags can be registered");
+    names[bit] = Some(name);
+    Tags(1 << bit)
+}
+
+/// Returns the tag registered as `name`, if any.
+pub fn tag_by_name(name: &str) -> Option<Tags> {
+    let names = TAG_NAMES.lock().unwrap();
+    names.iter().position(|&registered| registered == Some(name)).map(|bit| Tags(1 << bit))
+}
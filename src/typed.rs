@@ -0,0 +1,174 @@
+//! Reading log entries back into application types.
+//!
+//! Analysis code usually cares about a few kinds of events, each logged
+//! from one format string. Deriving `FromLogEntry` for a struct describing
+//! such an event lets `LogReader::typed` return the events as that struct,
+//! checked field by field, instead of positional `LogValue`s:
+//!
+//! ```
+//! # use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+//! # use binary_logger::typed::FromLogEntry;
+//! # use std::sync::{Arc, Mutex};
+//! # use std::time::SystemTime;
+//! # struct MemoryHandler(Arc<Mutex<Vec<u8>>>);
+//! # impl BufferHandler for MemoryHandler {
+//! #     fn handle_buffer(&self, data: &[u8]) {
+//! #         self.0.lock().unwrap().extend_from_slice(data);
+//! #     }
+//! # }
+//! #[derive(FromLogEntry)]
+//! #[log(format = "User {} logged in after {} ms")]
+//! struct UserLogin {
+//!     user: u32,
+//!     latency_ms: f64,
+//!     #[log(timestamp)]
+//!     at: SystemTime,
+//! }
+//!
+//! # let data = Arc::new(Mutex::new(Vec::new()));
+//! # {
+//! # let mut logger = Logger::<4096>::new(MemoryHandler(data.clone()));
+//! log_record!(logger, "User {} logged in after {} ms", 42u32, 12.5).unwrap();
+//! log_record!(logger, "Cache miss {}", 7).unwrap();
+//! # }
+//! # let data = data.lock().unwrap();
+//! let mut reader = LogReader::new(&data);
+//! let logins: Vec<UserLogin> = reader.typed::<UserLogin>().collect();
+//! assert_eq!(logins.len(), 1);
+//! assert_eq!((logins[0].user, logins[0].latency_ms), (42, 12.5));
+//! ```
+
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use crate::log_reader::{LogEntry, LogReader, LogValue};
+use crate::string_registry::register_string;
+
+pub use binary_logger_derive::FromLogEntry;
+
+/// An event type decoded from the entries of one format string.
+///
+/// Usually derived: `#[derive(FromLogEntry)]` with `#[log(format = "...")]`
+/// on the struct takes the arguments of the entry into the fields in order,
+/// converting each with `FromLogValue`. A field marked `#[log(timestamp)]`
+/// gets the entry's timestamp instead.
+pub trait FromLogEntry: Sized {
+    /// The format string the events are logged with.
+    const FORMAT: &'static str;
+
+    /// Decodes an entry logged with `FORMAT`.
+    ///
+    /// Returns `None` if its arguments don't match the fields, in number
+    /// or in type.
+    fn from_log_entry(entry: &LogEntry) -> Option<Self>;
+}
+
+/// A field type of a `FromLogEntry` struct.
+pub trait FromLogValue: Sized {
+    /// Converts a decoded argument, or returns `None` if it doesn't fit.
+    fn from_log_value(value: &LogValue) -> Option<Self>;
+}
+
+macro_rules! from_integer {
+    ($($ty:ty),*) => {$(
+        impl FromLogValue for $ty {
+            fn from_log_value(value: &LogValue) -> Option<Self> {
+                match value {
+                    LogValue::Integer(value) => <$ty>::try_from(*value).ok(),
+                    LogValue::Integer64(value) => <$ty>::try_from(*value).ok(),
+                    LogValue::Unsigned(value) => <$ty>::try_from(*value).ok(),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+from_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl FromLogValue for f64 {
+    fn from_log_value(value: &LogValue) -> Option<Self> {
+        match value {
+            LogValue::Float(value) => Some(*value),
+            LogValue::Float32(value) => Some(*value as f64),
+            LogValue::Integer(value) => Some(*value as f64),
+            LogValue::Integer64(value) => Some(*value as f64),
+            LogValue::Unsigned(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromLogValue for f32 {
+    fn from_log_value(value: &LogValue) -> Option<Self> {
+        match value {
+            LogValue::Float32(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromLogValue for bool {
+    fn from_log_value(value: &LogValue) -> Option<Self> {
+        match value {
+            LogValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromLogValue for String {
+    fn from_log_value(value: &LogValue) -> Option<Self> {
+        match value {
+            LogValue::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromLogValue for SystemTime {
+    fn from_log_value(value: &LogValue) -> Option<Self> {
+        match value {
+            LogValue::Instant(time) => Some(*time),
+            _ => None,
+        }
+    }
+}
+
+impl FromLogValue for LogValue {
+    fn from_log_value(value: &LogValue) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+/// Iterator over the events of one type in a log, see `LogReader::typed`.
+pub struct Typed<'r, 'a, T> {
+    reader: &'r mut LogReader<'a>,
+    format_id: u32,
+    event: PhantomData<T>,
+}
+
+impl<T: FromLogEntry> Iterator for Typed<'_, '_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            let entry = self.reader.read_entry()?;
+            if entry.format_id == self.format_id {
+                if let Some(event) = T::from_log_entry(&entry) {
+                    return Some(event);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> LogReader<'a> {
+    /// Returns the remaining entries logged with `T::FORMAT`, decoded as `T`.
+    ///
+    /// Other entries, and entries whose arguments don't fit `T`, are
+    /// skipped. Format strings are matched by their ID in the string
+    /// registry, as when the entries are formatted.
+    pub fn typed<T: FromLogEntry>(&mut self) -> Typed<'_, 'a, T> {
+        Typed { format_id: register_string(T::FORMAT), reader: self, event: PhantomData }
+    }
+}
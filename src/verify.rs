@@ -0,0 +1,301 @@
+#![allow(dead_code)]
+
+//! Integrity verification of binary logs, backing the `blog-verify` tool.
+//!
+//! A log may be a single file or a series of segments (for example rotated
+//! files) that together form one stream. `verify_segments` checks:
+//!
+//! * framing: every buffer's size header is consistent with the data
+//! * hash chain: for audit logs, every buffer links to the one before it,
+//!   continuing across segments
+//! * sequence: global sequence numbers strictly increase
+//! * timestamps: reconstructed timestamps never go backwards
+//!
+//! The resulting `VerifyReport` renders as JSON for compliance pipelines.
+
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+use crate::audit::{find_chain_link, skip_metadata_buffers, verify_chain};
+use crate::binary_logger::BUFFER_HEADER_SIZE;
+use crate::log_reader::LogReader;
+
+/// Outcome of one verification check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// The check ran and found no problems
+    Passed,
+
+    /// The check found a problem, described by the message
+    Failed(String),
+
+    /// The check does not apply to this log, for the reason given
+    Skipped(String),
+}
+
+impl CheckStatus {
+    /// Returns true unless the check failed.
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, CheckStatus::Failed(_))
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let (status, detail) = match self {
+            CheckStatus::Passed => ("passed", None),
+            CheckStatus::Failed(message) => ("failed", Some(message)),
+            CheckStatus::Skipped(reason) => ("skipped", Some(reason)),
+        };
+        let _ = write!(out, "{{\"status\":\"{}\"", status);
+        if let Some(detail) = detail {
+            out.push_str(",\"detail\":");
+            write_json_string(out, detail);
+        }
+        out.push('}');
+    }
+}
+
+/// Verification results for one segment.
+#[derive(Debug, Clone)]
+pub struct SegmentReport {
+    /// Name of the segment, usually its file name
+    pub name: String,
+
+    /// Size of the segment in bytes
+    pub bytes: usize,
+
+    /// Number of well-formed buffers
+    pub buffers: usize,
+
+    /// Buffer framing check
+    pub framing: CheckStatus,
+
+    /// Hash chain check
+    pub chain: CheckStatus,
+}
+
+/// Verification results for a log made of one or more segments.
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    /// Per-segment results, in stream order
+    pub segments: Vec<SegmentReport>,
+
+    /// Number of entries read across all segments
+    pub entries: u64,
+
+    /// Global sequence check across all segments
+    pub sequence: CheckStatus,
+
+    /// Number of jumps in the sequence
+    ///
+    /// Gaps are expected when several threads share the global counter, so
+    /// they are reported but don't fail the check.
+    pub sequence_gaps: u64,
+
+    /// Timestamp monotonicity check across all segments
+    pub timestamps: CheckStatus,
+}
+
+impl VerifyReport {
+    /// Returns true if no check failed.
+    pub fn passed(&self) -> bool {
+        self.sequence.is_ok()
+            && self.timestamps.is_ok()
+            && self.segments.iter().all(|s| s.framing.is_ok() && s.chain.is_ok())
+    }
+
+    /// Renders the report as a single JSON object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binary_logger::verify::verify_segments;
+    /// # fn example(data: Vec<u8>) {
+    /// let report = verify_segments(&[("app.blog".to_string(), data)]);
+    /// println!("{}", report.to_json());
+    /// # }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"passed\":{},\"entries\":{},\"sequence\":", self.passed(), self.entries);
+        self.sequence.write_json(&mut out);
+        let _ = write!(out, ",\"sequence_gaps\":{},\"timestamps\":", self.sequence_gaps);
+        self.timestamps.write_json(&mut out);
+        out.push_str(",\"segments\":[");
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            write_json_string(&mut out, &segment.name);
+            let _ = write!(out, ",\"bytes\":{},\"buffers\":{},\"framing\":", segment.bytes, segment.buffers);
+            segment.framing.write_json(&mut out);
+            out.push_str(",\"chain\":");
+            segment.chain.write_json(&mut out);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Verifies a log made of the given segments, in stream order.
+///
+/// Each segment is a name (used in the report) and its contents. The hash
+/// chain, sequence numbers and timestamps are checked across segment
+/// boundaries, so segments must be passed in the order they were written.
+///
+/// # Examples
+///
+/// ```
+/// # use binary_logger::verify::verify_segments;
+/// # fn example(data: Vec<u8>) {
+/// let report = verify_segments(&[("app.blog".to_string(), data)]);
+/// if !report.passed() {
+///     eprintln!("{}", report.to_json());
+/// }
+/// # }
+/// ```
+pub fn verify_segments(segments: &[(String, Vec<u8>)]) -> VerifyReport {
+    let mut report = VerifyReport {
+        segments: Vec::with_capacity(segments.len()),
+        entries: 0,
+        sequence: CheckStatus::Skipped("no sequenced records".to_string()),
+        sequence_gaps: 0,
+        timestamps: CheckStatus::Passed,
+    };
+
+    let mut chain_state = ChainState::Start;
+    let mut last_sequence: Option<u64> = None;
+    let mut last_timestamp: Option<SystemTime> = None;
+    let mut sequence_failure = None;
+    let mut regressions = 0u64;
+    let mut worst_regression = Duration::ZERO;
+
+    for (name, data) in segments {
+        let (buffers, framing) = check_framing(data);
+        let chain = check_chain(data, &mut chain_state);
+        report.segments.push(SegmentReport { name: name.clone(), bytes: data.len(), buffers, framing, chain });
+    }
+
+    // Later segments rely on the time base of earlier ones, so entries are
+    // read from the stream as a whole
+    let stream = segments.iter().flat_map(|(_, data)| data.iter().copied()).collect::<Vec<u8>>();
+    let mut reader = LogReader::new(&stream);
+    while let Some(entry) = reader.read_entry() {
+        report.entries += 1;
+
+        if let Some(sequence) = entry.sequence {
+            match last_sequence {
+                Some(last) if sequence <= last => {
+                    sequence_failure.get_or_insert_with(|| format!(
+                        "sequence {} follows {} at entry {}", sequence, last, report.entries));
+                }
+                Some(last) if sequence != last + 1 => report.sequence_gaps += 1,
+                _ => {}
+            }
+            last_sequence = Some(sequence);
+        }
+
+        if let Some(last) = last_timestamp {
+            if let Ok(regression) = last.duration_since(entry.timestamp) {
+                if !regression.is_zero() {
+                    regressions += 1;
+                    worst_regression = worst_regression.max(regression);
+                }
+            }
+        }
+        last_timestamp = Some(last_timestamp.map_or(entry.timestamp, |last| last.max(entry.timestamp)));
+    }
+
+    if let Some(message) = sequence_failure {
+        report.sequence = CheckStatus::Failed(message);
+    } else if last_sequence.is_some() {
+        report.sequence = CheckStatus::Passed;
+    }
+    if regressions > 0 {
+        report.timestamps = CheckStatus::Failed(format!(
+            "{} timestamps go backwards, worst by {}us", regressions, worst_regression.as_micros()));
+    }
+
+    report
+}
+
+/// Counts the buffers of a segment and checks their size headers.
+fn check_framing(data: &[u8]) -> (usize, CheckStatus) {
+    let mut offset = 0;
+    let mut buffers = 0;
+    while offset < data.len() {
+        let remaining = data.len() - offset;
+        let size = if remaining >= BUFFER_HEADER_SIZE {
+            let mut size_bytes = [0u8; 8];
+            size_bytes.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(size_bytes) as usize
+        } else {
+            0
+        };
+        if size < BUFFER_HEADER_SIZE || size > remaining {
+            return (buffers, CheckStatus::Failed(format!(
+                "buffer {} at offset {}: invalid size {} with {} bytes left", buffers, offset, size, remaining)));
+        }
+        offset += size;
+        buffers += 1;
+    }
+    (buffers, CheckStatus::Passed)
+}
+
+/// Where the hash chain stands between segments.
+enum ChainState {
+    /// No audit segment seen yet
+    Start,
+
+    /// The chain is intact up to a buffer with this hash
+    Head([u8; 32]),
+
+    /// An earlier segment failed, so later ones can't be linked
+    Broken,
+}
+
+/// Checks a segment's hash chain, continuing from the previous segment.
+fn check_chain(data: &[u8], state: &mut ChainState) -> CheckStatus {
+    let previous = match state {
+        ChainState::Start => {
+            // Logs written without audit mode have no chain to check
+            let data = skip_metadata_buffers(data);
+            if data.len() < BUFFER_HEADER_SIZE || find_chain_link(data).is_none() {
+                return CheckStatus::Skipped("not an audit log".to_string());
+            }
+            None
+        }
+        ChainState::Head(head) => Some(*head),
+        ChainState::Broken => return CheckStatus::Skipped("follows a broken segment".to_string()),
+    };
+
+    match verify_chain(data, previous) {
+        Ok(report) => {
+            *state = ChainState::Head(report.head);
+            CheckStatus::Passed
+        }
+        Err(e) => {
+            *state = ChainState::Broken;
+            CheckStatus::Failed(e.to_string())
+        }
+    }
+}
+
+/// Writes `s` as a JSON string literal.
+pub(crate) fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,615 @@
+#![allow(dead_code)]
+
+//! Low-level encoding and decoding of the binary format.
+//!
+//! `Logger` and `LogReader` are the usual way to write and read logs. This
+//! module exposes the layer below them for tools that handle the format
+//! directly: collectors rewriting streams in another codebase, exporters,
+//! or fuzzers. It covers the framing the README's "Binary Format" section
+//! describes:
+//!
+//! * Buffers: an 8-byte little-endian size, counting itself, followed by
+//!   records (`read_buffer_size`, `write_buffer_size`, `records`)
+//! * Records: the header with its type, flags, time and format ID, the
+//!   optional sequence number and channel, the wide IDs the 16-bit fields
+//!   escape (`WIDE_ID`), the length of long records (`LONG_LENGTH`), and the
+//!   payload, padded to an even length (`Record`, `decode_record`)
+//! * Varints: unsigned LEB128, used by delta times and some payloads
+//! * Stream headers (`StreamHeader`) and the base timestamps of base
+//!   records (`split_base`)
+//! * Record times, from relative and delta fields to UNIX microseconds
+//!   (`Timeline`)
+//!
+//! Payloads of log records are the arguments as encoded by the stream's
+//! codec (`codec::codec_by_id` with `StreamHeader::codec_id`).
+//!
+//! The items here are stable: the format only grows through new record
+//! types and `FormatFeatures`, and this module grows with it without
+//! changing what is here.
+//!
+//! # Examples
+//!
+//! ```
+//! # use binary_logger::{LogReader, log_record};
+//! # use binary_logger::codec::{Codec, RawCodec};
+//! use binary_logger::wire::{self, Record, RecordTime, Timeline, RECORD_TYPE_BASE, RECORD_TYPE_NORMAL};
+//!
+//! // A buffer of two records, written without a Logger
+//! let format_id = binary_logger::register_string("Batch {} of {}");
+//! let args = [3u32.to_le_bytes(), 8u32.to_le_bytes()];
+//! let mut payload = vec![0; RawCodec.encoded_len(&[&args[0], &args[1]])];
+//! RawCodec.encode(&[&args[0], &args[1]], &mut payload);
+//!
+//! let mut base = 1_700_000_000_000_000u64.to_le_bytes().to_vec();
+//! base.extend_from_slice(&payload);
+//! let first = Record::new(RECORD_TYPE_BASE, RecordTime::Relative(0), format_id, &base);
+//! let second = Record::new(RECORD_TYPE_NORMAL, RecordTime::Delta(250), format_id, &payload);
+//!
+//! let mut buffer = vec![0; wire::BUFFER_HEADER_SIZE + first.encoded_len() + second.encoded_len()];
+//! let mut pos = wire::BUFFER_HEADER_SIZE;
+//! pos += first.encode(&mut buffer[pos..]);
+//! pos += second.encode(&mut buffer[pos..]);
+//! wire::write_buffer_size(pos, &mut buffer);
+//!
+//! // Decoded with LogReader
+//! let entry = LogReader::new(&buffer).read_entry().unwrap();
+//! assert_eq!(entry.format(), "Batch 3 of 8");
+//!
+//! // And back with this module
+//! let mut timeline = Timeline::new();
+//! let times: Vec<Option<u64>> = wire::records(&buffer)
+//!     .map(|record| timeline.advance(&record.unwrap()))
+//!     .collect();
+//! assert_eq!(times, [Some(1_700_000_000_000_000), Some(1_700_000_000_000_250)]);
+//! ```
+
+use crate::binary_logger;
+use crate::efficient_clock;
+use crate::features::FormatFeatures;
+use crate::level::{Level, FLAG_LEVEL_MASK};
+use crate::string_registry::{self, decode_id, encoded_id, is_wide_id};
+
+/// Size of the buffer header: the buffer's size in bytes, header included,
+/// as a little-endian u64
+pub const BUFFER_HEADER_SIZE: usize = binary_logger::BUFFER_HEADER_SIZE;
+
+/// Size of a record header with a relative time and a format ID:
+/// `type(1) | flags(1) | relative_ts(2) | format_id(2) | payload_len(2)`
+pub const RECORD_HEADER_SIZE: usize = binary_logger::RECORD_HEADER_SIZE;
+
+/// Length of the period of relative times, in microseconds: a relative
+/// time below the one before it starts the next period
+pub const EPOCH_MICROS: u64 = efficient_clock::EPOCH_MICROS;
+
+/// Log record, timed relative to the current base
+pub const RECORD_TYPE_NORMAL: u8 = binary_logger::RECORD_TYPE_NORMAL;
+
+/// Record setting the base time: its payload starts with the base, in
+/// microseconds since the UNIX epoch, and is a log record if its format ID
+/// isn't 0 (see `split_base`)
+pub const RECORD_TYPE_BASE: u8 = binary_logger::RECORD_TYPE_BASE;
+
+/// Stream header, untimed (see `StreamHeader`)
+pub const RECORD_TYPE_STREAM_HEADER: u8 = binary_logger::RECORD_TYPE_STREAM_HEADER;
+
+/// SHA-256 of the previous buffer, untimed
+pub const RECORD_TYPE_CHAIN: u8 = binary_logger::RECORD_TYPE_CHAIN;
+
+/// Metric update, timed
+pub const RECORD_TYPE_METRIC: u8 = binary_logger::RECORD_TYPE_METRIC;
+
+/// Part of the string dictionary, timed
+pub const RECORD_TYPE_DICTIONARY: u8 = binary_logger::RECORD_TYPE_DICTIONARY;
+
+/// Log record whose payload starts with a u32 tag mask, timed
+pub const RECORD_TYPE_TAGGED: u8 = binary_logger::RECORD_TYPE_TAGGED;
+
+/// Count table, timed
+pub const RECORD_TYPE_COUNTS: u8 = binary_logger::RECORD_TYPE_COUNTS;
+
+/// Part of the string dictionary in a metadata buffer, untimed
+pub const RECORD_TYPE_METADATA: u8 = binary_logger::RECORD_TYPE_METADATA;
+
+/// Retention table, untimed
+pub const RECORD_TYPE_RETENTION: u8 = binary_logger::RECORD_TYPE_RETENTION;
+
+/// Filler between records, untimed
+pub const RECORD_TYPE_PADDING: u8 = binary_logger::RECORD_TYPE_PADDING;
+
+/// Log record without a time field (`RecordTime::None`), which has the
+/// time of the record before it
+pub const RECORD_TYPE_UNTIMED: u8 = binary_logger::RECORD_TYPE_UNTIMED;
+
+/// Log record whose payload starts with the argument count (u8) and a
+/// type tag per argument (see `arg_types::TypeTag`), timed
+pub const RECORD_TYPE_TYPED: u8 = binary_logger::RECORD_TYPE_TYPED;
+
+/// Offset of the writer's clock from a reference clock, applying to the
+/// records after it (see `clock_sync::ClockOffset`), untimed
+pub const RECORD_TYPE_CLOCK_OFFSET: u8 = binary_logger::RECORD_TYPE_CLOCK_OFFSET;
+
+/// Log record whose payload starts with the tag mask (u32, see `tags`),
+/// then the argument count and type tags of `RECORD_TYPE_TYPED`, timed
+pub const RECORD_TYPE_TAGGED_TYPED: u8 = binary_logger::RECORD_TYPE_TAGGED_TYPED;
+
+/// First record type of application-defined records, timed
+pub const RECORD_TYPE_USER_MIN: u8 = binary_logger::RECORD_TYPE_USER_MIN;
+
+/// Flag: a u64 sequence number follows the header
+pub const FLAG_SEQUENCE: u8 = binary_logger::FLAG_SEQUENCE;
+
+/// Flag: a u16 channel ID follows the header and sequence number
+pub const FLAG_CHANNEL: u8 = binary_logger::FLAG_CHANNEL;
+
+/// Value of a u16 format ID or channel field standing for a wide ID
+///
+/// A wide channel follows its field as a u32, and a wide format ID follows
+/// the channel, or the sequence number or header without one, as a u32.
+pub const WIDE_ID: u16 = string_registry::WIDE_ID;
+
+/// Value of the u16 payload length field of a record with a payload of
+/// 0xFFFF bytes or more
+///
+/// The length follows the wide format ID, or the fields before it without
+/// one, as a u32; see `FormatFeatures::LONG_RECORDS`.
+pub const LONG_LENGTH: u16 = binary_logger::LONG_LENGTH;
+
+/// Flag: the header omits the format ID, which is that of the record
+/// before it in the buffer
+pub const FLAG_SAME_FORMAT: u8 = binary_logger::FLAG_SAME_FORMAT;
+
+/// Flag: the header holds a varint of microseconds since the record before
+/// it instead of the relative time
+pub const FLAG_DELTA_TIME: u8 = binary_logger::FLAG_DELTA_TIME;
+
+/// Flag bits holding the record's level (see `Record::level`)
+pub const FLAG_LEVEL: u8 = FLAG_LEVEL_MASK;
+
+/// Flag: arguments were cut to the writer's capture limits
+pub const FLAG_TRUNCATED: u8 = binary_logger::FLAG_TRUNCATED;
+
+/// Flags `Record::encode` derives from the record's fields
+const FRAMING_FLAGS: u8 = FLAG_SEQUENCE | FLAG_CHANNEL | FLAG_SAME_FORMAT | FLAG_DELTA_TIME;
+
+/// The time field of a record header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordTime {
+    /// No time field, in `RECORD_TYPE_UNTIMED` records
+    None,
+
+    /// Microseconds since the current base, modulo `EPOCH_MICROS`
+    Relative(u16),
+
+    /// Microseconds since the record before (`FLAG_DELTA_TIME`)
+    Delta(u64),
+}
+
+/// A record, borrowing its payload.
+///
+/// Besides the level and truncation bits, `flags` may hold bits this
+/// version doesn't know. The framing bits, `FLAG_SEQUENCE`, `FLAG_CHANNEL`,
+/// `FLAG_SAME_FORMAT` and `FLAG_DELTA_TIME`, follow the other fields:
+/// `decode_record` sets them and `encode` ignores them in `flags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    pub record_type: u8,
+    pub flags: u8,
+    pub time: RecordTime,
+
+    /// The format ID, or None for that of the record before it in the
+    /// buffer (`FLAG_SAME_FORMAT`)
+    pub format_id: Option<u32>,
+
+    pub sequence: Option<u64>,
+
+    /// Registry ID of the channel name
+    pub channel: Option<u32>,
+
+    /// At most `u32::MAX` bytes, `LONG_LENGTH` and more making a long record
+    pub payload: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Creates a record without flags, sequence number or channel.
+    pub fn new(record_type: u8, time: RecordTime, format_id: u32, payload: &'a [u8]) -> Self {
+        Record { record_type, flags: 0, time, format_id: Some(format_id), sequence: None, channel: None, payload }
+    }
+
+    /// Returns the level in the record's flags.
+    pub fn level(&self) -> Option<Level> {
+        Level::from_flags(self.flags)
+    }
+
+    /// Sets the level in the record's flags.
+    pub fn set_level(&mut self, level: Option<Level>) {
+        self.flags = (self.flags & !FLAG_LEVEL) | Level::to_flags(level);
+    }
+
+    /// Returns the size of the record's header, without the sequence number,
+    /// channel, wide format ID and long length.
+    pub fn header_len(&self) -> usize {
+        let time_len = match self.time {
+            RecordTime::None => 0,
+            RecordTime::Relative(_) => 2,
+            RecordTime::Delta(delta) => varint_len(delta),
+        };
+        2 + time_len + if self.format_id.is_some() { 2 } else { 0 } + 2
+    }
+
+    /// Returns the size of the encoded record, padding included.
+    pub fn encoded_len(&self) -> usize {
+        let extra = if self.sequence.is_some() { 8 } else { 0 }
+            + self.channel.map_or(0, |id| encoded_id(id).1)
+            + self.format_id.filter(|&id| is_wide_id(id)).map_or(0, |_| 4)
+            + if self.is_long() { 4 } else { 0 };
+        (self.header_len() + extra + self.payload.len() + 1) & !1
+    }
+
+    /// Returns whether the payload's length follows the header fields, see
+    /// `LONG_LENGTH`.
+    pub fn is_long(&self) -> bool {
+        self.payload.len() >= LONG_LENGTH as usize
+    }
+
+    /// Returns the record's flags as encoded, with the framing bits set
+    /// from its fields.
+    pub fn encoded_flags(&self) -> u8 {
+        let mut flags = self.flags & !FRAMING_FLAGS;
+        if self.sequence.is_some() {
+            flags |= FLAG_SEQUENCE;
+        }
+        if self.channel.is_some() {
+            flags |= FLAG_CHANNEL;
+        }
+        if self.format_id.is_none() {
+            flags |= FLAG_SAME_FORMAT;
+        }
+        if matches!(self.time, RecordTime::Delta(_)) {
+            flags |= FLAG_DELTA_TIME;
+        }
+        flags
+    }
+
+    /// Writes the record to the start of `out`, and returns its size,
+    /// `encoded_len`.
+    ///
+    /// # Panics
+    ///
+    /// If `out` is shorter than `encoded_len`, or the payload is longer
+    /// than `u32::MAX` bytes.
+    pub fn encode(&self, out: &mut [u8]) -> usize {
+        let payload_len = u32::try_from(self.payload.len()).expect("record payload longer than u32::MAX bytes");
+        let len = self.encoded_len();
+        let out = &mut out[..len];
+        out[0] = self.record_type;
+        out[1] = self.encoded_flags();
+        let mut pos = 2;
+        match self.time {
+            RecordTime::None => {}
+            RecordTime::Relative(relative) => {
+                out[2..4].copy_from_slice(&relative.to_le_bytes());
+                pos += 2;
+            }
+            RecordTime::Delta(delta) => pos += write_varint(delta, &mut out[2..]),
+        }
+        if let Some(format_id) = self.format_id {
+            let field = if is_wide_id(format_id) { WIDE_ID } else { format_id as u16 };
+            out[pos..pos + 2].copy_from_slice(&field.to_le_bytes());
+            pos += 2;
+        }
+        let length_field = if self.is_long() { LONG_LENGTH } else { payload_len as u16 };
+        out[pos..pos + 2].copy_from_slice(&length_field.to_le_bytes());
+        pos += 2;
+        if let Some(sequence) = self.sequence {
+            out[pos..pos + 8].copy_from_slice(&sequence.to_le_bytes());
+            pos += 8;
+        }
+        if let Some(channel) = self.channel {
+            let (bytes, len) = encoded_id(channel);
+            out[pos..pos + len].copy_from_slice(&bytes[..len]);
+            pos += len;
+        }
+        if let Some(format_id) = self.format_id.filter(|&id| is_wide_id(id)) {
+            out[pos..pos + 4].copy_from_slice(&format_id.to_le_bytes());
+            pos += 4;
+        }
+        if self.is_long() {
+            out[pos..pos + 4].copy_from_slice(&payload_len.to_le_bytes());
+            pos += 4;
+        }
+        out[pos..pos + self.payload.len()].copy_from_slice(self.payload);
+        pos += self.payload.len();
+        if pos < len {
+            out[pos] = 0;
+        }
+        len
+    }
+}
+
+/// Reads the record at the start of `data`, returning it and the bytes it
+/// takes, padding included. Returns None if `data` ends within the record.
+///
+/// A record ending at the end of `data` without its padding byte is read
+/// whole.
+pub fn decode_record(data: &[u8]) -> Option<(Record<'_>, usize)> {
+    let header_len = binary_logger::record_header_size(data)?;
+    let header = data.get(..header_len)?;
+    let (record_type, flags) = (header[0], header[1]);
+    let time = match flags & FLAG_DELTA_TIME {
+        _ if record_type == RECORD_TYPE_UNTIMED => RecordTime::None,
+        0 => RecordTime::Relative(u16::from_le_bytes([header[2], header[3]])),
+        _ => RecordTime::Delta(read_varint(&header[2..])?.0),
+    };
+    let format_field = match flags & FLAG_SAME_FORMAT {
+        0 => Some(u16::from_le_bytes([header[header_len - 4], header[header_len - 3]])),
+        _ => None,
+    };
+    let length_field = u16::from_le_bytes([header[header_len - 2], header[header_len - 1]]);
+
+    let mut pos = header_len;
+    let sequence = match flags & FLAG_SEQUENCE {
+        0 => None,
+        _ => {
+            let bytes = data.get(pos..pos + 8)?;
+            pos += 8;
+            Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+        }
+    };
+    let channel = match flags & FLAG_CHANNEL {
+        0 => None,
+        _ => {
+            let (channel, len) = decode_id(data.get(pos..)?)?;
+            pos += len;
+            Some(channel)
+        }
+    };
+    let format_id = match format_field {
+        Some(WIDE_ID) => {
+            let bytes = data.get(pos..pos + 4)?;
+            pos += 4;
+            Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+        }
+        field => field.map(u32::from),
+    };
+    let payload_len = match length_field {
+        LONG_LENGTH => {
+            let bytes = data.get(pos..pos + 4)?;
+            pos += 4;
+            u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+        }
+        len => len as usize,
+    };
+    let payload = data.get(pos..pos + payload_len)?;
+    let end = ((pos + payload_len + 1) & !1).min(data.len());
+    Some((Record { record_type, flags, time, format_id, sequence, channel, payload }, end))
+}
+
+/// Reads the size in a buffer header, header included. Returns None if
+/// `data` is shorter than a header.
+pub fn read_buffer_size(data: &[u8]) -> Option<usize> {
+    let header = data.get(..BUFFER_HEADER_SIZE)?;
+    Some(u64::from_le_bytes(header.try_into().unwrap()) as usize)
+}
+
+/// Writes `size`, the size of the buffer header included, to the buffer
+/// header at the start of `out`.
+///
+/// # Panics
+///
+/// If `out` is shorter than a buffer header.
+pub fn write_buffer_size(size: usize, out: &mut [u8]) {
+    out[..BUFFER_HEADER_SIZE].copy_from_slice(&(size as u64).to_le_bytes());
+}
+
+/// Returns the records of the buffer at the start of `data`, in order.
+///
+/// The iteration ends at the end of the buffer, or with an error for a
+/// buffer size beyond `data` or a record cut short.
+pub fn records(data: &[u8]) -> Records<'_> {
+    let (rest, failed) = match read_buffer_size(data) {
+        Some(size) if (BUFFER_HEADER_SIZE..=data.len()).contains(&size) => (&data[BUFFER_HEADER_SIZE..size], false),
+        _ => (data, true),
+    };
+    Records { rest, failed }
+}
+
+/// Iterator over the records of a buffer, see `records`.
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    rest: &'a [u8],
+    failed: bool,
+}
+
+/// A buffer or record that doesn't fit the data holding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncated {
+    /// Bytes of the buffer not read, or of the data for a bad buffer size
+    pub remaining: usize,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<Record<'a>, Truncated>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if std::mem::take(&mut self.failed) {
+            let remaining = self.rest.len();
+            self.rest = &[];
+            return Some(Err(Truncated { remaining }));
+        }
+        if self.rest.is_empty() {
+            return None;
+        }
+        match decode_record(self.rest) {
+            Some((record, len)) => {
+                self.rest = &self.rest[len..];
+                Some(Ok(record))
+            }
+            None => {
+                let remaining = self.rest.len();
+                self.rest = &[];
+                Some(Err(Truncated { remaining }))
+            }
+        }
+    }
+}
+
+/// Splits the payload of a `RECORD_TYPE_BASE` record into its base time,
+/// in microseconds since the UNIX epoch, and the log record's arguments.
+pub fn split_base(payload: &[u8]) -> Option<(u64, &[u8])> {
+    let (base, args) = payload.split_first_chunk::<8>()?;
+    Some((u64::from_le_bytes(*base), args))
+}
+
+/// Splits the payload of a `RECORD_TYPE_TYPED` record, or that of a
+/// `RECORD_TYPE_TAGGED_TYPED` record after its tag mask, into its type tags
+/// (see `arg_types::TypeTag`) and the log record's arguments.
+pub fn split_type_tags(payload: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&count, rest) = payload.split_first()?;
+    rest.split_at_checked(count as usize)
+}
+
+/// Returns true for record types whose time field is meaningless.
+pub fn is_untimed(record_type: u8) -> bool {
+    matches!(record_type, RECORD_TYPE_STREAM_HEADER | RECORD_TYPE_CHAIN | RECORD_TYPE_METADATA | RECORD_TYPE_RETENTION | RECORD_TYPE_PADDING
+        | RECORD_TYPE_CLOCK_OFFSET)
+}
+
+/// The payload of a `RECORD_TYPE_STREAM_HEADER` record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamHeader {
+    pub version: u8,
+
+    /// The argument codec (see `codec::codec_by_id`)
+    pub codec_id: u8,
+
+    /// The features the stream may use; empty in headers without them
+    pub features: FormatFeatures,
+}
+
+impl StreamHeader {
+    /// Size of an encoded stream header
+    pub const ENCODED_LEN: usize = binary_logger::STREAM_HEADER_PAYLOAD_SIZE;
+
+    /// Magic bytes starting a stream header
+    pub const MAGIC: [u8; 4] = binary_logger::STREAM_MAGIC;
+
+    /// Creates the header of a stream in the current version.
+    pub fn new(codec_id: u8, features: FormatFeatures) -> Self {
+        StreamHeader { version: binary_logger::STREAM_VERSION, codec_id, features }
+    }
+
+    /// Reads a stream header payload, of this or an earlier version.
+    /// Returns None if it doesn't start with the magic bytes.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        if payload.get(..4)? != Self::MAGIC || payload.len() < binary_logger::STREAM_HEADER_BASE_SIZE {
+            return None;
+        }
+        let features = match payload.get(6..10) {
+            Some(bits) => FormatFeatures::from_bits(u32::from_le_bytes(bits.try_into().unwrap())),
+            None => FormatFeatures::from_bits(0),
+        };
+        Some(StreamHeader { version: payload[4], codec_id: payload[5], features })
+    }
+
+    /// Returns the encoded payload.
+    pub fn encode(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut payload = [0; Self::ENCODED_LEN];
+        payload[..4].copy_from_slice(&Self::MAGIC);
+        payload[4] = self.version;
+        payload[5] = self.codec_id;
+        payload[6..].copy_from_slice(&self.features.bits().to_le_bytes());
+        payload
+    }
+}
+
+/// Resolves record times to microseconds since the UNIX epoch, following
+/// the records of a stream in order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeline {
+    base: Option<u64>,
+    epoch: u64,
+    last_relative: u16,
+}
+
+impl Timeline {
+    /// Creates a timeline before the first base record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves past `record`, and returns its time. Returns None before the
+    /// first base record, and for records whose time field is meaningless
+    /// (`is_untimed`). `RECORD_TYPE_UNTIMED` records have the time of the
+    /// record before them.
+    ///
+    /// Feed it every record of the stream, since each time is relative to
+    /// those before it.
+    pub fn advance(&mut self, record: &Record) -> Option<u64> {
+        let relative = match record.time {
+            RecordTime::None => self.last_relative,
+            RecordTime::Relative(relative) => relative,
+            RecordTime::Delta(delta) => {
+                let offset = self.epoch * EPOCH_MICROS + self.last_relative as u64 + delta;
+                self.epoch = offset / EPOCH_MICROS;
+                self.last_relative = (offset % EPOCH_MICROS) as u16;
+                self.last_relative
+            }
+        };
+        match record.record_type {
+            RECORD_TYPE_BASE => {
+                self.base = Some(split_base(record.payload)?.0);
+                self.epoch = 0;
+            }
+            record_type if is_untimed(record_type) => return None,
+            _ if relative < self.last_relative => self.epoch += 1,
+            _ => {}
+        }
+        self.last_relative = relative;
+        Some(self.base? + self.epoch * EPOCH_MICROS + relative as u64)
+    }
+}
+
+/// Returns the size of `value` as an unsigned LEB128 varint.
+pub fn varint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+/// Writes `value` as an unsigned LEB128 varint to the start of `out`, and
+/// returns its size.
+///
+/// # Panics
+///
+/// If `out` is shorter than `varint_len(value)`.
+pub fn write_varint(mut value: u64, out: &mut [u8]) -> usize {
+    let mut pos = 0;
+    while value >= 0x80 {
+        out[pos] = (value as u8) | 0x80;
+        value >>= 7;
+        pos += 1;
+    }
+    out[pos] = value as u8;
+    pos + 1
+}
+
+/// Reads an unsigned LEB128 varint from the start of `input`, returning
+/// the value and the rest of the input. Returns None if `input` ends
+/// within it or it overflows 64 bits.
+pub fn read_varint(input: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, &byte) in input.iter().enumerate().take(10) {
+        // The tenth byte holds the top bit
+        if i == 9 && byte > 1 {
+            return None;
+        }
+        value |= ((byte & 0x7F) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &input[i + 1..]));
+        }
+    }
+    None
+}
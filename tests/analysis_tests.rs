@@ -0,0 +1,130 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, log_record_at, register_string};
+use binary_logger::analysis::{latency_between, LatencyBucket};
+use binary_logger::codec::{Codec, RawCodec};
+use binary_logger::level::Level;
+use binary_logger::wire::{self, Record, RecordTime, RECORD_TYPE_BASE, RECORD_TYPE_NORMAL};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use common::CollectingHandler;
+
+const START: &str = "Request {} received";
+const END: &str = "Request {} done in {}";
+
+/// Writes a buffer of records of (microseconds since the first, format,
+/// arguments).
+fn log_at(events: &[(u64, &'static str, &[&[u8]])]) -> Vec<u8> {
+    let mut buffer = vec![0; wire::BUFFER_HEADER_SIZE];
+    let mut last = 0;
+    for (index, &(micros, format, args)) in events.iter().enumerate() {
+        let mut payload = vec![0; RawCodec.encoded_len(args)];
+        RawCodec.encode(args, &mut payload);
+        let record = if index == 0 {
+            let mut base = 1_700_000_000_000_000u64.to_le_bytes().to_vec();
+            base.extend_from_slice(&payload);
+            payload = base;
+            Record::new(RECORD_TYPE_BASE, RecordTime::Relative(0), register_string(format), &payload)
+        } else {
+            Record::new(RECORD_TYPE_NORMAL, RecordTime::Delta(micros - last), register_string(format), &payload)
+        };
+        last = micros;
+        let mut out = vec![0; record.encoded_len()];
+        record.encode(&mut out);
+        buffer.extend_from_slice(&out);
+    }
+    let size = buffer.len();
+    wire::write_buffer_size(size, &mut buffer);
+    buffer
+}
+
+#[test]
+fn test_events_are_paired_by_key() {
+    let (a, b, c) = (7u32.to_le_bytes(), 8u32.to_le_bytes(), 9u32.to_le_bytes());
+    let other = 1u32.to_le_bytes();
+    let data = log_at(&[
+        (0, START, &[&a]),
+        (100, START, &[&b]),
+        (150, "Cache {} warmed", &[&other]),
+        (400, END, &[&b, &other]),
+        (1_000, END, &[&a, &other]),
+        (1_100, END, &[&c, &other]),
+        (2_000, START, &[&c]),
+    ]);
+    let report = latency_between(&mut LogReader::new(&data), START, END, 0);
+    assert_eq!(report.latencies(), [Duration::from_micros(300), Duration::from_micros(1_000)]);
+    assert_eq!((report.unmatched_starts, report.unmatched_ends), (1, 1));
+    assert_eq!(report.min(), Some(Duration::from_micros(300)));
+    assert_eq!(report.max(), Some(Duration::from_micros(1_000)));
+    assert_eq!(report.mean(), Some(Duration::from_micros(650)));
+    assert_eq!(report.to_string(), "count=2 min=300µs p50=300µs p90=1ms p99=1ms max=1ms unmatched_starts=1 unmatched_ends=1");
+}
+
+#[test]
+fn test_repeated_keys_are_paired_in_order() {
+    let key = 5u32.to_le_bytes();
+    let data = log_at(&[
+        (0, START, &[&key]),
+        (10, START, &[&key]),
+        (50, END, &[&key, &key]),
+        (90, END, &[&key, &key]),
+        (95, END, &[&key, &key]),
+    ]);
+    let report = latency_between(&mut LogReader::new(&data), START, END, 0);
+    assert_eq!(report.latencies(), [Duration::from_micros(50), Duration::from_micros(80)]);
+    assert_eq!((report.unmatched_starts, report.unmatched_ends), (0, 1));
+
+    // Events without the key argument are left out
+    let report = latency_between(&mut LogReader::new(&data), START, END, 1);
+    assert_eq!(report.count(), 0);
+    assert_eq!(report.to_string(), "count=0 unmatched_starts=0 unmatched_ends=3");
+}
+
+#[test]
+fn test_percentiles_and_histogram() {
+    let keys: Vec<[u8; 4]> = (0..100u32).map(u32::to_le_bytes).collect();
+    let mut events: Vec<(u64, &'static str, &[&[u8]])> = Vec::new();
+    let args: Vec<[&[u8]; 1]> = keys.iter().map(|key| [&key[..]]).collect();
+    // Request i takes i + 1 microseconds, and they don't overlap
+    for (i, arg) in args.iter().enumerate() {
+        let start = i as u64 * 1_000;
+        events.push((start, START, &arg[..]));
+        events.push((start + i as u64 + 1, "Request {} done", &arg[..]));
+    }
+    let data = log_at(&events);
+    let report = latency_between(&mut LogReader::new(&data), START, "Request {} done", 0);
+    assert_eq!(report.count(), 100);
+    assert_eq!(report.percentile(0.5), Some(Duration::from_micros(50)));
+    assert_eq!(report.percentile(0.99), Some(Duration::from_micros(99)));
+    assert_eq!(report.percentile(0.0), Some(Duration::from_micros(1)));
+    assert_eq!(report.percentile(1.0), Some(Duration::from_micros(100)));
+
+    let histogram = report.histogram();
+    let bucket = |min, end, count| LatencyBucket { min: Duration::from_micros(min), end: Duration::from_micros(end), count };
+    assert_eq!(histogram, [
+        bucket(1, 2, 1), bucket(2, 4, 2), bucket(4, 8, 4), bucket(8, 16, 8),
+        bucket(16, 32, 16), bucket(32, 64, 32), bucket(64, 128, 37),
+    ]);
+}
+
+#[test]
+fn test_keys_match_across_argument_types_and_filters_apply() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_type_tags(true);
+        for id in 0..20u32 {
+            log_record_at!(logger, Level::Info, "Job {} queued", id).unwrap();
+            let level = if id % 2 == 0 { Level::Info } else { Level::Debug };
+            log_record_at!(logger, level, "Job {} ran", id as u64).unwrap();
+        }
+    }
+    let data = data.lock().unwrap();
+    let report = latency_between(&mut LogReader::new(&data), "Job {} queued", "Job {} ran", 0);
+    assert_eq!((report.count(), report.unmatched_starts), (20, 0));
+
+    let mut reader = LogReader::new(&data);
+    reader.set_level_filter(Some(Level::Info));
+    let report = latency_between(&mut reader, "Job {} queued", "Job {} ran", 0);
+    assert_eq!((report.count(), report.unmatched_starts), (10, 10));
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_error, log_record};
+use binary_logger::backtrace::LogBacktrace;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn frames_of(value: &LogValue) -> &[String] {
+    match value {
+        LogValue::Backtrace(frames) => frames,
+        other => panic!("expected a backtrace, got {:?}", other),
+    }
+}
+
+#[inline(never)]
+fn failing_step<const CAP: usize>(logger: &mut Logger<CAP>) {
+    let err = std::fs::read("/no/such/file").unwrap_err();
+    log_error!(logger, "Step failed: {}", err).unwrap();
+}
+
+#[test]
+fn test_backtrace_per_call() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Invariant broken in batch {}", 7, LogBacktrace::capture()).unwrap();
+        log_record!(logger, "Twice {} {}", LogBacktrace::capture(), LogBacktrace::capture()).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    let frames = frames_of(&entries[0].parameters[1]);
+    assert!(!frames.is_empty() && frames.len() <= 32);
+    assert!(!frames[0].starts_with("binary_logger::backtrace::"));
+    assert!(frames.iter().any(|frame| frame.contains("test_backtrace_per_call")));
+
+    let text = entries[0].format();
+    assert!(text.starts_with("Invariant broken in batch 7\nbacktrace:\n   0: "));
+
+    // Two captures in one call see the same stack
+    assert_eq!(frames_of(&entries[1].parameters[0]).len(), frames_of(&entries[1].parameters[1]).len());
+}
+
+#[test]
+fn test_backtrace_on_log_error() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        failing_step(&mut logger);
+        logger.set_error_backtraces(true);
+        failing_step(&mut logger);
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    assert!(frames_of(&entries[0].parameters[1]).is_empty());
+    assert!(!entries[0].format().contains("backtrace:"));
+
+    let frames = frames_of(&entries[1].parameters[1]);
+    assert!(frames.iter().any(|frame| frame.contains("failing_step")));
+    assert!(entries[1].format().contains("\nbacktrace:\n"));
+}
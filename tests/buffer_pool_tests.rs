@@ -0,0 +1,98 @@
+use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+use binary_logger::buffer_pool::BufferPool;
+use binary_logger::codec::RawCodec;
+use binary_logger::pool::LoggerPool;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Log = Arc<Mutex<Vec<u8>>>;
+
+struct LogHandler(Log);
+
+impl BufferHandler for LogHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+fn messages(data: &Log) -> Vec<String> {
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect()
+}
+
+#[test]
+fn test_pooled_logger_roundtrip() {
+    let pool = Arc::new(BufferPool::<256>::new(1));
+    let data = Log::default();
+    {
+        let mut logger = Logger::with_buffer_pool(LogHandler(data.clone()), &RawCodec, pool.clone());
+        for i in 0..100 {
+            log_record!(logger, "Pooled record {}", i).unwrap();
+        }
+        assert!(logger.stats().buffers_flushed > 1, "Records should span several buffers");
+        assert_eq!(logger.stats().dropped_records, 0);
+    }
+
+    // One buffer was enough, and it is back in the pool
+    let stats = pool.stats();
+    assert_eq!((stats.allocated, stats.leased, stats.peak_leased), (1, 0, 1));
+    let expected: Vec<String> = (0..100).map(|i| format!("Pooled record {}", i)).collect();
+    assert_eq!(messages(&data), expected);
+}
+
+#[test]
+fn test_pool_exhaustion() {
+    let pool = Arc::new(BufferPool::<4096>::new(1));
+    let (first_data, second_data) = (Log::default(), Log::default());
+    let mut first = Logger::with_buffer_pool(LogHandler(first_data.clone()), &RawCodec, pool.clone());
+    let mut second = Logger::with_buffer_pool(LogHandler(second_data.clone()), &RawCodec, pool.clone());
+
+    log_record!(first, "First logger {}", 1).unwrap();
+    log_record!(second, "Second logger {}", 1).unwrap();
+    assert_eq!(second.stats().dropped_records, 1);
+    assert_eq!(pool.stats().exhausted, 1);
+
+    // An idle Logger holds no buffer after a flush
+    first.flush();
+    assert_eq!(pool.stats().leased, 0);
+    log_record!(second, "Second logger {}", 2).unwrap();
+    second.flush();
+
+    assert_eq!(messages(&first_data), ["First logger 1"]);
+    assert_eq!(messages(&second_data), ["Second logger 2"]);
+}
+
+#[test]
+fn test_logger_pool_with_buffer_pool() {
+    let buffers = Arc::new(BufferPool::<1024>::new(4));
+    let logs = Arc::new(Mutex::new(Vec::<Log>::new()));
+    let pool = {
+        let logs = logs.clone();
+        Arc::new(LoggerPool::<1024>::new(move || {
+            let data = Log::default();
+            logs.lock().unwrap().push(data.clone());
+            LogHandler(data)
+        }).buffer_pool(buffers.clone()))
+    };
+
+    let workers: Vec<_> = (0..4).map(|worker| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            for i in 0..50 {
+                pool.with(|logger| log_record!(logger, "Worker {} record {}", worker, i)).unwrap();
+            }
+            pool.flush_all();
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    // A buffer per thread at most, so nothing was dropped
+    let stats = buffers.stats();
+    assert!(stats.allocated <= 4);
+    assert_eq!((stats.leased, stats.exhausted), (0, 0));
+    let written: usize = logs.lock().unwrap().iter().map(|data| messages(data).len()).sum();
+    assert_eq!(written, 200);
+}
@@ -0,0 +1,84 @@
+use binary_logger::{Logger, BufferHandler, LogReader, LogValue, log_record, register_string};
+use std::sync::{Arc, Mutex};
+
+struct CollectingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CollectingHandler {
+    fn new() -> Self {
+        Self {
+            data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+fn write_channels() -> Vec<u8> {
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+    {
+        let mut logger = Logger::<512>::new(handler);
+        logger.set_global_sequence(true);
+        for i in 0..30 {
+            match i % 3 {
+                0 => log_record!(logger, "Debug {}", i).unwrap(),
+                1 => log_record!(logger, channel: "audit", "Audit {}", i).unwrap(),
+                _ => log_record!(logger, channel: "metrics", "Metric {} {}", i, 0.5).unwrap(),
+            }
+        }
+    }
+    let data = data.lock().unwrap();
+    data.clone()
+}
+
+fn first_value(entry: &binary_logger::LogEntry) -> i32 {
+    match entry.parameters.first() {
+        Some(LogValue::Integer(value)) => *value,
+        other => panic!("Expected integer parameter, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_channel_roundtrip() {
+    let data = write_channels();
+    let mut reader = LogReader::new(&data);
+
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        let i = first_value(&entry);
+        assert_eq!(i, count);
+        match i % 3 {
+            0 => assert_eq!((entry.channel, entry.channel_name()), (0, None)),
+            1 => assert_eq!(entry.channel_name(), Some("audit")),
+            _ => assert_eq!(entry.channel_name(), Some("metrics")),
+        }
+        assert!(entry.sequence.is_some(), "Channel and sequence should coexist");
+        count += 1;
+    }
+    assert_eq!(count, 30);
+}
+
+#[test]
+fn test_channel_filter_and_stats() {
+    let data = write_channels();
+    let audit = register_string("audit");
+    let metrics = register_string("metrics");
+
+    let mut reader = LogReader::new(&data);
+    reader.set_channel_filter(Some(&[audit]));
+    let values: Vec<i32> = std::iter::from_fn(|| reader.read_entry()).map(|e| first_value(&e)).collect();
+    assert_eq!(values, (0..30).filter(|i| i % 3 == 1).collect::<Vec<_>>());
+
+    // Filtered-out records still show up in the statistics
+    let stats = reader.channel_stats();
+    assert_eq!(stats[&0].records, 10);
+    assert_eq!(stats[&audit].records, 10);
+    assert_eq!(stats[&metrics].records, 10);
+    assert!(stats[&metrics].payload_bytes > stats[&audit].payload_bytes, "Metric records carry an extra argument");
+}
@@ -0,0 +1,149 @@
+use binary_logger::{Logger, BufferHandler, LogReader, LogMerger, log_record};
+use binary_logger::clock_sync::{ClockOffset, ClockSource};
+use binary_logger::features::FormatFeatures;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Keeps every buffer apart, to read them on their own.
+struct BufferCollector(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl BufferHandler for BufferCollector {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().push(data.to_vec());
+    }
+}
+
+#[test]
+fn test_clock_offset_applies_from_its_record_on() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    let ntp = ClockOffset::new(-250, 1000, ClockSource::Ntp);
+    let ptp = ClockOffset::new(1_500, 2, ClockSource::Ptp);
+    {
+        let mut logger = Logger::<4096>::new(BufferCollector(buffers.clone()));
+        log_record!(logger, "Before any offset {}", 1).unwrap();
+        logger.set_clock_offset(ntp);
+        assert_eq!(logger.clock_offset(), Some(ntp));
+        log_record!(logger, "Measured by NTP {}", 2).unwrap();
+        logger.set_clock_offset(ptp);
+        log_record!(logger, "Measured by PTP {}", 3).unwrap();
+    }
+    let data = buffers.lock().unwrap().concat();
+    let mut reader = LogReader::new(&data);
+    let mut offsets = Vec::new();
+    while let Some(entry) = reader.read_entry() {
+        offsets.push((entry.format(), reader.clock_offset()));
+    }
+    assert_eq!(offsets, [
+        ("Before any offset 1".to_string(), None),
+        ("Measured by NTP 2".to_string(), Some(ntp)),
+        ("Measured by PTP 3".to_string(), Some(ptp)),
+    ]);
+}
+
+#[test]
+fn test_every_buffer_carries_the_clock_offset() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    let offset = ClockOffset::new(42, 7, ClockSource::Manual);
+    {
+        let mut logger = Logger::<256>::new(BufferCollector(buffers.clone()));
+        logger.set_clock_offset(offset);
+        for i in 0..100u32 {
+            log_record!(logger, "Sample {}", i).unwrap();
+        }
+    }
+    let buffers = buffers.lock().unwrap();
+    assert!(buffers.len() > 5);
+    for buffer in buffers.iter() {
+        let mut reader = LogReader::new(buffer);
+        assert!(reader.read_entry().is_some());
+        assert_eq!(reader.clock_offset(), Some(offset));
+    }
+}
+
+#[test]
+fn test_aligned_merge_orders_hosts_on_the_reference_timeline() {
+    let (host_a, host_b) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+    let id = 7u32;
+    {
+        // Host A's clock is 10 s slow, so its record reads earlier than
+        // host B's, written after it
+        let mut logger = Logger::<4096>::new(BufferCollector(host_a.clone()));
+        logger.set_clock_offset(ClockOffset::new(10_000_000, 2_000, ClockSource::Ntp));
+        log_record!(logger, "Received request {}", id).unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(5));
+    {
+        // Host B's clock is right
+        let mut logger = Logger::<4096>::new(BufferCollector(host_b.clone()));
+        logger.set_clock_offset(ClockOffset::new(0, 100, ClockSource::Ptp));
+        log_record!(logger, "Sent request {}", id).unwrap();
+    }
+    let (host_a, host_b) = (host_a.lock().unwrap().concat(), host_b.lock().unwrap().concat());
+
+    let by_host_time: Vec<_> = LogMerger::new(vec![LogReader::new(&host_a), LogReader::new(&host_b)])
+        .map(|entry| entry.format())
+        .collect();
+    assert_eq!(by_host_time, ["Received request 7", "Sent request 7"]);
+
+    let mut merger = LogMerger::aligned(vec![LogReader::new(&host_a), LogReader::new(&host_b)]);
+    let sent = merger.read_aligned().unwrap();
+    let received = merger.read_aligned().unwrap();
+    assert!(merger.read_aligned().is_none());
+    assert_eq!((sent.entry.format(), sent.source), ("Sent request 7".to_string(), 1));
+    assert_eq!((received.entry.format(), received.source), ("Received request 7".to_string(), 0));
+    assert_eq!(sent.error_bound(), Some(Duration::from_micros(100)));
+    assert_eq!(received.error_bound(), Some(Duration::from_millis(2)));
+
+    // Nearly 10 s apart, far more than the bounds allow for
+    let apart = received.entry.timestamp.duration_since(sent.entry.timestamp).unwrap();
+    assert!(apart > Duration::from_secs(9) && apart < Duration::from_secs(10), "{:?}", apart);
+    assert!(!sent.concurrent_with(&received));
+}
+
+#[test]
+fn test_entries_within_their_error_bounds_are_concurrent() {
+    let (host_a, host_b) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+    let id = 9u32;
+    {
+        let mut a = Logger::<4096>::new(BufferCollector(host_a.clone()));
+        let mut b = Logger::<4096>::new(BufferCollector(host_b.clone()));
+        a.set_clock_offset(ClockOffset::new(-3_000, 50_000, ClockSource::Ntp));
+        b.set_clock_offset(ClockOffset::new(4_000, 50_000, ClockSource::Ntp));
+        log_record!(a, "Lock taken by A {}", id).unwrap();
+        log_record!(b, "Lock taken by B {}", id).unwrap();
+        log_record!(a, "Lock released by A {}", id).unwrap();
+    }
+    let (host_a, host_b) = (host_a.lock().unwrap().concat(), host_b.lock().unwrap().concat());
+    let mut merger = LogMerger::aligned(vec![LogReader::new(&host_a), LogReader::new(&host_b)]);
+    let entries: Vec<_> = std::iter::from_fn(|| merger.read_aligned()).collect();
+    assert_eq!(entries.len(), 3);
+
+    let (from_a, from_b): (Vec<_>, Vec<_>) = entries.iter().partition(|aligned| aligned.source == 0);
+    assert!(from_a[0].concurrent_with(from_b[0]));
+    assert!(from_b[0].concurrent_with(from_a[1]));
+    // The order of one host's entries is never in doubt
+    assert!(!from_a[0].concurrent_with(from_a[1]));
+}
+
+#[test]
+fn test_clock_offsets_need_their_format_feature() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(BufferCollector(buffers.clone()));
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::CLOCK_OFFSETS);
+        logger.set_clock_offset(ClockOffset::new(1_000, 10, ClockSource::Ptp));
+        assert_eq!(logger.clock_offset(), None);
+        log_record!(logger, "Without offsets {}", 1).unwrap();
+    }
+    let data = buffers.lock().unwrap().concat();
+    let mut reader = LogReader::new(&data);
+    let entry = reader.read_entry().unwrap();
+    assert!(!reader.format_features().unwrap().contains(FormatFeatures::CLOCK_OFFSETS));
+    assert_eq!(reader.clock_offset(), None);
+
+    // Without offsets, an aligned merge keeps the host's times
+    let mut merger = LogMerger::aligned(vec![LogReader::new(&data)]);
+    let aligned = merger.read_aligned().unwrap();
+    assert_eq!(aligned.entry.timestamp, entry.timestamp);
+    assert_eq!((aligned.offset, aligned.error_bound()), (None, None));
+}
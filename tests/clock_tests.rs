@@ -1,4 +1,4 @@
-use binary_logger::efficient_clock::{get_timestamp, TimestampConverter};
+use binary_logger::efficient_clock::{get_timestamp, reanchor, TimestampConverter};
 use std::thread;
 use std::time::Duration;
 
@@ -145,4 +145,21 @@ fn test_timestamp_precision() {
     if end != 0 {  // Only check if we didn't hit a base reset
         assert!(end > start, "Timestamp should be precise enough to detect 1ms difference");
     }
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_reanchor_keeps_time_monotonic() {
+    let mut converter = TimestampConverter::new();
+    converter.get_relative_timestamp();
+    let mut prev = converter.base_micros();
+    for _ in 0..5 {
+        thread::sleep(Duration::from_millis(5));
+        let offset = reanchor(Duration::from_millis(50));
+        assert!(offset.abs() < 1_000_000, "log time {}us off the wall clock", offset);
+        converter.reset();
+        let (_, is_base) = converter.get_relative_timestamp();
+        assert!(is_base);
+        let current = converter.base_micros();
+        assert!(current >= prev, "time went back from {} to {}", prev, current);
+        prev = current;
+    }
+}
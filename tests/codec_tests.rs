@@ -0,0 +1,130 @@
+use binary_logger::{Logger, BufferHandler, LogReader, LogValue, log_record};
+use binary_logger::codec::{Codec, RawCodec, PostcardCodec, CborCodec, CompactCodec, CBOR_CODEC_ID, COMPACT_CODEC_ID, codec_by_id};
+use std::sync::{Arc, Mutex};
+
+struct CollectingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CollectingHandler {
+    fn new() -> Self {
+        Self {
+            data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+fn encode(codec: &dyn Codec, args: &[&[u8]]) -> Vec<u8> {
+    let mut out = vec![0u8; codec.encoded_len(args)];
+    codec.encode(args, &mut out);
+    out
+}
+
+#[test]
+fn test_codec_roundtrip_through_logger() {
+    let codecs: [&'static dyn Codec; 4] = [&RawCodec, &PostcardCodec, &CborCodec, &CompactCodec];
+
+    for codec in codecs {
+        let handler = CollectingHandler::new();
+        let data = handler.data.clone();
+        {
+            let mut logger = Logger::<4096>::with_codec(handler, codec);
+            for i in 0..100 {
+                log_record!(logger, "Codec value {} flag {}", i, i % 2 == 0).unwrap();
+            }
+        }
+
+        let data = data.lock().unwrap();
+        let mut reader = LogReader::new(&data);
+        let mut count = 0;
+        while let Some(entry) = reader.read_entry() {
+            match entry.parameters.as_slice() {
+                [LogValue::Integer(value), LogValue::Boolean(flag)] => {
+                    assert_eq!(*value, count);
+                    assert_eq!(*flag, count % 2 == 0);
+                }
+                other => panic!("Unexpected parameters with {} codec: {:?}", codec.name(), other),
+            }
+            count += 1;
+        }
+
+        assert_eq!(count, 100, "All records should decode with the {} codec", codec.name());
+        assert_eq!(reader.codec().map(|c| c.id()), Some(codec.id()), "Reader should pick up the codec from the stream header");
+    }
+}
+
+#[test]
+fn test_postcard_encoding() {
+    let encoded = encode(&PostcardCodec, &[&[0xAA], &[0x01, 0x02, 0x03, 0x04]]);
+    assert_eq!(encoded, vec![0x02, 0x01, 0xAA, 0x04, 0x01, 0x02, 0x03, 0x04]);
+
+    // Lengths of 128 and above take a multi-byte varint
+    let long = [7u8; 200];
+    let encoded = encode(&PostcardCodec, &[&long]);
+    assert_eq!(&encoded[..3], &[0x01, 0xC8, 0x01]);
+    assert_eq!(PostcardCodec.decode(&encoded), vec![&long[..]]);
+}
+
+#[test]
+fn test_cbor_encoding() {
+    let encoded = encode(&CborCodec, &[&[0xAA], &[0x01, 0x02, 0x03, 0x04]]);
+    assert_eq!(encoded, vec![0x82, 0x41, 0xAA, 0x44, 0x01, 0x02, 0x03, 0x04]);
+
+    // Lengths of 24 and above carry a separate length byte
+    let long = [7u8; 30];
+    let encoded = encode(&CborCodec, &[&long]);
+    assert_eq!(&encoded[..3], &[0x81, 0x58, 30]);
+    assert_eq!(CborCodec.decode(&encoded), vec![&long[..]]);
+}
+
+#[test]
+fn test_compact_encoding() {
+    let encoded = encode(&CompactCodec, &[b"ready", &[], &[0x01, 0x02, 0x03, 0x04]]);
+    assert_eq!(encoded, [&[0x03, 0x05][..], b"ready", &[0x00, 0x04, 0x01, 0x02, 0x03, 0x04]].concat());
+
+    // Past 15 bytes the size follows the tag as a varint
+    let long = [7u8; 200];
+    let encoded = encode(&CompactCodec, &[&[7u8; 15], &long]);
+    assert_eq!(encoded[1], 0x0F);
+    assert_eq!(&encoded[17..20], &[0x10, 0xC8, 0x01]);
+    assert_eq!(CompactCodec.decode(&encoded), vec![&[7u8; 15][..], &long[..]]);
+    // Unknown tags end the arguments
+    assert_eq!(CompactCodec.decode(&[0x02, 0x01, 0xAA, 0x20, 0xBB]), vec![&[0xAA][..]]);
+
+    // A short string costs one byte over its bytes, where the raw layout takes four
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+    {
+        let mut logger = Logger::<4096>::with_codec(handler, &CompactCodec);
+        log_record!(logger, "Link {}", String::from("degraded")).unwrap();
+    }
+    let data = data.lock().unwrap();
+    let entry = LogReader::new(&data).read_entry().unwrap();
+    assert_eq!(entry.format(), "Link degraded");
+    assert_eq!(entry.raw_values.len(), 1 + 1 + "degraded".len());
+}
+
+#[test]
+fn test_decode_truncated_payload() {
+    let args: [&[u8]; 2] = [&[1, 2, 3, 4], &[5, 6, 7, 8]];
+    let codecs: [&'static dyn Codec; 4] = [&RawCodec, &PostcardCodec, &CborCodec, &CompactCodec];
+
+    for codec in codecs {
+        let encoded = encode(codec, &args);
+        let decoded = codec.decode(&encoded[..encoded.len() - 1]);
+        assert_eq!(decoded, vec![&[1u8, 2, 3, 4][..]], "{} should keep the complete arguments", codec.name());
+    }
+}
+
+#[test]
+fn test_codec_lookup() {
+    assert_eq!(codec_by_id(CBOR_CODEC_ID).map(|c| c.name()), Some("cbor"));
+    assert_eq!(codec_by_id(COMPACT_CODEC_ID).map(|c| c.name()), Some("compact"));
+    assert!(codec_by_id(0xEE).is_none());
+}
@@ -0,0 +1,22 @@
+//! Fixtures shared by the integration tests.
+
+#![allow(dead_code)]
+
+use binary_logger::{BufferHandler, LogEntry, LogReader};
+use std::sync::{Arc, Mutex};
+
+/// Collects every buffer the logger hands over into one log.
+#[derive(Clone)]
+pub struct CollectingHandler(pub Arc<Mutex<Vec<u8>>>);
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+/// Reads every entry of the log `data`.
+pub fn read_all(data: &[u8]) -> Vec<LogEntry> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).collect()
+}
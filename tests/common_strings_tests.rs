@@ -0,0 +1,137 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, LogValue, log_record, register_string};
+use binary_logger::codec::{Codec, CompactCodec, RawCodec};
+use binary_logger::common_strings::{common_string, common_string_id, COMMON_STRINGS, COMMON_STRING_ID_START};
+use binary_logger::features::FormatFeatures;
+use binary_logger::instrumentation::{InternalEvent, is_reserved_format_id};
+use binary_logger::string_registry::{get_string, registered_strings};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+fn write_log(codec: &'static dyn Codec, configure: impl FnOnce(&mut Logger<4096>)) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::with_codec(CollectingHandler(data.clone()), codec);
+        configure(&mut logger);
+        for (method, status) in [("GET", "200"), ("DELETE", "404"), ("FETCH", "ok"), ("HTTP/1.1", "418")] {
+            log_record!(logger, "Request {} answered {}", method.to_string(), status.to_string()).unwrap();
+        }
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+#[test]
+fn test_table_ids_are_reserved() {
+    assert_eq!(COMMON_STRINGS.iter().collect::<HashSet<_>>().len(), COMMON_STRINGS.len());
+    for (index, &s) in COMMON_STRINGS.iter().enumerate() {
+        let id = common_string_id(s).unwrap();
+        assert_eq!(id, COMMON_STRING_ID_START + index as u32);
+        assert!(is_reserved_format_id(id) && id < 0xFFFF);
+        assert_eq!((common_string(id), get_string(id)), (Some(s), Some(s)));
+    }
+    assert!(InternalEvent::ALL.iter().all(|event| event.format_id() < COMMON_STRING_ID_START));
+    assert_eq!(common_string_id("get"), None);
+    assert_eq!(common_string(COMMON_STRING_ID_START + COMMON_STRINGS.len() as u32), None);
+    assert_ne!(register_string("GET"), common_string_id("GET").unwrap());
+}
+
+#[test]
+fn test_common_string_arguments_are_shortened() {
+    for codec in [&RawCodec as &'static dyn Codec, &CompactCodec] {
+        for type_tags in [false, true] {
+            let plain = read_all(&write_log(codec, |logger| logger.set_type_tags(type_tags)));
+            let entries = read_all(&write_log(codec, |logger| {
+                logger.set_type_tags(type_tags);
+                logger.set_common_strings(true);
+                assert!(logger.common_strings());
+            }));
+            let lines: Vec<String> = entries.iter().map(LogEntry::format).collect();
+            assert_eq!(lines, [
+                "Request GET answered 200",
+                "Request DELETE answered 404",
+                "Request FETCH answered ok",
+                "Request HTTP/1.1 answered 418",
+            ]);
+            assert!(matches!(&entries[1].parameters[0], LogValue::String(s) if s == "DELETE"));
+            assert!(entries.iter().all(|entry| !entry.lossy));
+
+            // Strings of the table of 3 bytes and more take 2; "ok" and
+            // the others are stored as they are
+            let saved: Vec<usize> = plain.iter().zip(&entries).map(|(plain, entry)| plain.raw_values.len() - entry.raw_values.len()).collect();
+            assert_eq!(saved, [1 + 1, 4 + 1, 0, 6], "codec {} tags {}", codec.name(), type_tags);
+        }
+    }
+}
+
+#[test]
+fn test_common_literals_skip_the_registry() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_common_strings(true);
+        log_record!(logger, "Method {} via {}", "OPTIONS", "HTTP/3").unwrap();
+        logger.set_common_strings(false);
+        log_record!(logger, "Method {} via {}", "CONNECT", "HTTP/2").unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries[0].format(), "Method OPTIONS via HTTP/3");
+    assert_eq!(entries[1].format(), "Method CONNECT via HTTP/2");
+
+    let registered: HashSet<&str> = registered_strings().into_iter().map(|(_, s)| s).collect();
+    assert!(!registered.contains("OPTIONS") && !registered.contains("HTTP/3"));
+    assert!(registered.contains("CONNECT") && registered.contains("HTTP/2"));
+    // After the argument count and the argument's length
+    let first_id = u16::from_le_bytes(entries[0].raw_values[5..7].try_into().unwrap()) as u32;
+    assert_eq!(Some(first_id), common_string_id("OPTIONS"));
+}
+
+#[test]
+fn test_common_strings_need_the_feature() {
+    let data = write_log(&RawCodec, |logger| {
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::COMMON_STRINGS);
+        logger.set_common_strings(true);
+        assert!(!logger.common_strings());
+    });
+    let entries = read_all(&data);
+    assert_eq!(entries[0].format(), "Request GET answered 200");
+    assert!(!entries[0].raw_values.windows(1).any(|byte| byte[0] == 0xFF));
+
+    let data = write_log(&RawCodec, |logger| {
+        logger.set_common_strings(true);
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::COMMON_STRINGS);
+        assert!(!logger.common_strings());
+    });
+    let mut reader = LogReader::new(&data);
+    reader.read_entry().unwrap();
+    assert!(!reader.format_features().unwrap().contains(FormatFeatures::COMMON_STRINGS));
+}
+
+#[test]
+fn test_untyped_arguments_are_not_taken_for_the_marker() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let index = (common_string_id("POST").unwrap() - COMMON_STRING_ID_START) as u8;
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.write_args(register_string("Untyped {}"), &[&[0xFF, index]]).unwrap();
+        for common_strings in [false, true] {
+            logger.set_common_strings(common_strings);
+            for type_tags in [false, true] {
+                logger.set_type_tags(type_tags);
+                log_record!(logger, "Port {} and {}", 511u16, 0xFF05u16).unwrap();
+            }
+        }
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_ne!(entries[0].format(), "Untyped POST");
+    // 511 is stored as [0xFF, 0x01], the marker and the index of "DEBUG"
+    assert_eq!(common_string(COMMON_STRING_ID_START + 1), Some("DEBUG"));
+    for entry in &entries[1..] {
+        assert!(entry.parameters.iter().all(|value| !matches!(value, LogValue::String(s) if COMMON_STRINGS.contains(&s.as_str()))), "{}", entry.format());
+    }
+    for tagged in [&entries[2], &entries[4]] {
+        assert_eq!(tagged.format(), format!("Port 511 and {}", 0xFF05u16));
+    }
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "zstd")]
+
+use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+use binary_logger::compact::{covered_segments, read_segment, Compaction};
+use binary_logger::rotation;
+use binary_logger::verify::verify_segments;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Keeps each buffer apart, to be written as a segment.
+struct BufferCollector(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl BufferHandler for BufferCollector {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().push(data.to_vec());
+    }
+}
+
+/// Writes a hash-chained log as `count` segments of one buffer each in
+/// `dir`, returning their paths in stream order.
+fn write_segments(dir: &Path, count: usize) -> Vec<PathBuf> {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<1024>::new(BufferCollector(buffers.clone()));
+        logger.set_audit_chain(true);
+        for i in 0..count as u32 {
+            for order in 0..20 {
+                log_record!(logger, "Order {} of batch {} filled at {}", order, i, 99.5).unwrap();
+            }
+            logger.flush();
+        }
+    }
+    let buffers = buffers.lock().unwrap();
+    assert_eq!(buffers.len(), count);
+    buffers.iter().enumerate().map(|(i, buffer)| {
+        let path = dir.join(format!("app.{:04}.blog", i));
+        fs::write(&path, buffer).unwrap();
+        path
+    }).collect()
+}
+
+fn read_all(data: &[u8]) -> Vec<String> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect()
+}
+
+fn later() -> SystemTime {
+    SystemTime::now() + Duration::from_secs(2 * 86400)
+}
+
+#[test]
+fn test_aged_segments_are_merged_and_compressed() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = write_segments(dir.path(), 3);
+    let original: Vec<u8> = segments.iter().flat_map(|path| fs::read(path).unwrap()).collect();
+
+    let report = Compaction::new(later()).run(&segments).unwrap();
+    assert_eq!(report.compacted.len(), 1);
+    let compacted = &report.compacted[0];
+    assert_eq!(compacted.path, dir.path().join("app.0000.blog.zst"));
+    // One zstd frame
+    assert_eq!(fs::read(&compacted.path).unwrap()[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+    assert_eq!(compacted.segments, segments);
+    assert_eq!(compacted.bytes_in, original.len() as u64);
+    assert!(compacted.bytes_out < compacted.bytes_in / 2, "{:?}", compacted);
+    assert_eq!(report.bytes_saved(), compacted.bytes_in - compacted.bytes_out);
+    assert!(segments.iter().all(|path| !path.exists()));
+
+    // The compressed file reads as the segments did, chain and all
+    let data = read_segment(&compacted.path).unwrap();
+    assert_eq!(data, original);
+    assert_eq!(read_all(&data).len(), 60);
+    assert!(verify_segments(&[("app.0000.blog.zst".to_string(), data)]).passed());
+}
+
+#[test]
+fn test_runs_follow_target_size_and_skipped_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = write_segments(dir.path(), 5);
+    let size = fs::metadata(&segments[0]).unwrap().len();
+
+    // A compressed segment in the middle, compacted by an earlier version,
+    // splits the runs
+    let compressed = dir.path().join("app.0002.blog.lz4");
+    fs::rename(&segments[2], &compressed).unwrap();
+    let mut segments = segments;
+    segments[2] = compressed.clone();
+
+    let compaction = Compaction::new(later()).target_bytes(size * 2);
+    let (runs, skipped) = compaction.plan(&segments);
+    assert_eq!(runs, [vec![segments[0].clone(), segments[1].clone()], vec![segments[3].clone(), segments[4].clone()]]);
+    assert_eq!(skipped, [compressed]);
+    // Planning changes nothing
+    assert!(segments.iter().all(|path| path.exists()));
+
+    let report = compaction.run(&segments).unwrap();
+    let paths: Vec<_> = report.compacted.iter().map(|c| c.path.clone()).collect();
+    assert_eq!(paths, [dir.path().join("app.0000.blog.zst"), dir.path().join("app.0003.blog.zst")]);
+}
+
+#[test]
+fn test_recent_segments_are_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = write_segments(dir.path(), 2);
+    let report = Compaction::new(SystemTime::now()).min_age(Duration::from_secs(3600)).run(&segments).unwrap();
+    assert!(report.compacted.is_empty());
+    assert_eq!(report.skipped, segments);
+    assert!(segments.iter().all(|path| path.exists()));
+
+    // Uncompressed segments read as they are
+    assert_eq!(read_segment(&segments[0]).unwrap(), fs::read(&segments[0]).unwrap());
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn test_segments_compacted_with_lz4_still_read() {
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let segments = write_segments(dir.path(), 2);
+    let original = fs::read(&segments[0]).unwrap();
+    let compressed = dir.path().join("app.0000.blog.lz4");
+    let mut encoder = lz4::EncoderBuilder::new().level(12).build(fs::File::create(&compressed).unwrap()).unwrap();
+    encoder.write_all(&original).unwrap();
+    encoder.finish().1.unwrap();
+
+    assert_eq!(read_segment(&compressed).unwrap(), original);
+}
+
+#[test]
+fn test_interrupted_compaction_is_finished_not_repeated() {
+    let dir = tempfile::tempdir().unwrap();
+    // Named as rotated files, so that `rotation::segments` lists them
+    let segments: Vec<PathBuf> = write_segments(dir.path(), 3).into_iter().enumerate().map(|(i, path)| {
+        let rotated = dir.path().join(format!("app.{:06}.blog", i + 1));
+        fs::rename(&path, &rotated).unwrap();
+        rotated
+    }).collect();
+    let saved: Vec<Vec<u8>> = segments.iter().map(|path| fs::read(path).unwrap()).collect();
+    let report = Compaction::new(later()).run(&segments).unwrap();
+    let compressed = report.compacted[0].path.clone();
+    assert!(report.recovered.is_empty());
+    assert!(covered_segments(&compressed).is_empty());
+
+    // As if the process stopped after removing the first original only
+    for (path, data) in segments[1..].iter().zip(&saved[1..]) {
+        fs::write(path, data).unwrap();
+    }
+    fs::write(dir.path().join("app.000001.blog.zst.segments"), "app.000001.blog\napp.000002.blog\napp.000003.blog\n").unwrap();
+    assert_eq!(covered_segments(&compressed), segments);
+
+    // Readers see each entry once
+    let listed = rotation::segments(dir.path(), "app").unwrap();
+    assert_eq!(listed, vec![compressed.clone()]);
+    let all: Vec<PathBuf> = vec![compressed.clone(), segments[1].clone(), segments[2].clone()];
+    let (runs, skipped) = Compaction::new(later()).plan(&all);
+    assert!(runs.is_empty());
+    assert_eq!(skipped, vec![compressed.clone()]);
+
+    // And the next run removes the survivors instead of compacting them
+    let report = Compaction::new(later()).run(&all).unwrap();
+    assert!(report.compacted.is_empty());
+    assert_eq!(report.recovered, segments[1..].to_vec());
+    assert!(segments.iter().all(|path| !path.exists()));
+    assert!(!dir.path().join("app.000001.blog.zst.segments").exists());
+    assert_eq!(read_all(&read_segment(&compressed).unwrap()).len(), 60);
+}
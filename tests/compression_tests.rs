@@ -0,0 +1,177 @@
+#![cfg(any(feature = "lz4", feature = "zstd"))]
+
+use binary_logger::{Logger, BufferHandler, LogReader, StreamReader, MAX_BUFFER_SIZE, log_record};
+use binary_logger::compression::{compress_buffer, has_frames, inflate, inflate_frame, FrameCodec, COMPRESSED_FRAME};
+use std::sync::{Arc, Mutex};
+
+/// Keeps every buffer apart, to compress them one by one.
+struct BufferCollector(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl BufferHandler for BufferCollector {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().push(data.to_vec());
+    }
+}
+
+fn write_buffers(records: u32) -> Vec<Vec<u8>> {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<1024>::new(BufferCollector(buffers.clone()));
+        for i in 0..records {
+            log_record!(logger, "Compressed sample {} of {}", i, records).unwrap();
+        }
+    }
+    let buffers = buffers.lock().unwrap().clone();
+    buffers
+}
+
+fn texts(data: &[u8]) -> Vec<String> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect()
+}
+
+#[test]
+fn test_readers_decompress_frames_between_plain_buffers() {
+    let buffers = write_buffers(200);
+    assert!(buffers.len() > 4);
+    let plain = buffers.concat();
+    // Every other buffer compressed
+    let mixed: Vec<u8> = buffers.iter().enumerate()
+        .flat_map(|(i, buffer)| if i % 2 == 0 { compress_buffer(buffer, FrameCodec::default(), 0).unwrap() } else { buffer.clone() })
+        .collect();
+    assert!(!has_frames(&plain));
+    assert!(has_frames(&mixed));
+    assert!(u64::from_le_bytes(mixed[..8].try_into().unwrap()) & COMPRESSED_FRAME != 0);
+    assert!(mixed.len() < plain.len());
+
+    assert_eq!(inflate(&mixed).unwrap(), plain);
+    let expected = texts(&plain);
+    assert_eq!(expected.len(), 200);
+    assert_eq!(texts(&mixed), expected);
+
+    let mut stream = StreamReader::new(mixed.as_slice());
+    let mut streamed = Vec::new();
+    while let Some(entry) = stream.read_entry().unwrap() {
+        streamed.push(entry.format());
+    }
+    assert_eq!(streamed, expected);
+}
+
+#[test]
+fn test_damaged_frames_end_the_log() {
+    let buffers = write_buffers(100);
+    let mut frames: Vec<Vec<u8>> = buffers.iter().map(|buffer| compress_buffer(buffer, FrameCodec::default(), 0).unwrap()).collect();
+    let third = frames[2].len() / 2;
+    frames[2][third] ^= 0xFF;
+    frames[2][third + 1] ^= 0xFF;
+    let data = frames.concat();
+    assert!(inflate(&data).is_err());
+
+    // The readers keep the buffers before the damaged frame
+    let before = texts(&buffers[..2].concat());
+    assert_eq!(texts(&data), before);
+}
+
+#[test]
+fn test_frames_are_held_to_their_plain_size() {
+    let buffers = write_buffers(200);
+    let frame = compress_buffer(&buffers[0], FrameCodec::default(), 0).unwrap();
+    let with_plain_size = |size: u32| {
+        let mut frame = frame.clone();
+        frame[12..16].copy_from_slice(&size.to_le_bytes());
+        inflate_frame(&frame).map_err(|e| e.kind())
+    };
+    assert_eq!(with_plain_size(buffers[0].len() as u32).unwrap(), buffers[0]);
+
+    // Sizes no Logger writes are refused before allocating, and frames
+    // holding more than they claim before inflating them whole
+    assert_eq!(with_plain_size(u32::MAX), Err(std::io::ErrorKind::InvalidData));
+    assert_eq!(with_plain_size(MAX_BUFFER_SIZE as u32 + 1), Err(std::io::ErrorKind::InvalidData));
+    assert_eq!(with_plain_size(16), Err(std::io::ErrorKind::InvalidData));
+    assert!(compress_buffer(&vec![0; MAX_BUFFER_SIZE + 1], FrameCodec::default(), 0).is_err());
+}
+
+#[test]
+fn test_stream_reader_stops_at_a_frame_cut_short() {
+    let buffers = write_buffers(100);
+    let frames: Vec<Vec<u8>> = buffers.iter().map(|buffer| compress_buffer(buffer, FrameCodec::default(), 0).unwrap()).collect();
+    let mut data = frames[..2].concat();
+    data.extend_from_slice(&frames[2][..frames[2].len() - 3]);
+
+    let mut stream = StreamReader::new(data.as_slice());
+    let mut streamed = Vec::new();
+    while let Some(entry) = stream.read_entry().unwrap() {
+        streamed.push(entry.format());
+    }
+    assert_eq!(streamed, texts(&buffers[..2].concat()));
+}
+
+#[test]
+fn test_compressed_file_handler_writes_a_readable_log() {
+    use binary_logger::compact::read_segment;
+    use binary_logger::handlers::CompressedFileHandler;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("compressed.blog");
+    let (fast, strong) = (dir.path().join("fast.blog"), dir.path().join("strong.blog"));
+    for (path, handler) in [
+        (&path, CompressedFileHandler::create(&path, 2).unwrap()),
+        (&fast, CompressedFileHandler::with_level(&fast, 2, 0).unwrap()),
+        (&strong, CompressedFileHandler::with_level(&strong, 2, 12).unwrap()),
+    ] {
+        assert_eq!(handler.path(), path.as_path());
+        let mut logger = Logger::<4096>::new(handler);
+        for i in 0..500u32 {
+            log_record!(logger, "Compressed file sample {}", i).unwrap();
+        }
+    }
+
+    let data = std::fs::read(&path).unwrap();
+    assert!(has_frames(&data));
+    let plain = read_segment(&path).unwrap();
+    assert!(!has_frames(&plain));
+    assert!(data.len() * 2 < plain.len(), "{} compressed, {} plain", data.len(), plain.len());
+    let entries = texts(&data);
+    assert_eq!(entries.len(), 500);
+    assert_eq!(entries[499], "Compressed file sample 499");
+    assert_eq!(texts(&plain), entries);
+
+    assert_eq!(texts(&std::fs::read(&fast).unwrap()), entries);
+    assert_eq!(texts(&std::fs::read(&strong).unwrap()), entries);
+
+    // The files' timestamps differ, so the levels are compared on the same
+    // buffers. Level 0 is zstd's default rather than its fastest, so the
+    // fastest is 1.
+    let buffers = write_buffers(500);
+    let codec = FrameCodec::default();
+    let compressed_size = |level| buffers.iter().map(|buffer| compress_buffer(buffer, codec, level).unwrap().len()).sum::<usize>();
+    assert!(compressed_size(codec.max_level()) <= compressed_size(1));
+}
+
+#[cfg(all(feature = "lz4", feature = "zstd"))]
+#[test]
+fn test_frames_of_both_codecs_read_back() {
+    use binary_logger::compression::CODEC_ZSTD;
+
+    let buffers = write_buffers(200);
+    let plain = buffers.concat();
+    let codecs = [FrameCodec::Lz4, FrameCodec::Zstd];
+    let mixed: Vec<u8> = buffers.iter().enumerate()
+        .flat_map(|(i, buffer)| compress_buffer(buffer, codecs[i % 2], 0).unwrap())
+        .collect();
+    assert_eq!(inflate(&mixed).unwrap(), plain);
+    assert_eq!(texts(&mixed), texts(&plain));
+
+    // zstd at its strongest level is smaller than LZ4 at its own
+    let compressed_size = |codec: FrameCodec| buffers.iter()
+        .map(|buffer| compress_buffer(buffer, codec, codec.max_level()).unwrap().len())
+        .sum::<usize>();
+    assert!(compressed_size(FrameCodec::Zstd) < compressed_size(FrameCodec::Lz4));
+
+    // Frames name their codec; unknown codecs are refused
+    let mut frame = compress_buffer(&buffers[0], FrameCodec::Zstd, 3).unwrap();
+    assert_eq!(frame[8], CODEC_ZSTD);
+    assert_eq!(inflate_frame(&frame).unwrap(), buffers[0]);
+    frame[8] = 9;
+    assert_eq!(inflate_frame(&frame).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
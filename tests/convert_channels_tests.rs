@@ -0,0 +1,27 @@
+// Fills the channel names conversions may register, which are shared
+// process-wide, so it runs in a test binary of its own.
+
+use binary_logger::{LogReader, LogEntry};
+use binary_logger::convert::{from_text, TextPattern, MAX_CHANNELS};
+
+#[test]
+fn test_channels_past_the_cap_use_the_default_channel() {
+    let extra = 10;
+    let text: String = (0..MAX_CHANNELS + extra)
+        .map(|i| format!("2024-03-01T12:00:00Z INFO [worker-{}] started\n", i))
+        .collect();
+    let pattern: TextPattern = "{time} {level} [{channel}] {message}".parse().unwrap();
+    let converted = from_text(text.as_bytes(), &pattern).unwrap();
+    assert_eq!(converted.stats.unnamed_channels, extra as u64);
+    assert!(converted.stats.to_string().contains("10 records on the default channel"), "{}", converted.stats);
+
+    let mut reader = LogReader::new(&converted.data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), MAX_CHANNELS + extra);
+    assert_eq!(entries[MAX_CHANNELS - 1].channel_name(), Some(format!("worker-{}", MAX_CHANNELS - 1).as_str()));
+    assert!(entries[MAX_CHANNELS..].iter().all(|entry| entry.channel == 0 && entry.format() == "started"));
+
+    // A later conversion still finds the names registered before
+    let converted = from_text(text.as_bytes(), &pattern).unwrap();
+    assert_eq!(converted.stats.unnamed_channels, extra as u64);
+}
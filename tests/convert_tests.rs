@@ -0,0 +1,117 @@
+use binary_logger::{LogReader, LogEntry};
+use binary_logger::convert::{from_text, TextPattern};
+use binary_logger::level::Level;
+use binary_logger::render::TimeZone;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn entries(data: &[u8]) -> Vec<LogEntry> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).collect()
+}
+
+fn micros(entry: &LogEntry) -> u64 {
+    entry.timestamp.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64
+}
+
+#[test]
+fn test_messages_convert_losslessly() {
+    let text = "\
+starting up, no timestamp yet
+2024-03-01T12:00:00.250Z INFO  [db] took 42 ms for user u-1043
+2024-03-01T12:00:00,500Z DEBUG [db] cache hit ratio 0.75, size=1024 (max 2048).
+2024-03-01 12:00:01Z WARN  [net] peer 10.0.0.7:8443 retried -3 times at 1.50x, id a1b2
+2024-03-01T12:00:02+01:00 FATAL [app] unexpected {} in template, é9 and 12345678901
+2024-03-01T12:00:03Z ERROR [app] request failed
+    at handler (src/server.rs:210)
+    at main
+2024-03-01T12:00:04Z NOTICE [db] took 7 ms for user u-880
+1709294405.5 wat [db] plain words only
+";
+    let pattern: TextPattern = "{time} {level} [{channel}] {message}".parse().unwrap();
+    let converted = from_text(text.as_bytes(), &pattern).unwrap();
+    let stats = &converted.stats;
+    assert_eq!((stats.lines, stats.records, stats.continuations, stats.skipped), (10, 7, 2, 1));
+    assert_eq!((stats.whole_messages, stats.unknown_levels), (1, 1));
+
+    let entries = entries(&converted.data);
+    let messages: Vec<String> = entries.iter().map(LogEntry::format).collect();
+    let expected: Vec<&str> = text.lines().skip(1)
+        .filter(|line| !line.starts_with("    "))
+        .map(|line| line.split_once("] ").unwrap().1)
+        .collect();
+    assert_eq!(messages[..4], expected[..4]);
+    assert_eq!(messages[4], "request failed\n    at handler (src/server.rs:210)\n    at main");
+    assert_eq!(messages[5..], expected[5..]);
+
+    assert_eq!(entries[0].format_string.as_deref(), Some("took {} ms for user {}"));
+    assert_eq!(entries[5].format_id, entries[0].format_id);
+    assert_eq!(entries[1].format_string.as_deref(), Some("cache hit ratio {}, size={} (max {})."));
+    assert_eq!(micros(&entries[0]), 1_709_294_400_250_000);
+    assert_eq!(micros(&entries[1]), 1_709_294_400_500_000);
+    // 11:00:02 UTC, out of order, keeps the time of the line before it
+    assert_eq!(micros(&entries[3]), micros(&entries[2]));
+    assert_eq!(micros(&entries[6]), 1_709_294_405_500_000);
+
+    let levels: Vec<Option<Level>> = entries.iter().map(|entry| entry.level).collect();
+    assert_eq!(levels, [Some(Level::Info), Some(Level::Debug), Some(Level::Warn), Some(Level::Error), Some(Level::Error), Some(Level::Info), None]);
+    let channels: Vec<Option<&str>> = entries.iter().map(LogEntry::channel_name).collect();
+    assert_eq!(channels, [Some("db"), Some("db"), Some("net"), Some("app"), Some("app"), Some("db"), Some("db")]);
+}
+
+#[test]
+fn test_time_formats_and_zones() {
+    let pattern: TextPattern = "[{time:%d/%m/%Y %H:%M:%S%.f}] {_}: {message}".parse().unwrap();
+    let pattern = pattern.with_time_zone(TimeZone::Fixed(2 * 3600));
+    let text = "[01/03/2024 14:00:00.125] worker-3: job 17 done\n[01/03/2024 14:00:00] worker-1: job 18 done\n";
+    let converted = from_text(text.as_bytes(), &pattern).unwrap();
+    let entries = entries(&converted.data);
+    assert_eq!(entries[0].format(), "job 17 done");
+    assert_eq!(entries[0].timestamp, UNIX_EPOCH + Duration::from_micros(1_709_294_400_125_000));
+    assert_eq!(entries[1].timestamp, entries[0].timestamp);
+
+    for (pattern, error) in [
+        ("{time} {level}", "has no {message}"),
+        ("{level} {message}", "has no {time}"),
+        ("{time} {message} {message}", "appears twice"),
+        ("{time} {thread} {message}", "unknown field {thread}"),
+        ("{time} {message", "unclosed field"),
+    ] {
+        let err = pattern.parse::<TextPattern>().unwrap_err();
+        assert!(err.contains(error), "{}: {}", pattern, err);
+    }
+}
+
+#[test]
+fn test_repeated_messages_share_format_strings() {
+    let mut text = String::new();
+    for i in 0..3000u64 {
+        let time = 1_709_294_400 + i / 10;
+        match i % 3 {
+            0 => text.push_str(&format!("{}.{:03} INFO heartbeat from node-{} ok\n", time, i % 1000, i % 7)),
+            1 => text.push_str(&format!("{}.{:03} INFO order {} filled {} at {}.25\n", time, i % 1000, i, i % 100, 100 + i % 50)),
+            _ => text.push_str(&format!("{}.{:03} WARN queue depth {} over limit\n", time, i % 1000, i * 3)),
+        }
+    }
+    let pattern: TextPattern = "{time} {level} {message}".parse().unwrap();
+    let converted = from_text(text.as_bytes(), &pattern).unwrap();
+    assert_eq!((converted.stats.records, converted.stats.templates), (3000, 3));
+    assert!(converted.data.len() < text.len() * 2 / 3, "{} bytes from {}", converted.data.len(), text.len());
+
+    let converted_text: Vec<String> = entries(&converted.data).iter().map(LogEntry::format).collect();
+    let messages: Vec<&str> = text.lines().map(|line| line.splitn(3, ' ').nth(2).unwrap()).collect();
+    assert_eq!(converted_text, messages);
+}
+
+#[test]
+fn test_conversions_share_their_strings() {
+    let text = "2024-03-01T12:00:00Z INFO [shared-db] reused 5 times\n";
+    let pattern: TextPattern = "{time} {level} [{channel}] {message}".parse().unwrap();
+    let first = from_text(text.as_bytes(), &pattern).unwrap();
+    let second = from_text(text.as_bytes(), &pattern).unwrap();
+
+    // Each conversion counts the format strings it uses, registered once
+    assert_eq!((first.stats.templates, second.stats.templates), (1, 1));
+    let (first, second) = (entries(&first.data), entries(&second.data));
+    assert_eq!((first[0].format_id, first[0].channel), (second[0].format_id, second[0].channel));
+    assert_eq!((second[0].channel_name(), second[0].format()), (Some("shared-db"), "reused 5 times".to_string()));
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, log_count, log_record, register_string, get_string};
+use binary_logger::features::FormatFeatures;
+use binary_logger::inspect::inspect;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn totals(reader: &LogReader) -> BTreeMap<&'static str, u64> {
+    reader.counters().iter().map(|(&id, &total)| (get_string(id).unwrap(), total)).collect()
+}
+
+#[test]
+fn test_counters_are_tabled_per_buffer() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<256>::new(CollectingHandler(data.clone()));
+        for i in 0..100 {
+            for _ in 0..50 {
+                log_count!(logger, "cache_miss").unwrap();
+            }
+            log_count!(logger, "cache_hit").unwrap();
+            log_record!(logger, "Request {} served", i).unwrap();
+        }
+        assert_eq!(logger.stats().records_written, 100);
+        assert!(logger.stats().buffers_flushed > 5);
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let messages: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(messages.len(), 100);
+    assert_eq!(messages[99], "Request 99 served");
+    assert_eq!(totals(&reader), BTreeMap::from([("cache_miss", 5000), ("cache_hit", 100)]));
+    assert_eq!(reader.format_features().map(|features| features.contains(FormatFeatures::COUNTERS)), Some(true));
+
+    let mut dump = Vec::new();
+    inspect(&data, &mut dump).unwrap();
+    assert!(String::from_utf8(dump).unwrap().contains("(counts)"));
+}
+
+#[test]
+fn test_many_counters_and_seeks() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let ids: Vec<u32> = (0..200).map(|i| register_string(format!("counter {}", i).leak())).collect();
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Counting {} counters", ids.len()).unwrap();
+        for round in 0..3 {
+            for &id in &ids {
+                logger.count(id).unwrap();
+            }
+            log_record!(logger, "Round {} done", round).unwrap();
+        }
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let first = reader.read_entry().unwrap().id;
+    while reader.read_entry().is_some() {}
+    // Entries read again don't count their tables twice
+    reader.get(first).unwrap();
+    while reader.read_entry().is_some() {}
+    assert_eq!(reader.counters().len(), 200);
+    assert!(ids.iter().all(|id| reader.counters()[id] == 3));
+}
+
+#[test]
+fn test_counts_without_counters_feature() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::COUNTERS);
+        for _ in 0..3 {
+            log_count!(logger, "cache_miss").unwrap();
+        }
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let messages: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(messages, ["cache_miss"; 3]);
+    assert!(reader.counters().is_empty());
+}
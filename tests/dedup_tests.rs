@@ -0,0 +1,145 @@
+mod common;
+
+use binary_logger::{Logger, LogEntry, log_record, log_record_at, register_string};
+use binary_logger::dedup::DuplicateFilter;
+use binary_logger::instrumentation::InternalEvent;
+use binary_logger::level::Level;
+use binary_logger::LogValue;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use common::{CollectingHandler, read_all};
+
+/// Returns the count and format ID of a summary record.
+fn summary(entry: &LogEntry) -> Option<(i32, u32)> {
+    if entry.format_id != InternalEvent::DuplicatesSuppressed.format_id() {
+        return None;
+    }
+    match entry.parameters[..] {
+        [LogValue::Integer(count), LogValue::Integer(format_id), LogValue::Integer(_)] => Some((count, format_id as u32)),
+        _ => panic!("{:?}", entry.parameters),
+    }
+}
+
+#[test]
+fn test_duplicates_are_summarized_when_their_window_closes() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let failed = register_string("Disk {} failed");
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_duplicate_filter(Some(DuplicateFilter::new().default_window(Duration::from_millis(20))));
+        for _ in 0..5 {
+            log_record_at!(logger, Level::Error, "Disk {} failed", 3).unwrap();
+        }
+        // Other arguments open a window of their own
+        log_record_at!(logger, Level::Error, "Disk {} failed", 4).unwrap();
+        thread::sleep(Duration::from_millis(30));
+
+        // The next record written brings the summary out, and the closed
+        // window lets the same record through again
+        log_record_at!(logger, Level::Info, "Ready {}", 1).unwrap();
+        log_record_at!(logger, Level::Error, "Disk {} failed", 3).unwrap();
+        log_record_at!(logger, Level::Error, "Disk {} failed", 3).unwrap();
+        assert_eq!(logger.stats().duplicates_suppressed, 5);
+        // The rest are written when the logger is dropped
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let lines: Vec<(Option<Level>, String)> = entries.iter().map(|e| (e.level, e.format())).collect();
+    assert_eq!(lines.len(), 6, "{:?}", lines);
+    assert_eq!(lines[0], (Some(Level::Error), "Disk 3 failed".to_string()));
+    assert_eq!(lines[1], (Some(Level::Error), "Disk 4 failed".to_string()));
+    assert_eq!(summary(&entries[2]), Some((4, failed)));
+    assert_eq!(lines[2].0, Some(Level::Error));
+    assert!(lines[2].1.starts_with(&format!("[binary_logger] suppressed 4 duplicates of format {} in ", failed)), "{}", lines[2].1);
+    assert_eq!(lines[3].1, "Ready 1");
+    assert_eq!(lines[4].1, "Disk 3 failed");
+    assert_eq!(summary(&entries[5]), Some((1, failed)));
+}
+
+#[test]
+fn test_windows_are_set_per_format() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<65536>::new(CollectingHandler(data.clone()));
+        let filter = DuplicateFilter::new()
+            .window(register_string("Timeout {}"), Duration::from_secs(60))
+            .exempt(register_string("Served {}"));
+        assert_eq!(filter.window_for(register_string("Timeout {}")), Some(Duration::from_secs(60)));
+        assert_eq!(filter.window_for(register_string("Served {}")), None);
+        assert_eq!(filter.window_for(register_string("Polled {}")), None);
+        logger.set_duplicate_filter(Some(filter.clone().default_window(Duration::from_secs(60))));
+        assert_eq!(logger.duplicate_filter().unwrap().window_for(register_string("Polled {}")), Some(Duration::from_secs(60)));
+
+        for _ in 0..10 {
+            log_record!(logger, "Timeout {}", 1).unwrap();
+            log_record!(logger, "Served {}", 1).unwrap();
+            log_record!(logger, "Polled {}", 1).unwrap();
+        }
+        assert_eq!(logger.stats().duplicates_suppressed, 18);
+
+        // Without a default window, other formats are written
+        logger.set_duplicate_filter(Some(filter));
+        for _ in 0..10 {
+            log_record!(logger, "Polled {}", 1).unwrap();
+        }
+        logger.set_duplicate_filter(None);
+        for _ in 0..10 {
+            log_record!(logger, "Timeout {}", 1).unwrap();
+        }
+        assert_eq!(logger.stats().duplicates_suppressed, 18);
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let count = |format: &str| entries.iter().filter(|entry| entry.format() == format).count();
+    assert_eq!(count("Timeout 1"), 11);
+    assert_eq!(count("Served 1"), 10);
+    assert_eq!(count("Polled 1"), 11);
+    // Replacing the filter wrote the counts it held, in format ID order
+    let summaries: Vec<(i32, u32)> = entries.iter().filter_map(summary).collect();
+    let mut expected = [(9, register_string("Timeout {}")), (9, register_string("Polled {}"))];
+    expected.sort_unstable_by_key(|&(_, format_id)| format_id);
+    assert_eq!(summaries, expected);
+}
+
+#[test]
+fn test_flush_writes_counts_of_open_windows() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let format_id = register_string("Queue {} full");
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_duplicate_filter(Some(DuplicateFilter::new().default_window(Duration::from_secs(60))));
+        let value = 7u32.to_le_bytes();
+        for _ in 0..3 {
+            logger.write_args_on(2, format_id, &[&value]).unwrap();
+            logger.write_args_on(5, format_id, &[&value]).unwrap();
+        }
+        logger.flush();
+        // The window stays open, and counts anew
+        logger.write_args_on(2, format_id, &[&value]).unwrap();
+        assert_eq!(logger.stats().duplicates_suppressed, 5);
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let read: Vec<(u32, Option<(i32, u32)>)> = entries.iter().map(|entry| (entry.channel, summary(entry))).collect();
+    assert_eq!(read, [(2, None), (5, None), (2, Some((2, format_id))), (5, Some((2, format_id))), (2, Some((1, format_id)))]);
+}
+
+#[test]
+fn test_records_without_room_for_a_window_are_written() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_duplicate_filter(Some(DuplicateFilter::new().default_window(Duration::from_secs(60)).max_entries(2)));
+        for _ in 0..3 {
+            for user in 0..3 {
+                log_record!(logger, "Login of {} refused", user).unwrap();
+            }
+        }
+        assert_eq!(logger.stats().duplicates_suppressed, 4);
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let lines: Vec<String> = entries.iter().filter(|entry| summary(entry).is_none()).map(LogEntry::format).collect();
+    assert_eq!(lines, ["Login of 0 refused", "Login of 1 refused", "Login of 2 refused", "Login of 2 refused", "Login of 2 refused"]);
+}
@@ -0,0 +1,119 @@
+use binary_logger::{Logger, BufferHandler, LogReader, log_record, register_string};
+use binary_logger::audit::verify_chain;
+use binary_logger::features::FormatFeatures;
+use binary_logger::inspect::inspect;
+use binary_logger::log_reader::is_metadata_buffer;
+use std::sync::{Arc, Mutex};
+
+/// Keeps every buffer handed over, in order.
+struct BufferList(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl BufferHandler for BufferList {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().push(data.to_vec());
+    }
+}
+
+#[test]
+fn test_metadata_buffers_are_published_eagerly() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    let mut logger = Logger::<4096>::new(BufferList(buffers.clone()));
+    logger.set_dictionary_channel(true);
+
+    log_record!(logger, "Service {} started", 1).unwrap();
+    assert_eq!(buffers.lock().unwrap().len(), 1);
+    log_record!(logger, "Service {} started", 2).unwrap();
+    assert_eq!(buffers.lock().unwrap().len(), 1);
+
+    // A string registered at runtime is published before the next record
+    let name = register_string("dictionary channel test string");
+    logger.write_args(name, &[]).unwrap();
+    assert_eq!(buffers.lock().unwrap().len(), 2);
+    logger.flush();
+    drop(logger);
+
+    let buffers = buffers.lock().unwrap();
+    let kinds: Vec<bool> = buffers.iter().map(|buffer| is_metadata_buffer(buffer)).collect();
+    assert_eq!(kinds, [true, true, false]);
+    let mut dump = Vec::new();
+    inspect(&buffers[1], &mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert!(dump.contains("(metadata)"), "{}", dump);
+    assert!(dump.contains(&format!("string {}: \"dictionary channel test string\"", name)), "{}", dump);
+
+    let log = buffers.concat();
+    let mut reader = LogReader::new(&log);
+    let messages: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(messages, ["Service 1 started", "Service 2 started", "dictionary channel test string"]);
+    assert_eq!(reader.format_features().map(|features| features.contains(FormatFeatures::DICTIONARY_CHANNEL)), Some(true));
+}
+
+#[test]
+fn test_attach_mid_stream() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<512>::new(BufferList(buffers.clone()));
+        logger.set_dictionary_channel(true);
+        for i in 0..300u32 {
+            log_record!(logger, "Request {} took {} ms", i, i as f64 / 4.0).unwrap();
+            if i % 50 == 0 {
+                register_string(format!("attach test string {}", i).leak());
+            }
+        }
+    }
+
+    let buffers = buffers.lock().unwrap();
+    assert!(buffers.iter().filter(|buffer| is_metadata_buffer(buffer)).count() >= 6);
+    let log = buffers.concat();
+    let mut full = LogReader::new(&log);
+    let all: Vec<_> = std::iter::from_fn(|| full.read_entry()).map(|entry| (entry.timestamp, entry.format())).collect();
+    assert_eq!(all.len(), 300);
+
+    // Attach at every data buffer past the first, as a live tail would
+    let mut offset = 0;
+    for buffer in buffers.iter() {
+        if !is_metadata_buffer(buffer) && offset > 0 {
+            let mut reader = LogReader::attach(&log, offset);
+            assert_eq!(reader.format_features().map(|features| features.contains(FormatFeatures::DICTIONARY_CHANNEL)), Some(true));
+            let tail: Vec<_> = std::iter::from_fn(|| reader.read_entry()).map(|entry| (entry.timestamp, entry.format())).collect();
+            assert!(!tail.is_empty());
+            assert_eq!(tail[..], all[all.len() - tail.len()..]);
+        }
+        offset += buffer.len();
+    }
+}
+
+#[test]
+fn test_audit_chain_skips_metadata_buffers() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<512>::new(BufferList(buffers.clone()));
+        logger.set_audit_chain(true);
+        logger.set_dictionary_channel(true);
+        for i in 0..100u32 {
+            log_record!(logger, "Audited {}", i).unwrap();
+            if i % 30 == 0 {
+                register_string(format!("audit channel test string {}", i).leak());
+            }
+        }
+    }
+
+    let buffers = buffers.lock().unwrap();
+    let data_buffers = buffers.iter().filter(|buffer| !is_metadata_buffer(buffer)).count();
+    let report = verify_chain(&buffers.concat(), None).unwrap();
+    assert_eq!(report.buffers, data_buffers);
+}
+
+#[test]
+fn test_channel_needs_its_feature() {
+    let buffers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(BufferList(buffers.clone()));
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::DICTIONARY_CHANNEL);
+        logger.set_dictionary_channel(true);
+        log_record!(logger, "Plain stream {}", 1).unwrap();
+    }
+    let buffers = buffers.lock().unwrap();
+    assert_eq!(buffers.len(), 1);
+    assert!(!is_metadata_buffer(&buffers[0]));
+}
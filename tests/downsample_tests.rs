@@ -0,0 +1,77 @@
+mod common;
+
+use binary_logger::{Logger, log_record, log_record_at};
+use binary_logger::downsample::Downsampler;
+use binary_logger::level::Level;
+use binary_logger::fixed::Fixed;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use common::CollectingHandler;
+
+fn write_service_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        // Small buffers, so the log spans many of them
+        let mut logger = Logger::<512>::new(CollectingHandler(data.clone()));
+        for i in 1..=200u32 {
+            log_record!(logger, "Downsampled request took {} ms", i as f64).unwrap();
+            log_record!(logger, "Downsampled price {}", Fixed::<2>(i as i64)).unwrap();
+            if i % 20 == 0 {
+                log_record_at!(logger, Level::Error, "Downsampled failure {}", i).unwrap();
+            }
+        }
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+#[test]
+fn test_downsample_aggregates() {
+    let data = write_service_log();
+    // Large enough for a single bucket
+    let day = Duration::from_secs(86400);
+    let series = Downsampler::new(day).with_value("Downsampled request took {} ms", 0).run(&data);
+    let count: u64 = series.buckets.iter().map(|bucket| bucket.count).sum();
+    let errors: u64 = series.buckets.iter().map(|bucket| bucket.errors).sum();
+    assert_eq!((count, errors), (410, 10));
+    let busiest = series.buckets.iter().max_by_key(|bucket| bucket.samples).unwrap();
+    if series.buckets.len() == 1 {
+        assert_eq!(busiest.samples, 200);
+        assert_eq!(busiest.percentile, Some(198.0));
+    }
+
+    // Other percentiles, and values from decimals
+    let series = Downsampler::new(day).with_value("Downsampled price {}", 0).with_quantile(1.0).run(&data);
+    let max = series.buckets.iter().filter_map(|bucket| bucket.percentile).fold(f64::MIN, f64::max);
+    assert_eq!(max, 2.0);
+    assert_eq!(series.percentile_name(), "p100");
+    assert_eq!(Downsampler::new(day).with_quantile(0.999).run(&[]).percentile_name(), "p99.9");
+}
+
+#[test]
+fn test_downsample_buckets_and_output() {
+    let data = write_service_log();
+    let interval = Duration::from_micros(7);
+    let series = Downsampler::new(interval).with_value("Downsampled request took {} ms", 0).run(&data);
+    let count: u64 = series.buckets.iter().map(|bucket| bucket.count).sum();
+    assert_eq!(count, 410);
+
+    // Consecutive aligned intervals, empty ones included
+    for pair in series.buckets.windows(2) {
+        assert_eq!(pair[1].start.duration_since(pair[0].start).unwrap(), interval);
+    }
+    let micros = series.buckets[0].start.duration_since(UNIX_EPOCH).unwrap().as_micros();
+    assert_eq!(micros % 7, 0);
+
+    let csv = series.to_csv();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("time,count,errors,samples,p99"));
+    assert_eq!(lines.count(), series.buckets.len());
+
+    let json = series.to_grafana_json();
+    assert!(json.starts_with("[{\"target\":\"count\",\"datapoints\":[["), "{}", json);
+    assert!(json.contains("{\"target\":\"errors\""));
+    assert!(json.contains("{\"target\":\"p99\""));
+
+    assert!(Downsampler::new(interval).run(&[]).buckets.is_empty());
+}
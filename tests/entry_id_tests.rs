@@ -0,0 +1,64 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, EntryId, log_record};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn write_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<256>::new(CollectingHandler(data.clone()));
+        logger.set_global_sequence(true);
+        for i in 0..60 {
+            log_record!(logger, "Entry id test {}", i).unwrap();
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+fn same_entry(a: &LogEntry, b: &LogEntry) -> bool {
+    a.id == b.id && a.timestamp == b.timestamp && a.sequence == b.sequence && a.raw_values == b.raw_values
+}
+
+#[test]
+fn test_entry_ids_are_unique_and_ordered() {
+    let data = write_log();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    assert_eq!(entries.len(), 60);
+    assert!(entries.windows(2).all(|w| w[0].id < w[1].id));
+    assert!(entries.iter().any(|e| e.id.buffer > 0), "Small buffers should spread entries over several buffers");
+    assert_eq!(entries[0].id.to_string(), format!("0:0:{}", entries[0].id.offset));
+
+    let mut reader = LogReader::new(&data);
+    reader.set_file_index(3);
+    assert_eq!(reader.read_entry().unwrap().id.file, 3);
+}
+
+#[test]
+fn test_get_by_entry_id() {
+    let data = write_log();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    // A fresh reader finds entries in buffers it hasn't read yet, in any order
+    let mut reader = LogReader::new(&data);
+    for entry in entries.iter().rev() {
+        let found = reader.get(entry.id).unwrap();
+        assert!(same_entry(&found, entry), "{} differs", entry.id);
+    }
+
+    // Sequential reading is unaffected by lookups
+    let first = reader.read_entry().unwrap();
+    assert!(same_entry(&first, &entries[0]));
+    let _ = reader.get(entries[40].id);
+    assert!(same_entry(&reader.read_entry().unwrap(), &entries[1]));
+
+    // IDs that don't point at an entry
+    let id = entries[5].id;
+    assert!(reader.get(EntryId { offset: id.offset + 1, ..id }).is_none());
+    assert!(reader.get(EntryId { file: 1, ..id }).is_none());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_error, log_record};
+use binary_logger::error_chain::ErrorChain;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[derive(Debug)]
+struct Layer {
+    message: &'static str,
+    source: Option<Box<Layer>>,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl Error for Layer {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+fn chain(messages: &[&'static str]) -> Layer {
+    let mut layer = None;
+    for message in messages.iter().rev() {
+        layer = Some(Layer { message, source: layer.map(Box::new) });
+    }
+    layer.unwrap()
+}
+
+#[test]
+fn test_error_chain_round_trip() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let err = chain(&["connection lost", "read timed out", "os error 110"]);
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_error!(logger, "operation failed: {}", err).unwrap();
+        log_error!(logger, channel: "net", "{} after {} retries", chain(&["refused"]), 3).unwrap();
+        log_record!(logger, "Request {} failed: {}", 12, ErrorChain::new(&err)).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    match &entries[0].parameters[0] {
+        LogValue::ErrorChain(messages) => {
+            assert_eq!(messages, &["connection lost", "read timed out", "os error 110"]);
+        }
+        other => panic!("expected an error chain, got {:?}", other),
+    }
+    assert_eq!(
+        entries[0].format(),
+        "operation failed: connection lost\n  caused by: read timed out\n    caused by: os error 110"
+    );
+    assert_eq!(entries[1].format(), "refused after 3 retries");
+    assert_eq!(entries[1].channel_name(), Some("net"));
+    assert!(entries[2].format().starts_with("Request 12 failed: connection lost\n"));
+}
+
+#[test]
+fn test_error_chain_limits() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let long = "é".repeat(200);
+    let long: &'static str = Box::leak(long.into_boxed_str());
+    let deep: Vec<&'static str> = std::iter::once(long).chain(std::iter::repeat_n("cause", 20)).collect();
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_error!(logger, "deep: {}", chain(&deep)).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entry = reader.read_entry().unwrap();
+
+    match &entry.parameters[0] {
+        LogValue::ErrorChain(messages) => {
+            assert_eq!(messages.len(), 8);
+            assert_eq!(messages[0], "é".repeat(128));
+        }
+        other => panic!("expected an error chain, got {:?}", other),
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use binary_logger::{BinaryLog, LogValue, get_string, init_global};
+use binary_logger::level::Level;
+use binary_logger::pool::LoggerPool;
+use log::{Log, Record};
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+#[test]
+fn test_records_keep_level_target_and_message() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let backend = BinaryLog::<4096>::new(CollectingHandler(data.clone()));
+    let (ms, replica) = (42, String::from("replica"));
+    backend.log(&Record::builder().args(format_args!("Cache warmed")).level(log::Level::Info).target("app::cache").build());
+    backend.log(&Record::builder().args(format_args!("Query took {} ms on {}", ms, replica)).level(log::Level::Warn).target("app::db").build());
+    backend.log(&Record::builder().args(format_args!("Template {{}} unused")).level(log::Level::Error).target("app::cache").build());
+    backend.flush();
+
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries.len(), 3);
+
+    // Literal messages are interned as the format string
+    assert_eq!(entries[0].format_string.as_deref(), Some("Cache warmed"));
+    assert!(entries[0].parameters.is_empty());
+    assert_eq!(entries[0].level, Some(Level::Info));
+    assert_eq!(get_string(entries[0].channel), Some("app::cache"));
+
+    // Others are stored whole
+    assert_eq!(entries[1].format(), "Query took 42 ms on replica");
+    assert!(matches!(&entries[1].parameters[0], LogValue::String(s) if s == "Query took 42 ms on replica"));
+    assert_eq!(entries[1].level, Some(Level::Warn));
+    assert_eq!(get_string(entries[1].channel), Some("app::db"));
+
+    // A literal that would read as a placeholder is too
+    assert_eq!(entries[2].format(), "Template {} unused");
+    assert_eq!(entries[2].channel, entries[0].channel);
+}
+
+#[test]
+fn test_logger_level_applies() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let backend = BinaryLog::<4096>::new(CollectingHandler(data.clone()));
+    backend.with(|logger| logger.set_level(Level::Warn));
+    let info = Record::builder().args(format_args!("Polling")).level(log::Level::Info).target("app").build();
+    assert!(!backend.enabled(info.metadata()));
+    backend.log(&info);
+    let pct = 91;
+    backend.log(&Record::builder().args(format_args!("Disk {}% full", pct)).level(log::Level::Warn).target("app").build());
+    backend.flush();
+
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].format(), "Disk 91% full");
+}
+
+#[test]
+fn test_pool_backend_uses_a_logger_per_thread() {
+    let handlers = Arc::new(Mutex::new(Vec::new()));
+    let pool = {
+        let handlers = handlers.clone();
+        LoggerPool::<4096>::new(move || {
+            let handler = CollectingHandler(Arc::new(Mutex::new(Vec::new())));
+            handlers.lock().unwrap().push(handler.clone());
+            handler
+        })
+    };
+    let backend = Arc::new(BinaryLog::from_pool(pool));
+    let threads: Vec<_> = (0..3).map(|i| {
+        let backend = backend.clone();
+        std::thread::spawn(move || {
+            backend.log(&Record::builder().args(format_args!("Worker {} done", i)).level(log::Level::Info).target("app").build());
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Each thread's Logger wrote its last buffer as the thread exited
+    let handlers = handlers.lock().unwrap();
+    assert_eq!(handlers.len(), 3);
+    let mut lines: Vec<_> = handlers.iter().flat_map(|handler| read_all(&handler.0.lock().unwrap())).map(|entry| entry.format()).collect();
+    lines.sort();
+    assert_eq!(lines, ["Worker 0 done", "Worker 1 done", "Worker 2 done"]);
+}
+
+#[test]
+fn test_init_global() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    init_global(CollectingHandler(data.clone())).unwrap();
+    let invoice = 7001;
+    log::info!(target: "billing", "Invoice {} sent", invoice);
+    log::debug!("Retrying");
+    log::logger().flush();
+
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].format(), "Invoice 7001 sent");
+    assert_eq!(get_string(entries[0].channel), Some("billing"));
+    assert_eq!(entries[1].format(), "Retrying");
+    assert_eq!(entries[1].level, Some(Level::Debug));
+    assert_eq!(get_string(entries[1].channel), Some(module_path!()));
+
+    // There is only one global logger
+    assert!(init_global(CollectingHandler(data.clone())).is_err());
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, log_record, log_record_at, log_tagged, RECORD_TYPE_USER_MIN};
+use binary_logger::features::FormatFeatures;
+use binary_logger::inspect::inspect;
+use binary_logger::level::Level;
+use binary_logger::tags::{register_tag, Tags};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn write_log(configure: impl FnOnce(&mut Logger<4096>)) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        configure(&mut logger);
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+fn read_all(data: &[u8]) -> (Vec<LogEntry>, Option<FormatFeatures>) {
+    let mut reader = LogReader::new(data);
+    let entries = std::iter::from_fn(|| reader.read_entry()).collect();
+    (entries, reader.format_features())
+}
+
+#[test]
+fn test_declared_features() {
+    let data = write_log(|logger| {
+        log_record!(logger, "Started worker {}", 1).unwrap();
+    });
+    let (entries, features) = read_all(&data);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(features, Some(FormatFeatures::SUPPORTED));
+
+    let data = write_log(|logger| {
+        logger.set_format_features(FormatFeatures::DELTA_TIME | FormatFeatures::LEVELS);
+        logger.set_delta_timestamps(true);
+        log_record!(logger, "Started worker {}", 1).unwrap();
+        // The declared set can't grow
+        logger.set_format_features(FormatFeatures::SUPPORTED);
+        logger.set_global_sequence(true);
+        log_record!(logger, "Started worker {}", 2).unwrap();
+        assert_eq!(logger.format_features(), FormatFeatures::DELTA_TIME | FormatFeatures::LEVELS);
+    });
+    let (entries, features) = read_all(&data);
+    assert_eq!(features, Some(FormatFeatures::DELTA_TIME | FormatFeatures::LEVELS));
+    assert!(features.unwrap().unsupported().is_empty());
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.sequence.is_none()));
+}
+
+#[test]
+fn test_feature_subset() {
+    let tag = register_tag("subset");
+    let data = write_log(|logger| {
+        logger.set_format_features(FormatFeatures::NONE);
+        logger.set_header_compression(true);
+        logger.set_audit_chain(true);
+        logger.set_timestamps(false);
+        log_record_at!(logger, Level::Warn, "Disk {} almost full", 3).unwrap();
+        log_tagged!(logger, tags = tag, "Tagged {}", 4).unwrap();
+        log_record!(logger, "Long {}", "x".repeat(1000)).unwrap();
+        let error = logger.write_custom(RECORD_TYPE_USER_MIN, &[1, 2]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(logger.format_features(), FormatFeatures::NONE);
+        assert_eq!(logger.chain_head(), None);
+    });
+    let (entries, features) = read_all(&data);
+    assert_eq!(features, Some(FormatFeatures::NONE));
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].level, None);
+    assert_eq!(entries[1].tags, Tags::NONE);
+    assert!(!entries[2].truncated);
+    assert!(entries.iter().all(|entry| !entry.untimed));
+}
+
+#[test]
+fn test_old_and_newer_headers() {
+    let data = write_log(|logger| {
+        log_record!(logger, "Request {} served", 17).unwrap();
+    });
+    // Buffer header, then the stream header record: type, flags, time,
+    // format ID, payload length, and the payload from byte 16
+    assert_eq!(u16::from_le_bytes([data[14], data[15]]), 10);
+
+    // A header from before the feature set
+    let mut old = data.clone();
+    old.drain(22..26);
+    old[14..16].copy_from_slice(&6u16.to_le_bytes());
+    let size = u64::from_le_bytes(old[..8].try_into().unwrap()) - 4;
+    old[..8].copy_from_slice(&size.to_le_bytes());
+    let (entries, features) = read_all(&old);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(features, None);
+
+    // A header declaring a feature from a newer writer
+    let mut newer = data.clone();
+    let bits = FormatFeatures::SUPPORTED.bits() | 1 << 30;
+    newer[22..26].copy_from_slice(&bits.to_le_bytes());
+    let (entries, features) = read_all(&newer);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(features.unwrap().unsupported(), FormatFeatures::from_bits(1 << 30));
+
+    let mut out = Vec::new();
+    inspect(&newer, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("features=sequence|channels"), "{}", text);
+    assert!(text.contains("UNSUPPORTED 0x40000000"), "{}", text);
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_record};
+use binary_logger::fixed::Fixed;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[test]
+fn test_fixed_round_trip() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Order {} filled at {} for {}", 17, Fixed::<4>(1_234_500), Fixed::<2>(-5)).unwrap();
+        log_record!(logger, "Fixed lots {} vs float {}", Fixed::<0>(42), 0.1).unwrap();
+        log_record!(logger, "Fixed extreme {}", Fixed::<18>(i64::MIN)).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    assert_eq!(entries[0].format(), "Order 17 filled at 123.4500 for -0.05");
+    assert!(matches!(entries[0].parameters[1], LogValue::Fixed { value: 1_234_500, scale: 4 }));
+    assert_eq!(entries[1].format(), "Fixed lots 42 vs float 0.1");
+    assert!(matches!(entries[1].parameters[1], LogValue::Float(_)));
+    assert_eq!(entries[2].format(), "Fixed extreme -9.223372036854775808");
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_record};
+use binary_logger::flags::{Flags, FlagNames, FlagField};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+static VALVE_STATUS: FlagNames = FlagNames::new("open,fault,mode:3");
+
+#[test]
+fn test_flags_round_trip() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Valve status {}", Flags::new(1u32 | 5 << 2, &VALVE_STATUS)).unwrap();
+        log_record!(logger, "Valve status {}", Flags::new(0u8, &VALVE_STATUS)).unwrap();
+        log_record!(logger, "Valve status {} count {}", Flags::new(2u32 | 1 << 20, &VALVE_STATUS), 7).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    assert_eq!(entries[0].format(), "Valve status open|mode=5");
+    match &entries[0].parameters[0] {
+        LogValue::Flags(fields) => assert_eq!(fields, &[
+            FlagField { name: "open".to_string(), width: 1, value: 1 },
+            FlagField { name: "fault".to_string(), width: 1, value: 0 },
+            FlagField { name: "mode".to_string(), width: 3, value: 5 },
+        ]),
+        other => panic!("expected flags, got {:?}", other),
+    }
+    assert_eq!(entries[1].format(), "Valve status (none)");
+
+    // Bits beyond the names are kept, and other arguments still decode
+    assert_eq!(entries[2].format(), "Valve status fault|0x8000 count 7");
+}
+
+#[test]
+fn test_flag_names_come_from_the_embedded_dictionary() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_embedded_dictionary(true);
+        log_record!(logger, "Valve status {}", Flags::new(1u32 | 5 << 2, &VALVE_STATUS)).unwrap();
+    }
+    let mut data = data.lock().unwrap().clone();
+
+    // Edit the dictionary copy of the names, as if the log came from
+    // another process
+    let needle = b"open,fault,mode:3";
+    let at = data.windows(needle.len()).position(|window| window == needle).unwrap();
+    data[at..at + needle.len()].copy_from_slice(b"shut,alarm,gear:3");
+
+    let mut reader = LogReader::new(&data);
+    assert_eq!(reader.read_entry().unwrap().format(), "Valve status shut|gear=5");
+}
+
+#[test]
+fn test_flags_encoding_size() {
+    let small = Flags::new(0x7Fu32, &VALVE_STATUS);
+    assert_eq!(small.as_bytes().len(), 3);
+    let large = Flags::new(u32::MAX, &VALVE_STATUS);
+    assert_eq!(large.as_bytes().len(), 7);
+    assert_eq!(&small.as_bytes()[..2], &(VALVE_STATUS.id() as u16).to_le_bytes());
+}
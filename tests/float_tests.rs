@@ -0,0 +1,114 @@
+mod common;
+
+use binary_logger::{Logger, LogValue, log_record, register_string};
+use binary_logger::arg_types::TypeTag;
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+#[test]
+fn test_f32_arguments() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        let reading: f32 = 0.1;
+        log_record!(logger, "Sensor reading {} at {} C", reading, 21.5f32).unwrap();
+        log_record!(logger, "Sensor count {}", 7).unwrap();
+        log_record!(logger, channel: "sensor-float", "Sensor level {}", -3.25f32).unwrap();
+        logger.flush();
+    }
+    let entries = read_all(&data.lock().unwrap());
+
+    // f32 keeps its own precision rather than that of a widened f64
+    assert_eq!(entries[0].format(), "Sensor reading 0.1 at 21.5 C");
+    assert!(matches!(entries[0].parameters[0], LogValue::Float32(v) if v == 0.1));
+    assert!(matches!(entries[1].parameters[0], LogValue::Integer(7)));
+    assert_eq!(entries[2].format(), "Sensor level -3.25");
+
+    // 4 bytes per reading instead of 8: the count, then the size and bytes of each
+    assert_eq!(entries[0].raw_values.len(), 1 + 2 * (4 + 4));
+}
+
+#[cfg(feature = "f16")]
+#[test]
+fn test_f16_arguments() {
+    use half::f16;
+
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Half reading {} and {}", f16::from_f32(0.1), f16::from_f32(1000.0)).unwrap();
+        logger.flush();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries[0].format(), "Half reading 0.1 and 1000");
+    assert_eq!(entries[0].parameters[0].as_f16(), Some(f16::from_f32(0.1)));
+
+    // Every half converts and displays as `half` would have it
+    for bits in 0..=u16::MAX {
+        let (value, expected) = (LogValue::Float16(bits), f16::from_bits(bits));
+        let wide = value.as_f64().unwrap();
+        assert!(wide == expected.to_f64() || wide.is_nan() && expected.is_nan(), "{:#x}", bits);
+        let text = value.to_string();
+        if expected.is_finite() {
+            assert_eq!(f16::from_f32(text.parse().unwrap()).to_bits(), bits, "{:#x} displayed as {}", bits, text);
+        }
+    }
+}
+
+#[test]
+fn test_f16_arguments_decode_in_every_build() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        let format_id = register_string("Half reading {} and {}");
+        // 1.5 and -0.1 as half-precision bits, as a writer with `f16` logs them
+        let (first, second) = (0x3E00u16.to_le_bytes(), 0xAE66u16.to_le_bytes());
+        logger.write_typed_args(None, 0, format_id, &[&first, &second], &[TypeTag::F16, TypeTag::F16]).unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert!(matches!(entries[0].parameters[0], LogValue::Float16(0x3E00)));
+    assert_eq!(entries[0].parameters[0].as_f64(), Some(1.5));
+    assert_eq!(entries[0].format(), "Half reading 1.5 and -0.1");
+}
+
+#[test]
+fn test_float_display_matches_std() {
+    let mut f64s = vec![0.0, -0.0, 1.0, -1.0, 0.1, 25.5, 1e15, 1e16, 1e17, 1e-5, 1e-6, 1e-7, 123456.789,
+        f64::MAX, f64::MIN_POSITIVE, 5e-324, f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+    let mut f32s = vec![0.0, -0.0, 1.0, 0.1, 21.5, 1e-7, 3.4e38, f32::MIN_POSITIVE, 1e-45, f32::NAN, f32::NEG_INFINITY];
+    for exp in -320..=308 {
+        f64s.push(10f64.powi(exp));
+        f64s.push(-1.5 * 10f64.powi(exp));
+    }
+
+    // Arbitrary bit patterns cover mantissas with every digit count
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    for _ in 0..20_000 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        f64s.push(f64::from_bits(state));
+        f32s.push(f32::from_bits(state as u32));
+    }
+
+    // Large values with few binary fraction digits can fall exactly between
+    // two shortest candidates
+    for fraction_bits in 1..=8 {
+        let scale = (1u32 << fraction_bits) as f64;
+        for i in 0..4_000u64 {
+            let numerator = (1u64 << 53) - 1 - i * 7919;
+            f64s.push(numerator as f64 / scale);
+            f32s.push(((1u32 << 24) - 1 - i as u32 * 31) as f32 / scale as f32);
+        }
+    }
+
+    for value in f64s {
+        assert_eq!(LogValue::Float(value).to_string(), value.to_string(), "bits {:x}", value.to_bits());
+    }
+    for value in f32s {
+        assert_eq!(LogValue::Float32(value).to_string(), value.to_string(), "bits {:x}", value.to_bits());
+    }
+    for value in [0, 7, -1, i32::MIN, i32::MAX] {
+        assert_eq!(LogValue::Integer(value).to_string(), value.to_string());
+    }
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_record};
+use binary_logger::instrumentation::InternalEvent;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[test]
+fn test_post_fork_starts_a_new_stream() {
+    let parent = Arc::new(Mutex::new(Vec::new()));
+    let child = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(parent.clone()));
+        logger.set_global_sequence(true);
+        log_record!(logger, "Parent record {}", 1).unwrap();
+        log_record!(logger, "Parent record {}", 2).unwrap();
+
+        // As if in the child: the inherited records belong to the parent
+        logger.post_fork(CollectingHandler(child.clone()));
+        assert_eq!(logger.stats().records_written, 0);
+        assert_eq!(logger.stats().bytes_flushed, 0);
+        log_record!(logger, "Child record {}", 3).unwrap();
+        assert_eq!(logger.stats().records_written, 1);
+    }
+    assert!(parent.lock().unwrap().is_empty());
+
+    let data = child.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let forked = reader.read_entry().unwrap();
+    assert!(forked.is_internal());
+    assert_eq!(forked.format_id, InternalEvent::Forked.format_id());
+    let pid = std::process::id() as i32;
+    assert!(matches!(forked.parameters[..], [LogValue::Integer(child), LogValue::Integer(parent)] if child == pid && parent == pid));
+    assert_eq!(forked.sequence, Some(0));
+
+    let entry = reader.read_entry().unwrap();
+    assert_eq!(entry.format(), "Child record 3");
+    assert_eq!(entry.sequence, Some(1));
+    assert!(entry.timestamp >= forked.timestamp);
+    assert!(reader.read_entry().is_none());
+    assert!(reader.format_features().is_some());
+}
@@ -0,0 +1,79 @@
+use binary_logger::{Logger, BufferHandler, LogReader, HandlerPanicPolicy, log_record};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Panics on its first `failures` buffers, then collects them.
+struct FlakyHandler {
+    failures: usize,
+    calls: Arc<AtomicUsize>,
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl BufferHandler for FlakyHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        if self.calls.fetch_add(1, Ordering::Relaxed) < self.failures {
+            panic!("sink unavailable");
+        }
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+fn flaky_logger(failures: usize, policy: HandlerPanicPolicy) -> (Logger<4096>, Arc<AtomicUsize>, Arc<Mutex<Vec<u8>>>) {
+    let (calls, data) = (Arc::new(AtomicUsize::new(0)), Arc::new(Mutex::new(Vec::new())));
+    let mut logger = Logger::<4096>::new(FlakyHandler { failures, calls: calls.clone(), data: data.clone() });
+    logger.set_handler_panic_policy(policy);
+    (logger, calls, data)
+}
+
+fn messages(data: &[u8]) -> Vec<String> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect()
+}
+
+#[test]
+fn test_propagate_leaves_logger_usable() {
+    let (mut logger, _calls, data) = flaky_logger(1, HandlerPanicPolicy::Propagate);
+    assert_eq!(logger.handler_panic_policy(), HandlerPanicPolicy::Propagate);
+    log_record!(logger, "Lost record {}", 1).unwrap();
+    let panic = catch_unwind(AssertUnwindSafe(|| logger.flush())).unwrap_err();
+    assert_eq!(panic.downcast_ref::<&str>(), Some(&"sink unavailable"));
+
+    let status = logger.handler_status();
+    assert_eq!((status.panics, status.buffers_lost, status.disabled), (1, 1, false));
+    assert_eq!(status.last_panic.as_deref(), Some("sink unavailable"));
+    assert_eq!(logger.stats().bytes_pending, 0);
+
+    log_record!(logger, "Kept record {}", 2).unwrap();
+    logger.flush();
+    assert_eq!(messages(&data.lock().unwrap()), ["Kept record 2"]);
+}
+
+#[test]
+fn test_retry_calls_handler_with_next_buffer() {
+    let (mut logger, calls, data) = flaky_logger(2, HandlerPanicPolicy::Retry);
+    for i in 0..3 {
+        log_record!(logger, "Record {}", i).unwrap();
+        logger.flush();
+    }
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+    let status = logger.handler_status();
+    assert_eq!((status.panics, status.buffers_lost, status.disabled), (2, 2, false));
+    assert_eq!(logger.stats().buffers_flushed, 3);
+    assert_eq!(messages(&data.lock().unwrap()), ["Record 2"]);
+}
+
+#[test]
+fn test_disable_stops_calling_handler() {
+    let (mut logger, calls, data) = flaky_logger(1, HandlerPanicPolicy::Disable);
+    for i in 0..3 {
+        log_record!(logger, "Record {}", i).unwrap();
+        logger.flush();
+    }
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    let status = logger.handler_status().clone();
+    assert_eq!((status.panics, status.buffers_lost, status.disabled), (1, 3, true));
+    assert_eq!(logger.stats().buffers_flushed, 1);
+    drop(logger);
+    assert!(data.lock().unwrap().is_empty());
+}
@@ -0,0 +1,392 @@
+mod common;
+
+use binary_logger::{Logger, BufferHandler, HandlerPanicPolicy, LogReader, LogValue, log_record};
+use binary_logger::buffer_pool::LogBuffer;
+use binary_logger::handlers::{AsyncFileHandler, Backpressure, BackgroundWriter, FanOut};
+use binary_logger::instrumentation::InternalEvent;
+#[cfg(target_os = "linux")]
+use binary_logger::scheduling::ThreadScheduling;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use common::CollectingHandler;
+
+/// A sink failing on every other buffer.
+struct FlakyHandler(Arc<Mutex<usize>>);
+
+impl BufferHandler for FlakyHandler {
+    fn handle_buffer(&self, _data: &[u8]) {
+        let mut calls = self.0.lock().unwrap();
+        *calls += 1;
+        if *calls % 2 == 1 {
+            drop(calls);
+            panic!("sink unavailable");
+        }
+    }
+}
+
+#[test]
+fn test_fan_out_isolates_failing_sinks() {
+    let first = Arc::new(Mutex::new(Vec::new()));
+    let second = Arc::new(Mutex::new(Vec::new()));
+    let calls = Arc::new(Mutex::new(0));
+    {
+        let handler = FanOut(vec![
+            Box::new(CollectingHandler(first.clone())),
+            Box::new(FlakyHandler(calls.clone())),
+            Box::new(CollectingHandler(second.clone())),
+        ]);
+        let mut logger = Logger::<256>::new(handler);
+        for i in 0..50 {
+            log_record!(logger, "Fan out {}", i).unwrap();
+        }
+        logger.flush();
+    }
+
+    // Every sink saw every buffer, the failing one included
+    let first = first.lock().unwrap();
+    assert_eq!(*first, *second.lock().unwrap());
+    let mut reader = LogReader::new(&first);
+    assert_eq!(std::iter::from_fn(|| reader.read_entry()).count(), 50);
+    let (mut pos, mut buffers) = (0, 0);
+    while pos < first.len() {
+        pos += u64::from_le_bytes(first[pos..pos + 8].try_into().unwrap()) as usize;
+        buffers += 1;
+    }
+    assert!(buffers > 2);
+    assert_eq!(*calls.lock().unwrap(), buffers);
+}
+
+/// A sink keeping the buffers it is given, returning new ones.
+#[derive(Default)]
+struct KeepingHandler {
+    kept: Mutex<Vec<LogBuffer>>,
+    returned: Mutex<Vec<usize>>,
+}
+
+impl BufferHandler for KeepingHandler {
+    fn handle_buffer(&self, _data: &[u8]) {
+        panic!("owned buffers are lent");
+    }
+
+    fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+        let mut next = LogBuffer::new(buffer.capacity());
+        self.returned.lock().unwrap().push(next.as_mut_ptr() as usize);
+        self.kept.lock().unwrap().push(buffer);
+        next
+    }
+}
+
+#[test]
+fn test_handlers_take_over_buffers() {
+    let handler = Arc::new(KeepingHandler::default());
+    struct Shared(Arc<KeepingHandler>);
+    impl BufferHandler for Shared {
+        fn handle_buffer(&self, data: &[u8]) {
+            self.0.handle_buffer(data)
+        }
+        fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+            self.0.handle_owned_buffer(buffer)
+        }
+    }
+    {
+        let mut logger = Logger::<256>::new(Shared(handler.clone()));
+        for i in 0..200 {
+            log_record!(logger, "Taken over {}", i).unwrap();
+        }
+        assert_eq!(logger.handler_status().panics, 0);
+    }
+
+    let kept = handler.kept.lock().unwrap();
+    assert!(kept.len() > 4);
+    // The Logger wrote to the buffers it was given in return
+    let returned = handler.returned.lock().unwrap();
+    let mut kept_at: Vec<usize> = kept.iter().map(|buffer| buffer.as_ptr() as usize).collect();
+    kept_at.drain(..2);
+    assert!(kept_at.iter().all(|at| returned.contains(at)));
+
+    let data: Vec<u8> = kept.iter().flat_map(|buffer| buffer.iter().copied()).collect();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(entries.len(), 200);
+    assert_eq!(entries[199], "Taken over 199");
+}
+
+#[derive(Clone, Default)]
+struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_background_writer_writes_every_buffer_in_order() {
+    let out = SharedWriter::default();
+    {
+        let mut logger = Logger::<512>::new(BackgroundWriter::new(out.clone(), 2));
+        // Metadata buffers are lent, and copied
+        logger.set_dictionary_channel(true);
+        for i in 0..2000 {
+            log_record!(logger, "Background {}", i).unwrap();
+        }
+    }
+    let data = out.0.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    let expected: Vec<String> = (0..2000).map(|i| format!("Background {}", i)).collect();
+    assert_eq!(entries, expected);
+}
+
+struct FailingWriter;
+
+impl Write for FailingWriter {
+    fn write(&mut self, _data: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_background_write_failures_reach_the_logger() {
+    let mut logger = Logger::<256>::new(BackgroundWriter::new(FailingWriter, 1));
+    logger.set_handler_panic_policy(HandlerPanicPolicy::Retry);
+    for i in 0..200 {
+        log_record!(logger, "Lost {}", i).unwrap();
+    }
+    let status = logger.handler_status();
+    assert!(status.panics > 0);
+    assert_eq!(status.last_panic.as_deref(), Some("background write failed: disk full"));
+}
+
+/// Records the cores and nice value of the thread it is written from.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Default)]
+struct ProbeWriter(Arc<Mutex<Option<(String, i32)>>>);
+
+#[cfg(target_os = "linux")]
+impl Write for ProbeWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let status = std::fs::read_to_string("/proc/thread-self/status")?;
+        let cores = status.lines().find_map(|line| line.strip_prefix("Cpus_allowed_list:")).unwrap().trim().to_string();
+        // The fields after the command name, which ends with ')'
+        let stat = std::fs::read_to_string("/proc/thread-self/stat")?;
+        let nice = stat.rsplit_once(')').unwrap().1.split_whitespace().nth(16).unwrap().parse().unwrap();
+        *self.0.lock().unwrap() = Some((cores, nice));
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_background_writer_thread_is_pinned_and_niced() {
+    let probe = ProbeWriter::default();
+    let scheduling = ThreadScheduling::new().pin_to([0, 0]).nice(7);
+    assert_eq!((scheduling.cores(), scheduling.nice_value()), (&[0][..], Some(7)));
+    {
+        let writer = BackgroundWriter::with_scheduling(probe.clone(), 2, scheduling).unwrap();
+        let mut logger = Logger::<512>::new(writer);
+        log_record!(logger, "Pinned {}", 1).unwrap();
+    }
+    assert_eq!(*probe.0.lock().unwrap(), Some(("0".to_string(), 7)));
+
+    for (scheduling, kind) in [
+        (ThreadScheduling::new().pin_to([1 << 20]), io::ErrorKind::InvalidInput),
+        (ThreadScheduling::new().nice(40), io::ErrorKind::InvalidInput),
+        // Within the addressable cores, but not a core of this machine
+        (ThreadScheduling::new().pin_to([1000]), io::ErrorKind::InvalidInput),
+    ] {
+        let err = BackgroundWriter::with_scheduling(probe.clone(), 2, scheduling.clone()).err().unwrap();
+        assert_eq!(err.kind(), kind, "{:?}: {}", scheduling, err);
+    }
+}
+
+#[test]
+fn test_async_file_handler_writes_every_buffer_to_its_file() {
+    let path = std::env::temp_dir().join(format!("async_file_handler_{}.blog", std::process::id()));
+    {
+        let handler = AsyncFileHandler::create(&path, 2).unwrap();
+        assert_eq!(handler.path(), path.as_path());
+        let mut logger = Logger::<512>::new(handler);
+        for i in 0..2000 {
+            log_record!(logger, "To file {}", i).unwrap();
+        }
+    }
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    let expected: Vec<String> = (0..2000).map(|i| format!("To file {}", i)).collect();
+    assert_eq!(entries, expected);
+
+    let missing = std::env::temp_dir().join("no_such_directory").join("app.blog");
+    assert!(AsyncFileHandler::create(missing, 2).is_err());
+}
+
+/// A writer that doesn't write until its gate is opened.
+#[derive(Clone, Default)]
+struct GatedWriter {
+    out: SharedWriter,
+    gate: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl GatedWriter {
+    fn open(&self) {
+        *self.gate.0.lock().unwrap() = true;
+        self.gate.1.notify_all();
+    }
+}
+
+impl Write for GatedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let (open, opened) = &*self.gate;
+        drop(opened.wait_while(open.lock().unwrap(), |open| !*open).unwrap());
+        self.out.write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_background_writer_drops_buffers_when_its_queue_is_full() {
+    let writer = GatedWriter::default();
+    let handler = BackgroundWriter::new(writer.clone(), 2).backpressure(Backpressure::Drop);
+    let dropped = handler.dropped_buffers();
+    let mut logger = Logger::<512>::new(handler);
+    // The writer is stuck, yet logging goes on
+    for i in 0..2000u32 {
+        log_record!(logger, "Maybe kept {}", i).unwrap();
+    }
+    assert!(dropped.load(Ordering::Relaxed) > 0);
+    assert!(logger.stats().dropped_records > 0);
+    writer.open();
+    drop(logger);
+
+    let data = writer.out.0.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let kept: Vec<u32> = std::iter::from_fn(|| reader.read_entry())
+        .map(|entry| entry.format().strip_prefix("Maybe kept ").unwrap().parse().unwrap())
+        .collect();
+    // The first buffers and the last, whole and in order
+    assert_eq!(kept[0], 0);
+    assert_eq!(*kept.last().unwrap(), 1999);
+    assert!(kept.len() < 2000);
+    assert!(kept.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+/// Keeps the buffers it is handed, except the second, which it discards as
+/// a `BackgroundWriter` with a full queue would.
+#[derive(Default)]
+struct DiscardingHandler {
+    kept: Arc<Mutex<Vec<u8>>>,
+    handed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl BufferHandler for DiscardingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.kept.lock().unwrap().extend_from_slice(data);
+    }
+
+    fn handle_owned_buffer(&self, buffer: LogBuffer) -> LogBuffer {
+        match self.handed.fetch_add(1, Ordering::Relaxed) {
+            1 => self.dropped.fetch_add(1, Ordering::Relaxed),
+            _ => {
+                self.handle_buffer(&buffer);
+                0
+            }
+        };
+        buffer
+    }
+
+    fn buffers_dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn test_dropped_buffers_are_reported_as_dropped_records() {
+    let handler = DiscardingHandler::default();
+    let kept = handler.kept.clone();
+    let mut logger = Logger::<512>::new(handler);
+    logger.set_instrumentation(true);
+    for i in 0..100u32 {
+        log_record!(logger, "Maybe kept {}", i).unwrap();
+    }
+    let dropped = logger.stats().dropped_records;
+    assert!(dropped > 0);
+    drop(logger);
+
+    let data = kept.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    let records = entries.iter().filter(|entry| !entry.is_internal()).count() as u64;
+    assert_eq!(records + dropped, 100);
+    // The next buffer tells how many records went with the discarded one
+    let reported: Vec<_> = entries.iter()
+        .filter(|entry| entry.format_id == InternalEvent::Drops.format_id())
+        .map(|entry| match entry.parameters[0] {
+            LogValue::Integer(count) => count as u64,
+            ref other => panic!("unexpected count {:?}", other),
+        })
+        .collect();
+    assert_eq!(reported, [dropped]);
+}
+
+/// Records the bytes written by each flush.
+#[derive(Clone, Default)]
+struct FlushProbe {
+    out: SharedWriter,
+    flushes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Write for FlushProbe {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.out.write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes.lock().unwrap().push(self.out.0.lock().unwrap().len());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_background_writer_sync_waits_for_queued_buffers() {
+    let probe = FlushProbe::default();
+    let mut logger = Logger::<512>::new(BackgroundWriter::new(probe.clone(), 4));
+    for i in 0..200 {
+        log_record!(logger, "Queued {}", i).unwrap();
+    }
+    logger.mark_critical_begin();
+    let written = probe.out.0.lock().unwrap().len();
+    assert_eq!(*probe.flushes.lock().unwrap(), [written]);
+
+    let data = probe.out.0.lock().unwrap().clone();
+    let mut reader = LogReader::new(&data);
+    assert_eq!(std::iter::from_fn(|| reader.read_entry()).count(), 200);
+}
+
+#[test]
+fn test_raw_buffers_reach_handle_buffer() {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let data = [1u8, 2, 3];
+    // `data` is valid for its length
+    unsafe { CollectingHandler(collected.clone()).handle_switched_out_buffer(data.as_ptr(), data.len()) };
+    assert_eq!(*collected.lock().unwrap(), data);
+}
@@ -0,0 +1,121 @@
+use binary_logger::{Logger, BufferHandler, log_record};
+use binary_logger::health::{HealthFile, HealthStatus};
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, SystemTime};
+
+struct SlowHandler(Duration);
+
+impl BufferHandler for SlowHandler {
+    fn handle_buffer(&self, _data: &[u8]) {
+        std::thread::sleep(self.0);
+    }
+}
+
+/// Waits at the barrier twice per buffer: once on entering, once before
+/// returning.
+struct BlockedHandler(Arc<Barrier>);
+
+impl BufferHandler for BlockedHandler {
+    fn handle_buffer(&self, _data: &[u8]) {
+        self.0.wait();
+        self.0.wait();
+    }
+}
+
+/// Waits for the heartbeat thread to write a status that passes `done`.
+fn wait_for(path: &std::path::Path, done: impl Fn(&HealthStatus) -> bool) -> HealthStatus {
+    for _ in 0..1000 {
+        if let Some(status) = HealthStatus::read(path).ok().filter(&done) {
+            return status;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    panic!("no heartbeat");
+}
+
+#[test]
+fn test_health_file_updates() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.health");
+    let started = SystemTime::now();
+
+    let mut logger = Logger::<4096>::new(SlowHandler(Duration::from_millis(5)));
+    logger.set_health_file(Some(HealthFile::new(&path, Duration::ZERO)));
+    let status = HealthStatus::read(&path).unwrap();
+    assert!(status.heartbeat_at >= started);
+    assert_eq!(status.last_flush, None);
+    assert_eq!(status.bytes_behind, 0);
+
+    // With a zero interval the heartbeat thread picks up every write
+    log_record!(logger, "Health check {}", 1).unwrap();
+    let status = wait_for(&path, |status| status.bytes_behind > 0);
+    assert_eq!(status.buffers_flushed, 0);
+
+    // Idle, the heartbeat goes on
+    let idle = HealthStatus::read(&path).unwrap();
+    let status = wait_for(&path, |status| status.heartbeat_at > idle.heartbeat_at);
+    assert_eq!((status.bytes_behind, status.handler_since), (idle.bytes_behind, None));
+
+    logger.flush();
+    let status = wait_for(&path, |status| status.buffers_flushed == 1);
+    assert!(status.bytes_flushed > 8);
+    assert_eq!(status.bytes_behind, 0);
+    assert!(status.last_flush.unwrap() >= started);
+    assert!(status.handler_time >= Duration::from_millis(5));
+    assert_eq!(status.dropped, 0);
+    assert_eq!(status.handler_since, None);
+
+    // Only the health file itself is left behind
+    drop(logger);
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1);
+}
+
+#[test]
+fn test_health_file_interval() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.health");
+
+    let mut logger = Logger::<4096>::new(SlowHandler(Duration::ZERO));
+    logger.set_health_file(Some(HealthFile::new(&path, Duration::from_secs(3600))));
+    log_record!(logger, "Health interval {}", 1).unwrap();
+    assert_eq!(HealthStatus::read(&path).unwrap().bytes_behind, 0);
+
+    logger.heartbeat();
+    assert!(HealthStatus::read(&path).unwrap().bytes_behind > 0);
+
+    // Once unset, the file is left as it was
+    logger.set_health_file(None);
+    std::fs::remove_file(&path).unwrap();
+    logger.flush();
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_health_file_reports_a_blocked_handler() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.health");
+    let barrier = Arc::new(Barrier::new(2));
+
+    let logging = {
+        let (path, barrier) = (path.clone(), barrier.clone());
+        std::thread::spawn(move || {
+            let mut logger = Logger::<4096>::new(BlockedHandler(barrier));
+            logger.set_health_file(Some(HealthFile::new(&path, Duration::ZERO)));
+            log_record!(logger, "Health check {}", 1).unwrap();
+            logger.flush();
+            wait_for(&path, |status| status.buffers_flushed == 1)
+        })
+    };
+    barrier.wait();
+    let called = SystemTime::now();
+
+    // The heartbeat goes on while the logging thread is stuck
+    let blocked = wait_for(&path, |status| status.heartbeat_at > called);
+    assert!(blocked.handler_since.unwrap() <= called);
+    assert_eq!(blocked.buffers_flushed, 0);
+    barrier.wait();
+
+    let status = logging.join().unwrap();
+    assert_eq!(status.handler_since, None);
+}
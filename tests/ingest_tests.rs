@@ -0,0 +1,82 @@
+use binary_logger::{BufferHandler, LogReader, LogEntry};
+use binary_logger::convert::TextPattern;
+use binary_logger::ingest::ingest;
+use binary_logger::level::Level;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<u8>>>);
+
+impl BufferHandler for Collector {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+impl Collector {
+    fn messages(&self) -> Vec<String> {
+        let data = self.0.lock().unwrap().clone();
+        let mut reader = LogReader::new(&data);
+        std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect()
+    }
+
+    /// Waits up to two seconds for `count` records.
+    fn wait_for(&self, count: usize) -> Vec<String> {
+        let start = Instant::now();
+        loop {
+            let messages = self.messages();
+            if messages.len() >= count || start.elapsed() > Duration::from_secs(2) {
+                return messages;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+#[test]
+fn test_lines_are_written_while_the_pipe_is_open() {
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    let sink = Collector::default();
+    let pattern = TextPattern::without_time("{level} [{channel}] {message}").unwrap();
+    let ingesting = {
+        let sink = sink.clone();
+        thread::spawn(move || ingest(reader, &pattern, Duration::from_millis(50), sink))
+    };
+
+    let before = SystemTime::now();
+    writer.write_all(b"INFO [api] request 17 took 12 ms\nERROR [api] request 18 failed\n").unwrap();
+    assert_eq!(sink.wait_for(2), ["request 17 took 12 ms", "request 18 failed"]);
+
+    // Continues the error, which was already written
+    writer.write_all(b"  caused by: timeout\n").unwrap();
+    writer.write_all(b"WARN [db] slow query\xff\n").unwrap();
+    assert_eq!(sink.wait_for(4)[2..], ["  caused by: timeout", "slow query\u{fffd}"]);
+
+    drop(writer);
+    let stats = ingesting.join().unwrap().unwrap();
+    assert_eq!((stats.lines, stats.records, stats.continuations, stats.skipped), (4, 4, 1, 0));
+
+    let data = sink.0.lock().unwrap().clone();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    let levels: Vec<Option<Level>> = entries.iter().map(|entry| entry.level).collect();
+    assert_eq!(levels, [Some(Level::Info), Some(Level::Error), Some(Level::Error), Some(Level::Warn)]);
+    assert_eq!(entries[2].channel_name(), Some("api"));
+    assert!(entries.iter().all(|entry| entry.timestamp >= before - Duration::from_millis(1) && entry.timestamp <= SystemTime::now()));
+}
+
+#[test]
+fn test_timed_patterns_read_line_times() {
+    let text = "2024-03-01T12:00:00Z job 1 done\n2024-03-01T12:00:05Z job 2 done\n";
+    let sink = Collector::default();
+    let pattern: TextPattern = "{time} {message}".parse().unwrap();
+    let stats = ingest(std::io::Cursor::new(text), &pattern, Duration::from_secs(1), sink.clone()).unwrap();
+    assert_eq!(stats.records, 2);
+    assert_eq!(sink.messages(), ["job 1 done", "job 2 done"]);
+
+    let err = TextPattern::without_time("{time} {message}").unwrap_err();
+    assert!(err.contains("{time}"), "{}", err);
+}
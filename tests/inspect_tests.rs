@@ -0,0 +1,62 @@
+use binary_logger::{Logger, BufferHandler, log_record};
+use binary_logger::inspect::inspect;
+use std::sync::{Arc, Mutex};
+
+struct CollectingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+fn write_log(count: i32) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<256>::new(CollectingHandler { data: data.clone() });
+        logger.set_global_sequence(true);
+        for i in 0..count {
+            log_record!(logger, "Inspected {}", i).unwrap();
+        }
+    }
+    let data = data.lock().unwrap();
+    data.clone()
+}
+
+fn inspect_to_string(data: &[u8]) -> String {
+    let mut out = Vec::new();
+    inspect(data, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_inspect_walks_every_record() {
+    let data = write_log(20);
+    let output = inspect_to_string(&data);
+
+    let buffers = output.lines().filter(|l| l.contains("  buffer #")).count();
+    let records = output.lines().filter(|l| l.contains("  record #")).count();
+    assert!(buffers > 1, "Small buffers should produce several buffers:\n{}", output);
+    assert!(output.contains("type=2 (stream header)"), "Stream header should be annotated:\n{}", output);
+    assert!(output.contains("codec=0 (raw)"));
+    assert!(output.contains("type=1 (base)"));
+    assert!(output.contains("sequence="));
+    assert!(!output.contains("TRUNCATED") && !output.contains("INVALID"), "Clean file should have no errors:\n{}", output);
+
+    // Stream header + base anchors + 20 records
+    assert!(records > 20);
+    assert!(output.ends_with(&format!("{} buffers, {} records\n", buffers, records)));
+}
+
+#[test]
+fn test_inspect_reports_truncation() {
+    let mut data = write_log(3);
+    let len = data.len();
+    data.truncate(len - 5);
+
+    let output = inspect_to_string(&data);
+    assert!(output.contains("INVALID"), "Buffer size beyond the file should be reported:\n{}", output);
+    assert!(output.contains("TRUNCATED payload"), "Cut-off record should be reported:\n{}", output);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_record};
+use binary_logger::instant::LogInstant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use common::CollectingHandler;
+
+fn instant_of(value: &LogValue) -> SystemTime {
+    match value {
+        LogValue::Instant(time) => *time,
+        other => panic!("expected an instant, got {:?}", other),
+    }
+}
+
+fn distance(a: SystemTime, b: SystemTime) -> Duration {
+    a.duration_since(b).or_else(|_| b.duration_since(a)).unwrap()
+}
+
+#[test]
+fn test_instant_round_trip() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let now = SystemTime::now();
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        let started = LogInstant::from_system_time(now - Duration::from_secs(2));
+        let deadline = LogInstant::from_system_time(now + Duration::from_secs(5));
+        log_record!(logger, "Job {} started at {}, due at {}", 9, started, deadline).unwrap();
+        log_record!(logger, "Instant now {}", LogInstant::now()).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    let tolerance = Duration::from_millis(50);
+    let params = &entries[0].parameters;
+    assert!(matches!(params[0], LogValue::Integer(9)));
+    assert!(distance(instant_of(&params[1]), now - Duration::from_secs(2)) < tolerance);
+    assert!(distance(instant_of(&params[2]), now + Duration::from_secs(5)) < tolerance);
+    assert!(distance(instant_of(&entries[1].parameters[0]), entries[1].timestamp) < tolerance);
+
+    // A few seconds away fits in 4 bytes: the count, then size and bytes of each argument
+    assert!(entries[0].raw_values.len() <= 1 + (4 + 4) + 2 * (4 + 4));
+    assert!(entries[0].format().starts_with("Job 9 started at "));
+}
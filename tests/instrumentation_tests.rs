@@ -0,0 +1,123 @@
+use binary_logger::{Logger, BufferHandler, LogReader, LogEntry, LogValue, log_record};
+use binary_logger::instrumentation::InternalEvent;
+use std::sync::{Arc, Mutex};
+
+struct CollectingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CollectingHandler {
+    fn new() -> Self {
+        Self {
+            data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+fn read_all(data: &[u8]) -> Vec<LogEntry> {
+    let mut reader = LogReader::new(data);
+    let mut entries = Vec::new();
+    while let Some(entry) = reader.read_entry() {
+        entries.push(entry);
+    }
+    entries
+}
+
+fn events(entries: &[LogEntry], event: InternalEvent) -> Vec<&LogEntry> {
+    entries.iter().filter(|e| e.format_id == event.format_id()).collect()
+}
+
+#[test]
+fn test_reserved_ids_resolve() {
+    for event in InternalEvent::ALL {
+        assert_eq!(InternalEvent::from_format_id(event.format_id()), Some(event));
+        assert_eq!(binary_logger::get_string(event.format_id()), Some(event.format_string()));
+    }
+    assert_eq!(InternalEvent::from_format_id(1), None);
+}
+
+#[test]
+fn test_no_internal_events_by_default() {
+    const BUFFER_SIZE: usize = 512;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        for i in 0..100 {
+            log_record!(logger, "Plain {}", i).unwrap();
+        }
+    }
+
+    let data = data.lock().unwrap();
+    let entries = read_all(&data);
+    assert_eq!(entries.len(), 100);
+    assert!(entries.iter().all(|e| !e.is_internal()), "Internal events should be opt-in");
+}
+
+#[test]
+fn test_buffer_switch_events() {
+    const BUFFER_SIZE: usize = 512;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        logger.set_instrumentation(true);
+        for i in 0..100 {
+            log_record!(logger, "Instrumented {}", i).unwrap();
+        }
+        logger.flush();
+    }
+
+    let data = data.lock().unwrap();
+    let entries = read_all(&data);
+
+    let app: Vec<_> = entries.iter().filter(|e| !e.is_internal()).collect();
+    assert_eq!(app.len(), 100, "Instrumentation should not displace application records");
+
+    let starts = events(&entries, InternalEvent::BufferSwitchStart);
+    let ends = events(&entries, InternalEvent::BufferSwitchEnd);
+    assert!(starts.len() > 1, "Should have recorded several buffer switches");
+    assert!(ends.len() >= starts.len() - 1, "Each switch should report the handler duration");
+
+    for start in starts {
+        match start.parameters.first() {
+            Some(LogValue::Integer(bytes)) => assert!(*bytes > 8 && *bytes as usize <= BUFFER_SIZE),
+            other => panic!("Expected buffer size argument, got: {:?}", other),
+        }
+    }
+
+    // Internal events render through the reserved format strings
+    assert!(ends[0].format().starts_with("[binary_logger] buffer switch end: handler took "));
+}
+
+#[test]
+fn test_registry_growth_event() {
+    const BUFFER_SIZE: usize = 1024;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        logger.set_instrumentation(true);
+        log_record!(logger, "Registry growth first {}", 1).unwrap();
+        log_record!(logger, "Registry growth second {}", 2).unwrap();
+    }
+
+    let data = data.lock().unwrap();
+    let entries = read_all(&data);
+
+    let growth = events(&entries, InternalEvent::RegistryGrowth);
+    assert!(!growth.is_empty(), "Registering new strings should be reported");
+    match growth.last().unwrap().parameters.first() {
+        Some(LogValue::Integer(count)) => assert!(*count >= 2),
+        other => panic!("Expected registry size argument, got: {:?}", other),
+    }
+}
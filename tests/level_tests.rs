@@ -0,0 +1,232 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, log_record, log_record_at};
+use binary_logger::arg_types::CaptureLimits;
+use binary_logger::handlers::LevelRouter;
+use binary_logger::level::{self, Level};
+use binary_logger::render::render_line;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use common::{CollectingHandler, read_all};
+
+#[test]
+fn test_levels_round_trip() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "No level {}", 1).unwrap();
+        for level in Level::ALL {
+            log_record_at!(logger, level, "At level {}", level as u8).unwrap();
+        }
+        log_record_at!(logger, Level::Warn, channel: "disk", "Disk {}% full", 91).unwrap();
+        logger.flush();
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let levels: Vec<_> = entries.iter().map(|entry| entry.level).collect();
+    assert_eq!(levels[0], None);
+    assert_eq!(levels[1..6], Level::ALL.map(Some));
+    assert_eq!(entries[6].level, Some(Level::Warn));
+    assert_eq!(entries[6].channel_name(), Some("disk"));
+    assert_eq!(entries[6].format(), "Disk 91% full");
+    assert!(render_line(&entries[6]).ends_with(" WARN [disk] Disk 91% full"));
+    assert!(!render_line(&entries[0]).contains("INFO"));
+
+    assert_eq!("Warning".parse::<Level>(), Ok(Level::Warn));
+    assert!("loud".parse::<Level>().is_err());
+}
+
+#[test]
+fn test_level_router() {
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let archive = Arc::new(Mutex::new(Vec::new()));
+    {
+        let router = LevelRouter::new()
+            .route(Level::Warn, CollectingHandler(alerts.clone()))
+            .route(Level::Error, CollectingHandler(errors.clone()))
+            .route_all(CollectingHandler(archive.clone()));
+        let mut logger = Logger::<512>::new(router);
+        logger.set_global_sequence(true);
+        logger.set_header_compression(true);
+        logger.set_delta_timestamps(true);
+        for i in 0..300u32 {
+            let level = Level::ALL[(i % 5) as usize];
+            if i % 4 == 0 {
+                log_record!(logger, "Unleveled {}", i).unwrap();
+            }
+            log_record_at!(logger, level, channel: "app", "Routed {} at {}", i, level as u8).unwrap();
+            if i % 97 == 0 {
+                // Gaps longer than a relative timestamp can span
+                std::thread::sleep(Duration::from_millis(70));
+            }
+        }
+        logger.flush();
+    }
+
+    let archive = read_all(&archive.lock().unwrap());
+    assert_eq!(archive.len(), 375);
+    for (data, min_level) in [(alerts, Level::Warn), (errors, Level::Error)] {
+        let routed = read_all(&data.lock().unwrap());
+        let expected: Vec<_> = archive.iter().filter(|entry| entry.level >= Some(min_level)).collect();
+        assert_eq!(routed.len(), expected.len());
+        for (routed, expected) in routed.iter().zip(expected) {
+            assert_eq!(routed.timestamp, expected.timestamp);
+            assert_eq!(routed.sequence, expected.sequence);
+            assert_eq!(routed.level, expected.level);
+            assert_eq!(routed.channel, expected.channel);
+            assert_eq!(routed.format(), expected.format());
+        }
+    }
+}
+
+#[test]
+fn test_level_overrides() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_level(Level::Warn);
+        log_record_at!(logger, Level::Debug, "Skipped {}", 1).unwrap();
+        logger.with_level(Level::Trace, |logger| {
+            log_record_at!(logger, Level::Trace, "Scoped {}", 2).unwrap();
+        });
+        assert_eq!(logger.level(), Level::Warn);
+        log_record_at!(logger, Level::Info, "Skipped {}", 3).unwrap();
+
+        level::with_global_level(Level::Info, || {
+            assert_eq!(level::global_level(), Some(Level::Info));
+            log_record_at!(logger, Level::Info, "Global {}", 4).unwrap();
+            log_record_at!(logger, Level::Debug, "Skipped {}", 5).unwrap();
+        });
+        assert_eq!(level::global_level(), None);
+
+        level::boost_for(Level::Debug, Duration::from_millis(50));
+        log_record_at!(logger, Level::Debug, "Boosted {}", 6).unwrap();
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(level::global_level(), None, "The boost should expire");
+        log_record_at!(logger, Level::Debug, "Skipped {}", 7).unwrap();
+
+        level::boost_for(Level::Debug, Duration::from_secs(60));
+        level::clear_boost();
+        log_record_at!(logger, Level::Debug, "Skipped {}", 8).unwrap();
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let messages: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(messages, ["Scoped 2", "Global 4", "Boosted 6"]);
+}
+
+#[test]
+fn test_disabled_levels_skip_arguments() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let mut evaluated = 0;
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_level(Level::Info);
+        assert!(!logger.level_enabled(Level::Debug));
+        assert!(logger.level_enabled(Level::Error));
+        log_record_at!(logger, Level::Debug, "Skipped {}", { evaluated += 1; evaluated }).unwrap();
+        log_record_at!(logger, Level::Trace, channel: "disk", "Skipped {}", { evaluated += 1; evaluated }).unwrap();
+        log_record_at!(logger, Level::Warn, "Written {}", { evaluated += 1; evaluated }).unwrap();
+        log_record_at!(logger, Level::Error, "Written {}", { evaluated += 1; evaluated }).unwrap();
+        assert_eq!(logger.stats().records_written, 2);
+    }
+    assert_eq!(evaluated, 2);
+
+    let messages: Vec<String> = read_all(&data.lock().unwrap()).iter().map(|entry| entry.format()).collect();
+    assert_eq!(messages, ["Written 1", "Written 2"]);
+}
+
+#[test]
+fn test_level_filter() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "No level {}", 0).unwrap();
+        for level in Level::ALL {
+            log_record_at!(logger, level, "At level {}", level as u8).unwrap();
+        }
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    reader.set_level_filter(Some(Level::Warn));
+    let levels: Vec<_> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.level).collect();
+    assert_eq!(levels, [Some(Level::Warn), Some(Level::Error)]);
+
+    // Filtered records still count in the channel statistics
+    assert_eq!(reader.channel_stats()[&0].records, 6);
+}
+
+#[test]
+fn test_suppression_summaries() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_level(Level::Info);
+        logger.set_suppression_summaries(Some(Duration::from_millis(20)));
+        for i in 0..5 {
+            log_record_at!(logger, Level::Debug, "Polled {}", i).unwrap();
+        }
+        log_record_at!(logger, Level::Trace, "Tick {}", 1).unwrap();
+        thread::sleep(Duration::from_millis(30));
+
+        // The next record written brings the summaries out
+        log_record_at!(logger, Level::Info, "Ready {}", 1).unwrap();
+        for i in 0..3 {
+            log_record_at!(logger, Level::Debug, "Polled {}", i).unwrap();
+        }
+        // The rest are written when the logger is dropped
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let lines: Vec<(Option<Level>, String)> = entries.iter().map(|e| (e.level, e.format())).collect();
+    let summary = |count, format: &'static str| {
+        let format_id = binary_logger::register_string(format);
+        format!("[binary_logger] suppressed {} records from format {} in last ", count, format_id)
+    };
+
+    // Summaries come in format ID order and carry the level they count
+    assert_eq!(lines.len(), 4, "{:?}", lines);
+    assert_eq!(lines[0].0, Some(Level::Debug));
+    assert!(lines[0].1.starts_with(&summary(5, "Polled {}")), "{}", lines[0].1);
+    assert_eq!(lines[1].0, Some(Level::Trace));
+    assert!(lines[1].1.starts_with(&summary(1, "Tick {}")), "{}", lines[1].1);
+    assert_eq!(lines[2].1, "Ready 1");
+    assert_eq!(lines[3].0, Some(Level::Debug));
+    assert!(lines[3].1.starts_with(&summary(3, "Polled {}")), "{}", lines[3].1);
+    assert!(entries.iter().all(|e| e.is_internal() == (e.format() != "Ready 1")));
+}
+
+#[test]
+fn test_capture_limits() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let long = "é".repeat(40);
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_capture_limits(CaptureLimits::new()
+            .max_string_len(Level::Info, 16)
+            .max_string_len(Level::Debug, 1024)
+            .max_string_len(None, 2));
+        log_record_at!(logger, Level::Info, "Request body {} from {}", long.as_str(), String::from("client")).unwrap();
+        log_record_at!(logger, Level::Debug, "Request body {}", &long).unwrap();
+        log_record_at!(logger, Level::Error, "Request body {}", long.clone()).unwrap();
+        log_record!(logger, "Note {}", "literal strings are not captured").unwrap();
+        log_record!(logger, "Peer {}", String::from("abc")).unwrap();
+    }
+
+    let entries = read_all(&data.lock().unwrap());
+    let lines: Vec<(String, bool)> = entries.iter().map(|e| (e.format(), e.truncated)).collect();
+    // Cut at a character boundary, with the ellipsis within the limit
+    let cut = format!("{}…", "é".repeat(6));
+    assert_eq!(cut.len(), 15);
+    assert_eq!(lines, [
+        (format!("Request body {} from client", cut), true),
+        (format!("Request body {}", long), false),
+        (format!("Request body {}", long), false),
+        ("Note literal strings are not captured".to_string(), false),
+        ("Peer ab".to_string(), true),
+    ]);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, log_count, log_record, register_string};
+use binary_logger::log_stats::LogStats;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use common::CollectingHandler;
+
+#[test]
+fn test_counter_series_per_buffer() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let before = SystemTime::now() - Duration::from_millis(1);
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        for round in 0..3 {
+            for _ in 0..10 * (round + 1) {
+                log_count!(logger, "cache_miss").unwrap();
+            }
+            log_record!(logger, "Round {} done", round).unwrap();
+            thread::sleep(Duration::from_millis(5));
+            logger.flush();
+        }
+        log_count!(logger, "cache_hit").unwrap();
+    }
+    let after = SystemTime::now() + Duration::from_millis(1);
+
+    let data = data.lock().unwrap();
+    let stats = LogStats::collect(&mut LogReader::new(&data));
+    assert_eq!(stats.entries(), 3);
+    let (first, last) = stats.time_span().unwrap();
+    assert!(before <= first && first <= last && last <= after);
+
+    let misses = &stats.counters()["cache_miss"];
+    assert_eq!(misses.total, 60);
+    let counts: Vec<u64> = misses.intervals.iter().map(|interval| interval.count).collect();
+    assert_eq!(counts, [10, 20, 30]);
+    for pair in misses.intervals.windows(2) {
+        assert!(pair[0].end <= pair[1].start);
+    }
+    for interval in &misses.intervals {
+        assert!(before <= interval.start && interval.start <= interval.end && interval.end <= after);
+    }
+    assert!(misses.intervals[2].end.duration_since(misses.intervals[0].end).unwrap() >= Duration::from_millis(10));
+
+    // Counted after the last entry: the interval is the table itself
+    let hits = &stats.counters()["cache_hit"];
+    assert_eq!(hits.total, 1);
+    assert_eq!(hits.intervals[0].start, hits.intervals[0].end);
+}
+
+#[test]
+fn test_unnamed_counters_and_filters() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let id = register_string("stats counter");
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, channel: "net", "Connected",).unwrap();
+        logger.count(id).unwrap();
+        logger.count(id).unwrap();
+        log_record!(logger, "Idle",).unwrap();
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    reader.set_channel_filter(Some(&[register_string("net")]));
+    let stats = LogStats::collect(&mut reader);
+    // Filters apply to entries, not to the count tables
+    assert_eq!(stats.entries(), 1);
+    assert_eq!(stats.counters()["stats counter"].total, 2);
+    assert_eq!(stats.counters()["stats counter"].format_id, id);
+    assert!(reader.take_count_tables().is_empty());
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use binary_logger::{Logger, LogValue, log_record};
+use binary_logger::loggable::Loggable;
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+#[derive(Loggable)]
+struct Trade {
+    id: u64,
+    price: f32,
+    venue: String,
+    #[log(skip)]
+    #[allow(dead_code)]
+    cache: Vec<u64>,
+}
+
+#[derive(Loggable)]
+struct Point(i32, i32);
+
+#[derive(Loggable)]
+struct Segment {
+    from: Point,
+    to: Point,
+    label: &'static str,
+}
+
+#[derive(Loggable)]
+struct Tick;
+
+fn trade() -> Trade {
+    Trade { id: 7, price: 1.5, venue: "XNAS".to_string(), cache: vec![1, 2, 3] }
+}
+
+fn fields(value: &LogValue) -> (&str, Vec<&str>) {
+    match value {
+        LogValue::Struct { name, fields } => (name, fields.iter().map(|(field, _)| field.as_str()).collect()),
+        other => panic!("expected a struct, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_structs_are_read_back_field_by_field() {
+    for type_tags in [false, true] {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+            logger.set_type_tags(type_tags);
+            log_record!(logger, "Trade {} at {}", trade(), 300).unwrap();
+        }
+        let entries = read_all(&data.lock().unwrap());
+        assert_eq!(entries.len(), 1);
+        let trade = &entries[0].parameters[0];
+        assert_eq!(fields(trade), ("Trade", vec!["id", "price", "venue"]));
+        let LogValue::Struct { fields, .. } = trade else { unreachable!() };
+        assert!(matches!(fields[0].1, LogValue::Unsigned(7)));
+        assert!(matches!(fields[1].1, LogValue::Float32(price) if price == 1.5));
+        assert!(matches!(&fields[2].1, LogValue::String(venue) if venue == "XNAS"));
+        assert_eq!(trade.to_string(), "Trade { id: 7, price: 1.5, venue: XNAS }");
+        assert_eq!(entries[0].format(), "Trade Trade { id: 7, price: 1.5, venue: XNAS } at 300");
+    }
+}
+
+#[test]
+fn test_tuple_unit_and_nested_structs() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        let segment = Segment { from: Point(0, -1), to: Point(3, 4), label: "edge" };
+        log_record!(logger, "Drew {} and {} on {}", segment, Point(5, 6), Tick).unwrap();
+        // References log the struct they point to
+        log_record!(logger, "Again {}", &segment).unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries.len(), 2);
+    let params = &entries[0].parameters;
+    assert_eq!(fields(&params[0]), ("Segment", vec!["from", "to", "label"]));
+    let LogValue::Struct { fields: segment, .. } = &params[0] else { unreachable!() };
+    assert_eq!(crate::fields(&segment[0].1), ("Point", vec!["0", "1"]));
+    assert_eq!(params[0].to_string(), "Segment { from: Point(0, -1), to: Point(3, 4), label: edge }");
+    assert_eq!(params[1].to_string(), "Point(5, 6)");
+    assert_eq!(fields(&params[2]), ("Tick", vec![]));
+    assert_eq!(params[2].to_string(), "Tick");
+    assert_eq!(entries[1].parameters[0].to_string(), params[0].to_string());
+}
+
+#[test]
+fn test_fields_are_stored_without_their_names() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Trade {}", trade()).unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    // The layout ID, then a tag and a length byte per field
+    assert_eq!(entries[0].raw_values.len(), 1 + 4 + 2 + (2 + 8) + (2 + 4) + (2 + 4));
+}
+
+#[test]
+fn test_layout_ids_name_the_fields() {
+    let id = trade().layout_id();
+    assert_eq!(binary_logger::string_registry::get_string(id), Some("Trade{id,price,venue}"));
+    assert_eq!(binary_logger::string_registry::get_string(Tick.layout_id()), Some("Tick{}"));
+}
@@ -20,9 +20,9 @@ impl CountingHandler {
 }
 
 impl BufferHandler for CountingHandler {
-    fn handle_switched_out_buffer(&self, _buffer: *const u8, size: usize) {
+    fn handle_buffer(&self, data: &[u8]) {
         self.buffer_count.fetch_add(1, Ordering::SeqCst);
-        self.total_bytes.fetch_add(size, Ordering::SeqCst);
+        self.total_bytes.fetch_add(data.len(), Ordering::SeqCst);
     }
 }
 
@@ -39,13 +39,10 @@ impl CollectingHandler {
 }
 
 impl BufferHandler for CollectingHandler {
-    fn handle_switched_out_buffer(&self, buffer: *const u8, size: usize) {
+    fn handle_buffer(&self, buffer: &[u8]) {
         let mut data = self.data.lock().unwrap();
-        unsafe {
-            // Get the buffer data including the header
-            let buffer_slice = std::slice::from_raw_parts(buffer, size);
-            data.extend_from_slice(buffer_slice);
-        }
+        // The buffer data including the header
+        data.extend_from_slice(buffer);
     }
 }
 
@@ -213,6 +210,7 @@ fn test_buffer_switching() {
 }
 
 #[test]
+#[allow(clippy::get_first)]
 fn test_log_format() {
     const BUFFER_SIZE: usize = 1024;
     let handler = CollectingHandler::new();
@@ -381,4 +379,365 @@ fn test_format_deduplication() {
     }
     
     assert_eq!(count, 3, "Should have read all records");
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_multi_buffer_roundtrip() {
+    const BUFFER_SIZE: usize = 256;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+    
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        for i in 0..100 {
+            log_record!(logger, "Roundtrip {}", i).unwrap();
+        }
+    }
+    
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    
+    let mut values = Vec::new();
+    while let Some(entry) = reader.read_entry() {
+        if let Some(LogValue::Integer(value)) = entry.parameters.first() {
+            values.push(*value);
+        }
+    }
+    
+    assert_eq!(values, (0..100).collect::<Vec<_>>(), "All records should survive buffer switches");
+}
+
+#[test]
+fn test_global_sequence() {
+    const BUFFER_SIZE: usize = 1024;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+    
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        log_record!(logger, "Unsequenced {}", 1).unwrap();
+        logger.set_global_sequence(true);
+        for i in 0..3 {
+            log_record!(logger, "Sequenced {}", i).unwrap();
+        }
+    }
+    
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    
+    let first = reader.read_entry().expect("Failed to read entry");
+    assert_eq!(first.sequence, None);
+    
+    let mut last = None;
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        let sequence = entry.sequence.expect("Record should carry a sequence number");
+        if let Some(prev) = last {
+            assert!(sequence > prev, "Sequence numbers should increase");
+        }
+        last = Some(sequence);
+        count += 1;
+    }
+    
+    assert_eq!(count, 3, "Should have read all sequenced records");
+}
+
+#[test]
+fn test_wrap_epochs() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn write_paced(wrap_epochs: bool) -> (Vec<u8>, Vec<(u64, u64)>) {
+        let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+        let handler = CollectingHandler::new();
+        let data = handler.data.clone();
+        let mut windows = Vec::new();
+
+        {
+            let mut logger = Logger::<4096>::new(handler);
+            logger.set_wrap_epochs(wrap_epochs);
+            // 20ms apart: several 65ms epochs in total, none skipped
+            for i in 0..12 {
+                let before = now();
+                log_record!(logger, "Paced {}", i).unwrap();
+                windows.push((before, now()));
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+
+        let data = data.lock().unwrap().clone();
+        (data, windows)
+    }
+
+    let count_bases = |data: &[u8]| {
+        let mut out = Vec::new();
+        binary_logger::inspect::inspect(data, &mut out).unwrap();
+        String::from_utf8(out).unwrap().matches("type=1 (base)").count()
+    };
+
+    let (plain, _) = write_paced(false);
+    let (wrapped, windows) = write_paced(true);
+    assert!(count_bases(&wrapped) < count_bases(&plain),
+        "Wrap epochs should need fewer base records ({} vs {})", count_bases(&wrapped), count_bases(&plain));
+
+    // Reconstructed times must still match the wall clock
+    let mut reader = LogReader::new(&wrapped);
+    let mut count = 0;
+    for (before, after) in windows {
+        let entry = reader.read_entry().unwrap();
+        let micros = entry.timestamp.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+        assert!(micros + 2_000 >= before && micros <= after + 2_000,
+            "Entry {} at {}us outside its write window {}..{}", count, micros, before, after);
+        count += 1;
+    }
+    assert_eq!(count, 12);
+}
+
+#[test]
+fn test_header_compression() {
+    fn write(compressed: bool) -> Vec<u8> {
+        let handler = CollectingHandler::new();
+        let data = handler.data.clone();
+        {
+            let mut logger = Logger::<512>::new(handler);
+            logger.set_header_compression(compressed);
+            logger.set_audit_chain(true);
+            for i in 0..60u32 {
+                if i % 20 == 19 {
+                    log_record!(logger, channel: "net", "Peer {} closed", i).unwrap();
+                } else {
+                    log_record!(logger, "Compressed {}", i).unwrap();
+                }
+            }
+        }
+        let data = data.lock().unwrap();
+        data.clone()
+    }
+
+    let plain = write(false);
+    let compressed = write(true);
+    assert!(compressed.len() < plain.len() - 2 * 40, "Repeated formats should save 2 bytes each");
+
+    let entries = |data: &[u8]| {
+        let mut reader = LogReader::new(data);
+        std::iter::from_fn(|| reader.read_entry())
+            .map(|entry| (entry.format(), entry.channel))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(entries(&compressed), entries(&plain));
+    assert_eq!(entries(&compressed).len(), 60);
+
+    // Every buffer decodes on its own
+    binary_logger::audit::verify_chain(&compressed, None).unwrap();
+    let mut reader = LogReader::new(&compressed);
+    let ids: Vec<_> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.id).collect();
+    let mut lookup = LogReader::new(&compressed);
+    assert_eq!(lookup.get(ids[45]).unwrap().format(), "Compressed 45");
+
+    let mut dump = Vec::new();
+    binary_logger::inspect::inspect(&compressed, &mut dump).unwrap();
+    assert!(String::from_utf8(dump).unwrap().contains("(repeated)"));
+}
+
+#[test]
+fn test_delta_timestamps() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn write(delta: bool) -> (Vec<u8>, Vec<(u64, u64)>) {
+        let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+        let handler = CollectingHandler::new();
+        let data = handler.data.clone();
+        let mut windows = Vec::new();
+
+        {
+            let mut logger = Logger::<512>::new(handler);
+            logger.set_delta_timestamps(delta);
+            logger.set_header_compression(true);
+            logger.set_audit_chain(true);
+            for i in 0..80u32 {
+                let before = now();
+                log_record!(logger, "Delta {}", i).unwrap();
+                windows.push((before, now()));
+                // Bursts with a 70ms gap, longer than the relative timestamp spans
+                if i % 20 == 19 {
+                    thread::sleep(Duration::from_millis(70));
+                }
+            }
+        }
+
+        let data = data.lock().unwrap().clone();
+        (data, windows)
+    }
+
+    let (plain, _) = write(false);
+    let (delta, windows) = write(true);
+    assert!(delta.len() < plain.len(), "Delta timestamps should be smaller ({} vs {})", delta.len(), plain.len());
+
+    // Reconstructed times must still match the wall clock
+    let mut reader = LogReader::new(&delta);
+    let mut count = 0;
+    for (before, after) in windows {
+        let entry = reader.read_entry().unwrap();
+        assert_eq!(entry.format(), format!("Delta {}", count));
+        let micros = entry.timestamp.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+        assert!(micros + 2_000 >= before && micros <= after + 2_000,
+            "Entry {} at {}us outside its write window {}..{}", count, micros, before, after);
+        count += 1;
+    }
+    assert_eq!(count, 80);
+    assert!(reader.read_entry().is_none());
+
+    // Each buffer starts from its own base, so lookups and tools still work
+    binary_logger::audit::verify_chain(&delta, None).unwrap();
+    let mut reader = LogReader::new(&delta);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    let mut lookup = LogReader::new(&delta);
+    let found = lookup.get(entries[65].id).unwrap();
+    assert_eq!(found.format(), "Delta 65");
+    assert_eq!(found.timestamp, entries[65].timestamp);
+
+    let mut dump = Vec::new();
+    binary_logger::inspect::inspect(&delta, &mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert!(dump.contains("delta="));
+    assert!(!dump.contains("(wrap)"));
+}
+
+#[test]
+fn test_untimed_records() {
+    use std::time::SystemTime;
+
+    fn write(timestamps: bool) -> (Vec<u8>, SystemTime) {
+        let handler = CollectingHandler::new();
+        let data = handler.data.clone();
+        let before = SystemTime::now();
+        {
+            let mut logger = Logger::<512>::new(handler);
+            logger.set_timestamps(timestamps);
+            logger.set_global_sequence(true);
+            for i in 0..60u32 {
+                log_record!(logger, "Packet {}", i).unwrap();
+            }
+        }
+        let data = data.lock().unwrap().clone();
+        (data, before)
+    }
+
+    let (timed, _) = write(true);
+    let (untimed, before) = write(false);
+    assert!(untimed.len() < timed.len(), "Untimed records should be smaller ({} vs {})", untimed.len(), timed.len());
+
+    let mut reader = LogReader::new(&untimed);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), 60);
+    let mut buffers = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        assert_eq!(entry.format(), format!("Packet {}", i));
+        assert!(entry.timestamp >= before - Duration::from_millis(1) && entry.timestamp <= SystemTime::now());
+        if i > 0 {
+            assert!(entry.sequence > entries[i - 1].sequence);
+            assert!(entry.timestamp >= entries[i - 1].timestamp);
+        }
+        // The first record of each buffer is timed
+        if entry.id.buffer != entries.get(i.wrapping_sub(1)).map_or(u64::MAX, |prev| prev.id.buffer) {
+            assert!(!entry.untimed, "Entry {} starts a buffer", i);
+            buffers += 1;
+        } else {
+            assert!(entry.untimed, "Entry {} should be untimed", i);
+        }
+    }
+    assert!(buffers > 1);
+
+    let mut dump = Vec::new();
+    binary_logger::inspect::inspect(&untimed, &mut dump).unwrap();
+    assert!(String::from_utf8(dump).unwrap().contains("(untimed)  flags=0x01  no time"));
+}
+
+#[test]
+fn test_literal_args_interned() {
+    const BUFFER_SIZE: usize = 1024;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        for _ in 0..2 {
+            log_record!(logger, "state={} mode={}", "ready", 3).unwrap();
+        }
+        log_record!(logger, "mode={} state={}", -1, "idle",).unwrap();
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), 3);
+    for entry in &entries[..2] {
+        assert_eq!(entry.format(), "state=ready mode=3");
+        // Count, then size and bytes of a 2-byte ID for "ready" and of the integer
+        assert_eq!(entry.raw_values.len(), 1 + (4 + 2) + (4 + 4), "String literals should be stored as IDs");
+    }
+    assert_eq!(entries[2].format(), "mode=-1 state=idle");
+}
+
+#[test]
+fn test_string_args_logged_by_value() {
+    use std::borrow::Cow;
+    use std::rc::Rc;
+
+    const BUFFER_SIZE: usize = 4096;
+    let handler = CollectingHandler::new();
+    let data = handler.data.clone();
+
+    {
+        let mut logger = Logger::<BUFFER_SIZE>::new(handler);
+        let owned = String::from("owned");
+        let names = ["first", "second"];
+        let boxed: Box<str> = "boxed".into();
+        let borrowed: Cow<str> = Cow::Borrowed("borrowed");
+        let changed: Cow<str> = Cow::Owned(owned.to_uppercase());
+        log_record!(logger, "{} {} {}", owned, &owned, owned.as_str()).unwrap();
+        for name in names.iter() {
+            log_record!(logger, "name={}", name).unwrap();
+        }
+        log_record!(logger, "{} {} {}", boxed, borrowed, changed).unwrap();
+        log_record!(logger, "{} {}", Rc::<str>::from("rc"), Arc::<str>::from("arc")).unwrap();
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    let lines: Vec<String> = entries.iter().map(|entry| entry.format()).collect();
+    assert_eq!(lines, ["owned owned owned", "name=first", "name=second", "boxed borrowed OWNED", "rc arc"]);
+    assert!(matches!(&entries[3].parameters[0], LogValue::String(s) if s == "boxed"));
+}
+
+#[test]
+fn test_macros_evaluate_the_logger_once() {
+    use binary_logger::{log_error, log_record_at, log_sampled, log_tagged};
+    use binary_logger::level::Level;
+    use binary_logger::tags::register_tag;
+
+    /// A Logger reached through a method counting its calls
+    struct Counted {
+        logger: Logger<4096>,
+        evaluations: usize,
+    }
+
+    impl Counted {
+        fn logger(&mut self) -> &mut Logger<4096> {
+            self.evaluations += 1;
+            &mut self.logger
+        }
+    }
+
+    let mut counted = Counted { logger: Logger::new(CountingHandler::new()), evaluations: 0 };
+    let audit = register_tag("audit");
+    let error = std::io::Error::other("disk gone");
+    log_record!(counted.logger(), "Once {}", 1).unwrap();
+    log_record!(counted.logger(), channel: "once", "Once {}", 2).unwrap();
+    log_record_at!(counted.logger(), Level::Warn, "Once {}", 3).unwrap();
+    log_record_at!(counted.logger(), Level::Trace, channel: "once", "Skipped {}", 4).unwrap();
+    log_tagged!(counted.logger(), tags = audit, "Once {}", 5).unwrap();
+    log_tagged!(counted.logger(), Level::Info, tags = audit, "Once {}", 6).unwrap();
+    log_sampled!(counted.logger(), key = 7u64, "Once {}", 7).unwrap();
+    log_error!(counted.logger(), "{} once", error).unwrap();
+    assert_eq!(counted.evaluations, 8);
+}
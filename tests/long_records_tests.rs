@@ -0,0 +1,151 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, LogError, LogValue, log_record, log_record_at, register_string};
+use binary_logger::features::FormatFeatures;
+use binary_logger::handlers::LevelRouter;
+use binary_logger::inspect::inspect;
+use binary_logger::level::Level;
+use binary_logger::wire::{self, Record, RecordTime, LONG_LENGTH, RECORD_TYPE_NORMAL};
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+const CAP: usize = 256 * 1024;
+
+fn strings(entries: &[LogEntry]) -> Vec<usize> {
+    entries.iter().map(|entry| match &entry.parameters[..] {
+        [LogValue::String(s)] => s.len(),
+        other => panic!("{:?}", other),
+    }).collect()
+}
+
+#[test]
+fn test_long_records_read_back() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let body = "x".repeat(100_000);
+    // A payload of exactly LONG_LENGTH bytes, with the 5 bytes of framing
+    // and 2 of type tags
+    let wide = "y".repeat(LONG_LENGTH as usize - 5 - 2);
+    {
+        let mut logger = Logger::<CAP>::new(CollectingHandler(data.clone()));
+        logger.set_global_sequence(true);
+        logger.set_header_compression(true);
+        log_record!(logger, "Body {}", body.as_str()).unwrap();
+        log_record!(logger, "Body {}", String::from("short")).unwrap();
+        log_record_at!(logger, Level::Warn, "Body {}", wide.as_str()).unwrap();
+        log_record!(logger, "Body {}", body.as_str()).unwrap();
+    }
+    let data = data.lock().unwrap();
+    let entries = read_all(&data);
+    assert_eq!(strings(&entries), [100_000, 5, wide.len(), 100_000]);
+    assert_eq!(entries[2].level, Some(Level::Warn));
+    assert!(entries.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+
+    // The wire module reads the same lengths in the first buffer, the base
+    // record first, and inspect shows them
+    let payloads: Vec<usize> = wire::records(&data[..wire::read_buffer_size(&data).unwrap()])
+        .map(Result::unwrap)
+        .map(|record| record.payload.len())
+        .filter(|&len| len > 1000)
+        .collect();
+    assert_eq!(payloads.len(), 2);
+    assert!(payloads[0] > 100_000);
+    assert_eq!(payloads[1], LONG_LENGTH as usize);
+    let mut text = Vec::new();
+    inspect(&data, &mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("payload_len=long"), "{}", text);
+    assert!(text.contains(&format!("payload_len={}", payloads[0])), "{}", text);
+}
+
+#[test]
+fn test_long_records_round_trip_through_the_wire_module() {
+    let payload = vec![7u8; 70_000];
+    let mut record = Record::new(RECORD_TYPE_NORMAL, RecordTime::Relative(40), 0x1_0001, &payload);
+    record.channel = Some(3);
+    assert!(record.is_long());
+
+    // 8-byte header, channel, wide format ID, long length, payload
+    assert_eq!(record.encoded_len(), 8 + 2 + 4 + 4 + 70_000);
+    let mut out = vec![0; record.encoded_len()];
+    record.encode(&mut out);
+    assert_eq!(&out[6..8], &LONG_LENGTH.to_le_bytes());
+    assert_eq!(&out[14..18], &70_000u32.to_le_bytes());
+    let (decoded, size) = wire::decode_record(&out).unwrap();
+    assert_eq!((decoded.format_id, decoded.channel), (Some(0x1_0001), Some(3)));
+    assert_eq!((decoded.payload, size), (&payload[..], out.len()));
+}
+
+#[test]
+fn test_records_larger_than_a_buffer_are_refused() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Before {}", 1).unwrap();
+        let body = "x".repeat(5000);
+        let error = log_record!(logger, "Body {}", body.as_str()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        let Some(&LogError::PayloadTooLarge { size, limit }) = LogError::of(&error) else { panic!("{:?}", error) };
+        assert!(size > 5000 && limit < 4096, "{} {}", size, limit);
+
+        // The limit reported is the largest payload that fits
+        let fits = "x".repeat(limit - (size - 5000));
+        assert!(log_record!(logger, "Body {}", "x".repeat(fits.len() + 2).as_str()).is_err());
+        log_record!(logger, "Body {}", fits.as_str()).unwrap();
+        log_record!(logger, "After {}", 2).unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    let formats: Vec<u32> = entries.iter().map(|entry| entry.format_id).collect();
+    assert_eq!(formats, [register_string("Before {}"), register_string("Body {}"), register_string("After {}")]);
+}
+
+#[test]
+fn test_long_records_need_their_format_feature() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<CAP>::new(CollectingHandler(data.clone()));
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::LONG_RECORDS);
+        let error = log_record!(logger, "Body {}", "x".repeat(70_000).as_str()).unwrap_err();
+        assert!(matches!(LogError::of(&error), Some(LogError::PayloadTooLarge { limit: 65_526, .. })));
+        log_record!(logger, "Body {}", "x".repeat(60_000).as_str()).unwrap();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(strings(&entries), [60_000]);
+    assert!(!reader.format_features().unwrap().contains(FormatFeatures::LONG_RECORDS));
+}
+
+#[test]
+fn test_max_payload_size() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<CAP>::new(CollectingHandler(data.clone()));
+        assert_eq!(logger.max_payload_size(), None);
+        logger.set_max_payload_size(Some(1024));
+        let error = log_record!(logger, "Body {}", "x".repeat(2000).as_str()).unwrap_err();
+        assert_eq!(LogError::of(&error), Some(&LogError::PayloadTooLarge { size: 2007, limit: 1024 }));
+        assert_eq!(error.to_string(), "record payload of 2007 bytes over the limit of 1024 bytes");
+        log_record!(logger, "Body {}", "x".repeat(1000).as_str()).unwrap();
+        assert_eq!(logger.stats().dropped_records, 0);
+    }
+    assert_eq!(strings(&read_all(&data.lock().unwrap())), [1000]);
+}
+
+#[test]
+fn test_routed_long_records_keep_their_length() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let archive = Arc::new(Mutex::new(Vec::new()));
+    {
+        let router = LevelRouter::new()
+            .route(Level::Error, CollectingHandler(errors.clone()))
+            .route_all(CollectingHandler(archive.clone()));
+        let mut logger = Logger::<CAP>::new(router);
+        logger.set_delta_timestamps(true);
+        log_record_at!(logger, Level::Info, "Body {}", "x".repeat(80_000).as_str()).unwrap();
+        log_record_at!(logger, Level::Error, "Body {}", "y".repeat(90_000).as_str()).unwrap();
+        log_record_at!(logger, Level::Error, "Body {}", "z".repeat(10).as_str()).unwrap();
+    }
+    assert_eq!(strings(&read_all(&errors.lock().unwrap())), [90_000, 10]);
+    assert_eq!(strings(&read_all(&archive.lock().unwrap())), [80_000, 90_000, 10]);
+}
@@ -0,0 +1,137 @@
+use binary_logger::{Logger, BufferHandler, LogReader, LogMerger, LogValue, log_record};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+struct CollectingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CollectingHandler {
+    fn new() -> Self {
+        Self {
+            data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+/// Builds a single buffer holding one base record followed by normal records
+/// with the given relative timestamps and optional sequence numbers.
+fn build_log(base_ts: u64, records: &[(u16, Option<u64>, i32)]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    // Buffer header (8 bytes), patched with the real size below
+    data.extend_from_slice(&0u64.to_le_bytes());
+
+    // Pure base timestamp record
+    data.push(1); // Type = 1 (base timestamp)
+    data.push(0); // Flags
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&8u16.to_le_bytes());
+    data.extend_from_slice(&base_ts.to_le_bytes());
+
+    for &(rel_ts, sequence, value) in records {
+        data.push(0); // Type = 0 (normal record)
+        data.push(if sequence.is_some() { 0x01 } else { 0 }); // Flags
+        data.extend_from_slice(&rel_ts.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+
+        let mut payload = vec![1];
+        payload.extend_from_slice(&4u32.to_le_bytes());
+        payload.extend_from_slice(&value.to_le_bytes());
+        data.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+
+        if let Some(sequence) = sequence {
+            data.extend_from_slice(&sequence.to_le_bytes());
+        }
+        data.extend_from_slice(&payload);
+        if !payload.len().is_multiple_of(2) {
+            data.push(0);
+        }
+    }
+
+    let size = data.len() as u64;
+    data[0..8].copy_from_slice(&size.to_le_bytes());
+    data
+}
+
+fn values(merger: LogMerger) -> Vec<i32> {
+    merger
+        .map(|entry| match entry.parameters.first() {
+            Some(LogValue::Integer(value)) => *value,
+            other => panic!("Expected integer parameter, got: {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_merge_by_timestamp() {
+    let a = build_log(1_000_000, &[(10, None, 1), (30, None, 3), (50, None, 5)]);
+    let b = build_log(1_000_000, &[(20, None, 2), (40, None, 4)]);
+
+    let merger = LogMerger::new(vec![LogReader::new(&a), LogReader::new(&b)]);
+    assert_eq!(values(merger), vec![1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn test_merge_ties_broken_by_sequence() {
+    // Every record has the same timestamp; only the sequence orders them
+    let a = build_log(1_000_000, &[(10, Some(7), 2), (10, Some(9), 4)]);
+    let b = build_log(1_000_000, &[(10, Some(3), 1), (10, Some(8), 3)]);
+
+    let merger = LogMerger::new(vec![LogReader::new(&a), LogReader::new(&b)]);
+    assert_eq!(values(merger), vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_merge_timestamps_are_monotonic() {
+    let a = build_log(2_000_000, &[(100, None, 1), (200, None, 2)]);
+    let b = build_log(1_000_000, &[(100, None, 3)]);
+    let c = build_log(3_000_000, &[]);
+
+    let merger = LogMerger::new(vec![LogReader::new(&a), LogReader::new(&b), LogReader::new(&c)]);
+    let entries: Vec<_> = merger.collect();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].timestamp, UNIX_EPOCH + Duration::from_micros(1_000_100));
+    for window in entries.windows(2) {
+        assert!(window[0].timestamp <= window[1].timestamp, "Merged timestamps should not go backwards");
+    }
+}
+
+#[test]
+fn test_merge_interleaved_loggers() {
+    const BUFFER_SIZE: usize = 1024;
+    let handler_a = CollectingHandler::new();
+    let handler_b = CollectingHandler::new();
+    let data_a = handler_a.data.clone();
+    let data_b = handler_b.data.clone();
+
+    {
+        let mut logger_a = Logger::<BUFFER_SIZE>::new(handler_a);
+        let mut logger_b = Logger::<BUFFER_SIZE>::new(handler_b);
+        logger_a.set_global_sequence(true);
+        logger_b.set_global_sequence(true);
+
+        // Alternate between loggers so most records share a timestamp
+        for i in 0..200 {
+            if i % 3 == 0 {
+                log_record!(logger_b, "Interleaved {}", i).unwrap();
+            } else {
+                log_record!(logger_a, "Interleaved {}", i).unwrap();
+            }
+        }
+    }
+
+    let data_a = data_a.lock().unwrap();
+    let data_b = data_b.lock().unwrap();
+    let merger = LogMerger::new(vec![LogReader::new(&data_a), LogReader::new(&data_b)]);
+
+    assert_eq!(values(merger), (0..200).collect::<Vec<_>>(), "Merge should restore the write order");
+}
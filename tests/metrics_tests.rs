@@ -0,0 +1,84 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use binary_logger::{LogReader, log_record};
+use binary_logger::metrics::{collect_series, BinaryRecorder, MetricKind, MetricUpdate};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[test]
+fn test_metrics_round_trip() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let recorder = BinaryRecorder::<65536>::new(CollectingHandler(data.clone()));
+    metrics::with_local_recorder(&recorder, || {
+        let requests = metrics::counter!("requests", "method" => "GET");
+        requests.increment(2);
+        recorder.with_logger(|logger| log_record!(logger, "Request {} done", 1)).unwrap();
+        requests.increment(3);
+        metrics::counter!("restarts").absolute(7);
+
+        let depth = metrics::gauge!("queue_depth");
+        depth.set(10.0);
+        depth.increment(2.5);
+        depth.decrement(4.0);
+
+        metrics::histogram!("latency_us").record(250.0);
+        metrics::histogram!("latency_us").record(120.0);
+    });
+    recorder.flush();
+
+    let data = data.lock().unwrap();
+
+    // Plain readers only see the log records
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].format(), "Request 1 done");
+
+    let mut reader = LogReader::new(&data);
+    reader.set_metrics(true);
+    let first = reader.read_entry().unwrap();
+    assert_eq!(first.format_string.as_deref(), Some("requests{method=GET}"));
+    assert_eq!(first.metric, Some(MetricUpdate::CounterIncrement(2)));
+    assert!(reader.read_entry().unwrap().metric.is_none());
+
+    let series = collect_series(&mut LogReader::new(&data));
+    let values = |key: &str| series[key].points.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+    assert_eq!(series["requests{method=GET}"].kind, MetricKind::Counter);
+    assert_eq!(values("requests{method=GET}"), [2.0, 5.0]);
+    assert_eq!(values("restarts"), [7.0]);
+    assert_eq!(series["queue_depth"].kind, MetricKind::Gauge);
+    assert_eq!(values("queue_depth"), [10.0, 12.5, 8.5]);
+    assert_eq!(series["latency_us"].kind, MetricKind::Histogram);
+    assert_eq!(values("latency_us"), [250.0, 120.0]);
+
+    let points = &series["queue_depth"].points;
+    assert!(points.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+}
+
+#[test]
+fn test_metrics_from_threads() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let recorder = BinaryRecorder::<4096>::new(CollectingHandler(data.clone()));
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            let recorder = recorder.clone();
+            scope.spawn(move || {
+                metrics::with_local_recorder(&recorder, || {
+                    let counter = metrics::counter!("jobs");
+                    for _ in 0..500 {
+                        counter.increment(1);
+                    }
+                });
+            });
+        }
+    });
+    recorder.flush();
+
+    let data = data.lock().unwrap();
+    let series = collect_series(&mut LogReader::new(&data));
+    let points = &series["jobs"].points;
+    assert_eq!(points.len(), 2000);
+    assert_eq!(points.last().unwrap().1, 2000.0);
+}
@@ -0,0 +1,83 @@
+use binary_logger::{Logger, BufferHandler, log_count, log_record, log_record_at, log_signal_safe};
+use binary_logger::alloc_guard::{CountingAllocator, assert_no_alloc, count_allocations};
+use binary_logger::level::Level;
+use binary_logger::signal_safe::AsyncSignalSafeLogger;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+
+struct NullHandler;
+
+impl BufferHandler for NullHandler {
+    fn handle_buffer(&self, _data: &[u8]) {}
+}
+
+/// Logs a mix of primitive arguments, through enough records to switch
+/// buffers several times.
+fn log_primitives(logger: &mut Logger<4096>) {
+    for i in 0..1000u32 {
+        log_record!(logger, "Request {} took {} ms, cached: {}", i, 1.5f64, i % 2 == 0).unwrap();
+        log_record!(logger, "Ratio {} of {}", 0.25f32, -7i16).unwrap();
+        log_record_at!(logger, Level::Warn, "Queue depth {}", i as u64).unwrap();
+        log_record!(logger, channel: "net", "Packet {} dropped", i).unwrap();
+        log_record!(logger, "Heartbeat",).unwrap();
+        log_count!(logger, "cache_miss").unwrap();
+    }
+}
+
+#[test]
+fn test_alloc_guard_counts() {
+    assert_eq!(count_allocations(|| 1 + 1).1, 0);
+    assert_eq!(count_allocations(|| vec![1u8; 16]).1, 1);
+
+    // Nested counts add up in the enclosing one
+    let ((_, inner), outer) = count_allocations(|| {
+        let _first = Box::new(1);
+        count_allocations(|| Box::new(2))
+    });
+    assert_eq!((inner, outer), (1, 2));
+}
+
+#[test]
+#[should_panic(expected = "expected no heap allocations, but 1 were made")]
+fn test_assert_no_alloc_fails_loudly() {
+    assert_no_alloc(|| String::from("allocates"));
+}
+
+#[test]
+fn test_log_record_does_not_allocate() {
+    let mut logger = Logger::<4096>::new(NullHandler);
+    // The first records register their strings and argument kinds
+    log_primitives(&mut logger);
+
+    assert_no_alloc(|| log_primitives(&mut logger));
+    assert!(logger.stats().buffers_flushed > 2);
+}
+
+#[test]
+fn test_encodings_do_not_allocate() {
+    let mut logger = Logger::<4096>::new(NullHandler);
+    logger.set_global_sequence(true);
+    logger.set_header_compression(true);
+    logger.set_delta_timestamps(true);
+    logger.set_level(Level::Info);
+    log_primitives(&mut logger);
+
+    assert_no_alloc(|| {
+        log_primitives(&mut logger);
+        log_record_at!(logger, Level::Debug, "Skipped by level {}", 1).unwrap();
+        logger.flush();
+    });
+}
+
+#[test]
+fn test_signal_safe_logger_does_not_allocate() {
+    let logger = AsyncSignalSafeLogger::<65536>::new(NullHandler);
+    log_signal_safe!(logger, Level::Warn, "Caught signal {}", 15i32);
+
+    assert_no_alloc(|| {
+        for i in 0..100i32 {
+            assert!(log_signal_safe!(logger, Level::Warn, "Caught signal {}", i));
+        }
+    });
+}
@@ -0,0 +1,104 @@
+use binary_logger::{BufferHandler, LogReader, log_record, log_record_at};
+use binary_logger::level::Level;
+use binary_logger::pool::LoggerPool;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+type Log = Arc<Mutex<Vec<u8>>>;
+
+/// Collects the buffers of every thread's Logger, one log per handler.
+#[derive(Clone, Default)]
+struct Sink(Arc<Mutex<Vec<Log>>>);
+
+impl Sink {
+    fn handler(&self) -> LogHandler {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        self.0.lock().unwrap().push(data.clone());
+        LogHandler(data)
+    }
+
+    fn messages(&self) -> Vec<String> {
+        let mut messages = Vec::new();
+        for data in self.0.lock().unwrap().iter() {
+            let data = data.lock().unwrap();
+            let mut reader = LogReader::new(&data);
+            messages.extend(std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()));
+        }
+        messages.sort();
+        messages
+    }
+}
+
+struct LogHandler(Log);
+
+impl BufferHandler for LogHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+#[test]
+fn test_pool_creates_logger_per_thread() {
+    let sink = Sink::default();
+    let handlers = sink.clone();
+    let pool = Arc::new(LoggerPool::<4096>::new(move || handlers.handler()));
+    assert!(pool.is_empty());
+
+    // Keep the workers alive until the pool has been inspected
+    let logged = Arc::new(Barrier::new(4));
+    let inspected = Arc::new(Barrier::new(4));
+    let workers: Vec<_> = (0..3).map(|i| {
+        let (pool, logged, inspected) = (pool.clone(), logged.clone(), inspected.clone());
+        thread::Builder::new().name(format!("worker-{}", i)).spawn(move || {
+            for j in 0..2 {
+                pool.with(|logger| log_record!(logger, "Worker {} record {}", i, j)).unwrap();
+            }
+            logged.wait();
+            inspected.wait();
+        }).unwrap()
+    }).collect();
+
+    logged.wait();
+    assert_eq!(pool.len(), 3);
+    let mut metrics = pool.metrics_all();
+    metrics.sort_by(|a, b| a.thread.cmp(&b.thread));
+    let threads: Vec<&str> = metrics.iter().map(|m| m.thread.as_str()).collect();
+    assert_eq!(threads, ["worker-0", "worker-1", "worker-2"]);
+    assert!(metrics.iter().all(|m| m.stats.records_written == 2 && m.stats.bytes_pending > 0));
+
+    // Flushing from another thread hands every Logger's buffer over
+    pool.flush_all();
+    assert!(pool.metrics_all().iter().all(|m| m.stats.buffers_flushed == 1 && m.stats.bytes_pending == 0));
+    assert_eq!(sink.messages().len(), 6);
+
+    inspected.wait();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert!(pool.is_empty(), "Loggers of exited threads should be dropped");
+}
+
+#[test]
+fn test_pool_set_level_all() {
+    let sink = Sink::default();
+    let handlers = sink.clone();
+    let pool = Arc::new(LoggerPool::<4096>::new(move || handlers.handler()));
+
+    pool.with(|logger| log_record_at!(logger, Level::Debug, "Before {}", 1)).unwrap();
+    pool.set_level_all(Level::Warn);
+    pool.with(|logger| log_record_at!(logger, Level::Debug, "Filtered {}", 2)).unwrap();
+    pool.with(|logger| log_record_at!(logger, Level::Error, "Kept {}", 3)).unwrap();
+
+    // Loggers created after the change get the level too
+    let worker_pool = pool.clone();
+    thread::spawn(move || {
+        worker_pool.with(|logger| {
+            assert_eq!(logger.level(), Level::Warn);
+            log_record_at!(logger, Level::Info, "Filtered {}", 4).unwrap();
+            log_record!(logger, "Unleveled {}", 5).unwrap();
+        });
+    }).join().unwrap();
+
+    pool.flush_all();
+    assert_eq!(sink.messages(), ["Before 1", "Kept 3", "Unleveled 5"]);
+}
@@ -0,0 +1,128 @@
+//! Logs written on one architecture must decode identically on the others.
+//!
+//! `tests/fixtures/portable_*.blog` were written by `write_fixture` on
+//! x86_64, and `portable_*.txt` hold their decoded entries. CI reads them
+//! on aarch64, wasm32 and a big-endian target as well. After an intended
+//! format change, rewrite them with
+//! `cargo test --test portability_tests -- --ignored regenerate_fixtures`.
+//!
+//! The fixtures use arguments that decode by size alone and an embedded
+//! dictionary, so they read the same in any process.
+
+mod common;
+
+use binary_logger::{Logger, LogReader, log_record, register_string};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use common::CollectingHandler;
+
+const FIXTURES: [&str; 2] = ["portable_plain", "portable_compact"];
+
+fn fixture(name: &str, extension: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{}.{}", name, extension))
+}
+
+/// Writes the fixture `name` with the logger settings it is named after.
+fn write_fixture(name: &str) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<1024>::new(CollectingHandler(data.clone()));
+        logger.set_embedded_dictionary(true);
+        // The fixtures hold records without type tags, read by size
+        logger.set_type_tags(false);
+        if name == "portable_compact" {
+            logger.set_header_compression(true);
+            logger.set_delta_timestamps(true);
+            logger.set_global_sequence(true);
+        }
+        let raw = register_string("Portable raw {} {}");
+        for i in 0..60i32 {
+            let (filled, price) = (i % 3 == 0, 101.25f64 + i as f64);
+            log_record!(logger, "Portable order {} filled {} at {}", i, filled, price).unwrap();
+            let (queue, depth) = (i - 30, 1u32 << (i % 32));
+            log_record!(logger, channel: "ops", "Portable queue {} depth {}", queue, depth).unwrap();
+            // A 3-byte argument leaves the record to be padded
+            logger.write_args(raw, &[b"abc", &(i as u32).to_le_bytes()]).unwrap();
+            if i % 20 == 0 {
+                // Past a 16-bit relative timestamp
+                std::thread::sleep(Duration::from_millis(70));
+            }
+        }
+    }
+    let data = data.lock().unwrap();
+    data.clone()
+}
+
+/// Returns the decoded entries of a log as lines of microseconds since the
+/// UNIX epoch, sequence number, channel and text.
+fn decode(data: &[u8]) -> Vec<String> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry())
+        .map(|entry| {
+            let micros = entry.timestamp.duration_since(UNIX_EPOCH).unwrap().as_micros();
+            let sequence = entry.sequence.map_or("-".to_string(), |sequence| sequence.to_string());
+            format!("{} {} {} {}", micros, sequence, entry.channel_name().unwrap_or("-"), entry.format())
+        })
+        .collect()
+}
+
+/// Drops the timestamps and sequence numbers of decoded lines, which depend
+/// on when the log was written and on the loggers before it.
+fn untimed(lines: &[String]) -> Vec<&str> {
+    lines.iter().map(|line| line.splitn(3, ' ').nth(2).unwrap()).collect()
+}
+
+#[test]
+fn test_fixtures_decode_identically() {
+    for name in FIXTURES {
+        let data = fs::read(fixture(name, "blog")).unwrap();
+        let expected = fs::read_to_string(fixture(name, "txt")).unwrap();
+        assert_eq!(decode(&data).join("\n") + "\n", expected, "{}", name);
+    }
+}
+
+#[test]
+fn test_this_build_writes_the_fixtures() {
+    for name in FIXTURES {
+        let expected = fs::read_to_string(fixture(name, "txt")).unwrap();
+        let expected: Vec<String> = expected.lines().map(str::to_string).collect();
+        let written = decode(&write_fixture(name));
+        assert_eq!(untimed(&written), untimed(&expected), "{}", name);
+
+        let micros: Vec<u64> = written.iter().map(|line| line.split(' ').next().unwrap().parse().unwrap()).collect();
+        assert!(micros.windows(2).all(|pair| pair[0] <= pair[1]), "{}", name);
+        let sequences: Vec<u64> = written.iter().filter_map(|line| line.split(' ').nth(1).unwrap().parse().ok()).collect();
+        assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]), "{}", name);
+        // The sleeps between records are kept
+        assert!(micros[micros.len() - 1] - micros[0] >= 200_000, "{}", name);
+    }
+}
+
+#[test]
+fn test_numbers_are_little_endian() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<1024>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Portable bytes {} {} {}", 0x0102_0304u32, 0x0506_0708_090a_0b0cu64, 3usize).unwrap();
+    }
+    let data = data.lock().unwrap();
+    assert_eq!(u64::from_le_bytes(data[..8].try_into().unwrap()) as usize, data.len());
+    let find = |bytes: &[u8]| data.windows(bytes.len()).any(|window| window == bytes);
+    assert!(find(&[4, 3, 2, 1]));
+    assert!(find(&[0x0c, 0x0b, 0x0a, 9, 8, 7, 6, 5]));
+    // Pointer-sized integers take 64 bits everywhere
+    assert!(find(&[8, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]));
+}
+
+#[test]
+#[ignore]
+fn regenerate_fixtures() {
+    fs::create_dir_all(fixture("portable", "blog").parent().unwrap()).unwrap();
+    for name in FIXTURES {
+        let data = write_fixture(name);
+        fs::write(fixture(name, "blog"), &data).unwrap();
+        fs::write(fixture(name, "txt"), decode(&data).join("\n") + "\n").unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use binary_logger::{Logger, BufferHandler, log_record, log_record_at};
+use binary_logger::level::Level;
+use binary_logger::proxy::{DecodingProxy, Listener, OutputFormat};
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use common::CollectingHandler;
+
+/// Sends every buffer to the proxy, as a producer host would.
+struct SocketHandler<W: Write>(Mutex<W>);
+
+impl<W: Write> BufferHandler for SocketHandler<W> {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.0.lock().unwrap().write_all(data).unwrap();
+    }
+}
+
+/// A destination collecting the proxy's output.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Unwritable;
+
+impl Write for Unwritable {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn wait_for(proxy: &DecodingProxy, entries: u64) {
+    let start = Instant::now();
+    while proxy.stats().entries < entries {
+        assert!(start.elapsed() < Duration::from_secs(10), "proxy decoded {:?}", proxy.stats());
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn test_text_and_ndjson_lines() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_dictionary_channel(true);
+        log_record!(logger, "Proxy request {} took {} ms", 7, 1.5f64).unwrap();
+        log_record_at!(logger, Level::Warn, "Proxy said \"{}\"", "hi\nthere").unwrap();
+    }
+    let data = data.lock().unwrap();
+
+    let text = Output::default();
+    let proxy = DecodingProxy::new(OutputFormat::Text).with_destination(text.clone());
+    assert_eq!(proxy.handle_connection(&data[..], "host-a:4000").unwrap(), 2);
+    let lines = text.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("host-a:4000 ") && lines[0].ends_with(" Proxy request 7 took 1.5 ms"), "{}", lines[0]);
+    assert!(lines[1].ends_with(" WARN Proxy said \"hi\\nthere\""), "{}", lines[1]);
+
+    let json = Output::default();
+    let proxy = DecodingProxy::new(OutputFormat::Ndjson).with_destination(json.clone());
+    proxy.handle_connection(&data[..], "host-a:4000").unwrap();
+    let lines = json.lines();
+    assert!(lines[0].starts_with("{\"producer\":\"host-a:4000\",\"timestamp_us\":"), "{}", lines[0]);
+    assert!(lines[0].ends_with("\"message\":\"Proxy request 7 took 1.5 ms\"}"), "{}", lines[0]);
+    assert!(lines[0].contains(",\"level\":null,\"channel\":0,\"channel_name\":null,\"sequence\":null,"), "{}", lines[0]);
+    assert!(lines[1].contains("\"level\":\"WARN\""), "{}", lines[1]);
+    assert!(lines[1].ends_with("\"message\":\"Proxy said \\\"hi\\nthere\\\"\"}"), "{}", lines[1]);
+}
+
+#[test]
+fn test_many_producers_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let output = Output::default();
+    let proxy = Arc::new(DecodingProxy::new(OutputFormat::Text).with_destination(output.clone()));
+    {
+        let proxy = proxy.clone();
+        thread::spawn(move || proxy.serve(&Listener::Tcp(listener)));
+    }
+
+    let producers: Vec<_> = (0..3u32).map(|producer| thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut logger = Logger::<1024>::new(SocketHandler(Mutex::new(stream)));
+        logger.set_dictionary_channel(true);
+        for i in 0..200u32 {
+            log_record!(logger, "Producer {} event {}", producer, i).unwrap();
+        }
+    })).collect();
+    for producer in producers {
+        producer.join().unwrap();
+    }
+
+    wait_for(&proxy, 600);
+    let lines = output.lines();
+    assert_eq!(lines.len(), 600);
+    for producer in 0..3 {
+        let events: Vec<&String> = lines.iter().filter(|line| line.contains(&format!("Producer {} event", producer))).collect();
+        assert_eq!(events.len(), 200);
+        // Each producer's lines come in order, with the same address
+        let address = events[0].split(' ').next().unwrap();
+        assert!(events.iter().all(|line| line.starts_with(address)));
+        assert!(events[199].ends_with(&format!("Producer {} event 199", producer)));
+    }
+    assert_eq!(proxy.stats().connections, 3);
+    assert_eq!(proxy.stats().connection_errors, 0);
+}
+
+#[test]
+fn test_unix_socket_and_failed_destination() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("proxy.sock");
+    // Socket-activated descriptors are told apart by their family
+    let listener = unsafe { Listener::from_raw_fd(UnixListener::bind(&path).unwrap().into_raw_fd()) };
+    assert!(matches!(listener, Listener::Unix(_)));
+    let tcp = unsafe { Listener::from_raw_fd(TcpListener::bind("127.0.0.1:0").unwrap().into_raw_fd()) };
+    assert!(matches!(tcp, Listener::Tcp(_)));
+
+    let output = Output::default();
+    let proxy = Arc::new(DecodingProxy::new(OutputFormat::Ndjson)
+        .with_destination(Unwritable)
+        .with_destination(output.clone()));
+    {
+        let proxy = proxy.clone();
+        thread::spawn(move || proxy.serve(&listener));
+    }
+    {
+        let mut logger = Logger::<4096>::new(SocketHandler(Mutex::new(UnixStream::connect(&path).unwrap())));
+        logger.set_dictionary_channel(true);
+        log_record!(logger, "Over a unix socket {}", 1).unwrap();
+        log_record!(logger, "Over a unix socket {}", 2).unwrap();
+    }
+
+    wait_for(&proxy, 2);
+    let lines = output.lines();
+    let producer = format!("{{\"producer\":\"{}#1\"", path.display());
+    assert!(lines.iter().all(|line| line.starts_with(&producer)), "{:?}", lines);
+    assert!(lines[1].ends_with("\"message\":\"Over a unix socket 2\"}"));
+    assert_eq!(proxy.stats().failed_destinations, 1);
+}
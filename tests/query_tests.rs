@@ -0,0 +1,160 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, register_string, log_record, log_record_at, log_tagged};
+use binary_logger::level::Level;
+use binary_logger::tags::register_tag;
+use binary_logger::query::LogQuery;
+use binary_logger::render::{render_line_with, RenderOptions};
+use regex::{Regex, RegexBuilder};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn write_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        for i in 0..30 {
+            log_record!(logger, "Query: job {} started", i).unwrap();
+            log_record!(logger, channel: "query-io", "Query: READ {} bytes", i * 512).unwrap();
+            log_record!(logger, "Query: heartbeat", ).unwrap();
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+#[test]
+fn test_query_matching() {
+    let data = write_log();
+
+    let query = LogQuery::matching(Regex::new(r"job 1\d started").unwrap());
+    let hits: Vec<_> = query.run(LogReader::new(&data)).collect();
+    assert_eq!(hits.len(), 10);
+    assert!(hits.iter().all(|hit| hit.entry.format_id == register_string("Query: job {} started")));
+    assert!(hits[0].line.ends_with("Query: job 10 started"), "{}", hits[0].line);
+
+    let query = LogQuery::matching(Regex::new(r"\[query-io\] .* 1024 bytes$").unwrap());
+    let hits: Vec<_> = query.run(LogReader::new(&data)).collect();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].entry.channel_name(), Some("query-io"));
+
+    // The query can be run again, and reader settings still apply
+    let mut reader = LogReader::new(&data);
+    reader.set_channel_filter(Some(&[0]));
+    let query = LogQuery::matching(Regex::new("Query").unwrap());
+    assert_eq!(query.run(reader).count(), 60);
+    assert_eq!(query.run(LogReader::new(&data)).count(), 90);
+}
+
+#[test]
+fn test_query_respects_builder_case_insensitivity() {
+    let data = write_log();
+
+    // The pre-filter must not skip "READ" although the pattern text is lowercase
+    let regex = RegexBuilder::new("read 512 bytes").case_insensitive(true).build().unwrap();
+    let hits: Vec<_> = LogQuery::matching(regex).run(LogReader::new(&data)).collect();
+    assert_eq!(hits.len(), 1);
+
+    let regex = Regex::new("read 512 bytes").unwrap();
+    assert_eq!(LogQuery::matching(regex).run(LogReader::new(&data)).count(), 0);
+}
+
+#[test]
+fn test_query_with_context() {
+    let data = write_log();
+
+    // Entries are "job i", "READ", "heartbeat" for i in 0..30
+    let query = LogQuery::matching(Regex::new(r"job (3|4|20) started").unwrap()).with_context(1, 1);
+    let hits: Vec<_> = query.run(LogReader::new(&data)).collect();
+    let summary: Vec<(bool, bool)> = hits.iter().map(|hit| (hit.is_context, hit.gap_before)).collect();
+    assert_eq!(summary, [
+        // heartbeat 2, job 3, READ 3
+        (true, false), (false, false), (true, false),
+        // heartbeat 3, job 4, READ 4: adjacent to the previous group
+        (true, false), (false, false), (true, false),
+        // heartbeat 19, job 20, READ 20
+        (true, true), (false, false), (true, false),
+    ]);
+    assert!(hits[1].line.ends_with("job 3 started"));
+    assert!(hits[0].line.ends_with("heartbeat"));
+    assert!(hits[2].line.ends_with("READ 1536 bytes"));
+
+    // Overlapping windows don't repeat entries
+    let query = LogQuery::matching(Regex::new("heartbeat").unwrap()).with_context(5, 5);
+    assert_eq!(query.run(LogReader::new(&data)).count(), 90);
+
+    // Without context there are no groups
+    let query = LogQuery::matching(Regex::new("job").unwrap());
+    assert!(query.run(LogReader::new(&data)).all(|hit| !hit.is_context && !hit.gap_before));
+}
+
+/// Entries with and without a level, a channel and tags
+fn write_decorated_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_level(Level::Trace);
+        let (billing, audit) = (register_tag("q-billing"), register_tag("q-audit"));
+        for i in 0..3 {
+            log_tagged!(logger, tags = billing, "Decorated: checkpoint reached", ).unwrap();
+            log_tagged!(logger, Level::Warn, tags = billing | audit, "Decorated: step {}", i).unwrap();
+            log_record!(logger, "Decorated: checkpoint reached", ).unwrap();
+            log_record!(logger, channel: "query-lv", "Decorated: step {}", i).unwrap();
+            for level in Level::ALL {
+                log_record_at!(logger, level, "Decorated: checkpoint reached", ).unwrap();
+                log_record_at!(logger, level, channel: "query-lv", "Decorated: step {}", i).unwrap();
+            }
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+/// Returns the lines of the entries a query yields and of those whose
+/// rendered line the regex matches, checked one by one.
+fn query_and_scan(data: &[u8], pattern: &str, options: &RenderOptions) -> (Vec<String>, Vec<String>) {
+    let regex = Regex::new(pattern).unwrap();
+    let found = LogQuery::matching(regex.clone()).with_render_options(options.clone())
+        .run(LogReader::new(data)).map(|hit| hit.line).collect();
+    let mut reader = LogReader::new(data);
+    let expected = std::iter::from_fn(|| reader.read_entry())
+        .map(|entry| render_line_with(&entry, options))
+        .filter(|line| regex.is_match(line))
+        .collect();
+    (found, expected)
+}
+
+#[test]
+fn test_query_hits_match_rendered_lines() {
+    let data = write_decorated_log();
+    let options = RenderOptions::default();
+    for pattern in [
+        "ERROR", r"^\d+\.\d+ WARN", r"^\d+\.\d+ \[query-lv\]", r"INFO \[query-lv\] Decorated: step 2",
+        r"DEBUG Decorated", r"\d \[query-lv\]", r"^\S+ Decorated: checkpoint reached$", "checkpoint reached$",
+        r"\{q-billing\} Decorated", r"WARN \{q-billing\|q-audit\} Decorated: step 0", r"\d \{\S+\} Decorated",
+    ] {
+        let (found, expected) = query_and_scan(&data, pattern, &options);
+        assert!(!expected.is_empty(), "pattern {}", pattern);
+        assert_eq!(found, expected, "pattern {}", pattern);
+    }
+}
+
+#[test]
+fn test_query_hits_match_lines_with_configured_timestamps() {
+    let data = write_decorated_log();
+    for (flag, value, patterns) in [
+        ("--time-format", "rfc3339", vec![r"T.*checkpoint", r"Z ERROR", r"^\d{4}-\d\d-\d\dT\S+ \[query-lv\]"]),
+        ("--time-format", "%H:%M:%S on %Y", vec![r"on \d{4} WARN", r"^\d\d:\d\d:\d\d on \d+ Decorated"]),
+        ("--time-digits", "0", vec![r"^\d+ INFO", r"^\d+ \[query-lv\] Decorated: step 1"]),
+    ] {
+        let mut options = RenderOptions::default();
+        options.set_flag(flag, value).unwrap();
+        for pattern in patterns {
+            let (found, expected) = query_and_scan(&data, pattern, &options);
+            assert!(!expected.is_empty(), "pattern {} with {} {}", pattern, flag, value);
+            assert_eq!(found, expected, "pattern {} with {} {}", pattern, flag, value);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, RecordDecoder, RECORD_TYPE_USER_MIN, log_record};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+/// Decodes `[bid i32][ask i32]` snapshots
+struct QuoteDecoder;
+
+impl RecordDecoder for QuoteDecoder {
+    fn decode(&self, format_id: u32, payload: &[u8]) -> Vec<LogValue> {
+        let mut values = vec![LogValue::Integer(format_id as i32)];
+        values.extend(payload.chunks_exact(4)
+            .map(|chunk| LogValue::Integer(i32::from_le_bytes(chunk.try_into().unwrap()))));
+        values
+    }
+}
+
+/// A log of two entries with a buffer holding one application record between them.
+fn write_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+    log_record!(logger, "Before quote {}", 1).unwrap();
+    logger.flush();
+    let split = data.lock().unwrap().len();
+    log_record!(logger, "After quote {}", 2).unwrap();
+    logger.flush();
+    let mut data = data.lock().unwrap().clone();
+
+    let mut record = vec![RECORD_TYPE_USER_MIN, 0, 0x10, 0x00, 7, 0, 8, 0];
+    record.extend_from_slice(&100i32.to_le_bytes());
+    record.extend_from_slice(&101i32.to_le_bytes());
+    let mut buffer = ((8 + record.len()) as u64).to_le_bytes().to_vec();
+    buffer.extend(record);
+    data.splice(split..split, buffer);
+    data
+}
+
+#[test]
+fn test_registered_decoder() {
+    let data = write_log();
+    let mut reader = LogReader::new(&data);
+    reader.register_decoder(RECORD_TYPE_USER_MIN, Box::new(QuoteDecoder));
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].custom_type, None);
+    assert_eq!(entries[1].custom_type, Some(RECORD_TYPE_USER_MIN));
+    let values: Vec<String> = entries[1].parameters.iter().map(|v| v.to_string()).collect();
+    assert_eq!(values, ["7", "100", "101"]);
+    assert_eq!(entries[2].format(), "After quote 2");
+}
+
+#[test]
+fn test_unregistered_records_are_skipped() {
+    let data = write_log();
+    let mut plain = LogReader::new(&data);
+    let skipped: Vec<_> = std::iter::from_fn(|| plain.read_entry()).collect();
+    let mut decoding = LogReader::new(&data);
+    decoding.register_decoder(RECORD_TYPE_USER_MIN, Box::new(QuoteDecoder));
+    let decoded: Vec<_> = std::iter::from_fn(|| decoding.read_entry()).collect();
+
+    // The entries around the record are read the same way with or without its decoder
+    assert_eq!(skipped.len(), 2);
+    assert_eq!(skipped[1].format(), "After quote 2");
+    assert_eq!(skipped[1].timestamp, decoded[2].timestamp);
+}
+
+#[test]
+#[should_panic]
+fn test_library_record_types_are_rejected() {
+    let mut reader = LogReader::new(&[]);
+    reader.register_decoder(RECORD_TYPE_USER_MIN - 1, Box::new(QuoteDecoder));
+}
+
+#[test]
+fn test_write_custom() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_global_sequence(true);
+        // The first record of a stream needs a new time base
+        let mut quote = 200i32.to_le_bytes().to_vec();
+        quote.extend_from_slice(&201i32.to_le_bytes());
+        logger.write_custom(RECORD_TYPE_USER_MIN, &quote).unwrap();
+        log_record!(logger, "After custom {}", 3).unwrap();
+
+        let error = logger.write_custom(RECORD_TYPE_USER_MIN - 1, &[]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+
+    let mut reader = LogReader::new(&data);
+    reader.register_decoder(RECORD_TYPE_USER_MIN, Box::new(QuoteDecoder));
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].custom_type, Some(RECORD_TYPE_USER_MIN));
+    let values: Vec<String> = entries[0].parameters.iter().map(|v| v.to_string()).collect();
+    assert_eq!(values, ["0", "200", "201"]);
+    assert!(entries[0].timestamp > std::time::UNIX_EPOCH);
+    assert!(entries[1].timestamp >= entries[0].timestamp);
+    assert_eq!(entries[1].sequence, entries[0].sequence.map(|s| s + 1));
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_record};
+use binary_logger::render::{render_line, render_line_cached, render_line_with, render_text, render_text_cached, RenderCache, RenderOptions, TimeFormat, TimeZone};
+use binary_logger::instant::LogInstant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use common::CollectingHandler;
+
+#[test]
+fn test_render_text() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Rendered value {}", 42).unwrap();
+        log_record!(logger, channel: "render-net", "Rendered flag {}", true).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+
+    let text = render_text(&data);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(" Rendered value 42"), "{}", lines[0]);
+    assert!(lines[1].ends_with(" [render-net] Rendered flag true"), "{}", lines[1]);
+
+    // Lines start with seconds.micros since the epoch
+    let mut reader = LogReader::new(&data);
+    let entry = reader.read_entry().unwrap();
+    let micros = entry.timestamp.duration_since(std::time::UNIX_EPOCH).unwrap().as_micros();
+    let expected = format!("{}.{:06} ", micros / 1_000_000, micros % 1_000_000);
+    assert!(render_line(&entry).starts_with(&expected));
+}
+
+#[test]
+fn test_render_options_time() {
+    let time = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+    let options = |time_zone, time_format, fraction_digits| RenderOptions { time_zone, time_format, fraction_digits };
+
+    assert_eq!(RenderOptions::default().format_time(time), "1700000000.123456");
+    assert_eq!(options(TimeZone::Utc, TimeFormat::Epoch, 0).format_time(time), "1700000000");
+    assert_eq!(options(TimeZone::Utc, TimeFormat::Epoch, 9).format_time(time), "1700000000.123456000");
+    assert_eq!(options(TimeZone::Utc, TimeFormat::Rfc3339, 6).format_time(time), "2023-11-14T22:13:20.123456Z");
+    assert_eq!(options(TimeZone::Fixed(-(5 * 3600 + 1800)), TimeFormat::Rfc3339, 3).format_time(time),
+        "2023-11-14T16:43:20.123-05:30");
+    let custom = TimeFormat::Strftime("%d/%m/%Y %H:%M:%S%.3f".to_string());
+    assert_eq!(options(TimeZone::Fixed(3600), custom, 6).format_time(time), "14/11/2023 23:13:20.123");
+
+    assert_eq!("utc".parse(), Ok(TimeZone::Utc));
+    assert_eq!("local".parse(), Ok(TimeZone::Local));
+    assert_eq!("+02:00".parse(), Ok(TimeZone::Fixed(7200)));
+    assert_eq!("-0930".parse(), Ok(TimeZone::Fixed(-34200)));
+    assert!("02:00".parse::<TimeZone>().is_err());
+    assert!("+25".parse::<TimeZone>().is_err());
+    assert_eq!("rfc3339".parse(), Ok(TimeFormat::Rfc3339));
+    assert_eq!("%H:%M".parse(), Ok(TimeFormat::Strftime("%H:%M".to_string())));
+    assert!("%Q".parse::<TimeFormat>().is_err());
+
+    let mut flags = RenderOptions::default();
+    flags.set_flag("--tz", "+01:00").unwrap();
+    flags.set_flag("--time-format", "rfc3339").unwrap();
+    flags.set_flag("--time-digits", "0").unwrap();
+    assert_eq!(flags.format_time(time), "2023-11-14T23:13:20+01:00");
+    assert!(flags.set_flag("--time-digits", "10").is_err());
+}
+
+#[test]
+fn test_render_options_entries() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, channel: "render-opts", "Rendered at {}", LogInstant::now()).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+    let entry = LogReader::new(&data).read_entry().unwrap();
+
+    let options = RenderOptions { time_format: TimeFormat::Rfc3339, fraction_digits: 3, ..RenderOptions::default() };
+    let time = options.format_time(entry.timestamp);
+    assert!(time.ends_with('Z') && time.len() == "2023-11-14T22:13:20.123Z".len(), "{}", time);
+
+    // Both the line's timestamp and instant arguments follow the options
+    let line = render_line_with(&entry, &options);
+    let LogValue::Instant(instant) = entry.parameters[0] else { panic!("expected an instant") };
+    assert_eq!(line, format!("{} [render-opts] Rendered at {}", time, options.format_time(instant)));
+    assert_eq!(entry.format(), format!("Rendered at {}", RenderOptions::default().format_time(instant)));
+}
+
+#[test]
+fn test_render_cache() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<65536>::new(CollectingHandler(data.clone()));
+        for i in 0..100u32 {
+            log_record!(logger, "Heartbeat {} ok", i % 3).unwrap();
+            log_record!(logger, "Request at {}", LogInstant::now()).unwrap();
+        }
+        log_record!(logger, "Heartbeat {} ok", 3u64).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+
+    let options = RenderOptions::default();
+    let mut cache = RenderCache::new(2);
+    let mut reader = LogReader::new(&data);
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry() {
+        assert_eq!(render_line_cached(&entry, &options, &mut cache), render_line_with(&entry, &options));
+        count += 1;
+    }
+    assert_eq!(count, 201);
+
+    // Instants are formatted every time, and three heartbeat messages in
+    // turn evict each other from two slots
+    assert_eq!(cache.stats().hits, 0);
+    assert_eq!(cache.len(), 2);
+
+    let mut cache = RenderCache::new(RenderCache::DEFAULT_CAPACITY);
+    let mut reader = LogReader::new(&data);
+    while let Some(entry) = reader.read_entry() {
+        cache.message(&entry, &options);
+    }
+    // A u64 payload differs from the u32 ones, so it's a miss of its own
+    assert_eq!(cache.stats().misses, 100 + 3 + 1);
+    assert_eq!(cache.stats().hits, 97);
+    assert_eq!(cache.len(), 4);
+
+    assert_eq!(render_text_cached(&data, &options, &mut RenderCache::new(8)), render_text(&data));
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, log_record};
+use binary_logger::retention::Expiry;
+use binary_logger::string_registry::register_string;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use common::CollectingHandler;
+
+const DAY: Duration = Duration::from_secs(86400);
+
+/// Returns the time, channel and text of every entry.
+fn entries(data: &[u8]) -> Vec<(SystemTime, Option<String>, String)> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry())
+        .map(|entry| (entry.timestamp, entry.channel_name().map(str::to_string), entry.format()))
+        .collect()
+}
+
+#[test]
+fn test_expired_channels_are_removed_keeping_times() {
+    for (delta, compression) in [(false, false), (true, true)] {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut logger = Logger::<1024>::new(CollectingHandler(data.clone()));
+            logger.set_delta_timestamps(delta);
+            logger.set_header_compression(compression);
+            logger.set_channel_retention(register_string("pii"), Some(30 * DAY));
+            logger.set_channel_retention(register_string("ops"), Some(365 * DAY));
+            assert_eq!(logger.channel_retention(register_string("pii")), Some(30 * DAY));
+            for i in 0..300u32 {
+                // The same format on both channels, so records lean on the
+                // format ID of removed ones
+                log_record!(logger, channel: "pii", "Retention request {}", i).unwrap();
+                if i % 3 == 0 {
+                    log_record!(logger, channel: "ops", "Retention request {}", i).unwrap();
+                }
+                log_record!(logger, "Retention heartbeat {}", i).unwrap();
+            }
+        }
+        let data = data.lock().unwrap();
+        let before = entries(&data);
+        assert_eq!(before.len(), 700);
+
+        // Nothing has expired yet
+        let fresh = Expiry::new(SystemTime::now()).expire(&data).unwrap();
+        assert_eq!((fresh.entries, fresh.expired_entries()), (700, 0));
+        assert_eq!(fresh.data, *data);
+
+        let later = Expiry::new(SystemTime::now() + 60 * DAY).expire(&data).unwrap();
+        assert_eq!(later.expired_entries(), 300);
+        assert_eq!(later.expired.get(&register_string("pii")), Some(&300));
+        let expected: Vec<_> = before.iter().filter(|(_, channel, _)| channel.as_deref() != Some("pii")).cloned().collect();
+        assert_eq!(entries(&later.data), expected);
+
+        let much_later = Expiry::new(SystemTime::now() + 400 * DAY).expire(&later.data).unwrap();
+        assert_eq!(much_later.expired_entries(), 100);
+        assert!(entries(&much_later.data).iter().all(|(_, channel, text)| channel.is_none() && text.starts_with("Retention heartbeat")));
+    }
+}
+
+#[test]
+fn test_retention_applies_from_its_table_on() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, channel: "pii", "Before retention {}", 1).unwrap();
+        logger.set_channel_retention(register_string("pii"), Some(DAY));
+        log_record!(logger, channel: "pii", "With retention {}", 2).unwrap();
+        logger.set_channel_retention(register_string("pii"), None);
+        assert_eq!(logger.channel_retention(register_string("pii")), None);
+        log_record!(logger, channel: "pii", "After retention {}", 3).unwrap();
+    }
+    let data = data.lock().unwrap();
+
+    let expired = Expiry::new(SystemTime::now() + 2 * DAY).expire(&data).unwrap();
+    assert_eq!(expired.expired_entries(), 1);
+    let texts: Vec<String> = entries(&expired.data).into_iter().map(|(_, _, text)| text).collect();
+    assert_eq!(texts, ["Before retention 1", "After retention 3"]);
+}
+
+#[test]
+fn test_segments_and_audit_chain() {
+    // Segments are expired in order, timed from earlier ones
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<512>::new(CollectingHandler(data.clone()));
+        logger.set_channel_retention(register_string("pii"), Some(DAY));
+        for i in 0..100u32 {
+            log_record!(logger, channel: "pii", "Segmented {}", i).unwrap();
+            log_record!(logger, "Segment filler {}", i).unwrap();
+        }
+    }
+    let data = data.lock().unwrap();
+    let first_size = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+    let mut expiry = Expiry::new(SystemTime::now() + 2 * DAY);
+    let mut joined = expiry.expire(&data[..first_size]).unwrap().data;
+    joined.extend(expiry.expire(&data[first_size..]).unwrap().data);
+    let expected: Vec<_> = entries(&data).into_iter().filter(|(_, channel, _)| channel.is_none()).collect();
+    assert_eq!(entries(&joined), expected);
+
+    let chained = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(chained.clone()));
+        logger.set_audit_chain(true);
+        logger.set_channel_retention(register_string("pii"), Some(DAY));
+        log_record!(logger, channel: "pii", "Chained {}", 1).unwrap();
+    }
+    let chained = chained.lock().unwrap();
+    assert!(Expiry::new(SystemTime::now()).expire(&chained).is_ok());
+    let err = Expiry::new(SystemTime::now() + 2 * DAY).expire(&chained).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
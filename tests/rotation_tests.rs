@@ -0,0 +1,111 @@
+use binary_logger::{Logger, LogReader, log_record};
+use binary_logger::log_reader::is_metadata_buffer;
+use binary_logger::rotation::{segments, RotatingFileHandler};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn names(paths: &[PathBuf]) -> Vec<String> {
+    paths.iter().map(|path| path.file_name().unwrap().to_str().unwrap().to_string()).collect()
+}
+
+fn texts(data: &[u8]) -> Vec<String> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect()
+}
+
+fn read_all(paths: &[PathBuf]) -> Vec<u8> {
+    paths.iter().flat_map(|path| fs::read(path).unwrap()).collect()
+}
+
+#[test]
+fn test_files_rotate_by_size_under_enumerable_names() {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let handler = RotatingFileHandler::new(dir.path(), "orders").unwrap().max_file_size(4096);
+        assert_eq!(handler.active_path(), dir.path().join("orders.blog"));
+        let mut logger = Logger::<1024>::new(handler);
+        for i in 0..2000u32 {
+            log_record!(logger, "Rotated order {} filled", i).unwrap();
+        }
+    }
+    let files = segments(dir.path(), "orders").unwrap();
+    assert!(files.len() > 3, "{:?}", files);
+    assert_eq!(names(&files[..2]), ["orders.000001.blog", "orders.000002.blog"]);
+    assert_eq!(names(&files[files.len() - 1..]), ["orders.blog"]);
+    assert!(files.iter().all(|file| fs::metadata(file).unwrap().len() <= 4096));
+
+    // Read oldest first, the files are the whole log
+    let entries = texts(&read_all(&files));
+    let expected: Vec<_> = (0..2000).map(|i| format!("Rotated order {} filled", i)).collect();
+    assert_eq!(entries, expected);
+
+    // Other logs in the directory are left out
+    fs::write(dir.path().join("payments.000001.blog"), b"").unwrap();
+    fs::write(dir.path().join("orders.1.blog"), b"").unwrap();
+    assert_eq!(segments(dir.path(), "orders").unwrap(), files);
+}
+
+#[test]
+fn test_oldest_files_are_removed_and_the_rest_decode_on_their_own() {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let handler = RotatingFileHandler::new(dir.path(), "app").unwrap().max_file_size(2048).max_files(3);
+        let mut logger = Logger::<512>::new(handler);
+        logger.set_dictionary_channel(true);
+        for i in 0..300u32 {
+            log_record!(logger, "Kept request {}", i).unwrap();
+        }
+    }
+    let files = segments(dir.path(), "app").unwrap();
+    assert_eq!(files.len(), 3);
+    assert_eq!(names(&files[2..]), ["app.blog"]);
+
+    let mut last: Option<u32> = None;
+    for file in &files {
+        let data = fs::read(file).unwrap();
+        assert!(is_metadata_buffer(&data), "{} starts with the metadata", file.display());
+        let entries = texts(&data);
+        assert!(!entries.is_empty());
+        let first: u32 = entries[0].rsplit(' ').next().unwrap().parse().unwrap();
+        if let Some(last) = last {
+            assert_eq!(first, last + 1);
+        }
+        last = entries.last().map(|entry| entry.rsplit(' ').next().unwrap().parse().unwrap());
+    }
+    assert_eq!(last, Some(299));
+}
+
+#[test]
+fn test_files_rotate_when_the_interval_ends() {
+    let dir = tempfile::tempdir().unwrap();
+    let handler = RotatingFileHandler::new(dir.path(), "ticks").unwrap().interval(Duration::from_millis(100));
+    let mut logger = Logger::<4096>::new(handler);
+    let tick = 1u32;
+    log_record!(logger, "Tick {}", tick).unwrap();
+    logger.flush();
+    std::thread::sleep(Duration::from_millis(150));
+    log_record!(logger, "Tick {}", tick + 1).unwrap();
+    logger.flush();
+    drop(logger);
+
+    let files = segments(dir.path(), "ticks").unwrap();
+    assert_eq!(names(&files), ["ticks.000001.blog", "ticks.blog"]);
+    assert_eq!(texts(&fs::read(&files[0]).unwrap()), ["Tick 1"]);
+}
+
+#[test]
+fn test_a_new_run_rotates_the_last_active_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let run = |dir: &Path, n: u32| {
+        let mut logger = Logger::<4096>::new(RotatingFileHandler::new(dir, "runs").unwrap());
+        log_record!(logger, "Run {}", n).unwrap();
+    };
+    run(dir.path(), 1);
+    run(dir.path(), 2);
+    run(dir.path(), 3);
+    let files = segments(dir.path(), "runs").unwrap();
+    assert_eq!(names(&files), ["runs.000001.blog", "runs.000002.blog", "runs.blog"]);
+    assert_eq!(texts(&fs::read(&files[2]).unwrap()), ["Run 3"]);
+    assert_eq!(texts(&read_all(&files)), ["Run 1", "Run 2", "Run 3"]);
+}
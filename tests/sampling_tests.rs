@@ -0,0 +1,62 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_sampled};
+use binary_logger::level::Level;
+use binary_logger::sampling::KeySampler;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[test]
+fn test_key_sampler() {
+    let sampler = KeySampler::new(0.1);
+    let kept: Vec<u64> = (0..10_000u64).filter(|key| sampler.keep(key)).collect();
+    assert!((850..1150).contains(&kept.len()), "kept {} keys", kept.len());
+
+    // Raising the rate keeps the same keys and more
+    let wider = KeySampler::new(0.2);
+    assert!(kept.iter().all(|key| wider.keep(key)));
+
+    // Another seed selects other keys
+    let reseeded = KeySampler::new(0.1).with_seed(7);
+    assert!(kept.iter().any(|key| !reseeded.keep(key)));
+
+    assert!((0..1000u64).all(|key| KeySampler::new(1.0).keep(&key)));
+    assert!((0..1000u64).all(|key| !KeySampler::new(0.0).keep(&key)));
+    assert_eq!(KeySampler::new(1.0).rate(), 1.0);
+
+    let names: Vec<String> = (0..1000).map(|i| format!("user-{}", i)).collect();
+    assert!(names.iter().all(|name| sampler.keep(name) == sampler.keep(name.as_str())));
+}
+
+#[test]
+fn test_sampled_logging_keeps_whole_sequences() {
+    let sampler = KeySampler::new(0.25);
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<1024>::new(CollectingHandler(data.clone()));
+        logger.set_sampler(Some(sampler));
+        for user in 0..200u32 {
+            log_sampled!(logger, key = user, "User {} signed in", user).unwrap();
+            log_sampled!(logger, key = user, "User {} viewed {}", user, 3).unwrap();
+            log_sampled!(logger, Level::Warn, key = user, "User {} failed checkout", user).unwrap();
+        }
+        let stats = logger.stats();
+        assert_eq!(stats.records_written + stats.sampled_out, 600);
+        assert!(stats.sampled_out > 0);
+    }
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let mut events: BTreeMap<u32, usize> = BTreeMap::new();
+    while let Some(entry) = reader.read_entry() {
+        match entry.parameters.first() {
+            Some(LogValue::Unsigned(user)) => *events.entry(*user as u32).or_default() += 1,
+            other => panic!("Expected user ID, got: {:?}", other),
+        }
+    }
+
+    let expected: Vec<u32> = (0..200u32).filter(|user| sampler.keep(user)).collect();
+    assert_eq!(events.keys().copied().collect::<Vec<_>>(), expected);
+    assert!(events.values().all(|&count| count == 3));
+}
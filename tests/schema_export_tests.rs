@@ -0,0 +1,87 @@
+use binary_logger::register_string;
+use binary_logger::schema_export::flatbuffers_schema;
+
+#[test]
+fn test_schema_lists_registered_events() {
+    let id = register_string("Schema export: {} items");
+    let schema = flatbuffers_schema();
+
+    assert!(schema.contains(&format!("  Event{} = {},", id, id)), "Registered event should be in the EventId enum");
+    assert!(schema.contains("/// Schema export: {} items"), "Format string should be documented in the schema");
+    assert!(schema.contains("InternalBufferSwitchStart = 65280,"), "Internal events should be in the EventId enum");
+    assert!(schema.contains("root_type Log;"));
+    assert!(schema.contains("file_identifier \"BLOG\";"));
+}
+
+#[test]
+fn test_schema_event_ids_ascending() {
+    register_string("Schema order: first");
+    register_string("Schema order: second");
+
+    let ids: Vec<u32> = flatbuffers_schema()
+        .lines()
+        .filter_map(|line| line.trim().strip_suffix(','))
+        .filter_map(|line| line.split_once(" = "))
+        .filter(|(name, _)| name.starts_with("Event") || name.starts_with("Internal"))
+        .map(|(_, value)| value.parse().unwrap())
+        .collect();
+
+    assert!(!ids.is_empty());
+    assert!(ids.windows(2).all(|w| w[0] < w[1]), "FlatBuffers enum values must be ascending");
+}
+
+#[cfg(feature = "flatbuffers")]
+mod export {
+    use binary_logger::{Logger, BufferHandler, LogReader, log_record};
+    use binary_logger::schema_export::export_flatbuffers;
+    use flatbuffers::{ForwardsUOffset, Table, Vector};
+    use std::sync::{Arc, Mutex};
+
+    struct CollectingHandler {
+        data: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl BufferHandler for CollectingHandler {
+        fn handle_buffer(&self, data: &[u8]) {
+            self.data.lock().unwrap().extend_from_slice(data);
+        }
+    }
+
+    type Tables<'a> = Vector<'a, ForwardsUOffset<Table<'a>>>;
+
+    #[test]
+    fn test_export_roundtrip() {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut logger = Logger::<4096>::new(CollectingHandler { data: data.clone() });
+            log_record!(logger, "Exported {} with {}", 7, true).unwrap();
+            log_record!(logger, "Exported value {}", 2.5).unwrap();
+        }
+
+        let data = data.lock().unwrap();
+        let mut reader = LogReader::new(&data);
+        let exported = export_flatbuffers(std::iter::from_fn(|| reader.read_entry()));
+
+        assert!(flatbuffers::buffer_has_identifier(&exported, "BLOG", false));
+        unsafe {
+            let root = flatbuffers::root_unchecked::<Table>(&exported);
+            let entries = root.get::<ForwardsUOffset<Tables>>(6, None).unwrap();
+            let formats = root.get::<ForwardsUOffset<Tables>>(4, None).unwrap();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(formats.len(), 2);
+
+            let first = entries.get(0);
+            assert!(first.get::<u64>(4, Some(0)).unwrap() > 0, "Timestamp should be exported");
+            let args = first.get::<ForwardsUOffset<Tables>>(12, None).unwrap();
+            assert_eq!(args.len(), 2);
+            assert_eq!(args.get(0).get::<u8>(4, Some(0)), Some(1));
+            assert_eq!(args.get(0).get::<i32>(6, Some(0)), Some(7));
+            assert_eq!(args.get(1).get::<u8>(4, Some(0)), Some(2));
+            assert_eq!(args.get(1).get::<bool>(8, Some(false)), Some(true));
+
+            let format_id = first.get::<u32>(6, Some(0)).unwrap();
+            let format = formats.iter().find(|f| f.get::<u32>(4, Some(0)) == Some(format_id)).unwrap();
+            assert_eq!(format.get::<ForwardsUOffset<&str>>(6, None), Some("Exported {} with {}"));
+        }
+    }
+}
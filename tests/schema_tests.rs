@@ -0,0 +1,121 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, LogValue, log_record, log_record_at, register_string};
+use binary_logger::level::Level;
+use binary_logger::schema::{ArgType, SchemaRegistry};
+use std::io;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[test]
+fn test_captured_arguments_are_checked() {
+    let schema = SchemaRegistry::new()
+        .with_event("Schema order {} of {} at {}", &[ArgType::U32, ArgType::Str, ArgType::F32])
+        .with_event("Schema state {}", &[ArgType::Interned]);
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_schema_registry(Some(Arc::new(schema)));
+        logger.set_strict_schema(true);
+        let customer = String::from("acme");
+
+        log_record!(logger, "Schema order {} of {} at {}", 7u32, customer, 9.5f32).unwrap();
+        log_record_at!(logger, Level::Warn, "Schema state {}", "degraded").unwrap();
+
+        let err = log_record!(logger, "Schema order {} of {} at {}", 7u64, customer, 9.5f32).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().ends_with("\"Schema order {} of {} at {}\": argument 0 is a 8-byte value, the schema has a 4-byte value"), "{}", err);
+        let err = log_record!(logger, "Schema order {} of {} at {}", 7u32, customer, 9.5f64).unwrap_err();
+        assert!(err.to_string().ends_with("argument 2 is a 8-byte value, the schema has a f32"), "{}", err);
+        let err = log_record!(logger, "Schema order {} of {} at {}", 7u32, customer).unwrap_err();
+        assert!(err.to_string().ends_with("2 arguments, the schema has 3"), "{}", err);
+        let err = log_record!(logger, "Schema state {}", customer).unwrap_err();
+        assert!(err.to_string().ends_with("argument 0 is a string, the schema has a interned literal"), "{}", err);
+
+        // Format strings without a schema are written unchecked
+        log_record!(logger, "Schema-free {}", 1u32).unwrap();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let texts: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(texts, ["Schema order 7 of acme at 9.5", "Schema state degraded", "Schema-free 1"]);
+}
+
+#[test]
+fn test_raw_writes_check_counts_and_sizes() {
+    let order = register_string("Schema raw order {} x {}");
+    let mut schema = SchemaRegistry::new();
+    schema.insert(order, vec![ArgType::U32, ArgType::Any]);
+    assert_eq!(schema.get(order), Some(&[ArgType::U32, ArgType::Any][..]));
+    assert!(schema.check(order, &[&[1, 0, 0, 0], b"anything"]).is_ok());
+
+    let mut logger = Logger::<4096>::new(CollectingHandler(Arc::new(Mutex::new(Vec::new()))));
+    logger.set_schema_registry(Some(Arc::new(schema)));
+    logger.set_strict_schema(true);
+    logger.write_args(order, &[&3u32.to_le_bytes(), &[1]]).unwrap();
+    assert!(logger.write_args(order, &[&3u64.to_le_bytes(), &[1]]).is_err());
+    assert!(logger.write_args_at(Level::Info, 0, order, &[&3u32.to_le_bytes()]).is_err());
+
+    // Payloads are split into arguments by the Logger's codec
+    let encode = |args: &[&[u8]]| {
+        let mut payload = vec![0; logger.codec().encoded_len(args)];
+        logger.codec().encode(args, &mut payload);
+        payload
+    };
+    let (valid, short) = (encode(&[&3u32.to_le_bytes(), &[1, 2]]), encode(&[&3u16.to_le_bytes(), &[1, 2]]));
+    logger.write(order, &valid).unwrap();
+    assert!(logger.write(order, &short).is_err());
+
+    logger.set_schema_registry(None);
+    logger.write_args(order, &[&3u64.to_le_bytes()]).unwrap();
+}
+
+#[test]
+fn test_reader_coerces_to_the_schema_and_warns() {
+    let bytes = register_string("Coerced bytes {}");
+    let state = register_string("Coerced state {}");
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        // Arguments without type tags are read by size, and coerced
+        logger.set_type_tags(false);
+        // The literal makes the writer note the argument kinds
+        log_record!(logger, "Coerced {} in {}", 3u32, "eu-west").unwrap();
+        logger.write_args(bytes, &[&[0xff, 0xfe, 0]]).unwrap();
+        // Not a registered string ID
+        logger.write_args(state, &[&[0xff, 0xff]]).unwrap();
+        logger.write_args(state, &[]).unwrap();
+    }
+    let data = data.lock().unwrap();
+    let schema = SchemaRegistry::new()
+        .with_event("Coerced {} in {}", &[ArgType::F32, ArgType::Str])
+        .with_event("Coerced state {}", &[ArgType::Interned]);
+
+    let mut reader = LogReader::new(&data);
+    reader.set_schema_registry(Some(Arc::new(schema)));
+    reader.set_decode_warnings(true);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    let coerced = entries[0].format_id;
+    assert!(matches!(entries[0].parameters[0], LogValue::Float(value) if value == 3.0));
+    assert_eq!(entries[0].format(), "Coerced 3 in eu-west");
+    assert!(matches!(entries[1].parameters[..], [LogValue::Unknown(_)]));
+    assert_eq!(entries[2].format(), "Coerced state 0xffff");
+    let warnings = reader.take_decode_warnings();
+    let found: Vec<(u32, Option<usize>, &str)> = warnings.iter()
+        .map(|warning| (warning.format_id, warning.argument, warning.message.as_str()))
+        .collect();
+    assert_eq!(found, [
+        (coerced, Some(0), "4-byte value read as a float, the schema has a f32"),
+        (coerced, Some(1), "interned literal, the schema has a string"),
+        (bytes, Some(0), "3 bytes of unknown type"),
+        (state, Some(0), "damaged interned literal of 2 bytes read as hex"),
+        (state, None, "0 arguments, the schema has 1"),
+    ]);
+    assert_eq!(warnings[0].to_string(), format!("{} format {} argument 0: {}", entries[0].id, coerced, warnings[0].message));
+    assert!(reader.take_decode_warnings().is_empty());
+
+    // Without a schema arguments are read as written
+    let mut reader = LogReader::new(&data);
+    let entry = reader.read_entry().unwrap();
+    assert!(matches!(entry.parameters[0], LogValue::Integer(3)));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, register_string, log_record};
+use binary_logger::render::render_line;
+use binary_logger::search::FormatFilter;
+use regex::Regex;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+#[test]
+fn test_format_filter_verdicts() {
+    let plain = register_string("Search: disk full");
+    let templated = register_string("Search: temperature {} C");
+    let unregistered = 0xFEEE;
+
+    // A literal can come from an argument, but not from fixed text
+    let mut filter = FormatFilter::new("overheat");
+    assert!(!filter.may_match(plain, 0));
+    assert!(filter.may_match(templated, 0));
+    assert!(filter.may_match(unregistered, 0));
+
+    // Anchors are checked against the line layout
+    let mut filter = FormatFilter::new(r"^\d+\.\d{6} Search: temp");
+    assert!(!filter.may_match(plain, 0));
+    assert!(filter.may_match(templated, 0));
+    let mut filter = FormatFilter::new("C$");
+    assert!(!filter.may_match(plain, 0));
+    assert!(filter.may_match(templated, 0));
+    let mut filter = FormatFilter::new("^Search");
+    assert!(!filter.may_match(plain, 0));
+    assert!(!filter.may_match(templated, 0));
+
+    // Channels are part of the line
+    let net = register_string("search-net");
+    let mut filter = FormatFilter::new(r"\[search-net\] Search: disk");
+    assert!(!filter.may_match(plain, 0));
+    assert!(filter.may_match(plain, net));
+    let mut filter = FormatFilter::new(r"^\S+ \[search-net\] Search: disk");
+    assert!(filter.may_match(plain, net));
+    assert!(!filter.may_match(templated, net));
+
+    // Patterns the DFA can't handle don't narrow
+    let mut filter = FormatFilter::new(r"\bwith unicode boundary\b");
+    assert!(filter.may_match(plain, 0));
+}
+
+#[test]
+fn test_filtered_search_matches_full_search() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        for i in 0..50 {
+            log_record!(logger, "Search: request {} served", i).unwrap();
+            log_record!(logger, "Search: cache miss", ).unwrap();
+            log_record!(logger, channel: "search-db", "Search: query took {} us", i * 7).unwrap();
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap();
+
+    for pattern in ["served$", "cache", r"\[search-db\] .* 4\d us", "^nothing", r"request 4\d "] {
+        let regex = Regex::new(pattern).unwrap();
+
+        let mut reader = LogReader::new(&data);
+        let expected: Vec<String> = std::iter::from_fn(|| reader.read_entry())
+            .map(|entry| render_line(&entry))
+            .filter(|line| regex.is_match(line))
+            .collect();
+
+        let mut filter = FormatFilter::new(pattern);
+        let mut reader = LogReader::new(&data);
+        reader.set_record_filter(Some(Box::new(move |format_id, channel| filter.may_match(format_id, channel))));
+        let found: Vec<String> = std::iter::from_fn(|| reader.read_entry())
+            .map(|entry| render_line(&entry))
+            .filter(|line| regex.is_match(line))
+            .collect();
+
+        assert_eq!(found, expected, "pattern {}", pattern);
+    }
+
+    // Only records accepted by the filter are decoded
+    let wanted = register_string("Search: cache miss");
+    let mut reader = LogReader::new(&data);
+    reader.set_record_filter(Some(Box::new(move |format_id, _| format_id == wanted)));
+    assert_eq!(std::iter::from_fn(|| reader.read_entry()).count(), 50);
+}
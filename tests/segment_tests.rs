@@ -0,0 +1,142 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, log_record, log_record_at};
+use binary_logger::analysis::{latency_between, list_segments, Segment};
+use binary_logger::instrumentation::InternalEvent;
+use binary_logger::level::Level;
+use binary_logger::query::LogQuery;
+use regex::Regex;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn read_all(reader: &mut LogReader) -> Vec<LogEntry> {
+    std::iter::from_fn(|| reader.read_entry()).collect()
+}
+
+/// Logs requests 0 to 2 before any segment, then segments "a", "test run 7"
+/// and "a" again, with 3, 2 and 1 requests, and a segment "tail" ended by
+/// dropping the Logger.
+fn segmented_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_level(Level::Warn);
+        let mut request = 0;
+        for (segment, requests) in [(None, 3), (Some("a"), 3), (Some("test run 7"), 2), (Some("a"), 1)] {
+            if let Some(name) = segment {
+                logger.begin_segment(name).unwrap();
+                assert_eq!(logger.segment(), Some(name));
+            }
+            for _ in 0..requests {
+                log_record_at!(logger, Level::Warn, "Request {} received", request).unwrap();
+                log_record_at!(logger, Level::Info, "Request {} parsed", request).unwrap();
+                log_record!(logger, "Request {} done", request).unwrap();
+                request += 1;
+            }
+        }
+        logger.end_segment().unwrap();
+        assert_eq!(logger.segment(), None);
+        logger.end_segment().unwrap();
+        log_record!(logger, "Between {}", 1).unwrap();
+        logger.begin_segment("tail").unwrap();
+        log_record!(logger, "Shutting down {}", 1).unwrap();
+    }
+    let data = data.lock().unwrap();
+    data.clone()
+}
+
+#[test]
+fn test_segments_are_listed_with_their_records() {
+    let data = segmented_log();
+    let segments = list_segments(&mut LogReader::new(&data));
+    let listed: Vec<(&str, u64, bool)> = segments.iter().map(|s| (s.name.as_str(), s.records, s.end.is_some())).collect();
+    assert_eq!(listed, [("a", 6, true), ("test run 7", 4, true), ("a", 2, true), ("tail", 1, true)]);
+    assert!(segments.windows(2).all(|pair| pair[0].end.unwrap() <= pair[1].begin));
+
+    // The boundaries decode as internal entries, whatever the Logger's
+    // level, short names included
+    let entries = read_all(&mut LogReader::new(&data));
+    let boundaries: Vec<String> = entries.iter().filter(|entry| entry.is_internal()).map(LogEntry::format).collect();
+    assert_eq!(boundaries[..3], [
+        "[binary_logger] segment a begins",
+        "[binary_logger] segment a ends",
+        "[binary_logger] segment test run 7 begins",
+    ]);
+    assert_eq!(boundaries.last().unwrap(), "[binary_logger] segment tail ends");
+    assert_eq!(entries.iter().filter(|entry| entry.format_id == InternalEvent::SegmentEnd.format_id()).count(), 4);
+}
+
+#[test]
+fn test_segment_filter_keeps_one_segment() {
+    let data = segmented_log();
+    let mut reader = LogReader::new(&data);
+    reader.set_segment_filter(Some("a"));
+    let lines: Vec<String> = read_all(&mut reader).iter().map(LogEntry::format).collect();
+    assert_eq!(lines.len(), 2 + 6 + 2 + 2, "{:?}", lines);
+    assert_eq!(lines[..3], ["[binary_logger] segment a begins", "Request 3 received", "Request 3 done"]);
+    assert_eq!(lines[lines.len() - 3..], ["Request 8 received", "Request 8 done", "[binary_logger] segment a ends"]);
+
+    // The segments are followed whatever the other filters and skips
+    let mut reader = LogReader::new(&data);
+    reader.set_segment_filter(Some("test run 7"));
+    reader.set_level_filter(Some(Level::Warn));
+    let entries = read_all(&mut reader);
+    assert_eq!(entries.iter().map(LogEntry::format).collect::<Vec<_>>(), ["Request 6 received", "Request 7 received"]);
+    let report = {
+        let mut reader = LogReader::new(&data);
+        reader.set_segment_filter(Some("test run 7"));
+        latency_between(&mut reader, "Request {} received", "Request {} done", 0)
+    };
+    assert_eq!((report.count(), report.unmatched_starts, report.unmatched_ends), (2, 0, 0));
+
+    let mut reader = LogReader::new(&data);
+    reader.set_segment_filter(Some("tail"));
+    assert_eq!(read_all(&mut reader).len(), 3);
+    assert_eq!(reader.segment(), None);
+    let mut reader = LogReader::new(&data);
+    reader.set_segment_filter(Some("missing"));
+    assert!(reader.read_entry().is_none());
+
+    // Queries read through the reader's segment filter
+    let mut reader = LogReader::new(&data);
+    reader.set_segment_filter(Some("a"));
+    let hits: Vec<String> = LogQuery::matching(Regex::new("received").unwrap()).run(reader).map(|hit| hit.entry.format()).collect();
+    assert_eq!(hits, ["Request 3 received", "Request 4 received", "Request 5 received", "Request 8 received"]);
+}
+
+#[test]
+fn test_begin_ends_the_open_segment() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let unended;
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.begin_segment("first").unwrap();
+        log_record!(logger, "Step {}", 1).unwrap();
+        logger.begin_segment("second").unwrap();
+        log_record!(logger, "Step {}", 2).unwrap();
+        log_record!(logger, "Step {}", 3).unwrap();
+        logger.flush();
+        unended = data.lock().unwrap().clone();
+        // Dropping the logger ends "second"
+    }
+    let segments = list_segments(&mut LogReader::new(&unended));
+    assert_eq!((segments[1].records, segments[1].end, segments[1].duration()), (2, None, None));
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let mut in_segments = Vec::new();
+    while let Some(entry) = reader.read_entry() {
+        if !entry.is_internal() {
+            in_segments.push((entry.format(), reader.segment().map(str::to_string)));
+        }
+    }
+    assert_eq!(in_segments, [
+        ("Step 1".to_string(), Some("first".to_string())),
+        ("Step 2".to_string(), Some("second".to_string())),
+        ("Step 3".to_string(), Some("second".to_string())),
+    ]);
+    assert_eq!(reader.segment(), None);
+    let segments: Vec<Segment> = list_segments(&mut LogReader::new(&data));
+    assert_eq!(segments.iter().map(|s| (s.name.as_str(), s.records, s.end.is_some())).collect::<Vec<_>>(), [("first", 1, true), ("second", 2, true)]);
+    assert!(segments[1].duration().is_some());
+}
@@ -0,0 +1,20 @@
+use binary_logger::selftest;
+
+#[test]
+fn test_selftest_passes() {
+    let report = selftest();
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.failures().count(), 0);
+}
+
+#[test]
+fn test_selftest_covers_clock_and_codecs() {
+    let report = selftest();
+    let names: Vec<&str> = report.checks.iter().map(|check| check.name.as_str()).collect();
+
+    assert!(names.contains(&"clock calibration"));
+    for codec in ["raw", "postcard", "cbor", "compact"] {
+        assert!(names.contains(&format!("{} codec roundtrip", codec).as_str()), "Missing {} check: {:?}", codec, names);
+    }
+    assert!(report.to_string().starts_with("binary_logger self-test: PASSED"));
+}
@@ -0,0 +1,109 @@
+#![cfg(target_os = "linux")]
+
+use binary_logger::{Logger, BufferHandler, StreamReader, log_record};
+use binary_logger::proxy::{DecodingProxy, Listener, OutputFormat};
+use binary_logger::seqpacket::{Credentials, SeqpacketHandler, SeqpacketListener, MAX_MESSAGE_SIZE};
+use std::io::{self, Write};
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn own_credentials() -> Credentials {
+    Credentials {
+        pid: std::process::id(),
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+    }
+}
+
+#[test]
+fn test_buffers_larger_than_a_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collector.sock");
+    let listener = SeqpacketListener::bind(&path).unwrap();
+
+    let producer = {
+        let path = path.clone();
+        thread::spawn(move || {
+            let mut logger = Logger::<{ 4 * MAX_MESSAGE_SIZE }>::new(SeqpacketHandler::connect(&path).unwrap());
+            for i in 0..20_000u32 {
+                log_record!(logger, "Seqpacket event {} of tenant {}", i, "blue").unwrap();
+            }
+        })
+    };
+
+    let connection = listener.accept().unwrap();
+    assert_eq!(connection.peer_credentials(), own_credentials());
+    let mut reader = StreamReader::new(connection);
+    let mut count = 0;
+    while let Some(entry) = reader.read_entry().unwrap() {
+        assert_eq!(entry.format(), format!("Seqpacket event {} of tenant blue", count));
+        count += 1;
+    }
+    producer.join().unwrap();
+    assert_eq!(count, 20_000);
+}
+
+#[test]
+fn test_broken_connection_fails_every_later_buffer() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("collector.sock");
+    let listener = SeqpacketListener::bind(&path).unwrap();
+    let handler = SeqpacketHandler::connect(&path).unwrap();
+    drop(listener.accept().unwrap());
+
+    let buffer = [0u8; 64];
+    let send = || panic::catch_unwind(AssertUnwindSafe(|| handler.handle_buffer(&buffer)));
+    let first = send().unwrap_err();
+    let message = first.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with(&format!("cannot send buffer to {}: ", path.display())), "{}", message);
+    // A later buffer fails with the same error instead of continuing the
+    // stream mid-buffer
+    assert_eq!(send().unwrap_err().downcast_ref::<String>(), Some(message));
+}
+
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_proxy_names_producers_by_credentials() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("proxy.sock");
+    // Socket-activated seqpacket sockets are told apart by their type
+    let listener = unsafe { Listener::from_raw_fd(SeqpacketListener::bind(&path).unwrap().into_raw_fd()) };
+    assert!(matches!(listener, Listener::Seqpacket(_)));
+
+    let output = Output::default();
+    let proxy = Arc::new(DecodingProxy::new(OutputFormat::Text).with_destination(output.clone()));
+    {
+        let proxy = proxy.clone();
+        thread::spawn(move || proxy.serve(&listener));
+    }
+    {
+        let mut logger = Logger::<4096>::new(SeqpacketHandler::connect(&path).unwrap());
+        logger.set_dictionary_channel(true);
+        log_record!(logger, "Tenant request {}", 7).unwrap();
+    }
+
+    let start = Instant::now();
+    while proxy.stats().entries < 1 {
+        assert!(start.elapsed() < Duration::from_secs(10), "proxy decoded {:?}", proxy.stats());
+        thread::sleep(Duration::from_millis(5));
+    }
+    let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(output.starts_with(&format!("{} ", own_credentials())), "{}", output);
+    assert!(output.ends_with(" Tenant request 7\n"), "{}", output);
+}
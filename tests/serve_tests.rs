@@ -0,0 +1,74 @@
+#![cfg(feature = "serve")]
+
+mod common;
+
+use binary_logger::{Logger, log_record};
+use binary_logger::serve::LogServer;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn get(server: &LogServer, url: &str) -> Value {
+    let response = server.handle(url);
+    assert_eq!(response.status, 200, "{}: {}", url, String::from_utf8_lossy(&response.body));
+    serde_json::from_slice(&response.body).unwrap()
+}
+
+fn messages(page: &Value) -> Vec<&str> {
+    page["entries"].as_array().unwrap().iter().map(|e| e["message"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_serve_entries() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        for i in 0..5 {
+            log_record!(logger, "Served request {}", i).unwrap();
+        }
+        log_record!(logger, channel: "serve-db", "Served query failed", ).unwrap();
+        logger.flush();
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("app.blog");
+    std::fs::write(&path, &*data.lock().unwrap()).unwrap();
+    let server = LogServer::new(vec![path]);
+
+    let files = get(&server, "/api/files");
+    assert_eq!(files[0]["name"], "app.blog");
+    assert_eq!(files[0]["entries"], 6);
+
+    // Paging with limit and after
+    let first = get(&server, "/api/entries?file=0&limit=4");
+    assert_eq!(messages(&first), ["Served request 0", "Served request 1", "Served request 2", "Served request 3"]);
+    assert_eq!(first["more"], true);
+    let last_id = first["entries"][3]["id"].as_str().unwrap();
+    let rest = get(&server, &format!("/api/entries?file=0&after={}", last_id));
+    assert_eq!(messages(&rest), ["Served request 4", "Served query failed"]);
+    assert_eq!(rest["more"], false);
+    assert_eq!(rest["entries"][1]["channel_name"], "serve-db");
+
+    // Text search is case-insensitive and literal
+    let found = get(&server, "/api/entries?file=0&q=QUERY+FAILED");
+    assert_eq!(messages(&found), ["Served query failed"]);
+    let channel = found["entries"][0]["channel"].as_u64().unwrap();
+    let by_channel = get(&server, &format!("/api/entries?file=0&channel={}", channel));
+    assert_eq!(messages(&by_channel), ["Served query failed"]);
+
+    // Time range, with `to` exclusive
+    let start = first["entries"][0]["timestamp_us"].as_u64().unwrap();
+    let none = get(&server, &format!("/api/entries?file=0&to={}", start));
+    assert!(messages(&none).is_empty());
+    let all = get(&server, &format!("/api/entries?file=0&from={}", start));
+    assert_eq!(messages(&all).len(), 6);
+}
+
+#[test]
+fn test_serve_errors() {
+    let server = LogServer::new(Vec::new());
+    assert_eq!(server.handle("/").status, 200);
+    assert_eq!(server.handle("/missing").status, 404);
+    assert_eq!(server.handle("/api/entries?file=0").status, 404);
+    assert_eq!(server.handle("/api/entries?file=x").status, 400);
+    assert!(get(&server, "/api/files").as_array().unwrap().is_empty());
+}
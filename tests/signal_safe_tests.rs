@@ -0,0 +1,169 @@
+mod common;
+
+use binary_logger::{LogReader, LogValue, log_signal_safe};
+use binary_logger::features::FormatFeatures;
+use binary_logger::inspect::inspect;
+use binary_logger::level::Level;
+use binary_logger::signal_safe::{AsyncSignalSafeLogger, SharedLayout, CACHE_LINE};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use common::CollectingHandler;
+
+#[test]
+fn test_concurrent_writes_and_drains() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let logger = Arc::new(AsyncSignalSafeLogger::<4096>::new(CollectingHandler(data.clone())));
+    let done = Arc::new(AtomicBool::new(false));
+
+    let drainer = {
+        let (logger, done) = (logger.clone(), done.clone());
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                logger.drain();
+                thread::yield_now();
+            }
+        })
+    };
+    let writers: Vec<_> = (0..4i32).map(|thread| {
+        let logger = logger.clone();
+        thread::spawn(move || {
+            let mut written = 0;
+            for i in 0..2000i32 {
+                if log_signal_safe!(logger, Level::Info, "Thread {} record {}", thread, i) {
+                    written += 1;
+                }
+            }
+            written
+        })
+    }).collect();
+    let written: u64 = writers.into_iter().map(|writer| writer.join().unwrap()).sum();
+    done.store(true, Ordering::Relaxed);
+    drainer.join().unwrap();
+    let dropped = logger.dropped();
+    drop(logger);
+    assert_eq!(written + dropped, 8000);
+
+    // Every written record comes back intact, in time order
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let mut read = 0;
+    let mut last = SystemTime::UNIX_EPOCH;
+    while let Some(entry) = reader.read_entry() {
+        assert_eq!(entry.level, Some(Level::Info));
+        assert!(matches!(entry.parameters[..], [LogValue::Integer(0..=3), LogValue::Integer(0..=1999)]));
+        assert!(entry.timestamp >= last);
+        last = entry.timestamp;
+        read += 1;
+    }
+    assert_eq!(read, written);
+}
+
+#[test]
+fn test_drops_when_full_and_timestamps_across_epochs() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let logger = AsyncSignalSafeLogger::<256>::new(CollectingHandler(data.clone()));
+
+    let before = SystemTime::now() - Duration::from_millis(1);
+    assert!(log_signal_safe!(logger, "Caught signal {}", 15i32));
+    // Later than one 16-bit epoch of relative time
+    thread::sleep(Duration::from_millis(80));
+    assert!(log_signal_safe!(logger, Level::Error, "Fault, fatal: {}", true));
+    let after = SystemTime::now() + Duration::from_millis(1);
+
+    let mut written = 2;
+    while log_signal_safe!(logger, "Load {}", 0.5f64) {
+        written += 1;
+    }
+    assert_eq!(logger.dropped(), 1);
+    logger.drain();
+    assert!(log_signal_safe!(logger, "Drained"));
+    drop(logger);
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let first = reader.read_entry().unwrap();
+    assert_eq!(first.format(), "Caught signal 15");
+    let second = reader.read_entry().unwrap();
+    assert_eq!(second.format(), "Fault, fatal: true");
+    assert_eq!(second.level, Some(Level::Error));
+    assert!(before <= first.timestamp && second.timestamp <= after);
+    assert!(second.timestamp.duration_since(first.timestamp).unwrap() >= Duration::from_millis(80));
+
+    let mut rest: Vec<String> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(rest.pop().as_deref(), Some("Drained"));
+    assert_eq!(rest.len(), written - 2);
+    assert!(rest.iter().all(|message| message == "Load 0.5"));
+}
+
+#[test]
+fn test_cache_aligned_records_start_on_cache_lines() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let logger = Arc::new(AsyncSignalSafeLogger::<65536>::with_layout(CollectingHandler(data.clone()), SharedLayout::CACHE_ALIGNED));
+    assert_eq!(logger.layout(), SharedLayout::CACHE_ALIGNED);
+    let writers: Vec<_> = (0..4i32).map(|thread| {
+        let logger = logger.clone();
+        thread::spawn(move || {
+            for i in 0..50i32 {
+                assert!(log_signal_safe!(logger, Level::Debug, "Thread {} record {}", thread, i));
+            }
+            // 56 bytes of payload: a record that ends exactly on a line
+            assert!(log_signal_safe!(logger, "Totals {} {} {} {} {} {} {}", 1.0f64, 2.0f64, 3.0f64, 4.0f64, 5.0f64, 6.0f64, 7.0f64));
+        })
+    }).collect();
+    writers.into_iter().for_each(|writer| writer.join().unwrap());
+    logger.drain();
+    drop(logger);
+
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), 204);
+    assert_eq!(entries.iter().filter(|entry| entry.format().starts_with("Totals 1")).count(), 4);
+    assert_eq!(reader.format_features().map(|features| features.contains(FormatFeatures::PADDING)), Some(true));
+
+    // One buffer, so file offsets are buffer offsets
+    let mut dump = Vec::new();
+    inspect(&data, &mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    let records: Vec<(usize, &str)> = dump.lines()
+        .filter(|line| line.contains("  record #"))
+        .map(|line| (usize::from_str_radix(&line[..8], 16).unwrap(), line.split("type=").nth(1).unwrap()))
+        .collect();
+    let normal: Vec<usize> = records.iter().filter(|(_, kind)| kind.contains("(normal)")).map(|&(offset, _)| offset).collect();
+    assert_eq!(normal.len(), 204);
+    assert!(normal.iter().all(|offset| offset % CACHE_LINE == 0), "{:?}", normal);
+    assert!(records.iter().any(|(_, kind)| kind.contains("(padding)")));
+}
+
+#[test]
+fn test_packed_layout_has_no_padding() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let logger = AsyncSignalSafeLogger::<4096>::new(CollectingHandler(data.clone()));
+    assert_eq!(logger.layout(), SharedLayout::PACKED);
+    for i in 0..10i32 {
+        assert!(log_signal_safe!(logger, "Record {}", i));
+    }
+    drop(logger);
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    assert_eq!(std::iter::from_fn(|| reader.read_entry()).count(), 10);
+    assert_eq!(reader.format_features().map(|features| features.contains(FormatFeatures::PADDING)), Some(false));
+
+    // Padded cursors alone don't change the stream
+    let padded = Arc::new(Mutex::new(Vec::new()));
+    let logger = AsyncSignalSafeLogger::<4096>::with_layout(CollectingHandler(padded.clone()), SharedLayout { record_alignment: 2, pad_cursors: true });
+    for i in 0..10i32 {
+        assert!(log_signal_safe!(logger, "Record {}", i));
+    }
+    drop(logger);
+    assert_eq!(padded.lock().unwrap().len(), data.len());
+}
+
+#[test]
+#[should_panic(expected = "Record alignment")]
+fn test_rejects_unsupported_alignment() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    AsyncSignalSafeLogger::<4096>::with_layout(CollectingHandler(data), SharedLayout { record_alignment: 48, pad_cursors: false });
+}
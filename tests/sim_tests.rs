@@ -0,0 +1,119 @@
+#![cfg(feature = "sim")]
+
+//! `tests/fixtures/sim_scenario.blog` is what `scenario` writes. After an
+//! intended change of the writer, rewrite it with
+//! `cargo test --features sim --test sim_tests -- --ignored regenerate_golden_file`.
+
+use binary_logger::{HandlerPanicPolicy, LogReader, LogEntry, log_record, register_string};
+use binary_logger::sim::{MemorySink, Simulation, VirtualClock, SIM_EPOCH_MICROS};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn golden_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sim_scenario.blog")
+}
+
+/// Logs orders 250us apart in a Logger of 256 bytes, with a 70ms pause
+/// every 10 orders, and returns the virtual times of the records.
+///
+/// The strings start with '!', which sorts them, and numbers them, ahead of
+/// every other string the test binary logs, so their IDs don't change with
+/// the rest of the crate.
+fn scenario(sim: &Simulation) -> Vec<u64> {
+    let mut times = Vec::new();
+    let mut logger = sim.logger::<256>();
+    for i in 0..40u32 {
+        let price = 100.0 + i as f64 / 4.0;
+        times.push(sim.clock.micros());
+        log_record!(logger, "!sim order {} at {}", i, price).unwrap();
+        if i % 10 == 9 {
+            // Past a 16-bit relative timestamp
+            sim.clock.advance(Duration::from_millis(70));
+            times.push(sim.clock.micros());
+            let depth = i * 3;
+            log_record!(logger, channel: "!sim-ops", "!sim queue {} depth {}", i / 10, depth).unwrap();
+        }
+        sim.clock.advance(Duration::from_micros(250));
+    }
+    times
+}
+
+fn entries(data: &[u8]) -> Vec<LogEntry> {
+    let mut reader = LogReader::new(data);
+    std::iter::from_fn(|| reader.read_entry()).collect()
+}
+
+#[test]
+fn test_scenario_replays_bit_for_bit() {
+    let (first, second) = (Simulation::new(), Simulation::new());
+    assert_eq!(scenario(&first), scenario(&second));
+    let buffers = first.sink.buffers();
+    assert!(buffers.len() > 5);
+    assert_eq!(buffers, second.sink.buffers());
+}
+
+#[test]
+fn test_records_carry_virtual_times() {
+    let sim = Simulation::new();
+    let times = scenario(&sim);
+    let entries = entries(&sim.sink.bytes());
+    let logged: Vec<u64> = entries.iter()
+        .map(|entry| entry.timestamp.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64)
+        .collect();
+    assert_eq!(logged, times);
+    assert_eq!(entries[0].timestamp, UNIX_EPOCH + Duration::from_micros(SIM_EPOCH_MICROS));
+    assert_eq!(entries[10].format(), "!sim queue 0 depth 27");
+
+    // Buffers are handed over when they fill, at the time of the record
+    // that didn't fit
+    let buffers = sim.sink.buffers();
+    assert!(buffers.windows(2).all(|pair| pair[0].at_micros <= pair[1].at_micros));
+    assert!(buffers[..buffers.len() - 1].iter().all(|buffer| times.contains(&buffer.at_micros)));
+}
+
+#[test]
+fn test_scenario_matches_golden_file() {
+    assert_eq!(
+        [register_string("!sim order {} at {}"), register_string("!sim queue {} depth {}"), register_string("!sim-ops")],
+        [1, 2, 3],
+        "the scenario's strings must have the first IDs");
+    let sim = Simulation::new();
+    scenario(&sim);
+    let golden = fs::read(golden_file()).unwrap();
+    assert!(sim.sink.bytes() == golden, "the writer's output changed, see the top of this file");
+}
+
+#[test]
+fn test_sinks_simulate_slow_and_failing_handlers() {
+    let sink = MemorySink::new(VirtualClock::starting_at(SIM_EPOCH_MICROS)).with_latency(Duration::from_micros(1500)).failing_on(&[1]);
+    let sim = Simulation::with_sink(sink);
+    {
+        let mut logger = sim.logger::<256>();
+        logger.set_instrumentation(true);
+        logger.set_handler_panic_policy(HandlerPanicPolicy::Retry);
+        for i in 0..30u32 {
+            log_record!(logger, "Simulated event {}", i).unwrap();
+            sim.clock.advance(Duration::from_micros(10));
+        }
+        assert_eq!(logger.handler_status().buffers_lost, 1);
+    }
+    // The second buffer was lost
+    assert_eq!(sim.sink.calls(), sim.sink.buffers().len() + 1);
+
+    // The handler took exactly its latency, every time
+    let took: Vec<String> = entries(&sim.sink.bytes()).iter()
+        .filter(|entry| entry.is_internal() && entry.format().contains("handler took"))
+        .map(LogEntry::format)
+        .collect();
+    assert!(took.len() >= 2);
+    assert!(took.iter().all(|text| text.ends_with("handler took 1500 us")), "{:?}", took);
+}
+
+#[test]
+#[ignore]
+fn regenerate_golden_file() {
+    let sim = Simulation::new();
+    scenario(&sim);
+    fs::write(golden_file(), sim.sink.bytes()).unwrap();
+}
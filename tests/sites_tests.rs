@@ -0,0 +1,53 @@
+use binary_logger::{Logger, LogSite, log_record, log_record_at, log_sampled, log_signal_safe, sites};
+use binary_logger::level::Level;
+use binary_logger::signal_safe::AsyncSignalSafeLogger;
+use binary_logger::sites::write_sites;
+
+/// Never called: its sites are listed all the same.
+#[allow(dead_code)]
+fn handle_order(logger: &mut Logger<4096>, shared: &AsyncSignalSafeLogger<4096>, level: Level, id: u64) {
+    log_record!(logger, "!site order {} received", id).unwrap();
+    log_record_at!(logger, Level::Warn, channel: "audit", "!site order {} over limit", id).unwrap();
+    log_sampled!(logger, level, key = id, "!site order {} sampled", id).unwrap();
+    log_signal_safe!(shared, Level::Error, "!site order {}\tfailed", 1i32);
+}
+
+fn test_sites() -> Vec<LogSite> {
+    sites().into_iter().filter(|site| site.format.starts_with("!site")).collect()
+}
+
+#[test]
+fn test_sites_list_every_compiled_call_site() {
+    let sites = test_sites();
+    let formats: Vec<&str> = sites.iter().map(|site| site.format).collect();
+    assert_eq!(formats, ["!site order {} received", "!site order {} over limit", "!site order {} sampled", "!site order {}\tfailed"]);
+    assert!(sites.iter().all(|site| site.module == "sites_tests" && site.file.ends_with("sites_tests.rs")));
+    let lines: Vec<u32> = sites.iter().map(|site| site.line - sites[0].line).collect();
+    assert_eq!(lines, [0, 1, 2, 3]);
+
+    assert_eq!((sites[0].channel, sites[0].level_expr, sites[0].level()), (None, None, None));
+    assert_eq!((sites[1].channel, sites[1].level()), (Some("audit"), Some(Level::Warn)));
+    // Computed at runtime
+    assert_eq!((sites[2].level_expr, sites[2].level()), (Some("level"), None));
+    assert_eq!(sites[3].level(), Some(Level::Error));
+
+    // Sites of the library itself are listed too
+    assert!(binary_logger::sites().len() > sites.len());
+}
+
+#[test]
+fn test_site_listing() {
+    let sites = test_sites();
+    let mut out = Vec::new();
+    write_sites(&sites, &mut out).unwrap();
+    let listing = String::from_utf8(out).unwrap();
+    let rows: Vec<Vec<&str>> = listing.lines().map(|line| line.split('\t').skip(1).collect()).collect();
+    assert_eq!(rows, [
+        ["sites_tests", "-", "-", "!site order {} received"],
+        ["sites_tests", "WARN", "audit", "!site order {} over limit"],
+        ["sites_tests", "level", "-", "!site order {} sampled"],
+        ["sites_tests", "ERROR", "-", "!site order {}\\tfailed"],
+    ]);
+    let first = listing.lines().next().unwrap();
+    assert_eq!(first.split('\t').next(), Some(format!("{}:{}", sites[0].file, sites[0].line).as_str()));
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, log_record, log_record_at, log_tagged};
+use binary_logger::level::Level;
+use binary_logger::tags::register_tag;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn write_mixed_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        // Small buffers, so skipping crosses buffer boundaries
+        let mut logger = Logger::<256>::new(CollectingHandler(data.clone()));
+        logger.set_delta_timestamps(true);
+        let slow = register_tag("slow");
+        for i in 0..60u32 {
+            match i % 4 {
+                0 => log_record_at!(logger, Level::Debug, "Polling {}", i).unwrap(),
+                1 => log_record_at!(logger, Level::Error, "Failed request {}", i).unwrap(),
+                2 => log_tagged!(logger, tags = slow, "Slow query {}", i).unwrap(),
+                _ => log_record!(logger, "Heartbeat {}", i).unwrap(),
+            }
+        }
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+#[test]
+fn test_skip_while_header() {
+    let data = write_mixed_log();
+    let all: Vec<LogEntry> = {
+        let mut reader = LogReader::new(&data);
+        std::iter::from_fn(|| reader.read_entry()).collect()
+    };
+    assert_eq!(all.len(), 60);
+
+    let mut reader = LogReader::new(&data);
+    let mut errors = Vec::new();
+    let mut skipped = 0;
+    loop {
+        skipped += reader.skip_while_header(|header| header.level != Some(Level::Error));
+        let Some(entry) = reader.read_entry() else { break };
+        errors.push(entry);
+    }
+    assert_eq!(skipped, 45);
+    let expected: Vec<&LogEntry> = all.iter().filter(|entry| entry.level == Some(Level::Error)).collect();
+    assert_eq!(errors.len(), expected.len());
+    for (entry, expected) in errors.iter().zip(expected) {
+        assert_eq!(entry.format(), expected.format());
+        assert_eq!(entry.timestamp, expected.timestamp);
+        assert_eq!(entry.id, expected.id);
+    }
+    let records: u64 = reader.channel_stats().values().map(|stats| stats.records).sum();
+    assert_eq!(records, 60);
+}
+
+#[test]
+fn test_skip_sees_header_fields() {
+    let data = write_mixed_log();
+    let mut reader = LogReader::new(&data);
+    let mut headers = Vec::new();
+    let skipped = reader.skip_while_header(|header| {
+        headers.push(*header);
+        true
+    });
+    assert_eq!(skipped, 60);
+    assert!(reader.read_entry().is_none());
+
+    let slow = register_tag("slow");
+    assert_eq!(headers[0].level, Some(Level::Debug));
+    assert_eq!(headers[2].tags, slow);
+    assert_eq!(headers[3].level, None);
+    assert!(headers.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    assert!(headers.iter().all(|header| header.payload_len > 0 && header.custom_type.is_none()));
+
+    // Stopping leaves the record for read_entry, with the same filters
+    let mut reader = LogReader::new(&data);
+    reader.set_tag_filter(Some(slow));
+    assert_eq!(reader.skip_while_header(|header| header.format_id != headers[1].format_id), 1);
+    let entry = reader.read_entry().unwrap();
+    assert_eq!(entry.format(), "Slow query 2");
+}
@@ -0,0 +1,128 @@
+use binary_logger::{Logger, BufferHandler, HandlerPanicPolicy, log_record};
+use binary_logger::snapshot::{debug_snapshot, serve_snapshots, LoggerSnapshot};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+struct NullHandler;
+
+impl BufferHandler for NullHandler {
+    fn handle_buffer(&self, _data: &[u8]) {}
+}
+
+fn find(name: &str) -> Option<LoggerSnapshot> {
+    debug_snapshot().loggers.into_iter().find(|logger| logger.name == name)
+}
+
+#[test]
+fn test_published_logger_state() {
+    let mut logger = Logger::<4096>::new(NullHandler);
+    assert!(find("snapshot-state").is_none());
+    logger.set_debug_name(Some("snapshot-state"));
+    log_record!(logger, "Order {} filled at {}", 17, 99.5).unwrap();
+    log_record!(logger, "Order {} filled at {}", 18, 99.25).unwrap();
+
+    let state = find("snapshot-state").unwrap();
+    assert_eq!(state.stats, logger.stats());
+    assert_eq!(state.capacity, 4096);
+    assert!(state.fill() > 0.0 && state.fill() < 1.0);
+    assert_eq!(state.thread, thread::current().name().unwrap());
+    assert!(state.last_flush.is_none() && state.handler_busy_since.is_none());
+
+    logger.flush();
+    let state = find("snapshot-state").unwrap();
+    assert_eq!(state.stats.buffers_flushed, 1);
+    assert_eq!(state.fill(), 0.0);
+    assert!(state.last_flush.is_some());
+
+    // The registered strings come along
+    assert!(debug_snapshot().strings.iter().any(|(_, s)| *s == "Order {} filled at {}"));
+
+    logger.set_debug_name(None);
+    assert!(find("snapshot-state").is_none());
+    logger.set_debug_name(Some("snapshot-state"));
+    drop(logger);
+    assert!(find("snapshot-state").is_none());
+}
+
+/// Blocks in the handler until told to return.
+struct BlockingHandler(Mutex<mpsc::Receiver<()>>, mpsc::Sender<()>);
+
+impl BufferHandler for BlockingHandler {
+    fn handle_buffer(&self, _data: &[u8]) {
+        self.1.send(()).unwrap();
+        self.0.lock().unwrap().recv().unwrap();
+    }
+}
+
+#[test]
+fn test_stuck_handler_is_visible() {
+    let (release, released) = mpsc::channel();
+    let (entered, entering) = mpsc::channel();
+    let writer = thread::spawn(move || {
+        let mut logger = Logger::<4096>::new(BlockingHandler(Mutex::new(released), entered));
+        logger.set_debug_name(Some("snapshot-stuck"));
+        log_record!(logger, "Batch {} queued", 3).unwrap();
+        logger.flush();
+        logger.set_debug_name(None);
+    });
+
+    entering.recv().unwrap();
+    let state = find("snapshot-stuck").unwrap();
+    assert!(state.handler_busy_since.is_some());
+    assert!(state.last_flush.is_none());
+    assert!(state.stats.bytes_pending > 0);
+
+    release.send(()).unwrap();
+    writer.join().unwrap();
+}
+
+struct PanickingHandler;
+
+impl BufferHandler for PanickingHandler {
+    fn handle_buffer(&self, _data: &[u8]) {
+        panic!("disk full");
+    }
+}
+
+#[test]
+fn test_handler_failures_in_snapshot() {
+    let mut logger = Logger::<4096>::new(PanickingHandler);
+    logger.set_handler_panic_policy(HandlerPanicPolicy::Disable);
+    logger.set_debug_name(Some("snapshot-failing"));
+    log_record!(logger, "Tick {}", 1).unwrap();
+    logger.flush();
+
+    let state = find("snapshot-failing").unwrap();
+    assert_eq!(state.handler, *logger.handler_status());
+    assert_eq!(state.handler.last_panic.as_deref(), Some("disk full"));
+    assert!(state.handler.disabled);
+    assert!(state.handler_busy_since.is_none());
+}
+
+#[test]
+fn test_json_over_http() {
+    let mut logger = Logger::<4096>::new(NullHandler);
+    logger.set_debug_name(Some("snapshot \"http\""));
+    log_record!(logger, "Request {} served", 200).unwrap();
+
+    let json = debug_snapshot().to_json();
+    assert!(json.starts_with("{\"taken_us\":"), "{}", json);
+    assert!(json.contains("\"name\":\"snapshot \\\"http\\\"\""), "{}", json);
+    assert!(json.contains("\"string\":\"Request {} served\""), "{}", json);
+    assert!(json.contains("\"handler_busy_since_us\":null"), "{}", json);
+
+    let addr = serve_snapshots("127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(b"GET /snapshot HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Content-Type: application/json"), "{}", response);
+    let body = response.split_once("\r\n\r\n").unwrap().1;
+    assert!(body.contains("\"name\":\"snapshot \\\"http\\\"\""), "{}", body);
+}
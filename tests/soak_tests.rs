@@ -0,0 +1,26 @@
+use binary_logger::soak::{run, SoakConfig};
+use std::time::Duration;
+
+#[test]
+fn test_short_soak_passes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = SoakConfig::new(dir.path());
+    config.rate = 20_000;
+    config.duration = Duration::from_secs(3);
+    config.segment_bytes = 128 << 10;
+    config.reanchor_every = Duration::from_millis(500);
+    config.new_format_every = Duration::from_millis(200);
+
+    let mut verified = Vec::new();
+    let report = run(&config, |segment| verified.push(segment.to_string())).unwrap();
+    assert!(report.passed(), "{}", report.to_json());
+    assert!(report.records >= 50_000, "{}", report.to_json());
+    assert_eq!(report.entries, report.records);
+    assert!(report.segments > 2 && verified.len() as u64 == report.segments, "{:?}", verified);
+    assert!(verified.iter().all(|line| line.ends_with(" entries, ok")), "{:?}", verified);
+    assert!(report.new_formats >= 10 && report.reanchors >= 5, "{}", report.to_json());
+    assert!(report.to_json().starts_with("{\"passed\":true,"));
+
+    // Verified segments are deleted
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
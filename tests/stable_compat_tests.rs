@@ -0,0 +1,54 @@
+mod common;
+
+use binary_logger::{Logger, LogValue, log_record};
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+/// A type with no `LogArg` implementation of its own
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Port {
+    number: u16,
+    open: u16,
+}
+
+fn log_any<T>(logger: &mut Logger<4096>, value: T) {
+    log_record!(logger, "Generic {}", value).unwrap();
+}
+
+// These hold whichever way arguments are captured, with specialization on
+// nightly or without it (`stable-compat`)
+
+#[test]
+fn test_arguments_without_an_encoding_are_captured_as_bytes() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        let port = Port { number: 8080, open: 1 };
+        log_record!(logger, "Port {} of {}", port, [1u16, 2, 3, 4]).unwrap();
+        log_any(&mut logger, Port { number: 22, open: 0 });
+    }
+    let entries = read_all(&data.lock().unwrap());
+    // Read by size: 4 bytes as an i32, 8 as an f64
+    assert!(matches!(entries[0].parameters[0], LogValue::Integer(v) if v == 8080 | 1 << 16));
+    assert!(matches!(entries[0].parameters[1], LogValue::Float(v) if v.to_le_bytes() == [1, 0, 2, 0, 3, 0, 4, 0]));
+    assert!(matches!(entries[1].parameters[0], LogValue::Integer(22)));
+}
+
+#[test]
+fn test_arguments_keep_their_own_encoding() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_type_tags(true);
+        let name = String::from("eth0");
+        let (sent, lost) = (40u64, 2u64);
+        log_record!(logger, "{} sent {} lost {} ratio {}", name, sent - lost, lost, 0.25f32).unwrap();
+        log_record!(logger, "{} {} {}", &name, name.as_str(), 'x').unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries[0].format(), "eth0 sent 38 lost 2 ratio 0.25");
+    assert!(matches!(entries[0].parameters[1], LogValue::Unsigned(38)));
+    assert!(matches!(entries[0].parameters[3], LogValue::Float32(v) if v == 0.25));
+    assert_eq!(entries[1].format(), "eth0 eth0 x");
+}
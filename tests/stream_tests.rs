@@ -0,0 +1,137 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, StreamReader, ReaderOptions, log_record};
+use binary_logger::codec::CborCodec;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+/// A source returning at most a few bytes per read, like a slow pipe.
+struct Trickle(Vec<u8>, usize);
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(7).min(self.0.len() - self.1);
+        buf[..len].copy_from_slice(&self.0[self.1..self.1 + len]);
+        self.1 += len;
+        Ok(len)
+    }
+}
+
+/// A source failing after its data.
+struct Failing(io::Cursor<Vec<u8>>);
+
+impl Read for Failing {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf)? {
+            0 => Err(io::Error::other("connection reset")),
+            len => Ok(len),
+        }
+    }
+}
+
+fn write_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<256>::with_codec(CollectingHandler(data.clone()), &CborCodec);
+        logger.set_wrap_epochs(true);
+        for i in 0..200u32 {
+            log_record!(logger, "Stream test {} of {}", i, "many").unwrap();
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+fn read_all<R: Read>(mut reader: StreamReader<R>) -> Vec<LogEntry> {
+    std::iter::from_fn(|| reader.read_entry().unwrap()).collect()
+}
+
+fn same_entries(a: &[LogEntry], b: &[LogEntry]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| {
+        a.id == b.id && a.timestamp == b.timestamp && a.format() == b.format()
+    })
+}
+
+#[test]
+fn test_stream_matches_log_reader() {
+    let data = write_log();
+    let mut reader = LogReader::new(&data);
+    let expected: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(expected.len(), 200);
+    assert!(expected.iter().any(|e| e.id.buffer > 0), "Small buffers should spread entries over several buffers");
+
+    assert!(same_entries(&read_all(StreamReader::new(&data[..])), &expected));
+    assert!(same_entries(&read_all(StreamReader::new(Trickle(data.clone(), 0))), &expected));
+    for depth in [1, 4] {
+        let reader = StreamReader::new(Trickle(data.clone(), 0)).with_read_ahead(depth);
+        assert!(same_entries(&read_all(reader), &expected));
+    }
+
+    // A log cut off mid-buffer yields the complete records
+    let cut = &data[..data.len() - 20];
+    let mut reader = LogReader::new(cut);
+    let expected: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert!(same_entries(&read_all(StreamReader::new(cut)), &expected));
+}
+
+#[test]
+fn test_stream_errors() {
+    let data = write_log();
+    let mut reader = StreamReader::new(Failing(io::Cursor::new(data))).with_read_ahead(2);
+    reader.set_file_index(5);
+    let mut count = 0;
+    let err = loop {
+        match reader.read_entry() {
+            Ok(Some(entry)) => {
+                assert_eq!(entry.id.file, 5);
+                count += 1;
+            }
+            Ok(None) => panic!("The error should be reported"),
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(err.to_string(), "connection reset");
+    assert_eq!(count, 200);
+    assert!(reader.read_entry().unwrap().is_none());
+}
+
+#[test]
+fn test_stream_memory_limit() {
+    let data = write_log();
+    let buffer_size = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+
+    // Read-ahead waits for memory instead of going over the limit
+    let options = ReaderOptions { max_memory: Some(2 * buffer_size), ..Default::default() };
+    let mut reader = StreamReader::with_options(Trickle(data.clone(), 0), options).with_read_ahead(8);
+    let mut count = 0;
+    while reader.read_entry().unwrap().is_some() {
+        assert!(reader.memory_used() <= 2 * buffer_size);
+        count += 1;
+    }
+    assert_eq!(count, 200);
+    assert_eq!(reader.memory_used(), 0);
+    assert!(reader.peak_memory() >= buffer_size);
+    assert!(reader.peak_memory() <= 2 * buffer_size);
+
+    // A corrupt header declaring a huge buffer is refused, not allocated
+    let mut corrupt = data.clone();
+    corrupt[buffer_size..buffer_size + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    let options = ReaderOptions { max_memory: Some(1 << 20), ..Default::default() };
+    let mut reader = StreamReader::with_options(&corrupt[..], options);
+    let entries = std::iter::from_fn(|| reader.read_entry().transpose()).collect::<Vec<_>>();
+    let err = entries.last().unwrap().as_ref().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains(&format!("buffer at byte {}", buffer_size)), "{}", err);
+    assert!(entries[..entries.len() - 1].iter().all(|entry| entry.is_ok()));
+    drop(reader);
+
+    // Without a usable size the rest is one buffer, if it fits
+    corrupt[buffer_size..buffer_size + 8].copy_from_slice(&0u64.to_le_bytes());
+    let mut reader = StreamReader::with_options(&corrupt[..], ReaderOptions { max_memory: Some(data.len()), ..Default::default() });
+    assert!(std::iter::from_fn(|| reader.read_entry().unwrap()).count() > 0);
+    let mut reader = StreamReader::with_options(&corrupt[..], ReaderOptions { max_memory: Some(2 * buffer_size), ..Default::default() });
+    let err = std::iter::from_fn(|| reader.read_entry().transpose()).find_map(Result::err).unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -1,5 +1,12 @@
-use binary_logger::{register_string, get_string};
+mod common;
+
+use binary_logger::{register_string, get_string, Logger, LogReader, log_record, log_record_at};
+use binary_logger::handlers::LevelRouter;
+use binary_logger::level::Level;
+use binary_logger::string_registry::registered_strings;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use common::CollectingHandler;
 
 static TEST_STR: &str = "Test string";
 static DUPLICATE_STR: &str = "Duplicate string";
@@ -39,7 +46,7 @@ fn test_multiple_strings() {
 
 #[test]
 fn test_invalid_id() {
-    assert!(get_string(u16::MAX).is_none(), "Invalid ID should return None");
+    assert!(get_string(u16::MAX as u32).is_none(), "Invalid ID should return None");
 }
 
 #[test]
@@ -94,4 +101,91 @@ fn test_many_registrations() {
     for (s, id) in ids {
         assert_eq!(get_string(id).unwrap(), s);
     }
-} 
\ No newline at end of file
+}
+
+fn write_with_dictionary() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_embedded_dictionary(true);
+        log_record!(logger, "Embedded format {}", 1).unwrap();
+        log_record!(logger, channel: "embedded", "Embedded channel {}", 2).unwrap();
+    }
+    let data = data.lock().unwrap();
+    data.clone()
+}
+
+#[test]
+fn test_static_strings() {
+    // Call sites contribute their strings at link time, before they run
+    let strings = registered_strings();
+    let find = |wanted: &str| strings.iter().find(|(_, s)| *s == wanted).map(|&(id, _)| id);
+    let format_id = find("Embedded format {}").expect("Format string should be registered up front");
+    assert!(find("embedded").is_some(), "Channel name should be registered up front");
+    assert_eq!(register_string("Embedded format {}"), format_id);
+    assert!(strings.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+    let data = write_with_dictionary();
+    let mut reader = LogReader::new(&data);
+    assert_eq!(reader.read_entry().unwrap().format_id, format_id);
+}
+
+#[test]
+fn test_embedded_dictionary() {
+    let mut data = write_with_dictionary();
+
+    // Edit the dictionary copy of the format string, which comes before
+    // the records, as if the log came from another build
+    let needle = b"Embedded format {}";
+    let at = data.windows(needle.len()).position(|window| window == needle).unwrap();
+    data[at..at + needle.len()].copy_from_slice(b"Dictionary text {}");
+
+    let mut reader = LogReader::new(&data);
+    let entry = reader.read_entry().unwrap();
+    assert_eq!(entry.format_string.as_deref(), Some("Dictionary text {}"));
+    assert_eq!(entry.format(), "Dictionary text 1");
+    let entry = reader.read_entry().unwrap();
+    assert_eq!(entry.format(), "Embedded channel 2");
+    assert!(reader.read_entry().is_none());
+    drop(reader);
+
+    // The dictionary belongs to the reader, and its strings to the entries
+    // that use them, which outlive both the reader and the log
+    let entry = LogReader::new(&data).read_entry().unwrap();
+    drop(data);
+    assert_eq!(entry.format_string.as_deref(), Some("Dictionary text {}"));
+    assert_eq!(entry.format(), "Dictionary text 1");
+}
+
+#[test]
+fn test_strings_registered_later_are_embedded() {
+    let archive = Arc::new(Mutex::new(Vec::new()));
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    {
+        let router = LevelRouter::new()
+            .route(Level::Warn, CollectingHandler(alerts.clone()))
+            .route_all(CollectingHandler(archive.clone()));
+        let mut logger = Logger::<4096>::new(router);
+        logger.set_embedded_dictionary(true);
+        log_record_at!(logger, Level::Error, "Embedded up front {}", 1).unwrap();
+        logger.flush();
+
+        // Not a call site, so unknown until registered
+        let format_id = register_string("Registered at runtime {}");
+        logger.write_args_at(Level::Warn, 0, format_id, &[&7u32.to_le_bytes()]).unwrap();
+    }
+
+    // Both logs carry the late string, in a later buffer than the first
+    for data in [archive, alerts] {
+        let mut data = data.lock().unwrap().clone();
+        let needle = b"Registered at runtime {}";
+        let at = data.windows(needle.len()).position(|window| window == needle).unwrap();
+        data[at..at + needle.len()].copy_from_slice(b"Taken from the stream {}");
+
+        let mut reader = LogReader::new(&data);
+        assert_eq!(reader.read_entry().unwrap().format(), "Embedded up front 1");
+        let entry = reader.read_entry().unwrap();
+        assert_eq!((entry.format(), entry.level), ("Taken from the stream 7".to_string(), Some(Level::Warn)));
+        assert!(reader.read_entry().is_none());
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use binary_logger::{Logger, BufferHandler, LogReader, LogEntry, log_record, log_tagged};
+use binary_logger::handlers::LevelRouter;
+use binary_logger::inspect::inspect;
+use binary_logger::level::Level;
+use binary_logger::render::render_line;
+use binary_logger::tags::{register_tag, tag_by_name, Tags};
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+fn read_all(data: &[u8], filter: Option<Tags>) -> Vec<LogEntry> {
+    let mut reader = LogReader::new(data);
+    reader.set_tag_filter(filter);
+    std::iter::from_fn(|| reader.read_entry()).collect()
+}
+
+#[test]
+fn test_tag_registration() {
+    let orders = register_tag("orders");
+    let payments = register_tag("payments");
+    assert_ne!(orders, payments);
+    assert_eq!(register_tag("orders"), orders);
+    assert_eq!(tag_by_name("payments"), Some(payments));
+    assert_eq!(tag_by_name("no such tag"), None);
+    assert_eq!((orders | payments).bits().count_ones(), 2);
+    assert!((orders | payments).contains(orders));
+    assert!(!orders.intersects(payments));
+}
+
+#[test]
+fn test_tagged_roundtrip_and_filters() {
+    let security = register_tag("security");
+    let invoices = register_tag("invoices");
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<256>::new(CollectingHandler(data.clone()));
+        for i in 0..20 {
+            log_tagged!(logger, tags = security, "Login attempt {}", i).unwrap();
+            log_tagged!(logger, Level::Warn, tags = security | invoices, "Invoice {} edited", i).unwrap();
+            log_record!(logger, "Untagged {}", i).unwrap();
+        }
+
+        // Records without an enabled tag are skipped; untagged ones aren't
+        logger.set_tag_mask(invoices);
+        log_tagged!(logger, tags = security, "Skipped {}", 1).unwrap();
+        log_tagged!(logger, tags = Tags::NONE, "Written untagged {}", 2).unwrap();
+        assert_eq!(logger.stats().records_written, 61);
+    }
+    let data = data.lock().unwrap();
+
+    let entries = read_all(&data, None);
+    assert_eq!(entries.len(), 61);
+    assert_eq!(entries[0].tags, security);
+    assert_eq!(entries[0].format(), "Login attempt 0");
+    assert_eq!((entries[1].tags, entries[1].level), (security | invoices, Some(Level::Warn)));
+    assert_eq!(entries[1].format(), "Invoice 0 edited");
+    assert!(entries[2].tags.is_empty());
+    assert!(render_line(&entries[1]).ends_with(" WARN {security|invoices} Invoice 0 edited"));
+
+    let invoices_only = read_all(&data, Some(invoices));
+    assert_eq!(invoices_only.len(), 20);
+    assert!(invoices_only.iter().all(|entry| entry.format().starts_with("Invoice")));
+    assert_eq!(read_all(&data, Some(security)).len(), 40);
+
+    let mut dump = Vec::new();
+    inspect(&data, &mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert!(dump.contains("(tagged typed)") && dump.contains("tags=security|invoices"));
+}
+
+#[test]
+fn test_level_router_keeps_tags() {
+    let alerts = register_tag("alerts");
+    let routed = Arc::new(Mutex::new(Vec::new()));
+    {
+        let router = LevelRouter::new().route(Level::Warn, CollectingHandler(routed.clone()));
+        let mut logger = Logger::<512>::new(router);
+        for i in 0..40 {
+            log_tagged!(logger, Level::Info, tags = alerts, "Heartbeat {}", i).unwrap();
+            log_tagged!(logger, Level::Error, tags = alerts, "Disk {} failing", i).unwrap();
+        }
+    }
+
+    let routed = routed.lock().unwrap();
+    let entries = read_all(&routed, None);
+    assert_eq!(entries.len(), 40);
+    assert!(entries.iter().all(|entry| entry.tags == alerts && entry.level == Some(Level::Error)));
+    assert_eq!(entries[39].format(), "Disk 39 failing");
+}
+
+/// Keeps the buffers handed over, and how many of them were there at each
+/// sync.
+#[derive(Clone, Default)]
+struct SyncingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+    buffers: Arc<Mutex<usize>>,
+    syncs: Arc<Mutex<Vec<usize>>>,
+}
+
+impl BufferHandler for SyncingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+        *self.buffers.lock().unwrap() += 1;
+    }
+
+    fn sync(&self) {
+        self.syncs.lock().unwrap().push(*self.buffers.lock().unwrap());
+    }
+}
+
+#[test]
+fn test_critical_sections_sync_and_tag_their_records() {
+    let handler = SyncingHandler::default();
+    {
+        let mut logger = Logger::<4096>::new(handler.clone());
+        logger.set_type_tags(true);
+        let orders = register_tag("critical-orders");
+        let table = "orders";
+        log_record!(logger, "Before {}", table).unwrap();
+        logger.mark_critical_begin();
+        assert!(logger.in_critical_section());
+        log_record!(logger, "Inside {}", table).unwrap();
+        logger.mark_critical_begin();
+        log_tagged!(logger, tags = orders, "Nested {}", table).unwrap();
+        logger.mark_critical_end();
+        log_record!(logger, "Still inside {}", table).unwrap();
+        logger.mark_critical_end();
+        assert!(!logger.in_critical_section());
+        log_record!(logger, "After {}", table).unwrap();
+        // Ending no section changes nothing but the flush
+        logger.mark_critical_end();
+        log_record!(logger, "Last {}", table).unwrap();
+    }
+    // Every begin and end synced after handing over the records before it
+    assert_eq!(*handler.syncs.lock().unwrap(), [1, 2, 3, 4, 5]);
+
+    let data = handler.data.lock().unwrap();
+    let entries = read_all(&data, None);
+    let critical: Vec<(String, bool)> = entries.iter()
+        .map(|entry| (entry.format(), entry.tags.contains(Tags::CRITICAL)))
+        .collect();
+    assert_eq!(critical, [
+        ("Before orders".to_string(), false),
+        ("Inside orders".to_string(), true),
+        ("Nested orders".to_string(), true),
+        ("Still inside orders".to_string(), true),
+        ("After orders".to_string(), false),
+        ("Last orders".to_string(), false),
+    ]);
+    assert_eq!(entries[2].tags, Tags::CRITICAL | tag_by_name("critical-orders").unwrap());
+    assert!(render_line(&entries[1]).ends_with(" {critical} Inside orders"));
+
+    let selected: Vec<String> = read_all(&data, Some(Tags::CRITICAL)).iter().map(|entry| entry.format()).collect();
+    assert_eq!(selected, ["Inside orders", "Nested orders", "Still inside orders"]);
+    assert_eq!(register_tag("critical"), Tags::CRITICAL);
+    assert_eq!(tag_by_name("critical"), Some(Tags::CRITICAL));
+}
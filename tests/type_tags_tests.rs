@@ -0,0 +1,144 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, log_record, log_tagged, register_string};
+use binary_logger::arg_types::TypeTag;
+use binary_logger::features::FormatFeatures;
+use binary_logger::inspect::inspect;
+use binary_logger::level::Level;
+use binary_logger::tags::register_tag;
+use std::sync::{Arc, Mutex};
+use common::{CollectingHandler, read_all};
+
+fn write_samples(type_tags: bool, features: FormatFeatures) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_format_features(features);
+        logger.set_type_tags(type_tags);
+        let (total, offset, id, delta) = (u64::MAX, -5i64, 3_000_000_000u32, -2i8);
+        log_record!(logger, "Sent {} of {} bytes, id {}, delta {}", total, offset, id, delta).unwrap();
+        log_record!(logger, "Ratio {} ready {} grade {} name {}", 0.5f32, true, 'é', "abcd").unwrap();
+        log_record!(logger, "Wide {} {}", u128::MAX, i128::MIN).unwrap();
+        logger.flush();
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+#[test]
+fn test_tagged_arguments_round_trip() {
+    let entries = read_all(&write_samples(true, FormatFeatures::SUPPORTED));
+
+    let params = &entries[0].parameters;
+    assert!(matches!(params[0], LogValue::Unsigned(u64::MAX)));
+    assert!(matches!(params[1], LogValue::Integer64(-5)));
+    assert!(matches!(params[2], LogValue::Unsigned(3_000_000_000)));
+    assert!(matches!(params[3], LogValue::Integer(-2)));
+    assert_eq!(entries[0].format(), "Sent 18446744073709551615 of -5 bytes, id 3000000000, delta -2");
+
+    let params = &entries[1].parameters;
+    assert!(matches!(params[0], LogValue::Float32(v) if v == 0.5));
+    assert!(matches!(params[1], LogValue::Boolean(true)));
+    assert_eq!(entries[1].format(), "Ratio 0.5 ready true grade é name abcd");
+
+    assert_eq!(entries[2].format(), format!("Wide {} {}", u128::MAX, i128::MIN));
+
+    // The tags aren't part of the arguments
+    assert_eq!(entries[0].raw_values.len(), 1 + 4 * 4 + 8 + 8 + 4 + 1);
+}
+
+#[test]
+fn test_every_write_path_tags_by_default() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        let tag = register_tag("typed-default");
+        let (count, offset, level, port) = (5u64, -5i64, 200u8, 5u16);
+        log_record!(logger, "Default {} {} {} {}", count, offset, level, port).unwrap();
+        log_tagged!(logger, tags = tag, "Default {} {} {} {}", count, offset, level, port).unwrap();
+        let args: [&[u8]; 4] = [&count.to_le_bytes(), &offset.to_le_bytes(), &[level], &port.to_le_bytes()];
+        let types = [TypeTag::U64, TypeTag::I64, TypeTag::U8, TypeTag::U16];
+        logger.write_typed_args(Some(Level::Info), 0, register_string("Default {} {} {} {}"), &args, &types).unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries.len(), 3);
+    for entry in &entries {
+        assert_eq!(entry.format(), "Default 5 -5 200 5");
+        assert!(matches!(entry.parameters[..], [LogValue::Unsigned(5), LogValue::Integer64(-5), LogValue::Integer(200), LogValue::Integer(5)]),
+            "{:?}", entry.parameters);
+    }
+    assert!(!entries[1].tags.is_empty());
+}
+
+#[test]
+fn test_untagged_arguments_are_guessed() {
+    // The same records without tags are read by size
+    let entries = read_all(&write_samples(false, FormatFeatures::SUPPORTED));
+    assert!(matches!(entries[0].parameters[0], LogValue::Float(_)));
+    assert!(matches!(entries[0].parameters[2], LogValue::Integer(_)));
+    assert_eq!(entries[1].format(), "Ratio 0.5 ready true grade 233 name abcd");
+}
+
+#[test]
+fn test_type_tags_need_the_feature() {
+    let data = write_samples(true, FormatFeatures::SUPPORTED & !FormatFeatures::TYPE_TAGS);
+    let mut reader = LogReader::new(&data);
+    let first = reader.read_entry().unwrap();
+    assert!(matches!(first.parameters[0], LogValue::Float(_)));
+    assert!(!reader.format_features().unwrap().contains(FormatFeatures::TYPE_TAGS));
+}
+
+#[test]
+fn test_inspect_lists_type_tags() {
+    let data = write_samples(true, FormatFeatures::SUPPORTED);
+    let mut out = Vec::new();
+    inspect(&data, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("(typed)"), "{}", text);
+    assert!(text.contains("types=[u64, i64, u32, i8]"), "{}", text);
+    assert!(text.contains("types=[f32, bool, char, interned string]"), "{}", text);
+}
+
+#[test]
+fn test_byte_arguments_round_trip() {
+    for type_tags in [false, true] {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+            logger.set_type_tags(type_tags);
+            let frame: Vec<u8> = vec![0xde, 0xad, 0xbe, 0xef];
+            let header: &[u8] = &frame[..2];
+            let uuid = [7u8; 16];
+            log_record!(logger, "Frame {} header {} id {}", frame, header, uuid).unwrap();
+            log_record!(logger, "Magic {} empty {}", b"BLOG", Vec::<u8>::new()).unwrap();
+        }
+        let entries = read_all(&data.lock().unwrap());
+        assert!(matches!(&entries[0].parameters[0], LogValue::Bytes(bytes) if bytes == &[0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(entries[0].format(), format!("Frame 0xdeadbeef header 0xdead id 0x{}", "07".repeat(16)));
+        assert_eq!(entries[1].format(), "Magic 0x424c4f47 empty 0x");
+    }
+}
+
+#[test]
+fn test_primitive_references_log_their_value() {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_type_tags(true);
+        let (count, offset, ratio, ready) = (7u32, -3i64, 1.5f64, true);
+        let counts = [1usize, 2];
+        for index in counts.iter() {
+            log_record!(logger, "Index {}", index).unwrap();
+        }
+        log_record!(logger, "Count {} offset {} ratio {} ready {} grade {}", &count, &offset, &ratio, &ready, &'b').unwrap();
+    }
+    let entries = read_all(&data.lock().unwrap());
+    assert_eq!(entries.iter().map(|entry| entry.format()).collect::<Vec<_>>(), [
+        "Index 1",
+        "Index 2",
+        "Count 7 offset -3 ratio 1.5 ready true grade b",
+    ]);
+    assert!(matches!(entries[0].parameters[0], LogValue::Unsigned(1)));
+    assert!(matches!(entries[2].parameters[1], LogValue::Integer64(-3)));
+    assert_eq!(entries[2].raw_values.len(), 1 + 5 * 4 + 4 + 8 + 8 + 1 + 4);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, log_record};
+use binary_logger::typed::FromLogEntry;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use common::CollectingHandler;
+
+#[derive(FromLogEntry, Debug, PartialEq)]
+#[log(format = "Order {} filled: {} units, cancelled={}")]
+struct OrderFilled {
+    order: u64,
+    units: i16,
+    cancelled: bool,
+}
+
+#[derive(FromLogEntry, Debug)]
+#[log(format = "Connected to {} in {} ms")]
+struct Connected(String, f64, #[log(timestamp)] SystemTime);
+
+fn write_log() -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        log_record!(logger, "Order {} filled: {} units, cancelled={}", 1, 100, false).unwrap();
+        log_record!(logger, "Connected to {} in {} ms", "primary", 3.5).unwrap();
+        log_record!(logger, "Order {} filled: {} units, cancelled={}", 2, -5, true).unwrap();
+        // Out of range for the field, so skipped
+        log_record!(logger, "Order {} filled: {} units, cancelled={}", 3, 40_000, false).unwrap();
+    }
+    let data = data.lock().unwrap().clone();
+    data
+}
+
+#[test]
+fn test_typed_reader() {
+    let data = write_log();
+    let mut reader = LogReader::new(&data);
+    let orders: Vec<OrderFilled> = reader.typed().collect();
+    assert_eq!(orders, [
+        OrderFilled { order: 1, units: 100, cancelled: false },
+        OrderFilled { order: 2, units: -5, cancelled: true },
+    ]);
+
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+    let mut reader = LogReader::new(&data);
+    let connections: Vec<Connected> = reader.typed().collect();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].0, "primary");
+    assert_eq!(connections[0].1, 3.5);
+    assert_eq!(connections[0].2, entries[1].timestamp);
+}
+
+#[test]
+fn test_from_log_entry_checks_arguments() {
+    let data = write_log();
+    let mut reader = LogReader::new(&data);
+    let first = reader.read_entry().unwrap();
+    assert_eq!(OrderFilled::FORMAT, "Order {} filled: {} units, cancelled={}");
+    assert!(OrderFilled::from_log_entry(&first).is_some());
+
+    // Arguments of another format don't fit
+    let second = reader.read_entry().unwrap();
+    assert!(OrderFilled::from_log_entry(&second).is_none());
+    assert!(Connected::from_log_entry(&first).is_none());
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogValue, StreamReader, ReaderOptions, Utf8Mode, InvalidUtf8, log_record};
+use std::io;
+use std::sync::{Arc, Mutex};
+use common::CollectingHandler;
+
+/// Logs three entries with a string argument, and damages the UTF-8 of the
+/// second one's ("café").
+fn damaged_log(type_tags: bool) -> Vec<u8> {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_type_tags(type_tags);
+        for (order, item) in [(1u32, "tea"), (2, "café"), (3, "milk")] {
+            log_record!(logger, "Order {} for {}", order, item.to_string()).unwrap();
+        }
+    }
+    let mut data = data.lock().unwrap().clone();
+    let at = data.windows(5).position(|bytes| bytes == "café".as_bytes()).unwrap();
+    data[at + 3] = 0xff;
+    data
+}
+
+#[test]
+fn test_lossy_mode_replaces_and_flags() {
+    for type_tags in [false, true] {
+        let data = damaged_log(type_tags);
+        let mut reader = LogReader::new(&data);
+        reader.set_decode_warnings(true);
+        let entries: Vec<_> = std::iter::from_fn(|| reader.read_entry()).collect();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(&entries[1].parameters[1], LogValue::String(s) if s == "caf\u{fffd}\u{fffd}"));
+        assert_eq!(entries.iter().map(|entry| entry.lossy).collect::<Vec<_>>(), [false, true, false]);
+        assert!(reader.take_utf8_error().is_none());
+
+        let warnings = reader.take_decode_warnings();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!((warnings[0].id, warnings[0].argument), (entries[1].id, Some(1)));
+        assert_eq!(warnings[0].message, "invalid UTF-8 at byte 3 replaced");
+    }
+}
+
+#[test]
+fn test_strict_mode_stops_at_the_entry() {
+    for type_tags in [false, true] {
+        let data = damaged_log(type_tags);
+        let second = {
+            let mut reader = LogReader::new(&data);
+            reader.read_entry();
+            reader.read_entry().unwrap()
+        };
+        let mut reader = LogReader::new(&data);
+        reader.set_utf8_mode(Utf8Mode::Strict);
+        assert!(reader.read_entry().is_some_and(|entry| !entry.lossy));
+        assert!(reader.read_entry().is_none());
+        let error = reader.take_utf8_error().unwrap();
+        assert_eq!(error, InvalidUtf8 { id: second.id, format_id: second.format_id, argument: 1, offset: 3 });
+        assert_eq!(error.to_string(), format!("{} format {} argument 1: invalid UTF-8 at byte 3", second.id, second.format_id));
+        assert!(reader.take_utf8_error().is_none());
+
+        // Reading goes on after the entry
+        assert_eq!(reader.read_entry().unwrap().format(), "Order 3 for milk");
+        assert!(reader.read_entry().is_none());
+        assert!(reader.take_utf8_error().is_none());
+    }
+}
+
+#[test]
+fn test_stream_reader_options_select_the_mode() {
+    let data = damaged_log(true);
+    let mut reader = StreamReader::new(&data[..]);
+    let lossy: Vec<bool> = std::iter::from_fn(|| reader.read_entry().unwrap()).map(|entry| entry.lossy).collect();
+    assert_eq!(lossy, [false, true, false]);
+
+    let options = ReaderOptions { utf8: Utf8Mode::Strict, ..Default::default() };
+    let mut reader = StreamReader::with_options(&data[..], options);
+    let first = reader.read_entry().unwrap().unwrap();
+    let error = reader.read_entry().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let invalid = error.get_ref().and_then(|error| error.downcast_ref::<InvalidUtf8>()).unwrap();
+    assert_eq!((invalid.id.buffer, invalid.argument, invalid.offset), (first.id.buffer, 1, 3));
+    assert_eq!(reader.read_entry().unwrap().unwrap().format(), "Order 3 for milk");
+    assert!(reader.read_entry().unwrap().is_none());
+}
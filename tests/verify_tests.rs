@@ -0,0 +1,101 @@
+use binary_logger::{Logger, BufferHandler, log_record};
+use binary_logger::verify::{verify_segments, CheckStatus};
+use std::sync::{Arc, Mutex};
+
+struct CollectingHandler {
+    data: Arc<Mutex<Vec<u8>>>,
+    sizes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl BufferHandler for CollectingHandler {
+    fn handle_buffer(&self, data: &[u8]) {
+        self.data.lock().unwrap().extend_from_slice(data);
+        self.sizes.lock().unwrap().push(data.len());
+    }
+}
+
+/// Writes a sequenced log, returning the data and each buffer's size.
+fn write_log(audit: bool) -> (Vec<u8>, Vec<usize>) {
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let sizes = Arc::new(Mutex::new(Vec::new()));
+    {
+        let handler = CollectingHandler { data: data.clone(), sizes: sizes.clone() };
+        let mut logger = Logger::<256>::new(handler);
+        logger.set_audit_chain(audit);
+        logger.set_global_sequence(true);
+        for i in 0..40 {
+            log_record!(logger, "Verified action {}", i).unwrap();
+        }
+        logger.flush();
+    }
+    let data = data.lock().unwrap().clone();
+    let sizes = sizes.lock().unwrap().clone();
+    (data, sizes)
+}
+
+/// Splits a log into three segments at buffer boundaries.
+fn split(data: &[u8], sizes: &[usize]) -> Vec<(String, Vec<u8>)> {
+    let first = sizes[0];
+    let second = first + sizes[1] + sizes[2];
+    vec![
+        ("seg-0".to_string(), data[..first].to_vec()),
+        ("seg-1".to_string(), data[first..second].to_vec()),
+        ("seg-2".to_string(), data[second..].to_vec()),
+    ]
+}
+
+#[test]
+fn test_verify_segmented_audit_log() {
+    let (data, sizes) = write_log(true);
+    let report = verify_segments(&split(&data, &sizes));
+
+    assert!(report.passed(), "{}", report.to_json());
+    assert_eq!(report.entries, 40);
+    assert_eq!(report.sequence, CheckStatus::Passed);
+    assert_eq!(report.timestamps, CheckStatus::Passed);
+    assert_eq!(report.segments.iter().map(|s| s.buffers).sum::<usize>(), sizes.len());
+    assert!(report.segments.iter().all(|s| s.chain == CheckStatus::Passed));
+
+    let json = report.to_json();
+    assert!(json.starts_with("{\"passed\":true,\"entries\":40,"), "{}", json);
+    assert!(json.contains("{\"name\":\"seg-1\""), "{}", json);
+}
+
+#[test]
+fn test_verify_detects_tampering_and_reordering() {
+    let (data, sizes) = write_log(true);
+
+    // A modified byte breaks the link into the next segment
+    let mut segments = split(&data, &sizes);
+    let last = segments[0].1.len() - 2;
+    segments[0].1[last] ^= 0xFF;
+    let report = verify_segments(&segments);
+    assert!(!report.passed());
+    assert_eq!(report.segments[0].chain, CheckStatus::Passed);
+    assert!(matches!(report.segments[1].chain, CheckStatus::Failed(_)));
+    assert!(matches!(report.segments[2].chain, CheckStatus::Skipped(_)));
+    assert!(report.to_json().contains("\"status\":\"failed\""));
+
+    // Segments in the wrong order fail the chain and sequence checks
+    let mut segments = split(&data, &sizes);
+    segments.swap(1, 2);
+    let report = verify_segments(&segments);
+    assert!(matches!(report.segments[1].chain, CheckStatus::Failed(_)));
+    assert!(matches!(report.sequence, CheckStatus::Failed(_)));
+}
+
+#[test]
+fn test_verify_plain_and_truncated_logs() {
+    let (data, sizes) = write_log(false);
+
+    // Without audit mode the chain check doesn't apply
+    let report = verify_segments(&[("plain".to_string(), data.clone())]);
+    assert!(report.passed(), "{}", report.to_json());
+    assert!(matches!(report.segments[0].chain, CheckStatus::Skipped(_)));
+
+    let truncated = data[..sizes[0] + sizes[1] / 2].to_vec();
+    let report = verify_segments(&[("truncated".to_string(), truncated)]);
+    assert!(!report.passed());
+    assert_eq!(report.segments[0].buffers, 1);
+    assert!(matches!(report.segments[0].framing, CheckStatus::Failed(_)));
+}
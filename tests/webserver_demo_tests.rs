@@ -0,0 +1,59 @@
+//! Runs the server of `examples/webserver_demo` against real sockets and
+//! checks its logs, which exercises the Logger pool, background writers,
+//! global sequence numbers, embedded dictionaries, type tags and the merger
+//! together.
+
+#[allow(dead_code)]
+#[path = "../examples/webserver_demo/server.rs"]
+mod server;
+
+use binary_logger::LogReader;
+use server::{check_logs, get, log_files, DemoServer};
+
+#[test]
+fn test_requests_are_followed_across_threads() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = DemoServer::start(dir.path(), 3).unwrap();
+    let addr = server.addr();
+
+    assert_eq!(get(addr, "/orders/17").unwrap(), (200, "order 17\n".to_string()));
+    assert_eq!(get(addr, "/missing").unwrap().0, 404);
+    assert_eq!(get(addr, "/report").unwrap().0, 500);
+    let clients: Vec<_> = (0..4).map(|_| std::thread::spawn(move || {
+        for _ in 0..10 {
+            assert_eq!(get(addr, "/orders/5").unwrap().0, 200);
+        }
+    })).collect();
+    for client in clients {
+        client.join().unwrap();
+    }
+    server.stop();
+
+    // One file per thread that logged
+    let names: Vec<_> = log_files(dir.path()).unwrap().iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["accept.blog", "worker-0.blog", "worker-1.blog", "worker-2.blog"]);
+
+    let summary = check_logs(dir.path()).unwrap();
+    assert_eq!(summary.accepted, 43);
+    assert_eq!(summary.statuses.get(&200), Some(&41));
+    assert_eq!(summary.statuses.get(&404), Some(&1));
+    assert_eq!(summary.statuses.get(&500), Some(&1));
+    assert!(summary.broken.is_empty(), "{:?}", summary.broken);
+}
+
+#[test]
+fn test_worker_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = DemoServer::start(dir.path(), 1).unwrap();
+    get(server.addr(), "/report").unwrap();
+    server.stop();
+
+    let data = std::fs::read(dir.path().join("worker-0.blog")).unwrap();
+    let mut reader = LogReader::new(&data);
+    let lines: Vec<_> = std::iter::from_fn(|| reader.read_entry()).map(|entry| entry.format()).collect();
+    assert_eq!(lines[0], "Worker 0 ready");
+    assert!(lines.iter().any(|line| line.starts_with("Report unavailable: No such file")), "{:?}", lines);
+    assert!(lines.iter().any(|line| line == "Request 1 GET /report"), "{:?}", lines);
+}
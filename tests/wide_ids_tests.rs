@@ -0,0 +1,147 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, LogValue, register_string, get_string};
+use binary_logger::features::FormatFeatures;
+use binary_logger::instrumentation::{InternalEvent, RESERVED_FORMAT_ID_START};
+use binary_logger::string_registry::is_wide_id;
+use binary_logger::wire::{self, Record, RecordTime, RECORD_TYPE_NORMAL, RECORD_TYPE_TYPED, WIDE_ID};
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex, OnceLock};
+use common::CollectingHandler;
+
+/// Registers strings until the registry hands out wide IDs, returning the
+/// last narrow ID and the first two wide ones with their strings.
+fn wide_strings() -> &'static [(u32, &'static str); 3] {
+    static STRINGS: OnceLock<[(u32, &'static str); 3]> = OnceLock::new();
+    STRINGS.get_or_init(|| {
+        let mut n = 0;
+        let mut next = || {
+            n += 1;
+            let s: &'static str = format!("Generated site {} value {{}}", n).leak();
+            (register_string(s), s)
+        };
+        let mut last_narrow = next();
+        loop {
+            let string = next();
+            if is_wide_id(string.0) {
+                return [last_narrow, string, next()];
+            }
+            last_narrow = string;
+        }
+    })
+}
+
+#[test]
+fn test_registry_skips_the_reserved_range() {
+    let [(narrow, narrow_string), (wide, wide_string), (next, _)] = *wide_strings();
+    assert_eq!(narrow, RESERVED_FORMAT_ID_START - 1);
+    assert_eq!((wide, next), (0x1_0000, 0x1_0001));
+    assert_eq!(get_string(narrow), Some(narrow_string));
+    assert_eq!(get_string(wide), Some(wide_string));
+    assert_eq!(register_string(wide_string), wide);
+
+    // The reserved range still belongs to the internal events
+    assert_eq!(get_string(RESERVED_FORMAT_ID_START), Some(InternalEvent::BufferSwitchStart.format_string()));
+    assert_eq!(get_string(WIDE_ID as u32), None);
+    assert_eq!(get_string(0x2_0000), None);
+}
+
+#[test]
+fn test_wide_format_ids_and_channels_read_back() {
+    let [(narrow, _), (wide, wide_string), (channel, _)] = *wide_strings();
+    for compact in [false, true] {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+            logger.set_global_sequence(true);
+            logger.set_header_compression(compact);
+            logger.set_delta_timestamps(compact);
+            for i in 0..6u32 {
+                let value = i.to_le_bytes();
+                match i % 3 {
+                    0 => logger.write_args(wide, &[&value]).unwrap(),
+                    1 => logger.write_args_on(channel, wide, &[&value]).unwrap(),
+                    _ => logger.write_args_on(channel, narrow, &[&value]).unwrap(),
+                }
+            }
+        }
+        let data = data.lock().unwrap();
+        let mut reader = LogReader::new(&data);
+        let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+        assert!(reader.format_features().unwrap().contains(FormatFeatures::WIDE_IDS));
+        let read: Vec<(u32, u32, Option<u64>)> = entries.iter().map(|entry| (entry.format_id, entry.channel, entry.sequence)).collect();
+        let ids: Vec<(u32, u32)> = read.iter().map(|&(format_id, channel, _)| (format_id, channel)).collect();
+        assert_eq!(ids, [(wide, 0), (wide, channel), (narrow, channel), (wide, 0), (wide, channel), (narrow, channel)]);
+        assert!(read.windows(2).all(|pair| pair[0].2.unwrap() < pair[1].2.unwrap()));
+        assert_eq!(entries[3].format(), wide_string.replace("{}", "3"));
+        assert!(matches!(entries[4].parameters[..], [LogValue::Integer(4)]));
+
+        // The wire module reads the same IDs, typed records leaving their
+        // time base to a record of its own, and leaves repeated formats to
+        // the caller with header compression
+        let decoded: Vec<(Option<u32>, Option<u32>)> = wire::records(&data[..wire::read_buffer_size(&data).unwrap()])
+            .map(Result::unwrap)
+            .filter(|record| record.record_type == RECORD_TYPE_TYPED)
+            .map(|record| (record.format_id, record.channel))
+            .collect();
+        let repeated = if compact { None } else { Some(wide) };
+        let on_channel = |format_id| (format_id, Some(channel));
+        assert_eq!(decoded, [(Some(wide), None), on_channel(repeated), on_channel(Some(narrow)), (Some(wide), None), on_channel(repeated), on_channel(Some(narrow))]);
+    }
+}
+
+#[test]
+fn test_wide_records_round_trip_through_the_wire_module() {
+    let [_, (wide, _), (channel, _)] = *wide_strings();
+    let payload = 7u32.to_le_bytes();
+    let mut record = Record::new(RECORD_TYPE_NORMAL, RecordTime::Relative(40), wide, &payload);
+    record.channel = Some(channel);
+    record.sequence = Some(12);
+
+    // 6-byte header, sequence, escaped channel, wide format ID, payload
+    assert_eq!(record.encoded_len(), 8 + 8 + 6 + 4 + 4);
+    let mut out = vec![0; record.encoded_len()];
+    record.encode(&mut out);
+    assert_eq!(&out[4..6], &WIDE_ID.to_le_bytes());
+    let (decoded, size) = wire::decode_record(&out).unwrap();
+    assert_eq!((decoded.format_id, decoded.channel, decoded.sequence), (Some(wide), Some(channel), Some(12)));
+    assert_eq!((decoded.payload, size), (&payload[..], out.len()));
+}
+
+#[test]
+fn test_wide_ids_need_their_format_feature() {
+    let [(narrow, _), (wide, _), (channel, _)] = *wide_strings();
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        logger.set_format_features(FormatFeatures::SUPPORTED & !FormatFeatures::WIDE_IDS);
+        let value = 1u32.to_le_bytes();
+        assert_eq!(logger.write_args(wide, &[&value]).unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(logger.write_args_on(channel, narrow, &[&value]).unwrap_err().kind(), ErrorKind::Unsupported);
+        logger.write_args(narrow, &[&value]).unwrap();
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    assert_eq!(reader.read_entry().unwrap().format_id, narrow);
+    assert!(reader.read_entry().is_none());
+}
+
+#[test]
+fn test_wide_counters_are_written_as_records() {
+    let [(narrow, _), (wide, _), _] = *wide_strings();
+    let data = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut logger = Logger::<4096>::new(CollectingHandler(data.clone()));
+        for _ in 0..3 {
+            logger.count(narrow).unwrap();
+            logger.count(wide).unwrap();
+        }
+    }
+    let data = data.lock().unwrap();
+    let mut reader = LogReader::new(&data);
+    let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().all(|entry| entry.format_id == wide && entry.parameters.is_empty()));
+    assert_eq!(reader.counters().get(&narrow), Some(&3));
+    assert_eq!(reader.counters().get(&wide), None);
+}
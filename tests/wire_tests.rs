@@ -0,0 +1,184 @@
+mod common;
+
+use binary_logger::{Logger, LogReader, LogEntry, log_record, log_record_at};
+use binary_logger::codec::{codec_by_id, Codec, PostcardCodec};
+use binary_logger::features::FormatFeatures;
+use binary_logger::level::Level;
+use binary_logger::wire::{self, Record, RecordTime, StreamHeader, Timeline};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+use common::CollectingHandler;
+
+/// A log record as decoded with `wire`.
+#[derive(Debug, PartialEq)]
+struct Decoded {
+    micros: u64,
+    format_id: u32,
+    channel: u32,
+    sequence: Option<u64>,
+    level: Option<Level>,
+    args: Vec<Vec<u8>>,
+}
+
+/// Decodes the log records of `data` with `wire` alone.
+fn decode(data: &[u8]) -> Vec<Decoded> {
+    let mut decoded = Vec::new();
+    let mut timeline = Timeline::new();
+    let mut codec: &dyn Codec = codec_by_id(0).unwrap();
+    let mut pos = 0;
+    while pos < data.len() {
+        let size = wire::read_buffer_size(&data[pos..]).unwrap();
+        let mut last_format_id = 0;
+        for record in wire::records(&data[pos..pos + size]) {
+            let record = record.unwrap();
+            let micros = timeline.advance(&record);
+            let format_id = record.format_id.unwrap_or(last_format_id);
+            last_format_id = format_id;
+            let args = match record.record_type {
+                wire::RECORD_TYPE_STREAM_HEADER => {
+                    let header = StreamHeader::decode(record.payload).unwrap();
+                    codec = codec_by_id(header.codec_id).unwrap();
+                    continue;
+                }
+                wire::RECORD_TYPE_BASE if format_id != 0 => wire::split_base(record.payload).unwrap().1,
+                wire::RECORD_TYPE_NORMAL => record.payload,
+                wire::RECORD_TYPE_TYPED => wire::split_type_tags(record.payload).unwrap().1,
+                _ => continue,
+            };
+            decoded.push(Decoded {
+                micros: micros.unwrap(),
+                format_id,
+                channel: record.channel.unwrap_or(0),
+                sequence: record.sequence,
+                level: record.level(),
+                args: codec.decode(args).into_iter().map(<[u8]>::to_vec).collect(),
+            });
+        }
+        pos += size;
+    }
+    decoded
+}
+
+#[test]
+fn test_decodes_what_the_logger_writes() {
+    for compact in [false, true] {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut logger = Logger::<512>::new(CollectingHandler(data.clone()));
+            logger.set_global_sequence(true);
+            logger.set_header_compression(compact);
+            logger.set_delta_timestamps(compact);
+            for i in 0..200u32 {
+                match i % 3 {
+                    0 => log_record!(logger, "Wire plain {}", i).unwrap(),
+                    1 => log_record_at!(logger, Level::Warn, "Wire plain {}", i).unwrap(),
+                    _ => log_record!(logger, channel: "wire", "Wire pair {} {}", i, 0.5f64).unwrap(),
+                }
+                if i % 50 == 0 {
+                    // Past a 16-bit period of relative time
+                    std::thread::sleep(std::time::Duration::from_millis(70));
+                }
+            }
+        }
+        let data = data.lock().unwrap();
+        let mut reader = LogReader::new(&data);
+        let entries: Vec<LogEntry> = std::iter::from_fn(|| reader.read_entry()).collect();
+        let decoded = decode(&data);
+        assert_eq!(decoded.len(), 200);
+        for (entry, decoded) in entries.iter().zip(&decoded) {
+            assert_eq!(entry.timestamp, UNIX_EPOCH + std::time::Duration::from_micros(decoded.micros));
+            assert_eq!((entry.format_id, entry.channel, entry.sequence, entry.level), (decoded.format_id, decoded.channel, decoded.sequence, decoded.level));
+        }
+        assert_eq!(decoded[1].args, [1u32.to_le_bytes()]);
+        assert_eq!(decoded[2].args, [2u32.to_le_bytes().to_vec(), 0.5f64.to_le_bytes().to_vec()]);
+    }
+}
+
+#[test]
+fn test_records_round_trip() {
+    let format_id = binary_logger::register_string("Wire built {} times");
+    let args = 7u32.to_le_bytes();
+    let mut payload = vec![0; PostcardCodec.encoded_len(&[&args])];
+    PostcardCodec.encode(&[&args], &mut payload);
+    let header = StreamHeader::new(PostcardCodec.id(), FormatFeatures::SEQUENCE | FormatFeatures::CHANNELS | FormatFeatures::LEVELS);
+    let header_payload = header.encode();
+    let mut base = 1_700_000_000_000_000u64.to_le_bytes().to_vec();
+    base.extend_from_slice(&payload);
+
+    let mut records = vec![
+        Record::new(wire::RECORD_TYPE_STREAM_HEADER, RecordTime::Relative(0), 0, &header_payload),
+        Record::new(wire::RECORD_TYPE_BASE, RecordTime::Relative(10), format_id, &base),
+        Record { sequence: Some(u64::MAX), channel: Some(3), ..Record::new(wire::RECORD_TYPE_NORMAL, RecordTime::Delta(100_000), format_id, &payload) },
+        Record { format_id: None, ..Record::new(wire::RECORD_TYPE_NORMAL, RecordTime::Relative(5), 0, &payload) },
+        Record::new(wire::RECORD_TYPE_UNTIMED, RecordTime::None, format_id, &payload),
+    ];
+    records[3].set_level(Some(Level::Error));
+    records[3].flags |= wire::FLAG_TRUNCATED;
+    assert_eq!(records[2].encoded_flags(), wire::FLAG_SEQUENCE | wire::FLAG_CHANNEL | wire::FLAG_DELTA_TIME);
+
+    let size = wire::BUFFER_HEADER_SIZE + records.iter().map(Record::encoded_len).sum::<usize>();
+    let mut buffer = vec![0xAA; size];
+    let mut pos = wire::BUFFER_HEADER_SIZE;
+    for record in &records {
+        let len = record.encode(&mut buffer[pos..]);
+        assert_eq!(len % 2, 0);
+        pos += len;
+    }
+    assert_eq!(pos, size);
+    wire::write_buffer_size(size, &mut buffer);
+
+    // Decoded as written, framing flags aside
+    let read: Vec<Record> = wire::records(&buffer).map(Result::unwrap).collect();
+    assert_eq!(read.len(), 5);
+    assert_eq!(records[4].header_len(), 6);
+    for (read, written) in read.iter().zip(&records) {
+        assert_eq!(Record { flags: written.encoded_flags(), ..*written }, *read);
+    }
+    assert_eq!(StreamHeader::decode(read[0].payload), Some(header));
+    assert_eq!(read[3].level(), Some(Level::Error));
+
+    // And by LogReader
+    let mut reader = LogReader::new(&buffer);
+    let first = reader.read_entry().unwrap();
+    let second = reader.read_entry().unwrap();
+    let third = reader.read_entry().unwrap();
+    let fourth = reader.read_entry().unwrap();
+    assert_eq!(first.format(), "Wire built 7 times");
+    assert_eq!((second.sequence, second.channel), (Some(u64::MAX), 3));
+    assert_eq!(second.timestamp.duration_since(first.timestamp).unwrap().as_micros(), 100_000);
+    assert_eq!((third.format(), third.level, third.truncated), ("Wire built 7 times".to_string(), Some(Level::Error), true));
+    // 5us past the next 16-bit period, which the delta moved into
+    assert_eq!(third.timestamp.duration_since(first.timestamp).unwrap().as_micros() as u64, 2 * wire::EPOCH_MICROS + 5 - 10);
+    // Untimed, at the time of the record before
+    assert_eq!((fourth.format(), fourth.untimed, fourth.timestamp), ("Wire built 7 times".to_string(), true, third.timestamp));
+    assert_eq!(reader.format_features(), Some(header.features));
+}
+
+#[test]
+fn test_varints_and_damaged_data() {
+    for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+        let mut out = [0; 10];
+        let len = wire::write_varint(value, &mut out);
+        assert_eq!(len, wire::varint_len(value));
+        assert_eq!(wire::read_varint(&out[..len]), Some((value, &[][..])));
+        assert_eq!(wire::read_varint(&out[..len - 1]), None);
+    }
+    assert_eq!(wire::varint_len(u64::MAX), 10);
+    // Over 64 bits
+    assert_eq!(wire::read_varint(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]), None);
+
+    let payload = [1, 2, 3];
+    let record = Record::new(wire::RECORD_TYPE_NORMAL, RecordTime::Relative(1), 9, &payload);
+    let mut buffer = vec![0; wire::BUFFER_HEADER_SIZE + record.encoded_len()];
+    record.encode(&mut buffer[wire::BUFFER_HEADER_SIZE..]);
+    wire::write_buffer_size(buffer.len(), &mut buffer);
+    // The record cut short
+    let cut = buffer.len() - 2;
+    wire::write_buffer_size(cut, &mut buffer);
+    let results: Vec<_> = wire::records(&buffer[..cut]).collect();
+    assert_eq!(results, [Err(wire::Truncated { remaining: cut - wire::BUFFER_HEADER_SIZE })]);
+    // A size beyond the data
+    let results: Vec<_> = wire::records(&buffer[..cut - 1]).collect();
+    assert_eq!(results, [Err(wire::Truncated { remaining: cut - 1 })]);
+    assert_eq!(wire::decode_record(&buffer[wire::BUFFER_HEADER_SIZE..wire::BUFFER_HEADER_SIZE + 3]), None);
+}